@@ -0,0 +1,45 @@
+//! Walk arbitrary bytes as a RIFF tree, reading every chunk as each kind the readers understand.
+//! Errors are expected, panics are bugs.
+//!
+//! `cargo fuzz run riff` from `fuzzpaint-core`. The test documents make a good starting corpus.
+#![no_main]
+
+use fuzzpaint_core::io::riff::{decode::BinaryChunkReader, ChunkID};
+use std::io::{Cursor, Read};
+
+/// Read one chunk and everything within it. Children are copied out to recurse on, as nesting the
+/// readers themselves would be a new type at every level.
+fn walk(file: &[u8], depth: u32) -> std::io::Result<()> {
+    let mut chunk = BinaryChunkReader::new(Cursor::new(file))?;
+    match chunk.id() {
+        ChunkID::RIFF | ChunkID::LIST if depth < 32 => {
+            chunk.into_subchunks()?.try_for_each(|mut child| {
+                // Reassemble the header as claimed, which may not match the data actually present.
+                let mut file = child.id().0.to_vec();
+                let len = u32::try_from(child.data_len_unsanitized()).unwrap();
+                file.extend_from_slice(&len.to_le_bytes());
+                child.read_to_end(&mut file)?;
+                // Every chunk may also be attempted as a dict.
+                let _ = dict(&file);
+                walk(&file, depth + 1)
+            })
+        }
+        _ => chunk.read_to_end(&mut Vec::new()).map(drop),
+    }
+}
+fn dict(file: &[u8]) -> std::io::Result<()> {
+    let dict = BinaryChunkReader::new(Cursor::new(file))?.into_dict()?;
+    let _ = (
+        dict.version(),
+        dict.orphan_mode(),
+        dict.spillover_len_unsanitized(),
+    );
+    let mut spillover =
+        dict.try_for_each(|mut meta| meta.read_to_end(&mut Vec::new()).map(drop))?;
+    spillover.read_to_end(&mut Vec::new()).map(drop)
+}
+
+libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+    let _ = dict(data);
+    let _ = walk(data, 0);
+});
@@ -3,18 +3,21 @@
 //! Commands are the way the shared state of the document are modified. Every (nontrivial, like renaming a layer) change
 //! is recorded automatically as a command by a [`queue::writer`].
 
+pub use state::document::commands::Command as DocumentCommand;
 pub use state::graph::commands::Command as GraphCommand;
 pub use state::palette::commands::Command as PaletteCommand;
 pub use state::stroke_collection::commands::Command as StrokeCollectionCommand;
 
 use crate::state;
 
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum CommandError {
     #[error("command constructed for a state that does not match the current state")]
     MismatchedState,
     #[error("resource referenced by the command is not found")]
     UnknownResource,
+    #[error("document is read-only")]
+    ReadOnly,
 }
 pub trait CommandConsumer<C> {
     fn apply(&mut self, command: DoUndo<'_, C>) -> Result<(), CommandError>;
@@ -45,6 +48,7 @@ pub enum MetaCommand {
 #[derive(Clone, Debug)]
 pub enum Command {
     Meta(MetaCommand),
+    Document(DocumentCommand),
     Graph(GraphCommand),
     Palette(PaletteCommand),
     StrokeCollection(StrokeCollectionCommand),
@@ -57,6 +61,11 @@ impl From<MetaCommand> for Command {
         Self::Meta(value)
     }
 }
+impl From<DocumentCommand> for Command {
+    fn from(value: DocumentCommand) -> Self {
+        Self::Document(value)
+    }
+}
 impl From<GraphCommand> for Command {
     fn from(value: GraphCommand) -> Self {
         Self::Graph(value)
@@ -88,6 +97,13 @@ impl Command {
         }
     }
     #[must_use]
+    pub fn document(&self) -> Option<&DocumentCommand> {
+        match self {
+            Self::Document(m) => Some(m),
+            _ => None,
+        }
+    }
+    #[must_use]
     pub fn graph(&self) -> Option<&GraphCommand> {
         match self {
             Self::Graph(m) => Some(m),
@@ -108,6 +124,33 @@ impl Command {
             _ => None,
         }
     }
+    /// Estimated bytes of memory held on to by the command: itself, any it groups, and the points of any
+    /// stroke it creates, as found in `points`.
+    #[must_use]
+    pub fn memory(&self, points: &crate::repositories::points::Points) -> usize {
+        use state::stroke_collection::commands::StrokeCommand;
+        let held = match self {
+            Self::Meta(MetaCommand::Scope(_, commands)) => {
+                commands.iter().map(|command| command.memory(points)).sum()
+            }
+            Self::Meta(MetaCommand::Save(path)) => path.as_os_str().len(),
+            Self::StrokeCollection(StrokeCollectionCommand::Stroke {
+                command:
+                    StrokeCommand::Created {
+                        points: collection,
+                        clip,
+                        ..
+                    },
+                ..
+            }) => std::iter::once(*collection)
+                .chain(*clip)
+                .filter_map(|collection| points.summary_of(collection))
+                .map(|summary| summary.elements() * std::mem::size_of::<u32>())
+                .sum(),
+            _ => 0,
+        };
+        std::mem::size_of::<Self>() + held
+    }
 }
 
 #[derive(PartialEq, Eq, Debug)]
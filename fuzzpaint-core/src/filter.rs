@@ -0,0 +1,214 @@
+//! # Filters
+//!
+//! Non-destructive effects applied to the composited output of a group, before it is blended into
+//! its parent. See [`crate::state::graph::NodeType::Filtered`].
+
+/// The kind of a [`Filter`], without its parameters.
+#[derive(strum::AsRefStr, strum::EnumIter, PartialEq, Eq, Copy, Clone, Hash, Debug)]
+#[repr(u8)]
+pub enum FilterKind {
+    #[strum(serialize = "Gaussian blur")]
+    GaussianBlur = 0,
+    #[strum(serialize = "Brightness/Contrast")]
+    BrightnessContrast = 1,
+    #[strum(serialize = "Hue/Saturation")]
+    HueSaturation = 2,
+}
+impl FilterKind {
+    /// The filter of this kind with parameters that leave the image unchanged.
+    #[must_use]
+    pub fn identity(self) -> Filter {
+        match self {
+            Self::GaussianBlur => Filter::GaussianBlur { radius: 0.0 },
+            Self::BrightnessContrast => Filter::BrightnessContrast {
+                brightness: 0.0,
+                contrast: 0.0,
+            },
+            Self::HueSaturation => Filter::HueSaturation {
+                hue: 0.0,
+                saturation: 0.0,
+                lightness: 0.0,
+            },
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FilterDecodeError {
+    #[error("unknown filter kind {0}")]
+    UnknownKind(u8),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    GaussianBlur {
+        /// Distance in document pixels at which the blur falls off to nothing.
+        radius: f32,
+    },
+    BrightnessContrast {
+        /// Offset added to every color channel, in [`Filter::ADJUST_RANGE`].
+        brightness: f32,
+        /// Steepness of the curve about middle gray, in [`Filter::ADJUST_RANGE`]. Zero is unchanged.
+        contrast: f32,
+    },
+    HueSaturation {
+        /// Rotation of the hue, in degrees within [`Filter::HUE_RANGE`].
+        hue: f32,
+        /// In [`Filter::ADJUST_RANGE`], where -1 is grayscale and zero is unchanged.
+        saturation: f32,
+        /// In [`Filter::ADJUST_RANGE`], where -1 is black, 1 is white, and zero is unchanged.
+        lightness: f32,
+    },
+}
+impl Default for Filter {
+    fn default() -> Self {
+        FilterKind::GaussianBlur.identity()
+    }
+}
+impl Filter {
+    pub const BLUR_RADIUS_RANGE: std::ops::RangeInclusive<f32> = 0.0..=64.0;
+    pub const ADJUST_RANGE: std::ops::RangeInclusive<f32> = -1.0..=1.0;
+    pub const HUE_RANGE: std::ops::RangeInclusive<f32> = -180.0..=180.0;
+    /// Length of the encoding produced by [`Self::to_bytes`].
+    pub const ENCODED_LEN: usize = 13;
+
+    #[must_use]
+    pub fn kind(&self) -> FilterKind {
+        match self {
+            Self::GaussianBlur { .. } => FilterKind::GaussianBlur,
+            Self::BrightnessContrast { .. } => FilterKind::BrightnessContrast,
+            Self::HueSaturation { .. } => FilterKind::HueSaturation,
+        }
+    }
+    /// The parameters in declaration order, with unused trailing slots zero.
+    #[must_use]
+    pub fn params(&self) -> [f32; 3] {
+        match *self {
+            Self::GaussianBlur { radius } => [radius, 0.0, 0.0],
+            Self::BrightnessContrast {
+                brightness,
+                contrast,
+            } => [brightness, contrast, 0.0],
+            Self::HueSaturation {
+                hue,
+                saturation,
+                lightness,
+            } => [hue, saturation, lightness],
+        }
+    }
+    fn from_params(kind: FilterKind, [a, b, c]: [f32; 3]) -> Self {
+        match kind {
+            FilterKind::GaussianBlur => Self::GaussianBlur { radius: a },
+            FilterKind::BrightnessContrast => Self::BrightnessContrast {
+                brightness: a,
+                contrast: b,
+            },
+            FilterKind::HueSaturation => Self::HueSaturation {
+                hue: a,
+                saturation: b,
+                lightness: c,
+            },
+        }
+    }
+    /// Clamp every parameter into its range. Non-finite parameters become the identity.
+    #[must_use]
+    pub fn clamped(self) -> Self {
+        let clamp = |value: f32, range: std::ops::RangeInclusive<f32>| {
+            if value.is_finite() {
+                value.clamp(*range.start(), *range.end())
+            } else {
+                0.0
+            }
+        };
+        match self {
+            Self::GaussianBlur { radius } => Self::GaussianBlur {
+                radius: clamp(radius, Self::BLUR_RADIUS_RANGE),
+            },
+            Self::BrightnessContrast {
+                brightness,
+                contrast,
+            } => Self::BrightnessContrast {
+                brightness: clamp(brightness, Self::ADJUST_RANGE),
+                contrast: clamp(contrast, Self::ADJUST_RANGE),
+            },
+            Self::HueSaturation {
+                hue,
+                saturation,
+                lightness,
+            } => Self::HueSaturation {
+                hue: clamp(hue, Self::HUE_RANGE),
+                saturation: clamp(saturation, Self::ADJUST_RANGE),
+                lightness: clamp(lightness, Self::ADJUST_RANGE),
+            },
+        }
+    }
+    /// Whether applying this filter would leave the image unchanged, so it can be skipped.
+    #[must_use]
+    pub fn is_identity(&self) -> bool {
+        match *self {
+            // Less than a pixel has no visible effect.
+            Self::GaussianBlur { radius } => radius < 0.5,
+            _ => *self == self.kind().identity(),
+        }
+    }
+    /// Encode as the kind byte followed by the three [`Self::params`] as little-endian floats.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[0] = self.kind() as u8;
+        for (chunk, param) in bytes[1..].chunks_exact_mut(4).zip(self.params()) {
+            chunk.copy_from_slice(&param.to_le_bytes());
+        }
+        bytes
+    }
+    /// Decode from [`Self::to_bytes`]. Out-of-range parameters are clamped.
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Result<Self, FilterDecodeError> {
+        let kind = <FilterKind as strum::IntoEnumIterator>::iter()
+            .find(|kind| *kind as u8 == bytes[0])
+            .ok_or(FilterDecodeError::UnknownKind(bytes[0]))?;
+        let mut params = [0.0; 3];
+        for (param, chunk) in params.iter_mut().zip(bytes[1..].chunks_exact(4)) {
+            // Unwrap ok - chunks are exactly four bytes.
+            *param = f32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Ok(Self::from_params(kind, params).clamped())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Filter, FilterKind};
+    #[test]
+    fn encoding_roundtrip() {
+        let filters = [
+            Filter::GaussianBlur { radius: 4.5 },
+            Filter::BrightnessContrast {
+                brightness: -0.25,
+                contrast: 0.5,
+            },
+            Filter::HueSaturation {
+                hue: 90.0,
+                saturation: -1.0,
+                lightness: 0.125,
+            },
+        ];
+        for filter in filters {
+            assert_eq!(Filter::from_bytes(&filter.to_bytes()).unwrap(), filter);
+        }
+        let mut bad = filters[0].to_bytes();
+        bad[0] = 255;
+        assert!(Filter::from_bytes(&bad).is_err());
+    }
+    #[test]
+    fn identity() {
+        for kind in <FilterKind as strum::IntoEnumIterator>::iter() {
+            assert!(kind.identity().is_identity());
+            assert_eq!(kind.identity().kind(), kind);
+        }
+        assert!(!Filter::GaussianBlur { radius: 2.0 }.is_identity());
+        assert_eq!(
+            Filter::GaussianBlur { radius: f32::NAN }.clamped(),
+            Filter::GaussianBlur { radius: 0.0 }
+        );
+    }
+}
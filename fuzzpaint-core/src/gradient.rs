@@ -0,0 +1,184 @@
+//! # Gradients
+//!
+//! Colors blended smoothly across the document, filling a [gradient leaf](crate::state::graph::LeafType::Gradient).
+
+use crate::color::ColorOrPalette;
+
+/// How the position along a [`Gradient`] is measured from its endpoints.
+#[derive(strum::AsRefStr, strum::EnumIter, PartialEq, Eq, Copy, Clone, Hash, Debug)]
+#[repr(u8)]
+pub enum GradientShape {
+    /// Along the line from start to end, constant across it.
+    Linear = 0,
+    /// Outward from the start, reaching the end at the circle through the end point.
+    Radial = 1,
+}
+
+/// A color at some position along a [`Gradient`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Stop {
+    /// In `[0, 1]`, from start to end.
+    pub position: f32,
+    pub color: ColorOrPalette,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    /// Where position zero lies, in document pixels.
+    pub start: [f32; 2],
+    /// Where position one lies, in document pixels.
+    pub end: [f32; 2],
+    /// Sorted by position. Before the first and after the last, their colors continue unchanged.
+    pub stops: Vec<Stop>,
+}
+impl Gradient {
+    /// Most stops a gradient may have, which keeps it small to encode and to upload.
+    pub const MAX_STOPS: usize = 16;
+
+    /// A two-stop gradient from one color to another.
+    #[must_use]
+    pub fn new(
+        shape: GradientShape,
+        start: [f32; 2],
+        end: [f32; 2],
+        from: ColorOrPalette,
+        to: ColorOrPalette,
+    ) -> Self {
+        Self {
+            shape,
+            start,
+            end,
+            stops: vec![
+                Stop {
+                    position: 0.0,
+                    color: from,
+                },
+                Stop {
+                    position: 1.0,
+                    color: to,
+                },
+            ],
+        }
+    }
+    /// Sort the stops and clamp their positions into `[0, 1]`, dropping any past [`Self::MAX_STOPS`].
+    /// Non-finite positions and endpoints become zero.
+    #[must_use]
+    pub fn clamped(mut self) -> Self {
+        let finite = |value: f32| if value.is_finite() { value } else { 0.0 };
+        self.start = self.start.map(finite);
+        self.end = self.end.map(finite);
+        self.stops.truncate(Self::MAX_STOPS);
+        for stop in &mut self.stops {
+            stop.position = finite(stop.position).clamp(0.0, 1.0);
+        }
+        // Stable, so that stops sharing a position keep their order and make a hard edge.
+        self.stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        self
+    }
+    /// Position along the gradient of a point in document pixels, before clamping into `[0, 1]`.
+    /// The renderer evaluates this per-pixel, this is its reference.
+    #[must_use]
+    pub fn position_of(&self, point: [f32; 2]) -> f32 {
+        let axis = [self.end[0] - self.start[0], self.end[1] - self.start[1]];
+        let offset = [point[0] - self.start[0], point[1] - self.start[1]];
+        let axis_len_sq = axis[0] * axis[0] + axis[1] * axis[1];
+        if axis_len_sq <= f32::EPSILON {
+            // Endpoints together, everything lies past the end.
+            return 1.0;
+        }
+        match self.shape {
+            GradientShape::Linear => (offset[0] * axis[0] + offset[1] * axis[1]) / axis_len_sq,
+            GradientShape::Radial => {
+                ((offset[0] * offset[0] + offset[1] * offset[1]) / axis_len_sq).sqrt()
+            }
+        }
+    }
+    /// Add a stop at `position`, colored like the nearest existing stop, keeping the stops sorted.
+    /// Returns its index, or `None` if there are already [`Self::MAX_STOPS`].
+    pub fn insert_stop(&mut self, position: f32) -> Option<usize> {
+        if self.stops.len() >= Self::MAX_STOPS {
+            return None;
+        }
+        let position = if position.is_finite() {
+            position.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let color = self
+            .stops
+            .iter()
+            .min_by(|a, b| {
+                (a.position - position)
+                    .abs()
+                    .total_cmp(&(b.position - position).abs())
+            })
+            .map_or(ColorOrPalette::BLACK, |stop| stop.color);
+        let index = self.stops.partition_point(|stop| stop.position <= position);
+        self.stops.insert(index, Stop { position, color });
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Gradient, GradientShape, Stop};
+    use crate::color::ColorOrPalette;
+    #[test]
+    fn clamped_sorts() {
+        let mut gradient = Gradient::new(
+            GradientShape::Linear,
+            [0.0, f32::NAN],
+            [10.0, 0.0],
+            ColorOrPalette::BLACK,
+            ColorOrPalette::WHITE,
+        );
+        gradient.stops.push(Stop {
+            position: -2.0,
+            color: ColorOrPalette::TRANSPARENT,
+        });
+        gradient
+            .stops
+            .extend(std::iter::repeat(gradient.stops[0]).take(Gradient::MAX_STOPS));
+        let gradient = gradient.clamped();
+        assert_eq!(gradient.start, [0.0, 0.0]);
+        assert_eq!(gradient.stops.len(), Gradient::MAX_STOPS);
+        assert!(gradient
+            .stops
+            .windows(2)
+            .all(|pair| pair[0].position <= pair[1].position));
+        // Clamped to the start, after the stop already there.
+        assert_eq!(
+            gradient.stops[1],
+            Stop {
+                position: 0.0,
+                color: ColorOrPalette::TRANSPARENT,
+            }
+        );
+    }
+    #[test]
+    fn positions() {
+        let mut gradient = Gradient::new(
+            GradientShape::Linear,
+            [0.0, 0.0],
+            [10.0, 0.0],
+            ColorOrPalette::BLACK,
+            ColorOrPalette::WHITE,
+        );
+        assert!((gradient.position_of([5.0, 100.0]) - 0.5).abs() < 1e-6);
+        assert!((gradient.position_of([-10.0, 0.0]) + 1.0).abs() < 1e-6);
+        gradient.shape = GradientShape::Radial;
+        assert!((gradient.position_of([0.0, -5.0]) - 0.5).abs() < 1e-6);
+
+        assert_eq!(gradient.insert_stop(0.25), Some(1));
+        assert_eq!(gradient.stops[1].color, ColorOrPalette::BLACK);
+        assert_eq!(gradient.insert_stop(2.0), Some(3));
+        assert_eq!(
+            gradient.stops[3],
+            Stop {
+                position: 1.0,
+                color: ColorOrPalette::WHITE,
+            }
+        );
+    }
+}
@@ -4,14 +4,103 @@
 //!
 //! To get a process unique ID, simply use `FuzzID<YourNamespaceTy>`'s `Default` impl. To eargerly acquire many ids,
 //! use `FuzzID::many`.
+//!
+//! ## Deterministic IDs
+//! IDs otherwise depend on everything else the process has allocated, differing from run to run. Within
+//! [`with_seed`], IDs allocated on the calling thread instead come from a private sequence derived from the
+//! seed, so the same allocations yield the same IDs every time - for tests, and for replaying history or
+//! commands from elsewhere.
+//!
+//! Process IDs count up from one, and seeded IDs count down from the top in a window per seed, so the two
+//! modes don't overlap in practice. Should they ever meet, allocation fails as with exhaustion.
+
+/// The next process ID, and the lowest seeded ID handed out, of one namespace.
+struct Counters {
+    /// Every process ID below this has been allocated.
+    next: std::sync::atomic::AtomicU64,
+    /// The lowest seeded ID allocated. `u64::MAX` if none, which no seed yields.
+    seeded_floor: std::sync::atomic::AtomicU64,
+}
 
 // Collection of pending IDs by type.
 // Type name mess, but a RWLock'd BTreeMap from typeID to next available FuzzID
-static ID_SERVER: parking_lot::RwLock<
-    std::collections::BTreeMap<std::any::TypeId, std::sync::atomic::AtomicU64>,
-> = parking_lot::const_rwlock(std::collections::BTreeMap::new());
+static ID_SERVER: parking_lot::RwLock<std::collections::BTreeMap<std::any::TypeId, Counters>> =
+    parking_lot::const_rwlock(std::collections::BTreeMap::new());
+
+/// Seeds of every live [`with_seed`] scope, on any thread.
+static SEEDS_IN_USE: parking_lot::Mutex<std::collections::BTreeSet<u32>> =
+    parking_lot::const_mutex(std::collections::BTreeSet::new());
+
+/// Number of seeded IDs available in each namespace for each seed.
+const SEED_WINDOW: u64 = (1 << 32) - 2;
+
+/// The seeded sequence of the current thread.
+struct Seeded {
+    seed: u32,
+    /// Count of IDs allocated so far, by namespace.
+    allocated: std::collections::BTreeMap<std::any::TypeId, u64>,
+}
+std::thread_local! {
+    static SEEDED: std::cell::RefCell<Option<Seeded>> = const { std::cell::RefCell::new(None) };
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("ID seed {0} is already in use")]
+pub struct SeedInUse(pub u32);
+
+/// Run `f` with IDs allocated on this thread drawn from a sequence determined only by `seed`. The sequence
+/// starts anew each time, so the same allocations within yield the same IDs. Scopes may nest, the
+/// innermost applies.
+///
+/// IDs allocated on other threads, such as by a thread pool, are unaffected. Running the same seed again
+/// repeats its IDs - it is on the caller to ensure none from an earlier run are still in use.
+///
+/// # Errors
+/// If a scope with the same seed is already running, which would hand out the same IDs twice.
+pub fn with_seed<R>(seed: u32, f: impl FnOnce() -> R) -> Result<R, SeedInUse> {
+    /// Restores the outer scope and frees the seed, even if `f` panics.
+    struct Restore(Option<Seeded>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let inner = SEEDED.with_borrow_mut(|seeded| std::mem::replace(seeded, self.0.take()));
+            if let Some(inner) = inner {
+                SEEDS_IN_USE.lock().remove(&inner.seed);
+            }
+        }
+    }
+
+    if !SEEDS_IN_USE.lock().insert(seed) {
+        return Err(SeedInUse(seed));
+    }
+    let outer = SEEDED.with_borrow_mut(|seeded| {
+        seeded.replace(Seeded {
+            seed,
+            allocated: std::collections::BTreeMap::new(),
+        })
+    });
+    let _restore = Restore(outer);
+    Ok(f())
+}
+
+/// Stop the process, as IDs can't be allocated uniquely anymore.
+fn exhausted<T: std::any::Any>() -> ! {
+    // In builds, terminate. In testing, panic, so that tests for overflow may be implemented.
+    #[cfg(not(test))]
+    {
+        tracing::error!(
+            id_type = std::any::type_name::<T>(),
+            "ID overflow! Aborting!"
+        );
+        // Panic is not enough - we cannot allow any threads to continue, global state is unfixably borked!
+        std::process::abort();
+    }
+    #[cfg(test)]
+    {
+        panic!("{} ID overflow! Aborting!", std::any::type_name::<T>())
+    }
+}
 
-/// ID that is guarunteed unique within this execution of the program.
+/// ID that is guarunteed unique within this execution of the program, short of repeating a [seed](with_seed).
 /// IDs with different types may share a value but should not be considered equal.
 pub struct FuzzID<T: std::any::Any> {
     id: std::num::NonZeroU64,
@@ -56,27 +145,67 @@ impl<T: std::any::Any> FuzzID<T> {
     pub fn id(&self) -> u64 {
         self.id.get()
     }
-    /// Allocate many IDs at once. Much much faster than doing them one at a time for bulk operations and doesn't allocate.
-    ///
-    /// It is important to limit count to a reasonable amount - it is on the caller to ensure this.
-    /// Attempt to allocate after exhaustion of all `u64::MAX - 1` IDs leads to unclean program termination,
-    /// which can be reached in a two calls of this fn! Note that IDs are assigned eagerly - dropping the returned
-    /// iterator early does *not* recycle the unused IDs.
-    ///
-    /// *The order of IDs is undefined.* All that's guarunteed is that they're unique.
-    pub fn many(count: usize) -> impl ExactSizeIterator<Item = Self> {
-        // Count of 0 is not a logic error. it is handled gracefully :3
-        // Usize is always <= 64bits
-        let count_u64 = count as u64;
+    /// Allocate from the current thread's seeded sequence, if any. Returns the lowest of the `count` IDs.
+    fn seeded_start(count: u64) -> Option<u64> {
+        let ty = std::any::TypeId::of::<T>();
+        let (seed, allocated) = SEEDED.with_borrow_mut(|seeded| {
+            let seeded = seeded.as_mut()?;
+            let allocated = seeded.allocated.entry(ty).or_default();
+            let before = *allocated;
+            *allocated = before.saturating_add(count);
+            Some((seeded.seed, *allocated))
+        })?;
+        if allocated > SEED_WINDOW {
+            exhausted::<T>();
+        }
+        if count == 0 {
+            // Arbitrary, as nothing is yielded.
+            return Some(1);
+        }
+        // Count down from the top of the seed's window. Never `u64::MAX`, never zero.
+        let start = u64::MAX - 1 - (u64::from(seed) << 32) - (allocated - 1);
 
+        // Publish the floor *before* checking the process IDs, and the process allocator does the opposite,
+        // so at least one of the two sees the other.
+        let read = ID_SERVER.upgradable_read();
+        let next = if let Some(counters) = read.get(&ty) {
+            counters
+                .seeded_floor
+                .fetch_min(start, std::sync::atomic::Ordering::SeqCst);
+            counters.next.load(std::sync::atomic::Ordering::SeqCst)
+        } else {
+            let mut write = parking_lot::RwLockUpgradableReadGuard::upgrade(read);
+            write.insert(
+                ty,
+                Counters {
+                    next: 1.into(),
+                    seeded_floor: start.into(),
+                },
+            );
+            1
+        };
+        if start < next {
+            // Met the process IDs.
+            exhausted::<T>();
+        }
+        Some(start)
+    }
+    /// Allocate from the process-wide counter. Returns the lowest of the `count` IDs.
+    fn process_start(count: u64) -> u64 {
         // ID of zero will be invalid, start at one and go up.
-        let start_id = {
+        let (start_id, floor) = {
             let read = ID_SERVER.upgradable_read();
             let ty = std::any::TypeId::of::<T>();
-            if let Some(atomic) = read.get(&ty) {
+            if let Some(counters) = read.get(&ty) {
                 //We don't really care about the order things happen in, it just needs
                 //to be unique.
-                atomic.fetch_add(count_u64, std::sync::atomic::Ordering::Relaxed)
+                let start = counters
+                    .next
+                    .fetch_add(count, std::sync::atomic::Ordering::SeqCst);
+                let floor = counters
+                    .seeded_floor
+                    .load(std::sync::atomic::Ordering::SeqCst);
+                (start, floor)
             } else {
                 // We need to insert into the map - transition to exclusive access
                 // This is a hugely uncommon operation, so we optimize for the other path
@@ -85,29 +214,45 @@ impl<T: std::any::Any> FuzzID<T> {
                 // Initialize at count+1, return start ID of 1
                 // Wrapping add. It's not a logic error to request u64::MAX-1, but it will immediately crash
                 // on next alloc. Not a good idea, but not incorrect.
-                write.insert(ty, (count_u64.wrapping_add(1)).into());
-                1
+                write.insert(
+                    ty,
+                    Counters {
+                        next: (count.wrapping_add(1)).into(),
+                        seeded_floor: u64::MAX.into(),
+                    },
+                );
+                (1, u64::MAX)
             }
         };
 
         // Overflow occured! Todo: next alloc will succeed, should I latch to failure? Current impl
         // results in a situation where the next thread *could* alloc non-unique
         // IDs during the delay from detecting and logging the error.
-        #[allow(clippy::manual_assert)]
-        if (start_id.wrapping_add(count_u64)) <= count_u64 {
-            // In builds, terminate. In testing, panic, so that tests for overflow may be implemented.
-            #[cfg(not(test))]
-            {
-                log::error!("{} ID overflow! Aborting!", std::any::type_name::<T>());
-                log::logger().flush();
-                // Panic is not enough - we cannot allow any threads to continue, global state is unfixably borked!
-                std::process::abort();
-            }
-            #[cfg(test)]
-            {
-                panic!("{} ID overflow! Aborting!", std::any::type_name::<T>())
-            }
+        if (start_id.wrapping_add(count)) <= count {
+            exhausted::<T>();
         }
+        // Met the seeded IDs. The end is exclusive, and no seeded ID is `u64::MAX`.
+        if floor != u64::MAX && start_id + count > floor {
+            exhausted::<T>();
+        }
+        start_id
+    }
+    /// Allocate many IDs at once. Much much faster than doing them one at a time for bulk operations and doesn't allocate.
+    ///
+    /// It is important to limit count to a reasonable amount - it is on the caller to ensure this.
+    /// Attempt to allocate after exhaustion of all `u64::MAX - 1` IDs leads to unclean program termination,
+    /// which can be reached in a two calls of this fn! Note that IDs are assigned eagerly - dropping the returned
+    /// iterator early does *not* recycle the unused IDs.
+    ///
+    /// *The order of IDs is undefined.* All that's guarunteed is that they're unique - and within [`with_seed`],
+    /// that they're the same each run.
+    pub fn many(count: usize) -> impl ExactSizeIterator<Item = Self> {
+        // Count of 0 is not a logic error. it is handled gracefully :3
+        // Usize is always <= 64bits
+        let count_u64 = count as u64;
+
+        let start_id =
+            Self::seeded_start(count_u64).unwrap_or_else(|| Self::process_start(count_u64));
 
         // Must use `usize` indices for ExactSizeIterator, as absolute values of the IDs would
         // overflow a 32-bit system's usize
@@ -219,4 +364,33 @@ mod test {
         // Should panic!
         let _ = TestID::many(1);
     }
+    #[test]
+    fn seeded_repeats() {
+        // Local namespace for testing.
+        struct Namespace;
+        type TestID = FuzzID<Namespace>;
+
+        let alloc = || -> Vec<u64> {
+            let mut ids: Vec<_> = TestID::many(3).map(|id| id.id()).collect();
+            ids.push(TestID::default().id());
+            ids
+        };
+        let first = super::with_seed(1, alloc).unwrap();
+        // Process IDs in between don't disturb the sequence, nor collide with it.
+        let process: Vec<_> = TestID::many(16).map(|id| id.id()).collect();
+        let second = super::with_seed(1, alloc).unwrap();
+        assert_eq!(first, second);
+        assert!(first.iter().all(|id| !process.contains(id)));
+
+        // Other seeds differ.
+        let other = super::with_seed(2, alloc).unwrap();
+        assert!(first.iter().all(|id| !other.contains(id)));
+    }
+    #[test]
+    fn seed_in_use() {
+        let nested = super::with_seed(3, || super::with_seed(3, || ()));
+        assert_eq!(nested, Ok(Err(super::SeedInUse(3))));
+        // Freed once done.
+        assert_eq!(super::with_seed(3, || ()), Ok(()));
+    }
 }
@@ -0,0 +1,319 @@
+//! # Asset links
+//!
+//! Documents may make use of resources that live outside of them, such as stamp textures or reference
+//! images. Each is identified by the [`UniqueID`] of its contents and is either embedded into the document,
+//! or linked by path. Linked paths are written relative to the document where possible, so that a folder
+//! holding a document and its assets may be moved as a whole.
+//!
+//! In memory, linked paths are always resolved - relative paths only exist within files.
+
+use crate::brush::{UniqueID, UniqueIDMap};
+use crate::io::{
+    migrate::{Migrations, Reader},
+    riff::ChunkID,
+    Version,
+};
+use std::path::{Path, PathBuf};
+
+/// The versions of `aset` chunks that can be read, and the version written.
+pub const ASET_MIGRATIONS: Migrations<fn(&[u8], Option<&Path>) -> std::io::Result<AssetLinks>> =
+    Migrations {
+        chunk: ChunkID::ASET,
+        current: Version(0, 0, 0),
+        readers: &[Reader {
+            versions: Version(0, 0, 0)..=Version(0, 0, 0),
+            read: AssetLinks::read_v0,
+        }],
+        upgrades: &[],
+    };
+
+#[derive(Clone)]
+pub enum AssetSource {
+    /// The asset's bytes are stored within the document.
+    Embedded(std::sync::Arc<[u8]>),
+    /// The asset is read from this file.
+    Linked(PathBuf),
+}
+#[derive(Clone)]
+pub struct AssetLink {
+    /// Human-readable name, the file name the asset was linked from.
+    pub name: String,
+    pub source: AssetSource,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AssetError {
+    #[error("unknown asset")]
+    Unknown,
+    #[error("linked file is missing")]
+    Missing,
+    #[error("file contents do not match the asset")]
+    Mismatched,
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
+
+/// Compute a path to `to` from within the directory `from`, or None if there is no such relative path
+/// (e.g. they are on different drives). Both should be absolute.
+#[must_use]
+pub fn relative_path(from: &Path, to: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+    let mut from = from.components().peekable();
+    let mut to = to.components().peekable();
+    // Roots must match for there to be a relative path at all.
+    if !matches!(from.peek(), Some(Component::Prefix(_) | Component::RootDir))
+        || from.peek() != to.peek()
+    {
+        return None;
+    }
+    // Skip the shared ancestors.
+    while from.peek().is_some() && from.peek() == to.peek() {
+        from.next();
+        to.next();
+    }
+    let mut relative = PathBuf::new();
+    for component in from {
+        match component {
+            Component::Normal(_) => relative.push(".."),
+            Component::CurDir => (),
+            // Can't know what to climb out of without touching the filesystem.
+            _ => return None,
+        }
+    }
+    relative.extend(to);
+    Some(relative)
+}
+
+/// Read a file, mapping a missing file to [`AssetError::Missing`].
+fn read_file(path: &Path) -> Result<Vec<u8>, AssetError> {
+    std::fs::read(path).map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => AssetError::Missing,
+        _ => err.into(),
+    })
+}
+
+/// The external resources used by a document.
+#[derive(Clone, Default)]
+pub struct AssetLinks {
+    links: UniqueIDMap<AssetLink>,
+}
+impl AssetLinks {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+    #[must_use]
+    pub fn get(&self, id: UniqueID) -> Option<&AssetLink> {
+        self.links.get(&id)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (UniqueID, &AssetLink)> + '_ {
+        self.links.iter().map(|(id, link)| (*id, link))
+    }
+    pub fn remove(&mut self, id: UniqueID) -> Option<AssetLink> {
+        self.links.remove(&id)
+    }
+    /// Add the file as an asset, either embedding a copy of it or linking to it by path.
+    /// Adding the same contents twice gives the same ID, replacing the previous source.
+    pub fn add(&mut self, path: &Path, embed: bool) -> Result<UniqueID, AssetError> {
+        let bytes = read_file(path)?;
+        let id = UniqueID::from(blake3::hash(&bytes));
+        let source = if embed {
+            AssetSource::Embedded(bytes.into())
+        } else {
+            // Resolve now, in case the working directory changes.
+            AssetSource::Linked(std::fs::canonicalize(path)?)
+        };
+        let name = path
+            .file_name()
+            .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+            .into_owned();
+        self.links.insert(id, AssetLink { name, source });
+        Ok(id)
+    }
+    /// Embed the bytes as an asset, such as data that never came from a file.
+    /// Adding the same contents twice gives the same ID, replacing the previous source.
+    pub fn add_embedded(&mut self, name: impl Into<String>, bytes: Vec<u8>) -> UniqueID {
+        let id = UniqueID::from(blake3::hash(&bytes));
+        self.links.insert(
+            id,
+            AssetLink {
+                name: name.into(),
+                source: AssetSource::Embedded(bytes.into()),
+            },
+        );
+        id
+    }
+    /// Fetch the contents of the asset, verifying that a linked file still holds the same data.
+    pub fn load(&self, id: UniqueID) -> Result<std::sync::Arc<[u8]>, AssetError> {
+        match &self.get(id).ok_or(AssetError::Unknown)?.source {
+            AssetSource::Embedded(bytes) => Ok(bytes.clone()),
+            AssetSource::Linked(path) => {
+                let bytes = read_file(path)?;
+                if UniqueID::from(blake3::hash(&bytes)) == id {
+                    Ok(bytes.into())
+                } else {
+                    Err(AssetError::Mismatched)
+                }
+            }
+        }
+    }
+    /// Linked assets whose file no longer exists.
+    pub fn missing(&self) -> impl Iterator<Item = (UniqueID, &AssetLink)> + '_ {
+        self.iter().filter(|(_, link)| match &link.source {
+            AssetSource::Linked(path) => !path.is_file(),
+            AssetSource::Embedded(_) => false,
+        })
+    }
+    /// Point a linked asset at a new file, which must have the same contents as the original.
+    pub fn relink(&mut self, id: UniqueID, path: &Path) -> Result<(), AssetError> {
+        let link = self.links.get_mut(&id).ok_or(AssetError::Unknown)?;
+        let bytes = read_file(path)?;
+        if UniqueID::from(blake3::hash(&bytes)) != id {
+            return Err(AssetError::Mismatched);
+        }
+        link.source = AssetSource::Linked(std::fs::canonicalize(path)?);
+        Ok(())
+    }
+    /// Store a copy of a linked asset within the document, so that it no longer depends on the file.
+    pub fn embed(&mut self, id: UniqueID) -> Result<(), AssetError> {
+        let bytes = self.load(id)?;
+        // Unwrap ok - load checked that it exists.
+        self.links.get_mut(&id).unwrap().source = AssetSource::Embedded(bytes);
+        Ok(())
+    }
+    /// Encode every asset into an `aset` chunk. Linked paths are written relative to `document_dir`
+    /// where possible, otherwise they are written as-is.
+    pub fn write_chunk_into(
+        &self,
+        writer: impl std::io::Write,
+        document_dir: Option<&Path>,
+    ) -> std::io::Result<()> {
+        use crate::io::{riff::encode::SizedBinaryChunkWriter, OrphanMode};
+
+        fn push_bytes(buf: &mut Vec<u8>, bytes: &[u8]) -> std::io::Result<()> {
+            let len = u32::try_from(bytes.len())
+                .map_err(|_| std::io::Error::other(anyhow::anyhow!("asset too large")))?;
+            buf.extend_from_slice(&len.to_le_bytes());
+            buf.extend_from_slice(bytes);
+            Ok(())
+        }
+
+        let count = u32::try_from(self.links.len())
+            .map_err(|_| std::io::Error::other(anyhow::anyhow!("too many assets")))?;
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(bytemuck::bytes_of(&ASET_MIGRATIONS.current));
+        // Strokes refer to assets by ID, so the data stays meaningful even if not understood.
+        buf.push(OrphanMode::Keep as u8);
+        buf.extend_from_slice(&count.to_le_bytes());
+        for (id, link) in self.iter() {
+            buf.extend_from_slice(&id.0);
+            push_bytes(&mut buf, link.name.as_bytes())?;
+            match &link.source {
+                AssetSource::Embedded(bytes) => {
+                    buf.push(0);
+                    push_bytes(&mut buf, bytes)?;
+                }
+                AssetSource::Linked(path) => {
+                    buf.push(1);
+                    let path = match document_dir.and_then(|dir| relative_path(dir, path)) {
+                        // Always separate with slashes, so the file can be shared between platforms.
+                        Some(relative) => relative
+                            .components()
+                            .map(|component| component.as_os_str().to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("/"),
+                        None => path.to_string_lossy().into_owned(),
+                    };
+                    push_bytes(&mut buf, path.as_bytes())?;
+                }
+            }
+        }
+
+        SizedBinaryChunkWriter::write_buf(writer, ChunkID::ASET, &buf)
+    }
+    /// Decode the payload of an `aset` chunk, as written by [`Self::write_chunk_into`]. Relative paths are
+    /// resolved against `document_dir`.
+    pub fn read_chunk(
+        reader: impl std::io::Read,
+        document_dir: Option<&Path>,
+    ) -> std::io::Result<Self> {
+        let (read, payload) = ASET_MIGRATIONS.read_payload(reader)?;
+        read(&payload, document_dir)
+    }
+    /// Decode a version 0.0.0 `aset` payload, after the versioned header.
+    fn read_v0(mut reader: &[u8], document_dir: Option<&Path>) -> std::io::Result<Self> {
+        use std::io::{Error as IOError, Read};
+
+        fn read_u32(reader: &mut impl std::io::Read) -> std::io::Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        }
+        fn read_bytes(reader: &mut impl std::io::Read) -> std::io::Result<Vec<u8>> {
+            let len = read_u32(reader)?;
+            let mut bytes = Vec::new();
+            // Don't trust the length for allocation, a short chunk will EOF long before then.
+            std::io::Read::take(reader, len.into()).read_to_end(&mut bytes)?;
+            if bytes.len() != len as usize {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            Ok(bytes)
+        }
+        fn read_string(reader: &mut impl std::io::Read) -> std::io::Result<String> {
+            String::from_utf8(read_bytes(reader)?)
+                .map_err(|_| IOError::other(anyhow::anyhow!("asset string is not UTF-8")))
+        }
+
+        let count = read_u32(&mut reader)?;
+
+        let mut links = UniqueIDMap::default();
+        for _ in 0..count {
+            let mut id = [0; 32];
+            reader.read_exact(&mut id)?;
+            let name = read_string(&mut reader)?;
+            let mut kind = [0];
+            reader.read_exact(&mut kind)?;
+            let source = match kind[0] {
+                0 => AssetSource::Embedded(read_bytes(&mut reader)?.into()),
+                1 => {
+                    let path = PathBuf::from(read_string(&mut reader)?);
+                    match document_dir {
+                        Some(dir) if path.is_relative() => AssetSource::Linked(dir.join(path)),
+                        _ => AssetSource::Linked(path),
+                    }
+                }
+                _ => return Err(IOError::other(anyhow::anyhow!("unknown asset source"))),
+            };
+            links.insert(UniqueID(id), AssetLink { name, source });
+        }
+
+        Ok(Self { links })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::relative_path;
+    use std::path::{Path, PathBuf};
+    #[cfg(unix)]
+    #[test]
+    fn relative_paths() {
+        let relative = |from: &str, to: &str| relative_path(Path::new(from), Path::new(to));
+        assert_eq!(
+            relative("/art", "/art/textures/grain.png"),
+            Some(PathBuf::from("textures/grain.png"))
+        );
+        assert_eq!(
+            relative("/art/comics", "/art/textures/grain.png"),
+            Some(PathBuf::from("../textures/grain.png"))
+        );
+        assert_eq!(
+            relative("/art/comics/", "/grain.png"),
+            Some(PathBuf::from("../../grain.png"))
+        );
+        // Can't climb out of an unknown directory.
+        assert_eq!(relative("/art/..", "/grain.png"), None);
+        assert_eq!(relative("art", "/grain.png"), None);
+    }
+}
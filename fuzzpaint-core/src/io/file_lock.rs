@@ -0,0 +1,91 @@
+//! # File locks
+//!
+//! A document holds a lock for as long as it is open, so that a second instance opening the same file knows
+//! not to write over it. Locks are advisory on some platforms, so they only guard against other well-behaved
+//! writers.
+//!
+//! The lock is taken on a file of its own beside the document, named as the document with `.lock` appended,
+//! rather than on the document itself. Some platforms make locks mandatory, which would stop anyone from
+//! reading a locked document, even the instance that locked it. The lock file is created as needed and
+//! deleted once the lock is released. One left behind by a crash is unlocked, and taken over by the next to
+//! lock it. Saving [replaces](super::safe_save) the document without touching its lock.
+
+use std::path::{Path, PathBuf};
+
+/// An exclusive lock on a file, released on drop.
+pub struct FileLock {
+    /// The lock file, locked.
+    lock: std::fs::File,
+    path: PathBuf,
+}
+impl FileLock {
+    /// Lock the file at `path`. `Ok(None)` if it is already locked, by another process or by another open
+    /// of the same file.
+    pub fn try_new(path: impl Into<PathBuf>) -> std::io::Result<Option<Self>> {
+        let path = path.into();
+        let lock = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_path(&path))?;
+        match lock.try_lock() {
+            Ok(()) => Ok(Some(Self { lock, path })),
+            Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+            Err(std::fs::TryLockError::Error(err)) => Err(err),
+        }
+    }
+    /// The path of the locked file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // While still locked, so that no one takes over a lock file that's about to be deleted. Nothing to be
+        // done on failure, it's taken over next time.
+        let _ = std::fs::remove_file(lock_path(&self.path));
+    }
+}
+impl std::fmt::Debug for FileLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileLock")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+/// The lock file of the file at `path`.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock = path.as_os_str().to_owned();
+    lock.push(".lock");
+    lock.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileLock;
+    #[test]
+    fn exclusive() {
+        let dir = crate::util::scratch_dir("file-lock");
+        let path = dir.join("doc.fzp");
+        std::fs::write(&path, b"old").unwrap();
+
+        let lock = FileLock::try_new(&path).unwrap().unwrap();
+        assert!(FileLock::try_new(&path).unwrap().is_none());
+        // Still readable, as a second instance opening it read-only would.
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+
+        // Replaced, as with a save. Still locked.
+        crate::io::safe_save::save::<_, std::io::Error>(&path, 0, |mut file| {
+            std::io::Write::write_all(&mut file, b"new")
+        })
+        .unwrap();
+        assert!(FileLock::try_new(&path).unwrap().is_none());
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+
+        drop(lock);
+        assert!(!super::lock_path(&path).exists());
+        assert!(FileLock::try_new(&path).unwrap().is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
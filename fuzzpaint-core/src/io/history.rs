@@ -0,0 +1,1507 @@
+//! # History
+//!
+//! The `HIST` chunk, holding a line of the document's [command history](crate::queue::History) so that undo
+//! survives a save and reopen.
+//!
+//! The chunk holds a base of commands which recreate the state from before the oldest undoable command, the
+//! line of commands itself, and how many of them lead up to the present. The line is limited in length, so
+//! the base may describe anything from an empty document to the present. The document is rebuilt from the
+//! chunk when reading, as it is the only place the blend graph and stroke settings are currently written.
+//!
+//! Names, tags, and reference flags of nodes aren't part of history, so the present ones follow the commands.
+//! The reference flag shares the tag's byte, as [`REFERENCE_BIT`].
+//!
+//! IDs are process-local, and are written as file-local IDs. Point collections are referred to by their
+//! IDs within the `PTLS` dictionary.
+//!
+//! From version 0.1.0, each created stroke is followed by its [metadata](StrokeMetadata). Strokes read from
+//! older chunks have none. From version 0.2.0, the canvas may be changed by a command, before which it was
+//! always the default.
+
+use crate::{
+    blend::{Blend, BlendMode},
+    color::{Color, ColorOrPalette, PaletteIndex},
+    commands::{Command, MetaCommand, ScopeType},
+    gradient::{Gradient, GradientShape},
+    io::{
+        id::{FileLocalInterner, ProcessLocalInterner},
+        migrate::{Migrations, Reader},
+        riff::ChunkID,
+        Version,
+    },
+    repositories::points::PointCollectionIDMarker,
+    state::{
+        document::{self, Canvas},
+        graph::{self, AnyID, ColorTag, LeafID, LeafType, NodeID, NodeType},
+        palette,
+        stroke_collection::{self, ImmutableStroke, StrokeCollection, StrokeMetadata},
+        transform::{Matrix, Similarity},
+        DualStamp, Dynamics, DynamicsInput, Response, Scatter, StampOrientation,
+        StrokeBrushSettings, Taper,
+    },
+    units::Resolution,
+    util::FiniteF32,
+};
+use std::io::{Error as IOError, Read};
+
+/// The versions of `hist` chunks that can be read, and the version written.
+pub const HIST_MIGRATIONS: Migrations<
+    fn(&[u8], ProcessLocalInterner<PointCollectionIDMarker>) -> std::io::Result<ReadHistory>,
+> = Migrations {
+    chunk: ChunkID::HIST,
+    current: Version(0, 2, 0),
+    readers: &[
+        Reader {
+            versions: Version(0, 0, 0)..=Version(0, 0, 0),
+            read: read_v0,
+        },
+        Reader {
+            versions: Version(0, 1, 0)..=Version(0, 2, 0),
+            read: read_v1,
+        },
+    ],
+    upgrades: &[],
+};
+/// Set in a label's tag byte if the node is a reference leaf.
+const REFERENCE_BIT: u8 = 0x80;
+
+/// Tags of each command. Grouped by the state they act on, with room to grow.
+mod tag {
+    pub const SCOPE: u8 = 0;
+
+    pub const BLEND_CHANGED: u8 = 16;
+    pub const REPARENT: u8 = 17;
+    pub const LEAF_CREATED: u8 = 18;
+    pub const LEAF_INNER_TRANSFORM_CHANGED: u8 = 19;
+    pub const LEAF_OUTER_TRANSFORM_CHANGED: u8 = 20;
+    pub const LEAF_TY_CHANGED: u8 = 21;
+    pub const NODE_CREATED: u8 = 22;
+    pub const NODE_TY_CHANGED: u8 = 23;
+    pub const ANY_DELETED: u8 = 24;
+
+    pub const PALETTE_ADDED: u8 = 32;
+    pub const PALETTE_CHANGED: u8 = 33;
+
+    pub const COLLECTION_CREATED: u8 = 48;
+    pub const STROKE_CREATED: u8 = 49;
+    pub const STROKE_BRUSH_CHANGED: u8 = 50;
+
+    pub const CANVAS_CHANGED: u8 = 64;
+    pub const RESOLUTION_CHANGED: u8 = 65;
+}
+
+fn invalid(what: &str) -> IOError {
+    IOError::other(anyhow::anyhow!("invalid history: {what}"))
+}
+
+/// The file-local IDs given to everything a `hist` chunk mentions, which a [journal](super::journal) written
+/// after it continues from.
+#[derive(Clone, Default)]
+pub struct FileIds {
+    /// Leaves and nodes share one space of IDs.
+    graph: hashbrown::HashMap<AnyID, u32>,
+    collections: FileLocalInterner<StrokeCollection>,
+    strokes: FileLocalInterner<ImmutableStroke>,
+    pub(super) points: FileLocalInterner<PointCollectionIDMarker>,
+}
+/// The process-local IDs given to everything a `hist` chunk mentions as it was read, the reverse of
+/// [`FileIds`].
+#[derive(Default)]
+pub struct ProcessIds {
+    graph: hashbrown::HashMap<u32, AnyID>,
+    collections: ProcessLocalInterner<StrokeCollection>,
+    strokes: ProcessLocalInterner<ImmutableStroke>,
+    pub(super) points: ProcessLocalInterner<PointCollectionIDMarker>,
+}
+impl ProcessIds {
+    /// The same IDs the other way around, to write more that refers to them.
+    #[must_use]
+    pub fn to_file(&self) -> FileIds {
+        FileIds {
+            graph: self.graph.iter().map(|(&file, &id)| (id, file)).collect(),
+            collections: FileLocalInterner::from_process(&self.collections),
+            strokes: FileLocalInterner::from_process(&self.strokes),
+            points: FileLocalInterner::from_process(&self.points),
+        }
+    }
+}
+
+pub(super) struct Encoder {
+    pub(super) buf: Vec<u8>,
+    pub(super) ids: FileIds,
+}
+impl Encoder {
+    pub(super) fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+    pub(super) fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+    fn f32(&mut self, value: f32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+    fn len(&mut self, len: usize) -> std::io::Result<()> {
+        self.u32(u32::try_from(len).map_err(|_| invalid("too long"))?);
+        Ok(())
+    }
+    fn string(&mut self, string: &str) -> std::io::Result<()> {
+        self.len(string.len())?;
+        self.buf.extend_from_slice(string.as_bytes());
+        Ok(())
+    }
+    fn graph_id(&mut self, id: AnyID) -> std::io::Result<()> {
+        let next = u32::try_from(self.ids.graph.len()).map_err(|_| invalid("too many nodes"))?;
+        let id = *self.ids.graph.entry(id).or_insert(next);
+        self.u32(id);
+        Ok(())
+    }
+    fn parent(&mut self, parent: Option<NodeID>) -> std::io::Result<()> {
+        match parent {
+            None => self.u8(0),
+            Some(parent) => {
+                self.u8(1);
+                self.graph_id(parent.into())?;
+            }
+        }
+        Ok(())
+    }
+    fn any_id(&mut self, id: AnyID) -> std::io::Result<()> {
+        self.u8(match id {
+            AnyID::Leaf(_) => 0,
+            AnyID::Node(_) => 1,
+        });
+        self.graph_id(id)
+    }
+    fn collection(&mut self, id: stroke_collection::StrokeCollectionID) -> std::io::Result<()> {
+        let id = self
+            .ids
+            .collections
+            .get_or_insert(id)
+            .map_err(|_| invalid("too many collections"))?;
+        self.u32(id.id);
+        Ok(())
+    }
+    fn points(
+        &mut self,
+        id: crate::repositories::points::PointCollectionID,
+    ) -> std::io::Result<()> {
+        let id = self
+            .ids
+            .points
+            .get(id)
+            .ok_or_else(|| invalid("points not written"))?;
+        self.u32(id.id);
+        Ok(())
+    }
+    fn index(&mut self, idx: usize) -> std::io::Result<()> {
+        self.len(idx)
+    }
+    fn blend(&mut self, blend: Blend) {
+        self.u8(blend.mode as u8);
+        self.f32(blend.opacity);
+        self.u8(blend.alpha_clip.into());
+    }
+    fn color(&mut self, color: Color) {
+        for channel in color.as_array() {
+            self.f32(channel);
+        }
+    }
+    fn color_or_palette(&mut self, color: ColorOrPalette) {
+        match color.get() {
+            either::Either::Left(color) => {
+                self.u8(0);
+                self.color(color);
+            }
+            either::Either::Right(PaletteIndex(idx)) => {
+                self.u8(1);
+                self.u64(idx);
+            }
+        }
+    }
+    fn similarity(&mut self, similarity: &Similarity) {
+        self.f32(similarity.flip_scale);
+        self.f32(similarity.rotation);
+        self.f32(similarity.translation[0]);
+        self.f32(similarity.translation[1]);
+    }
+    fn matrix(&mut self, matrix: &Matrix) {
+        for element in matrix.elements.iter().flatten() {
+            self.f32(*element);
+        }
+    }
+    fn canvas(&mut self, canvas: &Canvas) {
+        for coordinate in canvas.origin.iter().chain(&canvas.size) {
+            self.f32(*coordinate);
+        }
+    }
+    fn resolution(&mut self, resolution: Resolution) {
+        self.u8(match resolution {
+            Resolution::Dpi(_) => 0,
+            Resolution::Dpcm(_) => 1,
+        });
+        self.f32(resolution.value());
+    }
+    fn leaf_ty(&mut self, ty: &LeafType) -> std::io::Result<()> {
+        match ty {
+            LeafType::StrokeLayer {
+                blend,
+                collection,
+                inner_transform,
+                outer_transform,
+            } => {
+                self.u8(0);
+                self.blend(*blend);
+                self.collection(*collection)?;
+                self.similarity(inner_transform);
+                self.matrix(outer_transform);
+            }
+            LeafType::SolidColor { blend, source } => {
+                self.u8(1);
+                self.blend(*blend);
+                self.color_or_palette(*source);
+            }
+            LeafType::Text {
+                blend,
+                text,
+                px_per_em,
+                outer_transform,
+            } => {
+                self.u8(2);
+                self.blend(*blend);
+                self.string(text)?;
+                self.f32(*px_per_em);
+                self.matrix(outer_transform);
+            }
+            LeafType::Note => self.u8(3),
+            LeafType::Image {
+                blend,
+                image,
+                outer_transform,
+            } => {
+                self.u8(4);
+                self.blend(*blend);
+                self.buf.extend_from_slice(&image.0);
+                self.matrix(outer_transform);
+            }
+            LeafType::Gradient { blend, gradient } => {
+                self.u8(5);
+                self.blend(*blend);
+                self.gradient(gradient)?;
+            }
+        }
+        Ok(())
+    }
+    fn gradient(&mut self, gradient: &Gradient) -> std::io::Result<()> {
+        self.u8(gradient.shape as u8);
+        for coordinate in gradient.start.iter().chain(&gradient.end) {
+            self.f32(*coordinate);
+        }
+        // At most `MAX_STOPS`, a byte is plenty.
+        if gradient.stops.len() > Gradient::MAX_STOPS {
+            return Err(invalid("too many gradient stops"));
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        self.u8(gradient.stops.len() as u8);
+        for stop in &gradient.stops {
+            self.f32(stop.position);
+            self.color_or_palette(stop.color);
+        }
+        Ok(())
+    }
+    fn node_ty(&mut self, ty: &NodeType) {
+        match ty {
+            NodeType::Passthrough => self.u8(0),
+            NodeType::GroupedBlend(blend) => {
+                self.u8(1);
+                self.blend(*blend);
+            }
+            NodeType::Filtered { blend, filter } => {
+                self.u8(2);
+                self.blend(*blend);
+                self.buf.extend_from_slice(&filter.to_bytes());
+            }
+        }
+    }
+    fn metadata(&mut self, metadata: &StrokeMetadata) {
+        for part in [metadata.created, metadata.author, metadata.device] {
+            match part {
+                None => self.u8(0),
+                Some(part) => {
+                    self.u8(1);
+                    self.u64(part);
+                }
+            }
+        }
+    }
+    fn brush(&mut self, brush: &StrokeBrushSettings) {
+        self.buf.extend_from_slice(&brush.brush.0);
+        self.color_or_palette(brush.color_modulate);
+        self.f32(brush.size_mul.get());
+        // Was the eraser flag alone, smudge takes the next bit and orientation the two after. The last four
+        // mark whether scatter, a dual stamp, taper, and dynamics follow the spacing.
+        let orientation: u8 = match brush.orientation {
+            StampOrientation::Random => 0,
+            StampOrientation::Direction => 1,
+            StampOrientation::Tilt => 2,
+        };
+        let scatter = !brush.scatter.is_none();
+        let taper = !brush.taper.is_none();
+        let dynamics = !brush.dynamics.is_default();
+        self.u8(u8::from(brush.is_eraser)
+            | u8::from(brush.is_smudge) << 1
+            | orientation << 2
+            | u8::from(scatter) << 4
+            | u8::from(brush.dual.is_some()) << 5
+            | u8::from(taper) << 6
+            | u8::from(dynamics) << 7);
+        self.f32(brush.spacing_px.get());
+        if scatter {
+            self.f32(brush.scatter.position);
+            self.f32(brush.scatter.size);
+            self.f32(brush.scatter.angle);
+        }
+        if let Some(dual) = brush.dual {
+            self.buf.extend_from_slice(&dual.brush.0);
+            self.f32(dual.scale);
+        }
+        if taper {
+            self.f32(brush.taper.start);
+            self.f32(brush.taper.end);
+            self.f32(brush.taper.velocity);
+        }
+        if dynamics {
+            for response in [
+                brush.dynamics.size,
+                brush.dynamics.flow,
+                brush.dynamics.opacity,
+            ] {
+                self.response(response);
+            }
+        }
+    }
+    fn response(&mut self, response: Response) {
+        self.u8(match response.input {
+            DynamicsInput::None => 0,
+            DynamicsInput::Pressure => 1,
+            DynamicsInput::Tilt => 2,
+            DynamicsInput::Speed => 3,
+            DynamicsInput::Wheel => 4,
+        });
+        self.f32(response.min);
+        self.f32(response.gamma);
+    }
+    pub(super) fn command(&mut self, command: &Command) -> std::io::Result<()> {
+        use document::commands::Command as Document;
+        use graph::commands::Command as Graph;
+        use palette::commands::Command as Palette;
+        use stroke_collection::commands::{Command as Strokes, StrokeCommand};
+        match command {
+            Command::Meta(MetaCommand::Scope(ty, commands)) => {
+                self.u8(tag::SCOPE);
+                self.u8(match ty {
+                    ScopeType::Atoms => 0,
+                    ScopeType::WritePanic => 1,
+                });
+                self.len(commands.len())?;
+                for command in commands.iter() {
+                    self.command(command)?;
+                }
+            }
+            // Neither changes the state, and neither are ever written into the history.
+            Command::Meta(MetaCommand::Save(..)) | Command::Dummy => {
+                return Err(invalid("unexpected command"));
+            }
+            Command::Document(Document::CanvasChanged { from, to }) => {
+                self.u8(tag::CANVAS_CHANGED);
+                self.canvas(from);
+                self.canvas(to);
+            }
+            Command::Document(Document::ResolutionChanged { from, to }) => {
+                self.u8(tag::RESOLUTION_CHANGED);
+                self.resolution(*from);
+                self.resolution(*to);
+            }
+            Command::Graph(Graph::BlendChanged { from, to, target }) => {
+                self.u8(tag::BLEND_CHANGED);
+                self.any_id(*target)?;
+                self.blend(*from);
+                self.blend(*to);
+            }
+            Command::Graph(Graph::Reparent {
+                target,
+                new_parent,
+                new_child_idx,
+                old_parent,
+                old_child_idx,
+            }) => {
+                self.u8(tag::REPARENT);
+                self.any_id(*target)?;
+                self.parent(*new_parent)?;
+                self.index(*new_child_idx)?;
+                self.parent(*old_parent)?;
+                self.index(*old_child_idx)?;
+            }
+            Command::Graph(Graph::LeafCreated {
+                target,
+                name,
+                ty,
+                destination,
+                child_idx,
+            }) => {
+                self.u8(tag::LEAF_CREATED);
+                self.graph_id((*target).into())?;
+                self.string(name)?;
+                self.leaf_ty(ty)?;
+                self.parent(*destination)?;
+                self.index(*child_idx)?;
+            }
+            Command::Graph(Graph::LeafInnerTransformChanged {
+                target,
+                old_transform,
+                new_transform,
+            }) => {
+                self.u8(tag::LEAF_INNER_TRANSFORM_CHANGED);
+                self.graph_id((*target).into())?;
+                self.similarity(old_transform);
+                self.similarity(new_transform);
+            }
+            Command::Graph(Graph::LeafOuterTransformChanged {
+                target,
+                old_transform,
+                new_transform,
+            }) => {
+                self.u8(tag::LEAF_OUTER_TRANSFORM_CHANGED);
+                self.graph_id((*target).into())?;
+                self.matrix(old_transform);
+                self.matrix(new_transform);
+            }
+            Command::Graph(Graph::LeafTyChanged { target, old_ty, ty }) => {
+                self.u8(tag::LEAF_TY_CHANGED);
+                self.graph_id((*target).into())?;
+                self.leaf_ty(old_ty)?;
+                self.leaf_ty(ty)?;
+            }
+            Command::Graph(Graph::NodeCreated {
+                target,
+                name,
+                ty,
+                destination,
+                child_idx,
+            }) => {
+                self.u8(tag::NODE_CREATED);
+                self.graph_id((*target).into())?;
+                self.string(name)?;
+                self.node_ty(ty);
+                self.parent(*destination)?;
+                self.index(*child_idx)?;
+            }
+            Command::Graph(Graph::NodeTyChanged { target, old_ty, ty }) => {
+                self.u8(tag::NODE_TY_CHANGED);
+                self.graph_id((*target).into())?;
+                self.node_ty(old_ty);
+                self.node_ty(ty);
+            }
+            Command::Graph(Graph::AnyDeleted { target }) => {
+                self.u8(tag::ANY_DELETED);
+                self.any_id(*target)?;
+            }
+            Command::Palette(Palette::Added {
+                target,
+                initial_color,
+            }) => {
+                self.u8(tag::PALETTE_ADDED);
+                self.u64(target.0);
+                self.color(*initial_color);
+            }
+            Command::Palette(Palette::Changed { target, from, to }) => {
+                self.u8(tag::PALETTE_CHANGED);
+                self.u64(target.0);
+                self.color(*from);
+                self.color(*to);
+            }
+            Command::StrokeCollection(Strokes::Created(id)) => {
+                self.u8(tag::COLLECTION_CREATED);
+                self.collection(*id)?;
+            }
+            Command::StrokeCollection(Strokes::Stroke {
+                target,
+                command:
+                    StrokeCommand::Created {
+                        target: stroke,
+                        brush,
+                        points,
+                        clip,
+                        metadata,
+                    },
+            }) => {
+                self.u8(tag::STROKE_CREATED);
+                self.collection(*target)?;
+                let stroke = self
+                    .ids
+                    .strokes
+                    .get_or_insert(*stroke)
+                    .map_err(|_| invalid("too many strokes"))?;
+                self.u32(stroke.id);
+                self.brush(brush);
+                self.points(*points)?;
+                match clip {
+                    None => self.u8(0),
+                    Some(clip) => {
+                        self.u8(1);
+                        self.points(*clip)?;
+                    }
+                }
+                self.metadata(metadata);
+            }
+            Command::StrokeCollection(Strokes::Stroke {
+                target,
+                command:
+                    StrokeCommand::BrushChanged {
+                        target: stroke,
+                        from,
+                        to,
+                    },
+            }) => {
+                self.u8(tag::STROKE_BRUSH_CHANGED);
+                self.collection(*target)?;
+                let stroke = self
+                    .ids
+                    .strokes
+                    .get_or_insert(*stroke)
+                    .map_err(|_| invalid("too many strokes"))?;
+                self.u32(stroke.id);
+                self.brush(from);
+                self.brush(to);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Collect every point collection the command refers to.
+pub(super) fn command_points(
+    command: &Command,
+    into: &mut Vec<crate::repositories::points::PointCollectionID>,
+) {
+    use stroke_collection::commands::{Command as Strokes, StrokeCommand};
+    match command {
+        Command::Meta(MetaCommand::Scope(_, commands)) => {
+            for command in commands.iter() {
+                command_points(command, into);
+            }
+        }
+        Command::StrokeCollection(Strokes::Stroke {
+            command: StrokeCommand::Created { points, clip, .. },
+            ..
+        }) => {
+            into.push(*points);
+            into.extend(*clip);
+        }
+        _ => (),
+    }
+}
+
+/// Every point collection the history refers to, which must be written into the `PTLS` dictionary
+/// alongside it.
+pub fn point_collections(
+    history: &crate::queue::History,
+) -> impl Iterator<Item = crate::repositories::points::PointCollectionID> + '_ {
+    history
+        .base
+        .iter()
+        .chain(&history.commands)
+        .flat_map(|command| {
+            let mut points = Vec::new();
+            command_points(command, &mut points);
+            points
+        })
+}
+
+/// Encode a line of history into a `hist` chunk, along with the names and tags of the `present` graph.
+/// `points` are the IDs the point collections were written under. Returns the IDs given to everything the
+/// history mentions.
+pub fn write_chunk_into(
+    history: &crate::queue::History,
+    present: &graph::BlendGraph,
+    points: FileLocalInterner<PointCollectionIDMarker>,
+    writer: impl std::io::Write,
+) -> std::io::Result<FileIds> {
+    use super::{riff::encode::SizedBinaryChunkWriter, OrphanMode};
+    let mut encoder = Encoder {
+        buf: Vec::new(),
+        ids: FileIds {
+            points,
+            ..Default::default()
+        },
+    };
+    encoder
+        .buf
+        .extend_from_slice(bytemuck::bytes_of(&HIST_MIGRATIONS.current));
+    // Stale history would undo into nonsense.
+    encoder.u8(OrphanMode::Discard as u8);
+    encoder.len(history.base.len())?;
+    encoder.len(history.commands.len())?;
+    encoder.len(history.present)?;
+    for command in history.base.iter().chain(&history.commands) {
+        encoder.command(command)?;
+    }
+    // Labels of every node the commands mentioned which is still around.
+    let labels: Vec<_> = present
+        .iter()
+        .filter(|(id, _)| encoder.ids.graph.contains_key(id))
+        .collect();
+    encoder.len(labels.len())?;
+    for (id, data) in labels {
+        encoder.graph_id(id)?;
+        encoder.string(data.name())?;
+        let reference = if data.is_reference() {
+            REFERENCE_BIT
+        } else {
+            0
+        };
+        encoder.u8(u8::from(data.tag()) | reference);
+    }
+
+    SizedBinaryChunkWriter::write_buf(writer, ChunkID::HIST, &encoder.buf)?;
+    Ok(encoder.ids)
+}
+
+/// Scopes are written nested at most one deep. Deeper is read, but not without bound, as each level of
+/// nesting is a level of recursion.
+const MAX_SCOPE_DEPTH: usize = 8;
+
+pub(super) struct Decoder<R> {
+    pub(super) reader: R,
+    pub(super) ids: ProcessIds,
+    /// Whether created strokes are followed by their metadata, as from version 0.1.0.
+    pub(super) metadata: bool,
+}
+impl<R: Read> Decoder<R> {
+    pub(super) fn bytes<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+    pub(super) fn u8(&mut self) -> std::io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+    fn bool(&mut self) -> std::io::Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("bad flag")),
+        }
+    }
+    pub(super) fn u32(&mut self) -> std::io::Result<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+    fn u64(&mut self) -> std::io::Result<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+    fn optional_u64(&mut self) -> std::io::Result<Option<u64>> {
+        if self.bool()? {
+            self.u64().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+    fn f32(&mut self) -> std::io::Result<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+    fn finite(&mut self) -> std::io::Result<FiniteF32> {
+        FiniteF32::new(self.f32()?).map_err(|_| invalid("non-finite value"))
+    }
+    fn len(&mut self) -> std::io::Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+    fn string(&mut self) -> std::io::Result<String> {
+        let len = self.u32()?;
+        let mut bytes = Vec::new();
+        // Don't trust the length for allocation, a short chunk will EOF long before then.
+        self.reader
+            .by_ref()
+            .take(len.into())
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != len as usize {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(bytes).map_err(|_| invalid("string is not UTF-8"))
+    }
+    /// Read an ID, allocating it as `new` if it is seen for the first time.
+    fn graph_id(&mut self, new: impl FnOnce() -> AnyID) -> std::io::Result<AnyID> {
+        let id = self.u32()?;
+        Ok(*self.ids.graph.entry(id).or_insert_with(new))
+    }
+    fn leaf_id(&mut self) -> std::io::Result<LeafID> {
+        self.graph_id(|| LeafID::new_unique().into())?
+            .try_into()
+            .map_err(|()| invalid("expected a leaf"))
+    }
+    fn node_id(&mut self) -> std::io::Result<NodeID> {
+        self.graph_id(|| NodeID::new_unique().into())?
+            .try_into()
+            .map_err(|()| invalid("expected a node"))
+    }
+    fn any_id(&mut self) -> std::io::Result<AnyID> {
+        if self.bool()? {
+            self.node_id().map(Into::into)
+        } else {
+            self.leaf_id().map(Into::into)
+        }
+    }
+    fn parent(&mut self) -> std::io::Result<Option<NodeID>> {
+        if self.bool()? {
+            self.node_id().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+    fn collection(&mut self) -> std::io::Result<stroke_collection::StrokeCollectionID> {
+        let id = self.u32()?;
+        Ok(self.ids.collections.get_or_insert(id.into()))
+    }
+    fn points(&mut self) -> std::io::Result<crate::repositories::points::PointCollectionID> {
+        let id = self.u32()?;
+        self.ids
+            .points
+            .get(id.into())
+            .ok_or_else(|| invalid("unknown points"))
+    }
+    fn blend(&mut self) -> std::io::Result<Blend> {
+        let mode = self.u8()?;
+        let mode = <BlendMode as strum::IntoEnumIterator>::iter()
+            .find(|m| *m as u8 == mode)
+            .ok_or_else(|| invalid("unknown blend mode"))?;
+        Ok(Blend {
+            mode,
+            opacity: self.f32()?,
+            alpha_clip: self.bool()?,
+        })
+    }
+    fn color(&mut self) -> std::io::Result<Color> {
+        let channels = [self.f32()?, self.f32()?, self.f32()?, self.f32()?];
+        Color::from_array_lossy(channels).map_err(|_| invalid("non-finite color"))
+    }
+    fn color_or_palette(&mut self) -> std::io::Result<ColorOrPalette> {
+        if self.bool()? {
+            Ok(ColorOrPalette::from_palette_index(PaletteIndex(
+                self.u64()?,
+            )))
+        } else {
+            self.color().map(ColorOrPalette::from_color)
+        }
+    }
+    fn similarity(&mut self) -> std::io::Result<Similarity> {
+        Ok(Similarity {
+            flip_scale: self.f32()?,
+            rotation: self.f32()?,
+            translation: [self.f32()?, self.f32()?],
+        })
+    }
+    fn matrix(&mut self) -> std::io::Result<Matrix> {
+        let mut matrix = Matrix::default();
+        for element in matrix.elements.iter_mut().flatten() {
+            *element = self.f32()?;
+        }
+        Ok(matrix)
+    }
+    fn canvas(&mut self) -> std::io::Result<Canvas> {
+        let canvas = Canvas {
+            origin: [self.f32()?, self.f32()?],
+            size: [self.f32()?, self.f32()?],
+        };
+        if canvas.is_valid() {
+            Ok(canvas)
+        } else {
+            Err(invalid("empty canvas"))
+        }
+    }
+    fn resolution(&mut self) -> std::io::Result<Resolution> {
+        let resolution = match self.u8()? {
+            0 => Resolution::Dpi(self.f32()?),
+            1 => Resolution::Dpcm(self.f32()?),
+            _ => return Err(invalid("unknown resolution unit")),
+        };
+        if document::Viewport::DPI_RANGE.contains(&resolution.into_dpi()) {
+            Ok(resolution)
+        } else {
+            Err(invalid("resolution out of range"))
+        }
+    }
+    fn leaf_ty(&mut self) -> std::io::Result<LeafType> {
+        Ok(match self.u8()? {
+            0 => LeafType::StrokeLayer {
+                blend: self.blend()?,
+                collection: self.collection()?,
+                inner_transform: self.similarity()?,
+                outer_transform: self.matrix()?,
+            },
+            1 => LeafType::SolidColor {
+                blend: self.blend()?,
+                source: self.color_or_palette()?,
+            },
+            2 => LeafType::Text {
+                blend: self.blend()?,
+                text: self.string()?,
+                px_per_em: self.f32()?,
+                outer_transform: self.matrix()?,
+            },
+            3 => LeafType::Note,
+            4 => LeafType::Image {
+                blend: self.blend()?,
+                image: crate::brush::UniqueID(self.bytes()?),
+                outer_transform: self.matrix()?,
+            },
+            5 => LeafType::Gradient {
+                blend: self.blend()?,
+                gradient: self.gradient()?,
+            },
+            _ => return Err(invalid("unknown leaf type")),
+        })
+    }
+    fn gradient(&mut self) -> std::io::Result<Gradient> {
+        let shape = self.u8()?;
+        let shape = <GradientShape as strum::IntoEnumIterator>::iter()
+            .find(|s| *s as u8 == shape)
+            .ok_or_else(|| invalid("unknown gradient shape"))?;
+        let start = [self.f32()?, self.f32()?];
+        let end = [self.f32()?, self.f32()?];
+        let len = usize::from(self.u8()?);
+        if len > Gradient::MAX_STOPS {
+            return Err(invalid("too many gradient stops"));
+        }
+        let stops = (0..len)
+            .map(|_| {
+                Ok(crate::gradient::Stop {
+                    position: self.f32()?,
+                    color: self.color_or_palette()?,
+                })
+            })
+            .collect::<std::io::Result<_>>()?;
+        Ok(Gradient {
+            shape,
+            start,
+            end,
+            stops,
+        }
+        .clamped())
+    }
+    fn node_ty(&mut self) -> std::io::Result<NodeType> {
+        Ok(match self.u8()? {
+            0 => NodeType::Passthrough,
+            1 => NodeType::GroupedBlend(self.blend()?),
+            2 => NodeType::Filtered {
+                blend: self.blend()?,
+                filter: crate::filter::Filter::from_bytes(&self.bytes()?)
+                    .map_err(IOError::other)?,
+            },
+            _ => return Err(invalid("unknown node type")),
+        })
+    }
+    fn metadata(&mut self) -> std::io::Result<StrokeMetadata> {
+        if !self.metadata {
+            return Ok(StrokeMetadata::default());
+        }
+        Ok(StrokeMetadata {
+            created: self.optional_u64()?,
+            author: self.optional_u64()?,
+            device: self.optional_u64()?,
+        })
+    }
+    fn brush(&mut self) -> std::io::Result<StrokeBrushSettings> {
+        let brush = crate::brush::UniqueID(self.bytes()?);
+        let color_modulate = self.color_or_palette()?;
+        let size_mul = self.finite()?;
+        // Every bit is in use.
+        let flags = self.u8()?;
+        let orientation = match (flags >> 2) & 0b11 {
+            0 => StampOrientation::Random,
+            1 => StampOrientation::Direction,
+            2 => StampOrientation::Tilt,
+            _ => return Err(invalid("unknown stamp orientation")),
+        };
+        let spacing_px = self.finite()?;
+        let scatter = if flags & 0b1_0000 == 0 {
+            Scatter::default()
+        } else {
+            Scatter {
+                position: self.finite()?.get(),
+                size: self.finite()?.get(),
+                angle: self.finite()?.get(),
+            }
+            .sanitized()
+        };
+        let dual = if flags & 0b10_0000 == 0 {
+            None
+        } else {
+            Some(
+                DualStamp {
+                    brush: crate::brush::UniqueID(self.bytes()?),
+                    scale: self.finite()?.get(),
+                }
+                .sanitized(),
+            )
+        };
+        let taper = if flags & 0b100_0000 == 0 {
+            Taper::default()
+        } else {
+            Taper {
+                start: self.finite()?.get(),
+                end: self.finite()?.get(),
+                velocity: self.finite()?.get(),
+            }
+            .sanitized()
+        };
+        let dynamics = if flags & 0b1000_0000 == 0 {
+            Dynamics::default()
+        } else {
+            Dynamics {
+                size: self.response()?,
+                flow: self.response()?,
+                opacity: self.response()?,
+            }
+        };
+        Ok(StrokeBrushSettings {
+            brush,
+            color_modulate,
+            size_mul,
+            is_eraser: flags & 0b01 != 0,
+            is_smudge: flags & 0b10 != 0,
+            orientation,
+            scatter,
+            dual,
+            taper,
+            dynamics,
+            spacing_px,
+        })
+    }
+    fn response(&mut self) -> std::io::Result<Response> {
+        let input = match self.u8()? {
+            0 => DynamicsInput::None,
+            1 => DynamicsInput::Pressure,
+            2 => DynamicsInput::Tilt,
+            3 => DynamicsInput::Speed,
+            4 => DynamicsInput::Wheel,
+            _ => return Err(invalid("unknown dynamics input")),
+        };
+        Ok(Response {
+            input,
+            min: self.finite()?.get(),
+            gamma: self.finite()?.get(),
+        }
+        .sanitized())
+    }
+    pub(super) fn command(&mut self) -> std::io::Result<Command> {
+        self.command_at(0)
+    }
+    /// Read a command within `depth` scopes.
+    fn command_at(&mut self, depth: usize) -> std::io::Result<Command> {
+        use document::commands::Command as Document;
+        use graph::commands::Command as Graph;
+        use palette::commands::Command as Palette;
+        use stroke_collection::commands::{Command as Strokes, StrokeCommand};
+        Ok(match self.u8()? {
+            tag::SCOPE => {
+                let ty = match self.u8()? {
+                    0 => ScopeType::Atoms,
+                    1 => ScopeType::WritePanic,
+                    _ => return Err(invalid("unknown scope")),
+                };
+                if depth >= MAX_SCOPE_DEPTH {
+                    return Err(invalid("scopes nested too deeply"));
+                }
+                let len = self.len()?;
+                // Don't trust the length for allocation.
+                let mut commands = Vec::with_capacity(len.min(256));
+                for _ in 0..len {
+                    commands.push(self.command_at(depth + 1)?);
+                }
+                MetaCommand::Scope(ty, commands.into()).into()
+            }
+            tag::CANVAS_CHANGED => Document::CanvasChanged {
+                from: self.canvas()?,
+                to: self.canvas()?,
+            }
+            .into(),
+            tag::RESOLUTION_CHANGED => Document::ResolutionChanged {
+                from: self.resolution()?,
+                to: self.resolution()?,
+            }
+            .into(),
+            tag::BLEND_CHANGED => Graph::BlendChanged {
+                target: self.any_id()?,
+                from: self.blend()?,
+                to: self.blend()?,
+            }
+            .into(),
+            tag::REPARENT => Graph::Reparent {
+                target: self.any_id()?,
+                new_parent: self.parent()?,
+                new_child_idx: self.len()?,
+                old_parent: self.parent()?,
+                old_child_idx: self.len()?,
+            }
+            .into(),
+            tag::LEAF_CREATED => Graph::LeafCreated {
+                target: self.leaf_id()?,
+                name: self.string()?,
+                ty: self.leaf_ty()?,
+                destination: self.parent()?,
+                child_idx: self.len()?,
+            }
+            .into(),
+            tag::LEAF_INNER_TRANSFORM_CHANGED => Graph::LeafInnerTransformChanged {
+                target: self.leaf_id()?,
+                old_transform: self.similarity()?,
+                new_transform: self.similarity()?,
+            }
+            .into(),
+            tag::LEAF_OUTER_TRANSFORM_CHANGED => Graph::LeafOuterTransformChanged {
+                target: self.leaf_id()?,
+                old_transform: self.matrix()?,
+                new_transform: self.matrix()?,
+            }
+            .into(),
+            tag::LEAF_TY_CHANGED => Graph::LeafTyChanged {
+                target: self.leaf_id()?,
+                old_ty: self.leaf_ty()?,
+                ty: self.leaf_ty()?,
+            }
+            .into(),
+            tag::NODE_CREATED => Graph::NodeCreated {
+                target: self.node_id()?,
+                name: self.string()?,
+                ty: self.node_ty()?,
+                destination: self.parent()?,
+                child_idx: self.len()?,
+            }
+            .into(),
+            tag::NODE_TY_CHANGED => Graph::NodeTyChanged {
+                target: self.node_id()?,
+                old_ty: self.node_ty()?,
+                ty: self.node_ty()?,
+            }
+            .into(),
+            tag::ANY_DELETED => Graph::AnyDeleted {
+                target: self.any_id()?,
+            }
+            .into(),
+            tag::PALETTE_ADDED => Palette::Added {
+                target: PaletteIndex(self.u64()?),
+                initial_color: self.color()?,
+            }
+            .into(),
+            tag::PALETTE_CHANGED => Palette::Changed {
+                target: PaletteIndex(self.u64()?),
+                from: self.color()?,
+                to: self.color()?,
+            }
+            .into(),
+            tag::COLLECTION_CREATED => Strokes::Created(self.collection()?).into(),
+            tag::STROKE_CREATED => {
+                let target = self.collection()?;
+                let stroke = self.u32()?;
+                Strokes::Stroke {
+                    target,
+                    command: StrokeCommand::Created {
+                        target: self.ids.strokes.get_or_insert(stroke.into()),
+                        brush: self.brush()?,
+                        points: self.points()?,
+                        clip: if self.bool()? {
+                            Some(self.points()?)
+                        } else {
+                            None
+                        },
+                        metadata: self.metadata()?,
+                    },
+                }
+                .into()
+            }
+            tag::STROKE_BRUSH_CHANGED => {
+                let target = self.collection()?;
+                let stroke = self.u32()?;
+                Strokes::Stroke {
+                    target,
+                    command: StrokeCommand::BrushChanged {
+                        target: self.ids.strokes.get_or_insert(stroke.into()),
+                        from: self.brush()?,
+                        to: self.brush()?,
+                    },
+                }
+                .into()
+            }
+            _ => return Err(invalid("unknown command")),
+        })
+    }
+}
+
+/// What to give a node once the history is replayed, as it isn't tracked by commands.
+pub struct Label {
+    pub name: String,
+    pub tag: ColorTag,
+    pub reference: bool,
+}
+
+/// A line of history read from a `hist` chunk, and the labels to give to its nodes once replayed.
+pub struct ReadHistory {
+    pub history: crate::queue::History,
+    pub labels: Vec<(AnyID, Label)>,
+    /// The IDs given to everything the history mentions.
+    pub ids: ProcessIds,
+}
+
+/// Decode the payload of a `hist` chunk, as written by [`write_chunk_into`]. `points` are the IDs read
+/// from the `PTLS` dictionary.
+pub fn read_chunk(
+    reader: impl Read,
+    points: ProcessLocalInterner<PointCollectionIDMarker>,
+) -> std::io::Result<ReadHistory> {
+    let (read, payload) = HIST_MIGRATIONS.read_payload(reader)?;
+    read(&payload, points)
+}
+/// Decode a version 0.0.0 `hist` payload, after the versioned header.
+fn read_v0(
+    reader: &[u8],
+    points: ProcessLocalInterner<PointCollectionIDMarker>,
+) -> std::io::Result<ReadHistory> {
+    read_payload(reader, points, false)
+}
+/// Decode a version 0.1.0 or 0.2.0 `hist` payload, after the versioned header. The latter only adds a
+/// command, which the former never holds.
+fn read_v1(
+    reader: &[u8],
+    points: ProcessLocalInterner<PointCollectionIDMarker>,
+) -> std::io::Result<ReadHistory> {
+    read_payload(reader, points, true)
+}
+/// Decode a `hist` payload, which only differ in whether strokes have `metadata`.
+fn read_payload(
+    reader: &[u8],
+    points: ProcessLocalInterner<PointCollectionIDMarker>,
+    metadata: bool,
+) -> std::io::Result<ReadHistory> {
+    let mut decoder = Decoder {
+        reader,
+        ids: ProcessIds {
+            points,
+            ..Default::default()
+        },
+        metadata,
+    };
+    let base_len = decoder.len()?;
+    let commands_len = decoder.len()?;
+    let present = decoder.len()?;
+    if present > commands_len {
+        return Err(invalid("present is past the end"));
+    }
+    // Don't trust the lengths for allocation, a short chunk will EOF long before then.
+    let mut base = Vec::with_capacity(base_len.min(1024));
+    for _ in 0..base_len {
+        base.push(decoder.command()?);
+    }
+    let mut commands = Vec::with_capacity(commands_len.min(1024));
+    for _ in 0..commands_len {
+        commands.push(decoder.command()?);
+    }
+    let labels_len = decoder.len()?;
+    let mut labels = Vec::with_capacity(labels_len.min(1024));
+    for _ in 0..labels_len {
+        let id = decoder.u32()?;
+        let id = *decoder
+            .ids
+            .graph
+            .get(&id)
+            .ok_or_else(|| invalid("label for an unknown node"))?;
+        let name = decoder.string()?;
+        let tag = decoder.u8()?;
+        labels.push((
+            id,
+            Label {
+                name,
+                tag: ColorTag::try_from(tag & !REFERENCE_BIT).unwrap_or_default(),
+                reference: tag & REFERENCE_BIT != 0,
+            },
+        ));
+    }
+
+    Ok(ReadHistory {
+        history: crate::queue::History {
+            base,
+            commands,
+            present,
+        },
+        labels,
+        ids: decoder.ids,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        color::Color,
+        queue::{state_reader::CommandQueueStateReader, DocumentCommandQueue},
+        state::graph::{LeafType, Location, NodeData},
+    };
+    /// Write the queue's history and read it back into a new queue.
+    fn roundtrip(queue: &DocumentCommandQueue, max: usize) -> DocumentCommandQueue {
+        use crate::io::riff::decode::BinaryChunkReader;
+        let (present, history) = queue.peek_history(max).unwrap();
+        let points = crate::io::id::FileLocalInterner::new();
+        let mut chunk = std::io::Cursor::new(Vec::new());
+        super::write_chunk_into(&history, present.graph(), points, &mut chunk).unwrap();
+        chunk.set_position(0);
+        let reader = BinaryChunkReader::new(chunk).unwrap();
+        let read = super::read_chunk(reader, crate::io::id::ProcessLocalInterner::new()).unwrap();
+        let queue = DocumentCommandQueue::from_history(Default::default(), read.history).unwrap();
+        queue.write_with(|writer| {
+            let mut graph = writer.graph();
+            for (id, label) in read.labels {
+                *graph.name_mut(id).unwrap() = label.name;
+                *graph.tag_mut(id).unwrap() = label.tag;
+                if let Some(reference) = graph.reference_mut(id) {
+                    *reference = label.reference;
+                }
+            }
+        });
+        queue
+    }
+    fn colors(reader: &impl CommandQueueStateReader) -> usize {
+        reader.palette().iter().count()
+    }
+    #[test]
+    fn roundtrip_undo() {
+        let queue = DocumentCommandQueue::new();
+        queue.write_with(|writer| {
+            writer
+                .graph()
+                .add_leaf(LeafType::Note, Location::IndexIntoRoot(0), "Note")
+                .unwrap();
+        });
+        for _ in 0..4 {
+            queue.write_with(|writer| {
+                writer.palette().insert(Color::BLACK);
+            });
+        }
+        queue.undo_n(1);
+
+        let read = roundtrip(&queue, 2);
+        // Two to undo, one to redo, the rest squashed into the start.
+        assert_eq!(read.history_depth(), (2, 1));
+        assert!(!read.is_dirty());
+        let state = read.peek_clone_state();
+        assert_eq!(colors(&state), 3);
+        let (id, _) = state.graph().iter_top_level().next().unwrap();
+        assert_eq!(state.graph().get(id).map(NodeData::name), Some("Note"));
+
+        read.undo_n(2);
+        assert_eq!(colors(&read.peek_clone_state()), 1);
+        read.redo_n(3);
+        assert_eq!(colors(&read.peek_clone_state()), 4);
+    }
+    #[test]
+    fn roundtrip_gradient() {
+        use crate::gradient::{Gradient, GradientShape};
+        let mut gradient = Gradient::new(
+            GradientShape::Radial,
+            [10.0, 20.0],
+            [30.0, 40.0],
+            crate::color::ColorOrPalette::BLACK,
+            crate::color::ColorOrPalette::from_palette_index(crate::color::PaletteIndex(7)),
+        );
+        gradient.insert_stop(0.5);
+        let ty = LeafType::Gradient {
+            blend: crate::blend::Blend::default(),
+            gradient,
+        };
+        let queue = DocumentCommandQueue::new();
+        queue.write_with(|writer| {
+            writer
+                .graph()
+                .add_leaf(ty.clone(), Location::IndexIntoRoot(0), "Gradient")
+                .unwrap();
+        });
+
+        let state = roundtrip(&queue, 1).peek_clone_state();
+        let (id, _) = state.graph().iter_top_level().next().unwrap();
+        assert_eq!(state.graph().get(id).and_then(NodeData::leaf), Some(&ty));
+    }
+    #[test]
+    fn roundtrip_canvas() {
+        use crate::state::document::Canvas;
+        let canvas = Canvas {
+            origin: [-10.0, 20.0],
+            size: [300.0, 200.0],
+        };
+        let queue = DocumentCommandQueue::new();
+        queue.write_with(|writer| {
+            let mut document = writer.document();
+            assert!(document
+                .set_canvas(Canvas {
+                    size: [0.0, 200.0],
+                    ..canvas
+                })
+                .is_err());
+            document.set_canvas(canvas).unwrap();
+        });
+        let canvas_of =
+            |queue: &DocumentCommandQueue| queue.peek_clone_state().document().viewport.canvas();
+        // Squashed into the base, or as a command of its own.
+        assert_eq!(canvas_of(&roundtrip(&queue, 0)), canvas);
+        let read = roundtrip(&queue, 1);
+        assert_eq!(canvas_of(&read), canvas);
+        read.undo_n(1);
+        assert_eq!(canvas_of(&read), Canvas::default());
+        read.redo_n(1);
+        assert_eq!(canvas_of(&read), canvas);
+    }
+    #[test]
+    fn roundtrip_resolution() {
+        use crate::units::Resolution;
+        let resolution = Resolution::Dpcm(120.0);
+        let queue = DocumentCommandQueue::new();
+        queue.write_with(|writer| {
+            let mut document = writer.document();
+            assert!(document.set_resolution(Resolution::Dpi(0.0)).is_err());
+            document.set_resolution(resolution).unwrap();
+        });
+        let resolution_of =
+            |queue: &DocumentCommandQueue| queue.peek_clone_state().document().viewport.resolution;
+        assert_eq!(resolution_of(&roundtrip(&queue, 0)), resolution);
+        let read = roundtrip(&queue, 1);
+        assert_eq!(resolution_of(&read), resolution);
+        read.undo_n(1);
+        assert_eq!(
+            resolution_of(&read),
+            crate::state::document::Viewport::default().resolution
+        );
+    }
+    #[test]
+    fn roundtrip_brush() {
+        use crate::state::{DynamicsInput, Response, StrokeBrushSettings};
+        let roundtrip = |brush: &StrokeBrushSettings| {
+            let mut encoder = super::Encoder {
+                buf: Vec::new(),
+                ids: super::FileIds::default(),
+            };
+            encoder.brush(brush);
+            let mut decoder = super::Decoder {
+                reader: encoder.buf.as_slice(),
+                ids: super::ProcessIds::default(),
+                metadata: true,
+            };
+            let read = decoder.brush().unwrap();
+            // All of it was read.
+            assert!(decoder.reader.is_empty());
+            read
+        };
+        let mut brush = StrokeBrushSettings {
+            brush: crate::brush::UniqueID([7; 32]),
+            color_modulate: crate::color::ColorOrPalette::BLACK,
+            size_mul: crate::util::FiniteF32::new(12.0).unwrap(),
+            is_eraser: false,
+            is_smudge: false,
+            orientation: crate::state::StampOrientation::Random,
+            scatter: crate::state::Scatter::default(),
+            dual: None,
+            taper: crate::state::Taper::default(),
+            dynamics: crate::state::Dynamics::default(),
+            spacing_px: crate::util::FiniteF32::new(0.5).unwrap(),
+        };
+        assert_eq!(roundtrip(&brush), brush);
+
+        brush.is_eraser = true;
+        brush.dynamics.size.gamma = 2.0;
+        brush.dynamics.flow = Response {
+            input: DynamicsInput::Speed,
+            min: 0.25,
+            gamma: 0.5,
+        };
+        brush.dynamics.opacity.input = DynamicsInput::Wheel;
+        assert_eq!(roundtrip(&brush), brush);
+
+        brush.is_eraser = false;
+        brush.is_smudge = true;
+        assert_eq!(roundtrip(&brush), brush);
+    }
+    #[test]
+    fn nested_scopes() {
+        let decode = |bytes: &[u8]| {
+            super::Decoder {
+                reader: bytes,
+                ids: super::ProcessIds::default(),
+                metadata: true,
+            }
+            .command()
+        };
+        // A scope of one scope, of an empty scope.
+        let mut shallow = [0, 0, 1, 0, 0, 0].repeat(2);
+        shallow.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        assert!(decode(&shallow).is_ok());
+        // Would overflow the stack, were it read.
+        let deep = [0, 0, 1, 0, 0, 0].repeat(1_000_000);
+        let err = decode(&deep).unwrap_err();
+        assert!(err.to_string().contains("scopes nested too deeply"));
+    }
+    #[test]
+    fn roundtrip_metadata() {
+        use crate::state::stroke_collection::StrokeMetadata;
+        let metadata = StrokeMetadata {
+            created: Some(1_700_000_000_000),
+            author: None,
+            device: Some(42),
+        };
+        let mut encoder = super::Encoder {
+            buf: Vec::new(),
+            ids: super::FileIds::default(),
+        };
+        encoder.metadata(&metadata);
+        let mut decoder = super::Decoder {
+            reader: encoder.buf.as_slice(),
+            ids: super::ProcessIds::default(),
+            metadata: true,
+        };
+        assert_eq!(decoder.metadata().unwrap(), metadata);
+        assert!(decoder.reader.is_empty());
+        // Older chunks have none to read.
+        let mut decoder = super::Decoder {
+            reader: encoder.buf.as_slice(),
+            ids: super::ProcessIds::default(),
+            metadata: false,
+        };
+        assert_eq!(decoder.metadata().unwrap(), StrokeMetadata::default());
+        assert_eq!(decoder.reader.len(), encoder.buf.len());
+    }
+    #[test]
+    fn roundtrip_labels() {
+        use crate::state::graph::ColorTag;
+        let queue = DocumentCommandQueue::new();
+        queue.write_with(|writer| {
+            let mut graph = writer.graph();
+            let sketch = graph
+                .add_leaf(LeafType::Note, Location::IndexIntoRoot(0), "Sketch")
+                .unwrap();
+            *graph.tag_mut(sketch.into()).unwrap() = ColorTag::Gray;
+            *graph.reference_mut(sketch.into()).unwrap() = true;
+            graph
+                .add_leaf(LeafType::Note, Location::IndexIntoRoot(0), "Ink")
+                .unwrap();
+        });
+
+        let state = roundtrip(&queue, 1).peek_clone_state();
+        let labels: Vec<_> = state
+            .graph()
+            .iter_top_level()
+            .map(|(_, data)| (data.name().to_owned(), data.tag(), data.is_reference()))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                ("Ink".to_owned(), ColorTag::None, false),
+                ("Sketch".to_owned(), ColorTag::Gray, true),
+            ]
+        );
+    }
+}
@@ -56,6 +56,14 @@ pub struct FileLocalInterner<T: std::any::Any> {
     /// latches to None on overflow.
     next_id: Option<u32>,
 }
+impl<T: std::any::Any> Clone for FileLocalInterner<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            next_id: self.next_id,
+        }
+    }
+}
 /// Increment in place, short circuiting to None if overflow occurs.
 /// returns the value *before* increment.
 fn checked_postfix_increment(val: &mut Option<u32>) -> Option<u32> {
@@ -95,6 +103,20 @@ impl<T: std::any::Any> FileLocalInterner<T> {
             }
         }
     }
+    /// Continue numbering where a file left off, from the IDs its entries were read as.
+    #[must_use]
+    pub fn from_process(process: &ProcessLocalInterner<T>) -> Self {
+        let map: hashbrown::HashMap<_, _> = process
+            .iter()
+            .map(|(&file_id, &fuzz_id)| (fuzz_id, file_id))
+            .collect();
+        let next_id = map
+            .values()
+            .map(|id| id.id)
+            .max()
+            .map_or(Some(0), |max| max.checked_add(1));
+        Self { map, next_id }
+    }
     /// Get a file-local id without creating it if it's not present.
     #[must_use]
     pub fn get(&self, id: FuzzID<T>) -> Option<FileLocalID<T>> {
@@ -124,6 +146,13 @@ impl<T: std::any::Any> FileLocalInterner<T> {
 pub struct ProcessLocalInterner<T: std::any::Any> {
     map: hashbrown::HashMap<FileLocalID<T>, FuzzID<T>>,
 }
+impl<T: std::any::Any> Clone for ProcessLocalInterner<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
 impl<T: std::any::Any> Default for ProcessLocalInterner<T> {
     fn default() -> Self {
         Self {
@@ -178,6 +207,10 @@ impl<T: std::any::Any> ProcessLocalInterner<T> {
     pub fn insert(&mut self, id: FileLocalID<T>) {
         self.get_or_insert(id);
     }
+    /// Map an id to a `FuzzID` allocated elsewhere, such as by the repository its data was inserted into.
+    pub fn insert_as(&mut self, id: FileLocalID<T>, fuzz_id: FuzzID<T>) {
+        self.map.insert(id, fuzz_id);
+    }
     pub fn iter(&self) -> impl Iterator<Item = (&FileLocalID<T>, &FuzzID<T>)> {
         self.map.iter()
     }
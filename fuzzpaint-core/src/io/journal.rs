@@ -0,0 +1,652 @@
+//! # Journal
+//!
+//! An append-only record of every change made to a document since it was last saved, written as each change
+//! happens so that work lost to a crash can be recovered by replaying it on top of the save.
+//!
+//! Commands are encoded as in the [history](super::history) chunk, continuing from the IDs the save gave to
+//! everything its history mentions - so a journal only replays onto the very save it was started from. The
+//! points of a new stroke are written just before the first command to mention them.
+//!
+//! Every record is prefixed by its length and written in one go, so a crash mid-write leaves at most the last
+//! one cut short, which ends the journal.
+
+use super::history::{self, FileIds, ProcessIds};
+use crate::commands::{Command, DoUndo};
+use std::io::{Error as IOError, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: [u8; 4] = *b"FZJL";
+const JOURNAL_WRITE_VERSION: super::Version = super::Version(0, 2, 0);
+/// Journals from before the base's modification time was recorded, which are otherwise the same.
+const JOURNAL_V1: super::Version = super::Version(0, 1, 0);
+/// Journals from before strokes had metadata, which are otherwise as [`JOURNAL_V1`].
+const JOURNAL_V0: super::Version = super::Version(0, 0, 0);
+
+/// Tags of each record.
+mod tag {
+    /// A point collection, mentioned by commands after it.
+    pub const POINTS: u8 = 0;
+    /// A new command, or one redone.
+    pub const DO: u8 = 1;
+    /// The present command was undone.
+    pub const UNDO: u8 = 2;
+}
+
+fn invalid(what: &str) -> IOError {
+    IOError::other(anyhow::anyhow!("invalid journal: {what}"))
+}
+
+/// The saved file a journal records changes on top of.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Base {
+    pub path: PathBuf,
+    /// Length of the file as saved, to tell if it has been written over since.
+    pub len: u64,
+    /// When the file was last modified as saved, for the same. None if the platform doesn't say, or the
+    /// journal is from before it was recorded.
+    pub modified: Option<std::time::SystemTime>,
+}
+impl Base {
+    /// The file at `path` as it is now.
+    pub fn of(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let metadata = std::fs::metadata(&path)?;
+        Ok(Self {
+            path,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+    /// Whether the file is still as it was saved, by its length and modification time.
+    pub fn is_unchanged(&self) -> std::io::Result<bool> {
+        let now = Self::of(&self.path)?;
+        Ok(now.len == self.len && (self.modified.is_none() || now.modified == self.modified))
+    }
+}
+/// Nanoseconds since the epoch, or zero if unknown.
+fn encode_time(time: Option<std::time::SystemTime>) -> u64 {
+    time.and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .and_then(|since| u64::try_from(since.as_nanos()).ok())
+        .unwrap_or(0)
+}
+fn decode_time(nanos: u64) -> Option<std::time::SystemTime> {
+    (nanos != 0).then(|| std::time::UNIX_EPOCH + std::time::Duration::from_nanos(nanos))
+}
+
+/// A journal being written, locked for as long as it's open so that other instances don't mistake it for
+/// one left behind by a crash.
+pub struct Journal {
+    file: std::fs::File,
+    path: PathBuf,
+    encoder: history::Encoder,
+}
+impl Journal {
+    /// Start a journal at `path`, replacing any there. `ids` are those the base was written or read with, or
+    /// the default if there is no base and changes are to be replayed onto an empty document.
+    pub fn create(
+        path: impl Into<PathBuf>,
+        base: Option<&Base>,
+        ids: FileIds,
+    ) -> std::io::Result<Self> {
+        let path = path.into();
+        // Readable, see `Self::reader`.
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        // Not every filesystem supports locks. Nothing to be done, carry on without.
+        if let Err(err) = file.try_lock() {
+            tracing::warn!(path = %path.display(), "failed to lock journal: {err}");
+        }
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(bytemuck::bytes_of(&JOURNAL_WRITE_VERSION));
+        header.push(0);
+        match base {
+            None => header.push(0),
+            Some(base) => {
+                header.push(1);
+                let base_path = base
+                    .path
+                    .to_str()
+                    .ok_or_else(|| invalid("base path is not UTF-8"))?;
+                let len = u32::try_from(base_path.len()).map_err(|_| invalid("too long"))?;
+                header.extend_from_slice(&len.to_le_bytes());
+                header.extend_from_slice(base_path.as_bytes());
+                header.extend_from_slice(&base.len.to_le_bytes());
+                header.extend_from_slice(&encode_time(base.modified).to_le_bytes());
+            }
+        }
+        let mut journal = Self {
+            file,
+            path,
+            encoder: history::Encoder {
+                buf: Vec::new(),
+                ids,
+            },
+        };
+        journal.file.write_all(&header)?;
+        Ok(journal)
+    }
+    /// Start a journal at `path` carrying on from this one as it is now, such as to write changes that may
+    /// never be appended to this one. It replays after this one, with the IDs this one was replayed with, see
+    /// [`JournalReader::replay_continued`]. Both can be written on independently.
+    pub fn fork_at(&self, path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        Self::create(path, None, self.encoder.ids.clone())
+    }
+    /// Read the journal back as it is now, such as to replay it while it's still being written. Read through
+    /// the journal's own file, as some platforms refuse other handles while it's locked.
+    pub fn reader(&self) -> std::io::Result<JournalReader<std::io::BufReader<&std::fs::File>>> {
+        let mut file = &self.file;
+        file.rewind()?;
+        JournalReader::new(std::io::BufReader::new(file))
+    }
+    /// Where the journal is being written.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Record a change to the document, such as those seen by a
+    /// [listener](crate::queue::DocumentCommandListener). The points of new strokes are read from `points`.
+    pub fn append(
+        &mut self,
+        change: DoUndo<'_, Command>,
+        points: &crate::repositories::points::Points,
+    ) -> std::io::Result<()> {
+        let command = match change {
+            DoUndo::Do(command) => command,
+            DoUndo::Undo(_) => return self.record(tag::UNDO, &[]),
+        };
+        let mut mentioned = Vec::new();
+        history::command_points(command, &mut mentioned);
+        for id in mentioned {
+            if self.encoder.ids.points.get(id).is_some() {
+                continue;
+            }
+            let read = points
+                .try_get(id)
+                .map_err(|_| invalid("points not found"))?;
+            let stroke = read.get();
+            let file_id = self
+                .encoder
+                .ids
+                .points
+                .get_or_insert(id)
+                .map_err(|_| invalid("too many collections"))?;
+            let len = u32::try_from(stroke.elements().len()).map_err(|_| invalid("too long"))?;
+            let mut payload = Vec::with_capacity(9 + stroke.bytes().len());
+            payload.extend_from_slice(&file_id.id.to_le_bytes());
+            payload.push(stroke.archetype().bits());
+            payload.extend_from_slice(&len.to_le_bytes());
+            for element in stroke.elements() {
+                payload.extend_from_slice(&element.to_le_bytes());
+            }
+            self.record(tag::POINTS, &payload)?;
+        }
+        self.encoder.buf.clear();
+        self.encoder.command(command)?;
+        let payload = std::mem::take(&mut self.encoder.buf);
+        let result = self.record(tag::DO, &payload);
+        // Keep the allocation for next time.
+        self.encoder.buf = payload;
+        result
+    }
+    fn record(&mut self, tag: u8, payload: &[u8]) -> std::io::Result<()> {
+        let len = u32::try_from(payload.len()).map_err(|_| invalid("too long"))?;
+        let mut record = Vec::with_capacity(payload.len() + 5);
+        record.push(tag);
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(payload);
+        // After wherever a reader left off.
+        self.file.seek(std::io::SeekFrom::End(0))?;
+        // All at once, see the module docs. Unbuffered, so that it reaches the OS before any crash can.
+        self.file.write_all(&record)
+    }
+    /// Close and delete the journal, once there's nothing left for it to recover.
+    pub fn discard(self) -> std::io::Result<()> {
+        drop(self.file);
+        std::fs::remove_file(&self.path)
+    }
+}
+
+/// Whether the journal at `path` is still open, by this or another instance.
+pub fn in_use(path: &Path) -> std::io::Result<bool> {
+    let file = std::fs::File::open(path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(false),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(true),
+        Err(std::fs::TryLockError::Error(err)) => Err(err),
+    }
+}
+
+/// A journal opened to be replayed.
+pub struct JournalReader<R> {
+    reader: R,
+    base: Option<Base>,
+    /// Whether created strokes are followed by their metadata, see [`history`].
+    metadata: bool,
+}
+impl JournalReader<std::io::BufReader<std::fs::File>> {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        Self::new(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+impl<R: Read> JournalReader<R> {
+    /// Read the header of the journal.
+    pub fn new(mut reader: R) -> std::io::Result<Self> {
+        let mut header = [0; 9];
+        reader.read_exact(&mut header)?;
+        if header[0..4] != MAGIC {
+            return Err(invalid("bad magic"));
+        }
+        let version = &header[4..7];
+        let (metadata, modified) = if version == bytemuck::bytes_of(&JOURNAL_WRITE_VERSION) {
+            (true, true)
+        } else if version == bytemuck::bytes_of(&JOURNAL_V1) {
+            (true, false)
+        } else if version == bytemuck::bytes_of(&JOURNAL_V0) {
+            (false, false)
+        } else {
+            return Err(invalid("unsupported version"));
+        };
+        let base = match header[8] {
+            0 => None,
+            1 => {
+                let mut len = [0; 4];
+                reader.read_exact(&mut len)?;
+                let len = u32::from_le_bytes(len);
+                let mut path = Vec::new();
+                reader.by_ref().take(len.into()).read_to_end(&mut path)?;
+                if path.len() != len as usize {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+                let path = String::from_utf8(path).map_err(|_| invalid("path is not UTF-8"))?;
+                let mut len = [0; 8];
+                reader.read_exact(&mut len)?;
+                let mut time = [0; 8];
+                if modified {
+                    reader.read_exact(&mut time)?;
+                }
+                Some(Base {
+                    path: path.into(),
+                    len: u64::from_le_bytes(len),
+                    modified: decode_time(u64::from_le_bytes(time)),
+                })
+            }
+            _ => return Err(invalid("bad flag")),
+        };
+        Ok(Self {
+            reader,
+            base,
+            metadata,
+        })
+    }
+    /// The save the journal continues from, or `None` if it continues from an empty document.
+    #[must_use]
+    pub fn base(&self) -> Option<&Base> {
+        self.base.as_ref()
+    }
+    /// The next whole record, or `None` at the end or at a record cut short.
+    fn next_record(&mut self) -> std::io::Result<Option<(u8, Vec<u8>)>> {
+        let mut head = [0; 5];
+        match self.reader.read_exact(&mut head) {
+            Ok(()) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let len = u32::from_le_bytes([head[1], head[2], head[3], head[4]]);
+        let mut payload = Vec::new();
+        // Don't trust the length for allocation, a short journal will EOF long before then.
+        self.reader
+            .by_ref()
+            .take(len.into())
+            .read_to_end(&mut payload)?;
+        if payload.len() != len as usize {
+            return Ok(None);
+        }
+        Ok(Some((head[0], payload)))
+    }
+    /// Count the changes in the journal without replaying them, reading it to the end.
+    pub fn changes(mut self) -> std::io::Result<usize> {
+        let mut changes = 0;
+        while let Some((tag, _)) = self.next_record()? {
+            if tag != tag::POINTS {
+                changes += 1;
+            }
+        }
+        Ok(changes)
+    }
+    /// Replay the journal onto `queue`, which must be the [base](Self::base) as it was read along with
+    /// `ids`, or a new document if there is none. New points are inserted into `points`.
+    ///
+    /// Returns how many changes were replayed. A change that fails to apply ends the replay early, as every
+    /// one after it may depend on it.
+    pub fn replay(
+        self,
+        queue: &crate::queue::DocumentCommandQueue,
+        mut ids: ProcessIds,
+        points: &crate::repositories::points::Points,
+    ) -> std::io::Result<usize> {
+        self.replay_continued(queue, &mut ids, points)
+    }
+    /// As [`Self::replay`], leaving `ids` as they are at the end of the journal so that a journal
+    /// [forked](Journal::fork_at) from this one may be replayed after it.
+    pub fn replay_continued(
+        mut self,
+        queue: &crate::queue::DocumentCommandQueue,
+        ids: &mut ProcessIds,
+        points: &crate::repositories::points::Points,
+    ) -> std::io::Result<usize> {
+        let mut decoder = history::Decoder {
+            reader: std::io::Cursor::new(Vec::new()),
+            ids: std::mem::take(ids),
+            metadata: self.metadata,
+        };
+        let mut replayed = 0;
+        while let Some((tag, payload)) = self.next_record()? {
+            decoder.reader = std::io::Cursor::new(payload);
+            match tag {
+                tag::POINTS => {
+                    let file_id = decoder.u32()?;
+                    let archetype = crate::stroke::Archetype::from_bits(decoder.u8()?)
+                        .ok_or_else(|| invalid("unknown archetype"))?;
+                    let len = decoder.u32()? as usize;
+                    let bytes = &decoder.reader.get_ref()[9..];
+                    if bytes.len() != len * 4 {
+                        return Err(invalid("points cut short"));
+                    }
+                    let elements: Vec<u32> = bytes
+                        .chunks_exact(4)
+                        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                        .collect();
+                    let stroke = crate::stroke::StrokeSlice::new(&elements, archetype)
+                        .ok_or_else(|| invalid("points don't fit their archetype"))?;
+                    let id = points
+                        .insert(stroke)
+                        .ok_or_else(|| invalid("points too long"))?;
+                    decoder.ids.points.insert_as(file_id.into(), id);
+                }
+                tag::DO => {
+                    let command = decoder.command()?;
+                    if let Err(err) = queue.replay(command) {
+                        tracing::warn!(replayed, "journal stopped replaying early: {err}");
+                        break;
+                    }
+                    replayed += 1;
+                }
+                tag::UNDO => {
+                    queue.undo_n(1);
+                    replayed += 1;
+                }
+                _ => return Err(invalid("unknown record")),
+            }
+        }
+        *ids = decoder.ids;
+        Ok(replayed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        color::Color,
+        queue::{state_reader::CommandQueueStateReader, DocumentCommandQueue},
+        state::graph::{LeafType, Location},
+    };
+    fn colors(reader: &impl CommandQueueStateReader) -> usize {
+        reader.palette().iter().count()
+    }
+    #[test]
+    fn replay_onto_save() {
+        let dir = crate::util::scratch_dir("journal");
+        let path = dir.join("doc.fzj");
+        let points = crate::repositories::points::Points::default();
+
+        let queue = DocumentCommandQueue::new();
+        let (leaf, collection) = queue.write_with(|writer| {
+            let collection = writer.stroke_collections().insert();
+            let leaf = writer
+                .graph()
+                .add_leaf(
+                    LeafType::StrokeLayer {
+                        blend: crate::blend::Blend::default(),
+                        collection,
+                        inner_transform: crate::state::transform::Similarity::default(),
+                        outer_transform: crate::state::transform::Matrix::default(),
+                    },
+                    Location::IndexIntoRoot(0),
+                    "Strokes",
+                )
+                .unwrap();
+            (leaf, collection)
+        });
+        // "Save" - just the history chunk, which is all the IDs come from.
+        let (saved, history) = queue.peek_history(usize::MAX).unwrap();
+        let mut chunk = std::io::Cursor::new(Vec::new());
+        let ids = crate::io::history::write_chunk_into(
+            &history,
+            saved.graph(),
+            crate::io::id::FileLocalInterner::new(),
+            &mut chunk,
+        )
+        .unwrap();
+        let mut journal = super::Journal::create(&path, None, ids).unwrap();
+        assert!(super::in_use(&path).unwrap());
+        let mut listener = queue.listen_from_now();
+        // As each change happens, else the listener would skip over the undone one.
+        let mut record = |journal: &mut super::Journal| {
+            for change in listener.forward_clone_state().unwrap().changes() {
+                journal.append(change, &points).unwrap();
+            }
+        };
+
+        // Strokes into the saved collection, a change to the saved leaf, and an undo.
+        let stroke = points
+            .insert(
+                crate::stroke::StrokeSlice::new(
+                    &[1.0f32, 2.0, 3.0, 4.0].map(f32::to_bits),
+                    crate::stroke::Archetype::POSITION,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        queue.write_with(|writer| {
+            writer
+                .stroke_collections()
+                .get_mut(collection)
+                .unwrap()
+                .push_back(
+                    crate::state::StrokeBrushSettings {
+                        is_eraser: false,
+                        is_smudge: false,
+                        brush: crate::brush::UniqueID([0; 32]),
+                        color_modulate: crate::color::ColorOrPalette::BLACK,
+                        size_mul: crate::util::FiniteF32::new(10.0).unwrap(),
+                        orientation: crate::state::StampOrientation::Random,
+                        scatter: crate::state::Scatter::default(),
+                        dual: None,
+                        taper: crate::state::Taper::default(),
+                        dynamics: crate::state::Dynamics::default(),
+                        spacing_px: crate::util::FiniteF32::new(0.5).unwrap(),
+                    },
+                    stroke,
+                    None,
+                );
+        });
+        record(&mut journal);
+        for _ in 0..3 {
+            queue.write_with(|writer| {
+                writer.palette().insert(Color::BLACK);
+            });
+            record(&mut journal);
+        }
+        queue.undo_n(1);
+        record(&mut journal);
+        queue.write_with(|writer| {
+            writer
+                .graph()
+                .change_blend(
+                    leaf.into(),
+                    crate::blend::Blend {
+                        opacity: 0.5,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        });
+        record(&mut journal);
+        drop(journal);
+        // A crash mid-write.
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, &[super::tag::DO, 200, 0]).unwrap();
+        drop(file);
+        assert!(!super::in_use(&path).unwrap());
+
+        // Reopened, as it was saved.
+        chunk.set_position(0);
+        let reader = crate::io::riff::decode::BinaryChunkReader::new(chunk).unwrap();
+        let read =
+            crate::io::history::read_chunk(reader, crate::io::id::ProcessLocalInterner::new())
+                .unwrap();
+        let recovered =
+            DocumentCommandQueue::from_history(Default::default(), read.history).unwrap();
+        assert_eq!(
+            super::JournalReader::open(&path)
+                .unwrap()
+                .changes()
+                .unwrap(),
+            6
+        );
+        let reader = super::JournalReader::open(&path).unwrap();
+        assert_eq!(reader.base(), None);
+        assert_eq!(reader.replay(&recovered, read.ids, &points).unwrap(), 6);
+
+        let (present, recovered) = (queue.peek_clone_state(), recovered.peek_clone_state());
+        assert_eq!(colors(&recovered), 2);
+        let (id, data) = recovered.graph().iter_top_level().next().unwrap();
+        assert_eq!(data.blend().map(|blend| blend.opacity), Some(0.5));
+        let Some(LeafType::StrokeLayer { collection, .. }) = data.leaf() else {
+            panic!("expected a stroke layer");
+        };
+        let strokes = &recovered
+            .stroke_collections()
+            .get(*collection)
+            .unwrap()
+            .strokes;
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(
+            points
+                .try_get(strokes[0].point_collection)
+                .unwrap()
+                .get()
+                .elements(),
+            points.try_get(stroke).unwrap().get().elements(),
+        );
+        // Same shape, under new IDs.
+        assert_ne!(id, crate::state::graph::AnyID::from(leaf));
+        assert_eq!(
+            present.graph().iter().count(),
+            recovered.graph().iter().count()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn forked_replays_after() {
+        let dir = crate::util::scratch_dir("fork");
+        let (first, second) = (dir.join("first.fzj"), dir.join("second.fzj"));
+        let points = crate::repositories::points::Points::default();
+
+        let queue = DocumentCommandQueue::new();
+        let mut listener = queue.listen_from_now();
+        let mut record = |journal: &mut super::Journal| {
+            for change in listener.forward_clone_state().unwrap().changes() {
+                journal.append(change, &points).unwrap();
+            }
+        };
+        let mut journal =
+            super::Journal::create(&first, None, crate::io::history::FileIds::default()).unwrap();
+        let leaf = queue.write_with(|writer| {
+            let collection = writer.stroke_collections().insert();
+            writer
+                .graph()
+                .add_leaf(
+                    LeafType::StrokeLayer {
+                        blend: crate::blend::Blend::default(),
+                        collection,
+                        inner_transform: crate::state::transform::Similarity::default(),
+                        outer_transform: crate::state::transform::Matrix::default(),
+                    },
+                    Location::IndexIntoRoot(0),
+                    "Strokes",
+                )
+                .unwrap()
+        });
+        record(&mut journal);
+        // Refers to the leaf by the ID the first gave it.
+        let mut fork = journal.fork_at(&second).unwrap();
+        queue.write_with(|writer| {
+            writer
+                .graph()
+                .change_blend(
+                    leaf.into(),
+                    crate::blend::Blend {
+                        opacity: 0.5,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        });
+        record(&mut fork);
+
+        // Read back while still open, as they're appended to.
+        let replayed = DocumentCommandQueue::new();
+        let mut ids = crate::io::history::ProcessIds::default();
+        for journal in [&journal, &fork] {
+            let reader = journal.reader().unwrap();
+            assert_eq!(
+                reader
+                    .replay_continued(&replayed, &mut ids, &points)
+                    .unwrap(),
+                1
+            );
+        }
+        let replayed = replayed.peek_clone_state();
+        let (_, data) = replayed.graph().iter_top_level().next().unwrap();
+        assert_eq!(data.blend().map(|blend| blend.opacity), Some(0.5));
+
+        drop((journal, fork));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn base_written_over() {
+        let dir = crate::util::scratch_dir("base");
+        let saved = dir.join("doc.fzp");
+        std::fs::write(&saved, b"saved").unwrap();
+
+        let base = super::Base::of(&saved).unwrap();
+        assert!(base.is_unchanged().unwrap());
+        // Survives the journal's header.
+        let path = dir.join("doc.fzj");
+        let journal =
+            super::Journal::create(&path, Some(&base), crate::io::history::FileIds::default())
+                .unwrap();
+        drop(journal);
+        let reader = super::JournalReader::open(&path).unwrap();
+        assert_eq!(reader.base(), Some(&base));
+
+        // The same length, written over later.
+        std::fs::write(&saved, b"other").unwrap();
+        let file = std::fs::File::options().write(true).open(&saved).unwrap();
+        file.set_modified(base.modified.unwrap() + std::time::Duration::from_secs(1))
+            .unwrap();
+        drop(file);
+        assert!(!base.is_unchanged().unwrap());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+//! # Migrations
+//!
+//! Each versioned chunk declares its [`Migrations`] - which versions of its payload it can read, and how.
+//! When a chunk's layout changes, its write version is bumped, and the reader of the old layout is either
+//! kept alongside the new one or replaced by an [`Upgrade`] which rewrites old payloads into a newer layout.
+//! Either way, files written by older versions continue to load, which is checked against fixtures of
+//! each version in `test-data/fixtures`.
+
+use super::{riff::ChunkID, Version};
+use std::io::Read;
+use std::ops::RangeInclusive;
+
+/// Reads payloads written as any of a range of versions. `F` is the signature of the read function, which
+/// varies between chunks with what else they need to be read.
+pub struct Reader<F> {
+    pub versions: RangeInclusive<Version>,
+    pub read: F,
+}
+
+/// Rewrites payloads written as any of a range of versions into the layout of a later version.
+pub struct Upgrade {
+    pub from: RangeInclusive<Version>,
+    /// Must be later than every version of `from`.
+    pub to: Version,
+    pub upgrade: fn(Vec<u8>) -> std::io::Result<Vec<u8>>,
+}
+
+/// The versions of a chunk that can be read.
+pub struct Migrations<F: 'static> {
+    /// The chunk, for errors.
+    pub chunk: ChunkID,
+    /// The version written, which must have a reader.
+    pub current: Version,
+    pub readers: &'static [Reader<F>],
+    pub upgrades: &'static [Upgrade],
+}
+impl<F: Copy> Migrations<F> {
+    /// Read a chunk's versioned header and the payload after it, upgrading the payload until there's a reader
+    /// for its version. Returns that reader, and the payload for it to read.
+    pub fn read_payload(&self, mut chunk: impl Read) -> std::io::Result<(F, Vec<u8>)> {
+        let mut header = [0; 4];
+        chunk.read_exact(&mut header)?;
+        let mut payload = Vec::new();
+        chunk.read_to_end(&mut payload)?;
+        self.migrate(Version(header[0], header[1], header[2]), payload)
+    }
+    /// Upgrade a payload written as `version` until there's a reader for it, returning that reader and the
+    /// upgraded payload.
+    pub fn migrate(
+        &self,
+        mut version: Version,
+        mut payload: Vec<u8>,
+    ) -> std::io::Result<(F, Vec<u8>)> {
+        loop {
+            if let Some(reader) = self.reader(version) {
+                return Ok((reader, payload));
+            }
+            let upgrade = self
+                .upgrades
+                .iter()
+                .find(|upgrade| upgrade.from.contains(&version))
+                .ok_or_else(|| unsupported(self.chunk, version))?;
+            // Only ever forward, so that this ends.
+            if upgrade.to <= version {
+                return Err(std::io::Error::other(anyhow::anyhow!(
+                    "\"{}\" upgrade from {version} goes backwards, to {}",
+                    self.chunk,
+                    upgrade.to
+                )));
+            }
+            payload = (upgrade.upgrade)(payload)?;
+            version = upgrade.to;
+        }
+    }
+    /// The reader of payloads written as `version`, without upgrading.
+    #[must_use]
+    pub fn reader(&self, version: Version) -> Option<F> {
+        self.readers
+            .iter()
+            .find(|reader| reader.versions.contains(&version))
+            .map(|reader| reader.read)
+    }
+}
+
+/// The error for a chunk written as a version that can't be read, such as one from a newer release.
+#[must_use]
+pub fn unsupported(chunk: ChunkID, version: Version) -> std::io::Error {
+    std::io::Error::other(anyhow::anyhow!("unsupported \"{chunk}\" version {version}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Migrations, Reader, Upgrade};
+    use crate::io::{riff::ChunkID, Version};
+
+    /// A chunk that was a `u8`, then became a `u16`, then a `u32`.
+    type Read = fn(&[u8]) -> std::io::Result<u32>;
+    fn read_u16(payload: &[u8]) -> std::io::Result<u32> {
+        Ok(u16::from_le_bytes(payload.try_into().map_err(std::io::Error::other)?).into())
+    }
+    fn read_u32(payload: &[u8]) -> std::io::Result<u32> {
+        Ok(u32::from_le_bytes(
+            payload.try_into().map_err(std::io::Error::other)?,
+        ))
+    }
+    fn widen_u8(payload: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        Ok(vec![payload[0], 0])
+    }
+    const MIGRATIONS: Migrations<Read> = Migrations {
+        chunk: ChunkID(*b"test"),
+        current: Version(0, 2, 0),
+        readers: &[
+            Reader {
+                versions: Version(0, 1, 0)..=Version(0, 1, 5),
+                read: read_u16,
+            },
+            Reader {
+                versions: Version(0, 2, 0)..=Version(0, 2, 0),
+                read: read_u32,
+            },
+        ],
+        upgrades: &[Upgrade {
+            from: Version(0, 0, 0)..=Version(0, 0, 9),
+            to: Version(0, 1, 0),
+            upgrade: widen_u8,
+        }],
+    };
+
+    #[test]
+    fn migrate() {
+        let read = |version, payload: &[u8]| -> std::io::Result<u32> {
+            let (read, payload) = MIGRATIONS.migrate(version, payload.to_vec())?;
+            read(&payload)
+        };
+        assert!(MIGRATIONS.reader(MIGRATIONS.current).is_some());
+        assert_eq!(read(Version(0, 2, 0), &[1, 2, 0, 0]).unwrap(), 0x0201);
+        assert_eq!(read(Version(0, 1, 3), &[1, 2]).unwrap(), 0x0201);
+        // Upgraded, then read by the next version's reader.
+        assert_eq!(read(Version(0, 0, 1), &[7]).unwrap(), 7);
+        // From the future, or from a gap in the history.
+        assert!(read(Version(0, 3, 0), &[0; 4]).is_err());
+        assert!(read(Version(0, 1, 6), &[0; 2]).is_err());
+    }
+    /// Every chunk must be able to read what it writes.
+    #[test]
+    fn current_readable() {
+        use crate::state::palette::PLTE_MIGRATIONS;
+        use crate::{io::asset::ASET_MIGRATIONS, io::history::HIST_MIGRATIONS};
+        assert!(PLTE_MIGRATIONS.reader(PLTE_MIGRATIONS.current).is_some());
+        assert!(ASET_MIGRATIONS.reader(ASET_MIGRATIONS.current).is_some());
+        assert!(HIST_MIGRATIONS.reader(HIST_MIGRATIONS.current).is_some());
+    }
+}
@@ -1,8 +1,14 @@
+pub mod asset;
 /// IO utilities not specific to the format.
 pub mod common;
+pub mod file_lock;
+pub mod history;
 pub mod id;
+pub mod journal;
+pub mod migrate;
 pub mod resource;
 pub mod riff;
+pub mod safe_save;
 
 /// Fields read from a file that were not understood, either due to unrecognized
 /// `ChunkID` or incompatible version, but the fields requested to be preserved through read/writes.
@@ -57,11 +63,14 @@ pub enum OrphanMode {
     /// The reader should not parse the document if it cannot parse this chunk.
     Deny = 2,
 }
-#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Eq)]
+/// The version of a chunk's layout, each chunk versioned on its own. See [`migrate`].
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 #[repr(C)]
 pub struct Version(pub u8, pub u8, pub u8);
-impl Version {
-    pub const CURRENT: Self = Version(0, 0, 0);
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
 }
 
 #[repr(C)]
@@ -106,12 +115,107 @@ pub enum IOStrategy {
 }
 const EMPTY_DICT: [u8; 12] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
 
+/// Standard RIFF `INFO` entries for the document's title, artist, and comments, and the
+/// resolution in dots per inch. Each is a nul-terminated string.
+const INFO_TITLE: riff::ChunkID = riff::ChunkID(*b"INAM");
+const INFO_AUTHOR: riff::ChunkID = riff::ChunkID(*b"IART");
+const INFO_DESCRIPTION: riff::ChunkID = riff::ChunkID(*b"ICMT");
+const INFO_DPI: riff::ChunkID = riff::ChunkID(*b"IDPI");
+
+/// Write the metadata and resolution as entries of an `INFO` list. Empty strings are left out.
+fn write_metadata_into(
+    metadata: &crate::state::document::Metadata,
+    resolution: crate::units::Resolution,
+    mut info: impl std::io::Write,
+) -> std::io::Result<()> {
+    use riff::encode::SizedBinaryChunkWriter;
+    let dpi = resolution.into_dpi().to_string();
+    for (id, text) in [
+        (INFO_TITLE, metadata.title.as_str()),
+        (INFO_AUTHOR, metadata.author.as_str()),
+        (INFO_DESCRIPTION, metadata.description.as_str()),
+        (INFO_DPI, dpi.as_str()),
+    ] {
+        if text.is_empty() {
+            continue;
+        }
+        // Interior nuls would end the string early.
+        let mut data: Vec<u8> = text.bytes().filter(|&b| b != 0).collect();
+        data.push(0);
+        SizedBinaryChunkWriter::write_buf(&mut info, id, &data)?;
+    }
+    Ok(())
+}
+/// Read one entry of an `INFO` list into the metadata or the viewport's resolution. Entries that aren't
+/// metadata, or can't be understood, are skipped.
+fn read_metadata_entry(
+    metadata: &mut crate::state::document::Metadata,
+    viewport: &mut crate::state::document::Viewport,
+    id: riff::ChunkID,
+    data: &[u8],
+) {
+    // Up to the nul, and tolerant of text from other encoders.
+    let text = data.split(|&b| b == 0).next().unwrap_or_default();
+    let text = String::from_utf8_lossy(text).into_owned();
+    match id {
+        INFO_TITLE => metadata.title = text,
+        INFO_AUTHOR => metadata.author = text,
+        INFO_DESCRIPTION => metadata.description = text,
+        INFO_DPI => {
+            if let Some(dpi) = text
+                .trim()
+                .parse()
+                .ok()
+                .filter(|dpi| crate::state::document::Viewport::DPI_RANGE.contains(dpi))
+            {
+                viewport.resolution = crate::units::Resolution::Dpi(dpi);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The canvas, as the `DOCV` chunk: origin then size, as little endian `f32`s.
+fn canvas_bytes(canvas: crate::state::document::Canvas) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    for (into, value) in bytes
+        .chunks_exact_mut(4)
+        .zip(canvas.origin.into_iter().chain(canvas.size))
+    {
+        into.copy_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+/// Read the canvas from the `DOCV` chunk. None if it's empty, as written by older versions, or not understood.
+fn read_canvas(data: &[u8]) -> Option<crate::state::document::Canvas> {
+    let data: &[u8; 16] = data.try_into().ok()?;
+    let mut values = data
+        .chunks_exact(4)
+        // Unwrap ok - chunks are exactly four.
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()));
+    let mut next = || values.next().unwrap_or_default();
+    let canvas = crate::state::document::Canvas {
+        origin: [next(), next()],
+        size: [next(), next()],
+    };
+    canvas.is_valid().then_some(canvas)
+}
+
 /// From the given document state reader and repository handle, write a `.fzp` document into the given writer.
+///
+/// `document_dir` is the directory the document is being written into, against which linked assets
+/// are made relative. `history` is embedded so that it can be undone after reopening, and should lead up
+/// to `document`. Without it, only the present is written.
+///
+/// Returns the IDs the history was written with, for a [journal] to continue from.
+#[tracing::instrument(level = "debug", skip_all)]
 pub fn write_into<Document, Writer>(
     document: &Document,
+    history: Option<&crate::queue::History>,
     point_repository: &crate::repositories::points::Points,
     writer: Writer,
-) -> Result<(), WriteError>
+    document_dir: Option<&std::path::Path>,
+) -> Result<Option<history::FileIds>, WriteError>
 where
     Document: crate::queue::state_reader::CommandQueueStateReader,
     Writer: std::io::Write + std::io::Seek,
@@ -121,30 +225,50 @@ where
         ChunkID,
     };
     let mut root = BinaryChunkWriter::new_subtype(writer, ChunkID::RIFF, ChunkID::FZP_)?;
+    let point_ids;
+    let ids;
     {
         {
             let mut info = BinaryChunkWriter::new_subtype(&mut root, ChunkID::LIST, ChunkID::INFO)?;
             SizedBinaryChunkWriter::write_buf(&mut info, ChunkID(*b"ISFT"), b"fuzzpaint\0")?;
+            write_metadata_into(
+                &document.document().metadata.read(),
+                document.document().viewport.resolution,
+                &mut info,
+            )?;
         }
         /*{
             const TEST_QOI: &'static [u8] = include_bytes!("../test-data/test image.qoi");
             SizedBinaryChunkWriter::write_buf(&mut root, ChunkID::THMB, TEST_QOI)?;
         }*/
-        SizedBinaryChunkWriter::write_buf(&mut root, ChunkID::DOCV, &[])?;
+        SizedBinaryChunkWriter::write_buf(
+            &mut root,
+            ChunkID::DOCV,
+            &canvas_bytes(document.document().viewport.canvas()),
+        )?;
         {
             let mut objs = BinaryChunkWriter::new_subtype(&mut root, ChunkID::LIST, ChunkID::OBJS)?;
 
             let collections = document.stroke_collections();
-            point_repository
+            // History may refer to strokes which are no longer in the present.
+            let history_points = history.into_iter().flat_map(history::point_collections);
+            point_ids = point_repository
                 .write_dict_into(
                     collections
                         .0
                         .iter()
                         .flat_map(|collection| collection.1.strokes.iter())
-                        .map(|stroke| stroke.point_collection),
+                        .map(|stroke| stroke.point_collection)
+                        .chain(history_points),
                     &mut objs,
                 )
                 .map_err(|err| -> anyhow::Error { err.into() })?;
+            document.palette().write_chunk_into(&mut objs)?;
+            document
+                .document()
+                .assets
+                .read()
+                .write_chunk_into(&mut objs, document_dir)?;
             SizedBinaryChunkWriter::write_buf(&mut objs, ChunkID::GRPH, &[])?;
             SizedBinaryChunkWriter::write_buf_subtype(
                 &mut objs,
@@ -153,10 +277,21 @@ where
                 &EMPTY_DICT,
             )?;
         }
-        SizedBinaryChunkWriter::write_buf(&mut root, ChunkID::HIST, &[])?;
+        ids = match history {
+            Some(history) => Some(history::write_chunk_into(
+                history,
+                document.graph(),
+                point_ids,
+                &mut root,
+            )?),
+            None => {
+                SizedBinaryChunkWriter::write_buf(&mut root, ChunkID::HIST, &[])?;
+                None
+            }
+        };
     }
 
-    Ok(())
+    Ok(ids)
 }
 
 // Todo: explicit bufread support in chunks!
@@ -164,13 +299,116 @@ pub fn read_path<Path: Into<std::path::PathBuf>>(
     path: Path,
     point_repository: &crate::repositories::points::Points,
 ) -> Result<crate::queue::DocumentCommandQueue, std::io::Error> {
+    read_path_with_ids(path, point_repository).map(|(queue, _)| queue)
+}
+/// As [`read_path`], also returning the IDs given to everything the document's history mentions, for a
+/// [journal] to be replayed with. `None` if the document was read without its history.
+pub fn read_path_with_ids<Path: Into<std::path::PathBuf>>(
+    path: Path,
+    point_repository: &crate::repositories::points::Points,
+) -> Result<
+    (
+        crate::queue::DocumentCommandQueue,
+        Option<history::ProcessIds>,
+    ),
+    std::io::Error,
+> {
+    read_path_with_progress(
+        path,
+        point_repository,
+        &crate::progress::Progress::detached(),
+    )
+}
+/// As [`read_path_with_ids`], reporting how much of the file has been read. Fails with
+/// [`Cancelled`](crate::progress::Cancelled) if the task is cancelled part way.
+pub fn read_path_with_progress<Path: Into<std::path::PathBuf>>(
+    path: Path,
+    point_repository: &crate::repositories::points::Points,
+    progress: &crate::progress::Progress,
+) -> Result<
+    (
+        crate::queue::DocumentCommandQueue,
+        Option<history::ProcessIds>,
+    ),
+    std::io::Error,
+> {
+    read_path_inner(path, point_repository, progress, None, false)
+}
+/// As [`read_path_with_ids`], leaving the document untitled as though it had never been saved, for reading a
+/// snapshot of a new document such as a [journal] of it replays onto.
+pub fn read_untitled_with_ids<Path: Into<std::path::PathBuf>>(
+    path: Path,
+    point_repository: &crate::repositories::points::Points,
+) -> Result<
+    (
+        crate::queue::DocumentCommandQueue,
+        Option<history::ProcessIds>,
+    ),
+    std::io::Error,
+> {
+    read_path_inner(
+        path,
+        point_repository,
+        &crate::progress::Progress::detached(),
+        None,
+        true,
+    )
+}
+/// Read as much of a damaged document as can be, for when [`read_path_with_progress`] fails. Parts that
+/// can't be read are left out rather than failing the whole read, returned as a description of each. Anything
+/// after damage to the file's structure, such as a bad chunk length, is lost.
+///
+/// # Errors
+/// If the file can't be read at all, isn't a document, or the task is cancelled.
+pub fn salvage_path<Path: Into<std::path::PathBuf>>(
+    path: Path,
+    point_repository: &crate::repositories::points::Points,
+    progress: &crate::progress::Progress,
+) -> Result<(crate::queue::DocumentCommandQueue, Vec<String>), std::io::Error> {
+    let mut skipped = Vec::new();
+    let (queue, _) = read_path_inner(path, point_repository, progress, Some(&mut skipped), false)?;
+    Ok((queue, skipped))
+}
+/// When salvaging, note the failure to read `what` and carry on without it. Otherwise, or if cancelled, fail.
+fn skip_failed(
+    skipped: &mut Option<&mut Vec<String>>,
+    what: impl std::fmt::Display,
+    result: std::io::Result<()>,
+) -> std::io::Result<()> {
+    match (result, skipped) {
+        (Err(err), Some(skipped)) if !crate::progress::Cancelled::is_cause_of(&err) => {
+            tracing::warn!("salvaging, skipped {what}: {err}");
+            skipped.push(format!("{what}: {err}"));
+            Ok(())
+        }
+        (result, _) => result,
+    }
+}
+/// See [`read_path_with_progress`] and [`salvage_path`], the latter if `skipped` is set.
+fn read_path_inner<Path: Into<std::path::PathBuf>>(
+    path: Path,
+    point_repository: &crate::repositories::points::Points,
+    progress: &crate::progress::Progress,
+    mut skipped: Option<&mut Vec<String>>,
+    untitled: bool,
+) -> Result<
+    (
+        crate::queue::DocumentCommandQueue,
+        Option<history::ProcessIds>,
+    ),
+    std::io::Error,
+> {
     use riff::{decode::BinaryChunkReader, ChunkID};
-    use std::io::Error as IOError;
+    use std::io::{Error as IOError, Read};
     let path_buf = path.into();
+    let _span = tracing::info_span!("read_path", path = %path_buf.display()).entered();
     let file = std::fs::File::open(&path_buf)?;
     let size = file.metadata().map(|meta| meta.len()).ok();
+    if let Some(size) = size {
+        progress.set_total(size);
+    }
     let start_time = std::time::Instant::now();
-    let r = std::io::BufReader::new(file);
+    let r = std::io::BufReader::new(crate::progress::ProgressRead::new(file, progress.clone()));
 
     // Dont need to check magic before extracting subchunks. If extracting fails, it
     // must've been bad anyway!
@@ -180,44 +418,141 @@ pub fn read_path<Path: Into<std::path::PathBuf>>(
     }
 
     let mut point_lists = None;
+    let mut palette = None;
+    let mut assets = None;
+    let mut history = None;
+    let mut metadata = crate::state::document::Metadata::default();
+    let mut viewport = crate::state::document::Viewport::default();
+    let document_dir = path_buf.parent();
 
     #[allow(clippy::match_same_arms)]
-    root.try_for_each(|subchunk| match subchunk.id() {
-        ChunkID::LIST => {
-            let subchunk = subchunk.into_subchunks()?;
-            match subchunk.subtype_id() {
-                ChunkID::INFO => Ok(()),
-                ChunkID::OBJS => subchunk.try_for_each(|obj| match obj.id() {
-                    ChunkID::DICT => {
-                        let dict = obj.into_dict()?;
-                        match dict.subtype_id() {
-                            ChunkID::PTLS => point_repository.read_dict(dict).map(|lists| {
-                                point_lists = Some(lists);
-                            }),
-                            ChunkID::BRSH => Ok(()),
-                            other => Err(IOError::other(anyhow::anyhow!(
-                                "Unrecognized dict \"{other}\""
-                            ))),
-                        }
-                    }
-                    ChunkID::GRPH => Ok(()),
+    let read = root.try_for_each(|mut subchunk| {
+        let id = subchunk.id();
+        let result = match id {
+            ChunkID::LIST => {
+                subchunk
+                    .into_subchunks()
+                    .and_then(|subchunk| match subchunk.subtype_id() {
+                        ChunkID::INFO => subchunk.try_for_each(|mut entry| {
+                            let mut data = Vec::new();
+                            entry.read_to_end(&mut data)?;
+                            read_metadata_entry(&mut metadata, &mut viewport, entry.id(), &data);
+                            Ok(())
+                        }),
+                        ChunkID::OBJS => subchunk.try_for_each(|obj| {
+                            let id = obj.id();
+                            let result = match id {
+                                ChunkID::DICT => {
+                                    obj.into_dict().and_then(|dict| match dict.subtype_id() {
+                                        ChunkID::PTLS => {
+                                            point_repository.read_dict(dict).map(|lists| {
+                                                point_lists = Some(lists);
+                                            })
+                                        }
+                                        ChunkID::BRSH => Ok(()),
+                                        other => Err(IOError::other(anyhow::anyhow!(
+                                            "Unrecognized dict \"{other}\""
+                                        ))),
+                                    })
+                                }
+                                ChunkID::PLTE => crate::state::palette::Palette::read_chunk(obj)
+                                    .map(|p| {
+                                        palette = Some(p);
+                                    }),
+                                ChunkID::GRPH => Ok(()),
+                                ChunkID::ASET => asset::AssetLinks::read_chunk(obj, document_dir)
+                                    .map(|a| {
+                                        assets = Some(a);
+                                    }),
 
-                    other => Err(IOError::other(anyhow::anyhow!(
-                        "Unrecognized obj \"{other}\""
-                    ))),
-                }),
-                other => Err(IOError::other(anyhow::anyhow!(
-                    "Unrecognized list \"{other}\""
-                ))),
+                                other => Err(IOError::other(anyhow::anyhow!(
+                                    "Unrecognized obj \"{other}\""
+                                ))),
+                            };
+                            skip_failed(&mut skipped, format_args!("\"{id}\" chunk"), result)
+                        }),
+                        other => Err(IOError::other(anyhow::anyhow!(
+                            "Unrecognized list \"{other}\""
+                        ))),
+                    })
+            }
+            ChunkID::THMB => Ok(()),
+            ChunkID::HIST => {
+                // Refers to point collections, which may not have been read yet.
+                let mut data = Vec::new();
+                subchunk.read_to_end(&mut data).map(|_| {
+                    // Empty when written without history.
+                    history = Some(data).filter(|data| !data.is_empty());
+                })
+            }
+            ChunkID::DOCV => {
+                let mut data = Vec::new();
+                subchunk.read_to_end(&mut data).map(|_| {
+                    if let Some(canvas) = read_canvas(&data) {
+                        viewport.set_canvas(canvas);
+                    }
+                })
+            }
+            other => Err(IOError::other(anyhow::anyhow!(
+                "Unrecognized chunk \"{other}\""
+            ))),
+        };
+        skip_failed(&mut skipped, format_args!("\"{id}\" chunk"), result)
+    });
+    // Past damage to the structure, there's no telling where the next chunk starts.
+    skip_failed(&mut skipped, "the rest of the file", read)?;
+    let (name, path) = if untitled {
+        (crate::state::document::Document::default().name, None)
+    } else {
+        (
+            // File stem (without ext) if available, else the whole path.
+            path_buf
+                .file_stem()
+                .map_or_else(|| path_buf.to_string_lossy(), |p| p.to_string_lossy())
+                .into_owned(),
+            Some(path_buf),
+        )
+    };
+    let document_info = crate::state::document::Document {
+        name,
+        path,
+        assets: std::sync::Arc::new(assets.unwrap_or_default().into()),
+        metadata: std::sync::Arc::new(metadata.into()),
+        viewport,
+        ..Default::default()
+    };
+    if let Some(size) = size {
+        let duration = start_time.elapsed();
+        let duration_micros = duration.as_micros();
+        let size = size as f64;
+        tracing::info!(
+            size = %human_bytes::human_bytes(size),
+            micros = duration_micros,
+            speed = %format_args!("{}/s", human_bytes::human_bytes(size / duration.as_secs_f64())),
+            "read document",
+        );
+    }
+    // Replaying the history is the last long step.
+    progress.check()?;
+    if let Some(history) = history {
+        // History rebuilds the viewport, starting from the default.
+        let base = crate::state::document::Document {
+            viewport: crate::state::document::Viewport::default(),
+            ..document_info.clone()
+        };
+        match read_history(&history, point_lists.as_ref(), base) {
+            Ok((queue, ids)) => return Ok((queue, Some(ids))),
+            Err(err) => {
+                tracing::warn!("failed to read history, falling back on the present: {err}");
+                if let Some(skipped) = &mut skipped {
+                    skipped.push(format!(
+                        "History, so only the strokes are left, on a single layer: {err}"
+                    ));
+                }
             }
         }
-        ChunkID::THMB => Ok(()),
-        ChunkID::HIST => Ok(()),
-        ChunkID::DOCV => Ok(()),
-        other => Err(IOError::other(anyhow::anyhow!(
-            "Unrecognized chunk \"{other}\""
-        ))),
-    })?;
+    }
+
     let strokes = match point_lists {
         Some(ref l) => l
             .iter()
@@ -226,11 +561,20 @@ pub fn read_path<Path: Into<std::path::PathBuf>>(
                 |collection| crate::state::stroke_collection::ImmutableStroke {
                     point_collection: *collection,
                     id: crate::FuzzID::default(),
+                    // Todo: clips aren't yet written to files.
+                    clip: None,
+                    metadata: crate::state::stroke_collection::StrokeMetadata::default(),
                     brush: crate::state::StrokeBrushSettings {
                         is_eraser: false,
+                        is_smudge: false,
                         brush: crate::brush::UniqueID([0; 32]),
                         color_modulate: crate::color::ColorOrPalette::BLACK,
                         size_mul: crate::util::FiniteF32::new(10.0).unwrap(),
+                        orientation: crate::state::StampOrientation::Random,
+                        scatter: crate::state::Scatter::default(),
+                        dual: None,
+                        taper: crate::state::Taper::default(),
+                        dynamics: crate::state::Dynamics::default(),
                         spacing_px: crate::util::FiniteF32::new(0.5).unwrap(),
                     },
                 },
@@ -264,30 +608,232 @@ pub fn read_path<Path: Into<std::path::PathBuf>>(
         )
         .unwrap();
 
-    let document_info = crate::state::document::Document {
-        // File stem (without ext) if available, else the whole path.
-        name: path_buf
-            .file_stem()
-            .map_or_else(|| path_buf.to_string_lossy(), |p| p.to_string_lossy())
-            .into_owned(),
-        path: Some(path_buf),
-        ..Default::default()
-    };
-    if let Some(size) = size {
-        let duration = start_time.elapsed();
-        let duration_micros = duration.as_micros();
-        let size = size as f64;
-        log::info!(
-            "Read {} in {}us ({}/s)",
-            human_bytes::human_bytes(size),
-            duration_micros,
-            human_bytes::human_bytes(size / duration.as_secs_f64())
-        );
-    }
-    Ok(crate::queue::DocumentCommandQueue::from_state(
+    let queue = crate::queue::DocumentCommandQueue::from_state(
         document_info,
         my_graph,
         stroke_state,
-        crate::state::palette::Palette::default(),
-    ))
+        palette.unwrap_or_default(),
+    );
+    Ok((queue, None))
+}
+/// Rebuild a document from its `hist` chunk.
+fn read_history(
+    data: &[u8],
+    point_lists: Option<
+        &id::ProcessLocalInterner<crate::repositories::points::PointCollectionIDMarker>,
+    >,
+    document: crate::state::document::Document,
+) -> Result<(crate::queue::DocumentCommandQueue, history::ProcessIds), std::io::Error> {
+    let read = history::read_chunk(data, point_lists.cloned().unwrap_or_default())?;
+    let queue = crate::queue::DocumentCommandQueue::from_history(document, read.history)
+        .map_err(std::io::Error::other)?;
+    // Names, tags, and reference flags aren't part of history, so aren't tracked either.
+    queue.write_with(|writer| {
+        let mut graph = writer.graph();
+        for (id, label) in read.labels {
+            if let Some(old) = graph.name_mut(id) {
+                *old = label.name;
+            }
+            if let Some(old) = graph.tag_mut(id) {
+                *old = label.tag;
+            }
+            if let Some(old) = graph.reference_mut(id) {
+                *old = label.reference;
+            }
+        }
+    });
+    Ok((queue, read.ids))
+}
+
+#[cfg(test)]
+mod test {
+    use super::riff::{decode::BinaryChunkReader, encode::BinaryChunkWriter, ChunkID};
+    use crate::state::document::Metadata;
+    use std::io::Read;
+    #[test]
+    fn metadata_round_trip() {
+        let metadata = Metadata {
+            title: "Sunset".to_owned(),
+            author: "Someone\0 else".to_owned(),
+            description: String::new(),
+        };
+        let resolution = crate::units::Resolution::Dpi(300.0);
+        let mut file = std::io::Cursor::new(Vec::new());
+        {
+            let mut info =
+                BinaryChunkWriter::new_subtype(&mut file, ChunkID::LIST, ChunkID::INFO).unwrap();
+            super::write_metadata_into(&metadata, resolution, &mut info).unwrap();
+        }
+        file.set_position(0);
+
+        let mut read = Metadata::default();
+        let mut viewport = crate::state::document::Viewport::default();
+        BinaryChunkReader::new(file)
+            .unwrap()
+            .into_subchunks()
+            .unwrap()
+            .try_for_each(|mut entry| {
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data)?;
+                super::read_metadata_entry(&mut read, &mut viewport, entry.id(), &data);
+                Ok(())
+            })
+            .unwrap();
+        // Nuls can't be stored.
+        let expected = Metadata {
+            author: "Someone else".to_owned(),
+            ..metadata
+        };
+        assert_eq!(read, expected);
+        assert_eq!(viewport.resolution, resolution);
+    }
+    #[test]
+    fn canvas_round_trip() {
+        use crate::state::document::Canvas;
+        let canvas = Canvas {
+            origin: [-12.0, 40.5],
+            size: [1920.0, 300.0],
+        };
+        assert_eq!(
+            super::read_canvas(&super::canvas_bytes(canvas)),
+            Some(canvas)
+        );
+        // Written empty by older versions.
+        assert_eq!(super::read_canvas(&[]), None);
+        let empty = Canvas {
+            size: [0.0, 300.0],
+            ..canvas
+        };
+        assert_eq!(super::read_canvas(&super::canvas_bytes(empty)), None);
+    }
+    #[test]
+    fn salvage() {
+        use super::riff::encode::SizedBinaryChunkWriter;
+        use crate::queue::state_reader::CommandQueueStateReader;
+        let metadata = Metadata {
+            title: "Damaged".to_owned(),
+            ..Default::default()
+        };
+        let mut file = std::io::Cursor::new(Vec::new());
+        {
+            let mut riff =
+                BinaryChunkWriter::new_subtype(&mut file, ChunkID::RIFF, ChunkID::FZP_).unwrap();
+            {
+                let mut info =
+                    BinaryChunkWriter::new_subtype(&mut riff, ChunkID::LIST, ChunkID::INFO)
+                        .unwrap();
+                super::write_metadata_into(
+                    &metadata,
+                    crate::state::document::Viewport::default().resolution,
+                    &mut info,
+                )
+                .unwrap();
+            }
+            SizedBinaryChunkWriter::write_buf(&mut riff, ChunkID(*b"what"), &[0; 4]).unwrap();
+        }
+        let mut bytes = file.into_inner();
+        // Cut off part way through a chunk header.
+        bytes.extend_from_slice(b"hi");
+        let riff_len = u32::try_from(bytes.len() - 8).unwrap();
+        bytes[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+        let dir = crate::util::scratch_dir("salvage");
+        let path = dir.join("damaged.fzp");
+        std::fs::write(&path, bytes).unwrap();
+        let points = crate::repositories::points::Points::default();
+        let progress = crate::progress::Progress::detached();
+
+        assert!(super::read_path_with_progress(&path, &points, &progress).is_err());
+        let (queue, skipped) = super::salvage_path(&path, &points, &progress).unwrap();
+        // The unknown chunk, then the cut off one.
+        assert_eq!(skipped.len(), 2);
+        let state = queue.peek_clone_state();
+        assert_eq!(state.document().metadata.read().title, "Damaged");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    /// Documents written by older versions must continue to load as the format evolves. When a chunk's version
+    /// is bumped, add a fixture written by the last release, and keep the old ones.
+    #[test]
+    fn fixtures() {
+        use crate::{color::Color, queue::state_reader::CommandQueueStateReader};
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/fixtures/0.0.0.fzp");
+        let points = crate::repositories::points::Points::default();
+        let queue = super::read_path(path, &points).unwrap();
+
+        let state = queue.peek_clone_state();
+        assert_eq!(state.document().metadata.read().title, "Fixture");
+        let assets = state.document().assets.read();
+        let grain = assets.get(crate::brush::UniqueID(std::array::from_fn(|i| {
+            u8::try_from(i).unwrap()
+        })));
+        assert_eq!(grain.map(|link| link.name.as_str()), Some("grain.png"));
+        drop(assets);
+        let red = Color::new_lossy(1.0, 0.0, 0.0, 1.0).unwrap();
+        assert_eq!(
+            state.palette().get(crate::color::PaletteIndex(0)),
+            Some(red)
+        );
+        drop(state);
+
+        // The undone recolor came along too.
+        queue.redo_n(1);
+        let blue = Color::new_lossy(0.0, 0.0, 1.0, 1.0).unwrap();
+        assert_eq!(
+            queue
+                .peek_clone_state()
+                .palette()
+                .get(crate::color::PaletteIndex(0)),
+            Some(blue)
+        );
+
+        // Strokes gained metadata.
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/fixtures/0.1.0.fzp");
+        let queue = super::read_path(path, &points).unwrap();
+        assert_eq!(queue.history_depth(), (1, 0));
+        let state = queue.peek_clone_state();
+        let collections = state.stroke_collections();
+        let stroke = collections
+            .0
+            .values()
+            .find_map(|collection| collection.iter_active().next())
+            .unwrap();
+        assert_eq!(
+            stroke.metadata,
+            crate::state::stroke_collection::StrokeMetadata {
+                created: Some(1_700_000_000_000),
+                author: None,
+                device: Some(42),
+            }
+        );
+        assert_eq!(
+            state.document().viewport.canvas(),
+            crate::state::document::Viewport::default().canvas()
+        );
+        drop(state);
+
+        // The canvas became undoable.
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/fixtures/0.2.0.fzp");
+        let queue = super::read_path(path, &points).unwrap();
+        assert_eq!(queue.history_depth(), (2, 0));
+        let resized = crate::state::document::Canvas {
+            origin: [0.0, 0.0],
+            size: [800.0, 600.0],
+        };
+        assert_eq!(
+            queue.peek_clone_state().document().viewport.canvas(),
+            resized
+        );
+        queue.undo_n(1);
+        let state = queue.peek_clone_state();
+        assert_eq!(
+            state.document().viewport.canvas(),
+            crate::state::document::Viewport::default().canvas()
+        );
+        let collections = state.stroke_collections();
+        assert!(collections
+            .0
+            .values()
+            .any(|collection| collection.iter_active().next().is_some()));
+    }
 }
@@ -168,10 +168,10 @@ impl<W: Write> Drop for SizedBinaryChunkWriter<W> {
         if self.writer.remaining() != 0 {
             // Best-practices warning
             #[cfg(debug_assertions)]
-            log::warn!("Padding in SizedBinaryChunkWriter dtor!");
+            tracing::warn!("Padding in SizedBinaryChunkWriter dtor!");
 
             if let Err(e) = self.writer.pad_slow() {
-                log::error!("Error in SizedBinaryChunkWriter dtor: {e}");
+                tracing::error!(error = %e, "failed to pad in SizedBinaryChunkWriter dtor");
             }
         }
     }
@@ -252,11 +252,11 @@ impl<W: Write + Seek> Drop for BinaryChunkWriter<W> {
         if self.needs_len_flush {
             // Give a best-practices warning
             #[cfg(debug_assertions)]
-            log::warn!("Flushing in BinaryChunkWriter dtor.");
+            tracing::warn!("Flushing in BinaryChunkWriter dtor.");
 
             // Flush and report errors.
             if let Err(e) = self.update_len() {
-                log::error!("Error in BinaryChunkWriter dtor: {e}");
+                tracing::error!(error = %e, "failed to flush in BinaryChunkWriter dtor");
             }
         }
     }
@@ -24,6 +24,9 @@ impl ChunkID {
     pub const BRSH: Self = ChunkID(*b"brsh");
     pub const PTLS: Self = ChunkID(*b"ptls");
     pub const STRK: Self = ChunkID(*b"strk");
+    // Object table items
+    pub const PLTE: Self = ChunkID(*b"plte");
+    pub const ASET: Self = ChunkID(*b"aset");
     // GRPH items
     pub const GRPH: Self = ChunkID(*b"GRPH");
     pub const NODE: Self = ChunkID(*b"node");
@@ -193,4 +196,192 @@ mod test {
             .unwrap();
         assert_eq!(chunks_remaining, 0);
     }
+
+    /// Tiny deterministic noise for the property tests below.
+    struct XorShift(u64);
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        /// In `[0, max)`
+        fn below(&mut self, max: u64) -> u64 {
+            self.next() % max
+        }
+        fn byte(&mut self) -> u8 {
+            self.next().to_le_bytes()[0]
+        }
+        fn word(&mut self) -> u32 {
+            self.next().to_le_bytes()[..4]
+                .try_into()
+                .map(u32::from_le_bytes)
+                .unwrap()
+        }
+        /// Any ID other than a `RIFF` or `LIST` container.
+        fn leaf_id(&mut self) -> ChunkID {
+            loop {
+                let bytes = self.next().to_le_bytes();
+                let id = ChunkID([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                if id != ChunkID::RIFF && id != ChunkID::LIST {
+                    return id;
+                }
+            }
+        }
+    }
+    /// An arbitrary chunk structure, where only `RIFF` and `LIST` have children.
+    #[derive(PartialEq, Eq, Debug)]
+    enum Tree {
+        Leaf(ChunkID, Vec<u8>),
+        List(ChunkID, ChunkID, Vec<Tree>),
+    }
+    impl Tree {
+        fn random(rand: &mut XorShift, depth: u32) -> Self {
+            if depth == 0 || rand.below(3) == 0 {
+                let data = (0..rand.below(64)).map(|_| rand.byte()).collect();
+                Self::Leaf(rand.leaf_id(), data)
+            } else {
+                let children = (0..rand.below(5))
+                    .map(|_| Self::random(rand, depth - 1))
+                    .collect();
+                Self::List(ChunkID::LIST, rand.leaf_id(), children)
+            }
+        }
+        fn random_file(rand: &mut XorShift) -> Self {
+            let children = (0..rand.below(5)).map(|_| Self::random(rand, 3)).collect();
+            Self::List(ChunkID::RIFF, ChunkID::FZP_, children)
+        }
+        /// Encode through the writers. Children are encoded into their own buffers, as nesting the writers
+        /// themselves would be a new type at every level.
+        fn write(&self) -> Vec<u8> {
+            use std::io::Write;
+            let mut file = Vec::new();
+            match self {
+                Self::Leaf(id, data) => {
+                    SizedBinaryChunkWriter::write_buf(&mut file, *id, data).unwrap();
+                }
+                Self::List(id, subtype, children) => {
+                    let mut list =
+                        BinaryChunkWriter::new_subtype(Cursor::new(&mut file), *id, *subtype)
+                            .unwrap();
+                    for child in children {
+                        list.write_all(&child.write()).unwrap();
+                    }
+                    list.update_len().unwrap();
+                }
+            }
+            file
+        }
+        /// Decode through the readers, recursing through buffers for the same reason as [`Self::write`].
+        fn read(file: &[u8]) -> std::io::Result<Self> {
+            let mut chunk = BinaryChunkReader::new(Cursor::new(file))?;
+            let id = chunk.id();
+            if id != ChunkID::RIFF && id != ChunkID::LIST {
+                let mut data = Vec::new();
+                chunk.read_to_end(&mut data)?;
+                return Ok(Self::Leaf(id, data));
+            }
+            let subchunks = chunk.into_subchunks()?;
+            let subtype = subchunks.subtype_id();
+            let mut children = Vec::new();
+            subchunks.try_for_each(|mut child| {
+                // Reassemble the header, as claimed - it may not match the data that is actually present.
+                let mut file = child.id().0.to_vec();
+                let len = u32::try_from(child.data_len_unsanitized()).unwrap();
+                file.extend_from_slice(&len.to_le_bytes());
+                child.read_to_end(&mut file)?;
+                children.push(Self::read(&file)?);
+                Ok(())
+            })?;
+            Ok(Self::List(id, subtype, children))
+        }
+    }
+    /// Interpret the chunk as a `DICT`, regardless of its ID, and read every part of it.
+    fn read_any_dict(file: &[u8]) -> std::io::Result<()> {
+        let dict = BinaryChunkReader::new(Cursor::new(file))?.into_dict()?;
+        let mut spillover =
+            dict.try_for_each(|mut meta| meta.read_to_end(&mut Vec::new()).map(drop))?;
+        spillover.read_to_end(&mut Vec::new()).map(drop)
+    }
+    /// Anything the writers produce, the readers give back unchanged.
+    #[test]
+    fn round_trip() {
+        let mut rand = XorShift(0x2545_F491_4F6C_DD1D);
+        for _ in 0..500 {
+            let tree = Tree::random_file(&mut rand);
+            let file = tree.write();
+            assert_eq!(Tree::read(&file).unwrap(), tree);
+        }
+    }
+    /// Malformed files may fail to read, but must never panic.
+    #[test]
+    fn malformed_never_panics() {
+        let mut rand = XorShift(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..100 {
+            let file = Tree::random_file(&mut rand).write();
+            // Every truncation.
+            for len in 0..file.len() {
+                let _ = Tree::read(&file[..len]);
+                let _ = read_any_dict(&file[..len]);
+            }
+            // Lengths and counts that point past the end, possibly overflowing.
+            for _ in 0..50 {
+                let mut file = file.clone();
+                let at = usize::try_from(rand.below(file.len() as u64 - 3)).unwrap();
+                let value = match rand.below(3) {
+                    0 => u32::MAX,
+                    1 => u32::MAX - rand.word() % 16,
+                    _ => rand.word(),
+                };
+                file[at..at + 4].copy_from_slice(&value.to_le_bytes());
+                let _ = Tree::read(&file);
+                let _ = read_any_dict(&file);
+            }
+        }
+        // Pure noise.
+        for _ in 0..1000 {
+            let file: Vec<u8> = (0..rand.below(128)).map(|_| rand.byte()).collect();
+            let _ = Tree::read(&file);
+            let _ = read_any_dict(&file);
+        }
+    }
+    /// A `DICT` of an unknown type reports each orphan mode, so the reader may decide what to do with it.
+    #[test]
+    fn unknown_dict_orphan_modes() {
+        use crate::io::OrphanMode;
+        let dict = |mode: u8| {
+            let mut data = ChunkID(*b"????").0.to_vec();
+            // Version, orphan mode.
+            data.extend_from_slice(&[0, 0, 0, mode]);
+            // No metadata, stride unused.
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(b"spillover");
+            let mut file = Vec::new();
+            SizedBinaryChunkWriter::write_buf(&mut file, ChunkID::DICT, &data).unwrap();
+            file
+        };
+        for mode in [OrphanMode::Keep, OrphanMode::Discard, OrphanMode::Deny] {
+            let file = dict(mode as u8);
+            let reader = BinaryChunkReader::new(Cursor::new(&file[..]))
+                .unwrap()
+                .into_dict()
+                .unwrap();
+            assert_eq!(reader.subtype_id(), ChunkID(*b"????"));
+            assert_eq!(reader.orphan_mode(), mode);
+            let mut spillover = Vec::new();
+            reader
+                .try_for_each(|_| unreachable!())
+                .unwrap()
+                .read_to_end(&mut spillover)
+                .unwrap();
+            assert_eq!(spillover, b"spillover");
+        }
+        // Not a valid orphan mode.
+        assert!(BinaryChunkReader::new(Cursor::new(&dict(3)[..]))
+            .unwrap()
+            .into_dict()
+            .is_err());
+    }
 }
@@ -0,0 +1,127 @@
+//! # Safe saves
+//!
+//! Writing straight over a file destroys the old contents before the new ones are complete, so a crash or
+//! full disk midway leaves neither. Instead, write into a temporary file beside the target, flush it to disk,
+//! and only then rename it over the target. Renames within a directory are atomic, so the target always
+//! holds either the old or the new contents in full.
+
+use std::path::{Path, PathBuf};
+
+/// Where the `number`th most recent previous version of the target is kept, `<name>.<number>.bak`.
+fn backup_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(".{number}.bak"));
+    path.with_file_name(name)
+}
+/// A hidden file beside the target, unique to this process and call.
+fn temporary_path(path: &Path) -> PathBuf {
+    static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}-{count}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+/// Shift each backup up by one, discarding the oldest, and keep the current target as the newest.
+fn rotate_backups(path: &Path, backups: usize) -> std::io::Result<()> {
+    if backups == 0 || !path.try_exists()? {
+        return Ok(());
+    }
+    for number in (1..backups).rev() {
+        match std::fs::rename(backup_path(path, number), backup_path(path, number + 1)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+    }
+    let newest = backup_path(path, 1);
+    match std::fs::remove_file(&newest) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => (),
+    }
+    // Link rather than rename, so that the target is never missing.
+    // Not every filesystem supports links, copying will do.
+    if std::fs::hard_link(path, &newest).is_err() {
+        std::fs::copy(path, &newest)?;
+    }
+    Ok(())
+}
+
+/// Save into `path` by way of a temporary file, which `write` fills. The target is only replaced once `write`
+/// succeeds and the data is on disk. Up to `backups` previous versions of the target are kept beside it.
+///
+/// On failure, the target is left as it was.
+pub fn save<T, E>(
+    path: &Path,
+    backups: usize,
+    write: impl FnOnce(&std::fs::File) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<std::io::Error>,
+{
+    let temporary = temporary_path(path);
+    let file = std::fs::File::options()
+        .write(true)
+        .read(true)
+        .create_new(true)
+        .open(&temporary)?;
+    let result = (|| -> Result<T, E> {
+        let value = write(&file)?;
+        file.sync_all()?;
+        drop(file);
+        rotate_backups(path, backups)?;
+        std::fs::rename(&temporary, path)?;
+        Ok(value)
+    })();
+    if result.is_err() {
+        // May already be gone, nothing more to do if not.
+        let _ = std::fs::remove_file(&temporary);
+        return result;
+    }
+    // Make the rename itself durable.
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        std::fs::File::open(parent)?.sync_all()?;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{backup_path, save};
+    #[test]
+    fn failed_write_keeps_target() {
+        use std::io::Write;
+        let dir = crate::util::scratch_dir("safe-save-failed");
+        let path = dir.join("doc.fzp");
+        std::fs::write(&path, b"old").unwrap();
+
+        let result: std::io::Result<()> = save(&path, 0, |mut file| {
+            file.write_all(b"partial")?;
+            Err(std::io::Error::other("crashed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        // No temporary left behind.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn rotates_backups() {
+        use std::io::Write;
+        let dir = crate::util::scratch_dir("safe-save-backups");
+        let path = dir.join("doc.fzp");
+        for version in 0..4u8 {
+            save::<_, std::io::Error>(&path, 2, |mut file| file.write_all(&[version])).unwrap();
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), [3]);
+        assert_eq!(std::fs::read(backup_path(&path, 1)).unwrap(), [2]);
+        assert_eq!(std::fs::read(backup_path(&path, 2)).unwrap(), [1]);
+        assert!(!backup_path(&path, 3).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,10 +6,15 @@ pub mod blend;
 pub mod brush;
 pub mod color;
 pub mod commands;
+pub mod filter;
+pub mod gradient;
 pub mod id;
 pub mod io;
+pub mod progress;
 pub mod queue;
 pub mod repositories;
+pub mod ruler;
+pub mod selection;
 pub mod state;
 pub mod stroke;
 pub mod units;
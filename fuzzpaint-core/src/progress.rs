@@ -0,0 +1,203 @@
+//! Progress and cancellation of long running work, such as reading a large document or exporting.
+//!
+//! The work is given a [`Progress`] to report through and to check for cancellation, while whoever is waiting
+//! on it holds the matching [`Task`] to read out or cancel. Work reporting into a [`Progress::detached`]
+//! handle can't be cancelled.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// The work was cancelled by its [`Task`] before it finished.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("cancelled")]
+pub struct Cancelled;
+impl Cancelled {
+    /// Whether the error is, or was caused by, a cancellation.
+    #[must_use]
+    pub fn is_cause_of(err: &std::io::Error) -> bool {
+        err.get_ref().is_some_and(|inner| inner.is::<Self>())
+    }
+}
+impl From<Cancelled> for std::io::Error {
+    fn from(value: Cancelled) -> Self {
+        // Not `Interrupted`, which readers are expected to retry.
+        std::io::Error::other(value)
+    }
+}
+
+struct Shared {
+    done: AtomicU64,
+    /// Zero if not yet known.
+    total: AtomicU64,
+    cancelled: AtomicBool,
+    /// Live [`Progress`] handles.
+    workers: AtomicUsize,
+    start: std::time::Instant,
+}
+
+/// The working side of a [`Task`], for reporting progress and checking whether to stop.
+pub struct Progress(Arc<Shared>);
+impl Clone for Progress {
+    fn clone(&self) -> Self {
+        self.0.workers.fetch_add(1, Ordering::Relaxed);
+        Self(self.0.clone())
+    }
+}
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.0.workers.fetch_sub(1, Ordering::Release);
+    }
+}
+impl Progress {
+    /// Progress that nobody is watching, which is never cancelled.
+    #[must_use]
+    pub fn detached() -> Self {
+        Task::new().1
+    }
+    /// Set how many units of work there are in all. Until set, the progress is indeterminate.
+    pub fn set_total(&self, total: u64) {
+        self.0.total.store(total, Ordering::Relaxed);
+    }
+    /// Set how many units of work have been done.
+    pub fn set_done(&self, done: u64) {
+        self.0.done.store(done, Ordering::Relaxed);
+    }
+    /// Note that `units` more work has been done.
+    pub fn advance(&self, units: u64) {
+        self.0.done.fetch_add(units, Ordering::Relaxed);
+    }
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+    /// Check whether the work should stop, for use with `?`.
+    /// # Errors
+    /// If the task has been cancelled.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The waiting side of some work, to watch its progress or cancel it.
+#[derive(Clone)]
+pub struct Task(Arc<Shared>);
+impl Task {
+    /// Create a task and the handle for its work to report through.
+    #[must_use]
+    pub fn new() -> (Self, Progress) {
+        let shared = Arc::new(Shared {
+            done: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+            workers: AtomicUsize::new(1),
+            start: std::time::Instant::now(),
+        });
+        (Self(shared.clone()), Progress(shared))
+    }
+    /// How much of the work is done, from zero to one. `None` if it's not known how much there is.
+    #[must_use]
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self.0.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return None;
+        }
+        let done = self.0.done.load(Ordering::Relaxed).min(total);
+        #[allow(clippy::cast_precision_loss)]
+        Some((done as f64 / total as f64) as f32)
+    }
+    /// Ask the work to stop. It will stop at the next chance it gets, which may not be right away.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+    }
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+    /// Whether the work has finished, cancelled or not, having dropped every [`Progress`] handle.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.0.workers.load(Ordering::Acquire) == 0
+    }
+    /// How long ago the task began.
+    #[must_use]
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.0.start.elapsed()
+    }
+}
+
+/// Reports the position within a stream as progress, failing with [`Cancelled`] once the task is cancelled.
+pub struct ProgressRead<R> {
+    inner: R,
+    progress: Progress,
+}
+impl<R> ProgressRead<R> {
+    pub fn new(inner: R, progress: Progress) -> Self {
+        Self { inner, progress }
+    }
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R: std::io::Read> std::io::Read for ProgressRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.progress.check()?;
+        let read = self.inner.read(buf)?;
+        self.progress.advance(read as u64);
+        Ok(read)
+    }
+}
+impl<S: std::io::Seek> std::io::Seek for ProgressRead<S> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.progress.check()?;
+        let pos = self.inner.seek(pos)?;
+        self.progress.set_done(pos);
+        Ok(pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn fraction() {
+        let (task, progress) = Task::new();
+        assert_eq!(task.fraction(), None);
+        progress.set_total(4);
+        progress.advance(1);
+        assert_eq!(task.fraction(), Some(0.25));
+        // Overshooting is clamped.
+        progress.advance(10);
+        assert_eq!(task.fraction(), Some(1.0));
+    }
+    #[test]
+    fn finished() {
+        let (task, progress) = Task::new();
+        let watcher = task.clone();
+        let work = progress.clone();
+        assert!(!task.is_finished());
+        drop(progress);
+        assert!(!task.is_finished());
+        drop(work);
+        assert!(task.is_finished());
+        assert!(watcher.is_finished());
+    }
+    #[test]
+    fn cancel_read() {
+        use std::io::Read;
+        let (task, progress) = Task::new();
+        let mut read = ProgressRead::new(std::io::Cursor::new([0u8; 16]), progress.clone());
+        let mut buf = [0; 4];
+        read.read_exact(&mut buf).unwrap();
+        assert_eq!(progress.0.done.load(Ordering::Relaxed), 4);
+        task.cancel();
+        let err = read.read_exact(&mut buf).unwrap_err();
+        assert!(Cancelled::is_cause_of(&err));
+        assert_eq!(progress.check(), Err(Cancelled));
+    }
+}
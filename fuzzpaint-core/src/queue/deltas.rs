@@ -0,0 +1,176 @@
+//! # Deltas
+//!
+//! Typed summaries of how a document's state changed, for observers such as UI panels which keep their own view
+//! of the document and would rather update it piecemeal than walk the whole state every frame.
+//!
+//! Deltas describe the state as it is *after* each change, so an undo reports the opposite of the command it
+//! undoes - undoing a new stroke reports it removed, undoing a blend change reports the blend it had before.
+//! See [`super::DocumentCommandListener::forward_deltas`].
+
+use crate::{
+    commands::{self, DoUndo},
+    state,
+};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Delta {
+    /// Nodes were created, deleted, or moved, so the structure of the graph differs.
+    GraphChanged,
+    /// A node's blend changed.
+    BlendChanged {
+        target: state::graph::AnyID,
+        blend: crate::blend::Blend,
+    },
+    /// A node's type or transforms changed, which may change how it looks without changing the structure.
+    NodeChanged(state::graph::AnyID),
+    StrokeAdded {
+        collection: state::stroke_collection::StrokeCollectionID,
+        stroke: state::stroke_collection::ImmutableStrokeID,
+    },
+    StrokeRemoved {
+        collection: state::stroke_collection::StrokeCollectionID,
+        stroke: state::stroke_collection::ImmutableStrokeID,
+    },
+    /// A stroke's brush changed, which changes how it looks without moving it.
+    StrokeChanged {
+        collection: state::stroke_collection::StrokeCollectionID,
+        stroke: state::stroke_collection::ImmutableStrokeID,
+    },
+    /// A palette entry was added, removed, or recolored.
+    PaletteChanged(crate::color::PaletteIndex),
+    /// The canvas was cropped, extended, or moved.
+    CanvasChanged(state::document::Canvas),
+}
+
+/// Append the deltas of a single step through history, in the order they took effect.
+pub fn push_deltas(change: DoUndo<'_, commands::Command>, into: &mut Vec<Delta>) {
+    let (command, undo) = match change {
+        DoUndo::Do(command) => (command, false),
+        DoUndo::Undo(command) => (command, true),
+    };
+    match command {
+        commands::Command::Meta(commands::MetaCommand::Scope(_, commands)) => {
+            // Undone last-to-first.
+            if undo {
+                for command in commands.iter().rev() {
+                    push_deltas(DoUndo::Undo(command), into);
+                }
+            } else {
+                for command in &**commands {
+                    push_deltas(DoUndo::Do(command), into);
+                }
+            }
+        }
+        commands::Command::Document(commands::DocumentCommand::CanvasChanged { from, to }) => {
+            into.push(Delta::CanvasChanged(if undo { *from } else { *to }));
+        }
+        // Pixels stay where they are, nothing looks any different.
+        commands::Command::Document(commands::DocumentCommand::ResolutionChanged { .. }) => (),
+        commands::Command::Graph(command) => {
+            use commands::GraphCommand;
+            into.push(match command {
+                GraphCommand::BlendChanged { from, to, target } => Delta::BlendChanged {
+                    target: *target,
+                    blend: if undo { *from } else { *to },
+                },
+                GraphCommand::LeafInnerTransformChanged { target, .. }
+                | GraphCommand::LeafOuterTransformChanged { target, .. }
+                | GraphCommand::LeafTyChanged { target, .. } => {
+                    Delta::NodeChanged((*target).into())
+                }
+                GraphCommand::NodeTyChanged { target, .. } => Delta::NodeChanged((*target).into()),
+                GraphCommand::Reparent { .. }
+                | GraphCommand::LeafCreated { .. }
+                | GraphCommand::NodeCreated { .. }
+                | GraphCommand::AnyDeleted { .. } => Delta::GraphChanged,
+            });
+        }
+        commands::Command::StrokeCollection(command) => {
+            use commands::StrokeCollectionCommand;
+            use state::stroke_collection::commands::StrokeCommand;
+            match command {
+                StrokeCollectionCommand::Stroke {
+                    target: collection,
+                    command: StrokeCommand::Created { target: stroke, .. },
+                } => {
+                    let (collection, stroke) = (*collection, *stroke);
+                    into.push(if undo {
+                        Delta::StrokeRemoved { collection, stroke }
+                    } else {
+                        Delta::StrokeAdded { collection, stroke }
+                    });
+                }
+                StrokeCollectionCommand::Stroke {
+                    target: collection,
+                    command: StrokeCommand::BrushChanged { target: stroke, .. },
+                } => into.push(Delta::StrokeChanged {
+                    collection: *collection,
+                    stroke: *stroke,
+                }),
+                // Collections come and go with the layers that own them, reported as `GraphChanged`.
+                StrokeCollectionCommand::Created(_) => (),
+            }
+        }
+        commands::Command::Palette(command) => {
+            use commands::PaletteCommand;
+            let (PaletteCommand::Added { target, .. } | PaletteCommand::Changed { target, .. }) =
+                command;
+            into.push(Delta::PaletteChanged(*target));
+        }
+        // Saving changes nothing about the state.
+        commands::Command::Meta(commands::MetaCommand::Save(_)) | commands::Command::Dummy => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Delta;
+    use crate::queue::DocumentCommandQueue;
+    use crate::state::graph::{Location, NodeType};
+
+    #[test]
+    fn deltas() {
+        let queue = DocumentCommandQueue::new();
+        let mut listener = queue.listen_from_now();
+        let group = queue.write_with(|writer| {
+            writer
+                .graph()
+                .add_node(
+                    NodeType::GroupedBlend(crate::blend::Blend::default()),
+                    Location::IndexIntoRoot(0),
+                    "Group",
+                )
+                .unwrap()
+        });
+        assert_eq!(listener.forward_deltas(), Ok(vec![Delta::GraphChanged]));
+        // Already seen.
+        assert_eq!(listener.forward_deltas(), Ok(vec![]));
+
+        let blend = crate::blend::Blend {
+            opacity: 0.5,
+            ..Default::default()
+        };
+        queue.write_with(|writer| {
+            let mut graph = writer.graph();
+            let _ = graph.change_blend(group.into(), blend);
+        });
+        let target = group.into();
+        assert_eq!(
+            listener.forward_deltas(),
+            Ok(vec![Delta::BlendChanged { target, blend }])
+        );
+
+        // Undoing reports the state as it is now, most recent first.
+        queue.undo_n(2);
+        assert_eq!(
+            listener.forward_deltas(),
+            Ok(vec![
+                Delta::BlendChanged {
+                    target,
+                    blend: crate::blend::Blend::default()
+                },
+                Delta::GraphChanged,
+            ])
+        );
+    }
+}
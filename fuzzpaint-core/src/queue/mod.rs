@@ -16,6 +16,7 @@ use crate::{
     state,
 };
 
+pub mod deltas;
 mod queue_state;
 pub mod state_reader;
 pub mod writer;
@@ -28,6 +29,225 @@ struct DocumentCommandQueueInner {
     state: queue_state::State,
     // "Pointer" into the tree where the most recent command took place.
     root: slab_tree::NodeId,
+    /// Snapshots of the state at some nodes of the command tree, from which any point in history
+    /// can be reached by replaying only a few commands. The root is always present, and every node
+    /// has a checkpoint within [`CHECKPOINT_INTERVAL`] of its ancestors.
+    checkpoints: hashbrown::HashMap<slab_tree::NodeId, Arc<queue_state::State>>,
+    /// The node whose state was last saved, or the state the queue was created with.
+    saved: slab_tree::NodeId,
+    /// Something outside of history changed since last saved, see [`DocumentCommandQueue::mark_changed`].
+    changed: bool,
+    /// Reject all changes, see [`DocumentCommandQueue::set_read_only`].
+    read_only: bool,
+    /// Labels of nodes the present has held, for those rebuilt by replaying history.
+    labels: state::graph::Labels,
+    /// History has been trimmed, and no longer reaches back to the state the queue was created with.
+    trimmed: bool,
+}
+/// Maximum number of commands between a node and its nearest checkpointed ancestor.
+const CHECKPOINT_INTERVAL: usize = 64;
+impl DocumentCommandQueueInner {
+    fn new(command_tree: slab_tree::Tree<commands::Command>, state: queue_state::State) -> Self {
+        let root = state.present;
+        let checkpoints = std::iter::once((root, Arc::new(state.fork()))).collect();
+        Self {
+            command_tree,
+            state,
+            root,
+            checkpoints,
+            saved: root,
+            changed: false,
+            read_only: false,
+            labels: state::graph::Labels::default(),
+            trimmed: false,
+        }
+    }
+    /// Take a checkpoint of the present state, if it is too far from the previous one.
+    /// Call after every new command.
+    fn maybe_checkpoint(&mut self) {
+        let Some(present) = self.command_tree.get(self.state.present) else {
+            return;
+        };
+        let near = std::iter::once(self.state.present)
+            .chain(present.ancestors().map(|node| node.node_id()))
+            .take(CHECKPOINT_INTERVAL)
+            .any(|id| self.checkpoints.contains_key(&id));
+        if !near {
+            self.checkpoints
+                .insert(self.state.present, Arc::new(self.state.fork()));
+        }
+    }
+    /// Find the checkpoint nearest above `target`.
+    fn nearest_checkpoint(
+        &self,
+        target: slab_tree::NodeId,
+    ) -> Result<&Arc<queue_state::State>, TraverseError> {
+        let node = self
+            .command_tree
+            .get(target)
+            .ok_or(TraverseError::NotFound)?;
+        std::iter::once(target)
+            .chain(node.ancestors().map(|node| node.node_id()))
+            .find_map(|id| self.checkpoints.get(&id))
+            // Root is always checkpointed, so this is a node from a floating subtree.
+            .ok_or(TraverseError::Disconnected)
+    }
+    /// Reconstruct the state as it was at `target`, replaying commands from the nearest checkpoint.
+    fn materialize(
+        &self,
+        target: slab_tree::NodeId,
+    ) -> Result<Arc<queue_state::State>, TraverseError> {
+        let checkpoint = self.nearest_checkpoint(target)?;
+        if checkpoint.present == target {
+            return Ok(checkpoint.clone());
+        }
+        let mut state = checkpoint.fork();
+        for command in traverse(&self.command_tree, checkpoint.present, target)? {
+            state.apply(command)?;
+        }
+        // Names and such aren't history, the present knows them best.
+        state.graph.restore_labels(&self.labels);
+        state.graph.adopt_labels(&self.state.graph);
+        state.present = target;
+        Ok(Arc::new(state))
+    }
+    /// Forget history before the ancestor `keep` commands above the present, which becomes the new root, with
+    /// its state as the base. Branches off the forgotten line go with it, along with their checkpoints.
+    ///
+    /// Returns the commands which are no longer part of history, oldest first, including the new root's.
+    fn trim(&mut self, keep: usize) -> Vec<commands::Command> {
+        let Some(present) = self.command_tree.get(self.state.present) else {
+            return Vec::new();
+        };
+        // Newest first, ending at the root.
+        let line: Vec<_> = std::iter::once(present.node_id())
+            .chain(present.ancestors().map(|node| node.node_id()))
+            .collect();
+        let Some(&new_root) = line.get(keep).filter(|&&node| node != self.root) else {
+            return Vec::new();
+        };
+        let base = match self.materialize(new_root) {
+            Ok(base) => base,
+            Err(err) => {
+                tracing::error!("can't trim history, failed to rebuild its new start: {err}");
+                return Vec::new();
+            }
+        };
+
+        // Oldest first, from the root to just above the new one.
+        let forgotten: Vec<_> = line[keep + 1..].iter().rev().copied().collect();
+        let mut commands = Vec::with_capacity(forgotten.len());
+        for (idx, &node) in forgotten.iter().enumerate() {
+            let next = forgotten.get(idx + 1).copied().unwrap_or(new_root);
+            // Undone branches, which can't be reached once the line is gone.
+            let branches: Vec<_> = match self.command_tree.get(node) {
+                Some(node) => node
+                    .children()
+                    .map(|child| child.node_id())
+                    .filter(|&child| child != next)
+                    .collect(),
+                None => Vec::new(),
+            };
+            for branch in branches {
+                self.command_tree
+                    .remove(branch, slab_tree::RemoveBehavior::DropChildren);
+            }
+            // Leaves `next` floating, the top of what remains.
+            let command = self
+                .command_tree
+                .remove(node, slab_tree::RemoveBehavior::OrphanChildren);
+            // The old root's was forgotten already, or is the dummy the tree started with.
+            if node != self.root {
+                commands.extend(command);
+            }
+        }
+        // The root can't be undone, so its command is no longer history either.
+        if let Some(mut root) = self.command_tree.get_mut(new_root) {
+            commands.push(std::mem::replace(root.data(), commands::Command::Dummy));
+        }
+        self.root = new_root;
+        self.trimmed = true;
+        let tree = &self.command_tree;
+        self.checkpoints.retain(|&node, _| tree.get(node).is_some());
+        self.checkpoints.insert(new_root, base);
+        commands
+    }
+    /// Move the present state to `target`, by replaying commands from the present or from a checkpoint,
+    /// whichever is shorter.
+    fn seek(&mut self, target: slab_tree::NodeId) -> Result<(), TraverseError> {
+        let start = self.state.present;
+        let distance = traverse(&self.command_tree, start, target)?.count();
+        // Nodes may drop out of the state on the way, to be rebuilt later knowing only how they were created.
+        self.labels.remember(&self.state.graph);
+        if distance > CHECKPOINT_INTERVAL {
+            match self.materialize(target) {
+                Ok(state) => {
+                    self.state = Arc::try_unwrap(state).unwrap_or_else(|shared| shared.fork());
+                    return Ok(());
+                }
+                // A checkpoint may predate nodes made by undone branches, which the present still holds.
+                // Walking from the present is slower, but doesn't need them.
+                Err(TraverseError::Replay(err)) => {
+                    tracing::debug!("checkpoint doesn't replay, walking from the present: {err}");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        for command in traverse(&self.command_tree, start, target)? {
+            if let Err(err) = self.state.apply(command) {
+                // Don't leave the present half-way to the target.
+                let state = self.materialize(start)?;
+                self.state = Arc::try_unwrap(state).unwrap_or_else(|shared| shared.fork());
+                return Err(err.into());
+            }
+        }
+        self.state.graph.restore_labels(&self.labels);
+        self.state.present = target;
+        Ok(())
+    }
+}
+/// Commands which recreate the state when replayed onto an empty one.
+fn creation_commands(base: &queue_state::State) -> Vec<commands::Command> {
+    // A new document starts on the default canvas and resolution.
+    let default = state::document::Viewport::default();
+    let canvas = base.document.viewport.canvas();
+    let canvas = (canvas != default.canvas()).then(|| {
+        commands::DocumentCommand::CanvasChanged {
+            from: default.canvas(),
+            to: canvas,
+        }
+        .into()
+    });
+    let resolution = base.document.viewport.resolution;
+    let resolution = (resolution != default.resolution).then(|| {
+        commands::DocumentCommand::ResolutionChanged {
+            from: default.resolution,
+            to: resolution,
+        }
+        .into()
+    });
+    canvas
+        .into_iter()
+        .chain(resolution)
+        .chain(base.palette.creation_commands().into_iter().map(Into::into))
+        .chain(
+            base.stroke_state
+                .creation_commands()
+                .into_iter()
+                .map(Into::into),
+        )
+        .chain(base.graph.creation_commands().into_iter().map(Into::into))
+        .collect()
+}
+/// A line of history through the present, see [`DocumentCommandQueue::peek_history`].
+pub struct History {
+    /// Commands which recreate the state before the first of `commands` when replayed onto an empty state.
+    /// These can't be undone.
+    pub base: Vec<commands::Command>,
+    /// Oldest first.
+    pub commands: Vec<commands::Command>,
+    /// How many of `commands` lead up to the present. Any after were undone, and can be redone.
+    pub present: usize,
 }
 pub struct DocumentCommandQueue {
     /// Mutable inner bits.
@@ -42,12 +262,7 @@ impl Default for DocumentCommandQueue {
         let root = command_tree.root_id().unwrap();
         Self {
             inner: Arc::new(
-                DocumentCommandQueueInner {
-                    state: queue_state::State::new(root),
-                    command_tree,
-                    root,
-                }
-                .into(),
+                DocumentCommandQueueInner::new(command_tree, queue_state::State::new(root)).into(),
             ),
             document: crate::FuzzID::default(),
         }
@@ -72,22 +287,82 @@ impl DocumentCommandQueue {
         let root = command_tree.root_id().unwrap();
         Self {
             inner: Arc::new(
-                DocumentCommandQueueInner {
-                    state: queue_state::State {
+                DocumentCommandQueueInner::new(
+                    command_tree,
+                    queue_state::State {
                         document,
                         graph: blend_graph,
                         stroke_state,
                         palette,
                         present: root,
                     },
-                    command_tree,
-                    root,
-                }
+                )
                 .into(),
             ),
             document: crate::FuzzID::default(),
         }
     }
+    /// Create a queue from a line of history, such as one read from a file. The base is replayed to
+    /// form the start of history, and then the commands up to the present.
+    ///
+    /// Fails if a command up to the present doesn't apply. Undone commands after it which don't apply are
+    /// dropped, along with any after them.
+    pub fn from_history(
+        document: state::document::Document,
+        history: History,
+    ) -> Result<Self, commands::CommandError> {
+        use commands::DoUndo;
+        let mut command_tree = slab_tree::TreeBuilder::new()
+            .with_root(commands::Command::Dummy)
+            .build();
+        let root = command_tree.root_id().unwrap();
+        let mut state = queue_state::State {
+            document,
+            ..queue_state::State::new(root)
+        };
+        for command in &history.base {
+            state.apply(DoUndo::Do(command))?;
+        }
+        let mut line = Vec::with_capacity(history.commands.len());
+        let mut parent = root;
+        for command in history.commands {
+            // Unwrap ok - the parent was just added.
+            parent = command_tree
+                .get_mut(parent)
+                .unwrap()
+                .append(command)
+                .node_id();
+            line.push(parent);
+        }
+        let present = history.present.min(line.len());
+
+        let mut inner = DocumentCommandQueueInner::new(command_tree, state);
+        for &node in &line[..present] {
+            let command = inner.command_tree.get(node).unwrap().data();
+            inner.state.apply(DoUndo::Do(command))?;
+            inner.state.present = node;
+            inner.maybe_checkpoint();
+        }
+        // Check that the redos apply, so that redoing them later can't fail.
+        if let Some(undone) = line.get(present..).filter(|undone| !undone.is_empty()) {
+            let mut state = inner.state.fork();
+            for &node in undone {
+                let command = inner.command_tree.get(node).unwrap().data();
+                if state.apply(DoUndo::Do(command)).is_err() {
+                    inner
+                        .command_tree
+                        .remove(node, slab_tree::RemoveBehavior::DropChildren);
+                    break;
+                }
+            }
+        }
+        inner.saved = inner.state.present;
+
+        Ok(Self {
+            inner: Arc::new(inner.into()),
+            document: crate::FuzzID::default(),
+        })
+    }
     #[must_use]
     pub fn id(&self) -> state::document::ID {
         self.document
@@ -98,6 +373,7 @@ impl DocumentCommandQueue {
     where
         F: FnOnce(&mut writer::CommandQueueWriter<'_>) -> T,
     {
+        let _span = tracing::trace_span!("write_with", document = %self.document).entered();
         let (result, _changed) = {
             let lock = self.inner.write();
             let mut writer = writer::CommandQueueWriter {
@@ -118,32 +394,116 @@ impl DocumentCommandQueue {
         // and we don't anticipate a broken command graph ofc...
         self.listen_from_now().forward_clone_state().unwrap()
     }
+    /// View the state as it was `undos` commands ago as a clone, without changing the present.
+    /// Clamped to the start of history. The view reports no changes.
+    ///
+    /// Replays at most a handful of commands from the nearest checkpoint, regardless of how far back it is.
+    /// Fails if those commands don't replay.
+    pub fn peek_clone_state_at(
+        &self,
+        undos: usize,
+    ) -> Result<state_reader::CommandQueueCloneLock, TraverseError> {
+        let lock = self.inner.read();
+        let target = lock
+            .command_tree
+            .get(lock.state.present)
+            .and_then(|present| present.ancestors().take(undos).last())
+            .map_or(lock.root, |node| node.node_id());
+        let shared_state = lock.materialize(target)?;
+        Ok(state_reader::CommandQueueCloneLock {
+            commands: Vec::new(),
+            shared_state,
+            inner: Arc::downgrade(&self.inner),
+        })
+    }
+    /// View the state history starts from as a clone, along with the commands that create it, such as to write
+    /// it out as a document of its own. Fails if the state can't be rebuilt.
+    pub fn peek_start(
+        &self,
+    ) -> Result<(state_reader::CommandQueueCloneLock, History), TraverseError> {
+        let lock = self.inner.read();
+        let start = lock.materialize(lock.root)?;
+        let history = History {
+            base: creation_commands(&start),
+            commands: Vec::new(),
+            present: 0,
+        };
+        let start = state_reader::CommandQueueCloneLock {
+            commands: Vec::new(),
+            shared_state: start,
+            inner: Arc::downgrade(&self.inner),
+        };
+        Ok((start, history))
+    }
+    /// View the present state as a clone, along with the line of history through it: up to `max` commands
+    /// leading to the present, up to `max` undone commands after it, and the state before them all.
+    ///
+    /// Branches off the line are left out, as undo and redo can't reach them anyway. Fails if the state before
+    /// them can't be rebuilt.
+    pub fn peek_history(
+        &self,
+        max: usize,
+    ) -> Result<(state_reader::CommandQueueCloneLock, History), TraverseError> {
+        let lock = self.inner.read();
+        // Unwrap OK - the present is always in the tree.
+        let present = lock.command_tree.get(lock.state.present).unwrap();
+        // Newest first, stopping short of the root which holds no command.
+        let mut line: Vec<_> = std::iter::once(present.node_id())
+            .chain(present.ancestors().map(|node| node.node_id()))
+            .take_while(|&node| node != lock.root)
+            .take(max)
+            .collect();
+        let base = line
+            .last()
+            // NodeRef::parent borrows the ref, not the tree.
+            .and_then(|&oldest| Some(lock.command_tree.get(oldest)?.parent()?.node_id()))
+            .unwrap_or(present.node_id());
+        line.reverse();
+        let done = line.len();
+        line.extend(
+            std::iter::successors(present.last_child(), slab_tree::NodeRef::last_child)
+                .take(max)
+                .map(|node| node.node_id()),
+        );
+
+        let base = lock.materialize(base)?;
+        let history = History {
+            base: creation_commands(&base),
+            commands: line
+                .iter()
+                // Unwrap OK - all came from the tree just now.
+                .map(|&node| lock.command_tree.get(node).unwrap().data().clone())
+                .collect(),
+            present: done,
+        };
+        let present = state_reader::CommandQueueCloneLock {
+            commands: Vec::new(),
+            shared_state: Arc::new(lock.state.fork()),
+            inner: Arc::downgrade(&self.inner),
+        };
+        Ok((present, history))
+    }
     pub fn undo_n(&self, num: usize) {
+        let _span = tracing::debug_span!("undo_n", document = %self.document, num).entered();
         let _changed = {
             // Linearly walk up the tree num steps. Todo: a more sophisticated approach, allowing for full navigation
             // of the tree!
             let mut lock = self.inner.write();
-            let DocumentCommandQueueInner {
-                command_tree,
-                root,
-                state,
-            } = &mut *lock;
-            let start = state.present;
-            let Some(ancestors) = command_tree.get(state.present).map(|this| this.ancestors())
-            else {
-                // Cursor not found - shouldn't be possible, as the tree is never trimmed!
+            if lock.read_only {
+                return;
+            }
+            let start = lock.state.present;
+            let Some(ancestors) = lock.command_tree.get(start).map(|this| this.ancestors()) else {
+                // Cursor not found - shouldn't be possible, as the present is never trimmed!
                 // This kinda means the command tree is now in an unusable state...
-                panic!(
-                    "Current Node {:?} not found in command tree!",
-                    state.present
-                );
+                panic!("Current Node {start:?} not found in command tree!");
             };
             let new_cursor = ancestors.take(num).last();
-            state.present = new_cursor.map_or(*root, |node| node.node_id());
-            let end = state.present;
+            let end = new_cursor.map_or(lock.root, |node| node.node_id());
             // Apply state changes from the commands:
-            for command in traverse(command_tree, start, end).unwrap() {
-                state.apply(command).unwrap();
+            if let Err(err) = lock.seek(end) {
+                tracing::error!("failed to move through history: {err}");
+                return;
             }
 
             // Changed if we ended up in a different spot!
@@ -151,39 +511,156 @@ impl DocumentCommandQueue {
         };
     }
     pub fn redo_n(&self, num: usize) {
+        let _span = tracing::debug_span!("redo_n", document = %self.document, num).entered();
         let _changed = {
             // Step down the tree, taking the last (most recent) child every time.
             let mut lock = self.inner.write();
-            let DocumentCommandQueueInner {
-                command_tree,
-                state,
-                ..
-            } = &mut *lock;
-            let start = state.present;
-            for _ in 0..num {
-                let Some(this) = command_tree.get(state.present) else {
-                    // Cursor not found - shouldn't be possible, as the tree is never trimmed!
-                    // This kinda means the command tree is now in an unusable state...
-                    panic!(
-                        "Current Node {:?} not found in command tree!",
-                        state.present
-                    );
-                };
-                let Some(last_child) = this.last_child() else {
-                    // We've gone as deep as we can go!
-                    return;
-                };
-                state.present = last_child.node_id();
+            if lock.read_only {
+                return;
             }
-            let end = state.present;
+            let start = lock.state.present;
+            let Some(this) = lock.command_tree.get(start) else {
+                // Cursor not found - shouldn't be possible, as the present is never trimmed!
+                // This kinda means the command tree is now in an unusable state...
+                panic!("Current Node {start:?} not found in command tree!");
+            };
+            // Stops early if we've gone as deep as we can go!
+            let end = std::iter::successors(Some(this), slab_tree::NodeRef::last_child)
+                .take(num.saturating_add(1))
+                .last()
+                .map_or(start, |node| node.node_id());
             // Apply state changes from the commands:
-            for command in traverse(command_tree, start, end).unwrap() {
-                state.apply(command).unwrap();
+            if let Err(err) = lock.seek(end) {
+                tracing::error!("failed to move through history: {err}");
+                return;
             }
             // Changed if we ended up in a different spot!
             start != end
         };
     }
+    /// Apply a command made elsewhere as though it were just written here, such as one read back from a
+    /// [journal](crate::io::journal). Fails, leaving the document as it was, if the command doesn't apply to
+    /// the present state or the document is read-only.
+    pub fn replay(&self, command: commands::Command) -> Result<(), commands::CommandError> {
+        use commands::DoUndo;
+        let mut lock = self.inner.write();
+        if lock.read_only {
+            return Err(commands::CommandError::ReadOnly);
+        }
+        let present = lock.state.present;
+        if let Err(err) = lock.state.apply(DoUndo::Do(&command)) {
+            // A scope may fail partway. Rebuild the present, rather than leave it half-changed.
+            match lock.materialize(present) {
+                Ok(state) => {
+                    lock.state = Arc::try_unwrap(state).unwrap_or_else(|shared| shared.fork());
+                }
+                Err(rebuild) => tracing::error!("failed to rebuild the present: {rebuild}"),
+            }
+            return Err(err);
+        }
+        // Unwrap ok - as above.
+        lock.state.present = lock
+            .command_tree
+            .get_mut(present)
+            .unwrap()
+            .append(command)
+            .node_id();
+        lock.maybe_checkpoint();
+        Ok(())
+    }
+    /// Forget the oldest history, so that at most `keep` commands can be undone from the present. The state
+    /// `keep` commands ago becomes the start of history, as though the queue were created with it, and redos
+    /// off the forgotten commands are lost too.
+    ///
+    /// The forgotten commands can't be undone into or replayed by
+    /// [listeners from the start](Self::listen_from_start) again, see [`Self::is_trimmed`]. They're returned
+    /// oldest first, such as to spool them to disk. Replayed in order onto the state history started with, see
+    /// [`Self::peek_start`], they recreate the new start.
+    /// Listeners that were left behind see [`ListenerError::Trimmed`], and if the saved state was among those
+    /// forgotten the document is dirty until saved again.
+    pub fn trim_history(&self, keep: usize) -> Vec<commands::Command> {
+        let _span = tracing::debug_span!("trim_history", document = %self.document, keep).entered();
+        self.inner.write().trim(keep)
+    }
+    /// Reject all changes to the document, or accept them again. While read-only, writes are rolled back as
+    /// soon as they are made without being recorded, and undo and redo do nothing.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.inner.write().read_only = read_only;
+    }
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.inner.read().read_only
+    }
+    /// Whether history has been [trimmed](Self::trim_history), such that its start is no longer the state
+    /// the queue was created with.
+    #[must_use]
+    pub fn is_trimmed(&self) -> bool {
+        self.inner.read().trimmed
+    }
+    /// Estimated bytes of memory held by each command that can be undone, newest first, see
+    /// [`commands::Command::memory`].
+    #[must_use]
+    pub fn undo_memory(&self, points: &crate::repositories::points::Points) -> Vec<usize> {
+        let lock = self.inner.read();
+        let Some(present) = lock.command_tree.get(lock.state.present) else {
+            return Vec::new();
+        };
+        std::iter::once(present.node_id())
+            .chain(present.ancestors().map(|node| node.node_id()))
+            .take_while(|&node| node != lock.root)
+            .filter_map(|node| lock.command_tree.get(node))
+            .map(|node| node.data().memory(points))
+            .collect()
+    }
+    /// Count how many commands can be undone and redone from the present, respectively.
+    ///
+    /// The redo count follows the same path as [`Self::redo_n`], taking the most recent branch.
+    #[must_use]
+    pub fn history_depth(&self) -> (usize, usize) {
+        let lock = self.inner.read();
+        let Some(present) = lock.command_tree.get(lock.state.present) else {
+            // See `undo_n` - shouldn't be possible.
+            return (0, 0);
+        };
+        let undo = present.ancestors().count();
+        let redo =
+            std::iter::successors(present.last_child(), slab_tree::NodeRef::last_child).count();
+
+        (undo, redo)
+    }
+    /// Whether the present state differs from the one last saved, or from the state the queue was created
+    /// with if it never was. Undoing back to the saved state makes the document clean again, unless it was
+    /// [changed outside of history](Self::mark_changed).
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        let inner = self.inner.read();
+        inner.state.present != inner.saved || inner.changed
+    }
+    /// Mark the document dirty for a change outside of history, such as to its
+    /// [metadata](state::document::Metadata), which can't be undone. It stays dirty until next saved.
+    pub fn mark_changed(&self) {
+        self.inner.write().changed = true;
+    }
+    /// Mark the state seen by `state` as saved. Only for writes to the document's own path - an autosave or
+    /// copy elsewhere doesn't save the user's work, and so shouldn't mark it.
+    pub fn mark_saved(&self, state: &state_reader::CommandQueueCloneLock) {
+        let mut inner = self.inner.write();
+        inner.saved = state.shared_state.present;
+        inner.changed = false;
+    }
+    /// Collect statistics of the present state and the history leading to it.
+    /// See [`state_reader::CommandQueueStateReader::statistics`].
+    #[must_use]
+    pub fn statistics(
+        &self,
+        points: &crate::repositories::points::Points,
+    ) -> state_reader::DocumentStatistics {
+        use state_reader::CommandQueueStateReader;
+        state_reader::DocumentStatistics {
+            history: Some(self.history_depth()),
+            ..self.peek_clone_state().statistics(points)
+        }
+    }
     /// Create a listener that starts at the beginning of history.
     #[must_use]
     pub fn listen_from_start(&self) -> DocumentCommandListener {
@@ -194,6 +671,16 @@ impl DocumentCommandQueue {
             inner: std::sync::Arc::downgrade(&self.inner),
         }
     }
+    /// Create a listener that starts at the state last saved, see [`Self::is_dirty`].
+    #[must_use]
+    pub fn listen_from_saved(&self) -> DocumentCommandListener {
+        let start = self.inner.read().saved;
+        DocumentCommandListener {
+            _document: self.document,
+            cursor: start,
+            inner: std::sync::Arc::downgrade(&self.inner),
+        }
+    }
     /// Create a listener that will only see new activity
     #[must_use]
     pub fn listen_from_now(&self) -> DocumentCommandListener {
@@ -212,6 +699,10 @@ pub enum ListenerError {
     // Hints that something has gone horribly wrong internally!
     #[error("tree malformed: {}", .0)]
     TreeMalformed(TraverseError),
+    /// The listener's point in time is older than any history kept, see
+    /// [`DocumentCommandQueue::trim_history`].
+    #[error("history since the listener last looked was trimmed")]
+    Trimmed,
 }
 pub struct DocumentCommandListener {
     _document: crate::state::document::ID,
@@ -221,6 +712,17 @@ pub struct DocumentCommandListener {
     inner: std::sync::Weak<parking_lot::RwLock<DocumentCommandQueueInner>>,
 }
 impl DocumentCommandListener {
+    /// The changes from this listener's point in time to `target`.
+    fn changes_to<'t>(
+        &self,
+        tree: &'t slab_tree::Tree<commands::Command>,
+        target: slab_tree::NodeId,
+    ) -> Result<TreeTraverser<'t, commands::Command>, ListenerError> {
+        if tree.get(self.cursor).is_none() {
+            return Err(ListenerError::Trimmed);
+        }
+        traverse(tree, self.cursor, target).map_err(ListenerError::TreeMalformed)
+    }
     /// Locks the shared state, without forwarding this listener's point in time.
     /// See [`state_reader::CommandQueueLock`]
     pub fn peek_lock_state(&self) -> Result<state_reader::CommandQueueReadLock, ListenerError> {
@@ -241,11 +743,10 @@ impl DocumentCommandListener {
         let inner = self.inner.upgrade().ok_or(ListenerError::DocumentClosed)?;
         let lock = inner.read();
         // Eagerly collect command traversal.
-        let commands: Vec<state_reader::OwnedDoUndo<_>> =
-            traverse(&lock.command_tree, self.cursor, lock.state.present)
-                .map_err(ListenerError::TreeMalformed)?
-                .map(Into::into)
-                .collect();
+        let commands: Vec<state_reader::OwnedDoUndo<_>> = self
+            .changes_to(&lock.command_tree, lock.state.present)?
+            .map(Into::into)
+            .collect();
 
         Ok(state_reader::CommandQueueCloneLock {
             inner: self.inner.clone(),
@@ -266,6 +767,47 @@ impl DocumentCommandListener {
         self.cursor = state.shared_state.present;
         Ok(state)
     }
+    /// Like [`Self::forward_clone_state`], but only advances up to `commands` steps towards the present.
+    /// Used to replay history a piece at a time, where every step is seen as it was back then.
+    pub fn forward_clone_state_by(
+        &'_ mut self,
+        commands: usize,
+    ) -> Result<state_reader::CommandQueueCloneLock, ListenerError> {
+        let inner = self.inner.upgrade().ok_or(ListenerError::DocumentClosed)?;
+        let lock = inner.read();
+        let mut traverser = self.changes_to(&lock.command_tree, lock.state.present)?;
+        let changes: Vec<state_reader::OwnedDoUndo<_>> =
+            traverser.by_ref().take(commands).map(Into::into).collect();
+        let target = traverser.cur.node_id();
+
+        let shared_state = lock
+            .materialize(target)
+            .map_err(ListenerError::TreeMalformed)?;
+        self.cursor = target;
+        Ok(state_reader::CommandQueueCloneLock {
+            inner: self.inner.clone(),
+            commands: changes,
+            shared_state,
+        })
+    }
+    /// Bring this listener up-to-date, returning how the state changed since it last looked, without cloning
+    /// the state. See [`deltas`].
+    pub fn forward_deltas(&mut self) -> Result<Vec<deltas::Delta>, ListenerError> {
+        let inner = self.inner.upgrade().ok_or(ListenerError::DocumentClosed)?;
+        let lock = inner.read();
+        let mut changes = Vec::new();
+        for change in self.changes_to(&lock.command_tree, lock.state.present)? {
+            deltas::push_deltas(change, &mut changes);
+        }
+        self.cursor = lock.state.present;
+        Ok(changes)
+    }
+    /// Whether this listener's point in time is the state last saved.
+    pub fn is_at_saved(&self) -> Result<bool, ListenerError> {
+        let inner = self.inner.upgrade().ok_or(ListenerError::DocumentClosed)?;
+        let is_saved = inner.read().saved == self.cursor;
+        Ok(is_saved)
+    }
     /// Moves the cursor forward up-to-date with the documnet, not reporting the changes.
     /// Returns `true` if any change occured.
     pub fn forward(&mut self) -> Result<bool, ListenerError> {
@@ -381,6 +923,8 @@ pub enum TraverseError {
     Disconnected,
     #[error("ID not present in tree")]
     NotFound,
+    #[error("history does not replay: {0}")]
+    Replay(#[from] commands::CommandError),
 }
 /// Create an iterator that traverses the shortest path between start and end nodes, or None if the start
 /// and end nodes are not from the same tree.
@@ -551,3 +1095,287 @@ mod traversal_test {
         assert!(traverse(&tree, id_of!(6), id_of!(9)).is_err());
     }
 }
+#[cfg(test)]
+mod history_test {
+    use super::DocumentCommandQueue;
+    use crate::color::Color;
+
+    fn push_command(queue: &DocumentCommandQueue) {
+        queue.write_with(|writer| {
+            writer.palette().insert(Color::BLACK);
+        });
+    }
+    #[test]
+    fn history_depth() {
+        let queue = DocumentCommandQueue::new();
+        assert_eq!(queue.history_depth(), (0, 0));
+
+        for _ in 0..3 {
+            push_command(&queue);
+        }
+        assert_eq!(queue.history_depth(), (3, 0));
+
+        queue.undo_n(2);
+        assert_eq!(queue.history_depth(), (1, 2));
+        queue.redo_n(1);
+        assert_eq!(queue.history_depth(), (2, 1));
+
+        // Writing after an undo branches, and the redo path follows the new branch.
+        queue.undo_n(1);
+        push_command(&queue);
+        assert_eq!(queue.history_depth(), (2, 0));
+
+        // Overshoot is clamped to the root.
+        queue.undo_n(10);
+        assert_eq!(queue.history_depth(), (0, 2));
+    }
+    #[test]
+    fn dirty() {
+        let queue = DocumentCommandQueue::new();
+        assert!(!queue.is_dirty());
+        push_command(&queue);
+        assert!(queue.is_dirty());
+        // Back to where it started.
+        queue.undo_n(1);
+        assert!(!queue.is_dirty());
+
+        push_command(&queue);
+        queue.mark_saved(&queue.peek_clone_state());
+        assert!(!queue.is_dirty());
+        queue.undo_n(1);
+        assert!(queue.is_dirty());
+        queue.redo_n(1);
+        assert!(!queue.is_dirty());
+
+        let saved = queue.listen_from_saved();
+        assert_eq!(saved.is_at_saved(), Ok(true));
+        push_command(&queue);
+        assert_eq!(saved.is_at_saved(), Ok(true));
+        // Saving again leaves it behind.
+        queue.mark_saved(&queue.peek_clone_state());
+        assert_eq!(saved.is_at_saved(), Ok(false));
+
+        // Changes outside of history can't be undone away, only saved.
+        queue.mark_changed();
+        assert!(queue.is_dirty());
+        queue.undo_n(1);
+        queue.redo_n(1);
+        assert!(queue.is_dirty());
+        queue.mark_saved(&queue.peek_clone_state());
+        assert!(!queue.is_dirty());
+    }
+    #[test]
+    fn checkpoints() {
+        use super::{state_reader::CommandQueueStateReader, CHECKPOINT_INTERVAL};
+        fn colors(reader: &impl CommandQueueStateReader) -> usize {
+            reader.palette().iter().count()
+        }
+        let queue = DocumentCommandQueue::new();
+        let total = CHECKPOINT_INTERVAL * 3 + 5;
+        for _ in 0..total {
+            push_command(&queue);
+        }
+        assert!(queue.inner.read().checkpoints.len() > 1);
+
+        // Every point in history, seen from the present.
+        for undos in 0..=total {
+            assert_eq!(
+                colors(&queue.peek_clone_state_at(undos).unwrap()),
+                total - undos
+            );
+        }
+        assert_eq!(colors(&queue.peek_clone_state_at(total + 10).unwrap()), 0);
+        assert_eq!(queue.history_depth(), (total, 0));
+
+        // Long jumps, which start from a checkpoint, land on the same state as short ones.
+        queue.undo_n(total - 3);
+        assert_eq!(colors(&queue.peek_clone_state()), 3);
+        queue.redo_n(CHECKPOINT_INTERVAL * 2);
+        assert_eq!(
+            colors(&queue.peek_clone_state()),
+            3 + CHECKPOINT_INTERVAL * 2
+        );
+        queue.redo_n(total);
+        assert_eq!(colors(&queue.peek_clone_state()), total);
+        assert_eq!(queue.history_depth(), (total, 0));
+    }
+    #[test]
+    fn recreate_from_checkpoint() {
+        use super::{state_reader::CommandQueueStateReader, CHECKPOINT_INTERVAL};
+        use crate::state::graph::{ColorTag, LeafType, Location};
+        let queue = DocumentCommandQueue::new();
+        let leaf = queue.write_with(|writer| {
+            writer
+                .graph()
+                .add_leaf(LeafType::Note, Location::IndexIntoRoot(0), "Leaf")
+                .unwrap()
+        });
+        // Not history, so replaying the creation alone would lose them.
+        queue.write_with(|writer| {
+            let mut graph = writer.graph();
+            *graph.name_mut(leaf.into()).unwrap() = "Renamed".to_owned();
+            *graph.tag_mut(leaf.into()).unwrap() = ColorTag::Red;
+        });
+        let total = CHECKPOINT_INTERVAL * 2;
+        for _ in 0..total {
+            push_command(&queue);
+        }
+        // Far enough back to start over from the root checkpoint, which predates everything.
+        queue.undo_n(total + 1);
+        queue.redo_n(1);
+        let state = queue.peek_clone_state();
+        let node = state.graph().get(leaf).unwrap();
+        assert_eq!((node.name(), node.tag()), ("Renamed", ColorTag::Red));
+        queue.redo_n(total);
+        assert_eq!(queue.peek_clone_state().palette().iter().count(), total);
+    }
+    #[test]
+    fn trim_history() {
+        use super::{state_reader::CommandQueueStateReader, ListenerError, CHECKPOINT_INTERVAL};
+        fn colors(reader: &impl CommandQueueStateReader) -> usize {
+            reader.palette().iter().count()
+        }
+        let queue = DocumentCommandQueue::new();
+        let mut from_start = queue.listen_from_start();
+        // An undone branch, off the part to be forgotten.
+        for _ in 0..10 {
+            push_command(&queue);
+        }
+        queue.undo_n(8);
+        let total = CHECKPOINT_INTERVAL * 3;
+        for _ in 0..total {
+            push_command(&queue);
+        }
+        let mut recent = queue.listen_from_now();
+        assert_eq!(queue.history_depth(), (total + 2, 0));
+        assert!(!queue.is_trimmed());
+        let memory = queue.undo_memory(&crate::repositories::points::Points::default());
+        assert_eq!(memory.len(), total + 2);
+        assert!(memory
+            .iter()
+            .all(|&bytes| bytes >= std::mem::size_of::<crate::commands::Command>()));
+
+        let trimmed = queue.trim_history(5);
+        assert!(queue.is_trimmed());
+        assert_eq!(trimmed.len(), total - 3);
+        assert_eq!(queue.history_depth(), (5, 0));
+        assert_eq!(colors(&queue.peek_clone_state()), total + 2);
+        let (start, history) = queue.peek_start().unwrap();
+        assert_eq!(colors(&start), total - 3);
+        assert!(history.commands.is_empty() && !history.base.is_empty());
+        // Undo stops at the new start.
+        assert_eq!(colors(&queue.peek_clone_state_at(100).unwrap()), total - 3);
+        queue.undo_n(100);
+        assert_eq!(queue.history_depth(), (0, 5));
+        assert_eq!(colors(&queue.peek_clone_state()), total - 3);
+        queue.redo_n(5);
+        {
+            let inner = queue.inner.read();
+            assert!(inner
+                .checkpoints
+                .keys()
+                .all(|&node| inner.command_tree.get(node).is_some()));
+        }
+
+        // The forgotten commands recreate the new start.
+        let replayed = DocumentCommandQueue::new();
+        for command in trimmed {
+            replayed.replay(command).unwrap();
+        }
+        assert_eq!(colors(&replayed.peek_clone_state()), total - 3);
+
+        assert_eq!(recent.forward_deltas().map(|deltas| deltas.len()), Ok(0));
+        assert!(matches!(
+            from_start.forward_deltas(),
+            Err(ListenerError::Trimmed)
+        ));
+        // Nothing more to forget.
+        assert!(queue.trim_history(5).is_empty());
+        assert!(queue.trim_history(100).is_empty());
+    }
+    #[test]
+    fn replay() {
+        use super::state_reader::CommandQueueStateReader;
+        let queue = DocumentCommandQueue::new();
+        for _ in 0..5 {
+            push_command(&queue);
+        }
+        let mut listener = queue.listen_from_start();
+        let mut seen = 0;
+        for step in [2, 2, 2] {
+            let lock = listener.forward_clone_state_by(step).unwrap();
+            let changes = lock.changes().count();
+            seen += changes;
+            // The state is that of the last change seen, not the present.
+            assert_eq!(lock.palette().iter().count(), seen);
+        }
+        assert_eq!(seen, 5);
+        // Caught up, nothing left to replay.
+        assert_eq!(
+            listener
+                .forward_clone_state_by(2)
+                .unwrap()
+                .changes()
+                .count(),
+            0
+        );
+    }
+    #[test]
+    fn seeded_replay() {
+        use crate::state::graph::{LeafType, Location};
+        let build = || {
+            let queue = DocumentCommandQueue::new();
+            queue.write_with(|writer| {
+                let mut graph = writer.graph();
+                [0, 1].map(|idx| {
+                    graph
+                        .add_leaf(LeafType::Note, Location::IndexIntoRoot(idx), "Leaf")
+                        .unwrap()
+                })
+            })
+        };
+        // Seed unused by other tests, which run alongside.
+        let first = crate::id::with_seed(100, build).unwrap();
+        let again = crate::id::with_seed(100, build).unwrap();
+        assert_eq!(first, again);
+        // Apart from the seed, the IDs are unique as ever.
+        let unseeded = build();
+        assert!(unseeded.iter().all(|id| !first.contains(id)));
+    }
+    #[test]
+    fn replay_command() {
+        use super::state_reader::CommandQueueStateReader;
+        use crate::commands::{Command, MetaCommand, PaletteCommand, ScopeType};
+        let queue = DocumentCommandQueue::new();
+        let added = |idx| -> Command {
+            PaletteCommand::Added {
+                target: crate::color::PaletteIndex(idx),
+                initial_color: Color::BLACK,
+            }
+            .into()
+        };
+        queue.replay(added(0)).unwrap();
+        assert_eq!(queue.history_depth(), (1, 0));
+        // Fails partway, leaving nothing behind.
+        let scope = MetaCommand::Scope(ScopeType::Atoms, [added(1), added(0)].into());
+        assert!(queue.replay(scope.into()).is_err());
+        assert_eq!(queue.history_depth(), (1, 0));
+        assert_eq!(queue.peek_clone_state().palette().iter().count(), 1);
+    }
+    #[test]
+    fn read_only() {
+        use super::state_reader::CommandQueueStateReader;
+        let queue = DocumentCommandQueue::new();
+        push_command(&queue);
+        queue.set_read_only(true);
+
+        push_command(&queue);
+        queue.undo_n(1);
+        assert_eq!(queue.history_depth(), (1, 0));
+        assert_eq!(queue.peek_clone_state().palette().iter().count(), 1);
+
+        queue.set_read_only(false);
+        push_command(&queue);
+        assert_eq!(queue.peek_clone_state().palette().iter().count(), 2);
+    }
+}
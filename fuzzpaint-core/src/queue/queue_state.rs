@@ -40,6 +40,11 @@ impl State {
 impl CommandConsumer<Command> for State {
     fn apply(&mut self, action: DoUndo<Command>) -> Result<(), CommandError> {
         match action {
+            DoUndo::Do(Command::Document(..)) | DoUndo::Undo(Command::Document(..)) => {
+                // Unwrap ok - guarded by match arm.
+                self.document
+                    .apply(action.filter_map(Command::document).unwrap())
+            }
             DoUndo::Do(Command::Graph(..)) | DoUndo::Undo(Command::Graph(..)) => {
                 // Unwrap ok - guarded by match arm.
                 self.graph.apply(action.filter_map(Command::graph).unwrap())
@@ -1,13 +1,77 @@
 //! Views into the document state represented by a command queue.
 
 use crate::{commands, state};
+
+/// Contents of a single stroke layer, see [`DocumentStatistics`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LayerStatistics {
+    pub id: state::graph::LeafID,
+    pub name: String,
+    /// Strokes which have not been undone.
+    pub strokes: usize,
+    /// Points across all of `strokes`.
+    pub points: usize,
+}
+/// Summary of the contents of a document.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct DocumentStatistics {
+    /// Every stroke layer in the graph, in arbitrary order.
+    pub layers: Vec<LayerStatistics>,
+    /// Sum of the strokes of every layer.
+    pub strokes: usize,
+    /// Sum of the points of every layer.
+    pub points: usize,
+    /// Bytes of point data referenced by the strokes. Strokes may share point data,
+    /// in which case it is counted more than once.
+    pub point_bytes: usize,
+    /// Count of commands that may be undone and redone, if known. A reader only sees the state,
+    /// so this is filled by [`super::DocumentCommandQueue::statistics`].
+    pub history: Option<(usize, usize)>,
+}
+
 pub trait CommandQueueStateReader {
+    fn document(&self) -> &state::document::Document;
     fn graph(&self) -> &state::graph::BlendGraph;
     fn stroke_collections(&self) -> &state::stroke_collection::StrokeCollectionState;
     fn palette(&self) -> &state::palette::Palette;
 
     fn changes(&'_ self) -> impl Iterator<Item = commands::DoUndo<'_, commands::Command>> + '_;
     fn has_changes(&self) -> bool;
+
+    /// Collect statistics of the document's contents. Point counts are looked up in `points`,
+    /// and strokes whose points are missing from it count as having none.
+    fn statistics(&self, points: &crate::repositories::points::Points) -> DocumentStatistics {
+        let mut statistics = DocumentStatistics::default();
+        for (id, data) in self.graph().iter() {
+            let Some(state::graph::LeafType::StrokeLayer { collection, .. }) = data.leaf() else {
+                continue;
+            };
+            let Ok(id) = state::graph::LeafID::try_from(id) else {
+                // Checked by `leaf`
+                continue;
+            };
+            let mut layer = LayerStatistics {
+                id,
+                name: data.name().to_owned(),
+                strokes: 0,
+                points: 0,
+            };
+            // Missing if the collection was undone, leaving the layer empty.
+            if let Some(collection) = self.stroke_collections().get(*collection) {
+                for stroke in collection.iter_active() {
+                    layer.strokes += 1;
+                    if let Some(summary) = points.summary_of(stroke.point_collection) {
+                        layer.points += summary.len;
+                        statistics.point_bytes += summary.elements() * std::mem::size_of::<u32>();
+                    }
+                }
+            }
+            statistics.strokes += layer.strokes;
+            statistics.points += layer.points;
+            statistics.layers.push(layer);
+        }
+        statistics
+    }
 }
 impl<T> CommandQueueStateReader for &T
 where
@@ -16,6 +80,9 @@ where
     fn changes(&'_ self) -> impl Iterator<Item = commands::DoUndo<'_, commands::Command>> + '_ {
         (*self).changes()
     }
+    fn document(&self) -> &state::document::Document {
+        (*self).document()
+    }
     fn graph(&self) -> &state::graph::BlendGraph {
         (*self).graph()
     }
@@ -115,6 +182,9 @@ impl CommandQueueStateReader for CommandQueueCloneLock {
             OwnedDoUndo::Undo(c) => commands::DoUndo::Undo(c),
         })
     }
+    fn document(&self) -> &state::document::Document {
+        &self.shared_state.document
+    }
     fn graph(&self) -> &state::graph::BlendGraph {
         &self.shared_state.graph
     }
@@ -128,3 +198,90 @@ impl CommandQueueStateReader for CommandQueueCloneLock {
         !self.commands.is_empty()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::CommandQueueStateReader;
+    use crate::{queue::DocumentCommandQueue, repositories::points::Points, state};
+
+    /// Add a layer holding `strokes` strokes of `points` points each.
+    fn add_layer(queue: &DocumentCommandQueue, repo: &Points, strokes: usize, points: usize) {
+        let elements = vec![0u32; points * 2];
+        let slice =
+            crate::stroke::StrokeSlice::new(&elements, crate::stroke::Archetype::POSITION).unwrap();
+        let brush = state::StrokeBrushSettings {
+            brush: crate::brush::UniqueID([0; 32]),
+            color_modulate: crate::color::ColorOrPalette::BLACK,
+            size_mul: crate::util::FiniteF32::new(1.0).unwrap(),
+            is_eraser: false,
+            is_smudge: false,
+            orientation: state::StampOrientation::Random,
+            scatter: state::Scatter::default(),
+            dual: None,
+            taper: state::Taper::default(),
+            dynamics: state::Dynamics::default(),
+            spacing_px: crate::util::FiniteF32::new(1.0).unwrap(),
+        };
+        queue.write_with(|writer| {
+            let mut collections = writer.stroke_collections();
+            let collection = collections.insert();
+            let mut collection_writer = collections.get_mut(collection).unwrap();
+            for _ in 0..strokes {
+                collection_writer.push_back(brush, repo.insert(slice).unwrap(), None);
+            }
+            writer
+                .graph()
+                .add_leaf(
+                    state::graph::LeafType::StrokeLayer {
+                        blend: crate::blend::Blend::default(),
+                        collection,
+                        inner_transform: state::transform::Similarity::default(),
+                        outer_transform: state::transform::Matrix::default(),
+                    },
+                    state::graph::Location::IndexIntoRoot(0),
+                    "Layer",
+                )
+                .unwrap();
+        });
+    }
+    /// Totals are always the sum of the layers.
+    fn assert_consistent(statistics: &super::DocumentStatistics) {
+        let strokes: usize = statistics.layers.iter().map(|l| l.strokes).sum();
+        let points: usize = statistics.layers.iter().map(|l| l.points).sum();
+        assert_eq!(statistics.strokes, strokes);
+        assert_eq!(statistics.points, points);
+    }
+    #[test]
+    fn statistics() {
+        let repo = Points::default();
+        let queue = DocumentCommandQueue::new();
+        let statistics = queue.statistics(&repo);
+        assert_eq!(statistics.layers.len(), 0);
+        assert_eq!(statistics.history, Some((0, 0)));
+
+        add_layer(&queue, &repo, 2, 3);
+        add_layer(&queue, &repo, 1, 5);
+        let statistics = queue.statistics(&repo);
+        assert_consistent(&statistics);
+        assert_eq!(statistics.layers.len(), 2);
+        assert_eq!(statistics.strokes, 3);
+        assert_eq!(statistics.points, 2 * 3 + 5);
+        // Two f32 elements per point.
+        assert_eq!(statistics.point_bytes, (2 * 3 + 5) * 2 * 4);
+        assert_eq!(statistics.history, Some((2, 0)));
+
+        // Undoing removes the layer, and with it the strokes.
+        queue.undo_n(1);
+        let statistics = queue.statistics(&repo);
+        assert_consistent(&statistics);
+        assert_eq!(statistics.layers.len(), 1);
+        assert_eq!(statistics.strokes, 2);
+        assert_eq!(statistics.history, Some((1, 1)));
+
+        // Reading through a detached view gives the same contents, without history.
+        let clone = queue.peek_clone_state();
+        let detached = clone.statistics(&repo);
+        assert_eq!(detached.history, None);
+        assert_eq!(detached.layers, statistics.layers);
+    }
+}
@@ -23,11 +23,23 @@ pub struct CommandQueueWriter<'a> {
 // Obviously not great, but sound at least.
 impl Drop for CommandQueueWriter<'_> {
     fn drop(&mut self) {
-        use crate::commands;
+        use crate::commands::{self, CommandConsumer};
         // Skip if nothing to write.
         if self.commands.is_empty() {
             return;
         }
+        if self.lock.read_only {
+            tracing::warn!(
+                count = self.commands.len(),
+                "rejected commands to a read-only document"
+            );
+            // Newest first, to leave the state as it was before the write.
+            for command in self.commands.drain(..).rev() {
+                // Just applied, so they can't fail to undo.
+                let _ = self.lock.state.apply(commands::DoUndo::Undo(&command));
+            }
+            return;
+        }
 
         // We always write exactly one command - bundle into one if more!
         // If panic exit, write as a panic scope (even if the scope is just one command long)
@@ -51,7 +63,7 @@ impl Drop for CommandQueueWriter<'_> {
         // Weird borrow issue :P
         let present = self.lock.state.present;
 
-        log::trace!("Writing new command: {:#?}", command);
+        tracing::trace!(?command, "writing new command");
 
         // Write the command or scope (as last child, as that corresponds to "latest change")
         // and update cursor.
@@ -65,6 +77,7 @@ impl Drop for CommandQueueWriter<'_> {
             .append(command)
             .node_id();
         self.lock.state.present = new;
+        self.lock.maybe_checkpoint();
     }
 }
 impl CommandQueueWriter<'_> {
@@ -72,6 +85,17 @@ impl CommandQueueWriter<'_> {
     pub fn changed(&self) -> bool {
         !self.commands.is_empty()
     }
+    pub fn document(
+        &'_ mut self,
+    ) -> crate::state::document::writer::Writer<
+        '_,
+        &mut smallvec::SmallVec<[crate::commands::Command; 1]>,
+    > {
+        crate::state::document::writer::Writer::new(
+            &mut self.commands,
+            &mut self.lock.state.document,
+        )
+    }
     pub fn graph(
         &'_ mut self,
     ) -> crate::state::graph::writer::GraphWriter<
@@ -109,6 +133,9 @@ impl super::state_reader::CommandQueueStateReader for CommandQueueWriter<'_> {
     ) -> impl Iterator<Item = crate::commands::DoUndo<'_, crate::commands::Command>> + '_ {
         self.commands.iter().map(crate::commands::DoUndo::Do)
     }
+    fn document(&self) -> &crate::state::document::Document {
+        &self.lock.state.document
+    }
     fn graph(&self) -> &crate::state::graph::BlendGraph {
         &self.lock.state.graph
     }
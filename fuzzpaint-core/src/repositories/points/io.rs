@@ -4,6 +4,10 @@ use std::collections::VecDeque;
 #[allow(clippy::wildcard_imports)]
 use super::*;
 
+/// The `DICT ptls` is read in place rather than into memory, as it's by far the largest chunk, so it can't be
+/// [upgraded](crate::io::migrate) - only read by a reader of its version.
+const PTLS_WRITE_VERSION: crate::io::Version = crate::io::Version(0, 0, 0);
+
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C, packed)]
 struct DictMetadata {
@@ -98,13 +102,11 @@ impl super::Points {
     ) -> Result<crate::io::id::FileLocalInterner<PointCollectionIDMarker>, WriteError> {
         use crate::io::{
             riff::{encode::SizedBinaryChunkWriter, ChunkID},
-            OrphanMode, Version,
+            OrphanMode,
         };
         use az::CheckedAs;
         use std::io::{IoSlice, Write};
 
-        const PTLS_WRITE_VERSION: Version = Version(0, 0, 0);
-
         let mut file_ids = crate::io::id::FileLocalInterner::default();
         // Collect all uniqe entries and allocs.
         let allocation_entries: Result<Vec<_>, WriteError> = ids
@@ -190,8 +192,9 @@ impl super::Points {
         compile_error!("FIXME!");
 
         // Collect and write bulk points
+        // Held until written, as the slices borrow from it.
+        let slabs = self.slabs.read();
         let data_slices: Result<Vec<IoSlice<'_>>, ()> = {
-            let slabs = self.slabs.read();
             allocation_entries
                 .iter()
                 .map(|entry| {
@@ -230,12 +233,14 @@ impl super::Points {
     where
         R: std::io::Read + crate::io::common::SoftSeek,
     {
-        use crate::io::{common::SoftSeek, id::ProcessLocalInterner, Version};
+        use crate::io::{common::SoftSeek, id::ProcessLocalInterner, riff::ChunkID};
         use az::CheckedAs;
         use std::io::{Error as IOError, Read};
-        if dict.version() != Version::CURRENT {
-            // TODO lol
-            return Err(IOError::other(anyhow::anyhow!("bad ver")));
+        if dict.version() != PTLS_WRITE_VERSION {
+            return Err(crate::io::migrate::unsupported(
+                ChunkID::PTLS,
+                dict.version(),
+            ));
         }
         // There's metas, but they're not the right size.
         // (this allows arbitrary size when there are zero entries - this is fine)
@@ -4,7 +4,10 @@
 //! Thus, it makes sense that their repository implementation should recieve the most care.
 //! For now, the collection just grows unboundedly and no eviction is done -
 //! however, the API is constructed to allow for smart in-memory compression or dumping old
-//! data to disk in the future.
+//! data to disk in the future. Collections with live [`SharedCollection`] handles must never be evicted.
+//!
+//! Readers [pin](BorrowedStrokeReadLock) the slab they read from. To evict a slab, first make it unreachable
+//! to new readers, then [wait](slab::Slab::wait_unpinned) for existing ones before [freeing](slab::Slab::free) it.
 
 pub mod io;
 mod slab;
@@ -49,17 +52,18 @@ pub struct PointCollectionIDMarker;
 pub type PointCollectionID = crate::FuzzID<PointCollectionIDMarker>;
 
 /// A handle for reading a collection of points. Can be cloned and shared between threads,
-/// however take care not to allow it to become leaked - it will not allow the resources
-/// to be reclaimed by the repository for the duration of the lock's lifetime.
+/// however take care not to allow it to become leaked - it pins the slab holding the points,
+/// which can't be reclaimed by the repository for the duration of the lock's lifetime.
 #[derive(Clone)]
 pub struct BorrowedStrokeReadLock {
-    stroke: StrokeSlice<'static>,
+    elements: slab::PinnedSlice<u32>,
+    archetype: Archetype,
 }
 impl BorrowedStrokeReadLock {
-    // we want to seal the fact that this is 'static. Can't be done with deref!
     #[must_use]
-    pub fn get<'a>(&'a self) -> StrokeSlice<'a> {
-        self.stroke
+    pub fn get(&self) -> StrokeSlice<'_> {
+        // Unwrap ok - checked when the lock was made.
+        StrokeSlice::new(self.elements.get(), self.archetype).unwrap()
     }
 }
 
@@ -90,11 +94,57 @@ struct PointCollectionAllocInfo {
 // 4MiB of floats
 pub const SLAB_ELEMENT_COUNT: usize = 1024 * 1024;
 type ElementSlab = slab::Slab<u32, SLAB_ELEMENT_COUNT>;
+/// The most points of the given archetype that fit in a single collection. Longer strokes must be split into
+/// [segments](StrokeSlice::segments) of at most this many points.
+#[must_use]
+pub const fn max_points(archetype: Archetype) -> usize {
+    // Empty archetype holds nothing, so any number of points fits.
+    match SLAB_ELEMENT_COUNT.checked_div(archetype.elements()) {
+        Some(max) => max,
+        None => usize::MAX,
+    }
+}
+
+/// A counted reference to a collection. Collections are immutable, so sharing one is as good as
+/// copying it - strokes copied out of a document refer to the same collection, without duplicating its points.
+/// The collection is kept alive for as long as any handle to it is, even if no document refers to it anymore.
+pub struct SharedCollection<'repo> {
+    repo: &'repo Points,
+    id: PointCollectionID,
+}
+impl SharedCollection<'_> {
+    #[must_use]
+    pub fn id(&self) -> PointCollectionID {
+        self.id
+    }
+}
+impl Clone for SharedCollection<'_> {
+    fn clone(&self) -> Self {
+        *self.repo.shares.lock().entry(self.id).or_default() += 1;
+        Self {
+            repo: self.repo,
+            id: self.id,
+        }
+    }
+}
+impl Drop for SharedCollection<'_> {
+    fn drop(&mut self) {
+        let mut shares = self.repo.shares.lock();
+        if let hashbrown::hash_map::Entry::Occupied(mut count) = shares.entry(self.id) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+            }
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct Points {
     slabs: parking_lot::RwLock<Vec<ElementSlab>>,
     allocs: parking_lot::RwLock<hashbrown::HashMap<PointCollectionID, PointCollectionAllocInfo>>,
+    /// Count of live [`SharedCollection`]s for each collection. Absent means zero.
+    shares: parking_lot::Mutex<hashbrown::HashMap<PointCollectionID, usize>>,
 }
 impl Points {
     /// Get the memory usage of resident data (uncompressed in RAM), in bytes, and the capacity.
@@ -110,7 +160,7 @@ impl Points {
         (usage, capacity)
     }
     /// Insert the collection into the repository, yielding a unique ID.
-    /// Fails if the length of the collection caintains > [`SLAB_ELEMENT_COUNT`] f32 elements
+    /// Fails if the length of the collection caintains > [`SLAB_ELEMENT_COUNT`] f32 elements, see [`max_points`].
     #[must_use = "the returned ID is needed to fetch the data in the future"]
     pub fn insert(&self, collection: StrokeSlice) -> Option<PointCollectionID> {
         let elements = collection.elements();
@@ -169,6 +219,17 @@ impl Points {
     pub fn summary_of(&self, id: PointCollectionID) -> Option<CollectionSummary> {
         self.alloc_of(id).map(|alloc| alloc.summary)
     }
+    /// Take a [shared handle](SharedCollection) to the collection. None if the ID is not known to this repository.
+    pub fn share(&self, id: PointCollectionID) -> Option<SharedCollection<'_>> {
+        self.alloc_of(id)?;
+        *self.shares.lock().entry(id).or_default() += 1;
+        Some(SharedCollection { repo: self, id })
+    }
+    /// Count of live [`SharedCollection`] handles to the collection.
+    #[must_use]
+    pub fn share_count(&self, id: PointCollectionID) -> usize {
+        self.shares.lock().get(&id).copied().unwrap_or(0)
+    }
     fn alloc_of(&self, id: PointCollectionID) -> Option<PointCollectionAllocInfo> {
         self.allocs.read().get(&id).copied()
     }
@@ -182,7 +243,7 @@ impl Points {
         let slabs_read = self.slabs.read();
         let Some(slab) = slabs_read.get(alloc.slab_id) else {
             // Implementation bug!
-            log::debug!("{id} allocation found, but slab doesn't exist!");
+            tracing::debug!(%id, "allocation found, but slab doesn't exist!");
             return Err(super::TryRepositoryError::NotFound);
         };
         // Check the alloc range is reasonable
@@ -193,17 +254,49 @@ impl Points {
             .and_then(|elem_len| elem_len.checked_add(alloc.start))
             .is_some_and(|last| last <= SLAB_ELEMENT_COUNT));
 
-        let Some(slice) = slab.try_read(
+        let Some(elements) = slab.try_read_pinned(
             alloc.start,
             // won't overflow, already checked!
             alloc.summary.len * alloc.summary.archetype.elements(),
         ) else {
             // Implementation bug!
-            log::debug!("{id} allocation found, but out of bounds within it's slab!");
+            tracing::debug!(%id, "allocation found, but out of bounds within it's slab!");
             return Err(super::TryRepositoryError::NotFound);
         };
+        if StrokeSlice::new(elements.get(), alloc.summary.archetype).is_none() {
+            // Implementation bug!
+            tracing::debug!(%id, "allocation found, but doesn't fit it's archetype!");
+            return Err(super::TryRepositoryError::NotFound);
+        }
         Ok(BorrowedStrokeReadLock {
-            stroke: StrokeSlice::new(slice, alloc.summary.archetype).unwrap(),
+            elements,
+            archetype: alloc.summary.archetype,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Points;
+    use crate::stroke::{Archetype, StrokeSlice};
+    #[test]
+    fn share_counts() {
+        let repo = Points::default();
+        let elements = [1.0f32, 2.0, 3.0, 4.0];
+        let slice = StrokeSlice::new(bytemuck::cast_slice(&elements), Archetype::POSITION).unwrap();
+        let id = repo.insert(slice).unwrap();
+        assert_eq!(repo.share_count(id), 0);
+
+        let shared = repo.share(id).unwrap();
+        let cloned = shared.clone();
+        assert_eq!(cloned.id(), id);
+        assert_eq!(repo.share_count(id), 2);
+        drop(shared);
+        assert_eq!(repo.share_count(id), 1);
+        drop(cloned);
+        assert_eq!(repo.share_count(id), 0);
+
+        // Unknown collections can't be shared.
+        assert!(repo.share(super::PointCollectionID::default()).is_none());
+    }
+}
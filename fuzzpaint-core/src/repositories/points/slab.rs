@@ -1,3 +1,70 @@
+/// Count of the live [`Pin`]s on a slab.
+#[derive(Default)]
+struct Pins {
+    count: std::sync::atomic::AtomicUsize,
+    /// Held while waiting on or notifying `released`, so that the last unpin is never missed.
+    lock: parking_lot::Mutex<()>,
+    /// Notified when `count` falls to zero.
+    released: parking_lot::Condvar,
+}
+/// Keeps a slab's memory alive beyond any borrow of the slab itself. The slab cannot be [freed](Slab::free)
+/// until every pin on it has been dropped.
+pub struct Pin {
+    pins: std::sync::Arc<Pins>,
+}
+impl Clone for Pin {
+    fn clone(&self) -> Self {
+        // Relaxed - an existing pin already keeps the memory alive, nothing to synchronize with.
+        self.pins
+            .count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Self {
+            pins: self.pins.clone(),
+        }
+    }
+}
+impl Drop for Pin {
+    fn drop(&mut self) {
+        // Release - all reads through this pin must finish before the memory can be seen as unpinned.
+        if self
+            .pins
+            .count
+            .fetch_sub(1, std::sync::atomic::Ordering::Release)
+            == 1
+        {
+            let _lock = self.pins.lock.lock();
+            self.pins.released.notify_all();
+        }
+    }
+}
+/// A region of a slab's immutable section, readable for as long as it lives.
+pub struct PinnedSlice<T> {
+    pin: Pin,
+    /// Points into the pinned slab, valid for `len` items.
+    data: *const T,
+    len: usize,
+}
+impl<T> PinnedSlice<T> {
+    pub fn get(&self) -> &[T] {
+        // Safety: The pin keeps the slab's memory allocated, and the region was within the
+        // immutable section when this was made, which it can never leave.
+        unsafe { std::slice::from_raw_parts(self.data, self.len) }
+    }
+}
+impl<T> Clone for PinnedSlice<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pin: self.pin.clone(),
+            data: self.data,
+            len: self.len,
+        }
+    }
+}
+// Safety - Only ever gives out shared references to the data, which is never mutated.
+unsafe impl<T: Sync> Send for PinnedSlice<T> {}
+// Safety - as above.
+unsafe impl<T: Sync> Sync for PinnedSlice<T> {}
+
 /// A large collection of continguous items on the heap, where concurrent immutable and mutable access are
 /// allowed on opposite sides of the partition.
 ///
@@ -5,6 +72,8 @@
 pub struct Slab<T: bytemuck::Pod, const N: usize> {
     /// a non-null pointer to array of slab_SIZE points.
     array: *mut T,
+    /// Readers who may outlive a borrow of the slab.
+    pins: std::sync::Arc<Pins>,
     /// Write access guard.
     write_access: parking_lot::Mutex<()>,
     /// Current past-the-end index for the allocator.
@@ -198,7 +267,7 @@ impl<T: bytemuck::Pod, const N: usize> Slab<T, N> {
     /// of the currently allocated memory.
     ///
     /// Performs no check that the given start and length correspond to a single suballocation.
-    pub fn try_read(&self, start: usize, len: usize) -> Option<&'static [T]> {
+    pub fn try_read(&self, start: usize, len: usize) -> Option<&[T]> {
         // Check if this whole region is within the allocated, read-only section.
         if start
             .checked_add(len)
@@ -217,6 +286,38 @@ impl<T: bytemuck::Pod, const N: usize> Slab<T, N> {
             None
         }
     }
+    /// [`Self::try_read`], pinning the slab so that the data remains readable after the borrow of `self` ends.
+    pub fn try_read_pinned(&self, start: usize, len: usize) -> Option<PinnedSlice<T>> {
+        let slice = self.try_read(start, len)?;
+        Some(PinnedSlice {
+            pin: self.pin(),
+            data: slice.as_ptr(),
+            len,
+        })
+    }
+    fn pin(&self) -> Pin {
+        // Relaxed - the slab is borrowed, so it can't be freed concurrently with this.
+        self.pins
+            .count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Pin {
+            pins: self.pins.clone(),
+        }
+    }
+    /// Whether any [`Pin`]s are live on this slab.
+    pub fn is_pinned(&self) -> bool {
+        self.pins.count.load(std::sync::atomic::Ordering::Acquire) != 0
+    }
+    /// Block until every [`Pin`] on this slab has been dropped.
+    ///
+    /// Before waiting, make sure no new readers can find the slab, or this may wait forever.
+    pub fn wait_unpinned(&self) {
+        let mut lock = self.pins.lock.lock();
+        // Acquire - pairs with the release of the last pin, so that its reads happen-before our return.
+        while self.pins.count.load(std::sync::atomic::Ordering::Acquire) != 0 {
+            self.pins.released.wait(&mut lock);
+        }
+    }
     /// Get the number of indices currently in use.
     /// This is a hint - it may become immediately out-of-date and is not suitable for use in safety preconditions!
     pub fn hint_usage(&self) -> usize {
@@ -270,21 +371,25 @@ impl<T: bytemuck::Pod, const N: usize> Slab<T, N> {
         } else {
             Some(Self {
                 array: mem,
+                pins: std::sync::Arc::default(),
                 write_access: parking_lot::const_mutex(()),
                 bump_position: 0.into(),
             })
         }
     }
-    /// Free the memory of this slab. By default, memory is leaked on drop as the references to this slab's
-    /// data live arbitrarily long.
+    /// Free the memory of this slab. By default, memory is leaked on drop as [pinned](Self::try_read_pinned)
+    /// readers may outlive it.
     ///
-    /// Destructors of the values are *not* run.
-    ///
-    /// Safety: There must not be any outstanding references to this slab's memory (acquired by `try_read`).
-    pub unsafe fn free(self) {
-        // Safety - using same layout as used to create it.
-        // Use-after-free forwarded to this fn's safety contract.
-        unsafe { std::alloc::dealloc(self.array.cast(), Self::layout()) }
+    /// Destructors of the values are *not* run. Fails and gives the slab back if it is still pinned,
+    /// see [`Self::wait_unpinned`].
+    pub fn free(self) -> Result<(), Self> {
+        if self.is_pinned() {
+            return Err(self);
+        }
+        // Safety - using same layout as used to create it. Unpinned, and borrows of the slab's memory
+        // can't outlive `self`, so there are no readers left.
+        unsafe { std::alloc::dealloc(self.array.cast(), Self::layout()) };
+        Ok(())
     }
     const fn layout() -> std::alloc::Layout {
         std::alloc::Layout::new::<[T; N]>()
@@ -296,3 +401,47 @@ impl<T: bytemuck::Pod, const N: usize> Slab<T, N> {
 unsafe impl<T: Send + Sync + bytemuck::Pod, const N: usize> Send for Slab<T, N> {}
 // Safety - The mutex prevents similtaneous mutable and immutable access.
 unsafe impl<T: Sync + Sync + bytemuck::Pod, const N: usize> Sync for Slab<T, N> {}
+
+#[cfg(test)]
+mod test {
+    // Small slabs, so that these run quickly under miri.
+    type Slab = super::Slab<u32, 16>;
+    #[test]
+    fn pinned_reads() {
+        let slab = Slab::new();
+        let start = slab.shared_bump_write(&[1, 2, 3, 4]).unwrap();
+        // Beyond what has been written.
+        assert!(slab.try_read_pinned(start, 5).is_none());
+
+        let pinned = slab.try_read_pinned(start + 1, 2).unwrap();
+        let cloned = pinned.clone();
+        assert_eq!(cloned.get(), &[2, 3]);
+        // Writing more doesn't disturb the pinned data.
+        slab.shared_bump_write(&[5, 6]).unwrap();
+        assert_eq!(pinned.get(), &[2, 3]);
+
+        assert!(slab.is_pinned());
+        drop(pinned);
+        assert!(slab.is_pinned());
+        let slab = slab.free().unwrap_err();
+        drop(cloned);
+        assert!(!slab.is_pinned());
+        assert!(slab.free().is_ok());
+    }
+    #[test]
+    fn wait_for_readers() {
+        let slab = Slab::new();
+        slab.shared_bump_write(&[1, 2, 3]).unwrap();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let pinned = slab.try_read_pinned(0, 3).unwrap();
+                std::thread::spawn(move || pinned.get().iter().sum::<u32>())
+            })
+            .collect();
+        slab.wait_unpinned();
+        assert!(slab.free().is_ok());
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 6);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! # Rulers
+//!
+//! Rulers assist in drawing straight lines. Each ruler describes a family of guide lines covering the whole
+//! document. A stroke drawn while one is active latches onto the guide passing through where it started
+//! which best matches the direction it set off in, and each of its points is projected onto that guide.
+//!
+//! All positions are in document space, angles are in radians from the x axis towards the y axis.
+
+/// Angle of the isometric axes either side of the vertical, from the horizontal.
+const ISOMETRIC_SLOPE: f32 = std::f32::consts::FRAC_PI_6;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Ruler {
+    /// Guides parallel to one another.
+    Parallel {
+        /// Where the ruler's handle sits. Has no effect on the guides.
+        origin: [f32; 2],
+        angle: f32,
+    },
+    /// Guides converging on a single point, as with one-point perspective.
+    Perspective { vanishing_point: [f32; 2] },
+    /// Guides along the three axes of an isometric grid - the vertical, and thirty degrees either side of
+    /// the horizontal - all rotated by `angle`.
+    Isometric {
+        /// Where the ruler's handle sits. Has no effect on the guides.
+        origin: [f32; 2],
+        angle: f32,
+    },
+}
+impl Ruler {
+    /// Where the ruler's handle sits, for editing it.
+    #[must_use]
+    pub fn handle(&self) -> [f32; 2] {
+        match *self {
+            Self::Parallel { origin, .. } | Self::Isometric { origin, .. } => origin,
+            Self::Perspective { vanishing_point } => vanishing_point,
+        }
+    }
+    pub fn handle_mut(&mut self) -> &mut [f32; 2] {
+        match self {
+            Self::Parallel { origin, .. } | Self::Isometric { origin, .. } => origin,
+            Self::Perspective { vanishing_point } => vanishing_point,
+        }
+    }
+    /// The rotation of the ruler, if it can be rotated.
+    pub fn angle_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            Self::Parallel { angle, .. } | Self::Isometric { angle, .. } => Some(angle),
+            Self::Perspective { .. } => None,
+        }
+    }
+    /// Directions of the guides passing through `point`, as unit vectors. Each guide extends both forward
+    /// and backward along its direction.
+    #[must_use]
+    pub fn directions(&self, point: [f32; 2]) -> smallvec::SmallVec<[[f32; 2]; 3]> {
+        let unit = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            [cos, sin]
+        };
+        match *self {
+            Self::Parallel { angle, .. } => smallvec::smallvec![unit(angle)],
+            Self::Perspective { vanishing_point } => {
+                let delta = [vanishing_point[0] - point[0], vanishing_point[1] - point[1]];
+                let len = delta[0].hypot(delta[1]);
+                // Every line passes through the vanishing point, there's no telling which to use.
+                if len < f32::EPSILON || !len.is_finite() {
+                    smallvec::SmallVec::new()
+                } else {
+                    smallvec::smallvec![[delta[0] / len, delta[1] / len]]
+                }
+            }
+            Self::Isometric { angle, .. } => smallvec::smallvec![
+                unit(angle + ISOMETRIC_SLOPE),
+                unit(angle + std::f32::consts::FRAC_PI_2),
+                unit(angle + std::f32::consts::PI - ISOMETRIC_SLOPE),
+            ],
+        }
+    }
+    /// The guide through `start` which most closely follows the direction from `start` towards `towards`.
+    /// None if no guide passes through `start`.
+    #[must_use]
+    pub fn guide(&self, start: [f32; 2], towards: [f32; 2]) -> Option<Guide> {
+        let motion = [towards[0] - start[0], towards[1] - start[1]];
+        let alignment = |dir: &[f32; 2]| (dir[0] * motion[0] + dir[1] * motion[1]).abs();
+        self.directions(start)
+            .into_iter()
+            .max_by(|a, b| alignment(a).total_cmp(&alignment(b)))
+            .map(|direction| Guide {
+                origin: start,
+                direction,
+            })
+    }
+}
+
+/// A single line of a [`Ruler`], which a stroke has latched onto.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Guide {
+    pub origin: [f32; 2],
+    /// Unit vector along the line.
+    pub direction: [f32; 2],
+}
+impl Guide {
+    /// The closest point on the guide.
+    #[must_use]
+    pub fn project(&self, point: [f32; 2]) -> [f32; 2] {
+        let [dx, dy] = self.direction;
+        let along = (point[0] - self.origin[0]) * dx + (point[1] - self.origin[1]) * dy;
+        [self.origin[0] + along * dx, self.origin[1] + along * dy]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Guide, Ruler};
+    fn assert_near(a: [f32; 2], b: [f32; 2]) {
+        assert!(
+            (a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4,
+            "{a:?} != {b:?}"
+        );
+    }
+    #[test]
+    fn project() {
+        let guide = Guide {
+            origin: [10.0, 10.0],
+            direction: [1.0, 0.0],
+        };
+        assert_near(guide.project([15.0, 30.0]), [15.0, 10.0]);
+        assert_near(guide.project([-5.0, -2.0]), [-5.0, 10.0]);
+    }
+    #[test]
+    fn perspective_converges() {
+        let ruler = Ruler::Perspective {
+            vanishing_point: [100.0, 0.0],
+        };
+        let guide = ruler.guide([0.0, 0.0], [0.0, 50.0]).unwrap();
+        // Only one guide through any point, regardless of the motion.
+        assert_near(guide.direction, [1.0, 0.0]);
+        assert_near(guide.project([50.0, 20.0]), [50.0, 0.0]);
+        // None through the vanishing point itself.
+        assert_eq!(ruler.guide([100.0, 0.0], [0.0, 0.0]), None);
+    }
+    #[test]
+    fn isometric_picks_nearest_axis() {
+        let ruler = Ruler::Isometric {
+            origin: [0.0, 0.0],
+            angle: 0.0,
+        };
+        // Mostly along y, the vertical axis.
+        let guide = ruler.guide([0.0, 0.0], [1.0, -10.0]).unwrap();
+        assert_near(guide.direction, [0.0, 1.0]);
+        // Backwards along the thirty degree axis.
+        let guide = ruler.guide([0.0, 0.0], [-10.0, -5.0]).unwrap();
+        assert_near(guide.direction, [3.0f32.sqrt() / 2.0, 0.5]);
+        // Along the hundred and fifty degree axis.
+        let guide = ruler.guide([0.0, 0.0], [-10.0, 5.0]).unwrap();
+        assert_near(guide.direction, [-(3.0f32.sqrt()) / 2.0, 0.5]);
+    }
+}
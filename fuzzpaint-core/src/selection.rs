@@ -0,0 +1,164 @@
+//! # Selection
+//!
+//! A selection limits where strokes have an effect. It is a closed polygon in document space, filled
+//! with the even-odd rule, and stored in the [point repository](crate::repositories::points) as a
+//! position-only collection so strokes can refer to it by ID. See
+//! [`ImmutableStroke::clip`](crate::state::stroke_collection::ImmutableStroke::clip).
+
+use crate::stroke::{Archetype, StrokeSlice};
+
+/// The archetype of point collections holding a selection polygon.
+pub const ARCHETYPE: Archetype = Archetype::POSITION;
+/// Rows sampled per pixel by [`rasterize`], for antialiased edges.
+const SUBSAMPLES: u32 = 4;
+
+/// Read the polygon back out of a point collection. None if the collection has no positions.
+#[must_use]
+pub fn polygon(slice: StrokeSlice<'_>) -> Option<Vec<[f32; 2]>> {
+    (0..slice.len())
+        .map(|idx| slice.get(idx)?.position())
+        .collect()
+}
+
+/// Whether the point is inside the polygon, by the same even-odd rule as [`rasterize`].
+#[must_use]
+pub fn contains(polygon: &[[f32; 2]], point: [f32; 2]) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a[1] <= point[1]) != (b[1] <= point[1]) {
+            let t = (point[1] - a[1]) / (b[1] - a[1]);
+            if point[0] < a[0] + t * (b[0] - a[0]) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Whether any point of the stroke falls inside the polygon, once moved by `transform` into the polygon's
+/// space, such as from a layer's into the document's. Points without a position never do.
+#[must_use]
+pub fn touches(
+    polygon: &[[f32; 2]],
+    stroke: StrokeSlice<'_>,
+    transform: &crate::state::transform::Matrix,
+) -> bool {
+    (0..stroke.len())
+        .filter_map(|idx| stroke.get(idx)?.position())
+        .any(|point| contains(polygon, transform.transform_point(point)))
+}
+
+/// Add the coverage of the horizontal span `[x0, x1)` into `row`.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn add_span(row: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let width = row.len() as f32;
+    let (x0, x1) = (x0.clamp(0.0, width), x1.clamp(0.0, width));
+    if x1 <= x0 {
+        return;
+    }
+    // Clamped, so the casts are exact.
+    let first = x0.floor() as usize;
+    let last = x1.ceil() as usize;
+    for (px, coverage) in (first..).zip(&mut row[first..last]) {
+        let px = px as f32;
+        *coverage += (x1.min(px + 1.0) - x0.max(px)) * weight;
+    }
+}
+
+/// Fill the polygon into a tightly packed `width * height` coverage mask, one byte per pixel.
+/// Polygons of fewer than three points cover nothing.
+#[must_use]
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn rasterize(polygon: &[[f32; 2]], width: u32, height: u32) -> Vec<u8> {
+    let width = width as usize;
+    let mut mask = vec![0; width * height as usize];
+    if polygon.len() < 3 {
+        return mask;
+    }
+
+    let mut row = vec![0.0f32; width];
+    let mut crossings = Vec::new();
+    for (y, out) in (0..height).zip(mask.chunks_exact_mut(width)) {
+        row.fill(0.0);
+        for sub in 0..SUBSAMPLES {
+            let sample_y = y as f32 + (sub as f32 + 0.5) / SUBSAMPLES as f32;
+            crossings.clear();
+            // Every edge, including the closing one from the last point back to the first.
+            for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+                // Half-open, so a vertex exactly on the sample line is only counted once.
+                if (a[1] <= sample_y) != (b[1] <= sample_y) {
+                    let t = (sample_y - a[1]) / (b[1] - a[1]);
+                    crossings.push(a[0] + t * (b[0] - a[0]));
+                }
+            }
+            crossings.sort_unstable_by(f32::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                add_span(&mut row, span[0], span[1], 1.0 / SUBSAMPLES as f32);
+            }
+        }
+        for (out, coverage) in out.iter_mut().zip(&row) {
+            // Clamped, so the cast is exact.
+            *out = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod test {
+    use super::{contains, rasterize, touches};
+    #[test]
+    fn rasterize_square() {
+        let square = [[2.0, 2.0], [6.0, 2.0], [6.0, 6.0], [2.0, 6.0]];
+        let mask = rasterize(&square, 8, 8);
+        let at = |x: usize, y: usize| mask[y * 8 + x];
+        assert_eq!(at(2, 2), 255);
+        assert_eq!(at(5, 5), 255);
+        assert_eq!(at(1, 3), 0);
+        assert_eq!(at(6, 3), 0);
+        assert_eq!(at(3, 7), 0);
+
+        // Edge through the middle of a column is half covered.
+        let half = [[0.0, 0.0], [2.5, 0.0], [2.5, 8.0], [0.0, 8.0]];
+        let mask = rasterize(&half, 8, 8);
+        assert_eq!(mask[2], 128);
+        assert_eq!(mask[3], 0);
+
+        // Degenerate selections cover nothing.
+        assert!(rasterize(&square[..2], 8, 8).iter().all(|&c| c == 0));
+    }
+    #[test]
+    fn contains_even_odd() {
+        let square = [[2.0, 2.0], [6.0, 2.0], [6.0, 6.0], [2.0, 6.0]];
+        assert!(contains(&square, [4.0, 4.0]));
+        assert!(!contains(&square, [1.0, 4.0]));
+        assert!(!contains(&square, [4.0, 7.0]));
+
+        // Bowtie, with its loops to the left and right.
+        let bowtie = [[0.0, 0.0], [4.0, 4.0], [4.0, 0.0], [0.0, 4.0]];
+        assert!(contains(&bowtie, [1.0, 2.5]));
+        assert!(contains(&bowtie, [3.0, 2.5]));
+        assert!(!contains(&bowtie, [2.0, 1.0]));
+        assert!(!contains(&square[..2], [4.0, 2.0]));
+    }
+    #[test]
+    fn touches_moved() {
+        use crate::stroke::{Archetype, StrokeSlice};
+        let square = [[2.0, 2.0], [6.0, 2.0], [6.0, 6.0], [2.0, 6.0]];
+        let points = [0.0f32, 0.0, 1.0, 1.0];
+        let stroke = StrokeSlice::new(bytemuck::cast_slice(&points), Archetype::POSITION).unwrap();
+        let identity = crate::state::transform::Matrix::default();
+        assert!(!touches(&square, stroke, &identity));
+        // Moved so that its second point is within.
+        let moved = crate::state::transform::Matrix::from([[1.0, 0.0], [0.0, 1.0], [2.0, 2.0]]);
+        assert!(touches(&square, stroke, &moved));
+    }
+}
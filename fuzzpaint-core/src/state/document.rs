@@ -7,6 +7,11 @@ pub struct Document {
     /// Name of the document, inferred from its path or generated.
     pub name: String,
     pub viewport: Viewport,
+    /// External resources used by the document. Shared by every state of the document, as links
+    /// are not a part of its history.
+    pub assets: std::sync::Arc<parking_lot::RwLock<crate::io::asset::AssetLinks>>,
+    /// Descriptive fields, shared by every state of the document as they are not a part of its history.
+    pub metadata: std::sync::Arc<parking_lot::RwLock<Metadata>>,
 }
 impl Default for Document {
     fn default() -> Self {
@@ -14,10 +19,22 @@ impl Default for Document {
             path: None,
             name: "New Document".into(),
             viewport: Viewport::default(),
+            assets: std::sync::Arc::default(),
+            metadata: std::sync::Arc::default(),
         }
     }
 }
 
+/// Information about a document, written into its file and into images exported from it. Its resolution is
+/// that of the [viewport](Viewport::resolution), which is a part of history.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Metadata {
+    /// Each of these is empty if unset.
+    pub title: String,
+    pub author: String,
+    pub description: String,
+}
+
 #[derive(Copy, Clone)]
 /// The render area of a document.
 pub struct Viewport {
@@ -31,6 +48,13 @@ pub struct Viewport {
     pub scale_factor: f32,
 }
 impl Viewport {
+    /// The resolutions a document may have, in dots per inch.
+    pub const DPI_RANGE: std::ops::RangeInclusive<f32> = 1.0..=2400.0;
+    /// Dots per inch, relating the document's pixels to a physical size when printed.
+    #[must_use]
+    pub fn dpi(&self) -> f32 {
+        self.resolution.into_dpi()
+    }
     /// Get the resolution (DPI/DPCM) after `scale_factor` is applied
     #[must_use]
     pub fn scaled_resolution(&self) -> crate::units::Resolution {
@@ -57,6 +81,19 @@ impl Viewport {
     pub fn size_logical_pixels(&self) -> [f32; 2] {
         self.size.map(|length| length.into_logical(self.resolution))
     }
+    /// Get the canvas this viewport covers, in logical pixels.
+    #[must_use]
+    pub fn canvas(&self) -> Canvas {
+        Canvas {
+            origin: self.origin_logical_pixels(),
+            size: self.size_logical_pixels(),
+        }
+    }
+    /// Cover the canvas, in logical pixels.
+    pub(crate) fn set_canvas(&mut self, canvas: Canvas) {
+        self.origin = canvas.origin.map(crate::units::Length::Logical);
+        self.size = canvas.size.map(crate::units::Length::Logical);
+    }
     /// Get the size of the viewport, in rounded physical pixels.
     #[must_use]
     pub fn size_physical_pixels(&self) -> [u32; 2] {
@@ -76,3 +113,125 @@ impl Default for Viewport {
         }
     }
 }
+
+/// The region of the document that is drawn and exported, in logical pixels. Everything outside of it is
+/// kept, just not shown.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Canvas {
+    /// Top-left corner of the canvas.
+    pub origin: [f32; 2],
+    /// Size of the canvas, extending down-right.
+    pub size: [f32; 2],
+}
+impl Canvas {
+    /// Whether the canvas is finite and covers any area.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.origin.iter().all(|c| c.is_finite())
+            && self.size.iter().all(|s| s.is_finite() && *s > 0.0)
+    }
+    /// The `[min, max]` corners of the canvas.
+    #[must_use]
+    pub fn corners(&self) -> [[f32; 2]; 2] {
+        [
+            self.origin,
+            [self.origin[0] + self.size[0], self.origin[1] + self.size[1]],
+        ]
+    }
+}
+impl Default for Canvas {
+    fn default() -> Self {
+        Viewport::default().canvas()
+    }
+}
+
+pub mod commands {
+    use super::Canvas;
+    use crate::units::Resolution;
+    #[derive(Clone, Debug)]
+    pub enum Command {
+        /// The canvas was cropped, extended, or moved.
+        CanvasChanged { from: Canvas, to: Canvas },
+        /// The relation of pixels to physical units changed. Pixels stay where they are.
+        ResolutionChanged { from: Resolution, to: Resolution },
+    }
+}
+pub mod writer {
+    use super::{commands::Command, Canvas};
+    use crate::queue::writer::CommandWrite;
+    use crate::units::Resolution;
+    pub struct Writer<'a, Write> {
+        writer: Write,
+        state: &'a mut super::Document,
+    }
+    impl<Write> std::ops::Deref for Writer<'_, Write> {
+        type Target = super::Document;
+        fn deref(&self) -> &Self::Target {
+            self.state
+        }
+    }
+    impl<'a, Write: CommandWrite<Command>> Writer<'a, Write> {
+        pub fn new(writer: Write, state: &'a mut super::Document) -> Self {
+            Self { writer, state }
+        }
+        /// Change the canvas. Does nothing if it's unchanged.
+        ///
+        /// Fails if the canvas is not [valid](Canvas::is_valid).
+        pub fn set_canvas(&mut self, to: Canvas) -> Result<(), ()> {
+            if !to.is_valid() {
+                return Err(());
+            }
+            let from = self.state.viewport.canvas();
+            if from == to {
+                return Ok(());
+            }
+            self.state.viewport.set_canvas(to);
+            self.writer.write(Command::CanvasChanged { from, to });
+            Ok(())
+        }
+        /// Change the resolution. Does nothing if it's unchanged.
+        ///
+        /// Fails if it's not within [`Viewport::DPI_RANGE`](super::Viewport::DPI_RANGE).
+        pub fn set_resolution(&mut self, to: Resolution) -> Result<(), ()> {
+            if !super::Viewport::DPI_RANGE.contains(&to.into_dpi()) {
+                return Err(());
+            }
+            let from = self.state.viewport.resolution;
+            if from == to {
+                return Ok(());
+            }
+            self.state.viewport.resolution = to;
+            self.writer.write(Command::ResolutionChanged { from, to });
+            Ok(())
+        }
+    }
+}
+impl crate::commands::CommandConsumer<commands::Command> for Document {
+    fn apply(
+        &mut self,
+        command: crate::commands::DoUndo<'_, commands::Command>,
+    ) -> Result<(), crate::commands::CommandError> {
+        use crate::commands::DoUndo;
+        let (command, undo) = match command {
+            DoUndo::Do(command) => (command, false),
+            DoUndo::Undo(command) => (command, true),
+        };
+        match command {
+            commands::Command::CanvasChanged { from, to } => {
+                let (from, to) = if undo { (to, from) } else { (from, to) };
+                if self.viewport.canvas() != *from {
+                    return Err(crate::commands::CommandError::MismatchedState);
+                }
+                self.viewport.set_canvas(*to);
+            }
+            commands::Command::ResolutionChanged { from, to } => {
+                let (from, to) = if undo { (to, from) } else { (from, to) };
+                if self.viewport.resolution != *from {
+                    return Err(crate::commands::CommandError::MismatchedState);
+                }
+                self.viewport.resolution = *to;
+            }
+        }
+        Ok(())
+    }
+}
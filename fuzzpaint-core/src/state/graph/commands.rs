@@ -16,6 +16,8 @@ pub enum Command {
     },
     LeafCreated {
         target: super::LeafID,
+        /// The name it was created with. Renames are not tracked, this is only for recreating it.
+        name: String,
         ty: super::LeafType,
         /// New parent, or None if root.
         destination: Option<super::NodeID>,
@@ -38,6 +40,8 @@ pub enum Command {
     },
     NodeCreated {
         target: super::NodeID,
+        /// The name it was created with. Renames are not tracked, this is only for recreating it.
+        name: String,
         ty: super::NodeType,
         /// New parent, or None if root.
         destination: Option<super::NodeID>,
@@ -40,6 +40,19 @@ pub enum LeafType {
     },
     // The name of the note is the note!
     Note,
+    /// A raster image, stored as one of the document's [assets](crate::io::asset).
+    Image {
+        blend: Blend,
+        /// The encoded image file.
+        image: crate::brush::UniqueID,
+        /// Transform from image pixels to document pixels.
+        outer_transform: transform::Matrix,
+    },
+    /// Filled with a [gradient](crate::gradient), placed in document pixels.
+    Gradient {
+        blend: Blend,
+        gradient: crate::gradient::Gradient,
+    },
 }
 impl LeafType {
     #[must_use]
@@ -47,7 +60,9 @@ impl LeafType {
         match self {
             Self::StrokeLayer { blend, .. }
             | Self::SolidColor { blend, .. }
-            | Self::Text { blend, .. } => Some(*blend),
+            | Self::Text { blend, .. }
+            | Self::Image { blend, .. }
+            | Self::Gradient { blend, .. } => Some(*blend),
             Self::Note => None,
         }
     }
@@ -55,7 +70,9 @@ impl LeafType {
         match self {
             Self::StrokeLayer { blend, .. }
             | Self::SolidColor { blend, .. }
-            | Self::Text { blend, .. } => Some(blend),
+            | Self::Text { blend, .. }
+            | Self::Image { blend, .. }
+            | Self::Gradient { blend, .. } => Some(blend),
             Self::Note => None,
         }
     }
@@ -64,7 +81,11 @@ impl LeafType {
             Self::StrokeLayer {
                 inner_transform, ..
             } => Some(inner_transform),
-            Self::Note | Self::SolidColor { .. } | Self::Text { .. } => None,
+            Self::Note
+            | Self::SolidColor { .. }
+            | Self::Text { .. }
+            | Self::Image { .. }
+            | Self::Gradient { .. } => None,
         }
     }
     pub fn outer_transform_mut(&mut self) -> Option<&mut transform::Matrix> {
@@ -74,8 +95,11 @@ impl LeafType {
             }
             | Self::Text {
                 outer_transform, ..
+            }
+            | Self::Image {
+                outer_transform, ..
             } => Some(outer_transform),
-            Self::Note | Self::SolidColor { .. } => None,
+            Self::Note | Self::SolidColor { .. } | Self::Gradient { .. } => None,
         }
     }
 }
@@ -86,20 +110,32 @@ pub enum NodeType {
     Passthrough,
     /// Leaves are rendered as a group, the output is then blended as a single image.
     GroupedBlend(Blend),
+    /// Like [`NodeType::GroupedBlend`], with the filter applied to the group's output before blending.
+    Filtered {
+        blend: Blend,
+        filter: crate::filter::Filter,
+    },
 }
 impl NodeType {
     #[must_use]
     pub fn blend(&self) -> Option<Blend> {
         match self {
             Self::Passthrough => None,
-            Self::GroupedBlend(blend) => Some(*blend),
+            Self::GroupedBlend(blend) | Self::Filtered { blend, .. } => Some(*blend),
         }
     }
     #[must_use]
     pub fn blend_mut(&mut self) -> Option<&mut Blend> {
         match self {
             Self::Passthrough => None,
-            Self::GroupedBlend(blend) => Some(blend),
+            Self::GroupedBlend(blend) | Self::Filtered { blend, .. } => Some(blend),
+        }
+    }
+    #[must_use]
+    pub fn filter(&self) -> Option<crate::filter::Filter> {
+        match self {
+            Self::Filtered { filter, .. } => Some(*filter),
+            Self::Passthrough | Self::GroupedBlend(_) => None,
         }
     }
 }
@@ -132,6 +168,52 @@ impl NodeDataTy {
         }
     }
 }
+/// A color the user can mark layers with to keep large documents organized. Has no effect on rendering.
+#[derive(strum::AsRefStr, strum::EnumIter, Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[repr(u8)]
+pub enum ColorTag {
+    #[default]
+    None = 0,
+    Red = 1,
+    Orange = 2,
+    Yellow = 3,
+    Green = 4,
+    Blue = 5,
+    Purple = 6,
+    Gray = 7,
+}
+impl ColorTag {
+    /// The sRGB color of the tag, or None if untagged.
+    #[must_use]
+    pub fn rgb(self) -> Option<[u8; 3]> {
+        match self {
+            Self::None => None,
+            Self::Red => Some([0xE0, 0x4F, 0x4F]),
+            Self::Orange => Some([0xE8, 0x8E, 0x3A]),
+            Self::Yellow => Some([0xE0, 0xC8, 0x3C]),
+            Self::Green => Some([0x5C, 0xB8, 0x5C]),
+            Self::Blue => Some([0x4F, 0x8F, 0xE0]),
+            Self::Purple => Some([0x9C, 0x6A, 0xD8]),
+            Self::Gray => Some([0x8C, 0x8C, 0x8C]),
+        }
+    }
+}
+/// The tag's discriminant, as stored in files.
+impl From<ColorTag> for u8 {
+    fn from(tag: ColorTag) -> Self {
+        tag as u8
+    }
+}
+impl TryFrom<u8> for ColorTag {
+    type Error = u8;
+    /// Fails with the unknown value.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        <Self as strum::IntoEnumIterator>::iter()
+            .find(|tag| *tag as u8 == value)
+            .ok_or(value)
+    }
+}
+
 #[derive(Clone)]
 pub struct NodeData {
     // NOT public, as we users could break the tree by mutating this!
@@ -140,6 +222,10 @@ pub struct NodeData {
     /// Represents whether the command that created this node has been undone.
     deleted: bool,
     pub name: String,
+    pub tag: ColorTag,
+    /// A reference image or sketch, shown while drawing but left out of exports.
+    /// Only leaves are ever references.
+    pub reference: bool,
 }
 impl NodeData {
     #[must_use]
@@ -150,6 +236,23 @@ impl NodeData {
         &mut self.name
     }
     #[must_use]
+    pub fn tag(&self) -> ColorTag {
+        self.tag
+    }
+    pub fn tag_mut(&mut self) -> &mut ColorTag {
+        &mut self.tag
+    }
+    /// Whether this is a reference leaf, shown but not exported.
+    #[must_use]
+    pub fn is_reference(&self) -> bool {
+        self.reference
+    }
+    /// Whether the node was deleted, or its creation undone.
+    #[must_use]
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+    #[must_use]
     pub fn is_leaf(&self) -> bool {
         self.ty.is_leaf()
     }
@@ -255,6 +358,8 @@ impl Default for BlendGraph {
             tree: id_tree::TreeBuilder::new()
                 .with_root(id_tree::Node::new(NodeData {
                     name: String::new(),
+                    tag: ColorTag::None,
+                    reference: false,
                     ty: NodeDataTy::Root,
                     deleted: false,
                 }))
@@ -409,6 +514,8 @@ impl BlendGraph {
     ) -> Result<NodeID, TargetError> {
         let node = id_tree::Node::new(NodeData {
             name,
+            tag: ColorTag::None,
+            reference: false,
             deleted: false,
             ty: NodeDataTy::Node(ty),
         });
@@ -435,6 +542,8 @@ impl BlendGraph {
     ) -> Result<LeafID, TargetError> {
         let node = id_tree::Node::new(NodeData {
             name,
+            tag: ColorTag::None,
+            reference: false,
             deleted: false,
             ty: NodeDataTy::Leaf(ty),
         });
@@ -471,6 +580,35 @@ impl BlendGraph {
 
         Some((parent_id.map(|id| NodeID(*id)), child_idx))
     }
+    /// Whether `ancestor` is `target` itself or one of its [grand]parents.
+    fn is_within(&self, target: AnyID, ancestor: AnyID) -> bool {
+        let mut cur = target;
+        loop {
+            if cur == ancestor {
+                return true;
+            }
+            match self.location_of(cur) {
+                Some((Some(parent), _)) => cur = parent.into(),
+                _ => return false,
+            }
+        }
+    }
+    /// The child index of the node and each of its parents, root first. Ordering by this puts nodes in the
+    /// order they're shown, top to bottom.
+    fn path_of(&self, id: AnyID) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut cur = id;
+        loop {
+            let (parent, idx) = self.location_of(cur)?;
+            path.push(idx);
+            match parent {
+                Some(parent) => cur = parent.into(),
+                None => break,
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
     /// Reparent the target onto a new parent.
     /// Children are brought along for the ride!
     pub fn reparent(
@@ -561,6 +699,153 @@ impl Clone for BlendGraph {
     }
 }
 
+impl BlendGraph {
+    /// Commands which recreate this graph when replayed onto an empty one. Deleted nodes are recreated too,
+    /// so that child indices are unchanged.
+    #[must_use]
+    pub fn creation_commands(&self) -> Vec<commands::Command> {
+        use commands::Command;
+        // Unwraps ok - the root always exists, and every other node has a parent and an ID.
+        let root = self.tree.root_node_id().unwrap();
+        let mut created = Vec::new();
+        let mut deleted = Vec::new();
+        // Pre-order, so that parents and older siblings are recreated first.
+        for tree_id in self.tree.traverse_pre_order_ids(root).unwrap() {
+            let node = self.tree.get(&tree_id).unwrap();
+            let data = node.data();
+            if matches!(data.ty, NodeDataTy::Root) {
+                continue;
+            }
+            let id = *self.ids.fuzz_id_from(&tree_id).unwrap();
+            let parent = node.parent().unwrap();
+            let destination = self.ids.fuzz_id_from(parent).map(|&parent| NodeID(parent));
+            let child_idx = self
+                .tree
+                .children_ids(parent)
+                .unwrap()
+                .position(|child| *child == tree_id)
+                .unwrap();
+            let target = match &data.ty {
+                NodeDataTy::Leaf(ty) => {
+                    created.push(Command::LeafCreated {
+                        target: LeafID(id),
+                        name: data.name.clone(),
+                        ty: ty.clone(),
+                        destination,
+                        child_idx,
+                    });
+                    AnyID::Leaf(LeafID(id))
+                }
+                NodeDataTy::Node(ty) => {
+                    created.push(Command::NodeCreated {
+                        target: NodeID(id),
+                        name: data.name.clone(),
+                        ty: ty.clone(),
+                        destination,
+                        child_idx,
+                    });
+                    AnyID::Node(NodeID(id))
+                }
+                NodeDataTy::Root => unreachable!(),
+            };
+            if data.deleted {
+                deleted.push(Command::AnyDeleted { target });
+            }
+        }
+        created.extend(deleted);
+        created
+    }
+    /// Insert a node under a known ID. For replaying creation onto a state that predates it.
+    fn recreate(
+        &mut self,
+        id: stable_id::FuzzNodeID,
+        parent: Option<NodeID>,
+        child_idx: usize,
+        data: NodeData,
+    ) -> Result<(), crate::commands::CommandError> {
+        use crate::commands::CommandError;
+        let parent = match parent {
+            Some(parent) => self
+                .ids
+                .tree_id_from_node(parent)
+                .ok_or(CommandError::UnknownResource)?
+                .clone(),
+            // Unwrap ok - the root always exists.
+            None => self.tree.root_node_id().unwrap().clone(),
+        };
+        let new_node = self
+            .tree
+            .insert(
+                id_tree::Node::new(data),
+                id_tree::InsertBehavior::UnderNode(&parent),
+            )
+            .map_err(|_| CommandError::UnknownResource)?;
+        // Unwrap ok - we just added it as a child.
+        let last_idx = self.tree.children_ids(&parent).unwrap().count() - 1;
+        if child_idx > last_idx {
+            // Siblings it was placed among are missing, such as those made by undone history not being replayed.
+            // Unwrap ok - as above, and it has no children yet.
+            self.tree
+                .remove_node(new_node, id_tree::RemoveBehavior::DropChildren)
+                .unwrap();
+            return Err(CommandError::MismatchedState);
+        }
+        // Unwrap ok - in range, as checked above.
+        self.tree.make_nth_sibling(&new_node, child_idx).unwrap();
+        self.ids.insert_pair(new_node, id);
+        Ok(())
+    }
+    /// Every node but the root with its ID, deleted or not.
+    fn iter_with_deleted(&self) -> impl Iterator<Item = (stable_id::FuzzNodeID, &NodeData)> + '_ {
+        // Unwraps ok - the root always exists, and every ID came from this tree.
+        let root = self.tree.root_node_id().unwrap();
+        self.tree
+            .traverse_pre_order_ids(root)
+            .unwrap()
+            .filter_map(|tree_id| {
+                let data = self.tree.get(&tree_id).unwrap().data();
+                if matches!(data.ty, NodeDataTy::Root) {
+                    return None;
+                }
+                Some((*self.ids.fuzz_id_from(&tree_id)?, data))
+            })
+    }
+    /// Take the names, tags, and reference flags of nodes also in `from`. These aren't tracked by history, so a
+    /// graph rebuilt by replaying commands only knows what each node was created with.
+    pub(crate) fn adopt_labels(&mut self, from: &Self) {
+        for (id, source) in from.iter_with_deleted() {
+            if let Some(data) = self.get_mut(NodeID(id)) {
+                data.name.clone_from(&source.name);
+                data.tag = source.tag;
+                data.reference = source.reference;
+            }
+        }
+    }
+    /// Take the labels of nodes remembered in `labels`, see [`Self::adopt_labels`].
+    pub(crate) fn restore_labels(&mut self, labels: &Labels) {
+        for (id, (name, tag, reference)) in &labels.0 {
+            if let Some(data) = self.get_mut(NodeID(*id)) {
+                data.name.clone_from(name);
+                data.tag = *tag;
+                data.reference = *reference;
+            }
+        }
+    }
+}
+/// What history doesn't track about nodes: their names, tags, and reference flags. Remembered so that nodes
+/// rebuilt by replaying history, which only know what they were created with, can have them back.
+#[derive(Clone, Default)]
+pub(crate) struct Labels(hashbrown::HashMap<stable_id::FuzzNodeID, (String, ColorTag, bool)>);
+impl Labels {
+    /// Remember the labels of every node in the graph, deleted or not, over any remembered before.
+    pub(crate) fn remember(&mut self, graph: &BlendGraph) {
+        self.0.extend(
+            graph
+                .iter_with_deleted()
+                .map(|(id, data)| (id, (data.name.clone(), data.tag, data.reference))),
+        );
+    }
+}
 impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
     fn apply(
         &mut self,
@@ -621,9 +906,11 @@ impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
                             Ok(())
                         }
                     }
-                    LeafType::Note | LeafType::SolidColor { .. } | LeafType::Text { .. } => {
-                        Err(CommandError::MismatchedState)
-                    }
+                    LeafType::Note
+                    | LeafType::SolidColor { .. }
+                    | LeafType::Text { .. }
+                    | LeafType::Image { .. }
+                    | LeafType::Gradient { .. } => Err(CommandError::MismatchedState),
                 }
             }
             DoUndo::Do(Command::LeafOuterTransformChanged {
@@ -646,6 +933,9 @@ impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
                     }
                     | LeafType::Text {
                         outer_transform, ..
+                    }
+                    | LeafType::Image {
+                        outer_transform, ..
                     } => {
                         // If NaN This becomes problematic.
                         if outer_transform != old_transform {
@@ -655,7 +945,7 @@ impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
                             Ok(())
                         }
                     }
-                    LeafType::Note | LeafType::SolidColor { .. } => {
+                    LeafType::Note | LeafType::SolidColor { .. } | LeafType::Gradient { .. } => {
                         Err(CommandError::MismatchedState)
                     }
                 }
@@ -696,15 +986,28 @@ impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
             }
             DoUndo::Do(Command::NodeCreated {
                 target,
+                name,
                 ty,
-                child_idx: _, // FIXME!
-                destination: _,
+                child_idx,
+                destination,
             }) => {
-                // This case only reachable with undo then redo.
-                // Clear the deleted flag!
                 let Some(node) = self.get_mut(*target) else {
-                    return Err(CommandError::UnknownResource);
+                    // Created after the state being replayed onto was taken, such as a checkpoint.
+                    return self.recreate(
+                        target.0,
+                        *destination,
+                        *child_idx,
+                        NodeData {
+                            name: name.clone(),
+                            tag: ColorTag::None,
+                            reference: false,
+                            deleted: false,
+                            ty: NodeDataTy::Node(ty.clone()),
+                        },
+                    );
                 };
+                // Otherwise, this case only reachable with undo then redo.
+                // Clear the deleted flag!
                 if node.node() != Some(ty) || !node.deleted {
                     return Err(CommandError::MismatchedState);
                 }
@@ -716,6 +1019,7 @@ impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
             }
             DoUndo::Undo(Command::NodeCreated {
                 target,
+                name: _,
                 ty,
                 child_idx: _, // FIXME!
                 destination: _,
@@ -734,15 +1038,28 @@ impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
             }
             DoUndo::Do(Command::LeafCreated {
                 target,
+                name,
                 ty,
-                child_idx: _, // FIXME!
-                destination: _,
+                child_idx,
+                destination,
             }) => {
-                // This case only reachable with undo then redo.
-                // Clear the deleted flag!
                 let Some(node) = self.get_mut(*target) else {
-                    return Err(CommandError::UnknownResource);
+                    // Created after the state being replayed onto was taken, such as a checkpoint.
+                    return self.recreate(
+                        target.0,
+                        *destination,
+                        *child_idx,
+                        NodeData {
+                            name: name.clone(),
+                            tag: ColorTag::None,
+                            reference: false,
+                            deleted: false,
+                            ty: NodeDataTy::Leaf(ty.clone()),
+                        },
+                    );
                 };
+                // Otherwise, this case only reachable with undo then redo.
+                // Clear the deleted flag!
                 if node.leaf() != Some(ty) || !node.deleted {
                     return Err(CommandError::MismatchedState);
                 }
@@ -754,6 +1071,7 @@ impl crate::commands::CommandConsumer<commands::Command> for BlendGraph {
             }
             DoUndo::Undo(Command::LeafCreated {
                 target,
+                name: _,
                 ty,
                 child_idx: _, // FIXME!
                 destination: _,
@@ -876,4 +1194,78 @@ mod test {
         let clone = graph.clone();
         assert_eq!(clone.get(soup_id).map(NodeData::name), Some("Soup!"));
     }
+    #[test]
+    // Exact values are set, not computed.
+    #[allow(clippy::float_cmp)]
+    fn bulk_changes() {
+        let mut graph = BlendGraph::default();
+        let mut commands = smallvec::SmallVec::<[crate::commands::Command; 1]>::new();
+        let mut writer = writer::GraphWriter::new(&mut commands, &mut graph);
+        let leaf = |writer: &mut writer::GraphWriter<_>, name| -> AnyID {
+            writer
+                .add_leaf(
+                    LeafType::SolidColor {
+                        blend: Blend::default(),
+                        source: crate::color::ColorOrPalette::WHITE,
+                    },
+                    Location::IndexIntoRoot(usize::MAX),
+                    name,
+                )
+                .unwrap()
+                .into()
+        };
+        let (top, middle, bottom) = (
+            leaf(&mut writer, "Top"),
+            leaf(&mut writer, "Middle"),
+            leaf(&mut writer, "Bottom"),
+        );
+        let note = writer
+            .add_leaf(LeafType::Note, Location::IndexIntoRoot(0), "Note")
+            .unwrap()
+            .into();
+
+        // Notes have no blend to change.
+        writer
+            .change_blends(&[bottom, note, top], |blend| Blend {
+                opacity: 0.5,
+                ..blend
+            })
+            .unwrap();
+        let opacity =
+            |writer: &writer::GraphWriter<_>, id| writer.get(id).unwrap().blend().unwrap().opacity;
+        assert_eq!(opacity(&writer, top), 0.5);
+        assert_eq!(opacity(&writer, middle), 1.0);
+        assert_eq!(opacity(&writer, bottom), 0.5);
+
+        // Grouped top to bottom where the topmost was, regardless of the order chosen.
+        let group = writer
+            .group(&[bottom, top], NodeType::Passthrough, "Group")
+            .unwrap();
+        let children: Vec<_> = writer.iter_node(group).unwrap().map(|(id, _)| id).collect();
+        assert_eq!(children, [top, bottom]);
+        let top_level: Vec<_> = writer.iter_top_level().map(|(id, _)| id).collect();
+        assert_eq!(top_level, [note, group.into(), middle]);
+        // Nested targets move along with their parent.
+        let outer = writer
+            .group(&[top, group.into()], NodeType::Passthrough, "Outer")
+            .unwrap();
+        let children: Vec<_> = writer.iter_node(outer).unwrap().map(|(id, _)| id).collect();
+        assert_eq!(children, [AnyID::from(group)]);
+        assert!(writer.group(&[], NodeType::Passthrough, "Empty").is_err());
+
+        // All or nothing.
+        assert!(writer.delete_many(&[middle, outer.into()]).is_ok());
+        assert!(writer.delete_many(&[note, middle]).is_err());
+        let top_level: Vec<_> = writer.iter_top_level().map(|(id, _)| id).collect();
+        assert_eq!(top_level, [note]);
+        drop(writer);
+        assert_eq!(commands.len(), 13);
+    }
+    #[test]
+    fn color_tag_encoding() {
+        for tag in <ColorTag as strum::IntoEnumIterator>::iter() {
+            assert_eq!(ColorTag::try_from(u8::from(tag)), Ok(tag));
+        }
+        assert_eq!(ColorTag::try_from(200), Err(200));
+    }
 }
@@ -21,6 +21,20 @@ impl std::hash::Hash for AnyID {
         self.as_ref().hash(state);
     }
 }
+impl LeafID {
+    /// Allocate an ID for a leaf that is yet to be created, such as one being read from a file.
+    #[must_use]
+    pub(crate) fn new_unique() -> Self {
+        Self(FuzzNodeID::default())
+    }
+}
+impl NodeID {
+    /// Allocate an ID for a node that is yet to be created, such as one being read from a file.
+    #[must_use]
+    pub(crate) fn new_unique() -> Self {
+        Self(FuzzNodeID::default())
+    }
+}
 impl AsRef<FuzzNodeID> for LeafID {
     fn as_ref(&self) -> &FuzzNodeID {
         &self.0
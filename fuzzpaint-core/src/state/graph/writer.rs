@@ -33,6 +33,19 @@ impl<'a, Write: CommandWrite<Command>> GraphWriter<'a, Write> {
     pub fn name_mut(&mut self, target: super::AnyID) -> Option<&mut String> {
         self.graph.get_mut(target).map(super::NodeData::name_mut)
     }
+    /// Access the color tag of a node, or None if not found.
+    /// Like names, tag changes are NOT tracked by the command queue.
+    pub fn tag_mut(&mut self, target: super::AnyID) -> Option<&mut super::ColorTag> {
+        self.graph.get_mut(target).map(super::NodeData::tag_mut)
+    }
+    /// Access whether a leaf is a reference, or None if not found or not a leaf.
+    /// Like names, this is NOT tracked by the command queue.
+    pub fn reference_mut(&mut self, target: super::AnyID) -> Option<&mut bool> {
+        self.graph
+            .get_mut(target)
+            .filter(|data| data.is_leaf())
+            .map(|data| &mut data.reference)
+    }
     /// Change the blend of any node or leaf. Does not insert a command
     /// if the blend is identical to what it was before!
     /// Returns `MismatchedState` if the chosen node does not have a blend property to modify.
@@ -112,15 +125,17 @@ impl<'a, Write: CommandWrite<Command>> GraphWriter<'a, Write> {
         location: super::Location<'_>,
         name: impl Into<String>,
     ) -> Result<super::LeafID, TargetError> {
+        let name = name.into();
         let new_id = self
             .graph
-            .add_leaf(location, name.into(), leaf_ty.clone())?;
+            .add_leaf(location, name.clone(), leaf_ty.clone())?;
         // Is this useful? Roundtrips the Location into a true location.
         // Allows the graph to determine Location behavior rather than duplicating it :3
         let (parent, idx) = self.graph.location_of(new_id).unwrap();
 
         self.writer.write(Command::LeafCreated {
             target: new_id,
+            name,
             ty: leaf_ty,
             destination: parent,
             child_idx: idx,
@@ -164,15 +179,17 @@ impl<'a, Write: CommandWrite<Command>> GraphWriter<'a, Write> {
         location: super::Location<'_>,
         name: impl Into<String>,
     ) -> Result<super::NodeID, TargetError> {
+        let name = name.into();
         let new_id = self
             .graph
-            .add_node(location, name.into(), node_ty.clone())?;
+            .add_node(location, name.clone(), node_ty.clone())?;
         // Is this useful? Roundtrips the Location into a true location.
         // Allows the graph to determine Location behavior rather than duplicating it :3
         let (parent, idx) = self.graph.location_of(new_id).unwrap();
 
         self.writer.write(Command::NodeCreated {
             target: new_id,
+            name,
             ty: node_ty,
             destination: parent,
             child_idx: idx,
@@ -224,6 +241,80 @@ impl<'a, Write: CommandWrite<Command>> GraphWriter<'a, Write> {
 
         Ok(())
     }
+    /// Check that each target is present and not deleted, and drop repeats. For checking multi-target
+    /// changes before any part of them is applied.
+    fn live_targets(&self, targets: &[super::AnyID]) -> Result<Vec<super::AnyID>, TargetError> {
+        let mut live = Vec::with_capacity(targets.len());
+        for &target in targets {
+            let node = self.graph.get(target).ok_or(TargetError::TargetNotFound)?;
+            if node.deleted {
+                return Err(TargetError::TargetDeleted.into());
+            }
+            if !live.contains(&target) {
+                live.push(target);
+            }
+        }
+        Ok(live)
+    }
+    /// Change the blend of each target to the result of `change` on its current blend, as with
+    /// [`Self::change_blend`]. Targets without a blend, such as passthrough nodes, are skipped.
+    /// Nothing changes unless every target is present.
+    pub fn change_blends(
+        &mut self,
+        targets: &[super::AnyID],
+        mut change: impl FnMut(crate::blend::Blend) -> crate::blend::Blend,
+    ) -> Result<(), TargetError> {
+        for target in self.live_targets(targets)? {
+            let Some(from) = self.graph.get(target).and_then(super::NodeData::blend) else {
+                continue;
+            };
+            self.change_blend(target, change(from))?;
+        }
+        Ok(())
+    }
+    /// Delete each target. Nothing is deleted unless every target is present.
+    pub fn delete_many(&mut self, targets: &[super::AnyID]) -> Result<(), TargetError> {
+        for target in self.live_targets(targets)? {
+            self.delete(target)?;
+        }
+        Ok(())
+    }
+    /// Move the targets into a new node, placed where the topmost of them was. They keep the order they were
+    /// shown in, and targets within another target are moved along with it rather than on their own.
+    /// Nothing changes unless every target is present.
+    pub fn group(
+        &mut self,
+        targets: &[super::AnyID],
+        node_ty: super::NodeType,
+        name: impl Into<String>,
+    ) -> Result<super::NodeID, super::ReparentError> {
+        let mut targets = self.live_targets(targets).map_err(|err| match err {
+            CommandError::Inner(err) => super::ReparentError::TargetError(err).into(),
+            CommandError::MismatchedState => CommandError::MismatchedState,
+        })?;
+        let all = targets.clone();
+        targets.retain(|&target| {
+            !all.iter()
+                .any(|&other| other != target && self.graph.is_within(target, other))
+        });
+        // Unwrap ok - checked present above.
+        targets.sort_by_cached_key(|&target| self.graph.path_of(target).unwrap());
+        let Some(&topmost) = targets.first() else {
+            return Err(super::ReparentError::TargetError(TargetError::TargetNotFound).into());
+        };
+
+        let group = self
+            .add_node(node_ty, super::Location::AboveSelection(&topmost), name)
+            .map_err(|err| match err {
+                CommandError::Inner(err) => super::ReparentError::DestinationError(err).into(),
+                CommandError::MismatchedState => CommandError::MismatchedState,
+            })?;
+        for target in targets {
+            // Index too large is clamped to the bottom, after those already moved.
+            self.reparent(target, super::Location::IndexIntoNode(&group, usize::MAX))?;
+        }
+        Ok(group)
+    }
     pub fn set_inner_transform(
         &mut self,
         target: super::LeafID,
@@ -281,6 +372,9 @@ impl<'a, Write: CommandWrite<Command>> GraphWriter<'a, Write> {
             }
             | super::LeafType::Text {
                 outer_transform, ..
+            }
+            | super::LeafType::Image {
+                outer_transform, ..
             } => {
                 let old = *outer_transform;
                 if old == transform {
@@ -9,6 +9,238 @@ pub mod rich_text;
 pub mod stroke_collection;
 pub mod transform;
 
+/// How each stamp of a stroke is turned, which shows for brushes whose texture isn't round.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, strum::EnumIter, strum::AsRefStr)]
+pub enum StampOrientation {
+    /// Turned at random, hiding the grain of the texture.
+    #[default]
+    Random,
+    /// Along the direction of the stroke, as with a calligraphy nib.
+    Direction,
+    /// Towards the direction the pen leans, for strokes with tilt. Follows the stroke where there is none, or
+    /// the pen is upright.
+    Tilt,
+}
+
+/// Random variation of each stamp of a stroke, each zero for none. The variation is seeded by the stroke and
+/// the stamp's place within it, so the stroke looks the same every time it's drawn.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Scatter {
+    /// Furthest a stamp strays from the stroke, as a fraction of its diameter. Within [`Self::POSITION_RANGE`].
+    pub position: f32,
+    /// Most a stamp shrinks, as a fraction of its size. Within `0..=1`.
+    pub size: f32,
+    /// Furthest a stamp turns either way from its orientation, in radians. Within `0..=PI`.
+    pub angle: f32,
+}
+impl Scatter {
+    pub const POSITION_RANGE: std::ops::RangeInclusive<f32> = 0.0..=4.0;
+    /// Clamp each into range, zeroing any that aren't numbers.
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let clamp = |value: f32, max: f32| {
+            if value.is_nan() {
+                0.0
+            } else {
+                value.clamp(0.0, max)
+            }
+        };
+        Self {
+            position: clamp(self.position, *Self::POSITION_RANGE.end()),
+            size: clamp(self.size, 1.0),
+            angle: clamp(self.angle, std::f32::consts::PI),
+        }
+    }
+    #[must_use]
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A second texture, multiplied into every stamp to give it a grain the brush's own texture doesn't have.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DualStamp {
+    pub brush: crate::brush::UniqueID,
+    /// Size of the second texture relative to the stamp. Within [`Self::SCALE_RANGE`]. Smaller than the stamp,
+    /// it repeats across it.
+    pub scale: f32,
+}
+impl DualStamp {
+    pub const SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.05..=4.0;
+    /// Clamp the scale into range, or reset it if it isn't a number.
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let scale = if self.scale.is_nan() {
+            1.0
+        } else {
+            self.scale
+                .clamp(*Self::SCALE_RANGE.start(), *Self::SCALE_RANGE.end())
+        };
+        Self { scale, ..self }
+    }
+}
+
+/// Thinning of a stroke towards its ends and where it was drawn quickly, each zero for none. Applied to the
+/// stroke's pressure as it's finished, so changing it doesn't change strokes already drawn.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Taper {
+    /// Length, in pixels, over which the stroke thickens from its start. Within [`Self::LENGTH_RANGE`].
+    pub start: f32,
+    /// Length, in pixels, over which the stroke thins to its end. Within [`Self::LENGTH_RANGE`].
+    pub end: f32,
+    /// How much the stroke thins when drawn at [`Self::FAST_SPEED`] or faster. Within `0..=1`.
+    pub velocity: f32,
+}
+impl Taper {
+    pub const LENGTH_RANGE: std::ops::RangeInclusive<f32> = 0.0..=1000.0;
+    /// Speed, in pixels per second, at which `velocity` takes full effect.
+    pub const FAST_SPEED: f32 = 3000.0;
+    /// Clamp each into range, zeroing any that aren't numbers.
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let clamp = |value: f32, max: f32| {
+            if value.is_nan() {
+                0.0
+            } else {
+                value.clamp(0.0, max)
+            }
+        };
+        Self {
+            start: clamp(self.start, *Self::LENGTH_RANGE.end()),
+            end: clamp(self.end, *Self::LENGTH_RANGE.end()),
+            velocity: clamp(self.velocity, 1.0),
+        }
+    }
+    #[must_use]
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+    /// Factor of the pressure at `along` pixels into a stroke `length` long, drawn at `speed` pixels per
+    /// second if known.
+    #[must_use]
+    pub fn factor(&self, along: f32, length: f32, speed: Option<f32>) -> f32 {
+        // Eased, for a rounded rather than pointed tip.
+        let ramp = |distance: f32, over: f32| {
+            if over <= 0.0 {
+                1.0
+            } else {
+                let t = (distance / over).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+        };
+        let ends = ramp(along, self.start) * ramp(length - along, self.end);
+        let speed = speed.map_or(1.0, |speed| {
+            1.0 - self.velocity * (speed / Self::FAST_SPEED).clamp(0.0, 1.0)
+        });
+        ends * speed
+    }
+}
+
+/// What part of the pen's motion a [`Response`] follows. Each reads as fully applied where the stroke has
+/// no such data.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, strum::EnumIter, strum::AsRefStr)]
+pub enum DynamicsInput {
+    /// Always fully applied.
+    None,
+    #[default]
+    Pressure,
+    /// How far the pen leans, from upright to flat.
+    Tilt,
+    /// How quickly the stroke was drawn, fully applied at [`Taper::FAST_SPEED`] or faster.
+    Speed,
+    /// The pen's wheel, from none to a full turn.
+    Wheel,
+}
+
+/// How one output of a stroke responds to an input as it varies from none to full.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Response {
+    pub input: DynamicsInput,
+    /// Output with no input, as a fraction of the full output. Within `0..=1`.
+    pub min: f32,
+    /// Exponent of the input - above one it responds more towards full input, below one more towards none.
+    /// Within [`Self::GAMMA_RANGE`].
+    pub gamma: f32,
+}
+impl Response {
+    pub const GAMMA_RANGE: std::ops::RangeInclusive<f32> = 0.1..=10.0;
+    /// Fully applied regardless of input.
+    pub const CONSTANT: Self = Self {
+        input: DynamicsInput::None,
+        min: 0.0,
+        gamma: 1.0,
+    };
+    /// Proportional to pressure.
+    pub const PRESSURE: Self = Self {
+        input: DynamicsInput::Pressure,
+        ..Self::CONSTANT
+    };
+    /// Clamp each into range, resetting any that aren't numbers.
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let min = if self.min.is_nan() {
+            0.0
+        } else {
+            self.min.clamp(0.0, 1.0)
+        };
+        let gamma = if self.gamma.is_nan() {
+            1.0
+        } else {
+            self.gamma
+                .clamp(*Self::GAMMA_RANGE.start(), *Self::GAMMA_RANGE.end())
+        };
+        Self {
+            input: self.input,
+            min,
+            gamma,
+        }
+    }
+    /// Fraction of the full output for an input within `0..=1`. The tessellator evaluates the same.
+    #[must_use]
+    pub fn eval(&self, input: f32) -> f32 {
+        let input = if self.input == DynamicsInput::None {
+            1.0
+        } else {
+            input.clamp(0.0, 1.0)
+        };
+        (1.0 - self.min).mul_add(input.powf(self.gamma), self.min)
+    }
+}
+
+/// How the size, flow, and opacity of each stamp of a stroke follow the pen.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Dynamics {
+    /// Between the spacing and the full size.
+    pub size: Response,
+    pub flow: Response,
+    /// As strokes are blended continuously, this too applies to each stamp, building up where they overlap
+    /// just like flow. It's kept apart so that the two can follow different inputs.
+    pub opacity: Response,
+}
+impl Default for Dynamics {
+    fn default() -> Self {
+        Self {
+            size: Response::PRESSURE,
+            flow: Response::CONSTANT,
+            opacity: Response::CONSTANT,
+        }
+    }
+}
+impl Dynamics {
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        Self {
+            size: self.size.sanitized(),
+            flow: self.flow.sanitized(),
+            opacity: self.opacity.sanitized(),
+        }
+    }
+    #[must_use]
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 /// Per-stroke settings, i.e. ones we expect the user to change frequently without counting it as a "new brush."
 pub struct StrokeBrushSettings {
@@ -20,6 +252,14 @@ pub struct StrokeBrushSettings {
     pub size_mul: crate::util::FiniteF32,
     /// If true, the blend constants must be set to generate an erasing effect.
     pub is_eraser: bool,
+    /// If true, drag the colors already under the stroke along it instead of painting. The alpha of
+    /// `color_modulate` is the strength of the drag. Takes precedence over `is_eraser`.
+    pub is_smudge: bool,
+    pub orientation: StampOrientation,
+    pub scatter: Scatter,
+    pub dual: Option<DualStamp>,
+    pub taper: Taper,
+    pub dynamics: Dynamics,
     /// This should be a property of the brush, not the settings! brushes still todo tho :3
     /// For now, also the minimum size (diameter of brush at pressure near 0)
     pub spacing_px: crate::util::FiniteF32,
@@ -1,6 +1,22 @@
 use crate::{
     color::{Color, PaletteIndex},
     commands::{CommandConsumer, CommandError, DoUndo},
+    io::{
+        migrate::{Migrations, Reader},
+        riff::ChunkID,
+        Version,
+    },
+};
+
+/// The versions of `plte` chunks that can be read, and the version written.
+pub const PLTE_MIGRATIONS: Migrations<fn(&[u8]) -> std::io::Result<Palette>> = Migrations {
+    chunk: ChunkID::PLTE,
+    current: Version(0, 0, 0),
+    readers: &[Reader {
+        versions: Version(0, 0, 0)..=Version(0, 0, 0),
+        read: Palette::read_v0,
+    }],
+    upgrades: &[],
 };
 
 pub mod commands {
@@ -100,6 +116,82 @@ impl Palette {
                 exists.then_some((idx, color))
             })
     }
+    /// Commands which recreate this palette when replayed onto an empty one. Removed colors are left as gaps,
+    /// so indices are unchanged.
+    #[must_use]
+    pub fn creation_commands(&self) -> Vec<commands::Command> {
+        self.iter()
+            .map(|(target, &initial_color)| commands::Command::Added {
+                target,
+                initial_color,
+            })
+            .collect()
+    }
+    /// Encode every slot of the palette into a `plte` chunk. Removed slots are kept, so that
+    /// indices remain stable across a write/read roundtrip.
+    pub fn write_chunk_into(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+        use crate::io::{riff::encode::SizedBinaryChunkWriter, OrphanMode};
+        use std::io::Write;
+
+        let count = u32::try_from(self.colors.len())
+            .map_err(|_| std::io::Error::other(anyhow::anyhow!("too many palette entries")))?;
+        // Versioned header, count, entries.
+        let len = 8 + self.colors.len() * std::mem::size_of::<[f32; 4]>();
+
+        let mut chunk = SizedBinaryChunkWriter::new(writer, ChunkID::PLTE, len)?;
+        chunk.write_all(bytemuck::bytes_of(&PLTE_MIGRATIONS.current))?;
+        // Strokes refer to palette entries by index, a stale palette would scramble them.
+        chunk.write_all(&[OrphanMode::Discard as u8])?;
+        chunk.write_all(&count.to_le_bytes())?;
+        for &(exists, color) in &self.colors {
+            // Removed slots are written as NaN, which no live color can contain.
+            let channels = if exists {
+                color.as_array()
+            } else {
+                [f32::NAN; 4]
+            };
+            for channel in channels {
+                chunk.write_all(&channel.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+    /// Decode the payload of a `plte` chunk, as written by [`Self::write_chunk_into`].
+    pub fn read_chunk(reader: impl std::io::Read) -> std::io::Result<Self> {
+        let (read, payload) = PLTE_MIGRATIONS.read_payload(reader)?;
+        read(&payload)
+    }
+    /// Decode a version 0.0.0 `plte` payload, after the versioned header.
+    fn read_v0(mut reader: &[u8]) -> std::io::Result<Self> {
+        use std::io::{Error as IOError, Read};
+
+        let mut count = [0; 4];
+        reader.read_exact(&mut count)?;
+        let count = u32::from_le_bytes(count);
+
+        // Don't trust the count for allocation, a short chunk will EOF long before then.
+        let mut colors = Vec::with_capacity((count as usize).min(256));
+        for _ in 0..count {
+            let mut entry = [0; 16];
+            reader.read_exact(&mut entry)?;
+            let mut channels = [0.0f32; 4];
+            for (channel, bytes) in channels.iter_mut().zip(entry.chunks_exact(4)) {
+                // Infallible.
+                *channel = f32::from_le_bytes(bytes.try_into().unwrap());
+            }
+
+            if channels.iter().all(|channel| channel.is_nan()) {
+                colors.push((false, Color::TRANSPARENT));
+            } else {
+                let color = Color::from_array_lossy(channels)
+                    .map_err(|_| IOError::other(anyhow::anyhow!("non-finite palette color")))?;
+                colors.push((true, color));
+            }
+        }
+
+        Ok(Self { colors })
+    }
 }
 
 impl CommandConsumer<commands::Command> for Palette {
@@ -119,7 +211,17 @@ impl CommandConsumer<commands::Command> for Palette {
                         Ok(())
                     }
                 }
-                None => Err(CommandError::UnknownResource),
+                // Added after the state being replayed onto was taken, such as a checkpoint. Bring it back.
+                None => {
+                    let idx =
+                        usize::try_from(target.0).map_err(|_| CommandError::UnknownResource)?;
+                    if idx >= self.colors.len() {
+                        // Gaps left by colors added in history that isn't being replayed.
+                        self.colors.resize(idx + 1, (false, Color::TRANSPARENT));
+                    }
+                    self.colors[idx] = (true, *initial_color);
+                    Ok(())
+                }
             },
             DoUndo::Undo(Command::Added {
                 target,
@@ -155,3 +257,34 @@ impl CommandConsumer<commands::Command> for Palette {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn chunk_roundtrip() {
+        use crate::io::riff::{decode::BinaryChunkReader, ChunkID};
+        let mut palette = Palette::default();
+        let red = palette.push(Color::new_lossy(1.0, 0.0, 0.0, 1.0).unwrap());
+        let removed = palette.push(Color::WHITE);
+        let clear = palette.push(Color::TRANSPARENT);
+        palette.get_mut(removed).unwrap().0 = false;
+
+        let mut file = Vec::<u8>::new();
+        palette
+            .write_chunk_into(std::io::Cursor::new(&mut file))
+            .unwrap();
+
+        let chunk = BinaryChunkReader::new(std::io::Cursor::new(&file)).unwrap();
+        assert_eq!(chunk.id(), ChunkID::PLTE);
+        let mut read = Palette::read_chunk(chunk).unwrap();
+
+        // Indices must survive, including the gap left by the removed color.
+        let expected: Vec<_> = palette.iter().map(|(idx, &color)| (idx, color)).collect();
+        let read_colors: Vec<_> = read.iter().map(|(idx, &color)| (idx, color)).collect();
+        assert_eq!(read_colors, expected);
+        assert_eq!(read.get(red), palette.get(red));
+        assert_eq!(read.get(clear), Some(Color::TRANSPARENT));
+        assert!(!read.get_mut(removed).unwrap().0);
+    }
+}
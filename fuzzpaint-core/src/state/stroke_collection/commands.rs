@@ -20,5 +20,13 @@ pub enum StrokeCommand {
         target: super::ImmutableStrokeID,
         brush: crate::state::StrokeBrushSettings,
         points: crate::repositories::points::PointCollectionID,
+        clip: Option<crate::repositories::points::PointCollectionID>,
+        metadata: super::StrokeMetadata,
+    },
+    /// The stroke's brush was changed after it was drawn, such as to recolor it. Its points stay the same.
+    BrushChanged {
+        target: super::ImmutableStrokeID,
+        from: crate::state::StrokeBrushSettings,
+        to: crate::state::StrokeBrushSettings,
     },
 }
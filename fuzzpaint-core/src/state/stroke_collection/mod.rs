@@ -8,12 +8,42 @@ pub mod writer;
 pub type StrokeCollectionID = crate::FuzzID<StrokeCollection>;
 pub type ImmutableStrokeID = crate::FuzzID<ImmutableStroke>;
 
+/// A stroke, whose points never change once drawn. Its brush may, see
+/// [`StrokeCommand::BrushChanged`](commands::StrokeCommand::BrushChanged).
 #[derive(Copy, Clone)]
 pub struct ImmutableStroke {
     pub id: ImmutableStrokeID,
     pub brush: crate::state::StrokeBrushSettings,
     /// Points are managed and owned by the (point repository)[crate::repositories::points::PointRepository], not the stroke nor the queue.
     pub point_collection: crate::repositories::points::PointCollectionID,
+    /// The [selection](crate::selection) this stroke was drawn within, outside of which it has no effect.
+    pub clip: Option<crate::repositories::points::PointCollectionID>,
+    pub metadata: StrokeMetadata,
+}
+
+/// Where a stroke came from, for attributing it and for replaying a drawing in the order it was made. Every
+/// part is optional, as strokes read from older files have none, and not every source knows every part.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StrokeMetadata {
+    /// When the stroke was finished, in milliseconds since the unix epoch.
+    pub created: Option<u64>,
+    /// Who drew it. Reserved for collaboration, strokes drawn locally have none.
+    pub author: Option<u64>,
+    /// Serial number of the tool it was drawn with, if the tool reports one.
+    pub device: Option<u64>,
+}
+impl StrokeMetadata {
+    /// Created now, by an unknown author and device.
+    #[must_use]
+    pub fn now() -> Self {
+        Self {
+            created: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()
+                .and_then(|since| u64::try_from(since.as_millis()).ok()),
+            ..Self::default()
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -105,6 +135,29 @@ impl StrokeCollectionState {
         // Return, only if active.
         collection.active.then_some(collection)
     }
+    /// Commands which recreate the active collections and their active strokes when replayed onto an empty
+    /// state.
+    #[must_use]
+    pub fn creation_commands(&self) -> Vec<commands::Command> {
+        self.0
+            .iter()
+            .filter(|(_, collection)| collection.active)
+            .flat_map(|(&id, collection)| {
+                std::iter::once(commands::Command::Created(id)).chain(collection.iter_active().map(
+                    move |stroke| commands::Command::Stroke {
+                        target: id,
+                        command: commands::StrokeCommand::Created {
+                            target: stroke.id,
+                            brush: stroke.brush,
+                            points: stroke.point_collection,
+                            clip: stroke.clip,
+                            metadata: stroke.metadata,
+                        },
+                    },
+                ))
+            })
+            .collect()
+    }
 }
 // Private methods for modification by the writer/command applier
 impl StrokeCollectionState {
@@ -126,14 +179,30 @@ impl CommandConsumer<commands::StrokeCommand> for StrokeCollection {
                 target,
                 brush,
                 points,
+                clip,
+                metadata,
             }) => {
                 const NEW_ACTIVE: bool = true;
+                if !self.strokes.iter().any(|stroke| stroke.id == *target) {
+                    // Created after the state being replayed onto was taken, such as a checkpoint.
+                    // Strokes are only ever appended, so it belongs at the end.
+                    self.push_back(ImmutableStroke {
+                        id: *target,
+                        brush: *brush,
+                        point_collection: *points,
+                        clip: *clip,
+                        metadata: *metadata,
+                    });
+                    return Ok(());
+                }
                 let (stroke, mut active) =
                     self.get_mut(*target).ok_or(CommandError::UnknownResource)?;
 
                 // Was already set! Or, state doesn't match.
                 if *active == NEW_ACTIVE
                     || stroke.point_collection != *points
+                    || stroke.clip != *clip
+                    || stroke.metadata != *metadata
                     || &stroke.brush != brush
                 {
                     Err(CommandError::MismatchedState)
@@ -146,6 +215,8 @@ impl CommandConsumer<commands::StrokeCommand> for StrokeCollection {
                 target,
                 brush,
                 points,
+                clip,
+                metadata,
             }) => {
                 const NEW_ACTIVE: bool = false;
                 let (stroke, mut active) =
@@ -154,6 +225,8 @@ impl CommandConsumer<commands::StrokeCommand> for StrokeCollection {
                 // Was already set! Or, state doesn't match.
                 if *active == NEW_ACTIVE
                     || stroke.point_collection != *points
+                    || stroke.clip != *clip
+                    || stroke.metadata != *metadata
                     || &stroke.brush != brush
                 {
                     Err(CommandError::MismatchedState)
@@ -162,6 +235,22 @@ impl CommandConsumer<commands::StrokeCommand> for StrokeCollection {
                     Ok(())
                 }
             }
+            DoUndo::Do(commands::StrokeCommand::BrushChanged { target, from, to })
+            | DoUndo::Undo(commands::StrokeCommand::BrushChanged {
+                target,
+                from: to,
+                to: from,
+            }) => {
+                let (stroke, active) =
+                    self.get_mut(*target).ok_or(CommandError::UnknownResource)?;
+                // Deleted strokes can't be changed, and the brush must be as it was.
+                if !*active || &stroke.brush != from {
+                    Err(CommandError::MismatchedState)
+                } else {
+                    stroke.brush = *to;
+                    Ok(())
+                }
+            }
         }
     }
 }
@@ -171,7 +260,13 @@ impl CommandConsumer<commands::Command> for StrokeCollectionState {
     fn apply(&mut self, command: DoUndo<'_, commands::Command>) -> Result<(), CommandError> {
         match command {
             DoUndo::Do(commands::Command::Created(id)) => {
-                let collection = self.get_mut(*id).ok_or(CommandError::UnknownResource)?;
+                let collection = self.0.entry(*id).or_insert_with(|| {
+                    // Created after the state being replayed onto was taken, such as a checkpoint.
+                    StrokeCollection {
+                        active: false,
+                        ..Default::default()
+                    }
+                });
                 if collection.active {
                     Err(CommandError::MismatchedState)
                 } else {
@@ -199,3 +294,69 @@ impl CommandConsumer<commands::Command> for StrokeCollectionState {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        color::ColorOrPalette,
+        queue::{state_reader::CommandQueueStateReader, DocumentCommandQueue},
+        state::{self, StrokeBrushSettings},
+    };
+    #[test]
+    fn change_brush() {
+        let brush = StrokeBrushSettings {
+            brush: crate::brush::UniqueID([0; 32]),
+            color_modulate: ColorOrPalette::BLACK,
+            size_mul: crate::util::FiniteF32::new(1.0).unwrap(),
+            is_eraser: false,
+            is_smudge: false,
+            orientation: state::StampOrientation::Random,
+            scatter: state::Scatter::default(),
+            dual: None,
+            taper: state::Taper::default(),
+            dynamics: state::Dynamics::default(),
+            spacing_px: crate::util::FiniteF32::new(1.0).unwrap(),
+        };
+        let recolored = StrokeBrushSettings {
+            color_modulate: ColorOrPalette::WHITE,
+            ..brush
+        };
+        let queue = DocumentCommandQueue::new();
+        let (collection, stroke) = queue.write_with(|writer| {
+            let mut collections = writer.stroke_collections();
+            let collection = collections.insert();
+            let stroke = collections.get_mut(collection).unwrap().push_back(
+                brush,
+                crate::repositories::points::PointCollectionID::default(),
+                None,
+            );
+            (collection, stroke)
+        });
+        queue.write_with(|writer| {
+            let mut collections = writer.stroke_collections();
+            let mut collection = collections.get_mut(collection).unwrap();
+            // Nothing changes unless every target is present.
+            assert!(collection
+                .change_brushes(&[stroke, super::ImmutableStrokeID::default()], |_| {
+                    recolored
+                })
+                .is_err());
+            assert_eq!(collection.get(stroke).unwrap().brush, brush);
+            collection.change_brushes(&[stroke], |_| recolored).unwrap();
+        });
+
+        let brush_of = |queue: &DocumentCommandQueue| {
+            queue
+                .peek_clone_state()
+                .stroke_collections()
+                .get(collection)
+                .and_then(|collection| collection.get(stroke))
+                .map(|stroke| stroke.brush)
+        };
+        assert_eq!(brush_of(&queue), Some(recolored));
+        queue.undo_n(1);
+        assert_eq!(brush_of(&queue), Some(brush));
+        queue.redo_n(1);
+        assert_eq!(brush_of(&queue), Some(recolored));
+    }
+}
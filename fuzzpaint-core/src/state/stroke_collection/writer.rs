@@ -1,8 +1,8 @@
 use super::{
     commands, ImmutableStroke, ImmutableStrokeID, StrokeCollection, StrokeCollectionID,
-    StrokeCollectionState,
+    StrokeCollectionState, StrokeMetadata,
 };
-use crate::queue::writer::CommandWrite;
+use crate::{commands::CommandError, queue::writer::CommandWrite};
 
 pub struct StrokeCollectionWriter<'s, Writer: CommandWrite<commands::Command>> {
     id: StrokeCollectionID,
@@ -21,16 +21,30 @@ impl<'s, Writer: CommandWrite<commands::Command>> StrokeCollectionWriter<'s, Wri
     pub fn id(&self) -> StrokeCollectionID {
         self.id
     }
+    /// Add a stroke, optionally clipped to a [selection](crate::selection), created now.
     pub fn push_back(
         &mut self,
         brush: crate::state::StrokeBrushSettings,
         points: crate::repositories::points::PointCollectionID,
+        clip: Option<crate::repositories::points::PointCollectionID>,
+    ) -> ImmutableStrokeID {
+        self.push_back_with_metadata(brush, points, clip, StrokeMetadata::now())
+    }
+    /// As [`Self::push_back`], with the given metadata rather than just the time.
+    pub fn push_back_with_metadata(
+        &mut self,
+        brush: crate::state::StrokeBrushSettings,
+        points: crate::repositories::points::PointCollectionID,
+        clip: Option<crate::repositories::points::PointCollectionID>,
+        metadata: StrokeMetadata,
     ) -> ImmutableStrokeID {
         let id = ImmutableStrokeID::default();
         let stroke = ImmutableStroke {
             brush,
             id,
             point_collection: points,
+            clip,
+            metadata,
         };
         self.writer.write(commands::Command::Stroke {
             target: self.id,
@@ -38,12 +52,58 @@ impl<'s, Writer: CommandWrite<commands::Command>> StrokeCollectionWriter<'s, Wri
                 target: id,
                 brush,
                 points,
+                clip,
+                metadata,
             },
         });
         self.collection.push_back(stroke);
 
         id
     }
+    /// Change the brush of a stroke, keeping its points. Does not insert a command if the brush is identical
+    /// to what it was before.
+    pub fn change_brush(
+        &mut self,
+        target: ImmutableStrokeID,
+        to: crate::state::StrokeBrushSettings,
+    ) -> Result<(), CommandError> {
+        let (stroke, active) = self
+            .collection
+            .get_mut(target)
+            .ok_or(CommandError::UnknownResource)?;
+        if !*active {
+            return Err(CommandError::UnknownResource);
+        }
+        let from = stroke.brush;
+        if from != to {
+            stroke.brush = to;
+            self.writer.write(commands::Command::Stroke {
+                target: self.id,
+                command: commands::StrokeCommand::BrushChanged { target, from, to },
+            });
+        }
+        Ok(())
+    }
+    /// Change the brush of each target to the result of `change` on its current brush, as with
+    /// [`Self::change_brush`]. Nothing changes unless every target is present.
+    pub fn change_brushes(
+        &mut self,
+        targets: &[ImmutableStrokeID],
+        mut change: impl FnMut(crate::state::StrokeBrushSettings) -> crate::state::StrokeBrushSettings,
+    ) -> Result<(), CommandError> {
+        if targets
+            .iter()
+            .any(|&target| self.collection.get(target).is_none())
+        {
+            return Err(CommandError::UnknownResource);
+        }
+        for &target in targets {
+            // Unwrap ok - checked above.
+            let from = self.collection.get(target).unwrap().brush;
+            self.change_brush(target, change(from))?;
+        }
+        Ok(())
+    }
 }
 
 pub struct StrokeCollectionStateWriter<'s, Writer: CommandWrite<commands::Command>> {
@@ -93,6 +93,14 @@ impl Matrix {
             ],
         }
     }
+    #[must_use]
+    pub fn transform_point(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let e = &self.elements;
+        [
+            e[0][0] * x + e[1][0] * y + e[2][0],
+            e[0][1] * x + e[1][1] * y + e[2][1],
+        ]
+    }
 }
 
 impl Default for Matrix {
@@ -220,6 +220,33 @@ impl<'a> StrokeSlice<'a> {
             len: sliced_len,
         })
     }
+    /// Split into consecutive segments of at most `max_len` points, for strokes too long to store whole.
+    ///
+    /// Each segment begins with the last point of the one before, so that drawn one after another they leave
+    /// no gap. Points are borrowed unchanged, so values that accumulate along the stroke such as arc length
+    /// don't start from zero in any but the first. Yields `self` alone if it already fits.
+    ///
+    /// # Panics
+    /// If `max_len` is less than two and the stroke doesn't fit, as such segments can't overlap and advance.
+    pub fn segments(self, max_len: usize) -> impl Iterator<Item = Self> + 'a {
+        assert!(
+            max_len >= 2 || self.len <= max_len,
+            "segments must hold at least two points"
+        );
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            // Past the end, or the last segment has been yielded.
+            if start >= self.len.max(1) {
+                return None;
+            }
+            let end = start.saturating_add(max_len).min(self.len);
+            // Unwrap ok - in bounds by construction.
+            let segment = self.slice(start..end).unwrap();
+            // Overlap the next by one, or stop if this reached the end.
+            start = if end == self.len { usize::MAX } else { end - 1 };
+            Some(segment)
+        })
+    }
     /// Fetch the first point of this stroke slice. `None` if empty.
     pub fn first(&self) -> Option<BorrowedPoint<'a>> {
         self.get(0)
@@ -252,3 +279,24 @@ impl std::fmt::Debug for StrokeSlice<'_> {
         d.finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Archetype, StrokeSlice};
+    #[test]
+    fn segments_overlap() {
+        let elements: Vec<u32> = (0..7 * 2).collect();
+        let stroke = StrokeSlice::new(&elements, Archetype::POSITION).unwrap();
+        let segments: Vec<_> = stroke.segments(3).map(StrokeSlice::elements).collect();
+        assert_eq!(
+            segments,
+            [&elements[0..6], &elements[4..10], &elements[8..14],]
+        );
+        // Fits already.
+        assert_eq!(stroke.segments(7).count(), 1);
+        assert_eq!(
+            StrokeSlice::empty(Archetype::POSITION).segments(3).count(),
+            1
+        );
+    }
+}
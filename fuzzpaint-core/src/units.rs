@@ -1,5 +1,7 @@
 pub const CM_PER_IN: f32 = 2.54;
 pub const IN_PER_CM: f32 = 1.0 / CM_PER_IN;
+pub const MM_PER_CM: f32 = 10.0;
+pub const CM_PER_MM: f32 = 1.0 / MM_PER_CM;
 /// Constant varies by who you ask - but this is the one defined by W3C.
 pub const PT_PER_IN: f32 = 72.0;
 pub const IN_PER_PT: f32 = 1.0 / PT_PER_IN;
@@ -22,20 +24,29 @@ pub enum Length {
     /// Typographic points, as defined by W3C.
     Point(f32),
     Centimeter(f32),
+    Millimeter(f32),
 }
 impl Length {
     /// Access the numeric component of the length.
     #[must_use]
     pub fn value(self) -> f32 {
         match self {
-            Self::Logical(x) | Self::Inch(x) | Self::Point(x) | Self::Centimeter(x) => x,
+            Self::Logical(x)
+            | Self::Inch(x)
+            | Self::Point(x)
+            | Self::Centimeter(x)
+            | Self::Millimeter(x) => x,
         }
     }
     /// Access the numeric component of the length.
     #[must_use]
     pub fn value_mut(&mut self) -> &mut f32 {
         match self {
-            Self::Logical(x) | Self::Inch(x) | Self::Point(x) | Self::Centimeter(x) => x,
+            Self::Logical(x)
+            | Self::Inch(x)
+            | Self::Point(x)
+            | Self::Centimeter(x)
+            | Self::Millimeter(x) => x,
         }
     }
     /// Fetch the name of the unit.
@@ -46,6 +57,7 @@ impl Length {
             Self::Inch(_) => "in",
             Self::Point(_) => "pt",
             Self::Centimeter(_) => "cm",
+            Self::Millimeter(_) => "mm",
         }
     }
     #[must_use]
@@ -56,6 +68,7 @@ impl Length {
             Self::Point(p) => resolution.into_dpi() * (p * IN_PER_PT),
             Self::Inch(i) => resolution.into_dpi() * i,
             Self::Centimeter(cm) => resolution.into_dpcm() * cm,
+            Self::Millimeter(mm) => resolution.into_dpcm() * (mm * CM_PER_MM),
         }
     }
     #[must_use]
@@ -66,6 +79,7 @@ impl Length {
             Self::Point(p) => p * IN_PER_PT,
             Self::Inch(i) => i,
             Self::Centimeter(cm) => cm * IN_PER_CM,
+            Self::Millimeter(mm) => mm * const { CM_PER_MM * IN_PER_CM },
         }
     }
     #[must_use]
@@ -76,9 +90,15 @@ impl Length {
             Self::Point(p) => p * const { IN_PER_PT * CM_PER_IN },
             Self::Inch(i) => i * CM_PER_IN,
             Self::Centimeter(cm) => cm,
+            Self::Millimeter(mm) => mm * CM_PER_MM,
         }
     }
     #[must_use]
+    /// Convert into millimeters, under the given resolution.
+    pub fn into_millimeters(self, resolution: Resolution) -> f32 {
+        self.into_centimeters(resolution) * MM_PER_CM
+    }
+    #[must_use]
     /// Convert into points, under the given resolution.
     pub fn into_points(self, resolution: Resolution) -> f32 {
         match self {
@@ -86,6 +106,7 @@ impl Length {
             Self::Point(p) => p,
             Self::Inch(i) => i * PT_PER_IN,
             Self::Centimeter(cm) => cm * const { IN_PER_CM * PT_PER_IN },
+            Self::Millimeter(mm) => mm * const { CM_PER_MM * IN_PER_CM * PT_PER_IN },
         }
     }
     /// Add another length, under the given resolution, keeping the units of `self`
@@ -96,6 +117,7 @@ impl Length {
             Self::Inch(i) => Self::Inch(i + other.into_inches(resolution)),
             Self::Centimeter(cm) => Self::Centimeter(cm + other.into_centimeters(resolution)),
             Self::Point(pt) => Self::Point(pt + other.into_points(resolution)),
+            Self::Millimeter(mm) => Self::Millimeter(mm + other.into_millimeters(resolution)),
         }
     }
     /// Subtract another length, under the given resolution, keeping the units of `self`
@@ -151,6 +173,7 @@ impl std::str::FromStr for Length {
             "in" => Self::Inch(0.0),
             "pt" => Self::Point(0.0),
             "cm" => Self::Centimeter(0.0),
+            "mm" => Self::Millimeter(0.0),
             _ => return Err(UnitParseError::UnrecognizedUnit),
         };
 
@@ -160,8 +183,52 @@ impl std::str::FromStr for Length {
     }
 }
 
+/// The unit the user chooses to see and enter lengths in, such as brush sizes and document dimensions.
+/// Lengths are always stored in logical pixels, and converted under the document's [`Resolution`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, strum::EnumIter)]
+pub enum Unit {
+    #[default]
+    Pixel,
+    Millimeter,
+    Inch,
+}
+impl Unit {
+    /// A length of `value` in this unit.
+    #[must_use]
+    pub fn length(self, value: f32) -> Length {
+        match self {
+            Self::Pixel => Length::Logical(value),
+            Self::Millimeter => Length::Millimeter(value),
+            Self::Inch => Length::Inch(value),
+        }
+    }
+    /// The numeric value of a length in this unit, under the given resolution.
+    #[must_use]
+    pub fn of(self, length: Length, resolution: Resolution) -> f32 {
+        match self {
+            Self::Pixel => length.into_logical(resolution),
+            Self::Millimeter => length.into_millimeters(resolution),
+            Self::Inch => length.into_inches(resolution),
+        }
+    }
+    /// Abbreviated name of the unit.
+    #[must_use]
+    pub fn suffix(self) -> &'static str {
+        self.length(0.0).unit()
+    }
+    /// How many decimal places are worth showing, roughly a tenth of a pixel at common resolutions.
+    #[must_use]
+    pub fn decimals(self) -> usize {
+        match self {
+            Self::Pixel => 1,
+            Self::Millimeter => 2,
+            Self::Inch => 3,
+        }
+    }
+}
+
 /// Defines the relationship between logical pixels for rendering and physical units.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Resolution {
     /// Dots (logical pixels) per inch
     Dpi(f32),
@@ -208,3 +275,20 @@ impl std::fmt::Display for Resolution {
         write!(f, "{}{}", self.value(), self.unit())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Length, Resolution, Unit};
+    #[test]
+    fn unit_conversion() {
+        let resolution = Resolution::Dpi(300.0);
+        let inch = Length::Logical(300.0);
+        assert!((Unit::Inch.of(inch, resolution) - 1.0).abs() < 1e-5);
+        assert!((Unit::Millimeter.of(inch, resolution) - 25.4).abs() < 1e-4);
+        // Round trips through pixels.
+        let mm = Unit::Millimeter.length(12.5);
+        let px = Unit::Pixel.of(mm, resolution);
+        assert!((Unit::Millimeter.of(Length::Logical(px), resolution) - 12.5).abs() < 1e-4);
+        assert_eq!("3mm".parse::<Length>().map(Length::unit), Ok("mm"));
+    }
+}
@@ -71,3 +71,14 @@ impl std::hash::Hash for FiniteF32 {
 // Would be fun to impl the operators here too, but unfortunately *None of them* are closed over the set of Non-NaN floats!!
 // Ie, Inf - Inf = NaN, 0 * Inf = NaN....
 // Even if some were, we can't trust that no FPU is quirked.
+
+/// A fresh, empty directory for a test to write into, by `name` and unique to this process. Anything left over
+/// from an earlier run is cleared first. The test removes it once it passes, so failures can be looked at.
+#[cfg(any(test, feature = "test-util"))]
+#[must_use]
+pub fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("fuzzpaint-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,440 @@
+# Fuzzpaint's interface, in English. Every message shown in the interface must be here, as other
+# languages fall back on these for whatever they're missing.
+#
+# To translate, copy this file into the `locales` folder of fuzzpaint's settings directory, renamed to
+# the language's tag, such as `fr-FR.ftl`, and translate each message. It can then be chosen in the
+# settings, and is reloaded each time it is.
+
+# Shown in the language list, in the language itself.
+language-name = English
+
+## Menu bar
+
+menu-file = File
+menu-file-new = New
+menu-file-save = Save
+menu-file-save-as = Save as...
+    .hover = Save a copy, and open it for editing
+menu-file-open = Open
+menu-file-open-read-only = Open read-only...
+    .hover = Open without allowing changes, such as to look at a file open elsewhere
+menu-file-export = Export...
+menu-file-export-again = Export again
+    .hover = Export with the same settings as last time
+menu-file-export-layers = Export layers...
+    .hover = Write each top-level layer to a PNG of its own, for game assets and animation
+menu-file-export-timelapse = Export timelapse...
+    .hover = Replay the document's history into a video or image sequence
+menu-file-properties = Properties...
+    .hover = Title, author, and print resolution, saved with the document and written into exports
+
+menu-edit = Edit
+menu-edit-deselect = Deselect
+menu-edit-ruler = Ruler
+menu-edit-restroke-brush = Apply brush to strokes
+    .hover = Redraw the selected layer's strokes with the current brush, each keeping its color. Only those within the selection, if there is one.
+menu-edit-restroke-color = Apply color to strokes
+    .hover = Recolor the selected layer's strokes with the current color. Only those within the selection, if there is one.
+menu-edit-canvas-size = Canvas size...
+    .hover = Add room around the canvas or crop it. What's drawn stays where it is.
+menu-edit-crop-to-selection = Crop to selection
+    .hover = Fit the canvas to the selection
+menu-edit-trim-canvas = Trim to content
+    .hover = Fit the canvas to what's drawn, leaving out the transparent edges
+menu-edit-settings = Settings
+
+menu-view = View
+menu-view-diagnostics = Diagnostics
+menu-view-visualize = Visualize
+menu-view-visualize-wireframe = Stamp wireframe
+    .hover = Outline every stamp strokes are drawn with. Not supported by every graphics device.
+menu-view-visualize-tiles = Tile boundaries
+    .hover = Show the tiles the document is updated in.
+menu-view-visualize-overdraw = Overdraw
+    .hover = Draw strokes as a heatmap of how many stamps cover each pixel, from red to white.
+menu-view-selection-outline = Selection outline
+menu-view-hover-loupe = Hover loupe
+    .hover = Magnify beneath the pen while it hovers close above the tablet. Needs a pen that reports its distance.
+menu-view-reference-window = Reference window
+    .hover = Show the document in a second window, zoomed to fit.
+menu-view-screenshot = Screenshot
+menu-view-screenshot-ui = Include UI
+    .hover = Capture the whole window, rather than just the viewport.
+menu-view-screenshot-clipboard = Copy to clipboard
+menu-view-screenshot-save = Save as...
+menu-view-saved-diff = Changes since save
+    .hover = Highlight the parts of the document that changed since it was saved.
+menu-view-window = Window
+
+## Dialog titles
+
+modal-new-document = New Document
+modal-relink-assets = Missing assets
+modal-create-brush = Create Brush
+modal-command-palette = Command palette
+modal-canvas-size = Canvas size
+modal-properties = Document properties
+modal-crash = Fuzzpaint crashed
+modal-export = Export
+modal-export-layers = Export layers
+modal-export-timelapse = Export timelapse
+modal-settings = Settings
+modal-recover = Recover unsaved work
+
+## Layers
+
+layers-title = Layers
+layers-search = Search by name or tag
+layers-empty = Nothing here... Add some layers!
+layers-no-match = No matching layers.
+layers-subtree = Subtree of { $name }
+layers-solo = Showing only { $name }
+layers-show-everything = Show everything
+# The name of a layer that's since been deleted.
+layers-unknown = Unknown
+layers-selected = { $count } layers selected
+layers-group = Group
+    .hover = Move the selected layers into a new group
+layer-new = Add layer
+layer-new-stroke = Stroke Layer
+layer-new-text = Text Layer
+layer-new-fill = Fill Layer
+layer-new-gradient = Gradient Layer
+layer-new-note = Note
+layer-new-group = Blend Group
+layer-new-filter = Filter Group
+# Names given to new layers, until the user renames them.
+layer-name-stroke = Stroke Layer
+layer-name-text = Text
+layer-name-fill = Fill
+layer-name-gradient = Gradient
+layer-name-note = Note
+layer-name-group = Group
+layer-name-filter = Filter
+layer-merge-down = Merge down
+layer-delete = Delete selected layers
+layer-drag = Drag to reorder
+layer-select = Select { $name }
+    .hover = Ctrl+click to select several
+layer-solo = Solo
+    .hover = Show only this
+layer-reference = Reference
+    .hover = Reference - shown, but not exported
+layer-tag = Set color tag
+layer-focus-subtree = Focus Subtree
+layer-children = Children
+layer-alpha-clip = Alpha clip
+layer-passthrough = Passthrough
+layer-properties = { $name } properties
+layer-raster-image = Raster image
+layer-stroke-info = { $count } stroke items from { $collection }
+layer-use-color = Use this color
+layer-fill-color = Fill color
+layer-replace-color = Replace
+    .hover = Replace layer color with active color
+layer-filter = Filter
+layer-filter-radius = Radius
+layer-filter-brightness = Brightness
+layer-filter-contrast = Contrast
+layer-filter-hue = Hue
+layer-filter-saturation = Saturation
+layer-filter-lightness = Lightness
+layer-gradient-shape = Shape
+layer-gradient-place = Place with the gradient tool
+layer-gradient-replace =
+    .hover = Replace stop color with active color
+layer-gradient-remove-stop = Remove stop
+layer-gradient-add-stop = Add stop
+layer-inner-transform = Inner Transform
+layer-outer-transform = Outer Transform
+layer-transform-reset = Reset
+layer-transform-scale = Scale
+layer-transform-flip = Flip
+layer-transform-rotate = Rotate:
+layer-transform-position = Position:
+layer-transform-skew = Skew:
+
+## Tools, as named in the toolbox and hint bar
+
+tool-brush = Brush
+tool-eraser = Eraser
+tool-picker = Picker
+tool-lasso = Lasso
+tool-ruler = Ruler
+tool-gradient = Gradient
+tool-gizmos = Gizmos
+tool-pan = Pan View
+tool-rotate = Rotate View
+tool-scrub = Scrub View
+hint-all-commands = { $key }: all commands
+
+## Export timelapse
+
+# Without the extension, which is added after.
+timelapse-file-name = { $name } timelapse
+timelapse-video = MP4 video
+    .hover = Requires ffmpeg to be installed
+timelapse-sequence = PNG sequence
+timelapse-fps = Frames per second
+timelapse-interval = Actions per frame
+timelapse-trimmed = The oldest history has been deleted to save space, so the timelapse starts partway through.
+timelapse-canvas-paused = The canvas won't update until the timelapse is rendered.
+timelapse-export = Export...
+timelapse-cancel = Cancel
+
+## Changing strokes already drawn
+
+restroke-changed = { $count ->
+    [one] Changed 1 stroke
+   *[other] Changed { $count } strokes
+}
+restroke-failed = Failed to change the strokes
+
+## Canvas size
+
+canvas-size-width = Width:
+canvas-size-height = Height:
+canvas-size-anchor = Anchor
+canvas-size-note = Nothing drawn is moved or scaled. Whatever falls outside the new canvas is kept, just hidden.
+canvas-size-apply = Apply
+canvas-size-cancel = Cancel
+
+## Document properties
+
+properties-title = Title
+properties-author = Author
+properties-description = Description
+properties-resolution = Resolution
+    .hover = Pixels per inch when printed, also written into exported images
+properties-resolution-unit = DPI
+properties-size = Size
+properties-size-value = { $width } × { $height } in ({ $width_cm } × { $height_cm } cm)
+properties-save = Save
+properties-cancel = Cancel
+
+## Stroke inspector
+
+inspector-title = Stroke inspector
+inspector-no-layer = No layer selected.
+inspector-not-strokes = Not a stroke layer.
+inspector-count = { $count ->
+    [one] 1 stroke
+   *[other] { $count } strokes
+}
+inspector-row = #{ $index }: { $description }
+inspector-stroke = { $created }, { $author }, { $device }
+inspector-age-seconds = { $count }s ago
+inspector-age-minutes = { $count }m ago
+inspector-age-hours = { $count }h ago
+inspector-age-days = { $count }d ago
+# The stroke's clock disagrees with this one, such as with a stroke drawn on another machine.
+inspector-age-future = in the future
+inspector-unknown-time = unknown time
+inspector-unknown-author = unknown author
+inspector-author = author { $author }
+inspector-unknown-device = unknown device
+inspector-device = device { $device }
+
+## Closing with unsaved changes
+
+close-title = Exit
+close-unsaved = These documents have unsaved changes:
+close-save-all = Save all
+close-discard = Discard
+close-cancel = Cancel
+close-save-failed = Failed to save { $name }
+
+## Settings
+
+settings-ok = Ok
+    .disabled-hover = Cannot write settings while an error is present.
+settings-close = Close
+settings-pane-hotkeys = Hotkeys
+settings-pane-interface = Interface
+settings-pane-tablet = Tablet
+settings-restart = Takes effect after restarting.
+
+settings-language = Language
+settings-language-failed = Failed to load { $language }, showing English instead
+settings-units = Units
+    .hover = What brush sizes, rulers, and document sizes are shown in. Physical units follow the document's resolution.
+settings-units-pixel = Pixels
+settings-units-millimeter = Millimeters
+settings-units-inch = Inches
+settings-ui-scale = UI scale
+settings-display-scale = Display scale { $native }×, total { $total }×
+settings-low-latency = Low latency mode
+    .hover = Show strokes sooner, at the cost of higher power use and possible tearing.
+settings-software-unavailable = Unavailable when rendering in software.
+settings-sharp-zoom = Sharp zoom
+    .hover = Redraw strokes at the zoomed resolution instead of magnifying the image.
+settings-smooth-edge = Smooth document edge
+    .hover = Blend the edge of the document into the background, rather than stepping at in-between zooms.
+settings-document-border = Document border
+    .hover = Outline the document with a faint line.
+settings-preview-buffers = Preview buffers
+    .hover = With 3, new frames of the document can be drawn while the last waits to be shown. Smoother on fast displays, but uses more memory.
+settings-backups = Backups
+    .hover = Previous versions of a document to keep beside it when saving over it.
+settings-saved-history = Saved history
+    .hover = Steps of undo to keep in a document when saving it, so they can be undone after reopening.
+settings-history-memory = Undo history
+    .hover = Memory the undo history of each open document may take up. Older steps are moved to disk, and can no longer be undone.
+settings-history-spool = History on disk
+    .hover = Space each document's older steps may take up on disk before the oldest are deleted. 0 deletes them straight away.
+settings-out-of-bounds = Off the edge
+    .hover = What becomes of strokes drawn off the edge of the document.
+settings-out-of-bounds-retain = Cut off at the edge
+settings-out-of-bounds-clamp = Run along the edge
+settings-window = Window
+settings-startup = Startup
+settings-startup-tool = Tool
+settings-startup-brush = Brush preset
+settings-startup-brush-none = Plain round
+settings-startup-template = New documents
+    .hover = The template new documents start from.
+settings-startup-reopen = Pick up where the last session left off
+    .hover = Reopens its documents, with their views and selected layers, when started without any others to open, and chooses its tool.
+settings-startup-new-document = Start with a new document
+    .hover = When no documents are opened.
+settings-device = Graphics device
+settings-device-automatic = Automatic
+settings-device-unsuitable = Missing features required by fuzzpaint.
+settings-device-missing = "{ $device }" is not available.
+
+settings-full-pressure = Full pressure at
+    .hover = How hard to press before reaching full pressure.
+settings-pressure-curve = Curve
+    .hover = Above one, light strokes become lighter.
+settings-pressure-smoothing = Pressure smoothing
+    .hover = Evens out noisy pressure, without slowing the cursor. Higher is smoother, but lags behind changes.
+settings-calibrate = Calibrate...
+settings-reset = Reset
+settings-tablet-mapping = Map tablet to
+    .hover = Where the tablet points to. The pen is only seen over the window, so the window and canvas work best with it maximized.
+settings-tablet-mapping-desktop = Whole desktop
+settings-tablet-mapping-window = Window
+settings-tablet-mapping-canvas = Canvas
+settings-tablet-mapping-absolute = Absolute
+    .hover = Each point of the tablet is a point of the target, like a pen on paper.
+settings-tablet-mapping-relative = Relative
+    .hover = The pointer moves as the pen does, like a mouse.
+settings-tablet-mapping-speed = Speed
+    .hover = How far the pointer moves for each movement of the pen.
+settings-tablet-mapping-keep-aspect = Keep aspect ratio
+    .hover = Shrink the target to the shape of the tablet, so circles stay round.
+settings-tablet-mapping-aspect = Tablet shape
+    .hover = Width over height of the tablet's active area.
+settings-tablet-mapping-preview = Click to choose what the tablet maps to.
+settings-mouse-pressure = Mouse pressure
+settings-mouse-pressure-amount = Pressure
+settings-mouse-fade-in = Fade in
+    .hover = Time for pressure to build at the start of a stroke. Zero starts at full pressure.
+settings-mouse-fade-speed = Fade out at
+    .hover = Pointer speed at which pressure fades away, for tapered flicks. Zero ignores speed.
+
+settings-hotkeys-error = An error occured reading the settings file. Defaults have been used. To prevent data loss, the file will not be overwritten.
+settings-hotkeys-retry = Retry
+    .hover = Try loading the file again.
+settings-hotkeys-overwrite = ⚠ Allow overwrite
+    .hover = Ignore the error, allowing replacing the erroneous file with new values. Existing settings data will be lost!
+
+## Banners over the document
+
+banner-software = ⚠ Rendering on the CPU, so everything will be slow.
+    .remedy = Install or update the Vulkan driver for your graphics card, or pick it in Settings > Interface. Low latency mode is disabled in the meantime.
+banner-dismiss = Dismiss
+banner-read-only = 🔒 Read-only, changes to this document are ignored.
+    .salvaged = Its file is damaged, and this is what could be salvaged from it.
+    .opened = It was opened read-only, or is open in another window.
+banner-save-as = Save as...
+banner-skipped = What couldn't be read ({ $count })
+
+## Errors
+
+toast-dismiss = Dismiss
+# A toast for an error that happened several times over.
+toast-repeated = { $summary } (×{ $count })
+fatal-title = Something went wrong
+fatal-details = Details
+fatal-recovered = Recovery copies saved to { $directory }
+fatal-save-recovery = Save recovery copies
+fatal-dismiss = Dismiss
+open-failed = Failed to open { $path }
+save-failed = Failed to save { $path }
+save-document-failed = Failed to save the document
+export-failed = Failed to export { $path }
+export-layers-failed = Failed to export layers to { $directory }
+export-timelapse-failed = Failed to export timelapse { $path }
+journal-start-failed = Failed to start a journal, changes won't be recoverable after a crash
+journal-write-failed = Failed to journal changes, they won't be recoverable after a crash
+recovery-directory-failed = Failed to create a recovery directory
+recovery-save-failed = Failed to save a recovery copy of { $name }
+preferences-save-failed = Failed to save preferences
+screenshot-failed = Failed to take a screenshot
+screenshot-save-failed = Failed to save the screenshot
+window-draw-failed = Failed to draw the window
+window-options-failed = Failed to change the window's options
+reference-window-open-failed = Failed to open the reference window
+reference-window-failed = The reference window failed, closing it
+renderer-stopped = The renderer stopped
+    .remedy = Save recovery copies of your work, then restart fuzzpaint.
+# What to do about an error from the graphics device, shown below its summary.
+remedy-device-lost = The GPU stopped responding. Save a recovery copy of your work, then restart fuzzpaint.
+remedy-out-of-memory = Out of memory. Closing other documents or applications may help.
+remedy-bug = This is likely a bug in fuzzpaint. Please report it, along with the log.
+
+## Actions, as named in the command palette, hotkey settings and hint bar
+
+action-category-edit = Edit
+action-category-view = View
+action-category-tools = Tools
+action-category-brush = Brush
+action-category-layers = Layers
+action-category-file = File
+action-category-window = Window
+
+action-undo = Undo
+action-redo = Redo
+action-copy = Copy
+action-paste = Paste
+action-paste-in-place = Paste in place
+action-viewport-pan = Pan view
+action-viewport-scrub = Scrub zoom
+action-viewport-rotate = Rotate view
+action-viewport-flip-horizontal = Flip view horizontally
+action-viewport-flip-vertical = Flip view vertically
+action-viewport-rotate-cw = Rotate view clockwise
+action-viewport-rotate-ccw = Rotate view counterclockwise
+action-viewport-grayscale = Toggle grayscale view
+action-viewport-filter-cycle = Cycle view filter
+action-zoom-in = Zoom in
+action-zoom-out = Zoom out
+action-picker = Picker tool
+action-picker-hold = Pick color while held
+action-gizmo = Gizmo tool
+action-brush = Brush tool
+action-erase = Erase
+action-lasso = Lasso tool
+action-ruler = Ruler tool
+action-nudge-left = Nudge left
+action-nudge-right = Nudge right
+action-nudge-up = Nudge up
+action-nudge-down = Nudge down
+action-brush-size-up = Increase brush size
+action-brush-size-down = Decrease brush size
+action-color-swap = Swap colors
+action-layer-up = Layer up
+action-layer-down = Layer down
+action-layer-new = New layer
+action-layer-delete = Delete layer
+action-new = New document
+action-save = Save
+action-save-as = Save as
+action-open = Open
+action-export-again = Export again
+action-screenshot = Screenshot viewport
+action-window-transparent = Toggle transparent window
+action-window-on-top = Toggle always on top
+action-window-borderless = Toggle borderless window
+action-command-palette = Command palette
@@ -29,6 +29,33 @@ pub const KEYBOARD: &[(Action, &[KeyboardHotkey])] = &[
             },
         ],
     ),
+    (
+        Action::Copy,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: false,
+            key: KeyCode::KeyC,
+        }],
+    ),
+    (
+        Action::Paste,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: false,
+            key: KeyCode::KeyV,
+        }],
+    ),
+    (
+        Action::PasteInPlace,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: true,
+            key: KeyCode::KeyV,
+        }],
+    ),
     (
         Action::ViewportPan,
         &[KeyboardHotkey {
@@ -74,6 +101,51 @@ pub const KEYBOARD: &[(Action, &[KeyboardHotkey])] = &[
             key: KeyCode::KeyM,
         }],
     ),
+    (
+        Action::ViewportFlipVertical,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: true,
+            key: KeyCode::KeyM,
+        }],
+    ),
+    (
+        Action::ViewportRotateCW,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::Period,
+        }],
+    ),
+    (
+        Action::ViewportRotateCCW,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::Comma,
+        }],
+    ),
+    (
+        Action::ViewportGrayscale,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::KeyY,
+        }],
+    ),
+    (
+        Action::ViewportFilterCycle,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: true,
+            key: KeyCode::KeyY,
+        }],
+    ),
     (
         Action::ZoomIn,
         &[KeyboardHotkey {
@@ -102,6 +174,15 @@ pub const KEYBOARD: &[(Action, &[KeyboardHotkey])] = &[
             key: KeyCode::KeyI,
         }],
     ),
+    (
+        Action::PickerHold,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: true,
+            key: KeyCode::KeyI,
+        }],
+    ),
     (
         Action::Gizmo,
         &[KeyboardHotkey {
@@ -139,6 +220,15 @@ pub const KEYBOARD: &[(Action, &[KeyboardHotkey])] = &[
             key: KeyCode::KeyL,
         }],
     ),
+    (
+        Action::Ruler,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::KeyU,
+        }],
+    ),
     (
         Action::BrushSizeDown,
         &[KeyboardHotkey {
@@ -211,4 +301,103 @@ pub const KEYBOARD: &[(Action, &[KeyboardHotkey])] = &[
             key: KeyCode::ArrowDown,
         }],
     ),
+    (
+        Action::NudgeLeft,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::ArrowLeft,
+        }],
+    ),
+    (
+        Action::NudgeRight,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::ArrowRight,
+        }],
+    ),
+    (
+        Action::NudgeUp,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::ArrowUp,
+        }],
+    ),
+    (
+        Action::NudgeDown,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::ArrowDown,
+        }],
+    ),
+    (
+        Action::New,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: false,
+            key: KeyCode::KeyN,
+        }],
+    ),
+    (
+        Action::Save,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: false,
+            key: KeyCode::KeyS,
+        }],
+    ),
+    (
+        Action::SaveAs,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: true,
+            key: KeyCode::KeyS,
+        }],
+    ),
+    (
+        Action::Open,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: false,
+            key: KeyCode::KeyO,
+        }],
+    ),
+    (
+        Action::ExportAgain,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: true,
+            key: KeyCode::KeyE,
+        }],
+    ),
+    (
+        Action::Screenshot,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: false,
+            shift: false,
+            key: KeyCode::F12,
+        }],
+    ),
+    (
+        Action::CommandPalette,
+        &[KeyboardHotkey {
+            alt: false,
+            ctrl: true,
+            shift: true,
+            key: KeyCode::KeyP,
+        }],
+    ),
 ];
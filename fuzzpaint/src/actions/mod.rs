@@ -30,19 +30,46 @@ pub enum Action {
     Undo,
     Redo,
 
+    /// Copy the strokes of the layer within the selection, or the whole layer.
+    Copy,
+    /// Paste, centered on the cursor.
+    Paste,
+    /// Paste where it was copied from.
+    PasteInPlace,
+
     ViewportPan,
     ViewportScrub,
     ViewportRotate,
     ViewportFlipHorizontal,
+    ViewportFlipVertical,
+    /// Rotate the view clockwise by a fixed step.
+    ViewportRotateCW,
+    /// Rotate the view counterclockwise by a fixed step.
+    ViewportRotateCCW,
+
+    /// Toggle previewing the document in grayscale, to check values.
+    ViewportGrayscale,
+    /// Step through each [`crate::document_viewport_proxy::ViewFilter`].
+    ViewportFilterCycle,
 
     ZoomIn,
     ZoomOut,
 
     Picker,
+    /// Pick colors while held, returning to the tool from before once released.
+    PickerHold,
     Gizmo,
     Brush,
     Erase,
     Lasso,
+    /// Edit the document's ruler.
+    Ruler,
+
+    /// Move the tool's focused handle one document pixel. Scrolling over a handle nudges it too.
+    NudgeLeft,
+    NudgeRight,
+    NudgeUp,
+    NudgeDown,
 
     BrushSizeUp,
     BrushSizeDown,
@@ -53,8 +80,168 @@ pub enum Action {
     LayerDown,
     LayerNew,
     LayerDelete,
+
+    /// Choose a template for a new document.
+    New,
+    Save,
+    /// Save a copy of the document, and open it for editing.
+    SaveAs,
+    Open,
+    /// Repeat the last export of the document.
+    ExportAgain,
+    /// Copy what the viewport shows to the clipboard, see [`crate::export::ScreenshotSettings`].
+    Screenshot,
+
+    /// Toggle showing the windows beneath through the canvas, see [`crate::window::WindowOptions`].
+    WindowTransparent,
+    /// Toggle keeping the window above all others.
+    WindowOnTop,
+    /// Toggle the window's title bar and border.
+    WindowBorderless,
+
+    /// Search for and run any action by name.
+    CommandPalette,
+}
+/// Grouping of [`Action`]s, for display.
+#[derive(strum::AsRefStr, strum::EnumIter, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Category {
+    Edit,
+    View,
+    Tools,
+    Brush,
+    Layers,
+    File,
+    Window,
 }
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+impl Category {
+    /// Human-readable name, in the interface's language.
+    #[must_use]
+    pub fn name(self) -> String {
+        crate::i18n::tr!(match self {
+            Self::Edit => "action-category-edit",
+            Self::View => "action-category-view",
+            Self::Tools => "action-category-tools",
+            Self::Brush => "action-category-brush",
+            Self::Layers => "action-category-layers",
+            Self::File => "action-category-file",
+            Self::Window => "action-category-window",
+        })
+    }
+}
+impl Action {
+    /// Human-readable name, in sentence case and the interface's language.
+    #[must_use]
+    pub fn name(self) -> String {
+        crate::i18n::tr!(self.message_id())
+    }
+    /// ID of the [name](Self::name) among the interface's strings, see [`crate::i18n`].
+    #[must_use]
+    pub fn message_id(self) -> &'static str {
+        match self {
+            Self::Undo => "action-undo",
+            Self::Redo => "action-redo",
+            Self::Copy => "action-copy",
+            Self::Paste => "action-paste",
+            Self::PasteInPlace => "action-paste-in-place",
+            Self::ViewportPan => "action-viewport-pan",
+            Self::ViewportScrub => "action-viewport-scrub",
+            Self::ViewportRotate => "action-viewport-rotate",
+            Self::ViewportFlipHorizontal => "action-viewport-flip-horizontal",
+            Self::ViewportFlipVertical => "action-viewport-flip-vertical",
+            Self::ViewportRotateCW => "action-viewport-rotate-cw",
+            Self::ViewportRotateCCW => "action-viewport-rotate-ccw",
+            Self::ViewportGrayscale => "action-viewport-grayscale",
+            Self::ViewportFilterCycle => "action-viewport-filter-cycle",
+            Self::ZoomIn => "action-zoom-in",
+            Self::ZoomOut => "action-zoom-out",
+            Self::Picker => "action-picker",
+            Self::PickerHold => "action-picker-hold",
+            Self::Gizmo => "action-gizmo",
+            Self::Brush => "action-brush",
+            Self::Erase => "action-erase",
+            Self::Lasso => "action-lasso",
+            Self::Ruler => "action-ruler",
+            Self::NudgeLeft => "action-nudge-left",
+            Self::NudgeRight => "action-nudge-right",
+            Self::NudgeUp => "action-nudge-up",
+            Self::NudgeDown => "action-nudge-down",
+            Self::BrushSizeUp => "action-brush-size-up",
+            Self::BrushSizeDown => "action-brush-size-down",
+            Self::ColorSwap => "action-color-swap",
+            Self::LayerUp => "action-layer-up",
+            Self::LayerDown => "action-layer-down",
+            Self::LayerNew => "action-layer-new",
+            Self::LayerDelete => "action-layer-delete",
+            Self::New => "action-new",
+            Self::Save => "action-save",
+            Self::SaveAs => "action-save-as",
+            Self::Open => "action-open",
+            Self::ExportAgain => "action-export-again",
+            Self::Screenshot => "action-screenshot",
+            Self::WindowTransparent => "action-window-transparent",
+            Self::WindowOnTop => "action-window-on-top",
+            Self::WindowBorderless => "action-window-borderless",
+            Self::CommandPalette => "action-command-palette",
+        }
+    }
+    #[must_use]
+    pub fn category(self) -> Category {
+        match self {
+            Self::Undo | Self::Redo | Self::Copy | Self::Paste | Self::PasteInPlace => {
+                Category::Edit
+            }
+            Self::ViewportPan
+            | Self::ViewportScrub
+            | Self::ViewportRotate
+            | Self::ViewportFlipHorizontal
+            | Self::ViewportFlipVertical
+            | Self::ViewportRotateCW
+            | Self::ViewportRotateCCW
+            | Self::ViewportGrayscale
+            | Self::ViewportFilterCycle
+            | Self::ZoomIn
+            | Self::ZoomOut => Category::View,
+            Self::Picker
+            | Self::PickerHold
+            | Self::Gizmo
+            | Self::Brush
+            | Self::Erase
+            | Self::Lasso
+            | Self::Ruler
+            | Self::NudgeLeft
+            | Self::NudgeRight
+            | Self::NudgeUp
+            | Self::NudgeDown => Category::Tools,
+            Self::BrushSizeUp | Self::BrushSizeDown | Self::ColorSwap => Category::Brush,
+            Self::LayerUp | Self::LayerDown | Self::LayerNew | Self::LayerDelete => {
+                Category::Layers
+            }
+            Self::New
+            | Self::Save
+            | Self::SaveAs
+            | Self::Open
+            | Self::ExportAgain
+            | Self::Screenshot => Category::File,
+            Self::WindowTransparent
+            | Self::WindowOnTop
+            | Self::WindowBorderless
+            | Self::CommandPalette => Category::Window,
+        }
+    }
+    /// True if the action only does anything while held, and so can't be run by a single press.
+    #[must_use]
+    pub fn is_hold(self) -> bool {
+        matches!(
+            self,
+            Self::ViewportPan
+                | Self::ViewportScrub
+                | Self::ViewportRotate
+                | Self::Erase
+                | Self::PickerHold
+        )
+    }
+}
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
 pub enum ActionEvent {
     Press,
     /// The action was held long enough that the key is being strobed by the OS.
@@ -135,6 +322,7 @@ type SenderStateLock = parking_lot::RwLock<(
     tokio::sync::broadcast::Receiver<(ActionEvent, Action)>,
 )>;
 
+#[derive(Clone)]
 pub struct ActionSender {
     // Weak, as we can stop carrying/updating this info when it stops being possible to create listeners
     // (ie, when the holder of the Arc is dropped)
@@ -157,7 +345,8 @@ impl ActionSender {
     pub fn unshadow(&self, action: Action) {
         self.push(ActionEvent::Unshadowed, action);
     }
-    fn oneshot(&self, action: Action) {
+    /// Press and immediately release, as if the action's hotkey was tapped.
+    pub fn oneshot(&self, action: Action) {
         // Double locks, could speed up.
         self.press(action);
         self.release(action);
@@ -316,6 +505,11 @@ pub struct ActionFrame {
     actions: Vec<(ActionEvent, Action)>,
 }
 impl ActionFrame {
+    /// Every event of the frame, in the order they occured.
+    #[must_use]
+    pub fn events(&self) -> &[(ActionEvent, Action)] {
+        &self.actions
+    }
     /// Count the number of times this action was triggered since the last frame.
     /// For actions that trigger once on press - like undo.
     /// Will return multiple counts if the OS repeats the key, +1 on the releasing edge.
@@ -398,3 +592,17 @@ impl ActionFrame {
         future
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Action;
+    #[test]
+    fn names_unique() {
+        let mut names = std::collections::HashSet::new();
+        for action in <Action as strum::IntoEnumIterator>::iter() {
+            assert!(names.insert(action.name()), "{action:?}");
+            // Every message exists in the built in language.
+            assert_ne!(action.name(), action.message_id(), "{action:?}");
+        }
+    }
+}
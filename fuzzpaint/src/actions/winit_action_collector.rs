@@ -26,74 +26,12 @@ impl WinitKeyboardActionCollector {
     pub fn push_event(&mut self, event: &winit::event::WindowEvent) {
         use winit::event::WindowEvent;
 
-        let hotkeys = crate::global::hotkeys::Hotkeys::read();
         match event {
             WindowEvent::KeyboardInput { event, .. } => {
                 let winit::keyboard::PhysicalKey::Code(code) = event.physical_key else {
                     return;
                 };
-
-                // Update currently_pressed set accordingly:
-                let was_pressed = if event.state.is_pressed() {
-                    // Returns true if WASN'T present
-                    !self.currently_pressed.insert(code)
-                } else {
-                    // Returns true if WAS present
-                    self.currently_pressed.remove(&code)
-                };
-
-                // Depending on the status of ctrl, shift, and alt, this key
-                // event could correspond to eight different actions. Check
-                // them all!
-
-                // Copy so that the iter does not borrow self.
-                let ctrl = self.ctrl;
-                let shift = self.shift;
-                let alt = self.alt;
-                let possible_keys = (0u8..(1
-                    << (u8::from(ctrl) + u8::from(shift) + u8::from(alt))))
-                    .map(|mut bits| {
-                        // Generates all unique combos of each flag where self.<flag> is set.
-                        // Or false if not set.
-                        let mut consume = |condition: bool| {
-                            if condition {
-                                let bit = bits & 1 == 1;
-                                bits >>= 1;
-                                bit
-                            } else {
-                                false
-                            }
-                        };
-                        super::hotkeys::KeyboardHotkey {
-                            key: code,
-                            alt: consume(alt),
-                            shift: consume(shift),
-                            ctrl: consume(ctrl),
-                        }
-                    })
-                    .filter_map(|key| {
-                        // find the action of each key, or skip if none.
-                        Some((hotkeys.keys_to_actions().action_of(key)?, key))
-                    });
-
-                match (was_pressed, event.state.is_pressed()) {
-                    // Just pressed
-                    (false, true) => {
-                        possible_keys.for_each(|(action, key)| self.push_key(action, key));
-                    }
-                    // OS key repeat
-                    (true, true) => possible_keys.for_each(|(action, _)| {
-                        // No bookkeeping to do, just emit directly
-                        self.sender.repeat(action);
-                    }),
-                    // Just released
-                    (_, false) => {
-                        possible_keys.for_each(|(action, key)| self.pop_key(action, key));
-                    }
-                }
-
-                // Shouldn't need to happen but it's not working and i'm getting tired of debugging TwT
-                self.cull();
+                self.push_key_code(code, event.state.is_pressed());
             }
             WindowEvent::ModifiersChanged(m) => {
                 let state = m.state();
@@ -114,28 +52,116 @@ impl WinitKeyboardActionCollector {
                 self.cull();
             }
             WindowEvent::MouseWheel { delta, .. } => {
-                let steps = match delta {
-                    winit::event::MouseScrollDelta::LineDelta(_, y) => y.ceil() as i32,
-                    winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y.ceil() as i32,
+                use crate::actions::Action;
+                let (x_steps, steps) = match delta {
+                    winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                        (x.ceil() as i32, y.ceil() as i32)
+                    }
+                    winit::event::MouseScrollDelta::PixelDelta(pos) => {
+                        (pos.x.ceil() as i32, pos.y.ceil() as i32)
+                    }
+                };
+                // Over a handle, scrolling nudges it instead of zooming.
+                let captured = crate::pen_tools::scroll_captured();
+                let (up, down) = if captured {
+                    (Action::NudgeUp, Action::NudgeDown)
+                } else {
+                    (Action::ZoomIn, Action::ZoomOut)
                 };
 
                 match steps {
                     1.. => {
                         for _ in 0..steps {
-                            self.sender.oneshot(crate::actions::Action::ZoomIn);
+                            self.sender.oneshot(up);
                         }
                     }
                     ..=-1 => {
                         for _ in 0..-steps {
-                            self.sender.oneshot(crate::actions::Action::ZoomOut);
+                            self.sender.oneshot(down);
                         }
                     }
                     _ => (),
                 }
+                if captured {
+                    let action = if x_steps > 0 {
+                        Action::NudgeRight
+                    } else {
+                        Action::NudgeLeft
+                    };
+                    for _ in 0..x_steps.unsigned_abs() {
+                        self.sender.oneshot(action);
+                    }
+                }
             }
             _ => (),
         }
     }
+    /// A key changed state, as from a [`WindowEvent::KeyboardInput`](winit::event::WindowEvent::KeyboardInput).
+    /// Split out, as key events can't be constructed outside of winit.
+    pub fn push_key_code(&mut self, code: winit::keyboard::KeyCode, pressed: bool) {
+        let hotkeys = crate::global::hotkeys::Hotkeys::read();
+
+        // Update currently_pressed set accordingly:
+        let was_pressed = if pressed {
+            // Returns true if WASN'T present
+            !self.currently_pressed.insert(code)
+        } else {
+            // Returns true if WAS present
+            self.currently_pressed.remove(&code)
+        };
+
+        // Depending on the status of ctrl, shift, and alt, this key
+        // event could correspond to eight different actions. Check
+        // them all!
+
+        // Copy so that the iter does not borrow self.
+        let ctrl = self.ctrl;
+        let shift = self.shift;
+        let alt = self.alt;
+        let possible_keys = (0u8..(1 << (u8::from(ctrl) + u8::from(shift) + u8::from(alt))))
+            .map(|mut bits| {
+                // Generates all unique combos of each flag where self.<flag> is set.
+                // Or false if not set.
+                let mut consume = |condition: bool| {
+                    if condition {
+                        let bit = bits & 1 == 1;
+                        bits >>= 1;
+                        bit
+                    } else {
+                        false
+                    }
+                };
+                super::hotkeys::KeyboardHotkey {
+                    key: code,
+                    alt: consume(alt),
+                    shift: consume(shift),
+                    ctrl: consume(ctrl),
+                }
+            })
+            .filter_map(|key| {
+                // find the action of each key, or skip if none.
+                Some((hotkeys.keys_to_actions().action_of(key)?, key))
+            });
+
+        match (was_pressed, pressed) {
+            // Just pressed
+            (false, true) => {
+                possible_keys.for_each(|(action, key)| self.push_key(action, key));
+            }
+            // OS key repeat
+            (true, true) => possible_keys.for_each(|(action, _)| {
+                // No bookkeeping to do, just emit directly
+                self.sender.repeat(action);
+            }),
+            // Just released
+            (_, false) => {
+                possible_keys.for_each(|(action, key)| self.pop_key(action, key));
+            }
+        }
+
+        // Shouldn't need to happen but it's not working and i'm getting tired of debugging TwT
+        self.cull();
+    }
     /// Release any events that have stopped being relavent.
     fn cull(&mut self) {
         let mut to_remove = Vec::<super::hotkeys::KeyboardHotkey>::new();
@@ -210,7 +236,9 @@ impl WinitKeyboardActionCollector {
                     // be in an unknown state.
                     // Nothing will be outright broken, but actions may stop making sense until all
                     // keys are released....
-                    log::warn!("{old_key:?} unshadowed too many times while removing {remove:?}!");
+                    tracing::warn!(
+                        "{old_key:?} unshadowed too many times while removing {remove:?}!"
+                    );
                     0
                 });
                 if *shadows == 0 {
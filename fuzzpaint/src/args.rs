@@ -0,0 +1,226 @@
+//! # Command line arguments
+//!
+//! `fuzzpaint [OPTIONS] [FILES...]`, where every file is opened as a document at startup.
+
+pub const USAGE: &str = "Usage: fuzzpaint [OPTIONS] [FILES...]
+
+Opens each of FILES as a document.
+
+Options:
+    --log <FILTER>    Set the log level or filter, e.g. `info` or `fuzzpaint=trace`.
+                      Takes precedence over RUST_LOG.
+    --no-vsync        Present frames as soon as they're ready, as if low latency were enabled.
+    --read-only       Open FILES without allowing changes to them.
+    --new-instance    Start a new window, instead of opening FILES in one already running.
+    --device <INDEX>  Use the Vulkan device at INDEX, in the order the driver lists them,
+                      instead of choosing automatically.
+    --bench [KEY=VALUE...]
+                      Render a generated document, print timings, and exit. Keys are
+                      strokes, layers, points (per stroke), and frames, e.g.
+                      `--bench strokes=100000 layers=50`.
+    --capture <FILE>  Record the pen, actions, and view to FILE as they're used, to attach
+                      to a bug report. Nothing of the documents themselves is recorded.
+    --replay <FILE>   Replay input recorded with --capture on the first of FILES, which
+                      should be a copy of the document it was recorded on.
+    --headless        With --replay, replay without a window as quickly as possible, print
+                      whether it went as recorded, and exit.
+    -h, --help        Print this message.
+    --                Treat all further arguments as files.";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    /// Documents to open.
+    pub paths: Vec<std::path::PathBuf>,
+    /// `tracing` filter directives, overriding the environment.
+    pub log: Option<String>,
+    pub no_vsync: bool,
+    /// Open `paths` read-only.
+    pub read_only: bool,
+    /// Don't hand `paths` off to a running instance.
+    pub new_instance: bool,
+    /// Index of the physical device to use.
+    pub device: Option<usize>,
+    /// Run the renderer benchmark instead of the app.
+    pub bench: Option<crate::renderer::bench::Config>,
+    /// Record input to this file.
+    pub capture: Option<std::path::PathBuf>,
+    /// Replay input from this file, taking precedence over `capture`.
+    pub replay: Option<std::path::PathBuf>,
+    /// Replay without a window.
+    pub headless: bool,
+    pub help: bool,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ArgsError {
+    #[error("unknown option {0:?}")]
+    Unknown(std::ffi::OsString),
+    #[error("option {0} expects a value")]
+    MissingValue(&'static str),
+    #[error("invalid value {value:?} for option {option}")]
+    InvalidValue {
+        option: &'static str,
+        value: std::ffi::OsString,
+    },
+}
+
+impl Args {
+    /// Parse from the process's arguments.
+    pub fn from_env() -> Result<Self, ArgsError> {
+        Self::parse(std::env::args_os().skip(1))
+    }
+    /// Parse from arguments, not including the program name.
+    pub fn parse(args: impl IntoIterator<Item = std::ffi::OsString>) -> Result<Self, ArgsError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().peekable();
+        // Paths are OSStrings, let the system handle character encoding restrictions.
+        // Todo: Expand glob patterns on windows (on unix this is handled by shell)
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.to_str().filter(|arg| arg.starts_with('-')) else {
+                parsed.paths.push(arg.into());
+                continue;
+            };
+            match option {
+                "--" => {
+                    parsed.paths.extend(args.by_ref().map(Into::into));
+                }
+                "-h" | "--help" => parsed.help = true,
+                "--no-vsync" => parsed.no_vsync = true,
+                "--read-only" => parsed.read_only = true,
+                "--new-instance" => parsed.new_instance = true,
+                "--headless" => parsed.headless = true,
+                "--capture" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue("--capture"))?;
+                    parsed.capture = Some(value.into());
+                }
+                "--replay" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue("--replay"))?;
+                    parsed.replay = Some(value.into());
+                }
+                "--log" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue("--log"))?;
+                    let value = value
+                        .into_string()
+                        .map_err(|value| ArgsError::InvalidValue {
+                            option: "--log",
+                            value,
+                        })?;
+                    parsed.log = Some(value);
+                }
+                "--device" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue("--device"))?;
+                    let index = value.to_str().and_then(|value| value.parse().ok());
+                    parsed.device = Some(index.ok_or(ArgsError::InvalidValue {
+                        option: "--device",
+                        value,
+                    })?);
+                }
+                "--bench" => {
+                    let mut config = crate::renderer::bench::Config::default();
+                    // Settings follow until the next option or file.
+                    while let Some(setting) = args.next_if(|arg| {
+                        arg.to_str()
+                            .is_some_and(|arg| arg.contains('=') && !arg.starts_with('-'))
+                    }) {
+                        // Unwrap ok - checked to be a str above.
+                        let (key, value) = setting.to_str().unwrap().split_once('=').unwrap();
+                        if !config.set(key, value) {
+                            return Err(ArgsError::InvalidValue {
+                                option: "--bench",
+                                value: setting,
+                            });
+                        }
+                    }
+                    parsed.bench = Some(config);
+                }
+                // A lone dash isn't an option, let it be opened as a file.
+                "-" => parsed.paths.push(arg.into()),
+                _ => return Err(ArgsError::Unknown(arg)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Args, ArgsError};
+    fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+        Args::parse(args.iter().map(Into::into))
+    }
+    #[test]
+    fn parse_args() {
+        assert_eq!(parse(&[]), Ok(Args::default()));
+        assert_eq!(
+            parse(&[
+                "a.fzp",
+                "--log",
+                "debug",
+                "b.fzp",
+                "--no-vsync",
+                "--device",
+                "1",
+                "--read-only",
+                "--new-instance"
+            ]),
+            Ok(Args {
+                paths: vec!["a.fzp".into(), "b.fzp".into()],
+                log: Some("debug".to_owned()),
+                no_vsync: true,
+                read_only: true,
+                new_instance: true,
+                device: Some(1),
+                bench: None,
+                capture: None,
+                replay: None,
+                headless: false,
+                help: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--replay", "bug.toml", "--headless", "a.fzp"]).map(|args| (
+                args.replay,
+                args.headless,
+                args.paths
+            )),
+            Ok((Some("bug.toml".into()), true, vec!["a.fzp".into()]))
+        );
+        assert_eq!(
+            parse(&["--bench", "strokes=100000", "layers=50", "c.fzp"]).map(|args| args.bench),
+            Ok(Some(crate::renderer::bench::Config {
+                strokes: 100_000,
+                layers: 50,
+                ..Default::default()
+            }))
+        );
+        // Everything after `--` is a file, even if it looks like an option.
+        assert_eq!(
+            parse(&["--", "--help"]).map(|args| args.paths),
+            Ok(vec!["--help".into()])
+        );
+
+        assert_eq!(
+            parse(&["--frobnicate"]),
+            Err(ArgsError::Unknown("--frobnicate".into()))
+        );
+        assert_eq!(parse(&["--log"]), Err(ArgsError::MissingValue("--log")));
+        assert_eq!(
+            parse(&["--capture"]),
+            Err(ArgsError::MissingValue("--capture"))
+        );
+        assert_eq!(
+            parse(&["--bench", "strokes=many"]),
+            Err(ArgsError::InvalidValue {
+                option: "--bench",
+                value: "strokes=many".into()
+            })
+        );
+        assert_eq!(
+            parse(&["--device", "first"]),
+            Err(ArgsError::InvalidValue {
+                option: "--device",
+                value: "first".into()
+            })
+        );
+    }
+}
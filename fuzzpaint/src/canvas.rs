@@ -0,0 +1,207 @@
+//! # Canvas
+//!
+//! Cropping and resizing the canvas, the region of the document that is shown and exported, see
+//! [`Canvas`]. Changing it is a step of history of its own. Nothing in the document is moved or resampled,
+//! whatever falls outside of the canvas is kept, just not shown.
+
+use fuzzpaint_core::state::document::{Canvas, ID};
+use vulkano::half::f16;
+
+/// Where the current canvas sits within a resized one, as seen in the viewport.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, strum::EnumIter)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+impl Anchor {
+    /// How far across the spare room the region is placed, from the least coordinate to the greatest.
+    #[must_use]
+    pub fn factors(self) -> [f32; 2] {
+        let x = match self {
+            Self::TopLeft | Self::Left | Self::BottomLeft => 0.0,
+            Self::Top | Self::Center | Self::Bottom => 0.5,
+            Self::TopRight | Self::Right | Self::BottomRight => 1.0,
+        };
+        // Document y increases down the viewport.
+        let y = match self {
+            Self::TopLeft | Self::Top | Self::TopRight => 0.0,
+            Self::Left | Self::Center | Self::Right => 0.5,
+            Self::BottomLeft | Self::Bottom | Self::BottomRight => 1.0,
+        };
+        [x, y]
+    }
+    /// An arrow pointing toward the anchored edges.
+    #[must_use]
+    pub fn arrow(self) -> &'static str {
+        match self {
+            Self::TopLeft => "↖",
+            Self::Top => "↑",
+            Self::TopRight => "↗",
+            Self::Left => "←",
+            Self::Center => "•",
+            Self::Right => "→",
+            Self::BottomLeft => "↙",
+            Self::Bottom => "↓",
+            Self::BottomRight => "↘",
+        }
+    }
+}
+
+/// A region of the document, as `[min, max]` document pixels.
+pub type Region = [[f32; 2]; 2];
+
+/// The current canvas resized to `size` document pixels, placed about it by the anchor. Larger, it adds room
+/// around the current canvas. Smaller, it crops it.
+#[must_use]
+pub fn resized(current: Canvas, size: [f32; 2], anchor: Anchor) -> Canvas {
+    let [fx, fy] = anchor.factors();
+    Canvas {
+        origin: [
+            current.origin[0] + (current.size[0] - size[0]) * fx,
+            current.origin[1] + (current.size[1] - size[1]) * fy,
+        ],
+        size,
+    }
+}
+
+/// The canvas covering the region, grown to whole document pixels.
+#[must_use]
+pub fn covering([min, max]: Region) -> Canvas {
+    let (min, max) = (min.map(f32::floor), max.map(f32::ceil));
+    Canvas {
+        origin: min,
+        size: [max[0] - min[0], max[1] - min[1]],
+    }
+}
+
+/// The region covering every texel of a downloaded document image, a square of `dimension` texels, that
+/// wouldn't be fully transparent once exported. It's in texels from the least corner of the image, which is the
+/// origin of the region the image covers. None if all would be transparent.
+pub fn content_region(texels: &[[f16; 4]], dimension: u32) -> anyhow::Result<Option<Region>> {
+    let dimension = usize::try_from(dimension)?;
+    if texels.len() != dimension * dimension {
+        anyhow::bail!("expected a {dimension}px image");
+    }
+    // Under half of the least step of eight bit alpha, which rounds to none.
+    let threshold = 0.5 / 255.0;
+    let mut bounds: Option<[usize; 4]> = None;
+    for (idx, texel) in texels.iter().enumerate() {
+        if texel[3].to_f32() < threshold {
+            continue;
+        }
+        let (x, row) = (idx % dimension, idx / dimension);
+        bounds = Some(match bounds {
+            None => [x, row, x, row],
+            Some([min_x, min_row, max_x, max_row]) => [
+                min_x.min(x),
+                min_row.min(row),
+                max_x.max(x),
+                max_row.max(row),
+            ],
+        });
+    }
+    #[allow(clippy::cast_precision_loss)]
+    Ok(bounds.map(|[min_x, min_row, max_x, max_row]| {
+        // The first row is the greatest y.
+        let flip = |row: usize| (dimension - row) as f32;
+        [
+            [min_x as f32, flip(max_row + 1)],
+            [(max_x + 1) as f32, flip(min_row)],
+        ]
+    }))
+}
+
+/// Change the document's canvas, as one step of history.
+pub fn set_canvas(document: ID, canvas: Canvas) -> anyhow::Result<()> {
+    if !canvas.is_valid() {
+        anyhow::bail!("canvas must not be empty");
+    }
+    let Some(result) = crate::global::provider().write(document, "canvas", |queue| {
+        queue.write_with(|writer| writer.document().set_canvas(canvas))
+    }) else {
+        anyhow::bail!("document closed or read-only")
+    };
+    result.map_err(|()| anyhow::anyhow!("canvas must not be empty"))
+}
+
+/// The bounds of the document's selection, if any.
+#[must_use]
+pub fn selection_region(document: ID) -> Option<Region> {
+    let selection = crate::global::selection::get(document)?;
+    let (first, rest) = selection.polygon.split_first()?;
+    Some(rest.iter().fold([*first, *first], |[min, max], &[x, y]| {
+        [
+            [min[0].min(x), min[1].min(y)],
+            [max[0].max(x), max[1].max(y)],
+        ]
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Anchor, Canvas};
+    #[test]
+    fn resized() {
+        let current = Canvas {
+            origin: [100.0, -20.0],
+            size: [400.0, 300.0],
+        };
+        // Doubled around the center, the current canvas sits in the middle of the new one.
+        assert_eq!(
+            super::resized(current, [800.0, 600.0], Anchor::Center),
+            Canvas {
+                origin: [-100.0, -170.0],
+                size: [800.0, 600.0],
+            }
+        );
+        // Halved from the top left, the rest is cropped away.
+        assert_eq!(
+            super::resized(current, [200.0, 150.0], Anchor::TopLeft),
+            Canvas {
+                origin: [100.0, -20.0],
+                size: [200.0, 150.0],
+            }
+        );
+        // From the bottom right, the far corner stays put.
+        let resized = super::resized(current, [200.0, 150.0], Anchor::BottomRight);
+        assert_eq!(resized.corners()[1], current.corners()[1]);
+    }
+    #[test]
+    fn covering() {
+        assert_eq!(
+            super::covering([[10.5, -3.2], [20.0, 7.1]]),
+            Canvas {
+                origin: [10.0, -4.0],
+                size: [10.0, 12.0],
+            }
+        );
+    }
+    #[test]
+    fn content_region() {
+        use vulkano::half::f16;
+        let mut texels = vec![[f16::ZERO; 4]; 16];
+        assert_eq!(super::content_region(&texels, 4).unwrap(), None);
+        // Row 0, the greatest y, columns 1 and 2.
+        texels[1][3] = f16::ONE;
+        texels[2][3] = f16::from_f32(0.1);
+        assert_eq!(
+            super::content_region(&texels, 4).unwrap(),
+            Some([[1.0, 3.0], [3.0, 4.0]])
+        );
+        // Too faint to show once exported.
+        texels[15][3] = f16::from_f32(0.001);
+        assert_eq!(
+            super::content_region(&texels, 4).unwrap(),
+            Some([[1.0, 3.0], [3.0, 4.0]])
+        );
+        assert!(super::content_region(&texels, 3).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! # Crash
+//!
+//! A panic on any thread takes fuzzpaint down, as whatever state it left behind can't be trusted. Before it
+//! goes, the panic hook flushes every [journal](crate::global::journals), saves recovery copies of the open
+//! documents, and writes a bundle of diagnostics. Bundles are offered to the user the next time fuzzpaint
+//! starts, to include in a bug report if they choose - nothing is sent anywhere.
+
+/// How long the panic hook waits on saving before giving up, in case it's stuck on a lock the panicking
+/// thread held.
+const SAVE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// The bundle's report, within its directory.
+const REPORT: &str = "report.txt";
+/// Present in a bundle once the user has been told of it.
+const SEEN: &str = "seen";
+
+/// Where crash bundles are kept, created if needed.
+fn directory() -> anyhow::Result<std::path::PathBuf> {
+    let mut path =
+        dirs::data_local_dir().ok_or_else(|| anyhow::anyhow!("no local data directory"))?;
+    path.push(env!("CARGO_PKG_NAME"));
+    path.push("crashes");
+    std::fs::create_dir_all(&path)?;
+    Ok(path)
+}
+
+/// Install the panic hook. The default hook still prints the panic first.
+pub fn install() {
+    static PANICKED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        // Another thread panicked meanwhile, or saving did. Leave it to the first.
+        if PANICKED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            return;
+        }
+        let panic = format!(
+            "{info}\n\nBacktrace:\n{}",
+            std::backtrace::Backtrace::force_capture()
+        );
+        // Saving needs locks the panicking thread may hold, so it's done elsewhere to be given up on.
+        let (send, recv) = std::sync::mpsc::sync_channel(1);
+        let saving = std::thread::Builder::new()
+            .name("Crash handler".to_owned())
+            .spawn(move || {
+                let _ = send.send(save(&panic));
+            });
+        match saving.map(|_| recv.recv_timeout(SAVE_TIMEOUT)) {
+            Ok(Ok(Ok(bundle))) => eprintln!("crash report written to {}", bundle.display()),
+            Ok(Ok(Err(err))) => eprintln!("failed to write crash report: {err:#}"),
+            Ok(Err(_)) => eprintln!("timed out writing crash report"),
+            Err(err) => eprintln!("failed to write crash report: {err}"),
+        }
+        std::process::abort();
+    }));
+}
+
+/// Flush journals, save recovery copies, and write the bundle, returning its directory.
+fn save(panic: &str) -> anyhow::Result<std::path::PathBuf> {
+    use std::fmt::Write;
+
+    crate::global::journals::flush(SAVE_TIMEOUT / 2);
+    let provider = crate::global::provider();
+    let documents: Vec<_> = provider
+        .document_iter()
+        .filter_map(|id| {
+            provider.inspect(id, |queue| {
+                let state = queue.peek_clone_state();
+                let document = state.document();
+                (
+                    id,
+                    document.name.clone(),
+                    document.path.clone(),
+                    queue.is_dirty(),
+                    queue.is_read_only(),
+                )
+            })
+        })
+        .collect();
+    let names: Vec<_> = documents
+        .iter()
+        .map(|(id, name, ..)| (*id, name.clone()))
+        .collect();
+    let recovery = crate::ui::save_recovery_copies(&names);
+
+    // Writing into a string can't fail.
+    let mut report = String::new();
+    let _ = writeln!(report, "{panic}");
+    let _ = writeln!(report, "{}", crate::diagnostics::report());
+    let _ = writeln!(report, "Open documents:");
+    for (_, name, path, dirty, read_only) in &documents {
+        let path = path
+            .as_deref()
+            .map_or_else(|| "[unsaved]".into(), |path| path.display().to_string());
+        let _ = writeln!(
+            report,
+            "{name} ({path}){}{}",
+            if *dirty { ", unsaved changes" } else { "" },
+            if *read_only { ", read-only" } else { "" },
+        );
+    }
+    match &recovery {
+        Ok(directory) => {
+            let _ = writeln!(report, "Recovery copies: {}", directory.display());
+        }
+        Err(err) => {
+            let _ = writeln!(report, "Recovery copies failed: {err:#}");
+        }
+    }
+    let _ = writeln!(report, "\nLog:");
+    for entry in crate::logging::entries(tracing::Level::TRACE) {
+        let _ = writeln!(report, "{entry}");
+    }
+
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let bundle = directory()?.join(since_epoch.as_secs().to_string());
+    std::fs::create_dir_all(&bundle)?;
+    std::fs::write(bundle.join(REPORT), report)?;
+    Ok(bundle)
+}
+
+/// A crash the user hasn't yet been told of.
+pub struct Bundle {
+    pub path: std::path::PathBuf,
+    /// The plain-text report, as written by the panic hook.
+    pub report: String,
+}
+impl Bundle {
+    /// Don't offer the bundle again, leaving it on disk.
+    pub fn mark_seen(&self) -> std::io::Result<()> {
+        std::fs::write(self.path.join(SEEN), [])
+    }
+    /// Delete the bundle.
+    pub fn discard(&self) -> std::io::Result<()> {
+        std::fs::remove_dir_all(&self.path)
+    }
+}
+/// Find the bundles left by crashes the user hasn't yet been told of, oldest first.
+#[must_use]
+pub fn unseen() -> Vec<Bundle> {
+    let entries = match directory().and_then(|dir| Ok(std::fs::read_dir(dir)?)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("failed to read crash reports: {e:#}");
+            return Vec::new();
+        }
+    };
+    let mut bundles: Vec<_> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.join(SEEN).exists() {
+                return None;
+            }
+            let report = std::fs::read_to_string(path.join(REPORT)).ok()?;
+            Some(Bundle { path, report })
+        })
+        .collect();
+    // Named by the time of the crash.
+    bundles.sort_by(|a, b| a.path.cmp(&b.path));
+    bundles
+}
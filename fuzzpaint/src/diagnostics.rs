@@ -0,0 +1,369 @@
+//! # Diagnostics
+//!
+//! Frame timings and resource usage, collected wherever the work happens and shown by the
+//! diagnostics overlay in the UI. Collection of GPU timings is skipped while the overlay is closed,
+//! as timestamp queries aren't free.
+//!
+//! Also the developer [visualizations](Visualizations) of how the document is drawn.
+
+use crate::vulkano_prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How many frames of CPU timings are kept.
+pub const HISTORY_LEN: usize = 120;
+
+/// Parts of a window frame, measured on the CPU.
+#[derive(strum::AsRefStr, strum::EnumIter, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CpuPhase {
+    /// Handling window and tablet events since the last frame.
+    Input,
+    /// Running the UI logic.
+    Ui,
+    /// Recording the document preview and UI command buffers.
+    Record,
+    /// Submitting and presenting.
+    Submit,
+}
+/// Sections of GPU work bracketed by timestamp queries.
+#[derive(strum::AsRefStr, strum::EnumIter, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GpuPass {
+    /// Tessellating and drawing stroke layers. Each layer is submitted and waited on by the host in turn, so
+    /// this is the wall time from the first submission until the last finished, rather than time the GPU spent
+    /// busy.
+    #[strum(serialize = "Tessellation (wall)")]
+    Tessellation,
+    /// Blending the layers into the final document image.
+    Composite,
+    /// Drawing the UI.
+    Egui,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CpuFrame {
+    phases: [Duration; 4],
+}
+impl CpuFrame {
+    #[must_use]
+    pub fn get(&self, phase: CpuPhase) -> Duration {
+        self.phases[phase as usize]
+    }
+    pub fn add(&mut self, phase: CpuPhase, time: Duration) {
+        self.phases[phase as usize] += time;
+    }
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.phases.iter().sum()
+    }
+}
+
+#[derive(Default)]
+pub struct Timings {
+    /// Most recent last.
+    cpu: std::collections::VecDeque<CpuFrame>,
+    gpu: [Option<Duration>; 3],
+    device_memory: Vec<crate::render_device::HeapUsage>,
+}
+impl Timings {
+    /// Recent CPU frame timings, oldest first.
+    pub fn cpu_frames(&self) -> impl ExactSizeIterator<Item = &CpuFrame> + '_ {
+        self.cpu.iter()
+    }
+    /// The most recent measurement of the pass, if any has been taken.
+    #[must_use]
+    pub fn gpu(&self, pass: GpuPass) -> Option<Duration> {
+        self.gpu[pass as usize]
+    }
+    /// Usage of each device-local memory heap, as of the last frame with diagnostics enabled.
+    #[must_use]
+    pub fn device_memory(&self) -> &[crate::render_device::HeapUsage] {
+        &self.device_memory
+    }
+}
+
+static ENABLED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Whether the overlay is open, and therefore whether costly measurements should be taken.
+#[must_use]
+pub fn enabled() -> bool {
+    ENABLED.load(std::sync::atomic::Ordering::Relaxed)
+}
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+bitflags::bitflags! {
+    /// Developer views of how the document is drawn, to help find where rendering time goes.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub struct Visualizations: u8 {
+        /// Outline every tessellated stamp over the strokes. Needs the `fillModeNonSolid` device feature.
+        const WIREFRAME = 1;
+        /// Draw the edges of the tiles the document is updated in.
+        const TILE_BOUNDARIES = 2;
+        /// Draw strokes as a heatmap of how many stamps cover each pixel, in place of their paint.
+        const OVERDRAW = 4;
+    }
+}
+static VISUALIZATIONS: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(0);
+/// Notified when visualizations that change how layers are drawn are toggled.
+static LAYERS_CHANGED: tokio::sync::Notify = tokio::sync::Notify::const_new();
+#[must_use]
+pub fn visualizations() -> Visualizations {
+    Visualizations::from_bits_truncate(VISUALIZATIONS.load(std::sync::atomic::Ordering::Relaxed))
+}
+pub fn set_visualizations(visualizations: Visualizations) {
+    let old = Visualizations::from_bits_truncate(
+        VISUALIZATIONS.swap(visualizations.bits(), std::sync::atomic::Ordering::Relaxed),
+    );
+    // Tile boundaries are drawn over the top, the others are drawn into the layers.
+    let in_layers = Visualizations::WIREFRAME | Visualizations::OVERDRAW;
+    if (old ^ visualizations).intersects(in_layers) {
+        LAYERS_CHANGED.notify_one();
+    }
+}
+/// Completes once visualizations drawn into layers have changed, and every layer must be drawn again.
+pub async fn layer_visualizations_changed() {
+    LAYERS_CHANGED.notified().await;
+}
+
+#[must_use]
+pub fn timings() -> &'static parking_lot::RwLock<Timings> {
+    static TIMINGS: std::sync::OnceLock<parking_lot::RwLock<Timings>> = std::sync::OnceLock::new();
+    TIMINGS.get_or_init(Default::default)
+}
+pub fn push_cpu_frame(frame: CpuFrame) {
+    let mut timings = timings().write();
+    if timings.cpu.len() >= HISTORY_LEN {
+        timings.cpu.pop_front();
+    }
+    timings.cpu.push_back(frame);
+}
+pub fn set_device_memory(heaps: Vec<crate::render_device::HeapUsage>) {
+    timings().write().device_memory = heaps;
+}
+
+static DEVICE_INFO: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+/// Record the description of the device, once it's chosen. Later calls are ignored.
+pub fn set_device_info(info: String) {
+    let _ = DEVICE_INFO.set(info);
+}
+
+/// Build a plain-text report of the application, device, and recent warnings, to be pasted
+/// into a bug report.
+#[must_use]
+pub fn report() -> String {
+    use std::fmt::Write;
+    // Writing into a string can't fail.
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "fuzzpaint v{}",
+        option_env!("CARGO_PKG_VERSION").unwrap_or("[unknown]")
+    );
+    let _ = writeln!(
+        report,
+        "Platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(
+        report,
+        "Device: {}",
+        DEVICE_INFO.get().map_or("[not yet chosen]", String::as_str)
+    );
+    {
+        let timings = timings().read();
+        for (idx, heap) in timings.device_memory().iter().enumerate() {
+            let _ = writeln!(
+                report,
+                "VRAM heap {idx}: {} bytes, {:?} used of {:?} budget",
+                heap.size, heap.usage, heap.budget
+            );
+        }
+    }
+
+    let _ = writeln!(report, "\nRecent warnings and errors:");
+    for entry in crate::logging::entries(tracing::Level::WARN) {
+        let _ = writeln!(report, "{entry}");
+    }
+    report
+}
+
+/// How many measurements a [`GpuTimer`] may have in flight, so that each is read frames after it was taken
+/// rather than waited on.
+const TIMER_SLOTS: u32 = 4;
+
+/// Pairs of timestamp queries, measuring the GPU time between two points in a queue's submissions.
+///
+/// The measurement includes any time the queue spent idle between the two points, so it is only
+/// meaningful when the work between them is submitted without waiting on the host. Nothing here waits on the
+/// GPU either: each measurement takes its own pair of queries, which [`Self::publish`] reads once they've
+/// finished, usually a frame or two later.
+pub struct GpuTimer {
+    context: Arc<crate::render_device::RenderContext>,
+    queue: Arc<vk::Queue>,
+    pool: Arc<vulkano::query::QueryPool>,
+    pass: GpuPass,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    /// Bits of the timestamp that are meaningful.
+    valid_bits: u32,
+    slots: parking_lot::Mutex<TimerSlots>,
+}
+struct TimerSlots {
+    /// The slot of the measurement begun last.
+    current: u32,
+    /// Slots whose end has been recorded, oldest first, to be read once finished.
+    ended: std::collections::VecDeque<u32>,
+    /// Submissions of [`GpuTimer::submit_begin`] and [`GpuTimer::submit_end`], kept until they finish, as
+    /// dropping them would wait.
+    submitted: Vec<vk::FenceSignalFuture<Box<dyn GpuFuture + Send>>>,
+}
+impl GpuTimer {
+    /// Create a timer for a pass submitted to the given queue. `None` if the queue does not
+    /// support timestamps.
+    pub fn new(
+        context: Arc<crate::render_device::RenderContext>,
+        queue: Arc<vk::Queue>,
+        pass: GpuPass,
+    ) -> anyhow::Result<Option<Self>> {
+        let physical = context.physical_device();
+        let Some(valid_bits) = physical
+            .queue_family_properties()
+            .get(queue.queue_family_index() as usize)
+            .and_then(|family| family.timestamp_valid_bits)
+        else {
+            return Ok(None);
+        };
+        let pool = vulkano::query::QueryPool::new(
+            context.device().clone(),
+            vulkano::query::QueryPoolCreateInfo {
+                query_count: TIMER_SLOTS * 2,
+                ..vulkano::query::QueryPoolCreateInfo::query_type(
+                    vulkano::query::QueryType::Timestamp,
+                )
+            },
+        )?;
+        Ok(Some(Self {
+            period: physical.properties().timestamp_period,
+            context,
+            queue,
+            pool,
+            pass,
+            valid_bits,
+            slots: parking_lot::Mutex::new(TimerSlots {
+                current: 0,
+                ended: std::collections::VecDeque::new(),
+                submitted: Vec::new(),
+            }),
+        }))
+    }
+    /// Command buffer to execute before the pass, beginning a new measurement.
+    pub fn begin(&self) -> anyhow::Result<Arc<vk::PrimaryAutoCommandBuffer>> {
+        let slot = {
+            let mut slots = self.slots.lock();
+            slots.current = (slots.current + 1) % TIMER_SLOTS;
+            let current = slots.current;
+            // Reused before it was read, that measurement is lost.
+            slots.ended.retain(|&slot| slot != current);
+            current
+        };
+        let mut command_buffer = self.command_buffer()?;
+        // Safety: the queries are reset before use, and only read once available.
+        unsafe {
+            command_buffer
+                .reset_query_pool(self.pool.clone(), slot * 2..slot * 2 + 2)?
+                .write_timestamp(
+                    self.pool.clone(),
+                    slot * 2,
+                    vulkano::sync::PipelineStage::TopOfPipe,
+                )?;
+        }
+        Ok(command_buffer.build()?)
+    }
+    /// Command buffer to execute after the pass, ending the measurement of the latest [`Self::begin`].
+    pub fn end(&self) -> anyhow::Result<Arc<vk::PrimaryAutoCommandBuffer>> {
+        let slot = {
+            let mut slots = self.slots.lock();
+            let current = slots.current;
+            slots.ended.push_back(current);
+            current
+        };
+        let mut command_buffer = self.command_buffer()?;
+        // Safety: see `begin`.
+        unsafe {
+            command_buffer.write_timestamp(
+                self.pool.clone(),
+                slot * 2 + 1,
+                vulkano::sync::PipelineStage::BottomOfPipe,
+            )?;
+        }
+        Ok(command_buffer.build()?)
+    }
+    /// Submit [`Self::begin`] on its own, for passes that are submitted in several parts. Any time the host
+    /// takes between those parts is measured too.
+    pub fn submit_begin(&self) -> anyhow::Result<()> {
+        self.submit(self.begin()?)
+    }
+    /// Submit [`Self::end`] on its own, and publish any measurements that have finished.
+    pub fn submit_end_and_publish(&self) -> anyhow::Result<()> {
+        self.submit(self.end()?)?;
+        self.publish()
+    }
+    /// Submit without waiting, keeping the submission until it's finished.
+    fn submit(&self, command_buffer: Arc<vk::PrimaryAutoCommandBuffer>) -> anyhow::Result<()> {
+        let future = self
+            .context
+            .now()
+            .then_execute(self.queue.clone(), command_buffer)?
+            .boxed_send()
+            .then_signal_fence_and_flush()?;
+        let mut slots = self.slots.lock();
+        // Finished ones drop without waiting.
+        slots
+            .submitted
+            .retain(|future| !future.is_signaled().unwrap_or(true));
+        slots.submitted.push(future);
+        Ok(())
+    }
+    /// Publish the latest finished measurement to [`timings`], if any. Doesn't wait on the GPU, measurements
+    /// still in flight are left for a later call.
+    pub fn publish(&self) -> anyhow::Result<()> {
+        let mut latest = None;
+        {
+            let mut slots = self.slots.lock();
+            while let Some(&slot) = slots.ended.front() {
+                let mut stamps = [0u64; 2];
+                // Without `WAIT`, false if not yet available.
+                if !self.pool.get_results(
+                    slot * 2..slot * 2 + 2,
+                    &mut stamps,
+                    vulkano::query::QueryResultFlags::empty(),
+                )? {
+                    break;
+                }
+                slots.ended.pop_front();
+                latest = Some(stamps);
+            }
+        }
+        let Some(stamps) = latest else {
+            return Ok(());
+        };
+        let mask = u64::MAX >> (64 - self.valid_bits.min(64));
+        let ticks = (stamps[1] & mask).wrapping_sub(stamps[0] & mask) & mask;
+        #[allow(clippy::cast_precision_loss)]
+        let nanos = ticks as f64 * f64::from(self.period);
+        let elapsed = Duration::from_secs_f64(nanos / 1_000_000_000.0);
+
+        timings().write().gpu[self.pass as usize] = Some(elapsed);
+        Ok(())
+    }
+    fn command_buffer(
+        &self,
+    ) -> anyhow::Result<vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>> {
+        Ok(vk::AutoCommandBufferBuilder::primary(
+            self.context.allocators().command_buffer(),
+            self.queue.queue_family_index(),
+            vk::CommandBufferUsage::OneTimeSubmit,
+        )?)
+    }
+}
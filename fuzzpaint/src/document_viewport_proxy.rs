@@ -2,12 +2,25 @@ use crate::vulkano_prelude::*;
 use std::sync::Arc;
 
 use crate::{gizmos::GizmoTree, pen_tools, render_device, view_transform, AnyResult};
+use fuzzpaint_core::state::document::Canvas;
+
+/// What the swapchain image holds as a proxy's commands begin.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageState {
+    /// Nothing has drawn to it this frame. The proxy must initialize the whole image.
+    Undefined,
+    /// Proxies beneath have drawn to it. The proxy must draw over, leaving what it doesn't cover intact.
+    Drawn,
+}
 
 /// Proxy called into by the window renderer to perform the necessary synchronization and such to render the screen
 /// behind the Egui content.
 pub trait PreviewRenderProxy {
     /// Create the render commands for this frame. Assume used resources are borrowed until a matching "`render_complete`" for this
     /// frame idx is called.
+    ///
+    /// If `state` is [`ImageState::Undefined`], the commands must initialize every pixel of the image, or
+    /// else return none at all.
     /// # Safety
     ///
     /// the previous render should be finished before the return result is executed.
@@ -15,11 +28,15 @@ pub trait PreviewRenderProxy {
         &self,
         swapchain_image: Arc<vk::Image>,
         swapchain_image_idx: u32,
+        state: ImageState,
     ) -> AnyResult<smallvec::SmallVec<[Arc<vk::PrimaryAutoCommandBuffer>; 2]>>;
     /// The window surface has been invalidated and remade.
     fn surface_changed(&self, render_surface: &render_device::RenderSurface);
     /// Is this proxy requesting a redraw?
     fn has_update(&self) -> bool;
+    /// Is this proxy waiting on the device, such that [`Self::has_update`] may become true without the event
+    /// loop being woken?
+    fn is_pending(&self) -> bool;
     /// The area used for this viewport has changed. Not the same as the surface - rather, the central area
     /// between UI elements where this proxy is visible. Proxies should still initialize the whole screen, however.
     fn viewport_changed(&self, position: ultraviolet::Vec2, size: ultraviolet::Vec2);
@@ -28,6 +45,80 @@ pub trait PreviewRenderProxy {
     fn cursor(&self) -> Option<crate::gizmos::CursorOrInvisible>;
 }
 
+/// Where in a [`ProxyStack`] a proxy draws. Later layers draw over earlier ones.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ProxyLayer {
+    /// Beneath the document, such as neighboring frames for onion skinning.
+    Underlay,
+    Document,
+    /// Above the document, such as tool gizmos.
+    Overlay,
+}
+
+/// Many proxies drawn one over another into the same swapchain image, bottom to top by [`ProxyLayer`],
+/// then by the order they were pushed. Acts as a single proxy to the window.
+#[derive(Default)]
+pub struct ProxyStack {
+    /// Sorted by layer.
+    proxies: Vec<(ProxyLayer, Arc<dyn PreviewRenderProxy>)>,
+}
+impl ProxyStack {
+    /// Add a proxy on top of all others in its layer.
+    pub fn push(&mut self, layer: ProxyLayer, proxy: Arc<dyn PreviewRenderProxy>) {
+        let idx = self.proxies.partition_point(|(other, _)| *other <= layer);
+        self.proxies.insert(idx, (layer, proxy));
+    }
+    /// Proxies from the bottom up.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Arc<dyn PreviewRenderProxy>> + '_ {
+        self.proxies.iter().map(|(_, proxy)| proxy)
+    }
+}
+impl PreviewRenderProxy for ProxyStack {
+    #[deny(unsafe_op_in_unsafe_fn)]
+    unsafe fn render(
+        &self,
+        swapchain_image: Arc<vk::Image>,
+        swapchain_image_idx: u32,
+        mut state: ImageState,
+    ) -> AnyResult<smallvec::SmallVec<[Arc<vk::PrimaryAutoCommandBuffer>; 2]>> {
+        let mut commands = smallvec::SmallVec::new();
+        for proxy in self.iter() {
+            // Safety: contract forwarded to the contract of this fn.
+            match unsafe { proxy.render(swapchain_image.clone(), swapchain_image_idx, state) } {
+                Ok(proxy_commands) => {
+                    if !proxy_commands.is_empty() {
+                        state = ImageState::Drawn;
+                    }
+                    commands.extend(proxy_commands);
+                }
+                // Don't let one broken layer hide the rest.
+                Err(e) => tracing::warn!("Failed to build preview layer commands {e:?}"),
+            }
+        }
+        Ok(commands)
+    }
+    fn surface_changed(&self, render_surface: &render_device::RenderSurface) {
+        for proxy in self.iter() {
+            proxy.surface_changed(render_surface);
+        }
+    }
+    fn has_update(&self) -> bool {
+        self.iter().any(|proxy| proxy.has_update())
+    }
+    fn is_pending(&self) -> bool {
+        self.iter().any(|proxy| proxy.is_pending())
+    }
+    fn viewport_changed(&self, position: ultraviolet::Vec2, size: ultraviolet::Vec2) {
+        for proxy in self.iter() {
+            proxy.viewport_changed(position, size);
+        }
+    }
+    fn cursor(&self) -> Option<crate::gizmos::CursorOrInvisible> {
+        // The topmost to ask for one wins.
+        self.iter().rev().find_map(|proxy| proxy.cursor())
+    }
+}
+
 mod shaders {
     pub mod vertex {
         vulkano_shaders::shader! {
@@ -35,21 +126,44 @@ mod shaders {
             src:r"
             #version 460
             
-            layout(push_constant) uniform Matrix {
+            layout(push_constant) uniform PushConstants {
                 mat4 mat;
-            } matrix;
+                // The canvas within the quad, as min.xy and max.xy of the unit square.
+                vec4 canvas;
+                uint view_filter;
+                // Bool, whether the window shows through instead of the grid.
+                uint transparent;
+                // Size of the render target, in pixels.
+                vec2 surface_size;
+                // Matches `DocumentEdge::flags`
+                uint edge;
+            } push;
+
+            const uint ANTIALIAS = 1u;
+            const uint BORDER = 2u;
 
             layout(location = 0) out vec2 out_uv;
+            layout(location = 1) out vec2 out_quad;
 
             void main() {
-                vec4 pos = vec4(
+                vec2 corner = vec2(
                     float(gl_VertexIndex & 1),
-                    float((gl_VertexIndex & 2) / 2),
-                    0.0,
-                    1.0
+                    float((gl_VertexIndex & 2) / 2)
+                );
+                // Grow the quad by a few pixels, for the fragments the smoothed edge and border reach.
+                float margin = (push.edge & BORDER) != 0u ? 2.0
+                    : (push.edge & ANTIALIAS) != 0u ? 1.0
+                    : 0.0;
+                vec2 pixels_per_unit = vec2(
+                    length((push.mat * vec4(1.0, 0.0, 0.0, 0.0)).xy * push.surface_size * 0.5),
+                    length((push.mat * vec4(0.0, 1.0, 0.0, 0.0)).xy * push.surface_size * 0.5)
                 );
+                vec2 grow = margin / max(pixels_per_unit, vec2(1e-6));
+                vec4 pos = vec4(mix(push.canvas.xy - grow, push.canvas.zw + grow, corner), 0.0, 1.0);
+
                 out_uv = vec2(pos.x, 1.0 - pos.y);
-                gl_Position = matrix.mat * pos;
+                out_quad = pos.xy;
+                gl_Position = push.mat * pos;
             }"
         }
     }
@@ -63,9 +177,54 @@ mod shaders {
             const float DARK = 0.7;
             const uint SIZE = uint(16);
 
+            // Matches `ViewFilter`
+            const uint GRAYSCALE = 1u;
+            const uint PROTANOPIA = 2u;
+            const uint DEUTERANOPIA = 3u;
+            const uint TRITANOPIA = 4u;
+
+            // Matches `DocumentEdge::flags`
+            const uint ANTIALIAS = 1u;
+            const uint BORDER = 2u;
+            // Width of the border around the document, in pixels, and its pre-multiplied color.
+            const float BORDER_WIDTH = 1.0;
+            const vec4 BORDER_COLOR = vec4(vec3(0.5), 1.0) * 0.3;
+
+            // Machado, Oliveira, Fernandes 2009, at full severity. Operates on linear RGB.
+            // Written row-by-row, so these are applied as `color * MATRIX`.
+            const mat3 PROTANOPIA_MAT = mat3(
+                0.152286, 1.052583, -0.204868,
+                0.114503, 0.786281, 0.099216,
+                -0.003882, -0.048116, 1.051998
+            );
+            const mat3 DEUTERANOPIA_MAT = mat3(
+                0.367322, 0.860646, -0.227968,
+                0.280085, 0.672501, 0.047413,
+                -0.011820, 0.042940, 0.968881
+            );
+            const mat3 TRITANOPIA_MAT = mat3(
+                1.255528, -0.076749, -0.178779,
+                -0.078411, 0.930809, 0.147602,
+                0.004733, 0.691367, 0.303900
+            );
+
+            layout(push_constant) uniform PushConstants {
+                mat4 mat;
+                // The canvas within the quad, as min.xy and max.xy of the unit square.
+                vec4 canvas;
+                uint view_filter;
+                // Bool, whether the window shows through instead of the grid.
+                uint transparent;
+                // Size of the render target, in pixels.
+                vec2 surface_size;
+                // Matches `DocumentEdge::flags`
+                uint edge;
+            } push;
+
             layout(set = 0, binding = 0) uniform sampler2D image;
 
             layout(location = 0) in vec2 uv;
+            layout(location = 1) in vec2 quad;
 
             layout(location = 0) out vec4 color;
 
@@ -75,21 +234,360 @@ mod shaders {
                 vec3 grid_color = 1.0 - vec3(vec3(is_light ? LIGHT : DARK));
 
                 vec4 col = texture(image, uv);
-                // col is pre-multiplied, grid color is not. Combine!
-                color = vec4(grid_color * (1.0 - col.a) + col.rgb, 1.0);
+                // All linear, so these are unaffected by col being pre-multiplied.
+                switch (push.view_filter) {
+                    case GRAYSCALE:
+                        col.rgb = vec3(dot(col.rgb, vec3(0.2126, 0.7152, 0.0722)));
+                        break;
+                    case PROTANOPIA:
+                        col.rgb = max(col.rgb * PROTANOPIA_MAT, 0.0);
+                        break;
+                    case DEUTERANOPIA:
+                        col.rgb = max(col.rgb * DEUTERANOPIA_MAT, 0.0);
+                        break;
+                    case TRITANOPIA:
+                        col.rgb = max(col.rgb * TRITANOPIA_MAT, 0.0);
+                        break;
+                    default:
+                        break;
+                }
+                if (push.transparent != 0u) {
+                    // Let the window beneath show through, which takes pre-multiplied color as-is.
+                    color = col;
+                } else {
+                    // col is pre-multiplied, grid color is not. Combine!
+                    color = vec4(grid_color * (1.0 - col.a) + col.rgb, 1.0);
+                }
+
+                // Signed distance from the edge of the canvas in pixels, negative within. The canvas is clipped
+                // to the quad, which otherwise only ends off-screen, so this is the canvas's edge where it's visible.
+                vec2 pixels_per_unit = 1.0 / max(vec2(
+                    length(vec2(dFdx(quad.x), dFdy(quad.x))),
+                    length(vec2(dFdx(quad.y), dFdy(quad.y)))
+                ), vec2(1e-6));
+                vec2 outside = max(push.canvas.xy - quad, quad - push.canvas.zw) * pixels_per_unit;
+                float dist = length(max(outside, 0.0)) + min(max(outside.x, outside.y), 0.0);
+
+                // Analytic coverage of the pixel, approximated as a box filter across the edge.
+                float coverage = (push.edge & ANTIALIAS) != 0u
+                    ? clamp(0.5 - dist, 0.0, 1.0)
+                    : float(dist <= 0.0);
+                float border = (push.edge & BORDER) != 0u
+                    ? clamp(0.5 + BORDER_WIDTH - dist, 0.0, 1.0) - coverage
+                    : 0.0;
+                color = color * coverage + BORDER_COLOR * max(border, 0.0);
             }"
         }
     }
 }
 
+/// A preview of how the document may look to others, applied only to the viewport.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u32)]
+pub enum ViewFilter {
+    #[default]
+    None = 0,
+    /// Luminance only, to check values.
+    Grayscale = 1,
+    /// No red cones.
+    Protanopia = 2,
+    /// No green cones.
+    Deuteranopia = 3,
+    /// No blue cones.
+    Tritanopia = 4,
+}
+impl ViewFilter {
+    /// The next filter, wrapping back around to `None`.
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Grayscale,
+            Self::Grayscale => Self::Protanopia,
+            Self::Protanopia => Self::Deuteranopia,
+            Self::Deuteranopia => Self::Tritanopia,
+            Self::Tritanopia => Self::None,
+        }
+    }
+}
+
+/// How the edge of the document meets the background of the viewport.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DocumentEdge {
+    /// Blend the edge into the background by how much of each pixel it covers, rather than stepping at
+    /// fractional zooms.
+    pub antialias: bool,
+    /// Outline the document with a faint line, to find its edge against a similar background.
+    pub border: bool,
+}
+impl Default for DocumentEdge {
+    fn default() -> Self {
+        Self {
+            antialias: true,
+            border: false,
+        }
+    }
+}
+impl DocumentEdge {
+    /// Bits as the proxy shader takes them.
+    fn flags(self) -> u32 {
+        u32::from(self.antialias) | u32::from(self.border) << 1
+    }
+}
+
+/// An inclusive rectangle of document tiles that changed since the image was last shown.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DirtyTiles {
+    min: [u32; 2],
+    max: [u32; 2],
+}
+impl DirtyTiles {
+    /// Side length of each tile, in document pixels. Must match `dirty_tiles.comp`.
+    pub const TILE_SIZE: u32 = 64;
+    #[must_use]
+    pub const fn tiles_per_side() -> u32 {
+        crate::DOCUMENT_DIMENSION.div_ceil(Self::TILE_SIZE)
+    }
+    /// Every tile of the document.
+    #[must_use]
+    pub const fn whole() -> Self {
+        let last = Self::tiles_per_side() - 1;
+        Self {
+            min: [0, 0],
+            max: [last, last],
+        }
+    }
+    /// Bounds of the nonzero flags of a row-major mask of [`Self::tiles_per_side`] squared tiles,
+    /// or None if nothing changed.
+    #[must_use]
+    pub fn from_mask(mask: &[u32]) -> Option<Self> {
+        let per_row = Self::tiles_per_side();
+        (0u32..)
+            .zip(mask)
+            .filter(|(_, &flag)| flag != 0)
+            .map(|(idx, _)| {
+                let tile = [idx % per_row, idx / per_row];
+                Self {
+                    min: tile,
+                    max: tile,
+                }
+            })
+            .reduce(Self::union)
+    }
+    /// The smallest rectangle containing both.
+    #[must_use]
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            max: [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        }
+    }
+    /// Top-left and bottom-right corners of the tiles of an image spanning `region`, in document pixels.
+    #[must_use]
+    pub fn document_rect(
+        self,
+        region: DocumentRegion,
+    ) -> (cgmath::Point2<f32>, cgmath::Point2<f32>) {
+        let scale = region.scale();
+        let corner = |[x, y]: [u32; 2]| cgmath::Point2 {
+            x: region.origin.x
+                + (x * Self::TILE_SIZE).min(crate::DOCUMENT_DIMENSION) as f32 / scale,
+            y: region.origin.y
+                + (y * Self::TILE_SIZE).min(crate::DOCUMENT_DIMENSION) as f32 / scale,
+        };
+        (corner(self.min), corner(self.max.map(|v| v + 1)))
+    }
+}
+
+/// The square area of the document, in document pixels, that a preview image covers. Usually the whole
+/// canvas, but a zoomed-in view may be rasterized over only the part of it that's visible.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DocumentRegion {
+    pub origin: cgmath::Point2<f32>,
+    pub size: f32,
+}
+impl DocumentRegion {
+    /// The square from the top-left of the canvas that covers all of it. Past the shorter side of the canvas,
+    /// the region is left blank.
+    #[must_use]
+    pub fn covering(canvas: Canvas) -> Self {
+        Self {
+            origin: canvas.origin.into(),
+            size: canvas.size[0].max(canvas.size[1]),
+        }
+    }
+    /// Slack around the visible area, so that small pans don't each need a new region.
+    const MARGIN: f32 = 1.5;
+    /// The region to rasterize for the given view, or None if the image covering the whole canvas already has
+    /// enough resolution for it.
+    ///
+    /// Regions are chosen from power-of-two steps in size and a grid of an eighth their size, so that most
+    /// view changes map to the same region.
+    #[must_use]
+    pub fn for_view(view: &crate::view_transform::ViewInfo) -> Option<Self> {
+        let covering = Self::covering(view.canvas);
+        let whole = covering.size;
+        let (min, extent) = view.document_space_aabb()?;
+        let side = extent.x.max(extent.y) * Self::MARGIN;
+        if !side.is_finite() || side <= 0.0 {
+            return None;
+        }
+        // Halve the whole document until the next halving would no longer fit the view.
+        let steps = (whole / side).log2().floor();
+        if steps < 1.0 {
+            return None;
+        }
+        // Past this, stamps are larger than any sensible zoom needs.
+        let size = whole / steps.min(8.0).exp2();
+        let grid = size / 8.0;
+        let snap = |center: f32, origin: f32| {
+            origin
+                + (((center - origin - size / 2.0) / grid).floor() * grid).clamp(0.0, whole - size)
+        };
+        Some(Self {
+            origin: cgmath::Point2 {
+                x: snap(min.x + extent.x / 2.0, covering.origin.x),
+                y: snap(min.y + extent.y / 2.0, covering.origin.y),
+            },
+            size,
+        })
+    }
+    /// Texels per document pixel of an image of [`crate::DOCUMENT_DIMENSION`] spanning this region.
+    #[must_use]
+    pub fn scale(self) -> f32 {
+        crate::DOCUMENT_DIMENSION as f32 / self.size
+    }
+    /// The part of the canvas within this region, as `[min x, min y, max x, max y]` of the unit square of the
+    /// preview quad.
+    fn canvas_quad(self, canvas: Canvas) -> [f32; 4] {
+        let [min, max] = canvas.corners();
+        let unit = |v: f32, origin: f32| ((v - origin) / self.size).clamp(0.0, 1.0);
+        [
+            unit(min[0], self.origin.x),
+            unit(min[1], self.origin.y),
+            unit(max[0], self.origin.x),
+            unit(max[1], self.origin.y),
+        ]
+    }
+    /// This region grown by `margin` document pixels on every side.
+    #[must_use]
+    pub fn padded(self, margin: f32) -> Self {
+        Self {
+            origin: cgmath::Point2 {
+                x: self.origin.x - margin,
+                y: self.origin.y - margin,
+            },
+            size: self.size + 2.0 * margin,
+        }
+    }
+    /// Matrix taking the unit square of the preview quad onto this region of the document.
+    fn quad_matrix(self) -> ultraviolet::Mat4 {
+        ultraviolet::Mat4::from_translation(ultraviolet::Vec3 {
+            x: self.origin.x,
+            y: self.origin.y,
+            z: 0.0,
+        }) * ultraviolet::Mat4::from_nonuniform_scale(ultraviolet::Vec3 {
+            x: self.size,
+            y: self.size,
+            z: 1.0,
+        })
+    }
+}
+
+/// The level of detail to composite at for the given view, see [`crate::renderer::LOD_LEVELS`]. Each level
+/// halves the resolution, chosen such that the level still has at least one pixel per pixel of the view.
+#[must_use]
+pub fn lod_for_view(view: &crate::view_transform::ViewInfo) -> u32 {
+    let Some(transform) = view.calculate_transform() else {
+        return 0;
+    };
+    let steps = (1.0 / transform.view_points_per_document_point())
+        .log2()
+        .floor();
+    if !steps.is_finite() || steps < 1.0 {
+        return 0;
+    }
+    // Positive and finite, checked above.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let steps = steps as u32;
+    steps.min(crate::renderer::LOD_LEVELS - 1)
+}
+
+/// Changes submitted to the proxy that haven't been shown yet.
+#[derive(Default)]
+struct PendingDirty {
+    tiles: Option<DirtyTiles>,
+    /// Flags of each submitted image since the last shown, read once the GPU is done with them.
+    masks: Vec<vk::Subbuffer<[u32]>>,
+}
+impl PendingDirty {
+    fn mark(&mut self, tiles: Option<DirtyTiles>) {
+        self.tiles = match (self.tiles, tiles) {
+            (Some(a), Some(b)) => Some(a.union(b)),
+            (a, b) => a.or(b),
+        };
+    }
+    /// Fold the masks into the dirty tiles, and return them. The submissions that write the masks
+    /// must be complete and their futures dropped.
+    fn resolve(&mut self) -> Option<DirtyTiles> {
+        for mask in std::mem::take(&mut self.masks) {
+            let tiles = match mask.read() {
+                Ok(mask) => DirtyTiles::from_mask(&mask),
+                Err(e) => {
+                    // Can't tell what changed, assume everything did.
+                    tracing::warn!("failed to read dirty tiles: {e:?}");
+                    Some(DirtyTiles::whole())
+                }
+            };
+            self.mark(tiles);
+        }
+        self.tiles
+    }
+}
+
 /// An acquired image from the proxy. Will become the current image when dropped,
 /// or after a user-provided GPU fence.
 pub struct ImageGuard<'proxy> {
     proxy: &'proxy Proxy,
     image: Arc<vk::ImageView>,
+    /// Which of the proxy's images this is.
+    image_idx: usize,
     is_submitted: bool,
+    /// Which tiles differ from the previously submitted image, or None if unknown.
+    dirty_mask: Option<vk::Subbuffer<[u32]>>,
+    /// Work still writing this image from before it was taken back, see [`Self::take_after`].
+    after: Option<vk::sync::future::FenceSignalFuture<Box<dyn GpuFuture + Send>>>,
+    region: DocumentRegion,
+    canvas: Canvas,
 }
 impl ImageGuard<'_> {
+    /// The future to start writing this image after. If the image was taken back before it was shown, this is
+    /// the work that was still writing it, to chain onto rather than wait for.
+    #[must_use]
+    pub fn take_after(&mut self) -> Box<dyn GpuFuture + Send> {
+        match self.after.take() {
+            Some(after) => after.boxed_send(),
+            None => self.proxy.render_context.now().boxed_send(),
+        }
+    }
+    /// Set the area of the document that was drawn into this image. Defaults to covering the default canvas.
+    pub fn set_region(&mut self, region: DocumentRegion) {
+        self.region = region;
+    }
+    /// Set the canvas of the document this image shows, the only part of the region that is drawn.
+    pub fn set_canvas(&mut self, canvas: Canvas) {
+        self.canvas = canvas;
+    }
+    fn publish_region(&self) {
+        self.proxy.regions.write()[self.image_idx] = (self.region, self.canvas);
+        *self.proxy.canvas.write() = self.canvas;
+    }
+    /// Provide the flags of each tile that differs from the previously submitted image, see
+    /// [`DirtyTiles::from_mask`]. They must be written by the time the image is ready. Otherwise, the whole
+    /// image is assumed to have changed.
+    pub fn set_dirty_tiles(&mut self, mask: vk::Subbuffer<[u32]>) {
+        self.dirty_mask = Some(mask);
+    }
     /// Submit this image for display immediately. The image should be done writing by the device, as it
     /// will be used for reading without synchronizing!
     pub fn submit_now(self) {
@@ -103,13 +601,21 @@ impl ImageGuard<'_> {
     ) {
         // Surpress default drop behavior.
         self.is_submitted = true;
+        self.publish_region();
 
-        // Place this fence within the proxy.
+        // Place this fence within the proxy, superseding any image not yet shown.
         let mut write = self.proxy.swap_after.write();
-        // This should not be possible - if this proxy exists, there should have been no
-        // outstanding writes waiting.
-        assert!(write.is_empty());
+        self.proxy.take_back(&mut write);
         *write = SwapAfter::Fence(fence);
+        self.proxy
+            .pending_buf
+            .store(self.image_idx, std::sync::atomic::Ordering::Relaxed);
+        let mut dirty = self.proxy.dirty.lock();
+        match self.dirty_mask.take() {
+            Some(mask) => dirty.masks.push(mask),
+            None => dirty.mark(Some(DirtyTiles::whole())),
+        }
+        crate::global::wake::wake(crate::global::wake::Wake::Poll);
     }
 }
 impl Drop for ImageGuard<'_> {
@@ -118,12 +624,22 @@ impl Drop for ImageGuard<'_> {
             return;
         }
         self.is_submitted = true;
-        // Place an immediate swap into the proxy.
+        self.publish_region();
+        // Never chained onto, so it must finish before the image is shown.
+        drop(self.after.take());
+        // Place an immediate swap into the proxy, superseding any image not yet shown.
         let mut write = self.proxy.swap_after.write();
-        // This should not be possible - if this proxy exists, there should have been no
-        // outstanding writes waiting.
-        assert!(write.is_empty());
+        self.proxy.take_back(&mut write);
         *write = SwapAfter::Now;
+        self.proxy
+            .pending_buf
+            .store(self.image_idx, std::sync::atomic::Ordering::Relaxed);
+        // The masks are only valid alongside a fence.
+        let mut dirty = self.proxy.dirty.lock();
+        dirty.masks.clear();
+        dirty.mark(Some(DirtyTiles::whole()));
+        drop(dirty);
+        crate::global::wake::wake(crate::global::wake::Wake::Poll);
     }
 }
 impl std::ops::Deref for ImageGuard<'_> {
@@ -154,31 +670,43 @@ struct SurfaceData {
     context: Arc<crate::render_device::RenderContext>,
     pipeline: Arc<vk::GraphicsPipeline>,
     framebuffers: Box<[Arc<vk::Framebuffer>]>,
-    document_image_bindings: [Arc<vk::PersistentDescriptorSet>; 2],
+    /// Drawing over what proxies beneath left in the image, rather than clearing it.
+    /// Compatible with the pass the framebuffers were made for.
+    load_render_pass: Arc<vk::RenderPass>,
+    document_image_bindings: Box<[Arc<vk::PersistentDescriptorSet>]>,
     // Lazily recorded command buffers. Must be rebuilt on viewport size/document view change.
-    // indexed by swapchain idx, then by image idx
-    prerecorded_command_buffers: Vec<[std::sync::OnceLock<Arc<vk::PrimaryAutoCommandBuffer>>; 2]>,
-    cached_matrix: std::sync::OnceLock<[[f32; 4]; 4]>,
+    // indexed by render target idx, then by image state, then by image idx
+    prerecorded_command_buffers:
+        Vec<[Box<[std::sync::OnceLock<Arc<vk::PrimaryAutoCommandBuffer>>]>; 2]>,
+    // Indexed by image idx, as each may cover a different region.
+    cached_matrices: Box<[std::sync::OnceLock<[[f32; 4]; 4]>]>,
+    /// The area of the document covered by each image, and the canvas within it.
+    regions: Box<[(DocumentRegion, Canvas)]>,
     transform: crate::view_transform::DocumentTransform,
+    view_filter: ViewFilter,
+    edge: DocumentEdge,
     view_pos: cgmath::Point2<f32>,
     view_size: cgmath::Vector2<f32>,
     surface_dimensions: [u32; 2],
+    /// See [`render_device::RenderSurface::transparent`].
+    transparent: bool,
 }
 impl SurfaceData {
     fn new(
         context: Arc<render_device::RenderContext>,
         render_surface: &render_device::RenderSurface,
         render_pass: Arc<vk::RenderPass>,
+        load_render_pass: Arc<vk::RenderPass>,
         pipeline: Arc<vk::GraphicsPipeline>,
-        document_image_bindings: &[Arc<vk::PersistentDescriptorSet>; 2],
+        document_image_bindings: &[Arc<vk::PersistentDescriptorSet>],
 
         viewport_pos: cgmath::Point2<f32>,
         viewport_size: cgmath::Vector2<f32>,
         document_transform: crate::view_transform::DocumentTransform,
+        view_filter: ViewFilter,
     ) -> Self {
         let framebuffers: AnyResult<Vec<_>> = render_surface
-            .swapchain_images()
-            .iter()
+            .render_targets()
             .map(|image| -> AnyResult<_> {
                 // Todo: duplication of view resources.
                 let view = vk::ImageView::new_default(image.clone())?;
@@ -196,41 +724,51 @@ impl SurfaceData {
             .collect();
         let framebuffers = framebuffers.unwrap().into_boxed_slice();
 
-        let mut prerecorded_command_buffers =
-            Vec::with_capacity(render_surface.swapchain_images().len());
+        let mut prerecorded_command_buffers = Vec::with_capacity(framebuffers.len());
+        let images = document_image_bindings.len();
         prerecorded_command_buffers.resize_with(prerecorded_command_buffers.capacity(), || {
-            [std::sync::OnceLock::new(), std::sync::OnceLock::new()]
+            std::array::from_fn(|_| (0..images).map(|_| std::sync::OnceLock::new()).collect())
         });
 
         Self {
             context,
             pipeline,
             surface_dimensions: render_surface.extent(),
+            transparent: render_surface.transparent(),
 
             prerecorded_command_buffers,
 
             framebuffers,
-            document_image_bindings: [
-                document_image_bindings[0].clone(),
-                document_image_bindings[1].clone(),
-            ],
+            load_render_pass,
+            document_image_bindings: document_image_bindings.into(),
 
             transform: document_transform,
+            view_filter,
+            edge: crate::global::preferences::Preferences::read().document_edge,
             view_pos: viewport_pos,
             view_size: viewport_size,
-            cached_matrix: std::sync::OnceLock::new(),
+            cached_matrices: (0..images).map(|_| std::sync::OnceLock::new()).collect(),
+            regions: vec![
+                (
+                    DocumentRegion::covering(Canvas::default()),
+                    Canvas::default()
+                );
+                images
+            ]
+            .into(),
         }
     }
     fn get_commands(
         &self,
         swapchain_idx: u32,
         image_idx: usize,
+        state: ImageState,
     ) -> anyhow::Result<Arc<vk::PrimaryAutoCommandBuffer>> {
         // Try to fetch from the cache:
         let cached = self
             .prerecorded_command_buffers
             .get(swapchain_idx as usize)
-            .and_then(|bufs| bufs.get(image_idx))
+            .and_then(|bufs| bufs[state as usize].get(image_idx))
             .and_then(|lock| lock.get());
         if let Some(cached) = cached {
             return Ok(cached.clone());
@@ -244,6 +782,7 @@ impl SurfaceData {
             .document_image_bindings
             .get(image_idx)
             .ok_or_else(|| anyhow::anyhow!("Image idx out of bounds"))?;
+        let (region, canvas) = self.regions[image_idx];
 
         let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
             self.context.allocators().command_buffer(),
@@ -251,50 +790,48 @@ impl SurfaceData {
             vk::CommandBufferUsage::MultipleSubmit,
         )?;
 
-        let matrix = self
-            .cached_matrix
-            .get_or_try_init(|| -> anyhow::Result<_> {
-                let transform = match &self.transform {
-                    view_transform::DocumentTransform::Fit(f) => f
-                        .make_transform(
-                            cgmath::vec2(
-                                crate::DOCUMENT_DIMENSION as f32,
-                                crate::DOCUMENT_DIMENSION as f32,
-                            ),
-                            self.view_pos,
-                            self.view_size,
-                        )
-                        .ok_or_else(|| anyhow::anyhow!("Malformed document transform"))?,
-                    view_transform::DocumentTransform::Transform(t) => *t,
-                };
-
-                let base_xform = ultraviolet::Mat4::from_nonuniform_scale(ultraviolet::Vec3 {
-                    x: crate::DOCUMENT_DIMENSION as f32,
-                    y: crate::DOCUMENT_DIMENSION as f32,
-                    z: 1.0,
-                });
-                // convert cgmath to ultraviolet (todo, switch all to ultraviolet)
-                let mat4: cgmath::Matrix4<f32> = transform.into();
-                let mat4: [[f32; 4]; 4] = mat4.into();
-                let mat4: ultraviolet::Mat4 = mat4.into();
-
-                let proj = crate::vk::projection::orthographic_vk(
-                    0.0,
-                    self.surface_dimensions[0] as f32,
-                    0.0,
-                    self.surface_dimensions[1] as f32,
-                    -1.0,
-                    1.0,
-                );
-                let proj = proj * mat4 * base_xform;
-                let transform_matrix: [[f32; 4]; 4] = proj.into();
-                Ok(transform_matrix)
-            })?;
+        let matrix = self.cached_matrices[image_idx].get_or_try_init(|| -> anyhow::Result<_> {
+            let transform = match &self.transform {
+                view_transform::DocumentTransform::Fit(f) => f
+                    .make_transform(canvas, self.view_pos, self.view_size)
+                    .ok_or_else(|| anyhow::anyhow!("Malformed document transform"))?,
+                view_transform::DocumentTransform::Transform(t) => *t,
+            };
+
+            let base_xform = region.quad_matrix();
+            // convert cgmath to ultraviolet (todo, switch all to ultraviolet)
+            let mat4: cgmath::Matrix4<f32> = transform.into();
+            let mat4: [[f32; 4]; 4] = mat4.into();
+            let mat4: ultraviolet::Mat4 = mat4.into();
+
+            let proj = crate::vk::projection::orthographic_vk(
+                0.0,
+                self.surface_dimensions[0] as f32,
+                0.0,
+                self.surface_dimensions[1] as f32,
+                -1.0,
+                1.0,
+            );
+            let proj = proj * mat4 * base_xform;
+            let transform_matrix: [[f32; 4]; 4] = proj.into();
+            Ok(transform_matrix)
+        })?;
         command_buffer
             .begin_render_pass(
-                vk::RenderPassBeginInfo {
-                    clear_values: vec![Some([0.05, 0.05, 0.05, 1.0].into())],
-                    ..vk::RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                match state {
+                    ImageState::Undefined => vk::RenderPassBeginInfo {
+                        clear_values: vec![Some(if self.transparent {
+                            [0.0; 4].into()
+                        } else {
+                            [0.05, 0.05, 0.05, 1.0].into()
+                        })],
+                        ..vk::RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                    },
+                    ImageState::Drawn => vk::RenderPassBeginInfo {
+                        render_pass: self.load_render_pass.clone(),
+                        clear_values: vec![None],
+                        ..vk::RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                    },
                 },
                 vk::SubpassBeginInfo {
                     contents: vk::SubpassContents::Inline,
@@ -322,7 +859,17 @@ impl SurfaceData {
             .push_constants(
                 self.pipeline.layout().clone(),
                 0,
-                shaders::vertex::Matrix { mat: *matrix },
+                shaders::vertex::PushConstants {
+                    mat: *matrix,
+                    canvas: region.canvas_quad(canvas),
+                    view_filter: self.view_filter as u32,
+                    transparent: self.transparent.into(),
+                    surface_size: [
+                        self.surface_dimensions[0] as f32,
+                        self.surface_dimensions[1] as f32,
+                    ],
+                    edge: self.edge.flags(),
+                },
             )?
             .draw(4, 1, 0, 0)?
             .end_render_pass(vk::SubpassEndInfo::default())?;
@@ -333,7 +880,7 @@ impl SurfaceData {
         if let Some(lock) = self
             .prerecorded_command_buffers
             .get(swapchain_idx as usize)
-            .and_then(|bufs| bufs.get(image_idx))
+            .and_then(|bufs| bufs[state as usize].get(image_idx))
         {
             let _ = lock.set(command_buffer.clone());
         }
@@ -342,16 +889,42 @@ impl SurfaceData {
     }
     fn clear_cache(&mut self) {
         // Take and discard all cached command buffers
-        for [a, b] in &mut self.prerecorded_command_buffers {
-            a.take();
-            b.take();
+        for bufs in self.prerecorded_command_buffers.iter_mut().flatten() {
+            for lock in bufs.iter_mut() {
+                lock.take();
+            }
+        }
+        for matrix in self.cached_matrices.iter_mut() {
+            matrix.take();
+        }
+    }
+    /// The image at `image_idx` now covers a different area or canvas of the document.
+    fn set_region(&mut self, image_idx: usize, region: (DocumentRegion, Canvas)) {
+        if self.regions[image_idx] == region {
+            return;
         }
-        self.cached_matrix.take();
+        self.regions[image_idx] = region;
+        for bufs in self.prerecorded_command_buffers.iter_mut().flatten() {
+            bufs[image_idx].take();
+        }
+        self.cached_matrices[image_idx].take();
     }
     fn set_transform(&mut self, transform: crate::view_transform::DocumentTransform) {
         self.transform = transform;
         self.clear_cache();
     }
+    fn set_view_filter(&mut self, filter: ViewFilter) {
+        self.view_filter = filter;
+        self.clear_cache();
+    }
+    /// Pick up changes to [`crate::global::preferences::Preferences::document_edge`].
+    fn update_edge(&mut self) {
+        let edge = crate::global::preferences::Preferences::read().document_edge;
+        if self.edge != edge {
+            self.edge = edge;
+            self.clear_cache();
+        }
+    }
     fn set_viewport_size(&mut self, pos: cgmath::Point2<f32>, size: cgmath::Vector2<f32>) {
         self.view_pos = pos;
         self.view_size = size;
@@ -362,7 +935,7 @@ impl SurfaceData {
     }
 }
 
-/// An double-buffering interface between the asynchronous edit->render pipeline of documents
+/// A double- or triple-buffering interface between the asynchronous edit->render pipeline of documents
 /// and the synchronous redrawing of the many swapchain images.
 /// (Because dealing with one image is easier than potentially many, as we don't care about excess framerate)
 /// Provides a method to get a drawable buffer asynchronously, and handles drawing that to the screen
@@ -372,37 +945,61 @@ pub struct Proxy {
 
     document_transform: tokio::sync::RwLock<crate::view_transform::DocumentTransform>,
     viewport: parking_lot::RwLock<(cgmath::Point2<f32>, cgmath::Vector2<f32>)>,
-
-    // Double buffer data =========
-    document_images: [Arc<vk::ImageView>; 2],
-    document_image_bindings: [Arc<vk::PersistentDescriptorSet>; 2],
+    view_filter: parking_lot::RwLock<ViewFilter>,
+    /// Set when the view changed in a way that needs a redraw, despite no new image.
+    view_changed: std::sync::atomic::AtomicBool,
+
+    // Multi buffer data =========
+    document_images: Box<[Arc<vk::ImageView>]>,
+    document_image_bindings: Box<[Arc<vk::PersistentDescriptorSet>]>,
+    /// The area of the document each image covers and the canvas within it, as of its last submission.
+    regions: parking_lot::RwLock<Box<[(DocumentRegion, Canvas)]>>,
+    /// The canvas of the latest submitted image.
+    canvas: parking_lot::RwLock<Canvas>,
 
     // Sync + Swap data ===========
-    /// After this fence is completed, a swap occurs.
-    /// If this is not none, it implies both buffers are in use.
+    /// After this fence is completed, a swap to `pending_buf` occurs.
+    /// Locked while reading or changing which buffer is in what role.
     swap_after: parking_lot::RwLock<SwapAfter<Box<dyn GpuFuture + Send>>>,
-    /// A buffer is available for writing if this notify is set.
+    /// Tiles that changed in the submitted image, if any. Only locked while `swap_after` is.
+    dirty: parking_lot::Mutex<PendingDirty>,
+    /// Fences of images taken back or superseded before they were shown, by image index. Only locked while
+    /// `swap_after` is. Kept until the image is written again, which chains onto its fence.
+    unfinished: parking_lot::Mutex<
+        Vec<(
+            usize,
+            vk::sync::future::FenceSignalFuture<Box<dyn GpuFuture + Send>>,
+        )>,
+    >,
+    /// Set when the window swaps to a submitted image, which it only does once the frame reading the previous
+    /// image has finished.
     write_ready_notify: tokio::sync::Notify,
+    /// How many [`ReferenceView`]s show the whole document, making every change visible.
+    references: std::sync::atomic::AtomicUsize,
     /// Which buffer is the swapchain reading from?
-    read_buf: std::sync::atomic::AtomicU8,
+    read_buf: std::sync::atomic::AtomicUsize,
+    /// Which buffer was submitted, if `swap_after` isn't empty.
+    pending_buf: std::sync::atomic::AtomicUsize,
 
     // Static render data ============
     render_pass: Arc<vk::RenderPass>,
+    load_render_pass: Arc<vk::RenderPass>,
     pipeline: Arc<vk::GraphicsPipeline>,
-    gizmo_renderer: Arc<crate::gizmos::renderer::Renderer>,
 
     // Surface-derived render data ===============
     surface_data: tokio::sync::RwLock<SurfaceData>,
-
-    // User render data ============
-    cursor: parking_lot::RwLock<Option<crate::gizmos::CursorOrInvisible>>,
-    tool_render_as: parking_lot::RwLock<crate::pen_tools::RenderAs>,
 }
 
 impl Proxy {
-    pub fn new(render_surface: &render_device::RenderSurface) -> AnyResult<Self> {
-        // Only one frame-in-flight - Keep an additional buffer for writing to.
-        const NUM_DOCUMENT_BUFFERS: u32 = 2;
+    /// Buffer counts that may be given to [`Self::new`].
+    pub const BUFFERS_RANGE: std::ops::RangeInclusive<u32> = 2..=3;
+    /// Create a proxy with `buffers` document images, clamped to [`Self::BUFFERS_RANGE`].
+    ///
+    /// One image is read by the window and one is written by the renderer. With a third, a finished image can
+    /// wait to be shown while the renderer carries on with the next, rather than the renderer writing over it
+    /// before the window gets the chance.
+    pub fn new(render_surface: &render_device::RenderSurface, buffers: u32) -> AnyResult<Self> {
+        let buffers = buffers.clamp(*Self::BUFFERS_RANGE.start(), *Self::BUFFERS_RANGE.end());
 
         let document_image_array = vk::Image::new(
             render_surface.context().allocators().memory().clone(),
@@ -410,7 +1007,7 @@ impl Proxy {
                 image_type: vk::ImageType::Dim2d,
                 format: crate::DOCUMENT_FORMAT,
                 extent: [crate::DOCUMENT_DIMENSION, crate::DOCUMENT_DIMENSION, 1],
-                array_layers: NUM_DOCUMENT_BUFFERS,
+                array_layers: buffers,
                 // Too many!!
                 usage: vk::ImageUsage::COLOR_ATTACHMENT
                     | vk::ImageUsage::INPUT_ATTACHMENT
@@ -431,7 +1028,8 @@ impl Proxy {
 
             let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
                 context.allocators().command_buffer(),
-                context.queues().compute().idx(),
+                // The images are exclusive to graphics, which does everything else with them.
+                context.queues().graphics().idx(),
                 vk::CommandBufferUsage::OneTimeSubmit,
             )?;
 
@@ -450,38 +1048,28 @@ impl Proxy {
 
             context
                 .now()
-                .then_execute(context.queues().compute().queue().clone(), command_buffer)?
+                .then_execute(context.queues().graphics().queue().clone(), command_buffer)?
                 .then_signal_fence_and_flush()?
         };
         // Wait on the future at the end of init
         let _defer = defer::defer(move || initialize_future.wait(None).unwrap());
 
-        let document_image_views = [
-            vk::ImageView::new(
-                document_image_array.clone(),
-                vk::ImageViewCreateInfo {
-                    subresource_range: vk::ImageSubresourceRange {
-                        array_layers: 0..1,
-                        aspects: vk::ImageAspects::COLOR,
-                        mip_levels: 0..1,
-                    },
-                    view_type: vk::ImageViewType::Dim2d,
-                    ..vk::ImageViewCreateInfo::from_image(&document_image_array)
-                },
-            )?,
-            vk::ImageView::new(
-                document_image_array.clone(),
-                vk::ImageViewCreateInfo {
-                    subresource_range: vk::ImageSubresourceRange {
-                        array_layers: 1..2,
-                        aspects: vk::ImageAspects::COLOR,
-                        mip_levels: 0..1,
+        let document_image_views = (0..buffers)
+            .map(|layer| {
+                vk::ImageView::new(
+                    document_image_array.clone(),
+                    vk::ImageViewCreateInfo {
+                        subresource_range: vk::ImageSubresourceRange {
+                            array_layers: layer..layer + 1,
+                            aspects: vk::ImageAspects::COLOR,
+                            mip_levels: 0..1,
+                        },
+                        view_type: vk::ImageViewType::Dim2d,
+                        ..vk::ImageViewCreateInfo::from_image(&document_image_array)
                     },
-                    view_type: vk::ImageViewType::Dim2d,
-                    ..vk::ImageViewCreateInfo::from_image(&document_image_array)
-                },
-            )?,
-        ];
+                )
+            })
+            .collect::<Result<Box<[_]>, _>>()?;
 
         let render_pass = vulkano::single_pass_renderpass!(
             render_surface.context().device().clone(),
@@ -498,6 +1086,21 @@ impl Proxy {
                 depth_stencil: {},
             },
         )?;
+        let load_render_pass = vulkano::single_pass_renderpass!(
+            render_surface.context().device().clone(),
+            attachments: {
+                document: {
+                    format: render_surface.format(),
+                    samples: 1,
+                    load_op: Load,
+                    store_op: Store,
+                },
+            },
+            pass: {
+                color: [document],
+                depth_stencil: {},
+            },
+        )?;
 
         let sampler = vk::Sampler::new(
             render_surface.context().device().clone(),
@@ -518,15 +1121,27 @@ impl Proxy {
         let vertex_stage = vk::PipelineShaderStageCreateInfo::new(vertex_shader);
         let fragment_stage = vk::PipelineShaderStageCreateInfo::new(fragment_shader);
 
-        let no_blend = vk::ColorBlendState::with_attachment_states(
+        // Everything within the document is opaque or pre-multiplied already, blending only matters for the
+        // smoothed edge and border.
+        let premul_blend = vk::ColorBlendState::with_attachment_states(
             1,
-            vk::ColorBlendAttachmentState::default(),
+            vk::ColorBlendAttachmentState {
+                blend: Some(vk::AttachmentBlend {
+                    src_color_blend_factor: vk::BlendFactor::One,
+                    dst_color_blend_factor: vk::BlendFactor::OneMinusSrcAlpha,
+                    color_blend_op: vk::BlendOp::Add,
+                    src_alpha_blend_factor: vk::BlendFactor::One,
+                    dst_alpha_blend_factor: vk::BlendFactor::OneMinusSrcAlpha,
+                    alpha_blend_op: vk::BlendOp::Add,
+                }),
+                ..Default::default()
+            },
         );
 
-        let matrix_push_constant = vk::PushConstantRange {
+        let push_constant_range = vk::PushConstantRange {
             offset: 0,
-            stages: vk::ShaderStages::VERTEX,
-            size: std::mem::size_of::<shaders::vertex::Matrix>() as u32,
+            stages: vk::ShaderStages::VERTEX | vk::ShaderStages::FRAGMENT,
+            size: std::mem::size_of::<shaders::vertex::PushConstants>() as u32,
         };
         let layout = vk::PipelineLayout::new(
             render_surface.context().device().clone(),
@@ -549,7 +1164,7 @@ impl Proxy {
                         ..Default::default()
                     },
                 )?],
-                push_constant_ranges: vec![matrix_push_constant],
+                push_constant_ranges: vec![push_constant_range],
                 ..Default::default()
             },
         )?;
@@ -557,7 +1172,7 @@ impl Proxy {
             render_surface.context().device().clone(),
             None,
             vk::GraphicsPipelineCreateInfo {
-                color_blend_state: Some(no_blend),
+                color_blend_state: Some(premul_blend),
                 input_assembly_state: Some(vk::InputAssemblyState {
                     topology: vk::PrimitiveTopology::TriangleStrip,
                     primitive_restart_enable: false,
@@ -580,28 +1195,21 @@ impl Proxy {
                 ..vk::GraphicsPipelineCreateInfo::layout(layout.clone())
             },
         )?;
-        let document_image_bindings = [
-            vk::PersistentDescriptorSet::new(
-                render_surface.context().allocators().descriptor_set(),
-                layout.set_layouts()[0].clone(),
-                [vk::WriteDescriptorSet::image_view_sampler(
-                    0,
-                    document_image_views[0].clone(),
-                    sampler.clone(),
-                )],
-                [],
-            )?,
-            vk::PersistentDescriptorSet::new(
-                render_surface.context().allocators().descriptor_set(),
-                layout.set_layouts()[0].clone(),
-                [vk::WriteDescriptorSet::image_view_sampler(
-                    0,
-                    document_image_views[1].clone(),
-                    sampler,
-                )],
-                [],
-            )?,
-        ];
+        let document_image_bindings = document_image_views
+            .iter()
+            .map(|view| {
+                vk::PersistentDescriptorSet::new(
+                    render_surface.context().allocators().descriptor_set(),
+                    layout.set_layouts()[0].clone(),
+                    [vk::WriteDescriptorSet::image_view_sampler(
+                        0,
+                        view.clone(),
+                        sampler.clone(),
+                    )],
+                    [],
+                )
+            })
+            .collect::<Result<Box<[_]>, _>>()?;
 
         let viewport_pos = [0.0, 0.0].into();
         let viewport_size = [
@@ -615,50 +1223,63 @@ impl Proxy {
             render_surface.context().clone(),
             render_surface,
             render_pass.clone(),
+            load_render_pass.clone(),
             pipeline.clone(),
             &document_image_bindings,
             viewport_pos,
             viewport_size,
             document_transform,
+            ViewFilter::None,
         );
 
         let notify = tokio::sync::Notify::new();
         // Start as notified - write buffer is available immediately.
         notify.notify_one();
 
-        let gizmo_renderer =
-            crate::gizmos::renderer::Renderer::new(render_surface.context().clone())?;
-
         Ok(Self {
             render_context: render_surface.context().clone(),
 
             document_transform: document_transform.into(),
             viewport: (viewport_pos, viewport_size).into(),
+            view_filter: ViewFilter::None.into(),
+            view_changed: false.into(),
 
             pipeline,
             render_pass,
+            load_render_pass,
 
             swap_after: SwapAfter::Empty.into(),
+            dirty: PendingDirty::default().into(),
+            unfinished: Vec::new().into(),
             read_buf: 0.into(),
+            pending_buf: 0.into(),
             write_ready_notify: notify,
+            references: 0.into(),
 
+            regions: vec![
+                (
+                    DocumentRegion::covering(Canvas::default()),
+                    Canvas::default()
+                );
+                document_image_views.len()
+            ]
+            .into_boxed_slice()
+            .into(),
+            canvas: Canvas::default().into(),
             document_images: document_image_views,
             document_image_bindings,
 
             surface_data: surface_data.into(),
-            gizmo_renderer: gizmo_renderer.into(),
-
-            cursor: None.into(),
-            tool_render_as: pen_tools::RenderAs::None.into(),
         })
     }
-    /// Internal use only. After the user's buffer is deemed swappable, the read index in switched over and returned.
-    /// Furthermore, the old read buffer is signalled as being writable to any waiting users. New read idx is returned.
+    /// Internal use only. After the user's buffer is deemed swappable, the read index in switched over to it and
+    /// returned. The old read buffer is then free for writing, and signalled as such to any waiting writer.
+    /// Must be called with `swap_after` locked.
     fn swap(&self) -> usize {
-        // Unsure of the proper ordering here. It's not the hottest path, so the strictest one should be okeyyyy.
-        let idx = self
-            .read_buf
-            .fetch_xor(1, std::sync::atomic::Ordering::SeqCst) as usize;
+        // Only changed with `swap_after` locked, which orders it.
+        let idx = self.pending_buf.load(std::sync::atomic::Ordering::Relaxed);
+        self.read_buf
+            .store(idx, std::sync::atomic::Ordering::Relaxed);
         self.write_ready_notify.notify_one();
         idx
     }
@@ -670,42 +1291,127 @@ impl Proxy {
         let mut lock = self.swap_after.write();
         match &*lock {
             // Nothin to do
-            SwapAfter::Empty => self.read_buf.load(std::sync::atomic::Ordering::SeqCst) as usize,
+            SwapAfter::Empty => self.read_buf.load(std::sync::atomic::Ordering::SeqCst),
             // Immediate swap
             SwapAfter::Now => {
                 *lock = SwapAfter::Empty;
+                *self.dirty.lock() = PendingDirty::default();
                 self.swap()
             }
             // Swap if the fence is signalled - without waiting. If not, do nothing.
             SwapAfter::Fence(fence) => {
                 if fence.is_signaled().unwrap() {
                     *lock = SwapAfter::Empty;
+                    *self.dirty.lock() = PendingDirty::default();
                     self.swap()
                 } else {
-                    self.read_buf.load(std::sync::atomic::Ordering::SeqCst) as usize
+                    self.read_buf.load(std::sync::atomic::Ordering::SeqCst)
                 }
             }
         }
     }
     /// Returns true if a new image was submitted that hasn't been
-    /// acquired yet
+    /// acquired yet, and it changed somewhere visible.
     pub fn redraw_requested(&self) -> bool {
-        match &*self.swap_after.read() {
-            SwapAfter::Empty => false,
-            SwapAfter::Now => true,
-            SwapAfter::Fence(fence) => fence.is_signaled().unwrap(),
+        let mut swap_after = self.swap_after.write();
+        match &*swap_after {
+            SwapAfter::Empty => return false,
+            SwapAfter::Now => (),
+            SwapAfter::Fence(fence) => {
+                if !fence.is_signaled().unwrap() {
+                    return false;
+                }
+                // Release the finished future, so that the mask may be read.
+                *swap_after = SwapAfter::Now;
+            }
+        }
+        // Submitted earlier on the same queue, so those unshown are finished too.
+        self.unfinished
+            .lock()
+            .retain(|(_, fence)| !fence.is_signaled().unwrap_or(true));
+        let tiles = self.dirty.lock().resolve();
+        drop(swap_after);
+        tiles.is_some_and(|tiles| self.is_visible(tiles))
+    }
+    /// Whether any of the tiles are within the viewport.
+    fn is_visible(&self, tiles: DirtyTiles) -> bool {
+        if self.references.load(std::sync::atomic::Ordering::Relaxed) != 0 {
+            return true;
+        }
+        let Some(transform) = self.get_view_transform_sync() else {
+            return true;
+        };
+        // The tiles are of the submitted image.
+        let (region, _) =
+            self.regions.read()[self.pending_buf.load(std::sync::atomic::Ordering::Relaxed)];
+        let (min, max) = tiles.document_rect(region);
+        // Bounding box of the tiles in view space, which may be rotated.
+        let corners = [
+            min,
+            cgmath::Point2 { x: max.x, y: min.y },
+            cgmath::Point2 { x: min.x, y: max.y },
+            max,
+        ]
+        .map(|corner| transform.project(corner));
+        let (pos, size) = self.get_viewport();
+        let fold = |f: fn(f32, f32) -> f32, get: fn(&cgmath::Point2<f32>) -> f32| {
+            corners.iter().map(get).reduce(f).unwrap()
+        };
+        fold(f32::max, |p| p.x) >= pos.x
+            && fold(f32::min, |p| p.x) <= pos.x + size.x
+            && fold(f32::max, |p| p.y) >= pos.y
+            && fold(f32::min, |p| p.y) <= pos.y + size.y
+    }
+    /// Take back the submitted image, if any, so that it may be written again. It has not been shown, so its
+    /// changes are kept to be shown alongside the next. Its fence isn't waited on, but kept for the next write
+    /// of the image to chain onto.
+    fn take_back(&self, swap_after: &mut SwapAfter<Box<dyn GpuFuture + Send>>) {
+        if let SwapAfter::Fence(fence) = std::mem::replace(swap_after, SwapAfter::Empty) {
+            let idx = self.pending_buf.load(std::sync::atomic::Ordering::Relaxed);
+            self.unfinished.lock().push((idx, fence));
         }
     }
+    /// Acquire an image to draw into, one that is neither being shown nor waiting to be.
+    ///
+    /// With nothing waiting to be shown, this first waits for the window to swap to the last image submitted, as
+    /// until then a frame in flight may still be reading the image that would be handed out. With only two
+    /// images, a submitted image that hasn't been shown yet - likely because nothing visible changed, or the
+    /// window is slower than the renderer - is taken back and written over instead.
     pub async fn write(&self) -> ImageGuard<'_> {
-        self.write_ready_notify.notified().await;
-        assert!(self.swap_after.read().is_empty());
-        // We are now the sole writer. Hopefully. Return the proxy:
+        if self.swap_after.read().is_empty() {
+            self.write_ready_notify.notified().await;
+        }
+        let mut swap_after = self.swap_after.write();
+        let read_buf = self.read_buf.load(std::sync::atomic::Ordering::Relaxed);
+        let pending_buf = if swap_after.is_empty() {
+            None
+        } else {
+            Some(self.pending_buf.load(std::sync::atomic::Ordering::Relaxed))
+        };
+        // Unwrap ok - there are at least two images, and if they're all taken the pending one is free to take.
+        let image_idx = (0..self.document_images.len())
+            .find(|&idx| idx != read_buf && Some(idx) != pending_buf)
+            .or_else(|| {
+                self.take_back(&mut swap_after);
+                pending_buf
+            })
+            .unwrap();
+        let after = {
+            let mut unfinished = self.unfinished.lock();
+            unfinished
+                .iter()
+                .position(|&(idx, _)| idx == image_idx)
+                .map(|pos| unfinished.swap_remove(pos).1)
+        };
+        drop(swap_after);
         ImageGuard {
-            // Return whichever image is *not* the read buf. Uhm uh ordering??
-            image: self.document_images
-                [(self.read_buf.load(std::sync::atomic::Ordering::SeqCst) ^ 1) as usize]
-                .clone(),
+            image: self.document_images[image_idx].clone(),
+            image_idx,
             is_submitted: false,
+            dirty_mask: None,
+            after,
+            region: DocumentRegion::covering(Canvas::default()),
+            canvas: Canvas::default(),
             proxy: self,
         }
     }
@@ -727,6 +1433,7 @@ impl Proxy {
 
         Some(crate::view_transform::ViewInfo {
             transform,
+            canvas: self.canvas(),
             viewport_position: ultraviolet::Vec2 { x: pos.x, y: pos.y },
             viewport_size: ultraviolet::Vec2 {
                 x: size.x,
@@ -734,25 +1441,22 @@ impl Proxy {
             },
         })
     }
-    pub fn insert_cursor(&self, new_cursor: Option<crate::gizmos::CursorOrInvisible>) {
-        *self.cursor.write() = new_cursor;
+    #[must_use]
+    pub fn view_filter(&self) -> ViewFilter {
+        *self.view_filter.read()
     }
-    pub fn insert_tool_render(&self, new_render_as: crate::pen_tools::RenderAs) {
-        *self.tool_render_as.write() = new_render_as;
+    pub async fn set_view_filter(&self, filter: ViewFilter) {
+        *self.view_filter.write() = filter;
+        self.surface_data.write().await.set_view_filter(filter);
+        self.view_changed
+            .store(true, std::sync::atomic::Ordering::Relaxed);
     }
     pub fn get_view_transform_sync(&self) -> Option<crate::view_transform::ViewTransform> {
         // lock, clone, release asap
         match *self.document_transform.blocking_read() {
             crate::view_transform::DocumentTransform::Fit(f) => {
                 let (pos, size) = *self.viewport.read();
-                f.make_transform(
-                    cgmath::Vector2 {
-                        x: crate::DOCUMENT_DIMENSION as f32,
-                        y: crate::DOCUMENT_DIMENSION as f32,
-                    },
-                    pos,
-                    size,
-                )
+                f.make_transform(self.canvas(), pos, size)
             }
             crate::view_transform::DocumentTransform::Transform(t) => Some(t),
         }
@@ -760,80 +1464,149 @@ impl Proxy {
     pub fn get_viewport(&self) -> (cgmath::Point2<f32>, cgmath::Vector2<f32>) {
         *self.viewport.read()
     }
+    /// The canvas of the document as of the latest submitted image.
+    #[must_use]
+    pub fn canvas(&self) -> Canvas {
+        *self.canvas.read()
+    }
+    /// The region of the document to rasterize for the current view, see [`DocumentRegion::for_view`].
+    /// None while a [`ReferenceView`] is open, as it shows the whole document.
+    pub async fn zoom_region(&self) -> Option<DocumentRegion> {
+        if self.references.load(std::sync::atomic::Ordering::Relaxed) != 0 {
+            return None;
+        }
+        DocumentRegion::for_view(&self.get_view_transform().await?)
+    }
+    /// The level of detail to composite at for the current view, see [`lod_for_view`]. Full detail while a
+    /// [`ReferenceView`] is open, as it may need it.
+    pub async fn zoom_lod(&self) -> u32 {
+        if self.references.load(std::sync::atomic::Ordering::Relaxed) != 0 {
+            return 0;
+        }
+        self.get_view_transform()
+            .await
+            .map_or(0, |view| lod_for_view(&view))
+    }
+}
+/// A second, non-interactive view of the proxy's image, zoomed to fit its own surface. Shows whichever image
+/// the main view last read, never swapping the buffers itself.
+pub struct ReferenceView {
+    proxy: Arc<Proxy>,
+    surface_data: parking_lot::RwLock<SurfaceData>,
+}
+impl ReferenceView {
+    pub fn new(
+        proxy: Arc<Proxy>,
+        render_surface: &render_device::RenderSurface,
+    ) -> AnyResult<Self> {
+        // The render pass and pipeline are shared with the main view.
+        if proxy.render_pass.attachments()[0].format != render_surface.format() {
+            anyhow::bail!("reference surface format differs from the main surface");
+        }
+        let surface_data = Self::surface_data(&proxy, render_surface);
+        proxy
+            .references
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(Self {
+            proxy,
+            surface_data: surface_data.into(),
+        })
+    }
+    fn surface_data(proxy: &Proxy, render_surface: &render_device::RenderSurface) -> SurfaceData {
+        let extent = render_surface.extent();
+        SurfaceData::new(
+            proxy.render_context.clone(),
+            render_surface,
+            proxy.render_pass.clone(),
+            proxy.load_render_pass.clone(),
+            proxy.pipeline.clone(),
+            &proxy.document_image_bindings,
+            [0.0, 0.0].into(),
+            [extent[0] as f32, extent[1] as f32].into(),
+            crate::view_transform::DocumentTransform::default(),
+            proxy.view_filter(),
+        )
+    }
+    /// Get the commands to draw the current image into the given swapchain image.
+    /// # Safety
+    ///
+    /// The main view must not [`Proxy::read`] until these commands are complete, as that may hand the
+    /// image over to be written.
+    pub unsafe fn render(
+        &self,
+        swapchain_idx: u32,
+    ) -> AnyResult<Arc<vk::PrimaryAutoCommandBuffer>> {
+        // Show the same filter as the main view.
+        let view_filter = self.proxy.view_filter();
+        if self.surface_data.read().view_filter != view_filter {
+            self.surface_data.write().set_view_filter(view_filter);
+        }
+        self.surface_data.write().update_edge();
+        let image_idx = self
+            .proxy
+            .read_buf
+            .load(std::sync::atomic::Ordering::SeqCst);
+        let region = self.proxy.regions.read()[image_idx];
+        if self.surface_data.read().regions[image_idx] != region {
+            self.surface_data.write().set_region(image_idx, region);
+        }
+        self.surface_data
+            .read()
+            .get_commands(swapchain_idx, image_idx, ImageState::Undefined)
+    }
+    /// The window surface has been invalidated and remade.
+    pub fn surface_changed(&self, render_surface: &render_device::RenderSurface) {
+        *self.surface_data.write() = Self::surface_data(&self.proxy, render_surface);
+    }
+}
+impl Drop for ReferenceView {
+    fn drop(&mut self) {
+        self.proxy
+            .references
+            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    }
 }
 impl PreviewRenderProxy for Proxy {
     #[deny(unsafe_op_in_unsafe_fn)]
     unsafe fn render(
         &self,
-        swapchain_image: Arc<vk::Image>,
+        _: Arc<vk::Image>,
         swapchain_idx: u32,
+        state: ImageState,
     ) -> AnyResult<smallvec::SmallVec<[Arc<vk::PrimaryAutoCommandBuffer>; 2]>> {
         // Safety: contract forwarded to the contract of this fn.
         let image_idx = unsafe { self.read() };
-        let read = self.surface_data.blocking_read();
-        let commands = read.get_commands(swapchain_idx, image_idx)?;
-
-        // Do we have anything to render?
-        let tool_render_as = self.tool_render_as.read();
-        let tool_buffer = if matches!(
-            *tool_render_as,
-            pen_tools::RenderAs::SharedGizmoCollection(..) | pen_tools::RenderAs::InlineGizmos(..)
-        ) {
-            let proj = crate::vk::projection::orthographic_vk(
-                0.0,
-                read.surface_dimensions[0] as f32,
-                0.0,
-                read.surface_dimensions[1] as f32,
-                -1.0,
-                1.0,
-            );
-            let proj: [[f32; 4]; 4] = proj.into();
-            let proj: cgmath::Matrix4<f32> = proj.into();
-            let mut visitor = self.gizmo_renderer.render_visit(
-                swapchain_image,
-                [
-                    read.surface_dimensions[0] as f32,
-                    read.surface_dimensions[1] as f32,
-                ],
-                self.get_view_transform_sync().unwrap(),
-                proj,
-            )?;
-            match &*tool_render_as {
-                pen_tools::RenderAs::SharedGizmoCollection(shared) => {
-                    shared.blocking_read().visit_painter(&mut visitor);
-                }
-                pen_tools::RenderAs::InlineGizmos(gizmos) => {
-                    for gizmo in gizmos {
-                        gizmo.visit_painter(&mut visitor);
-                    }
-                }
-                pen_tools::RenderAs::None => unreachable!(), // Guarded above
-            }
-            Some(visitor.build()?)
-        } else {
-            None
-        };
-        let mut vec = smallvec::SmallVec::with_capacity(2);
-        vec.push(commands);
-        if let Some(tool_buffer) = tool_buffer {
-            vec.push(tool_buffer);
+        self.view_changed
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+        self.surface_data.blocking_write().update_edge();
+        let (region, canvas) = self.regions.read()[image_idx];
+        if self.surface_data.blocking_read().regions[image_idx] != (region, canvas) {
+            self.surface_data
+                .blocking_write()
+                .set_region(image_idx, (region, canvas));
         }
-
-        Ok(vec)
+        let commands =
+            self.surface_data
+                .blocking_read()
+                .get_commands(swapchain_idx, image_idx, state)?;
+        Ok(smallvec::smallvec![commands])
     }
     fn surface_changed(&self, render_surface: &render_device::RenderSurface) {
         let viewport = *self.viewport.read();
         let transform = *self.document_transform.blocking_read();
+        let view_filter = *self.view_filter.read();
 
         let new = SurfaceData::new(
             self.render_context.clone(),
             render_surface,
             self.render_pass.clone(),
+            self.load_render_pass.clone(),
             self.pipeline.clone(),
             &self.document_image_bindings,
             viewport.0,
             viewport.1,
             transform,
+            view_filter,
         );
         *self.surface_data.blocking_write() = new;
     }
@@ -855,9 +1628,300 @@ impl PreviewRenderProxy for Proxy {
         *self.viewport.write() = cg;
     }
     fn has_update(&self) -> bool {
-        self.redraw_requested()
+        self.redraw_requested() || self.view_changed.load(std::sync::atomic::Ordering::Relaxed)
+    }
+    fn is_pending(&self) -> bool {
+        match &*self.swap_after.read() {
+            SwapAfter::Fence(fence) => !fence.is_signaled().unwrap(),
+            SwapAfter::Now | SwapAfter::Empty => false,
+        }
     }
+    fn cursor(&self) -> Option<crate::gizmos::CursorOrInvisible> {
+        None
+    }
+}
+/// The active tool's gizmos and cursor, drawn over the document [`Proxy`] as a [`ProxyLayer::Overlay`].
+pub struct GizmoOverlay {
+    /// The document the gizmos are placed over, whose view they follow and whose image they may sample.
+    document: Arc<Proxy>,
+    renderer: crate::gizmos::renderer::Renderer,
+    surface_dimensions: parking_lot::RwLock<[u32; 2]>,
+    cursor: parking_lot::RwLock<Option<crate::gizmos::CursorOrInvisible>>,
+    tool_render_as: parking_lot::RwLock<crate::pen_tools::RenderAs>,
+}
+impl GizmoOverlay {
+    pub fn new(
+        render_surface: &render_device::RenderSurface,
+        document: Arc<Proxy>,
+    ) -> AnyResult<Self> {
+        Ok(Self {
+            document,
+            renderer: crate::gizmos::renderer::Renderer::new(render_surface.context().clone())?,
+            surface_dimensions: render_surface.extent().into(),
+            cursor: None.into(),
+            tool_render_as: pen_tools::RenderAs::None.into(),
+        })
+    }
+    pub fn insert_cursor(&self, new_cursor: Option<crate::gizmos::CursorOrInvisible>) {
+        *self.cursor.write() = new_cursor;
+    }
+    pub fn insert_tool_render(&self, new_render_as: crate::pen_tools::RenderAs) {
+        *self.tool_render_as.write() = new_render_as;
+    }
+}
+impl PreviewRenderProxy for GizmoOverlay {
+    unsafe fn render(
+        &self,
+        swapchain_image: Arc<vk::Image>,
+        _: u32,
+        state: ImageState,
+    ) -> AnyResult<smallvec::SmallVec<[Arc<vk::PrimaryAutoCommandBuffer>; 2]>> {
+        // Do we have anything to render? Gizmos only draw over, so need the document beneath.
+        let tool_render_as = self.tool_render_as.read();
+        if state == ImageState::Undefined || matches!(*tool_render_as, pen_tools::RenderAs::None) {
+            return Ok(smallvec::SmallVec::new());
+        }
+        let Some(transform) = self.document.get_view_transform_sync() else {
+            return Ok(smallvec::SmallVec::new());
+        };
+        // The image the document layer beneath just read.
+        let image_idx = self
+            .document
+            .read_buf
+            .load(std::sync::atomic::Ordering::SeqCst);
+        let (region, _) = self.document.regions.read()[image_idx];
+        let [width, height] = self.surface_dimensions.read().map(|dim| dim as f32);
+        let proj = crate::vk::projection::orthographic_vk(0.0, width, 0.0, height, -1.0, 1.0);
+        let proj: [[f32; 4]; 4] = proj.into();
+        let proj: cgmath::Matrix4<f32> = proj.into();
+        let mut visitor = self.renderer.render_visit(
+            swapchain_image,
+            [width, height],
+            transform,
+            proj,
+            Some(crate::gizmos::renderer::DocumentImage {
+                view: self.document.document_images[image_idx].clone(),
+                origin: [region.origin.x, region.origin.y],
+                size: region.size,
+            }),
+        )?;
+        match &*tool_render_as {
+            pen_tools::RenderAs::SharedGizmoCollection(shared) => {
+                shared.blocking_read().visit_painter(&mut visitor);
+            }
+            pen_tools::RenderAs::InlineGizmos(gizmos) => {
+                for gizmo in gizmos {
+                    gizmo.visit_painter(&mut visitor);
+                }
+            }
+            pen_tools::RenderAs::None => unreachable!(), // Guarded above
+        }
+        Ok(smallvec::smallvec![visitor.build()?])
+    }
+    fn surface_changed(&self, render_surface: &render_device::RenderSurface) {
+        *self.surface_dimensions.write() = render_surface.extent();
+    }
+    fn has_update(&self) -> bool {
+        false
+    }
+    fn is_pending(&self) -> bool {
+        false
+    }
+    fn viewport_changed(&self, _: ultraviolet::Vec2, _: ultraviolet::Vec2) {}
     fn cursor(&self) -> Option<crate::gizmos::CursorOrInvisible> {
         *self.cursor.read()
     }
 }
+#[cfg(test)]
+mod test {
+    use super::{Canvas, DirtyTiles, DocumentRegion};
+    /// Asks for a cursor, drawing nothing.
+    struct CursorProxy(Option<crate::gizmos::CursorOrInvisible>);
+    impl super::PreviewRenderProxy for CursorProxy {
+        unsafe fn render(
+            &self,
+            _: std::sync::Arc<crate::vk::Image>,
+            _: u32,
+            _: super::ImageState,
+        ) -> crate::AnyResult<
+            smallvec::SmallVec<[std::sync::Arc<crate::vk::PrimaryAutoCommandBuffer>; 2]>,
+        > {
+            Ok(smallvec::SmallVec::new())
+        }
+        fn surface_changed(&self, _: &crate::render_device::RenderSurface) {}
+        fn has_update(&self) -> bool {
+            self.0.is_some()
+        }
+        fn is_pending(&self) -> bool {
+            false
+        }
+        fn viewport_changed(&self, _: ultraviolet::Vec2, _: ultraviolet::Vec2) {}
+        fn cursor(&self) -> Option<crate::gizmos::CursorOrInvisible> {
+            self.0
+        }
+    }
+    #[test]
+    fn stack_layers() {
+        use super::{PreviewRenderProxy, ProxyLayer, ProxyStack};
+        use crate::gizmos::CursorOrInvisible;
+        use winit::window::CursorIcon;
+        let mut stack = ProxyStack::default();
+        assert!(!stack.has_update());
+        stack.push(
+            ProxyLayer::Overlay,
+            std::sync::Arc::new(CursorProxy(Some(CursorOrInvisible::Icon(
+                CursorIcon::Crosshair,
+            )))),
+        );
+        // Pushed later, but beneath the overlay.
+        stack.push(
+            ProxyLayer::Document,
+            std::sync::Arc::new(CursorProxy(Some(CursorOrInvisible::Invisible))),
+        );
+        stack.push(ProxyLayer::Underlay, std::sync::Arc::new(CursorProxy(None)));
+        stack.push(ProxyLayer::Overlay, std::sync::Arc::new(CursorProxy(None)));
+        assert!(stack.has_update());
+
+        let cursors: Vec<_> = stack.iter().map(|proxy| proxy.cursor()).collect();
+        assert!(matches!(
+            cursors[..],
+            [
+                None,
+                Some(CursorOrInvisible::Invisible),
+                Some(CursorOrInvisible::Icon(CursorIcon::Crosshair)),
+                None
+            ]
+        ));
+        // The topmost overlay has no preference, so the one beneath decides.
+        assert!(matches!(
+            stack.cursor(),
+            Some(CursorOrInvisible::Icon(CursorIcon::Crosshair))
+        ));
+    }
+    #[test]
+    fn dirty_tiles_from_mask() {
+        let per_row = DirtyTiles::tiles_per_side() as usize;
+        let mut mask = vec![0; per_row * per_row];
+        assert_eq!(DirtyTiles::from_mask(&mask), None);
+
+        mask[per_row + 2] = 1;
+        mask[3 * per_row + 1] = 1;
+        let tiles = DirtyTiles::from_mask(&mask).unwrap();
+        assert_eq!(
+            tiles,
+            DirtyTiles {
+                min: [1, 1],
+                max: [2, 3],
+            }
+        );
+        let size = DirtyTiles::TILE_SIZE as f32;
+        let whole = DocumentRegion::covering(Canvas::default());
+        assert_eq!(
+            tiles.document_rect(whole),
+            ([size, size].into(), [3.0 * size, 4.0 * size].into())
+        );
+        // The last tile is clipped to the document.
+        let dimension = crate::DOCUMENT_DIMENSION as f32;
+        assert_eq!(
+            DirtyTiles::whole().document_rect(whole),
+            ([0.0, 0.0].into(), [dimension, dimension].into())
+        );
+        // Tiles of a zoomed region span less of the document, from its origin.
+        let zoomed = DocumentRegion {
+            origin: [100.0, -50.0].into(),
+            size: dimension / 2.0,
+        };
+        assert_eq!(
+            tiles.document_rect(zoomed),
+            (
+                [100.0 + size / 2.0, -50.0 + size / 2.0].into(),
+                [100.0 + 1.5 * size, -50.0 + 2.0 * size].into()
+            )
+        );
+    }
+    #[test]
+    fn covering_canvas() {
+        let canvas = Canvas {
+            origin: [-100.0, 50.0],
+            size: [400.0, 200.0],
+        };
+        let region = DocumentRegion::covering(canvas);
+        assert_eq!(
+            region,
+            DocumentRegion {
+                origin: [-100.0, 50.0].into(),
+                size: 400.0,
+            }
+        );
+        // The canvas spans the width of the quad, and the top half of its height.
+        assert_eq!(region.canvas_quad(canvas), [0.0, 0.0, 1.0, 0.5]);
+        // Zoomed in, the canvas is clipped to the region.
+        let zoomed = DocumentRegion {
+            origin: [0.0, 100.0].into(),
+            size: 200.0,
+        };
+        assert_eq!(zoomed.canvas_quad(canvas), [0.0, 0.0, 1.0, 0.75]);
+    }
+    #[test]
+    fn zoom_region_covers_view() {
+        let dimension = crate::DOCUMENT_DIMENSION as f32;
+        let view = |scale| crate::view_transform::ViewInfo {
+            transform: crate::view_transform::DocumentTransform::Transform(
+                crate::view_transform::ViewTransform::center_on(
+                    // Slightly off the viewport's center, so the view isn't aligned to the region grid.
+                    cgmath::point2(380.0, 290.0),
+                    cgmath::vec2(dimension, dimension),
+                    cgmath::Rad(0.3),
+                    scale,
+                ),
+            ),
+            canvas: Canvas::default(),
+            viewport_position: ultraviolet::Vec2::zero(),
+            viewport_size: ultraviolet::Vec2::new(800.0, 600.0),
+        };
+        // Zoomed out, the whole image has plenty of resolution.
+        assert_eq!(DocumentRegion::for_view(&view(0.5)), None);
+
+        let zoomed = view(8.0);
+        let region = DocumentRegion::for_view(&zoomed).unwrap();
+        let (min, extent) = zoomed.document_space_aabb().unwrap();
+        assert!(region.size < dimension);
+        assert!(region.origin.x <= min.x && region.origin.y <= min.y);
+        assert!(region.origin.x + region.size >= min.x + extent.x);
+        assert!(region.origin.y + region.size >= min.y + extent.y);
+        // A small pan stays within the same region.
+        let mut panned = zoomed;
+        panned
+            .make_transformed()
+            .unwrap()
+            .pan(cgmath::vec2(1.0, -1.0));
+        assert_eq!(DocumentRegion::for_view(&panned), Some(region));
+    }
+    #[test]
+    fn lod_keeps_resolution() {
+        let dimension = crate::DOCUMENT_DIMENSION as f32;
+        let view = |scale| crate::view_transform::ViewInfo {
+            transform: crate::view_transform::DocumentTransform::Transform(
+                crate::view_transform::ViewTransform::center_on(
+                    cgmath::point2(400.0, 300.0),
+                    cgmath::vec2(dimension, dimension),
+                    cgmath::Rad(0.0),
+                    scale,
+                ),
+            ),
+            canvas: Canvas::default(),
+            viewport_position: ultraviolet::Vec2::zero(),
+            viewport_size: ultraviolet::Vec2::new(800.0, 600.0),
+        };
+        assert_eq!(super::lod_for_view(&view(4.0)), 0);
+        assert_eq!(super::lod_for_view(&view(0.75)), 0);
+        assert_eq!(super::lod_for_view(&view(0.5)), 1);
+        assert_eq!(super::lod_for_view(&view(0.3)), 1);
+        assert_eq!(super::lod_for_view(&view(0.05)), 4);
+        // No smaller than the last level.
+        assert_eq!(
+            super::lod_for_view(&view(0.001)),
+            crate::renderer::LOD_LEVELS - 1
+        );
+    }
+}
@@ -27,6 +27,8 @@ pub struct Ctx {
     redraw_this_frame: bool,
     redraw_next_frame: bool,
     full_output: Option<egui::FullOutput>,
+    /// Pixels per point and geometry of the frame last built, to draw again for screenshots.
+    last_frame: Option<(f32, Vec<egui::epaint::ClippedPrimitive>)>,
     repaint_times: std::collections::VecDeque<std::time::Instant>,
 }
 impl Ctx {
@@ -44,6 +46,10 @@ impl Ctx {
             None,
             None,
         );
+        // The zoom factor is owned by the user's UI scale preference. Don't let egui's own hotkeys fight it.
+        state
+            .egui_ctx()
+            .options_mut(|options| options.zoom_with_keyboard = false);
         let properties = render_surface.context().physical_device().properties();
         let max_size = properties.max_image_dimension2_d;
         state.set_max_texture_side(max_size as usize);
@@ -54,12 +60,43 @@ impl Ctx {
             redraw_this_frame: false,
             redraw_next_frame: true,
             full_output: None,
+            last_frame: None,
             repaint_times: std::collections::VecDeque::new(),
         })
     }
+    /// Expose the UI to screen readers and other assistive technology, whose requests arrive as
+    /// [`Wake::Accessibility`](crate::global::wake::Wake::Accessibility) events. Must be called before the
+    /// window is first shown.
+    pub fn init_accesskit(
+        &mut self,
+        window: &winit::window::Window,
+        event_loop: winit::event_loop::EventLoopProxy<crate::global::wake::Wake>,
+    ) {
+        let egui_ctx = self.state.egui_ctx().clone();
+        self.state.init_accesskit(window, event_loop, move || {
+            // Only called once something asks for the tree, so it costs nothing until then.
+            egui_ctx.enable_accesskit();
+            // The full tree comes with the next update.
+            egui_ctx.request_repaint();
+            egui_ctx.accesskit_placeholder_tree_update()
+        });
+    }
+    /// Handle a request from assistive technology, such as to click or focus a widget.
+    pub fn push_accesskit_request(&mut self, request: egui::accesskit::ActionRequest) {
+        self.state.on_accesskit_action_request(request);
+        self.redraw_this_frame = true;
+    }
     pub fn wants_pointer_input(&self) -> bool {
         self.state.egui_ctx().wants_pointer_input()
     }
+    /// Physical pixels per egui point, the product of the window's scale factor and the zoom factor.
+    pub fn pixels_per_point(&self) -> f32 {
+        self.state.egui_ctx().pixels_per_point()
+    }
+    /// Set the user's scale, multiplied on top of the window's scale factor. Takes effect next update.
+    pub fn set_zoom_factor(&self, zoom_factor: f32) {
+        self.state.egui_ctx().set_zoom_factor(zoom_factor);
+    }
     pub fn replace_surface(&mut self, surface: &RenderSurface) -> anyhow::Result<()> {
         self.renderer.gen_framebuffers(surface)
     }
@@ -118,6 +155,17 @@ impl Ctx {
         // Closure always runs, this is not presented on a type level though.
         user_output.unwrap()
     }
+    /// Rerun the UI next frame, as state it shows has changed.
+    pub fn request_update(&mut self) {
+        self.redraw_this_frame = true;
+    }
+    /// The soonest repaint egui scheduled for the future, if any. Those already due are reported by
+    /// [`Self::peek_wants_update`] instead.
+    pub fn next_repaint(&self) -> Option<std::time::Instant> {
+        let now = std::time::Instant::now();
+        // Sorted, see `insert_repaint`
+        self.repaint_times.iter().copied().find(|t| *t > now)
+    }
     /// Peek the update flag without destroying it.
     pub fn peek_wants_update(&self) -> bool {
         let now = &std::time::Instant::now();
@@ -176,16 +224,33 @@ impl Ctx {
         // Check if there's anything to draw!
         let output = self.full_output.take()?;
 
+        let pixels_per_point = output.pixels_per_point;
         let res: AnyResult<_> = try_block::try_block! {
             let transfer_commands = self.renderer.do_image_deltas(output.textures_delta).transpose()?;
-            let tess_geom = self.state.egui_ctx().tessellate(output.shapes, output.pixels_per_point);
-            let draw_commands = self.renderer.upload_and_render(output.pixels_per_point, swapchain_idx, &tess_geom, clear)?;
-            drop(tess_geom);
+            let tess_geom = self.state.egui_ctx().tessellate(output.shapes, pixels_per_point);
+            let draw_commands = self.renderer.upload_and_render(pixels_per_point, swapchain_idx, &tess_geom, clear)?;
 
-            Ok((transfer_commands, draw_commands))
+            Ok((transfer_commands, draw_commands, tess_geom))
         };
 
-        Some(res.unwrap()) //also stinky
+        let (transfer_commands, draw_commands, tess_geom) = res.unwrap(); //also stinky
+        self.last_frame = Some((pixels_per_point, tess_geom));
+        Some((transfer_commands, draw_commands))
+    }
+    /// Draw the frame last built by [`Self::build_commands`] again, into the render target at `target_idx`,
+    /// such as the surface's [capture image](RenderSurface::capture_image). Textures are as that frame left them.
+    /// None if no frame has been built.
+    pub fn build_capture_commands(
+        &self,
+        target_idx: u32,
+        clear: bool,
+    ) -> anyhow::Result<Option<Arc<vk::PrimaryAutoCommandBuffer>>> {
+        let Some((pixels_per_point, tess_geom)) = &self.last_frame else {
+            return Ok(None);
+        };
+        self.renderer
+            .upload_and_render(*pixels_per_point, target_idx, tess_geom, clear)
+            .map(Some)
     }
 }
 
@@ -276,6 +341,38 @@ impl From<egui::epaint::Vertex> for EguiVertex {
         }
     }
 }
+/// Convert an egui clip rect, in points, into a scissor rect in framebuffer pixels.
+///
+/// The rect is rounded outward to whole pixels and clamped to `framebuffer_extent`, as
+/// egui happily hands out clip rects that extend past the window (or start at negative
+/// coordinates) which are invalid as a scissor. Returns `None` if the clipped region has zero area
+/// (or the rect is degenerate or NaN), in which case the draw should be skipped entirely.
+fn clip_rect_to_scissor(
+    clip_rect: egui::Rect,
+    scale_factor: f32,
+    framebuffer_extent: [u32; 2],
+) -> Option<vk::Scissor> {
+    // Precision loss is fine, framebuffers are not anywhere near 2^24 pixels wide.
+    #[allow(clippy::cast_precision_loss)]
+    let [width, height] = framebuffer_extent.map(|dim| dim as f32);
+
+    // Clamp of NaN is NaN, which fails the area check below.
+    let min_x = (clip_rect.min.x * scale_factor).floor().clamp(0.0, width);
+    let min_y = (clip_rect.min.y * scale_factor).floor().clamp(0.0, height);
+    let max_x = (clip_rect.max.x * scale_factor).ceil().clamp(0.0, width);
+    let max_y = (clip_rect.max.y * scale_factor).ceil().clamp(0.0, height);
+
+    // Written this way to reject NaNs too.
+    if !(max_x > min_x && max_y > min_y) {
+        return None;
+    }
+
+    // All values are finite, integral, and within [0, extent] - casts are lossless.
+    let offset = [min_x, min_y].map(az::saturating_cast::<f32, u32>);
+    let extent = [max_x - min_x, max_y - min_y].map(az::saturating_cast::<f32, u32>);
+
+    Some(vk::Scissor { offset, extent })
+}
 struct Texture {
     image: Arc<vk::Image>,
 
@@ -413,8 +510,7 @@ impl Render {
         surface: &crate::render_device::RenderSurface,
     ) -> anyhow::Result<()> {
         let framebuffers: anyhow::Result<Vec<_>> = surface
-            .swapchain_images()
-            .iter()
+            .render_targets()
             .map(|image| -> anyhow::Result<_> {
                 let fb = vk::Framebuffer::new(
                     self.render_pass.clone(),
@@ -562,18 +658,17 @@ impl Render {
 
         for clipped in tesselated_geom {
             if let egui::epaint::Primitive::Mesh(mesh) = &clipped.primitive {
-                // *Technically* it wants a float scissor rect. But.. oh well
-                let offset = clipped.clip_rect.left_top();
-                let offset = [
-                    (offset.x.max(0.0) * scale_factor) as u32,
-                    (offset.y.max(0.0) * scale_factor) as u32,
-                ];
-
-                let extent = clipped.clip_rect.size() * scale_factor;
-                let extent = [extent.x as u32, extent.y as u32];
+                let Some(scissor) =
+                    clip_rect_to_scissor(clipped.clip_rect, scale_factor, framebuffer.extent())
+                else {
+                    // Entirely clipped away, nothing to draw. Still need to skip over its data!
+                    start_index_buffer_offset += mesh.indices.len();
+                    start_vertex_buffer_offset += mesh.vertices.len();
+                    continue;
+                };
 
                 command_buffer_builder
-                    .set_scissor(0, smallvec::smallvec![vk::Scissor { offset, extent }])?
+                    .set_scissor(0, smallvec::smallvec![scissor])?
                     //Maybe there's a better way than rebinding every draw.
                     //shaderSampledImageArrayDynamicIndexing perhaps?
                     .bind_descriptor_sets(
@@ -826,3 +921,60 @@ impl Render {
         Ok(command_buffer.build()?)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::clip_rect_to_scissor;
+    use egui_winit::egui;
+
+    const EXTENT: [u32; 2] = [800, 600];
+
+    fn scissor(rect: egui::Rect, scale_factor: f32) -> Option<([u32; 2], [u32; 2])> {
+        clip_rect_to_scissor(rect, scale_factor, EXTENT).map(|s| (s.offset, s.extent))
+    }
+    #[test]
+    fn within_bounds() {
+        let rect = egui::Rect::from_min_max(egui::pos2(10.0, 20.0), egui::pos2(110.0, 220.0));
+        assert_eq!(scissor(rect, 1.0), Some(([10, 20], [100, 200])));
+        // Scale factor applies to both offset and extent.
+        assert_eq!(scissor(rect, 2.0), Some(([20, 40], [200, 400])));
+    }
+    #[test]
+    fn rounds_outward() {
+        let rect = egui::Rect::from_min_max(egui::pos2(10.5, 20.5), egui::pos2(20.25, 30.75));
+        assert_eq!(scissor(rect, 1.0), Some(([10, 20], [11, 11])));
+    }
+    #[test]
+    fn negative_origin() {
+        let rect = egui::Rect::from_min_max(egui::pos2(-50.0, -10.0), egui::pos2(50.0, 10.0));
+        // Clipped to the origin, *not* shifted.
+        assert_eq!(scissor(rect, 1.0), Some(([0, 0], [50, 10])));
+    }
+    #[test]
+    fn larger_than_framebuffer() {
+        let rect = egui::Rect::from_min_max(egui::pos2(100.0, 100.0), egui::pos2(5000.0, 5000.0));
+        assert_eq!(scissor(rect, 1.0), Some(([100, 100], [700, 500])));
+
+        assert_eq!(scissor(egui::Rect::EVERYTHING, 1.0), Some(([0, 0], EXTENT)));
+    }
+    #[test]
+    fn zero_area() {
+        // Empty
+        let rect = egui::Rect::from_min_max(egui::pos2(10.0, 10.0), egui::pos2(10.0, 50.0));
+        assert_eq!(scissor(rect, 1.0), None);
+        // Inverted
+        assert_eq!(scissor(egui::Rect::NOTHING, 1.0), None);
+        // Entirely offscreen
+        let rect = egui::Rect::from_min_max(egui::pos2(900.0, 0.0), egui::pos2(1000.0, 50.0));
+        assert_eq!(scissor(rect, 1.0), None);
+        let rect = egui::Rect::from_min_max(egui::pos2(-100.0, -100.0), egui::pos2(-1.0, -1.0));
+        assert_eq!(scissor(rect, 1.0), None);
+    }
+    #[test]
+    fn nan() {
+        let rect = egui::Rect::from_min_max(egui::pos2(f32::NAN, 0.0), egui::pos2(50.0, 50.0));
+        assert_eq!(scissor(rect, 1.0), None);
+        let rect = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(50.0, 50.0));
+        assert_eq!(scissor(rect, f32::NAN), None);
+    }
+}
@@ -0,0 +1,122 @@
+//! # Errors
+//!
+//! Errors the user should hear about, rather than only the log. Subsystems report them where they're handled,
+//! and the UI shows them as toasts - or for fatal ones, a dialog offering to save recovery copies of every open
+//! document. Outcomes worth a word that aren't errors at all go the same way, see [`Report::info`].
+
+use crate::vulkano_prelude::*;
+
+/// How bad an error is for the user, which decides how it's shown.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    /// Nothing failed, the user is told how an operation went.
+    Info,
+    /// The operation failed, but nothing was lost. Trying again may work.
+    Recoverable,
+    /// The user's work may be lost, such as when a save fails.
+    DataLoss,
+    /// Rendering or editing can't continue, and fuzzpaint must be restarted.
+    Fatal,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub severity: Severity,
+    /// What failed, in the user's terms.
+    pub summary: String,
+    /// The full error chain.
+    pub detail: String,
+    /// What the user can do about it, if anything.
+    pub remedy: Option<String>,
+}
+impl Report {
+    #[must_use]
+    pub fn new(severity: Severity, summary: impl Into<String>, error: &anyhow::Error) -> Self {
+        Self {
+            severity,
+            summary: summary.into(),
+            detail: format!("{error:#}"),
+            remedy: None,
+        }
+    }
+    /// Tell the user how an operation went, with no error behind it.
+    #[must_use]
+    pub fn info(summary: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Info,
+            summary: summary.into(),
+            detail: String::new(),
+            remedy: None,
+        }
+    }
+    /// Classify an error from the device, with a remedy to match.
+    #[must_use]
+    pub fn gpu(summary: impl Into<String>, error: &anyhow::Error) -> Self {
+        let vulkan = error.chain().find_map(|cause| {
+            cause.downcast_ref::<vk::VulkanError>().or_else(|| {
+                match cause.downcast_ref::<vk::Validated<vk::VulkanError>>() {
+                    Some(vk::Validated::Error(error)) => Some(error),
+                    _ => None,
+                }
+            })
+        });
+        let (severity, remedy) = match vulkan {
+            Some(vk::VulkanError::DeviceLost) => (Severity::Fatal, "remedy-device-lost"),
+            Some(vk::VulkanError::OutOfDeviceMemory | vk::VulkanError::OutOfHostMemory) => {
+                (Severity::Recoverable, "remedy-out-of-memory")
+            }
+            // A validation error or unexpected failure is fuzzpaint's fault, not the user's.
+            _ => (Severity::Recoverable, "remedy-bug"),
+        };
+        Self::new(severity, summary, error).with_remedy(crate::i18n::tr!(remedy))
+    }
+    #[must_use]
+    pub fn with_remedy(self, remedy: impl Into<String>) -> Self {
+        Self {
+            remedy: Some(remedy.into()),
+            ..self
+        }
+    }
+    /// Log the report, and queue it to be shown by the UI.
+    pub fn send(self) {
+        match self.severity {
+            Severity::Info => tracing::info!("{}", self.summary),
+            Severity::Recoverable => tracing::warn!("{}: {}", self.summary, self.detail),
+            Severity::DataLoss | Severity::Fatal => {
+                tracing::error!("{}: {}", self.summary, self.detail);
+            }
+        }
+        PENDING.lock().push(self);
+        crate::global::wake::wake(crate::global::wake::Wake::Ui);
+    }
+}
+
+static PENDING: parking_lot::Mutex<Vec<Report>> = parking_lot::Mutex::new(Vec::new());
+
+/// Take every report sent since the last call, oldest first.
+#[must_use]
+pub fn take() -> Vec<Report> {
+    std::mem::take(&mut *PENDING.lock())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Report, Severity};
+    use crate::vulkano_prelude::*;
+    #[test]
+    fn gpu_classification() {
+        let lost = anyhow::Error::from(vk::VulkanError::DeviceLost).context("presenting");
+        assert_eq!(Report::gpu("lost", &lost).severity, Severity::Fatal);
+
+        let memory = anyhow::Error::from(vk::Validated::Error(vk::VulkanError::OutOfDeviceMemory));
+        assert_eq!(
+            Report::gpu("memory", &memory).severity,
+            Severity::Recoverable
+        );
+
+        let other = anyhow::anyhow!("something else");
+        let report = Report::gpu("other", &other);
+        assert_eq!(report.severity, Severity::Recoverable);
+        assert!(report.remedy.is_some());
+    }
+}
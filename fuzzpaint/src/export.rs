@@ -0,0 +1,814 @@
+//! # Export
+//!
+//! Writing the composited document out as a flat image. The render worker downloads the document's
+//! image and hands it to [`write`], which converts it from the renderer's linear, premultiplied
+//! half-floats into the chosen format.
+//!
+//! Layers are exported each to an image of their own, see [`LayerExportSettings`].
+//!
+//! Timelapses replay the document's history, writing a frame every few commands with a [`TimelapseEncoder`].
+//!
+//! Screenshots are instead of the viewport, exactly as the window shows it, see [`ScreenshotSettings`].
+
+use fuzzpaint_core::state::document::Metadata;
+use vulkano::half::f16;
+
+/// Longest `_vNNN` run of versions [`next_free_path`] will search before giving up and overwriting.
+const MAX_INCREMENT_SEARCH: usize = 10_000;
+
+#[derive(strum::AsRefStr, strum::EnumIter, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ExportFormat {
+    #[default]
+    #[strum(serialize = "PNG")]
+    Png,
+    /// Lossy, without transparency.
+    #[strum(serialize = "JPEG")]
+    Jpeg,
+}
+impl ExportFormat {
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+        }
+    }
+}
+
+/// Rendering the document at a multiple of its resolution for export. Its strokes, text, and gradients are drawn
+/// afresh at the larger size, rather than the document's own image being enlarged, so edges stay crisp when the
+/// export is scaled up.
+#[derive(strum::AsRefStr, strum::EnumIter, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Supersample {
+    #[default]
+    Off,
+    #[strum(serialize = "2×")]
+    X2,
+    #[strum(serialize = "4×")]
+    X4,
+}
+impl Supersample {
+    /// Multiplier on the document's resolution, along each side.
+    #[must_use]
+    pub fn factor(self) -> u32 {
+        match self {
+            Self::Off => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+        }
+    }
+}
+
+/// Everything needed to repeat an export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportSettings {
+    pub path: std::path::PathBuf,
+    pub format: ExportFormat,
+    /// Multiplier on the document's size. Always within [`Self::SCALE_RANGE`].
+    pub scale: f32,
+    /// Resolution the document is rendered at before being scaled to `scale`.
+    pub supersample: Supersample,
+    /// Whether repeated exports advance a `_v001` suffix rather than overwriting.
+    pub auto_increment: bool,
+}
+impl ExportSettings {
+    pub const SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.1..=4.0;
+    /// Settings for exporting again after these settings were used.
+    #[must_use]
+    pub fn next(&self) -> Self {
+        if self.auto_increment {
+            Self {
+                path: next_free_path(&self.path),
+                ..self.clone()
+            }
+        } else {
+            self.clone()
+        }
+    }
+}
+
+/// Advance the `_vNNN` version at the end of the path's file stem, keeping the digit count,
+/// or append `_v001` if there is none.
+#[must_use]
+pub fn increment_path(path: &std::path::Path) -> std::path::PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let versioned = stem.rsplit_once("_v").and_then(|(base, digits)| {
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let version = digits.parse::<u32>().ok()?.checked_add(1)?;
+        Some((base.to_owned(), version, digits.len()))
+    });
+    let (base, version, width) = versioned.unwrap_or((stem, 1, 3));
+
+    let mut name = format!("{base}_v{version:0width$}");
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+/// [`increment_path`] until a path is found that doesn't exist yet.
+#[must_use]
+pub fn next_free_path(path: &std::path::Path) -> std::path::PathBuf {
+    let mut next = increment_path(path);
+    for _ in 0..MAX_INCREMENT_SEARCH {
+        if !next.exists() {
+            break;
+        }
+        next = increment_path(&next);
+    }
+    next
+}
+
+/// Linear to sRGB transfer function, for a value in `[0, 1]`.
+fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn quantize(value: f32) -> u8 {
+    // Clamped, so the cast is exact.
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+/// Convert a linear, premultiplied texel into sRGB with straight alpha.
+fn to_srgb8(texel: [f16; 4]) -> [u8; 4] {
+    let [r, g, b, a] = texel.map(f32::from);
+    let alpha = a.clamp(0.0, 1.0);
+    let unpremultiply = |c: f32| {
+        if alpha > 0.0 {
+            linear_to_srgb((c / alpha).clamp(0.0, 1.0))
+        } else {
+            0.0
+        }
+    };
+    [
+        quantize(unpremultiply(r)),
+        quantize(unpremultiply(g)),
+        quantize(unpremultiply(b)),
+        quantize(alpha),
+    ]
+}
+
+/// Write a PNG, with the document's metadata as text chunks and its resolution as the physical pixel size.
+fn write_png(
+    image: &image::RgbaImage,
+    path: &std::path::Path,
+    metadata: &Metadata,
+    dpi: f32,
+) -> anyhow::Result<()> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // PNG measures in pixels per meter.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let per_meter = (dpi / 0.0254).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: per_meter,
+        yppu: per_meter,
+        unit: png::Unit::Meter,
+    }));
+    // Keywords as registered for PNG, UTF-8 text.
+    for (keyword, text) in [
+        ("Title", metadata.title.as_str()),
+        ("Author", metadata.author.as_str()),
+        ("Description", metadata.description.as_str()),
+        ("Software", "fuzzpaint"),
+    ] {
+        if !text.is_empty() {
+            encoder.add_itxt_chunk(keyword.to_owned(), text.to_owned())?;
+        }
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+    Ok(())
+}
+/// Write a JPEG, with the document's resolution as its pixel density. JPEG has no standard place for the
+/// other metadata.
+fn write_jpeg(image: image::RgbaImage, path: &std::path::Path, dpi: f32) -> anyhow::Result<()> {
+    let image = image::DynamicImage::ImageRgba8(image).to_rgb8();
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new(file);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let dpi = dpi.round().clamp(1.0, f32::from(u16::MAX)) as u16;
+    encoder.set_pixel_density(image::codecs::jpeg::PixelDensity::dpi(dpi));
+    encoder.encode_image(&image)?;
+    Ok(())
+}
+
+/// Join the downloaded images of `across[0]` by `across[1]` square tiles of the document, each `dimension`
+/// texels, into one of `total` texels wide and tall. Tiles are in order of the regions they cover, rows of
+/// increasing y each of increasing x. As with the document's own image, the greatest y of each tile is its first
+/// row, so the last row of tiles comes first.
+///
+/// Tiles overlap by `margin` texels on every side, which are left out, so that each tile's inside is drawn as
+/// though the tiles around it were there. Insides past `total` are left out too, so one tile without a margin is
+/// cropped to its least corner.
+pub fn stitch(
+    tiles: &[Vec<[f16; 4]>],
+    dimension: u32,
+    across: [u32; 2],
+    margin: u32,
+    total: [u32; 2],
+) -> anyhow::Result<Vec<[f16; 4]>> {
+    let dimension = usize::try_from(dimension)?;
+    let [across_x, across_y] = [usize::try_from(across[0])?, usize::try_from(across[1])?];
+    let margin = usize::try_from(margin)?;
+    let [width, height] = [usize::try_from(total[0])?, usize::try_from(total[1])?];
+    if tiles.len() != across_x * across_y {
+        anyhow::bail!(
+            "expected {} tiles, got {}",
+            across_x * across_y,
+            tiles.len()
+        );
+    }
+    if tiles.iter().any(|tile| tile.len() != dimension * dimension) {
+        anyhow::bail!("expected tiles of {dimension}px");
+    }
+    let inner = dimension.saturating_sub(2 * margin);
+    if inner * across_x < width || inner * across_y < height {
+        anyhow::bail!(
+            "{across_x}x{across_y} tiles of {inner}px inside don't cover {width}x{height}px"
+        );
+    }
+    let mut texels = Vec::with_capacity(width * height);
+    // Rows from the greatest y, counted from the least.
+    for y in (0..height).rev() {
+        let tile_row = &tiles[y / inner * across_x..][..across_x];
+        // Of the tile, which is also flipped.
+        let row = dimension - 1 - (y % inner + margin);
+        let mut x = 0;
+        while x < width {
+            let run = inner.min(width - x);
+            let start = row * dimension + margin;
+            texels.extend_from_slice(&tile_row[x / inner][start..start + run]);
+            x += run;
+        }
+    }
+    Ok(texels)
+}
+
+/// Encode the downloaded document image, `size` texels wide and tall, and write it according
+/// to `settings`, tagged with the document's `metadata` and `dpi`. Progress is reported per step, and nothing is
+/// written if it's cancelled before the last.
+///
+/// The image is expected to have been rendered at [`ExportSettings::supersample`] times the document's
+/// resolution, and is scaled from the document's size.
+pub fn write(
+    texels: &[[f16; 4]],
+    size: [u32; 2],
+    settings: &ExportSettings,
+    metadata: &Metadata,
+    dpi: f32,
+    progress: &fuzzpaint_core::progress::Progress,
+) -> anyhow::Result<()> {
+    let [width, height] = size;
+    let expected_len = usize::try_from(u64::from(width) * u64::from(height))?;
+    if texels.len() != expected_len {
+        anyhow::bail!(
+            "expected {expected_len} texels for a {width}x{height}px image, got {}",
+            texels.len()
+        );
+    }
+    // Convert, resize, encode.
+    progress.set_total(3);
+    let bytes = texels.iter().copied().flat_map(to_srgb8).collect();
+    // Unwrap ok - length checked above.
+    let mut image = image::RgbaImage::from_raw(width, height, bytes).unwrap();
+    progress.advance(1);
+    progress.check()?;
+
+    let scale = settings.scale.clamp(
+        *ExportSettings::SCALE_RANGE.start(),
+        *ExportSettings::SCALE_RANGE.end(),
+    );
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let scaled = size.map(|side| {
+        let document = side / settings.supersample.factor();
+        ((document as f32 * scale).round() as u32).max(1)
+    });
+    if scaled != size {
+        image = image::imageops::resize(
+            &image,
+            scaled[0],
+            scaled[1],
+            image::imageops::FilterType::Lanczos3,
+        );
+    }
+    progress.advance(1);
+    progress.check()?;
+
+    // Scaling up keeps the physical size, packing in more pixels.
+    let dpi = dpi * scale;
+    match settings.format {
+        ExportFormat::Png => write_png(&image, &settings.path, metadata, dpi)?,
+        ExportFormat::Jpeg => write_jpeg(image, &settings.path, dpi)?,
+    }
+    progress.advance(1);
+    Ok(())
+}
+
+/// Everything needed to export each top-level layer of the document to a PNG of its own, named after it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayerExportSettings {
+    /// Folder the images are written into, overwriting any of the same names.
+    pub dir: std::path::PathBuf,
+    /// Crop each to the pixels it covers, listing where each was cropped from in [`MANIFEST_NAME`]. Layers
+    /// covering nothing are left out.
+    pub trim: bool,
+    /// Also write the whole document, as [`COMPOSITE_NAME`].
+    pub composite: bool,
+}
+/// File stem of the whole document in a layer export. No layer's file takes it.
+pub const COMPOSITE_NAME: &str = "composite";
+/// File listing where each trimmed layer of an export lies within the document.
+pub const MANIFEST_NAME: &str = "layers.toml";
+
+/// File stems for layers of these names, each unique and safe to write on any platform. Case is ignored when
+/// comparing, as some filesystems do.
+#[must_use]
+pub fn layer_file_stems<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut taken = hashbrown::HashSet::new();
+    taken.insert(COMPOSITE_NAME.to_owned());
+    names
+        .into_iter()
+        .map(|name| {
+            let sanitized: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_control()
+                        || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+                    {
+                        '_'
+                    } else {
+                        c
+                    }
+                })
+                .collect();
+            // Windows drops trailing dots and spaces.
+            let sanitized = sanitized.trim().trim_end_matches('.');
+            let base = if sanitized.is_empty() {
+                "layer"
+            } else {
+                sanitized
+            };
+            let mut stem = base.to_owned();
+            let mut count = 1;
+            while !taken.insert(stem.to_lowercase()) {
+                count += 1;
+                stem = format!("{base}_{count}");
+            }
+            stem
+        })
+        .collect()
+}
+
+/// The smallest rectangle, `[x, y, width, height]`, holding every texel that isn't fully transparent, or None
+/// if all are.
+fn content_bounds(image: &image::RgbaImage) -> Option<[u32; 4]> {
+    let mut bounds: Option<[u32; 4]> = None;
+    for (x, y, texel) in image.enumerate_pixels() {
+        if texel.0[3] == 0 {
+            continue;
+        }
+        bounds = Some(match bounds {
+            None => [x, y, x, y],
+            Some([min_x, min_y, max_x, max_y]) => {
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            }
+        });
+    }
+    bounds.map(|[min_x, min_y, max_x, max_y]| [min_x, min_y, max_x - min_x + 1, max_y - min_y + 1])
+}
+
+/// Where a trimmed layer lies within the document, in pixels from its top left.
+#[derive(serde::Serialize)]
+struct Placement<'a> {
+    file: String,
+    name: &'a str,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+#[derive(serde::Serialize)]
+struct Manifest<'a> {
+    #[serde(rename = "layer")]
+    layers: Vec<Placement<'a>>,
+}
+
+/// Write the downloaded images of each layer, named, and of the whole document if given, each `size` texels
+/// wide and tall, according to `settings`, tagged with the document's `metadata` and `dpi`. Progress is
+/// reported per image.
+pub fn write_layers(
+    layers: &[(String, Vec<[f16; 4]>)],
+    composite: Option<&[[f16; 4]]>,
+    size: [u32; 2],
+    settings: &LayerExportSettings,
+    metadata: &Metadata,
+    dpi: f32,
+    progress: &fuzzpaint_core::progress::Progress,
+) -> anyhow::Result<()> {
+    let [width, height] = size;
+    let expected_len = usize::try_from(u64::from(width) * u64::from(height))?;
+    let to_image = |texels: &[[f16; 4]]| -> anyhow::Result<image::RgbaImage> {
+        if texels.len() != expected_len {
+            anyhow::bail!(
+                "expected {expected_len} texels for a {width}x{height}px image, got {}",
+                texels.len()
+            );
+        }
+        let bytes = texels.iter().copied().flat_map(to_srgb8).collect();
+        // Unwrap ok - length checked above.
+        Ok(image::RgbaImage::from_raw(width, height, bytes).unwrap())
+    };
+    std::fs::create_dir_all(&settings.dir)?;
+    progress.set_total(layers.len() + usize::from(composite.is_some()));
+
+    let stems = layer_file_stems(layers.iter().map(|(name, _)| name.as_str()));
+    let mut placements = Vec::new();
+    for ((name, texels), stem) in layers.iter().zip(stems) {
+        progress.check()?;
+        let image = to_image(texels)?;
+        let file = format!("{stem}.png");
+        let path = settings.dir.join(&file);
+        if settings.trim {
+            if let Some([x, y, width, height]) = content_bounds(&image) {
+                let trimmed = image::imageops::crop_imm(&image, x, y, width, height).to_image();
+                write_png(&trimmed, &path, metadata, dpi)?;
+                placements.push(Placement {
+                    file,
+                    name,
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+        } else {
+            write_png(&image, &path, metadata, dpi)?;
+        }
+        progress.advance(1);
+    }
+    if let Some(composite) = composite {
+        progress.check()?;
+        let path = settings.dir.join(format!("{COMPOSITE_NAME}.png"));
+        write_png(&to_image(composite)?, &path, metadata, dpi)?;
+        progress.advance(1);
+    }
+    if settings.trim {
+        let manifest = toml::to_string(&Manifest { layers: placements })?;
+        std::fs::write(settings.dir.join(MANIFEST_NAME), manifest)?;
+    }
+    Ok(())
+}
+
+/// Where a screenshot is written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScreenshotTarget {
+    Clipboard,
+    /// A PNG file.
+    File(std::path::PathBuf),
+}
+/// A capture of what the window shows, drawn again into an image apart from the swapchain rather than read
+/// from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenshotSettings {
+    pub target: ScreenshotTarget,
+    /// Include the UI, capturing the whole window. Otherwise, only the viewport with its gizmos is captured.
+    pub ui: bool,
+}
+/// Convert a downloaded texel of the window's surface, sRGB BGRA, into straight RGBA.
+/// Color is premultiplied where the surface is transparent.
+fn surface_to_rgba8([b, g, r, a]: [u8; 4]) -> [u8; 4] {
+    let unpremultiply = |c: u8| {
+        if a == 0 {
+            0
+        } else {
+            // Fits, as c <= a when premultiplied.
+            u8::try_from(u16::from(c) * 255 / u16::from(a)).unwrap_or(u8::MAX)
+        }
+    };
+    [unpremultiply(r), unpremultiply(g), unpremultiply(b), a]
+}
+/// Write a screenshot downloaded from the window's surface, `extent` texels of sRGB BGRA, according to
+/// `settings`.
+pub fn write_screenshot(
+    texels: &[u8],
+    extent: [u32; 2],
+    settings: &ScreenshotSettings,
+) -> anyhow::Result<()> {
+    let [width, height] = extent;
+    let expected_len = usize::try_from(u64::from(width) * u64::from(height) * 4)?;
+    if texels.len() != expected_len {
+        anyhow::bail!(
+            "expected {expected_len} bytes for a {width}x{height} screenshot, got {}",
+            texels.len()
+        );
+    }
+    let bytes: Vec<u8> = texels
+        .chunks_exact(4)
+        // Unwrap ok - chunks are exactly four.
+        .flat_map(|texel| surface_to_rgba8(texel.try_into().unwrap()))
+        .collect();
+    match &settings.target {
+        ScreenshotTarget::Clipboard => {
+            arboard::Clipboard::new()?.set_image(arboard::ImageData {
+                width: usize::try_from(width)?,
+                height: usize::try_from(height)?,
+                bytes: bytes.into(),
+            })?;
+        }
+        ScreenshotTarget::File(path) => {
+            // Unwrap ok - length checked above.
+            image::RgbaImage::from_raw(width, height, bytes)
+                .unwrap()
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+    }
+    Ok(())
+}
+
+/// How timelapse frames are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimelapseOutput {
+    /// Numbered PNGs, `frame_00000.png` onwards, in the directory at the settings' path.
+    ImageSequence,
+    /// An H.264 video at the settings' path, encoded by an `ffmpeg` found on the `PATH`.
+    Mp4 { fps: u32 },
+}
+/// Everything needed to replay a document's history into a timelapse.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimelapseSettings {
+    pub path: std::path::PathBuf,
+    pub output: TimelapseOutput,
+    /// Commands replayed between each frame.
+    pub interval: std::num::NonZeroUsize,
+}
+
+/// Convert a linear, premultiplied texel into opaque sRGB, as if drawn over white.
+/// Videos have no transparency, and a blank canvas is expected to look like paper.
+fn over_white(texel: [f16; 4]) -> [u8; 3] {
+    let [r, g, b, a] = texel.map(f32::from);
+    let under = 1.0 - a.clamp(0.0, 1.0);
+    [r, g, b].map(|c| quantize(linear_to_srgb((c + under).clamp(0.0, 1.0))))
+}
+
+enum Encoder {
+    Images { dir: std::path::PathBuf },
+    Ffmpeg(std::process::Child),
+}
+/// Writes out the frames of a timelapse as they're rendered, see [`TimelapseSettings`].
+pub struct TimelapseEncoder {
+    encoder: Encoder,
+    size: [u32; 2],
+    frames: usize,
+}
+impl TimelapseEncoder {
+    /// Prepare to write frames `size` texels wide and tall. For videos, this starts the encoder, and each side
+    /// must be even.
+    pub fn new(settings: &TimelapseSettings, size: [u32; 2]) -> anyhow::Result<Self> {
+        let encoder = match settings.output {
+            TimelapseOutput::ImageSequence => {
+                std::fs::create_dir_all(&settings.path)?;
+                Encoder::Images {
+                    dir: settings.path.clone(),
+                }
+            }
+            TimelapseOutput::Mp4 { fps } => {
+                if size.iter().any(|side| side % 2 != 0) {
+                    anyhow::bail!(
+                        "video frames must be of even size, got {}x{}",
+                        size[0],
+                        size[1]
+                    );
+                }
+                let video_size = format!("{}x{}", size[0], size[1]);
+                let child = std::process::Command::new("ffmpeg")
+                    // Overwrite, the user already chose this path.
+                    .arg("-y")
+                    .args(["-f", "rawvideo", "-pixel_format", "rgb24"])
+                    .args(["-video_size", &video_size])
+                    .args(["-framerate", &fps.max(1).to_string()])
+                    .args(["-i", "-"])
+                    // Most widely playable.
+                    .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+                    .arg(&settings.path)
+                    .stdin(std::process::Stdio::piped())
+                    // Not read, and could fill up and stall the encoder if piped.
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .spawn()
+                    .map_err(|err| match err.kind() {
+                        std::io::ErrorKind::NotFound => {
+                            anyhow::anyhow!("ffmpeg must be installed to export videos")
+                        }
+                        _ => err.into(),
+                    })?;
+                Encoder::Ffmpeg(child)
+            }
+        };
+        Ok(Self {
+            encoder,
+            size,
+            frames: 0,
+        })
+    }
+    /// Write the next frame, a downloaded document image.
+    pub fn push(&mut self, texels: &[[f16; 4]]) -> anyhow::Result<()> {
+        match &mut self.encoder {
+            Encoder::Images { dir } => write(
+                texels,
+                self.size,
+                &ExportSettings {
+                    path: dir.join(format!("frame_{:05}.png", self.frames)),
+                    format: ExportFormat::Png,
+                    scale: 1.0,
+                    supersample: Supersample::Off,
+                    auto_increment: false,
+                },
+                &Metadata::default(),
+                fuzzpaint_core::state::document::Viewport::default().dpi(),
+                // The timelapse as a whole is cancelled between frames.
+                &fuzzpaint_core::progress::Progress::detached(),
+            )?,
+            Encoder::Ffmpeg(child) => {
+                use std::io::Write;
+                let expected_len =
+                    usize::try_from(u64::from(self.size[0]) * u64::from(self.size[1]))?;
+                if texels.len() != expected_len {
+                    anyhow::bail!("expected {expected_len} texels, got {}", texels.len());
+                }
+                let bytes: Vec<u8> = texels.iter().copied().flat_map(over_white).collect();
+                // Unwrap ok - piped when spawned, only taken by `finish`.
+                child.stdin.as_mut().unwrap().write_all(&bytes)?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+    /// Finish writing, returning how many frames were written. For videos, this waits for the encoder to exit.
+    pub fn finish(self) -> anyhow::Result<usize> {
+        if let Encoder::Ffmpeg(mut child) = self.encoder {
+            // Closing stdin ends the stream.
+            drop(child.stdin.take());
+            let status = child.wait()?;
+            if !status.success() {
+                anyhow::bail!("ffmpeg failed with {status}");
+            }
+        }
+        Ok(self.frames)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::increment_path;
+    use std::path::Path;
+    #[test]
+    fn increment() {
+        assert_eq!(
+            increment_path(Path::new("dir/painting.png")),
+            Path::new("dir/painting_v001.png")
+        );
+        assert_eq!(
+            increment_path(Path::new("painting_v003.png")),
+            Path::new("painting_v004.png")
+        );
+        // Keeps the width, grows past it when needed.
+        assert_eq!(
+            increment_path(Path::new("painting_v0099.png")),
+            Path::new("painting_v0100.png")
+        );
+        assert_eq!(
+            increment_path(Path::new("painting_v999.png")),
+            Path::new("painting_v1000.png")
+        );
+        // Not a version, just a name with a `v` in it.
+        assert_eq!(
+            increment_path(Path::new("my_vacation.jpg")),
+            Path::new("my_vacation_v001.jpg")
+        );
+    }
+    #[test]
+    fn srgb_conversion() {
+        use vulkano::half::f16;
+        let texel = |c: f32, a: f32| {
+            [
+                f16::from_f32(c * a),
+                f16::from_f32(c * a),
+                f16::from_f32(c * a),
+                f16::from_f32(a),
+            ]
+        };
+        assert_eq!(super::to_srgb8(texel(1.0, 1.0)), [255; 4]);
+        assert_eq!(super::to_srgb8(texel(0.0, 0.0)), [0; 4]);
+        // Half-coverage white stays white once unpremultiplied.
+        assert_eq!(super::to_srgb8(texel(1.0, 0.5)), [255, 255, 255, 128]);
+
+        // Nothing drawn is paper-white, opaque black stays black.
+        assert_eq!(super::over_white(texel(0.0, 0.0)), [255; 3]);
+        assert_eq!(super::over_white(texel(0.0, 1.0)), [0; 3]);
+    }
+    #[test]
+    fn stitch() {
+        use vulkano::half::f16;
+        // Two by two tiles of two by two texels, each filled with its index.
+        let tiles: Vec<_> = (0..4u8)
+            .map(|idx| vec![[f16::from_f32(f32::from(idx)); 4]; 4])
+            .collect();
+        let texels = super::stitch(&tiles, 2, [2, 2], 0, [4, 4]).unwrap();
+        let at = |x: usize, y: usize| texels[y * 4 + x][0].to_f32();
+        assert_eq!(texels.len(), 16);
+        // Greatest y first.
+        assert_eq!(
+            [at(0, 0), at(3, 0), at(0, 3), at(3, 3)],
+            [2.0, 3.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            [at(1, 1), at(2, 1), at(1, 2), at(2, 2)],
+            [2.0, 3.0, 0.0, 1.0]
+        );
+
+        assert!(super::stitch(&tiles[..3], 2, [2, 2], 0, [4, 4]).is_err());
+        assert!(super::stitch(&tiles, 3, [2, 2], 0, [4, 4]).is_err());
+        assert!(super::stitch(&tiles, 2, [2, 2], 0, [4, 5]).is_err());
+        // Wider than tall, four tiles in a row.
+        let texels = super::stitch(&tiles, 2, [4, 1], 0, [7, 2]).unwrap();
+        let at = |x: usize, y: usize| texels[y * 7 + x][0].to_f32();
+        assert_eq!(texels.len(), 14);
+        assert_eq!(
+            [at(0, 0), at(2, 1), at(5, 0), at(6, 1)],
+            [0.0, 1.0, 2.0, 3.0]
+        );
+
+        // Four by four texels, each its tile's index then its own, with one texel of margin. The two by two
+        // insides cover three texels square, only part of the last row and column of tiles.
+        let tiles: Vec<_> = (0..4u8)
+            .map(|idx| {
+                (0..16u8)
+                    .map(|texel| [f16::from_f32(f32::from(idx * 16 + texel)); 4])
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let texels = super::stitch(&tiles, 4, [2, 2], 1, [3, 3]).unwrap();
+        let at = |x: usize, y: usize| texels[y * 3 + x][0].to_f32();
+        assert_eq!(texels.len(), 9);
+        // Greatest y, the least of the second row of tiles, which is the third row of each.
+        assert_eq!(
+            [at(0, 0), at(1, 0), at(2, 0)],
+            [32.0 + 9.0, 32.0 + 10.0, 48.0 + 9.0]
+        );
+        // Least y, the least of the first row of tiles.
+        assert_eq!([at(0, 2), at(1, 2), at(2, 2)], [9.0, 10.0, 16.0 + 9.0]);
+        // Second row of each tile.
+        assert_eq!([at(0, 1), at(2, 1)], [5.0, 16.0 + 5.0]);
+
+        // A lone tile without a margin is cropped to its least corner, the last rows and first columns.
+        let texels = super::stitch(&tiles[..1], 4, [1, 1], 0, [3, 2]).unwrap();
+        let values: Vec<_> = texels.iter().map(|texel| texel[0].to_f32()).collect();
+        assert_eq!(values, [8.0, 9.0, 10.0, 12.0, 13.0, 14.0]);
+    }
+    #[test]
+    fn layer_file_stems() {
+        assert_eq!(
+            super::layer_file_stems(["Sky", "sky", "a/b: c?", "  ", "Composite", "Trail..."]),
+            ["Sky", "sky_2", "a_b_ c_", "layer", "Composite_2", "Trail"]
+        );
+    }
+    #[test]
+    fn content_bounds() {
+        let mut image = image::RgbaImage::new(8, 8);
+        assert_eq!(super::content_bounds(&image), None);
+        image.put_pixel(2, 3, image::Rgba([0, 0, 0, 1]));
+        assert_eq!(super::content_bounds(&image), Some([2, 3, 1, 1]));
+        image.put_pixel(6, 1, image::Rgba([255, 0, 0, 255]));
+        assert_eq!(super::content_bounds(&image), Some([2, 1, 5, 3]));
+    }
+    #[test]
+    fn surface_conversion() {
+        // Swizzled from BGRA.
+        assert_eq!(
+            super::surface_to_rgba8([10, 20, 30, 255]),
+            [30, 20, 10, 255]
+        );
+        // Half-coverage white stays white once unpremultiplied.
+        assert_eq!(
+            super::surface_to_rgba8([128, 128, 128, 128]),
+            [255, 255, 255, 128]
+        );
+        assert_eq!(super::surface_to_rgba8([0; 4]), [0; 4]);
+    }
+}
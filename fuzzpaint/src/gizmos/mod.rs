@@ -31,6 +31,9 @@ pub enum TextureMode {
     },
     /// Screenspace ant-trail effect.
     AntTrail,
+    /// The composited document as shown beneath, magnified `zoom` times about the gizmo's origin.
+    /// Not drawn if the renderer has no document image.
+    Document { zoom: f32 },
 }
 impl TextureMode {
     #[must_use]
@@ -71,6 +74,7 @@ impl Visual {
 }
 
 /// How can a gizmo be interacted with by the mouse?
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum GizmoInteraction {
     None,
     /// Can be dragged, and arbitrarily constrained.
@@ -138,8 +142,42 @@ pub trait MutableGizmoVisitor<T> {
     fn end_collection_mut(&mut self, gizmo: &mut Collection) -> ControlFlow<T>;
 }
 
+/// Identifies a gizmo in the [`GizmoEvent`]s of its interactions.
+pub type ID = crate::FuzzID<Gizmo>;
+
+/// Something the user did to a gizmo, according to its [`GizmoInteraction`]. Reported once the
+/// interaction finishes.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GizmoEvent {
+    /// Clicked without dragging.
+    Opened,
+    /// Dragged, by this much in document space from where it was grabbed.
+    Moved { delta: ultraviolet::Vec2 },
+    /// Turned about its origin by this many radians, in its local space.
+    Rotated { delta: f32 },
+}
+
+/// Everyone waiting on [`GizmoEvent`]s, see [`listen`].
+static LISTENERS: parking_lot::Mutex<Vec<crossbeam::channel::Sender<(ID, GizmoEvent)>>> =
+    parking_lot::const_mutex(Vec::new());
+/// Receive the events of every gizmo with an [`ID`], from now until the receiver is dropped.
+#[must_use]
+pub fn listen() -> crossbeam::channel::Receiver<(ID, GizmoEvent)> {
+    let (send, recv) = crossbeam::channel::unbounded();
+    LISTENERS.lock().push(send);
+    recv
+}
+/// Deliver an event to every listener, forgetting those that have hung up.
+pub fn emit(id: ID, event: GizmoEvent) {
+    LISTENERS
+        .lock()
+        .retain(|listener| listener.send((id, event)).is_ok());
+}
+
 pub struct Gizmo {
     pub visual: Visual,
+    /// If set, interactions with this gizmo are reported as [`GizmoEvent`]s.
+    pub id: Option<ID>,
 
     pub interaction: GizmoInteraction,
     pub hit_shape: GizmoShape,
@@ -153,6 +191,7 @@ impl Default for Gizmo {
     fn default() -> Self {
         Self {
             visual: Visual::empty(),
+            id: None,
             hit_shape: GizmoShape::None,
             grab_cursor: CursorOrInvisible::default(),
             hover_cursor: CursorOrInvisible::default(),
@@ -27,12 +27,55 @@ pub struct WideLineVertex {
     /// wide-line, where V increases from zero to one from "right" to "left" (relative to the line's forward vector)
     #[format(R32_SFLOAT)]
     pub tex_coord: f32,
-    /// Diameter of the line, in the same unit as `pos`
-    /// TODO: would be nice to have a separate coordinate space for this :V
+    /// Diameter of the line, in the unit of the gizmo's [`width_pinning`](super::transform::Transform::width_pinning).
     #[format(R32_SFLOAT)]
     pub width: f32,
 }
 
+/// The composited document image, for [`super::TextureMode::Document`].
+pub struct DocumentImage {
+    pub view: Arc<vk::ImageView>,
+    /// Document-space position of the image's top left corner.
+    pub origin: [f32; 2],
+    /// Document-space size of the image's sides.
+    pub size: f32,
+}
+impl DocumentImage {
+    /// Texture coordinate of a document point.
+    fn uv_of(&self, point: cgmath::Point2<f32>) -> [f32; 2] {
+        [
+            (point.x - self.origin[0]) / self.size,
+            1.0 - (point.y - self.origin[1]) / self.size,
+        ]
+    }
+    /// Rows of the affine map from a viewport pixel to the texture coordinate showing it, magnified
+    /// `zoom` times about the viewport point `origin`.
+    fn uv_rows(
+        &self,
+        document_transform: &crate::view_transform::ViewTransform,
+        origin: cgmath::Point2<f32>,
+        zoom: f32,
+    ) -> Option<[[f32; 4]; 2]> {
+        // Sample the origin and a pixel either way, then extend. Every step is affine.
+        let uv_at = |dx: f32, dy: f32| -> Option<[f32; 2]> {
+            let viewport = cgmath::Point2 {
+                x: origin.x + dx / zoom,
+                y: origin.y + dy / zoom,
+            };
+            Some(self.uv_of(document_transform.unproject(viewport).ok()?))
+        };
+        let center = uv_at(0.0, 0.0)?;
+        let right = uv_at(1.0, 0.0)?;
+        let down = uv_at(0.0, 1.0)?;
+        let row = |axis: usize| {
+            let dx = right[axis] - center[axis];
+            let dy = down[axis] - center[axis];
+            [dx, dy, center[axis] - dx * origin.x - dy * origin.y, 0.0]
+        };
+        Some([row(0), row(1)])
+    }
+}
+
 #[derive(PartialEq, Eq)]
 enum VertexBuffer {
     WideLines(vk::Subbuffer<[WideLineVertex]>),
@@ -50,6 +93,7 @@ mod shaders {
         Solid,
         Textured,
         AntTrail,
+        Document,
     }
 
     pub fn processing_of(
@@ -65,6 +109,7 @@ mod shaders {
             TextureMode::AntTrail => FragmentProcessing::AntTrail,
             TextureMode::Solid(..) => FragmentProcessing::Solid,
             TextureMode::Texture { .. } => FragmentProcessing::Textured,
+            TextureMode::Document { .. } => FragmentProcessing::Document,
         };
 
         Some((vertex, fragment))
@@ -76,6 +121,18 @@ mod shaders {
         pub transform: [[f32; 4]; 4],
         /// The color the whole object is multiplied by.
         pub color: [f32; 4],
+        /// Factor on wide line widths, from their pinned unit to the transform's. See
+        /// [`super::super::transform::Transform::width_scale`].
+        pub width_scale: f32,
+        /// Keeps [`DocumentPushConstants`] aligned.
+        pub pad: [f32; 3],
+    }
+    /// Follows [`PushConstants`], for [`FragmentProcessing::Document`].
+    #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct DocumentPushConstants {
+        /// Rows of the affine map from fragment coordinate to texture coordinate. W unused.
+        pub uv_rows: [[f32; 4]; 2],
     }
     pub mod vertex {
         vulkano_shaders::shader! {
@@ -121,6 +178,37 @@ mod shaders {
             "#
         }
     }
+    pub mod fragment_document {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r#"#version 460
+
+            layout(std430, push_constant) uniform Push {
+                layout(offset = 96) vec4 uv_x;
+                vec4 uv_y;
+            };
+            layout(set = 0, binding = 0) uniform sampler2D document;
+
+            layout(location = 0) in vec4 inColor;
+            layout(location = 1) in vec2 _;
+
+            layout(location = 0) out vec4 outColor;
+
+            // Shows through transparent parts, so the gizmo reads as solid.
+            const vec3 BACKDROP = vec3(0.2);
+
+            void main() {
+                vec3 frag = vec3(gl_FragCoord.xy, 1.0);
+                vec2 uv = vec2(dot(uv_x.xyz, frag), dot(uv_y.xyz, frag));
+                // Premultiplied. Nothing was drawn outside the image.
+                vec4 col = all(greaterThanEqual(uv, vec2(0.0))) && all(lessThanEqual(uv, vec2(1.0)))
+                    ? texture(document, uv)
+                    : vec4(0.0);
+                outColor = vec4(col.rgb + BACKDROP * (1.0 - col.a), 1.0) * inColor;
+            }
+            "#
+        }
+    }
     pub mod fragment_untextured {
         vulkano_shaders::shader! {
             ty: "fragment",
@@ -179,6 +267,7 @@ mod shaders {
                 layout(std430, push_constant) uniform Push {
                     mat4 transform;
                     vec4 gizmo_color;
+                    float width_scale;
                 };
     
                 layout(location = 0) in vec2 pos;
@@ -186,7 +275,7 @@ mod shaders {
                 // Polyline has a single-dimension UV, or just U (that would be confusing tho lol)
                 // Expands into UV in the widening geometry shader
                 layout(location = 2) in float tex_coord;
-                // Width, in transform units.
+                // Width, in pinned units, see `width_scale`.
                 layout(location = 3) in float width;
     
                 layout(location = 0) out vec4 out_color;
@@ -196,7 +285,7 @@ mod shaders {
                 void main() {
                     out_color = color * gizmo_color;
                     out_texcoord = tex_coord;
-                    out_width = width;
+                    out_width = width * width_scale;
 
                     gl_Position = transform * vec4(pos, 0.0, 1.0);
                 }
@@ -330,6 +419,8 @@ pub struct Renderer {
     triangulated_shapes: vk::Subbuffer<[GizmoVertex]>,
     triangulated_square: vk::Subbuffer<[GizmoVertex]>,
     triangulated_circle: vk::Subbuffer<[GizmoVertex]>,
+    /// Magnifying shows pixels, like the document view does.
+    document_sampler: Arc<vk::Sampler>,
 }
 impl Renderer {
     const CIRCLE_RES: usize = 32;
@@ -529,7 +620,10 @@ impl Renderer {
                         vk::PrimitiveTopology::LineStripWithAdjacency
                     }
                 };
-                let texture_descriptor = if fragment == shaders::FragmentProcessing::Textured {
+                let texture_descriptor = if matches!(
+                    fragment,
+                    shaders::FragmentProcessing::Textured | shaders::FragmentProcessing::Document
+                ) {
                     Some(vk::DescriptorSetLayout::new(
                         device.clone(),
                         vk::DescriptorSetLayoutCreateInfo {
@@ -560,6 +654,14 @@ impl Renderer {
                         Some(shaders::thick_polyline::geom::load(device.clone())?),
                     ),
                 };
+                let document_range =
+                    (fragment == shaders::FragmentProcessing::Document).then(|| {
+                        vk::PushConstantRange {
+                            offset: std::mem::size_of::<shaders::PushConstants>() as u32,
+                            stages: vk::ShaderStages::FRAGMENT,
+                            size: std::mem::size_of::<shaders::DocumentPushConstants>() as u32,
+                        }
+                    });
                 let fragment = match fragment {
                     shaders::FragmentProcessing::AntTrail => {
                         shaders::fragment_ant_trail::load(device.clone())?
@@ -570,15 +672,18 @@ impl Renderer {
                     shaders::FragmentProcessing::Textured => {
                         shaders::fragment_textured::load(device.clone())?
                     }
+                    shaders::FragmentProcessing::Document => {
+                        shaders::fragment_document::load(device.clone())?
+                    }
                 };
 
                 let push_constant_ranges = {
-                    let mut ranges = Vec::with_capacity(2);
-                    // Vertex always needs xform and color
+                    let mut ranges = Vec::with_capacity(3);
+                    // Vertex always needs xform and color, and wide lines their width scale.
                     let matrix_color_range = vk::PushConstantRange {
                         offset: 0,
                         stages: vk::ShaderStages::VERTEX,
-                        size: 4 * 4 * 4 + 4 * 4, //4x4 matrix of f32, + vec4 of f32
+                        size: std::mem::size_of::<shaders::PushConstants>() as u32,
                     };
                     ranges.push(matrix_color_range);
 
@@ -591,6 +696,7 @@ impl Renderer {
                         };
                         ranges.push(matrix_range);
                     }
+                    ranges.extend(document_range);
                     ranges
                 };
 
@@ -674,8 +780,18 @@ impl Renderer {
                 * <shaders::FragmentProcessing as strum::EnumCount>::COUNT,
         );
 
+        let document_sampler = vk::Sampler::new(
+            context.device().clone(),
+            vk::SamplerCreateInfo {
+                min_filter: vk::Filter::Linear,
+                mag_filter: vk::Filter::Nearest,
+                ..Default::default()
+            },
+        )?;
+
         Ok(Self {
             context,
+            document_sampler,
             lazy_pipelines: lazy_pipelines.into(),
             interned_widelines: hashbrown::HashMap::new().into(),
             triangulated_shapes: shapes,
@@ -684,12 +800,14 @@ impl Renderer {
         })
     }
     // Temporary api. passing around swapchain images and proj matrices like this feels dirty :P
+    /// `document` is sampled by [`super::TextureMode::Document`] gizmos, which are skipped without it.
     pub fn render_visit(
         &self,
         into_image: Arc<vk::Image>,
         image_size: [f32; 2],
         document_transform: crate::view_transform::ViewTransform,
         proj: cgmath::Matrix4<f32>,
+        document: Option<DocumentImage>,
     ) -> anyhow::Result<RenderVisitor<'_>> {
         let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
             self.context.allocators().command_buffer(),
@@ -724,6 +842,8 @@ impl Renderer {
             command_buffer,
             current_pipeline: None,
             proj,
+            document,
+            document_set: None,
         })
     }
 }
@@ -738,8 +858,33 @@ pub struct RenderVisitor<'a> {
     // would be nice to use a big buffer and just cursor around it with first_vertex, todo!
     // current_vertex_buffer: Option<VertexBuffer>,
     proj: cgmath::Matrix4<f32>,
+    document: Option<DocumentImage>,
+    /// Binding of `document`, made on first use.
+    document_set: Option<Arc<vk::PersistentDescriptorSet>>,
 }
 impl RenderVisitor<'_> {
+    fn document_set(
+        &mut self,
+        pipeline: &vk::GraphicsPipeline,
+    ) -> anyhow::Result<Arc<vk::PersistentDescriptorSet>> {
+        if let Some(set) = &self.document_set {
+            return Ok(set.clone());
+        }
+        let Some(document) = &self.document else {
+            anyhow::bail!("no document image to bind")
+        };
+        let set = vk::PersistentDescriptorSet::new(
+            self.renderer.context.allocators().descriptor_set(),
+            pipeline.layout().set_layouts()[0].clone(),
+            [vk::WriteDescriptorSet::image_view_sampler(
+                0,
+                document.view.clone(),
+                self.renderer.document_sampler.clone(),
+            )],
+            [],
+        )?;
+        Ok(self.document_set.insert(set).clone())
+    }
     pub fn build(mut self) -> anyhow::Result<Arc<vk::PrimaryAutoCommandBuffer>> {
         self.command_buffer.end_rendering()?;
         let build = self.command_buffer.build()?;
@@ -771,6 +916,9 @@ impl<'a> super::GizmoVisitor<anyhow::Error> for RenderVisitor<'a> {
         let Some((vertex, fragment)) = shaders::processing_of(&gizmo.visual) else {
             return std::ops::ControlFlow::Continue(());
         };
+        if fragment == shaders::FragmentProcessing::Document && self.document.is_none() {
+            return std::ops::ControlFlow::Continue(());
+        }
         // try_block macro doesn't impl FnMut it's kinda weird :V
         // We use this to map Result<> to ControlFlow
         let mut try_block = || -> anyhow::Result<()> {
@@ -782,6 +930,7 @@ impl<'a> super::GizmoVisitor<anyhow::Error> for RenderVisitor<'a> {
             // unwrap ok - checked above.
             let base_xform = self.xform_stack.first().unwrap();
             let local_xform = gizmo.transform.apply(base_xform, parent_xform);
+            let width_scale = gizmo.transform.width_scale(base_xform, parent_xform);
 
             // `MeshMode::None` handled gracefully above
             if matches!(&gizmo.visual.mesh, super::MeshMode::Triangles) {
@@ -827,6 +976,8 @@ impl<'a> super::GizmoVisitor<anyhow::Error> for RenderVisitor<'a> {
                     [time; 4]
                 }
                 super::TextureMode::Solid(c) => c,
+                // Modulates the document, leave it be.
+                super::TextureMode::Document { .. } => [255; 4],
                 super::TextureMode::Texture { modulate: _, .. } => {
                     // Todo: bind texture descriptor.
                     unimplemented!();
@@ -840,6 +991,8 @@ impl<'a> super::GizmoVisitor<anyhow::Error> for RenderVisitor<'a> {
                     f32::from(color[3]) / 255.0,
                 ],
                 transform: matrix.into(),
+                width_scale,
+                pad: [0.0; 3],
             };
 
             let pipeline = self.renderer.lazy_pipeline_for(vertex, fragment)?;
@@ -852,6 +1005,31 @@ impl<'a> super::GizmoVisitor<anyhow::Error> for RenderVisitor<'a> {
                 self.current_pipeline = Some(pipeline.clone());
             }
 
+            if let super::TextureMode::Document { zoom } = gizmo.visual.texture {
+                let origin = local_xform.project(cgmath::Point2 { x: 0.0, y: 0.0 });
+                // Checked at the start.
+                let Some(uv_rows) = self
+                    .document
+                    .as_ref()
+                    .and_then(|document| document.uv_rows(base_xform, origin, zoom))
+                else {
+                    anyhow::bail!("degenerate document transform")
+                };
+                let set = self.document_set(&pipeline)?;
+                self.command_buffer
+                    .bind_descriptor_sets(
+                        vk::PipelineBindPoint::Graphics,
+                        pipeline.layout().clone(),
+                        0,
+                        set,
+                    )?
+                    .push_constants(
+                        pipeline.layout().clone(),
+                        std::mem::size_of::<shaders::PushConstants>() as u32,
+                        shaders::DocumentPushConstants { uv_rows },
+                    )?;
+            }
+
             let vertex_buffer = self.renderer.vertices_for(&gizmo.visual.mesh)?;
             let num_verts = match vertex_buffer {
                 VertexBuffer::Normal(n) => {
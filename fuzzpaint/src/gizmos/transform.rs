@@ -15,7 +15,7 @@ pub enum OriginPinning {
 pub enum BasisPinning {
     /// Size is in document pixels. Rotation is relative to the document space.
     Document,
-    /// Size is in viewport logical pixels, rotation is absolute.
+    /// Size is in viewport logical pixels (see [`viewport_scale`]), rotation is absolute.
     Viewport,
     /// Calculate based on parent's transform.
     ///
@@ -23,12 +23,47 @@ pub enum BasisPinning {
     Inherit,
 }
 
+/// Bits of an `f32`, as there is no atomic float.
+static VIEWPORT_SCALE: std::sync::atomic::AtomicU32 =
+    std::sync::atomic::AtomicU32::new(1.0f32.to_bits());
+
+/// Set the number of physical pixels per logical viewport pixel, to match the UI's scale.
+pub fn set_viewport_scale(scale: f32) {
+    VIEWPORT_SCALE.store(scale.to_bits(), std::sync::atomic::Ordering::Relaxed);
+}
+/// Physical pixels per logical viewport pixel. Gizmos with [`BasisPinning::Viewport`] sizes are scaled
+/// by this, for both rendering and hit-testing, so that they remain the same size relative to the UI.
+#[must_use]
+pub fn viewport_scale() -> f32 {
+    f32::from_bits(VIEWPORT_SCALE.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+/// Scale of the basis chosen by `pinning`, in viewport physical pixels per unit.
+fn basis_scale(
+    pinning: &BasisPinning,
+    document_transform: &crate::view_transform::ViewTransform,
+    parent_transform: &crate::view_transform::ViewTransform,
+) -> f32 {
+    match pinning {
+        BasisPinning::Document => document_transform.decomposed.scale,
+        BasisPinning::Inherit => parent_transform.decomposed.scale,
+        BasisPinning::Viewport => viewport_scale(),
+    }
+}
+
+/// Where a gizmo is, and which of its parts follow the document's zoom and which stay the same size on screen.
+/// The usual combinations have constructors - [`Self::handle`] for things grabbed or pointed with, which stay
+/// the same size on screen, and [`Self::outline`] for lines traced along the document, which follow it but
+/// keep the width of their lines.
 pub struct Transform {
     pub position: ultraviolet::Vec2,
     pub origin_pinning: OriginPinning,
     pub scale_pinning: BasisPinning,
     pub rotation: f32,
     pub rotation_pinning: BasisPinning,
+    /// The unit of the width of [wide lines](super::MeshMode::WideLineStrip), apart from the unit of their
+    /// positions. Rotation plays no part.
+    pub width_pinning: BasisPinning,
 }
 impl Transform {
     /// Apply this gizmo transform to the given document and parent gizmo transforms, returning a new transform representing
@@ -52,11 +87,7 @@ impl Transform {
             }
         }
         .to_vec();
-        let scale = match self.scale_pinning {
-            BasisPinning::Document => document_transform.decomposed.scale,
-            BasisPinning::Inherit => parent_transform.decomposed.scale,
-            BasisPinning::Viewport => 1.0,
-        };
+        let scale = basis_scale(&self.scale_pinning, document_transform, parent_transform);
         let rotation = cgmath::Basis2::from_angle(cgmath::Rad(self.rotation));
         let rot = match self.rotation_pinning {
             BasisPinning::Document => document_transform.decomposed.rot * rotation,
@@ -64,18 +95,109 @@ impl Transform {
             BasisPinning::Viewport => rotation,
         };
 
+        // Mirroring follows the rotation, as they share a basis.
+        let flip_x = match self.rotation_pinning {
+            BasisPinning::Document => document_transform.flip_x,
+            BasisPinning::Inherit => parent_transform.flip_x,
+            BasisPinning::Viewport => false,
+        };
+
         crate::view_transform::ViewTransform {
+            flip_x,
             decomposed: cgmath::Decomposed { scale, rot, disp },
         }
     }
+    /// Factor from line widths in the units of [`Self::width_pinning`] to this gizmo's local units, with the
+    /// same arguments as [`Self::apply`].
+    #[must_use]
+    pub fn width_scale(
+        &self,
+        document_transform: &crate::view_transform::ViewTransform,
+        parent_transform: &crate::view_transform::ViewTransform,
+    ) -> f32 {
+        let local = basis_scale(&self.scale_pinning, document_transform, parent_transform);
+        let width = basis_scale(&self.width_pinning, document_transform, parent_transform);
+        let factor = width / local;
+        // A degenerate local space draws nothing anyway.
+        if factor.is_finite() {
+            factor
+        } else {
+            1.0
+        }
+    }
     #[must_use]
     pub fn inherit_all() -> Self {
         Self {
             origin_pinning: OriginPinning::Inherit,
             rotation_pinning: BasisPinning::Inherit,
             scale_pinning: BasisPinning::Inherit,
+            width_pinning: BasisPinning::Inherit,
             position: ultraviolet::Vec2 { x: 0.0, y: 0.0 },
             rotation: 0.0,
         }
     }
+    /// At a document position, upright and the same size on screen whatever the view. Sizes, widths included,
+    /// are in viewport logical pixels.
+    #[must_use]
+    pub fn handle(position: ultraviolet::Vec2) -> Self {
+        Self {
+            position,
+            origin_pinning: OriginPinning::Document,
+            scale_pinning: BasisPinning::Viewport,
+            rotation: 0.0,
+            rotation_pinning: BasisPinning::Viewport,
+            width_pinning: BasisPinning::Viewport,
+        }
+    }
+    /// Following the parent as it's zoomed and turned, like [`Self::inherit_all`], but with line widths in
+    /// viewport logical pixels so they stay thin and legible at any zoom.
+    #[must_use]
+    pub fn outline() -> Self {
+        Self {
+            width_pinning: BasisPinning::Viewport,
+            ..Self::inherit_all()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BasisPinning, Transform};
+    use crate::view_transform::ViewTransform;
+
+    fn zoomed(scale: f32) -> ViewTransform {
+        use cgmath::Rotation2;
+        ViewTransform {
+            flip_x: false,
+            decomposed: cgmath::Decomposed {
+                scale,
+                rot: cgmath::Basis2::from_angle(cgmath::Rad(0.0)),
+                disp: cgmath::vec2(0.0, 0.0),
+            },
+        }
+    }
+    #[test]
+    fn width_scale() {
+        let document = zoomed(8.0);
+        let viewport = super::viewport_scale();
+        // Lines along the document keep their width on screen, undoing the zoom.
+        let outline = Transform::outline();
+        assert!(
+            (outline.width_scale(&document, &document) * document.decomposed.scale - viewport)
+                .abs()
+                < 1e-5
+        );
+        // Handles are already in viewport pixels.
+        let handle = Transform::handle(ultraviolet::Vec2 { x: 1.0, y: 2.0 });
+        assert!((handle.width_scale(&document, &document) - 1.0).abs() < 1e-5);
+        // Widths that follow the document grow with it, as they always have.
+        assert!((Transform::inherit_all().width_scale(&document, &document) - 1.0).abs() < 1e-5);
+        let document_widths = Transform {
+            width_pinning: BasisPinning::Document,
+            ..Transform::handle(ultraviolet::Vec2 { x: 0.0, y: 0.0 })
+        };
+        assert!((document_widths.width_scale(&document, &document) * viewport - 8.0).abs() < 1e-5);
+        // Degenerate views don't poison the width.
+        assert!((outline.width_scale(&zoomed(0.0), &zoomed(0.0)) - 1.0).abs() < 1e-5);
+    }
 }
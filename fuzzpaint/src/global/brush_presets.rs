@@ -0,0 +1,425 @@
+//! Named brush presets, saved to the user's preferences and shared between all documents.
+//!
+//! Presets can also be exported to and imported from standalone preset packs, which share the
+//! same format as the presets file.
+
+use super::config_file::ConfigFile;
+use fuzzpaint_core::{
+    brush::UniqueID,
+    state::{
+        DualStamp, Dynamics, DynamicsInput, Response, Scatter, StampOrientation,
+        StrokeBrushSettings, Taper,
+    },
+    util::FiniteF32,
+};
+
+const DOCUMENTATION: &str = r#"# Fuzzpaint brush presets. You may edit this file, but be aware that formatting and comments will
+# not be preserved.
+
+# Each [[preset]] has a name, the ID of the brush it uses, the diameter in document pixels at
+# full pressure, the spacing between stamps in pixels, whether it erases, and whether it smudges.
+# Smudging drags the colors already on the layer along the stroke, and takes precedence over erasing.
+# Color is not part of a preset, applying one keeps the current color.
+# orientation turns each stamp: random, direction to follow the stroke, or tilt to follow where the pen leans.
+# scatter_position, scatter_size, and scatter_angle vary each stamp at random: how far it strays from the stroke
+# as a fraction of its diameter, how much it shrinks as a fraction of its size, and how far it turns either way
+# in degrees. A [preset.dual] table multiplies a second brush texture into every stamp, at scale times the
+# stamp's size.
+# taper_start and taper_end thin the stroke towards its ends, over that many pixels. taper_velocity, from 0 to 1,
+# is how much it thins where it was drawn quickly, so that flicks come out tapered.
+# [preset.size_dynamics], [preset.flow_dynamics], and [preset.opacity_dynamics] tables set what each of those
+# follows. input is one of none, pressure, tilt, speed, or wheel, min is the output with no input from 0 to 1, and
+# gamma bends the response, above 1 favoring full input. Left out, size follows pressure and the others are constant.
+
+# Example:
+# [[preset]]
+# name = "Big eraser"
+# brush = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8L"
+# size = 40.0
+# spacing = 2.0
+# eraser = true
+#
+# [[preset]]
+# name = "Chalk"
+# brush = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+# size = 20.0
+# spacing = 2.0
+# scatter_position = 0.2
+# scatter_angle = 180.0
+# taper_end = 30.0
+# taper_velocity = 0.5
+# [preset.flow_dynamics]
+# input = "tilt"
+# min = 0.3
+# [preset.dual]
+# brush = "AQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAACc"
+# scale = 0.5
+
+"#;
+
+#[derive(thiserror::Error, Debug)]
+pub enum InvalidPreset {
+    #[error("bad brush ID: {0}")]
+    Brush(#[from] fuzzpaint_core::brush::UniqueIDParseError),
+    #[error("size and spacing must be finite")]
+    NotFinite(#[from] fuzzpaint_core::util::FiniteF32Error),
+}
+
+/// Serde stand-in for [`StampOrientation`], which the core crate doesn't derive for.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(remote = "StampOrientation", rename_all = "snake_case")]
+enum StampOrientationDef {
+    Random,
+    Direction,
+    Tilt,
+}
+
+/// Likewise, for [`DynamicsInput`].
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(remote = "DynamicsInput", rename_all = "snake_case")]
+enum DynamicsInputDef {
+    None,
+    Pressure,
+    Tilt,
+    Speed,
+    Wheel,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct BrushPreset {
+    pub name: String,
+    /// [`UniqueID`] of the brush, in its string form.
+    pub brush: String,
+    pub size: f32,
+    pub spacing: f32,
+    #[serde(default)]
+    pub eraser: bool,
+    #[serde(default)]
+    pub smudge: bool,
+    #[serde(default, with = "StampOrientationDef")]
+    pub orientation: StampOrientation,
+    #[serde(default)]
+    pub scatter_position: f32,
+    #[serde(default)]
+    pub scatter_size: f32,
+    /// In degrees, unlike [`Scatter::angle`].
+    #[serde(default)]
+    pub scatter_angle: f32,
+    #[serde(default)]
+    pub taper_start: f32,
+    #[serde(default)]
+    pub taper_end: f32,
+    #[serde(default)]
+    pub taper_velocity: f32,
+    /// These and the rest must follow the values, as they're tables. Left out when they're the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_dynamics: Option<ResponsePreset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow_dynamics: Option<ResponsePreset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity_dynamics: Option<ResponsePreset>,
+    #[serde(default)]
+    pub dual: Option<DualPreset>,
+}
+/// See [`Response`].
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ResponsePreset {
+    #[serde(with = "DynamicsInputDef")]
+    pub input: DynamicsInput,
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default = "ResponsePreset::default_gamma")]
+    pub gamma: f32,
+}
+impl ResponsePreset {
+    fn default_gamma() -> f32 {
+        1.0
+    }
+}
+impl From<Response> for ResponsePreset {
+    fn from(response: Response) -> Self {
+        Self {
+            input: response.input,
+            min: response.min,
+            gamma: response.gamma,
+        }
+    }
+}
+impl From<ResponsePreset> for Response {
+    fn from(preset: ResponsePreset) -> Self {
+        Self {
+            input: preset.input,
+            min: preset.min,
+            gamma: preset.gamma,
+        }
+    }
+}
+/// See [`DualStamp`].
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct DualPreset {
+    /// [`UniqueID`] of the second brush, in its string form.
+    pub brush: String,
+    pub scale: f32,
+}
+impl BrushPreset {
+    /// Capture everything but the color of the given settings.
+    #[must_use]
+    pub fn from_settings(name: String, settings: &StrokeBrushSettings) -> Self {
+        let defaults = Dynamics::default();
+        let unless_default =
+            |response: Response, default| (response != default).then(|| response.into());
+        Self {
+            name,
+            brush: settings.brush.to_string(),
+            size: settings.size_mul.get(),
+            spacing: settings.spacing_px.get(),
+            eraser: settings.is_eraser,
+            smudge: settings.is_smudge,
+            orientation: settings.orientation,
+            scatter_position: settings.scatter.position,
+            scatter_size: settings.scatter.size,
+            scatter_angle: settings.scatter.angle.to_degrees(),
+            taper_start: settings.taper.start,
+            taper_end: settings.taper.end,
+            taper_velocity: settings.taper.velocity,
+            size_dynamics: unless_default(settings.dynamics.size, defaults.size),
+            flow_dynamics: unless_default(settings.dynamics.flow, defaults.flow),
+            opacity_dynamics: unless_default(settings.dynamics.opacity, defaults.opacity),
+            dual: settings.dual.map(|dual| DualPreset {
+                brush: dual.brush.to_string(),
+                scale: dual.scale,
+            }),
+        }
+    }
+    /// Apply the preset onto the settings, keeping their color. On error, the settings are unchanged.
+    pub fn apply(&self, settings: &mut StrokeBrushSettings) -> Result<(), InvalidPreset> {
+        let brush: UniqueID = self.brush.parse()?;
+        // Same lower limits as the brush panel sliders.
+        let size = FiniteF32::new(self.size.max(0.1))?;
+        let spacing = FiniteF32::new(self.spacing.max(0.1))?;
+        let dual = match &self.dual {
+            Some(dual) => Some(
+                DualStamp {
+                    brush: dual.brush.parse()?,
+                    scale: dual.scale,
+                }
+                .sanitized(),
+            ),
+            None => None,
+        };
+
+        settings.brush = brush;
+        settings.size_mul = size;
+        settings.spacing_px = spacing;
+        settings.is_eraser = self.eraser;
+        settings.is_smudge = self.smudge;
+        settings.orientation = self.orientation;
+        settings.scatter = Scatter {
+            position: self.scatter_position,
+            size: self.scatter_size,
+            angle: self.scatter_angle.to_radians(),
+        }
+        .sanitized();
+        settings.dual = dual;
+        settings.taper = Taper {
+            start: self.taper_start,
+            end: self.taper_end,
+            velocity: self.taper_velocity,
+        }
+        .sanitized();
+        let defaults = Dynamics::default();
+        settings.dynamics = Dynamics {
+            size: self.size_dynamics.map_or(defaults.size, Response::from),
+            flow: self.flow_dynamics.map_or(defaults.flow, Response::from),
+            opacity: self
+                .opacity_dynamics
+                .map_or(defaults.opacity, Response::from),
+        }
+        .sanitized();
+        Ok(())
+    }
+}
+
+/// On-disk representation of both the presets file and packs. TOML needs a table at the top level.
+#[derive(serde::Deserialize)]
+pub struct PresetsFile {
+    #[serde(default, rename = "preset")]
+    presets: Vec<BrushPreset>,
+}
+/// Serialize a borrowed view, to avoid cloning every preset.
+#[derive(serde::Serialize)]
+struct PresetsFileRef<'a> {
+    #[serde(rename = "preset")]
+    presets: &'a [BrushPreset],
+}
+fn to_string(presets: &[BrushPreset]) -> Result<String, toml::ser::Error> {
+    let string = toml::ser::to_string_pretty(&PresetsFileRef { presets })?;
+    Ok(DOCUMENTATION.to_owned() + &string)
+}
+
+/// Read the presets of a pack file.
+pub fn import_pack(path: &std::path::Path) -> anyhow::Result<Vec<BrushPreset>> {
+    let string = std::fs::read_to_string(path)?;
+    let file: PresetsFile = toml::from_str(&string)?;
+    Ok(file.presets)
+}
+/// Write the presets into a pack file, overwriting contents.
+pub fn export_pack(path: &std::path::Path, presets: &[BrushPreset]) -> anyhow::Result<()> {
+    std::fs::write(path, to_string(presets)?)?;
+    Ok(())
+}
+
+pub struct BrushPresets {
+    pub load_blocker: Option<super::hotkeys::LoadBlockReason>,
+    pub presets: Vec<BrushPreset>,
+}
+impl BrushPresets {
+    /// Extension used for exported packs.
+    pub const PACK_EXTENSION: &'static str = "toml";
+    /// Shared read access to the global presets.
+    pub fn read() -> parking_lot::RwLockReadGuard<'static, Self> {
+        Self::global().read()
+    }
+    /// Exclusive write access to the global presets.
+    pub fn write() -> parking_lot::RwLockWriteGuard<'static, Self> {
+        Self::global().write()
+    }
+    fn global() -> &'static parking_lot::RwLock<Self> {
+        static GLOBAL_PRESETS: std::sync::OnceLock<parking_lot::RwLock<BrushPresets>> =
+            std::sync::OnceLock::new();
+
+        GLOBAL_PRESETS.get_or_init(|| Self::from_default_file().into())
+    }
+    /// Returns the reason for read/write blockage, if any.
+    #[must_use]
+    pub fn load_blocker(&self) -> Option<&super::hotkeys::LoadBlockReason> {
+        self.load_blocker.as_ref()
+    }
+    /// Add presets from a pack, replacing any existing presets of the same name.
+    pub fn merge(&mut self, presets: impl IntoIterator<Item = BrushPreset>) {
+        for preset in presets {
+            if let Some(existing) = self.presets.iter_mut().find(|p| p.name == preset.name) {
+                *existing = preset;
+            } else {
+                self.presets.push(preset);
+            }
+        }
+    }
+}
+impl ConfigFile for BrushPresets {
+    const FILENAME: &'static str = "brush_presets.toml";
+    const DESCRIPTION: &'static str = "brush presets";
+    type File = PresetsFile;
+    fn with_defaults() -> Self {
+        Self {
+            load_blocker: None,
+            presets: Vec::new(),
+        }
+    }
+    fn from_file(file: PresetsFile) -> Result<Self, super::hotkeys::LoadBlockReason> {
+        Ok(Self {
+            load_blocker: None,
+            presets: file.presets,
+        })
+    }
+    fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        to_string(&self.presets)
+    }
+    fn set_load_blocker(&mut self, blocker: super::hotkeys::LoadBlockReason) {
+        self.load_blocker = Some(blocker);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        BrushPreset, BrushPresets, ConfigFile, DualStamp, Dynamics, DynamicsInput, PresetsFile,
+        Response, Scatter, StampOrientation, Taper,
+    };
+    fn settings() -> fuzzpaint_core::state::StrokeBrushSettings {
+        fuzzpaint_core::state::StrokeBrushSettings {
+            brush: fuzzpaint_core::brush::UniqueID([7; 32]),
+            color_modulate: fuzzpaint_core::color::ColorOrPalette::BLACK,
+            size_mul: fuzzpaint_core::util::FiniteF32::new(12.0).unwrap(),
+            is_eraser: true,
+            is_smudge: true,
+            orientation: StampOrientation::Direction,
+            // Angle left out, as it's not exact through degrees.
+            scatter: Scatter {
+                position: 0.5,
+                size: 0.25,
+                angle: 0.0,
+            },
+            dual: Some(DualStamp {
+                brush: fuzzpaint_core::brush::UniqueID([3; 32]),
+                scale: 0.5,
+            }),
+            taper: Taper {
+                start: 10.0,
+                end: 20.0,
+                velocity: 0.5,
+            },
+            dynamics: Dynamics {
+                flow: Response {
+                    input: DynamicsInput::Tilt,
+                    min: 0.25,
+                    gamma: 2.0,
+                },
+                ..Dynamics::default()
+            },
+            spacing_px: fuzzpaint_core::util::FiniteF32::new(0.5).unwrap(),
+        }
+    }
+    #[test]
+    fn roundtrip() {
+        let preset = BrushPreset::from_settings("Test".to_owned(), &settings());
+        let string = super::to_string(std::slice::from_ref(&preset)).unwrap();
+        let file: PresetsFile = toml::from_str(&string).unwrap();
+        assert_eq!(file.presets, [preset.clone()]);
+        // Only what differs from the default is written.
+        assert!(preset.size_dynamics.is_none() && preset.flow_dynamics.is_some());
+
+        let mut applied = fuzzpaint_core::state::StrokeBrushSettings {
+            is_eraser: false,
+            is_smudge: false,
+            orientation: StampOrientation::Random,
+            scatter: Scatter::default(),
+            dual: None,
+            taper: Taper::default(),
+            dynamics: Dynamics::default(),
+            ..settings()
+        };
+        preset.apply(&mut applied).unwrap();
+        assert_eq!(applied, settings());
+    }
+    #[test]
+    fn bad_id() {
+        let preset = BrushPreset {
+            brush: "not an id".to_owned(),
+            ..BrushPreset::from_settings("Test".to_owned(), &settings())
+        };
+        let mut applied = settings();
+        applied.is_eraser = false;
+        assert!(preset.apply(&mut applied).is_err());
+        // Unchanged on error.
+        assert!(!applied.is_eraser);
+    }
+    #[test]
+    fn merge_replaces_by_name() {
+        let mut presets = BrushPresets::with_defaults();
+        let a = BrushPreset::from_settings("A".to_owned(), &settings());
+        presets.merge([a.clone()]);
+        presets.merge([
+            BrushPreset {
+                size: 1.0,
+                ..a.clone()
+            },
+            BrushPreset {
+                name: "B".to_owned(),
+                ..a
+            },
+        ]);
+        assert_eq!(presets.presets.len(), 2);
+        assert!((presets.presets[0].size - 1.0).abs() < f32::EPSILON);
+    }
+}
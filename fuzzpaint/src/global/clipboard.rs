@@ -0,0 +1,472 @@
+//! Copy and paste of strokes and layers, within and between documents.
+//!
+//! Nothing is deep-copied. Strokes are immutable, so the clipboard holds [shared handles](SharedCollection)
+//! to their point collections, and pasting refers to those very same collections again.
+//!
+//! Images copied by other programs are read from the system clipboard, and pasted as image layers. Copying here
+//! puts a text [marker](Clip::marker) on the system clipboard in place of whatever was there, so an image
+//! copied since takes precedence, and one copied before doesn't.
+
+use fuzzpaint_core::{
+    color::{Color, ColorOrPalette},
+    queue::{state_reader::CommandQueueStateReader, writer::CommandQueueWriter},
+    repositories::points::{PointCollectionID, SharedCollection},
+    state::{
+        self,
+        graph::{AnyID, ColorTag, LeafType, Location, NodeData, NodeType, TargetError},
+        transform,
+    },
+};
+
+struct Stroke {
+    brush: state::StrokeBrushSettings,
+    points: SharedCollection<'static>,
+    clip: Option<SharedCollection<'static>>,
+    /// Kept by pasted copies, which were drawn when and by whom the original was.
+    metadata: state::stroke_collection::StrokeMetadata,
+}
+enum Kind {
+    StrokeLayer {
+        blend: fuzzpaint_core::blend::Blend,
+        inner_transform: transform::Similarity,
+        outer_transform: transform::Matrix,
+        strokes: Vec<Stroke>,
+    },
+    /// Any other leaf. These hold no points.
+    Leaf(LeafType),
+    Node {
+        ty: NodeType,
+        /// Top to bottom.
+        children: Vec<Layer>,
+    },
+}
+struct Layer {
+    name: String,
+    tag: ColorTag,
+    reference: bool,
+    kind: Kind,
+}
+struct Clip {
+    source: state::document::ID,
+    /// The source document's palette, to resolve palette colors when pasting into another document.
+    palette: state::palette::Palette,
+    /// Document-space center of the copied strokes, which is placed on the cursor when pasting there.
+    center: Option<[f32; 2]>,
+    /// Only some strokes of the layer were copied, not the layer itself.
+    loose: bool,
+    layer: Layer,
+    /// Text put on the system clipboard with this copy, unique to it. While the system clipboard still holds it,
+    /// nothing has been copied elsewhere since.
+    marker: String,
+}
+
+fn clipboard() -> &'static parking_lot::Mutex<Option<Clip>> {
+    static CLIPBOARD: std::sync::OnceLock<parking_lot::Mutex<Option<Clip>>> =
+        std::sync::OnceLock::new();
+    CLIPBOARD.get_or_init(Default::default)
+}
+
+fn apply(matrix: &transform::Matrix, [x, y]: [f32; 2]) -> [f32; 2] {
+    let [a, b, t] = matrix.elements;
+    [x * a[0] + y * b[0] + t[0], x * a[1] + y * b[1] + t[1]]
+}
+fn translated(mut matrix: transform::Matrix, offset: Option<[f32; 2]>) -> transform::Matrix {
+    if let Some([x, y]) = offset {
+        matrix.elements[2][0] += x;
+        matrix.elements[2][1] += y;
+    }
+    matrix
+}
+
+/// Document-space bounds, as `[min, max]`.
+#[derive(Default)]
+struct Bounds(Option<[[f32; 2]; 2]>);
+impl Bounds {
+    fn insert(&mut self, [x, y]: [f32; 2]) {
+        let [min, max] = self.0.get_or_insert([[x, y], [x, y]]);
+        *min = [min[0].min(x), min[1].min(y)];
+        *max = [max[0].max(x), max[1].max(y)];
+    }
+    fn center(&self) -> Option<[f32; 2]> {
+        self.0
+            .map(|[min, max]| [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0])
+    }
+}
+
+/// Copy the strokes of a stroke layer, keeping only those with a point within `selection` if given.
+fn copy_strokes(
+    state: &impl CommandQueueStateReader,
+    collection: state::stroke_collection::StrokeCollectionID,
+    layer_to_document: &transform::Matrix,
+    selection: Option<&[[f32; 2]]>,
+    bounds: &mut Bounds,
+) -> Vec<Stroke> {
+    let points = super::points();
+    let Some(collection) = state.stroke_collections().get(collection) else {
+        return Vec::new();
+    };
+    collection
+        .iter_active()
+        .filter_map(|stroke| {
+            // Any collection with positions reads back the same way a selection does.
+            let positions: Vec<_> = points
+                .try_get(stroke.point_collection)
+                .ok()
+                .and_then(|read| fuzzpaint_core::selection::polygon(read.get()))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|position| apply(layer_to_document, position))
+                .collect();
+            if let Some(selection) = selection {
+                if !positions
+                    .iter()
+                    .any(|&position| fuzzpaint_core::selection::contains(selection, position))
+                {
+                    return None;
+                }
+            }
+            let stroke = Stroke {
+                brush: stroke.brush,
+                points: points.share(stroke.point_collection)?,
+                clip: stroke.clip.and_then(|clip| points.share(clip)),
+                metadata: stroke.metadata,
+            };
+            positions
+                .into_iter()
+                .for_each(|position| bounds.insert(position));
+            Some(stroke)
+        })
+        .collect()
+}
+fn copy_layer(
+    state: &impl CommandQueueStateReader,
+    id: AnyID,
+    data: &NodeData,
+    selection: Option<&[[f32; 2]]>,
+    bounds: &mut Bounds,
+) -> Layer {
+    let kind = match data.leaf() {
+        Some(&LeafType::StrokeLayer {
+            blend,
+            collection,
+            inner_transform,
+            outer_transform,
+        }) => {
+            let layer_to_document = transform::Matrix::from(inner_transform).then(&outer_transform);
+            Kind::StrokeLayer {
+                blend,
+                inner_transform,
+                outer_transform,
+                strokes: copy_strokes(state, collection, &layer_to_document, selection, bounds),
+            }
+        }
+        Some(leaf) => Kind::Leaf(leaf.clone()),
+        None => {
+            let children = match id {
+                AnyID::Node(node) => state.graph().iter_node(node),
+                AnyID::Leaf(_) => None,
+            };
+            Kind::Node {
+                ty: data.node().cloned().unwrap_or(NodeType::Passthrough),
+                children: children
+                    .into_iter()
+                    .flatten()
+                    .map(|(id, data)| copy_layer(state, id, data, None, bounds))
+                    .collect(),
+            }
+        }
+    };
+    Layer {
+        name: data.name().to_owned(),
+        tag: data.tag(),
+        reference: data.is_reference(),
+        kind,
+    }
+}
+
+/// Copy the strokes of the layer within the selection, or the whole layer if there is no selection or it's not
+/// a stroke layer. Returns whether anything was copied - if not, the clipboard is left as it was.
+pub fn copy(document: &fuzzpaint_core::queue::DocumentCommandQueue, node: AnyID) -> bool {
+    let state = document.peek_clone_state();
+    let Some(data) = state.graph().get(node) else {
+        return false;
+    };
+    let selected = super::selection::get(document.id())
+        .filter(|_| matches!(data.leaf(), Some(LeafType::StrokeLayer { .. })));
+
+    let mut bounds = Bounds::default();
+    let selection = selected.as_ref().map(|selected| &*selected.polygon);
+    let layer = copy_layer(&state, node, data, selection, &mut bounds);
+    let loose = selection.is_some();
+    if loose && matches!(&layer.kind, Kind::StrokeLayer { strokes, .. } if strokes.is_empty()) {
+        return false;
+    }
+
+    static COPIES: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let marker = format!(
+        "fuzzpaint clip {}-{}",
+        std::process::id(),
+        COPIES.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    );
+    // Otherwise, an image left on the system clipboard would be pasted instead of this.
+    if let Err(err) = arboard::Clipboard::new().and_then(|mut system| system.set_text(&marker)) {
+        tracing::warn!("failed to mark system clipboard: {err}");
+    }
+    *clipboard().lock() = Some(Clip {
+        source: document.id(),
+        palette: state.palette().clone(),
+        center: bounds.center(),
+        loose,
+        layer,
+        marker,
+    });
+    true
+}
+
+/// Resolves colors and moves strokes while pasting.
+struct Paster {
+    /// Resolve palette colors against this palette, if pasting into another document.
+    palette: Option<state::palette::Palette>,
+    offset: Option<[f32; 2]>,
+    /// Clip polygons are in document space and don't move with the layer, so moved copies are made
+    /// of them. They're small, unlike the strokes.
+    moved_clips: hashbrown::HashMap<PointCollectionID, Option<PointCollectionID>>,
+}
+impl Paster {
+    fn color(&self, color: ColorOrPalette) -> ColorOrPalette {
+        match &self.palette {
+            Some(palette) => color
+                .get()
+                .left_or_else(|idx| palette.get(idx).unwrap_or(Color::BLACK))
+                .into(),
+            None => color,
+        }
+    }
+    fn clip(&mut self, clip: PointCollectionID) -> Option<PointCollectionID> {
+        let Some([x, y]) = self.offset else {
+            return Some(clip);
+        };
+        *self.moved_clips.entry(clip).or_insert_with(|| {
+            let points = super::points();
+            let polygon: Vec<[f32; 2]> =
+                fuzzpaint_core::selection::polygon(points.try_get(clip).ok()?.get())?
+                    .into_iter()
+                    .map(|[px, py]| [px + x, py + y])
+                    .collect();
+            let elements: &[u32] = bytemuck::cast_slice(&polygon);
+            // Unwrap ok - elements are exactly positions.
+            points.insert(
+                fuzzpaint_core::stroke::StrokeSlice::new(
+                    elements,
+                    fuzzpaint_core::selection::ARCHETYPE,
+                )
+                .unwrap(),
+            )
+        })
+    }
+    fn strokes(
+        &mut self,
+        writer: &mut CommandQueueWriter<'_>,
+        collection: state::stroke_collection::StrokeCollectionID,
+        strokes: &[Stroke],
+    ) {
+        let strokes: Vec<_> = strokes
+            .iter()
+            .map(|stroke| {
+                let brush = state::StrokeBrushSettings {
+                    color_modulate: self.color(stroke.brush.color_modulate),
+                    ..stroke.brush
+                };
+                let clip = stroke.clip.as_ref().and_then(|clip| self.clip(clip.id()));
+                (brush, stroke.points.id(), clip, stroke.metadata)
+            })
+            .collect();
+        let mut collections = writer.stroke_collections();
+        let Some(mut collection) = collections.get_mut(collection) else {
+            return;
+        };
+        for (brush, points, clip, metadata) in strokes {
+            collection.push_back_with_metadata(brush, points, clip, metadata);
+        }
+    }
+    fn layer(
+        &mut self,
+        writer: &mut CommandQueueWriter<'_>,
+        layer: &Layer,
+        location: Location<'_>,
+    ) -> Result<AnyID, TargetError> {
+        let name = layer.name.clone();
+        let id: AnyID = match &layer.kind {
+            Kind::StrokeLayer {
+                blend,
+                inner_transform,
+                outer_transform,
+                strokes,
+            } => {
+                let collection = writer.stroke_collections().insert();
+                self.strokes(writer, collection, strokes);
+                let leaf = LeafType::StrokeLayer {
+                    blend: *blend,
+                    collection,
+                    inner_transform: *inner_transform,
+                    outer_transform: translated(*outer_transform, self.offset),
+                };
+                writer.graph().add_leaf(leaf, location, name)?.into()
+            }
+            Kind::Leaf(leaf) => {
+                let mut leaf = leaf.clone();
+                match &mut leaf {
+                    LeafType::SolidColor { source, .. } => *source = self.color(*source),
+                    LeafType::Gradient { gradient, .. } => {
+                        for stop in &mut gradient.stops {
+                            stop.color = self.color(stop.color);
+                        }
+                    }
+                    _ => (),
+                }
+                if let Some(outer_transform) = leaf.outer_transform_mut() {
+                    *outer_transform = translated(*outer_transform, self.offset);
+                }
+                writer.graph().add_leaf(leaf, location, name)?.into()
+            }
+            Kind::Node { ty, children } => {
+                let node = writer.graph().add_node(ty.clone(), location, name)?;
+                for (idx, child) in children.iter().enumerate() {
+                    self.layer(writer, child, Location::IndexIntoNode(&node, idx))?;
+                }
+                node.into()
+            }
+        };
+        let mut graph = writer.graph();
+        if let Some(tag) = graph.tag_mut(id) {
+            *tag = layer.tag;
+        }
+        if let Some(reference) = graph.reference_mut(id) {
+            *reference = layer.reference;
+        }
+        Ok(id)
+    }
+}
+
+/// Paste above the selected node, or at the top if there is none. If `at` is given, the copied strokes are moved to be
+/// centered there, otherwise they're pasted where they were copied from.
+///
+/// Strokes copied from within a selection and pasted in place are added to the selected layer if it has the same
+/// transforms as the one they were copied from, otherwise they're pasted as a new layer.
+///
+/// Returns the new layer, if one was made.
+pub fn paste(
+    document: &fuzzpaint_core::queue::DocumentCommandQueue,
+    node: Option<AnyID>,
+    at: Option<[f32; 2]>,
+) -> Option<AnyID> {
+    let clip = clipboard().lock();
+    let clip = clip.as_ref()?;
+    let mut paster = Paster {
+        palette: (clip.source != document.id()).then(|| clip.palette.clone()),
+        offset: at
+            .zip(clip.center)
+            .map(|(at, center)| [at[0] - center[0], at[1] - center[1]]),
+        moved_clips: hashbrown::HashMap::new(),
+    };
+
+    document.write_with(|writer| {
+        if let (
+            true,
+            None,
+            Some(AnyID::Leaf(target)),
+            Kind::StrokeLayer {
+                inner_transform,
+                outer_transform,
+                strokes,
+                ..
+            },
+        ) = (clip.loose, paster.offset, node, &clip.layer.kind)
+        {
+            let target = writer.graph().get(target).and_then(NodeData::leaf).cloned();
+            if let Some(LeafType::StrokeLayer {
+                collection,
+                inner_transform: target_inner,
+                outer_transform: target_outer,
+                ..
+            }) = target
+            {
+                if (target_inner, target_outer) == (*inner_transform, *outer_transform) {
+                    paster.strokes(writer, collection, strokes);
+                    return None;
+                }
+            }
+        }
+
+        let location = node
+            .as_ref()
+            .map_or(Location::IndexIntoRoot(0), Location::AboveSelection);
+        match paster.layer(writer, &clip.layer, location) {
+            Ok(id) => Some(id),
+            Err(err) => {
+                tracing::warn!("failed to paste: {err}");
+                None
+            }
+        }
+    })
+}
+
+/// Paste an image from the system clipboard as a new image layer centered at `at`, above the node if any.
+/// Returns the new layer, or None if the system clipboard holds no image, or nothing was copied elsewhere since
+/// the last copy here.
+///
+/// The image is embedded into the document's assets, which undoing the paste leaves in place.
+pub fn paste_system_image(
+    document: &fuzzpaint_core::queue::DocumentCommandQueue,
+    node: Option<AnyID>,
+    at: [f32; 2],
+) -> Option<AnyID> {
+    let mut system = arboard::Clipboard::new().ok()?;
+    if let (Some(clip), Ok(text)) = (&*clipboard().lock(), system.get_text()) {
+        if text == clip.marker {
+            return None;
+        }
+    }
+    let image = system.get_image().ok()?;
+    let image = image::RgbaImage::from_raw(
+        image.width.try_into().ok()?,
+        image.height.try_into().ok()?,
+        image.bytes.into_owned(),
+    )?;
+    let mut png = Vec::new();
+    if let Err(err) = image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png) {
+        tracing::warn!("failed to encode pasted image: {err}");
+        return None;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let size = [image.width() as f32, image.height() as f32];
+
+    document.write_with(|writer| {
+        let asset = writer
+            .document()
+            .assets
+            .write()
+            .add_embedded("Pasted image", png);
+        let location = node
+            .as_ref()
+            .map_or(Location::IndexIntoRoot(0), Location::AboveSelection);
+        let leaf = LeafType::Image {
+            blend: fuzzpaint_core::blend::Blend::default(),
+            image: asset,
+            outer_transform: transform::Matrix {
+                elements: [
+                    [1.0, 0.0],
+                    [0.0, 1.0],
+                    [at[0] - size[0] / 2.0, at[1] - size[1] / 2.0],
+                ],
+            },
+        };
+        match writer.graph().add_leaf(leaf, location, "Pasted image") {
+            Ok(id) => Some(id.into()),
+            Err(err) => {
+                tracing::warn!("failed to paste image: {err}");
+                None
+            }
+        }
+    })
+}
@@ -0,0 +1,122 @@
+//! Loading and saving the TOML files kept in the [preferences directory](super::hotkeys::preferences_dir).
+
+use super::hotkeys::LoadBlockReason;
+
+/// Settings loaded from a file of the preferences directory, and saved back to it as they're changed.
+///
+/// A file that can't be read or parsed is never saved over without the user's say-so: the settings start from
+/// their defaults, remembering why in [`Self::set_load_blocker`], and a file that failed to parse is first
+/// copied beside itself with `.bak` appended, so that a hand edit gone wrong is never lost.
+pub trait ConfigFile: Sized {
+    /// Name of the file within the preferences directory.
+    const FILENAME: &'static str;
+    /// What the file holds, for the log.
+    const DESCRIPTION: &'static str;
+    /// The contents of the file, as parsed.
+    type File: serde::de::DeserializeOwned;
+    /// The settings when nothing's been saved.
+    fn with_defaults() -> Self;
+    /// Fails if the parsed contents don't make sense together.
+    fn from_file(file: Self::File) -> Result<Self, LoadBlockReason>;
+    /// The text of the file, to save.
+    fn to_toml(&self) -> Result<String, toml::ser::Error>;
+    /// Remember the error that kept the file from loading, see [`Self::load_or_default`].
+    fn set_load_blocker(&mut self, blocker: LoadBlockReason);
+
+    #[must_use]
+    fn default_file_location() -> Option<std::path::PathBuf> {
+        let mut dir = super::hotkeys::preferences_dir()?;
+        dir.push(Self::FILENAME);
+        Some(dir)
+    }
+    /// Load from the default file location.
+    #[must_use]
+    fn from_default_file() -> Self {
+        Self::default_file_location()
+            .as_deref()
+            .map_or_else(Self::with_defaults, Self::load_or_default)
+    }
+    /// Attempts to load from the given path. On file-not-found, defaults. On other error, defaults with a
+    /// load-blocking message for the user.
+    #[must_use]
+    fn load_or_default(path: &std::path::Path) -> Self {
+        let loaded: Result<Option<Self>, LoadBlockReason> = try_block::try_block! {
+            let string = match std::fs::read_to_string(path) {
+                Ok(string) => string,
+                // Not an error, nothing has been saved yet.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            let loaded = toml::from_str(&string)
+                .map_err(LoadBlockReason::from)
+                .and_then(Self::from_file);
+            if loaded.is_err() {
+                backup(path);
+            }
+            Ok(Some(loaded?))
+        };
+
+        match loaded {
+            Ok(Some(loaded)) => loaded,
+            Ok(None) => {
+                tracing::info!("{} not found, defaulting", Self::DESCRIPTION);
+                Self::with_defaults()
+            }
+            // Take defaults, but prevent writes until the user clears the error.
+            Err(e) => {
+                tracing::error!("failed to load {}: {e}", Self::DESCRIPTION);
+                let mut defaults = Self::with_defaults();
+                defaults.set_load_blocker(e);
+                defaults
+            }
+        }
+    }
+    /// Save to the default location, overwriting contents.
+    /// *This should not be called if the load was blocked unless the user explicitly called for it.*
+    fn save(&self) -> anyhow::Result<()> {
+        let mut preferences = super::hotkeys::preferences_dir()
+            .ok_or_else(|| anyhow::anyhow!("No preferences dir found"))?;
+        // Explicity do *not* create recursively. If not found, the user probably has a good reason.
+        // Ignore errors (could already exist). Any real errors will be emitted by file access below.
+        let _ = std::fs::DirBuilder::new().create(&preferences);
+
+        preferences.push(Self::FILENAME);
+        std::fs::write(preferences, self.to_toml()?)?;
+        Ok(())
+    }
+}
+/// Copy the file at `path` beside itself with `.bak` appended, replacing any older copy.
+fn backup(path: &std::path::Path) {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    match std::fs::copy(path, &backup) {
+        Ok(_) => tracing::info!("kept a copy of {} at {backup:?}", path.display()),
+        Err(e) => tracing::warn!("failed to keep a copy of {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConfigFile;
+    use crate::global::palettes::Palettes;
+    #[test]
+    fn broken_file_kept() {
+        let dir = fuzzpaint_core::util::scratch_dir("config");
+        let path = dir.join(Palettes::FILENAME);
+        let backup = dir.join(format!("{}.bak", Palettes::FILENAME));
+
+        // Nothing saved yet, which isn't an error.
+        let missing = Palettes::load_or_default(&path);
+        assert!(missing.load_blocker().is_none());
+        assert!(!backup.exists());
+
+        let broken = "[[palette]]\nname = ";
+        std::fs::write(&path, broken).unwrap();
+        let loaded = Palettes::load_or_default(&path);
+        assert!(loaded.load_blocker().is_some());
+        assert!(loaded.palettes.is_empty());
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), broken);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
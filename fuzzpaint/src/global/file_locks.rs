@@ -0,0 +1,121 @@
+//! Locks on the file of each open document, see [`fuzzpaint_core::io::file_lock`].
+//!
+//! A document whose file is already locked by another instance is opened read-only, so that the two can't
+//! write over one another. So is a document too damaged to read whole, with whatever could be
+//! [salvaged](fuzzpaint_core::io::salvage_path), see [`salvaged`].
+
+use fuzzpaint_core::{
+    io::{file_lock::FileLock, history::ProcessIds},
+    progress::Progress,
+    queue::DocumentCommandQueue,
+    state::document::ID,
+};
+
+fn locks() -> &'static parking_lot::Mutex<hashbrown::HashMap<ID, FileLock>> {
+    static LOCKS: std::sync::OnceLock<parking_lot::Mutex<hashbrown::HashMap<ID, FileLock>>> =
+        std::sync::OnceLock::new();
+    LOCKS.get_or_init(Default::default)
+}
+/// What was left out of each salvaged document.
+fn salvage_reports() -> &'static parking_lot::Mutex<hashbrown::HashMap<ID, Vec<String>>> {
+    static REPORTS: std::sync::OnceLock<parking_lot::Mutex<hashbrown::HashMap<ID, Vec<String>>>> =
+        std::sync::OnceLock::new();
+    REPORTS.get_or_init(Default::default)
+}
+/// Whether reading failed on what's in the file, rather than being unable to read the file at all.
+fn is_damage(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    // Malformed or truncated contents. Anything else, such as an interrupted or timed out read, may well
+    // succeed if tried again, and is no reason to open the file read-only.
+    !fuzzpaint_core::progress::Cancelled::is_cause_of(err)
+        && matches!(
+            err.kind(),
+            ErrorKind::InvalidData | ErrorKind::UnexpectedEof | ErrorKind::Other
+        )
+}
+
+/// Read the document at `path`, locking its file for as long as it's open. The document is read-only if
+/// `read_only` is set, or if the file is locked elsewhere. Otherwise its changes are
+/// [journaled](super::journals). Reading reports into `progress`, and stops if it's cancelled.
+///
+/// If the file is damaged, what can be is salvaged into a read-only document instead.
+pub fn open(
+    path: &std::path::Path,
+    read_only: bool,
+    progress: &Progress,
+) -> Result<DocumentCommandQueue, std::io::Error> {
+    let (queue, ids) = match open_with_ids(path, read_only, progress) {
+        Ok(opened) => opened,
+        Err(err) if is_damage(&err) => {
+            tracing::warn!(path = %path.display(), "failed to open, salvaging: {err}");
+            let (queue, skipped) =
+                fuzzpaint_core::io::salvage_path(path, super::points(), progress)?;
+            // Left as it is, for the user to salvage a copy with Save as.
+            queue.set_read_only(true);
+            salvage_reports().lock().insert(queue.id(), skipped);
+            return Ok(queue);
+        }
+        Err(err) => return Err(err),
+    };
+    if let Some(ids) = ids.filter(|_| !queue.is_read_only()) {
+        super::journals::start(&queue, path, ids.to_file());
+    }
+    Ok(queue)
+}
+/// As [`open`], returning the IDs the document's history was read with instead of starting its journal.
+pub fn open_with_ids(
+    path: &std::path::Path,
+    read_only: bool,
+    progress: &Progress,
+) -> Result<(DocumentCommandQueue, Option<ProcessIds>), std::io::Error> {
+    let (lock, read_only) = if read_only {
+        (None, true)
+    } else {
+        match FileLock::try_new(path) {
+            Ok(Some(lock)) => (Some(lock), false),
+            Ok(None) => {
+                tracing::info!(path = %path.display(), "file is open elsewhere, opening read-only");
+                (None, true)
+            }
+            // Not every filesystem supports locks. Nothing to be done, carry on without.
+            Err(err) => {
+                tracing::warn!(path = %path.display(), "failed to lock file: {err}");
+                (None, false)
+            }
+        }
+    };
+    let (queue, ids) =
+        fuzzpaint_core::io::read_path_with_progress(path, super::points(), progress)?;
+    queue.set_read_only(read_only);
+    if let Some(lock) = lock {
+        locks().lock().insert(queue.id(), lock);
+    }
+    Ok((queue, ids))
+}
+/// What was left out of the document, if it was salvaged from a damaged file.
+#[must_use]
+pub fn salvaged(document: ID) -> Option<Vec<String>> {
+    salvage_reports().lock().get(&document).cloned()
+}
+/// Release the lock on the document's file, once it is closed.
+pub fn release(document: ID) {
+    locks().lock().remove(&document);
+    salvage_reports().lock().remove(&document);
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn damage_is_only_the_contents() {
+        use std::io::{Error, ErrorKind};
+        assert!(super::is_damage(&Error::other("bad file magic")));
+        assert!(super::is_damage(&ErrorKind::UnexpectedEof.into()));
+        // Reading again may well work.
+        assert!(!super::is_damage(&ErrorKind::Interrupted.into()));
+        assert!(!super::is_damage(&ErrorKind::TimedOut.into()));
+        assert!(!super::is_damage(&ErrorKind::PermissionDenied.into()));
+        assert!(!super::is_damage(
+            &fuzzpaint_core::progress::Cancelled.into()
+        ));
+    }
+}
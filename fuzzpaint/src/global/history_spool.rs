@@ -0,0 +1,320 @@
+//! Keeping the undo history of each open document within a memory budget, however long the session.
+//!
+//! Once the steps that can be undone hold more than [`Preferences::history_memory_mb`] of
+//! [memory](fuzzpaint_core::commands::Command::memory), the oldest are
+//! [trimmed](fuzzpaint_core::queue::DocumentCommandQueue::trim_history) from the document, down to
+//! [`TRIM_TO`] of the budget so that the start of history is rebuilt and written out rarely. The present is
+//! still rendered from what's kept in memory.
+//!
+//! Trimmed steps can't be undone into, but aren't lost. They're spooled to disk, as a snapshot of the state
+//! history started from and a [journal](fuzzpaint_core::io::journal) of the trimmed steps on top of it, so
+//! that the whole history can be [rebuilt](rebuild), such as for a timelapse. Each document's spool may take
+//! up to [`Preferences::history_spool_mb`]. Past that, the next trim starts it anew from a snapshot of the
+//! start of the history kept in memory, truncating the oldest steps. Spools are deleted when their document is
+//! closed or the app exits, so any found unlocked at startup were left behind by a crash.
+//!
+//! [`Preferences::history_memory_mb`]: super::preferences::Preferences::history_memory_mb
+//! [`Preferences::history_spool_mb`]: super::preferences::Preferences::history_spool_mb
+
+use fuzzpaint_core::{
+    commands::DoUndo,
+    io::journal::{Base, Journal},
+    queue::{state_reader::CommandQueueCloneLock, DocumentCommandQueue, History},
+    state::document::ID,
+};
+use std::sync::Arc;
+
+/// How long the worker waits for changes before checking again.
+const POLL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Fraction of the memory budget history is trimmed down to, as a numerator and denominator.
+pub const TRIM_TO: (usize, usize) = (3, 4);
+const MEGABYTE: u64 = 1024 * 1024;
+
+struct Spool {
+    /// The state the journal replays onto.
+    snapshot: std::path::PathBuf,
+    /// Steps trimmed since the snapshot, oldest first.
+    journal: Journal,
+    /// Steps before the snapshot were trimmed without being spooled, so it isn't the start of the document.
+    truncated: bool,
+}
+impl Spool {
+    /// Bytes taken up on disk.
+    fn len(&self) -> std::io::Result<u64> {
+        Ok(
+            std::fs::metadata(&self.snapshot)?.len()
+                + std::fs::metadata(self.journal.path())?.len(),
+        )
+    }
+    /// Close and delete the journal and its snapshot.
+    fn discard(self) {
+        if let Err(err) = self.journal.discard() {
+            tracing::warn!("failed to delete spooled history: {err}");
+        }
+        if let Err(err) = std::fs::remove_file(self.snapshot) {
+            tracing::warn!("failed to delete spooled history snapshot: {err}");
+        }
+    }
+}
+/// Each spool is locked on its own, so that the files of one are written without holding up the rest, and
+/// for as long as the history in memory is trimmed onto it. Empty until the first trim, and once finished.
+type Slot = Arc<parking_lot::Mutex<Option<Spool>>>;
+
+fn spools() -> &'static parking_lot::Mutex<hashbrown::HashMap<ID, Slot>> {
+    static SPOOLS: std::sync::OnceLock<parking_lot::Mutex<hashbrown::HashMap<ID, Slot>>> =
+        std::sync::OnceLock::new();
+    SPOOLS.get_or_init(Default::default)
+}
+
+/// Where spooled history is kept, created if needed.
+fn directory() -> anyhow::Result<std::path::PathBuf> {
+    let mut path =
+        dirs::data_local_dir().ok_or_else(|| anyhow::anyhow!("no local data directory"))?;
+    path.push(env!("CARGO_PKG_NAME"));
+    path.push("history");
+    std::fs::create_dir_all(&path)?;
+    Ok(path)
+}
+/// The file in `directory` of the document's spooled journal, or of its snapshot.
+fn file_of(directory: &std::path::Path, document: ID, extension: &str) -> std::path::PathBuf {
+    directory.join(format!(
+        "{}-{}.{extension}",
+        std::process::id(),
+        document.id()
+    ))
+}
+
+/// Start the worker that trims and spools the history of each document as it grows.
+pub fn spawn() -> std::io::Result<()> {
+    // Without somewhere to spool to, history is still trimmed.
+    if let Ok(directory) = directory() {
+        remove_leftovers(&directory);
+    }
+    std::thread::Builder::new()
+        .name("History spool worker".to_owned())
+        .spawn(|| {
+            let changes = super::provider().change_listener();
+            loop {
+                for id in changes.take_timeout(POLL) {
+                    if let Err(e) = trim(id) {
+                        tracing::warn!(document = %id, "failed to spool history: {e:#}");
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+/// Delete the spools left behind by instances that have since exited, found by their journals being
+/// unlocked. Snapshots are deleted along with their journal, or without one if it was never created.
+fn remove_leftovers(directory: &std::path::Path) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        let journal = match path.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("fzh" | "fzl") => path.clone(),
+            Some("fzp") => path.with_extension("fzh"),
+            _ => continue,
+        };
+        let in_use = match fuzzpaint_core::io::journal::in_use(&journal) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+            in_use => in_use.unwrap_or(true),
+        };
+        if !in_use {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+/// Trim the document's history if it holds more than the budget, spooling what was trimmed.
+fn trim(document: ID) -> anyhow::Result<()> {
+    let (memory_budget, spool_budget) = {
+        let preferences = super::preferences::Preferences::read();
+        (
+            usize::try_from(u64::from(preferences.history_memory_mb) * MEGABYTE)
+                .unwrap_or(usize::MAX),
+            u64::from(preferences.history_spool_mb) * MEGABYTE,
+        )
+    };
+    let Some(memory) =
+        super::provider().inspect(document, |queue| queue.undo_memory(super::points()))
+    else {
+        return Ok(());
+    };
+    if memory.iter().sum::<usize>() <= memory_budget {
+        return Ok(());
+    }
+    // The newest steps that fit.
+    let target = memory_budget / TRIM_TO.1 * TRIM_TO.0;
+    let mut held = 0;
+    let keep = memory
+        .iter()
+        .take_while(|&&memory| {
+            held += memory;
+            held <= target
+        })
+        .count();
+
+    let slot = spools().lock().entry(document).or_default().clone();
+    // Held until the trimmed steps are on disk, so that a rebuild never sees a gap between the spool and
+    // memory.
+    let mut spool = slot.lock();
+    let restart = spool_budget != 0
+        && match spool.as_ref() {
+            None => true,
+            Some(spool) => spool.len()? > spool_budget,
+        };
+    let Some((start, trimmed)) = super::provider().write(document, "history spool", |queue| {
+        // Before anything is trimmed from it.
+        let start = restart.then(|| (queue.is_trimmed(), queue.peek_start()));
+        (start, queue.trim_history(keep))
+    }) else {
+        // Closed in the meantime.
+        return Ok(());
+    };
+    tracing::debug!(document = %document, commands = trimmed.len(), keep, "trimmed history");
+    if spool_budget == 0 || restart {
+        if let Some(old) = spool.take() {
+            old.discard();
+        }
+    }
+    if let Some((truncated, start)) = start {
+        let (reader, history) = start?;
+        *spool = Some(begin(document, &reader, &history, truncated)?);
+    }
+    if let Some(current) = spool.as_mut() {
+        let written = trimmed
+            .iter()
+            .try_for_each(|command| current.journal.append(DoUndo::Do(command), super::points()));
+        if let Err(e) = written {
+            // Missing steps don't replay, don't leave it around.
+            if let Some(spool) = spool.take() {
+                spool.discard();
+            }
+            return Err(e.into());
+        }
+    }
+    // Unless it's been finished since.
+    if !spools()
+        .lock()
+        .get(&document)
+        .is_some_and(|current| Arc::ptr_eq(current, &slot))
+    {
+        if let Some(spool) = spool.take() {
+            spool.discard();
+        }
+    }
+    Ok(())
+}
+/// Start a spool from a snapshot of the start of history.
+fn begin(
+    document: ID,
+    reader: &CommandQueueCloneLock,
+    history: &History,
+    truncated: bool,
+) -> anyhow::Result<Spool> {
+    let directory = directory()?;
+    let snapshot = file_of(&directory, document, "fzp");
+    let journal = (|| -> anyhow::Result<Journal> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&snapshot)?);
+        let ids = fuzzpaint_core::io::write_into(
+            reader,
+            Some(history),
+            super::points(),
+            &mut file,
+            None,
+        )?
+        .ok_or_else(|| anyhow::anyhow!("snapshot written without history"))?;
+        file.into_inner()
+            .map_err(std::io::IntoInnerError::into_error)?;
+        Ok(Journal::create(
+            file_of(&directory, document, "fzh"),
+            Some(&Base::of(&snapshot)?),
+            ids,
+        )?)
+    })();
+    match journal {
+        Ok(journal) => Ok(Spool {
+            snapshot,
+            journal,
+            truncated,
+        }),
+        Err(e) => {
+            let _ = std::fs::remove_file(&snapshot);
+            Err(e)
+        }
+    }
+}
+/// Whether the document's whole history, from the state it was opened or created with, can be
+/// [rebuilt](rebuild).
+#[must_use]
+pub fn is_complete(document: ID) -> bool {
+    let trimmed = super::provider()
+        .inspect(document, DocumentCommandQueue::is_trimmed)
+        .unwrap_or_default();
+    let Some(slot) = spools().lock().get(&document).cloned() else {
+        return !trimmed;
+    };
+    let spool = slot.lock();
+    match spool.as_ref() {
+        Some(spool) => !spool.truncated,
+        None => !trimmed,
+    }
+}
+/// Rebuild the document's history as a new queue, from the start of its spool to the present, such as to
+/// replay it for a timelapse. Returns `None` if nothing has been spooled, in which case the document's own
+/// history is all there is. The points of the spooled history are read anew.
+pub fn rebuild(document: ID) -> anyhow::Result<Option<DocumentCommandQueue>> {
+    let Some(slot) = spools().lock().get(&document).cloned() else {
+        return Ok(None);
+    };
+    // Held so that nothing is trimmed from memory onto the spool partway through.
+    let spool = slot.lock();
+    let Some(spool) = spool.as_ref() else {
+        return Ok(None);
+    };
+    let (_, history) = super::provider()
+        .inspect(document, |queue| queue.peek_history(usize::MAX))
+        .ok_or_else(|| anyhow::anyhow!("document closed"))??;
+    // The history in memory carries on from the spool, without being written into it.
+    let mut line = spool
+        .journal
+        .fork_at(spool.journal.path().with_extension("fzl"))?;
+    let queue = (|| -> anyhow::Result<DocumentCommandQueue> {
+        for command in &history.commands[..history.present] {
+            line.append(DoUndo::Do(command), super::points())?;
+        }
+        let (queue, ids) =
+            fuzzpaint_core::io::read_untitled_with_ids(&spool.snapshot, super::points())?;
+        let mut ids = ids.ok_or_else(|| anyhow::anyhow!("snapshot has no history"))?;
+        let spooled =
+            spool
+                .journal
+                .reader()?
+                .replay_continued(&queue, &mut ids, super::points())?;
+        let kept = line
+            .reader()?
+            .replay_continued(&queue, &mut ids, super::points())?;
+        tracing::debug!(document = %document, spooled, kept, "rebuilt history");
+        Ok(queue)
+    })();
+    if let Err(err) = line.discard() {
+        tracing::warn!("failed to delete rebuilt history: {err}");
+    }
+    queue.map(Some)
+}
+/// Delete the document's spooled history, once it is closed.
+pub fn finish(document: ID) {
+    let slot = spools().lock().remove(&document);
+    if let Some(spool) = slot.and_then(|slot| slot.lock().take()) {
+        spool.discard();
+    }
+}
+/// Delete all spooled history, as the app exits cleanly.
+pub fn finish_all() {
+    let slots: Vec<_> = spools().lock().drain().map(|(_, slot)| slot).collect();
+    for slot in slots {
+        if let Some(spool) = slot.lock().take() {
+            spool.discard();
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::config_file::ConfigFile;
 use crate::actions;
 
 const DOCUMENTATION: &str = r#"# Fuzzpaint hotkeys. You may edit this file, but be aware that formatting and comments will not
@@ -45,7 +46,6 @@ pub struct Hotkeys {
     keys_to_actions: actions::hotkeys::KeysToActions,
 }
 impl Hotkeys {
-    const FILENAME: &'static str = "hotkeys.toml";
     /// Shared read access to the global hotkeys.
     pub fn read() -> parking_lot::RwLockReadGuard<'static, Self> {
         Self::global().read()
@@ -66,98 +66,34 @@ impl Hotkeys {
 
         GLOBAL_HOTKEYS.get_or_init(|| Self::from_default_file().into())
     }
+    /// Returns the reason for read/write blockage, if any.
     #[must_use]
-    pub fn default_file_location() -> Option<std::path::PathBuf> {
-        let mut dir = preferences_dir()?;
-        dir.push(Self::FILENAME);
-        Some(dir)
-    }
-    /// Load from the default file location.
-    #[must_use]
-    pub fn from_default_file() -> Self {
-        Self::default_file_location()
-            .as_deref()
-            .map_or_else(Self::with_defaults, Self::load_or_default)
+    pub fn load_blocker(&self) -> Option<&LoadBlockReason> {
+        self.load_blocker.as_ref()
     }
+}
+impl ConfigFile for Hotkeys {
+    const FILENAME: &'static str = "hotkeys.toml";
+    const DESCRIPTION: &'static str = "hotkeys";
+    type File = actions::hotkeys::ActionsToKeys;
     /// Load default hotkeys from static memory.
-    #[must_use]
     fn with_defaults() -> Self {
-        use actions::hotkeys::ActionsToKeys;
-        let default = ActionsToKeys::default();
         // Default action map is reversable - this is assured by the default impl when debugging.
-        let reverse = (&default).try_into().unwrap();
-
-        Self {
-            load_blocker: None,
-            keys_to_actions: reverse,
-            actions_to_keys: default,
-        }
+        actions::hotkeys::ActionsToKeys::default()
+            .try_into()
+            .unwrap()
     }
-    /// Attempts to load the settings from the given path. On file-not-found, defaults. On other error, defaults with a load-blocking message for the user.
-    #[must_use]
-    fn load_or_default(path: &std::path::Path) -> Self {
-        use actions::hotkeys::{ActionsToKeys, KeysToActions};
-
-        // The parsed mappings, or an error. If the value is none, file not found - not actually an error.
-        let mappings: Result<Option<_>, LoadBlockReason> = try_block::try_block! {
-            let string = match std::fs::read_to_string(path) {
-                Ok(string) => string,
-                // File not found. This isn't an error, the file just doesn't exist. Write it!
-                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
-                // Other IO error, block loading.
-                Err(e) => return Err(e.into()),
-            };
-            // Parse and invert, reporting parse or inversion errors as necessary.
-            // Hehe, funny map syntax!
-            let actions_to_keys : ActionsToKeys = toml::from_str(&string)?;
-            let keys_to_actions : KeysToActions = (&actions_to_keys).try_into()?;
-
-            Ok(Some((actions_to_keys,keys_to_actions)))
-        };
-
-        match mappings {
-            // All went well~!
-            Ok(Some((actions_to_keys, keys_to_actions))) => Self {
-                load_blocker: None,
-                actions_to_keys,
-                keys_to_actions,
-            },
-            // File-not-found, write defaults.
-            Ok(None) => {
-                log::info!("hotkeys not found, defaulting");
-                Self::with_defaults()
-            }
-            // Some kind of error exists when parsing, load defaults and prevent writes until user clears the error.
-            Err(e) => {
-                log::error!("failed to load hotkeys: {e}");
-                // Take defaults but remember the error.
-                Self {
-                    load_blocker: Some(e),
-                    ..Self::with_defaults()
-                }
-            }
-        }
+    /// Invert the mappings, failing if one key is bound to many actions.
+    fn from_file(file: actions::hotkeys::ActionsToKeys) -> Result<Self, LoadBlockReason> {
+        Ok(file.try_into()?)
     }
-    /// Returns the reason for read/write blockage, if any.
-    #[must_use]
-    pub fn load_blocker(&self) -> Option<&LoadBlockReason> {
-        self.load_blocker.as_ref()
-    }
-    /// Save the loaded keys to the default location, overwriting contents.
-    /// *This should not be called if [`Self::load_blocker`] is `Some` unless the user explicitly called for it.*
-    pub fn save(&self) -> anyhow::Result<()> {
-        let mut preferences =
-            preferences_dir().ok_or_else(|| anyhow::anyhow!("No preferences dir found"))?;
-        // Explicity do *not* create recursively. If not found, the user probably has a good reason.
-        // Ignore errors (could already exist). Any real errors will be emitted by file access below.
-        let _ = std::fs::DirBuilder::new().create(&preferences);
-
-        preferences.push(Self::FILENAME);
-        let mut string = toml::ser::to_string_pretty(&self.actions_to_keys)?;
+    fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        let string = toml::ser::to_string_pretty(&self.actions_to_keys)?;
         // Prefix some documentation.
-        string = DOCUMENTATION.to_owned() + &string;
-        std::fs::write(preferences, string)?;
-        Ok(())
+        Ok(DOCUMENTATION.to_owned() + &string)
+    }
+    fn set_load_blocker(&mut self, blocker: LoadBlockReason) {
+        self.load_blocker = Some(blocker);
     }
 }
 impl TryFrom<crate::actions::hotkeys::ActionsToKeys> for Hotkeys {
@@ -0,0 +1,348 @@
+//! Journals of the changes made to each open document since it was saved, see
+//! [`fuzzpaint_core::io::journal`].
+//!
+//! Documents opened from or saved to a file are journaled on top of that save. Untitled documents are journaled
+//! on top of a snapshot of how they started, written beside the journal. Journals and their snapshots are
+//! deleted when their document is closed or the app exits cleanly, so any found unlocked at startup were left
+//! behind by a crash.
+//!
+//! Changes are appended as soon as they're made. Only commands are changes, so a stroke still being drawn when
+//! the app crashes is lost.
+
+use crate::i18n::tr;
+use fuzzpaint_core::{
+    io::{
+        history::FileIds,
+        journal::{Base, Journal, JournalReader},
+    },
+    queue::{state_reader::CommandQueueStateReader, DocumentCommandListener, DocumentCommandQueue},
+    state::document::ID,
+};
+use std::sync::Arc;
+
+struct Entry {
+    journal: Journal,
+    /// Changes the journal has yet to see.
+    listener: DocumentCommandListener,
+    /// The snapshot the journal replays onto, if the document is untitled.
+    snapshot: Option<std::path::PathBuf>,
+}
+impl Entry {
+    /// Close and delete the journal and its snapshot, once there's nothing left for them to recover.
+    fn discard(self) {
+        if let Err(err) = self.journal.discard() {
+            tracing::warn!("failed to delete journal: {err}");
+        }
+        if let Some(snapshot) = self.snapshot {
+            if let Err(err) = std::fs::remove_file(snapshot) {
+                tracing::warn!("failed to delete journal snapshot: {err}");
+            }
+        }
+    }
+}
+/// Each journal is locked on its own, so that the files of one are written without holding up the rest.
+/// Empty once finished.
+type Slot = Arc<parking_lot::Mutex<Option<Entry>>>;
+
+fn journals() -> &'static parking_lot::Mutex<hashbrown::HashMap<ID, Slot>> {
+    static JOURNALS: std::sync::OnceLock<parking_lot::Mutex<hashbrown::HashMap<ID, Slot>>> =
+        std::sync::OnceLock::new();
+    JOURNALS.get_or_init(Default::default)
+}
+
+/// Where journals are kept, created if needed.
+fn directory() -> anyhow::Result<std::path::PathBuf> {
+    let mut path =
+        dirs::data_local_dir().ok_or_else(|| anyhow::anyhow!("no local data directory"))?;
+    path.push(env!("CARGO_PKG_NAME"));
+    path.push("journals");
+    std::fs::create_dir_all(&path)?;
+    Ok(path)
+}
+/// The file in `directory` of the document's journal, or of its snapshot.
+fn file_of(directory: &std::path::Path, document: ID, extension: &str) -> std::path::PathBuf {
+    directory.join(format!(
+        "{}-{}.{extension}",
+        std::process::id(),
+        document.id()
+    ))
+}
+/// Whether the journal's base is a snapshot of an untitled document, rather than a save of the user's.
+fn is_snapshot(base: &std::path::Path) -> bool {
+    directory().is_ok_and(|directory| base.parent() == Some(directory.as_path()))
+}
+
+/// Begin journaling, or report why it couldn't be.
+fn begin(document: ID, entry: anyhow::Result<Entry>) {
+    match entry {
+        Ok(entry) => {
+            journals()
+                .lock()
+                .insert(document, Arc::new(parking_lot::Mutex::new(Some(entry))));
+        }
+        Err(e) => crate::errors::Report::new(
+            crate::errors::Severity::Recoverable,
+            tr!("journal-start-failed"),
+            &e,
+        )
+        .send(),
+    }
+}
+/// Start journaling the document's changes since it was saved to `path`, replacing any journal it had.
+/// `ids` are those the save was written or read with.
+pub fn start(queue: &DocumentCommandQueue, path: &std::path::Path, ids: FileIds) {
+    // Release the old journal's lock before its file is replaced.
+    finish(queue.id());
+    let entry = (|| -> anyhow::Result<Entry> {
+        let file = file_of(&directory()?, queue.id(), "fzj");
+        Ok(Entry {
+            journal: Journal::create(file, Some(&Base::of(path)?), ids)?,
+            listener: queue.listen_from_saved(),
+            snapshot: None,
+        })
+    })();
+    begin(queue.id(), entry);
+}
+/// Start journaling a new, untitled document from how it is now. It must not have been shared yet, so that
+/// nothing changes it between the snapshot and the journal's start.
+pub fn start_untitled(queue: &DocumentCommandQueue) {
+    finish(queue.id());
+    let directory = match directory() {
+        Ok(directory) => directory,
+        Err(e) => {
+            begin(queue.id(), Err(e));
+            return;
+        }
+    };
+    let snapshot = file_of(&directory, queue.id(), "fzp");
+    let entry = (|| -> anyhow::Result<Entry> {
+        let (reader, history) = queue.peek_history(usize::MAX)?;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&snapshot)?);
+        let ids = fuzzpaint_core::io::write_into(
+            &reader,
+            Some(&history),
+            super::points(),
+            &mut file,
+            None,
+        )?
+        .ok_or_else(|| anyhow::anyhow!("snapshot written without history"))?;
+        file.into_inner()
+            .map_err(std::io::IntoInnerError::into_error)?;
+        Ok(Entry {
+            journal: Journal::create(
+                file_of(&directory, queue.id(), "fzj"),
+                Some(&Base::of(&snapshot)?),
+                ids,
+            )?,
+            listener: queue.listen_from_now(),
+            snapshot: Some(snapshot.clone()),
+        })
+    })();
+    if entry.is_err() {
+        let _ = std::fs::remove_file(&snapshot);
+    }
+    begin(queue.id(), entry);
+}
+/// Stop journaling the document and delete its journal, once it is closed.
+pub fn finish(document: ID) {
+    let slot = journals().lock().remove(&document);
+    if let Some(entry) = slot.and_then(|slot| slot.lock().take()) {
+        entry.discard();
+    }
+}
+/// Delete every journal, as the app exits cleanly.
+pub fn finish_all() {
+    let slots: Vec<_> = journals().lock().drain().map(|(_, slot)| slot).collect();
+    for slot in slots {
+        if let Some(entry) = slot.lock().take() {
+            entry.discard();
+        }
+    }
+}
+
+/// Start the worker that appends changes to each journal as they happen.
+pub fn spawn() -> std::io::Result<()> {
+    std::thread::Builder::new()
+        .name("Journal worker".to_owned())
+        .spawn(|| {
+            let changes = super::provider().change_listener();
+            loop {
+                for id in changes.wait() {
+                    let Some(slot) = journals().lock().get(&id).cloned() else {
+                        continue;
+                    };
+                    let mut entry = slot.lock();
+                    let Some(result) = entry.as_mut().map(append) else {
+                        // Finished in the meantime.
+                        continue;
+                    };
+                    if let Err(e) = result {
+                        // Stop journaling rather than leave a journal that won't replay to be found after a
+                        // crash.
+                        if let Some(entry) = entry.take() {
+                            entry.discard();
+                        }
+                        drop(entry);
+                        let mut journals = journals().lock();
+                        // Unless it's been started anew since.
+                        if journals
+                            .get(&id)
+                            .is_some_and(|current| Arc::ptr_eq(current, &slot))
+                        {
+                            journals.remove(&id);
+                        }
+                        crate::errors::Report::new(
+                            crate::errors::Severity::Recoverable,
+                            tr!("journal-write-failed"),
+                            &e,
+                        )
+                        .send();
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+/// Append the changes the worker hasn't yet, without waiting for it. Gives up on each journal after `timeout` if
+/// it's locked, such as by a thread that panicked while holding it.
+pub fn flush(timeout: std::time::Duration) {
+    let Some(slots) = journals()
+        .try_lock_for(timeout)
+        .map(|journals| journals.values().cloned().collect::<Vec<_>>())
+    else {
+        tracing::warn!("journals locked, failed to flush");
+        return;
+    };
+    for slot in slots {
+        let Some(mut entry) = slot.try_lock_for(timeout) else {
+            tracing::warn!("journal locked, failed to flush");
+            continue;
+        };
+        if let Some(Err(err)) = entry.as_mut().map(append) {
+            tracing::warn!("failed to flush journal: {err:#}");
+        }
+    }
+}
+fn append(entry: &mut Entry) -> anyhow::Result<()> {
+    let state = entry.listener.forward_clone_state()?;
+    for change in state.changes() {
+        entry.journal.append(change, super::points())?;
+    }
+    Ok(())
+}
+
+/// A journal left behind by a crash.
+pub struct Leftover {
+    pub path: std::path::PathBuf,
+    /// The save it replays onto, or None if the document was untitled.
+    pub base: Option<std::path::PathBuf>,
+    /// The snapshot it replays onto instead, if the document was untitled.
+    snapshot: Option<std::path::PathBuf>,
+    pub changes: usize,
+}
+impl Leftover {
+    /// Delete the journal, and its snapshot if any, without recovering it.
+    pub fn discard(&self) -> std::io::Result<()> {
+        std::fs::remove_file(&self.path)?;
+        if let Some(snapshot) = &self.snapshot {
+            std::fs::remove_file(snapshot)?;
+        }
+        Ok(())
+    }
+}
+/// Find the journals left behind by a crash. Those with nothing to recover are deleted.
+#[must_use]
+pub fn leftover() -> Vec<Leftover> {
+    let entries = match directory().and_then(|dir| Ok(std::fs::read_dir(dir)?)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("failed to read journals: {e:#}");
+            return Vec::new();
+        }
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "fzj" {
+                return None;
+            }
+            // Open in this or another instance.
+            if fuzzpaint_core::io::journal::in_use(&path).unwrap_or(true) {
+                return None;
+            }
+            let reader = match JournalReader::open(&path) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    tracing::warn!(path = %path.display(), "failed to read journal: {err}");
+                    return None;
+                }
+            };
+            let base = reader.base().map(|base| base.path.clone());
+            let (base, snapshot) = match base {
+                Some(base) if is_snapshot(&base) => (None, Some(base)),
+                base => (base, None),
+            };
+            let changes = reader.changes().ok()?;
+            let leftover = Leftover {
+                path,
+                base,
+                snapshot,
+                changes,
+            };
+            if changes == 0 {
+                let _ = leftover.discard();
+                return None;
+            }
+            Some(leftover)
+        })
+        .collect()
+}
+/// Replay a leftover journal on top of its save, continuing to journal the recovered document in its place.
+/// The recovered changes are unsaved, as is all of an untitled document.
+pub fn recover(leftover: &Leftover) -> anyhow::Result<DocumentCommandQueue> {
+    let reader = JournalReader::open(&leftover.path)?;
+    let base = reader
+        .base()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("journal has no save to replay onto"))?;
+    if !base.is_unchanged()? {
+        anyhow::bail!(
+            "{} has been written over since the journal began",
+            base.path.display()
+        );
+    }
+    if leftover.snapshot.is_some() {
+        let (queue, ids) = fuzzpaint_core::io::read_untitled_with_ids(&base.path, super::points())?;
+        let ids = ids.ok_or_else(|| anyhow::anyhow!("snapshot has no history"))?;
+        let replayed = reader.replay(&queue, ids, super::points())?;
+        tracing::info!(replayed, "recovered untitled document from journal");
+        // Journaled anew from a snapshot of its own.
+        start_untitled(&queue);
+        leftover.discard()?;
+        return Ok(queue);
+    }
+    let (queue, ids) = super::file_locks::open_with_ids(
+        &base.path,
+        false,
+        &fuzzpaint_core::progress::Progress::detached(),
+    )?;
+    if queue.is_read_only() {
+        anyhow::bail!("{} is open elsewhere", base.path.display());
+    }
+    let Some(ids) = ids else {
+        super::file_locks::release(queue.id());
+        anyhow::bail!("{} has no history", base.path.display());
+    };
+    // Journal the recovered changes anew, from the same save.
+    start(&queue, &base.path, ids.to_file());
+    match reader.replay(&queue, ids, super::points()) {
+        Ok(replayed) => tracing::info!(replayed, "recovered changes from journal"),
+        Err(e) => {
+            finish(queue.id());
+            super::file_locks::release(queue.id());
+            return Err(e.into());
+        }
+    }
+    leftover.discard()?;
+    Ok(queue)
+}
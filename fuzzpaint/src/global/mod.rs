@@ -1,7 +1,22 @@
 //! Global singletons.
 
+pub mod brush_presets;
+pub mod clipboard;
+pub mod config_file;
+pub mod file_locks;
+pub mod history_spool;
 pub mod hotkeys;
+pub mod journals;
+pub mod palettes;
+pub mod preferences;
 mod provider;
+pub mod recent_colors;
+pub mod rulers;
+pub mod selection;
+pub mod session;
+pub mod tasks;
+pub mod templates;
+pub mod wake;
 
 pub use provider::provider;
 
@@ -0,0 +1,96 @@
+//! Named palettes, saved to the user's preferences and shared between all documents.
+
+use super::config_file::ConfigFile;
+use fuzzpaint_core::color::Color;
+
+const DOCUMENTATION: &str = r#"# Fuzzpaint palettes. You may edit this file, but be aware that formatting and comments will not
+# be preserved.
+
+# Each [[palette]] has a name and a list of colors. Colors are written as [r, g, b, a] in
+# premultiplied, linear space. Values outside of [0, 1] are allowed.
+
+# Example:
+# [[palette]]
+# name = "Grayscale"
+# colors = [[0.0, 0.0, 0.0, 1.0], [0.5, 0.5, 0.5, 1.0], [1.0, 1.0, 1.0, 1.0]]
+
+"#;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct NamedPalette {
+    pub name: String,
+    pub colors: Vec<[f32; 4]>,
+}
+impl NamedPalette {
+    /// Iterate the valid colors of this palette, skipping any that are not finite.
+    pub fn iter_colors(&self) -> impl Iterator<Item = Color> + '_ {
+        self.colors
+            .iter()
+            .filter_map(|&color| Color::from_array_lossy(color).ok())
+    }
+}
+
+/// On-disk representation. TOML needs a table at the top level.
+#[derive(serde::Deserialize)]
+pub struct PalettesFile {
+    #[serde(default, rename = "palette")]
+    palettes: Vec<NamedPalette>,
+}
+
+pub struct Palettes {
+    pub load_blocker: Option<super::hotkeys::LoadBlockReason>,
+    pub palettes: Vec<NamedPalette>,
+}
+impl Palettes {
+    /// Shared read access to the global palettes.
+    pub fn read() -> parking_lot::RwLockReadGuard<'static, Self> {
+        Self::global().read()
+    }
+    /// Exclusive write access to the global palettes.
+    pub fn write() -> parking_lot::RwLockWriteGuard<'static, Self> {
+        Self::global().write()
+    }
+    fn global() -> &'static parking_lot::RwLock<Self> {
+        static GLOBAL_PALETTES: std::sync::OnceLock<parking_lot::RwLock<Palettes>> =
+            std::sync::OnceLock::new();
+
+        GLOBAL_PALETTES.get_or_init(|| Self::from_default_file().into())
+    }
+    /// Returns the reason for read/write blockage, if any.
+    #[must_use]
+    pub fn load_blocker(&self) -> Option<&super::hotkeys::LoadBlockReason> {
+        self.load_blocker.as_ref()
+    }
+}
+impl ConfigFile for Palettes {
+    const FILENAME: &'static str = "palettes.toml";
+    const DESCRIPTION: &'static str = "palettes";
+    type File = PalettesFile;
+    fn with_defaults() -> Self {
+        Self {
+            load_blocker: None,
+            palettes: Vec::new(),
+        }
+    }
+    fn from_file(file: PalettesFile) -> Result<Self, super::hotkeys::LoadBlockReason> {
+        Ok(Self {
+            load_blocker: None,
+            palettes: file.palettes,
+        })
+    }
+    fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        // Serialize a borrowed view, to avoid cloning every palette.
+        #[derive(serde::Serialize)]
+        struct PalettesFileRef<'a> {
+            #[serde(rename = "palette")]
+            palettes: &'a [NamedPalette],
+        }
+        let string = toml::ser::to_string_pretty(&PalettesFileRef {
+            palettes: &self.palettes,
+        })?;
+        Ok(DOCUMENTATION.to_owned() + &string)
+    }
+    fn set_load_blocker(&mut self, blocker: super::hotkeys::LoadBlockReason) {
+        self.load_blocker = Some(blocker);
+    }
+}
@@ -0,0 +1,331 @@
+//! General application settings, saved to the user's preferences.
+
+use super::config_file::ConfigFile;
+use fuzzpaint_core::units::Unit;
+
+const DOCUMENTATION: &str = r"# Fuzzpaint settings. You may edit this file, but be aware that formatting and comments will not
+# be preserved. Missing values are defaulted.
+
+# language is the tag of the language to show the interface in, such as en-US. Translations are read from the
+# locales folder beside this file. If missing, English is used.
+
+# ui_scale multiplies the size of the interface, on top of the scale requested by the operating system.
+
+# low_latency presents frames as soon as they are ready, at the cost of power and possible tearing.
+
+# smart_zoom redraws strokes at the viewport's resolution when zoomed in, rather than magnifying the document
+# image, and composites layers at a reduced resolution when zoomed far out.
+
+# preview_buffers is how many images the document view cycles through, 2 or 3. With 3, the renderer may finish
+# the next image while the last waits to be shown, which can be smoother on high refresh rate displays at the
+# cost of memory. Takes effect after restarting.
+
+# backups is how many previous versions of a document to keep beside it when saving over it, named
+# like drawing.fzp.1.bak from newest to oldest.
+
+# saved_history is how many steps of undo, and of redo, to keep in a document when saving it.
+
+# history_memory_mb is how many megabytes of memory the undo history of each open document may hold. Past it,
+# the oldest steps are trimmed away and written to the history folder beside the journals, where they can no
+# longer be undone but are still replayed by timelapses. history_spool_mb is how many megabytes each document's
+# trimmed steps may take up there before the oldest are deleted. 0 deletes them straight away.
+
+# out_of_bounds is what becomes of stroke points drawn off the edge of the document. retain keeps them,
+# cutting the stroke off at the edge, and clamp pins them to the edge so the stroke runs along it.
+
+# units is what lengths such as brush sizes, rulers, and document dimensions are shown and entered in: pixel,
+# millimeter, or inch. Physical units are converted at the document's resolution.
+
+# pressure_smoothing_ms is how long, in milliseconds, tablet pressure takes to settle on a new reading, evening
+# out noisy tablets. Position is unaffected. 0 disables it.
+
+# device is the name of the graphics device to use. If missing, or if the device is not found, one is chosen
+# automatically.
+
+# [window] options make fuzzpaint usable as a tracing overlay. transparent shows the windows beneath through the
+# canvas, always_on_top keeps it above them, and borderless hides the title bar.
+
+# [document_edge] is how the document meets the background of the view. antialias smooths its edge at
+# fractional zooms, and border outlines it with a faint line.
+
+# [pressure_curve] remaps tablet pressure to (raw / saturation) ^ gamma. Set by calibrating in the settings.
+
+# [mouse_pressure] is made up for input without pressure, such as a mouse. pressure is that of a slow stroke,
+# which fades in from zero over fade_in_ms milliseconds, and fades out as the pointer approaches fade_speed
+# pixels per second. Zero disables either fade.
+
+# [tablet_mapping] is where the tablet points to. target is desktop, as the system maps it, window, or canvas.
+# mode is absolute, each point of the tablet being a point of the target, or relative, moving the pointer as
+# the pen moves like a mouse, at speed times the distance. keep_aspect shrinks the target to tablet_aspect, the
+# width over the height of the tablet's active area, so circles stay round. The pen is only seen while over the
+# window as the system maps it, so window and canvas work best with the window maximized.
+
+# [startup] is how fuzzpaint starts. tool is the tool chosen, such as brush, eraser, or lasso. brush_preset is
+# the name of the brush preset to start with. reopen picks up where it was last closed, reopening the documents
+# open then with their views and selected layers, if no others are given, and choosing the tool chosen then.
+# That session is kept in session.toml. new_document makes a new document if none are opened. template is the
+# name of the template new documents start from, otherwise the first built in one.
+
+# [layout] lists which panels are shown in the left, right, and bottom docks, in order.
+# Panels not listed are hidden.
+
+";
+
+/// Serde stand-in for [`Unit`], which the core crate doesn't derive for.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(remote = "Unit", rename_all = "snake_case")]
+enum UnitDef {
+    Pixel,
+    Millimeter,
+    Inch,
+}
+
+/// How fuzzpaint starts.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Startup {
+    pub tool: crate::pen_tools::StateLayer,
+    /// Name of a [brush preset](super::brush_presets), or None for the plain round brush.
+    pub brush_preset: Option<String>,
+    /// Restore the [session](super::session) left when fuzzpaint was last closed.
+    pub reopen: bool,
+    /// Make a new document if none are opened.
+    pub new_document: bool,
+    /// Name of the [template](super::templates) new documents start from, or None for the first built in one.
+    pub template: Option<String>,
+}
+
+/// On-disk representation. Every field defaults, so that older files continue to load.
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct PreferencesFile {
+    language: Option<String>,
+    ui_scale: f32,
+    low_latency: bool,
+    smart_zoom: bool,
+    preview_buffers: u32,
+    backups: usize,
+    saved_history: usize,
+    history_memory_mb: u32,
+    history_spool_mb: u32,
+    pressure_smoothing_ms: u32,
+    out_of_bounds: crate::pen_tools::OutOfBounds,
+    #[serde(with = "UnitDef")]
+    units: Unit,
+    device: Option<String>,
+    window: crate::window::WindowOptions,
+    document_edge: crate::document_viewport_proxy::DocumentEdge,
+    pressure_curve: crate::stylus_events::PressureCurve,
+    mouse_pressure: crate::stylus_events::SimulatedPressure,
+    tablet_mapping: crate::stylus_events::TabletMapping,
+    startup: Startup,
+    layout: crate::ui::layout::Layout,
+}
+impl Default for PreferencesFile {
+    fn default() -> Self {
+        Self {
+            language: None,
+            ui_scale: 1.0,
+            low_latency: false,
+            smart_zoom: true,
+            preview_buffers: 2,
+            backups: 1,
+            saved_history: 64,
+            history_memory_mb: 256,
+            history_spool_mb: 512,
+            pressure_smoothing_ms: 0,
+            out_of_bounds: crate::pen_tools::OutOfBounds::default(),
+            units: Unit::default(),
+            device: None,
+            window: crate::window::WindowOptions::default(),
+            document_edge: crate::document_viewport_proxy::DocumentEdge::default(),
+            pressure_curve: crate::stylus_events::PressureCurve::default(),
+            mouse_pressure: crate::stylus_events::SimulatedPressure::default(),
+            tablet_mapping: crate::stylus_events::TabletMapping::default(),
+            startup: Startup::default(),
+            layout: crate::ui::layout::Layout::default(),
+        }
+    }
+}
+
+pub struct Preferences {
+    pub load_blocker: Option<super::hotkeys::LoadBlockReason>,
+    /// Tag of the language the interface is shown in, or None for English. See [`crate::i18n`].
+    pub language: Option<String>,
+    /// User multiplier on top of the window's scale factor. Always within [`Self::UI_SCALE_RANGE`].
+    pub ui_scale: f32,
+    /// Prefer presenting immediately over waiting for vertical sync, and avoid queuing frames. `--no-vsync` forces
+    /// it for the session without changing this, see [`crate::render_device::wants_low_latency`].
+    pub low_latency: bool,
+    /// Re-rasterize the visible strokes when zoomed in, instead of magnifying the document image, and composite
+    /// at a lower level of detail when zoomed out.
+    pub smart_zoom: bool,
+    /// Count of images the document view cycles through. Always within [`Self::PREVIEW_BUFFERS_RANGE`].
+    /// Takes effect on restart.
+    pub preview_buffers: u32,
+    /// Count of previous versions of a document to keep when saving over it.
+    pub backups: usize,
+    /// Count of undo steps, and of redo steps, to embed in a document when saving it.
+    pub saved_history: usize,
+    /// Megabytes of memory the undo history of each open document may hold, always within
+    /// [`Self::HISTORY_MEMORY_RANGE`]. See [`super::history_spool`].
+    pub history_memory_mb: u32,
+    /// Megabytes of history trimmed from each document to keep on disk, or 0 for none. Always within
+    /// [`Self::HISTORY_SPOOL_RANGE`].
+    pub history_spool_mb: u32,
+    /// Time constant of the filter smoothing tablet pressure, in milliseconds, or 0 for none. Always within
+    /// [`Self::PRESSURE_SMOOTHING_RANGE`]. See [`crate::stylus_events::PressureFilter`].
+    pub pressure_smoothing_ms: u32,
+    /// Applied to stroke points as they're drawn.
+    pub out_of_bounds: crate::pen_tools::OutOfBounds,
+    /// Lengths are shown and entered in this unit, but always stored in pixels.
+    pub units: Unit,
+    /// Name of the physical device to use, or None to choose automatically. Takes effect on restart.
+    pub device: Option<String>,
+    /// How the main window sits among others, applied as soon as it changes.
+    pub window: crate::window::WindowOptions,
+    /// Drawn around the document in the viewport, applied as soon as it changes.
+    pub document_edge: crate::document_viewport_proxy::DocumentEdge,
+    /// Applied to tablet pressure before it reaches the tools.
+    pub pressure_curve: crate::stylus_events::PressureCurve,
+    /// Stands in for pressure from input that has none.
+    pub mouse_pressure: crate::stylus_events::SimulatedPressure,
+    /// Where tablet positions are mapped to, applied as soon as it changes.
+    pub tablet_mapping: crate::stylus_events::TabletMapping,
+    /// Applied as fuzzpaint starts.
+    pub startup: Startup,
+    pub layout: crate::ui::layout::Layout,
+}
+impl Preferences {
+    pub const UI_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+    pub const PREVIEW_BUFFERS_RANGE: std::ops::RangeInclusive<u32> =
+        crate::document_viewport_proxy::Proxy::BUFFERS_RANGE;
+    pub const BACKUPS_RANGE: std::ops::RangeInclusive<usize> = 0..=10;
+    pub const SAVED_HISTORY_RANGE: std::ops::RangeInclusive<usize> = 0..=1024;
+    pub const HISTORY_MEMORY_RANGE: std::ops::RangeInclusive<u32> = 16..=8192;
+    pub const HISTORY_SPOOL_RANGE: std::ops::RangeInclusive<u32> = 0..=8192;
+    pub const PRESSURE_SMOOTHING_RANGE: std::ops::RangeInclusive<u32> = 0..=200;
+    /// Shared read access to the global preferences.
+    pub fn read() -> parking_lot::RwLockReadGuard<'static, Self> {
+        Self::global().read()
+    }
+    /// Exclusive write access to the global preferences.
+    pub fn write() -> parking_lot::RwLockWriteGuard<'static, Self> {
+        Self::global().write()
+    }
+    fn global() -> &'static parking_lot::RwLock<Self> {
+        static GLOBAL_PREFERENCES: std::sync::OnceLock<parking_lot::RwLock<Preferences>> =
+            std::sync::OnceLock::new();
+
+        GLOBAL_PREFERENCES.get_or_init(|| Self::from_default_file().into())
+    }
+    /// From the file's contents, clamped to the ranges above.
+    fn sanitized(file: PreferencesFile) -> Self {
+        Self {
+            load_blocker: None,
+            language: file.language,
+            ui_scale: if file.ui_scale.is_finite() {
+                file.ui_scale
+                    .clamp(*Self::UI_SCALE_RANGE.start(), *Self::UI_SCALE_RANGE.end())
+            } else {
+                1.0
+            },
+            low_latency: file.low_latency,
+            smart_zoom: file.smart_zoom,
+            preview_buffers: file.preview_buffers.clamp(
+                *Self::PREVIEW_BUFFERS_RANGE.start(),
+                *Self::PREVIEW_BUFFERS_RANGE.end(),
+            ),
+            backups: file.backups.min(*Self::BACKUPS_RANGE.end()),
+            saved_history: file.saved_history.min(*Self::SAVED_HISTORY_RANGE.end()),
+            history_memory_mb: file.history_memory_mb.clamp(
+                *Self::HISTORY_MEMORY_RANGE.start(),
+                *Self::HISTORY_MEMORY_RANGE.end(),
+            ),
+            history_spool_mb: file.history_spool_mb.min(*Self::HISTORY_SPOOL_RANGE.end()),
+            pressure_smoothing_ms: file
+                .pressure_smoothing_ms
+                .min(*Self::PRESSURE_SMOOTHING_RANGE.end()),
+            out_of_bounds: file.out_of_bounds,
+            units: file.units,
+            device: file.device,
+            window: file.window,
+            document_edge: file.document_edge,
+            pressure_curve: file.pressure_curve.sanitized(),
+            mouse_pressure: file.mouse_pressure.sanitized(),
+            tablet_mapping: file.tablet_mapping.sanitized(),
+            startup: file.startup,
+            layout: file.layout.deduplicated(),
+        }
+    }
+    /// Returns the reason for read/write blockage, if any.
+    #[must_use]
+    pub fn load_blocker(&self) -> Option<&super::hotkeys::LoadBlockReason> {
+        self.load_blocker.as_ref()
+    }
+}
+impl ConfigFile for Preferences {
+    const FILENAME: &'static str = "settings.toml";
+    const DESCRIPTION: &'static str = "preferences";
+    type File = PreferencesFile;
+    fn with_defaults() -> Self {
+        Self::sanitized(PreferencesFile::default())
+    }
+    fn from_file(file: PreferencesFile) -> Result<Self, super::hotkeys::LoadBlockReason> {
+        Ok(Self::sanitized(file))
+    }
+    fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        // Serialize a borrowed view, to avoid cloning every field.
+        #[derive(serde::Serialize)]
+        struct PreferencesFileRef<'a> {
+            language: Option<&'a str>,
+            ui_scale: f32,
+            low_latency: bool,
+            smart_zoom: bool,
+            preview_buffers: u32,
+            backups: usize,
+            saved_history: usize,
+            history_memory_mb: u32,
+            history_spool_mb: u32,
+            pressure_smoothing_ms: u32,
+            out_of_bounds: crate::pen_tools::OutOfBounds,
+            #[serde(with = "UnitDef")]
+            units: Unit,
+            // Must precede the tables.
+            device: Option<&'a str>,
+            window: crate::window::WindowOptions,
+            document_edge: crate::document_viewport_proxy::DocumentEdge,
+            pressure_curve: crate::stylus_events::PressureCurve,
+            mouse_pressure: crate::stylus_events::SimulatedPressure,
+            tablet_mapping: crate::stylus_events::TabletMapping,
+            startup: &'a Startup,
+            layout: &'a crate::ui::layout::Layout,
+        }
+        let string = toml::ser::to_string_pretty(&PreferencesFileRef {
+            language: self.language.as_deref(),
+            ui_scale: self.ui_scale,
+            low_latency: self.low_latency,
+            smart_zoom: self.smart_zoom,
+            preview_buffers: self.preview_buffers,
+            backups: self.backups,
+            saved_history: self.saved_history,
+            history_memory_mb: self.history_memory_mb,
+            history_spool_mb: self.history_spool_mb,
+            pressure_smoothing_ms: self.pressure_smoothing_ms,
+            out_of_bounds: self.out_of_bounds,
+            units: self.units,
+            device: self.device.as_deref(),
+            window: self.window,
+            document_edge: self.document_edge,
+            pressure_curve: self.pressure_curve,
+            mouse_pressure: self.mouse_pressure,
+            tablet_mapping: self.tablet_mapping,
+            startup: &self.startup,
+            layout: &self.layout,
+        })?;
+        Ok(DOCUMENTATION.to_owned() + &string)
+    }
+    fn set_load_blocker(&mut self, blocker: super::hotkeys::LoadBlockReason) {
+        self.load_blocker = Some(blocker);
+    }
+}
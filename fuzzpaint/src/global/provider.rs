@@ -6,13 +6,120 @@
 //! on a server, ect.
 
 use fuzzpaint_core::{queue::DocumentCommandQueue, state::document::ID};
+use std::sync::{Arc, Weak};
+
+/// Documents changed since a [`ChangeListener`] last looked.
+#[derive(Default)]
+struct DirtySet {
+    dirty: parking_lot::Mutex<hashbrown::HashSet<ID>>,
+    /// Notified when `dirty` becomes non-empty.
+    changed: parking_lot::Condvar,
+}
+impl DirtySet {
+    fn mark(&self, id: ID) {
+        let mut dirty = self.dirty.lock();
+        dirty.insert(id);
+        self.changed.notify_all();
+    }
+}
+/// Receives the IDs of documents that have been opened or modified.
+///
+/// Any number of changes to a document between reads are coalesced into one, so the listener never lags behind
+/// no matter how slowly it is read, and never blocks the documents' writers.
+pub struct ChangeListener {
+    set: Arc<DirtySet>,
+}
+impl ChangeListener {
+    /// Take every document changed since the last call, without blocking. Empty if none.
+    #[must_use]
+    pub fn take(&self) -> hashbrown::HashSet<ID> {
+        std::mem::take(&mut *self.set.dirty.lock())
+    }
+    /// Take every document changed since the last call, waiting up to `timeout` for a change
+    /// if there are none yet. Empty if the timeout elapsed.
+    #[must_use]
+    pub fn take_timeout(&self, timeout: std::time::Duration) -> hashbrown::HashSet<ID> {
+        let mut dirty = self.set.dirty.lock();
+        if dirty.is_empty() {
+            // Spurious wakeups and timeouts alike just result in an empty set.
+            let _ = self.set.changed.wait_for(&mut dirty, timeout);
+        }
+        std::mem::take(&mut *dirty)
+    }
+    /// Take every document changed since the last call, waiting as long as it takes for a change if there are
+    /// none yet.
+    #[must_use]
+    pub fn wait(&self) -> hashbrown::HashSet<ID> {
+        let mut dirty = self.set.dirty.lock();
+        while dirty.is_empty() {
+            self.set.changed.wait(&mut dirty);
+        }
+        std::mem::take(&mut *dirty)
+    }
+}
+
+/// How long a writer waits on another before warning of a possible deadlock.
+const WRITE_WAIT_WARNING: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Who holds a document's [`WriteToken`].
+#[derive(Clone, Copy, Debug)]
+struct Holder {
+    /// Names the work, such as "brush", for diagnostics.
+    name: &'static str,
+    thread: std::thread::ThreadId,
+    since: std::time::Instant,
+}
+/// Tickets handed out to writers of a document, served in the order they were taken.
+#[derive(Default)]
+struct Writers {
+    state: parking_lot::Mutex<WritersState>,
+    /// Notified whenever a writer finishes.
+    released: parking_lot::Condvar,
+}
+#[derive(Default)]
+struct WritersState {
+    next_ticket: u64,
+    serving: u64,
+    holder: Option<Holder>,
+}
+#[derive(thiserror::Error, Debug)]
+pub enum WriteError {
+    #[error("document not found")]
+    NotFound,
+    /// The thread asking already holds the token, and would wait on itself forever.
+    #[error("{0} already holds the document's write token on this thread")]
+    Reentrant(&'static str),
+}
+/// The exclusive right to modify a document, see [`Local::write_token`]. Released on drop, to the writer
+/// that has waited longest.
+pub struct WriteToken {
+    document: ID,
+    writers: Arc<Writers>,
+}
+impl WriteToken {
+    #[must_use]
+    pub fn document(&self) -> ID {
+        self.document
+    }
+}
+impl Drop for WriteToken {
+    fn drop(&mut self) {
+        let mut state = self.writers.state.lock();
+        state.holder = None;
+        state.serving += 1;
+        self.writers.released.notify_all();
+    }
+}
 
 struct PerDocument {
     queue: DocumentCommandQueue,
+    writers: Arc<Writers>,
 }
 /// A provider that keeps documents in-memory.
+#[derive(Default)]
 pub struct Local {
-    on_change: parking_lot::Mutex<bus::Bus<ChangeMessage>>,
+    /// The dirty sets of every live [`ChangeListener`].
+    on_change: parking_lot::Mutex<Vec<Weak<DirtySet>>>,
     // We don't expect high contention - will only be locked for writing when a new queue is inserted.
     documents: parking_lot::RwLock<hashbrown::HashMap<ID, PerDocument>>,
 }
@@ -25,12 +132,11 @@ impl Local {
         let new_id = new_document.id();
         let new_document = PerDocument {
             queue: new_document,
+            writers: Arc::default(),
         };
         self.documents.write().insert(new_id, new_document);
 
-        self.on_change
-            .lock()
-            .broadcast(ChangeMessage::Opened(new_id));
+        self.notify(new_id);
 
         new_id
     }
@@ -41,17 +147,21 @@ impl Local {
         match self.documents.write().entry(id) {
             hashbrown::hash_map::Entry::Occupied(_) => return Err(queue),
             hashbrown::hash_map::Entry::Vacant(v) => {
-                let queue = PerDocument { queue };
+                let queue = PerDocument {
+                    queue,
+                    writers: Arc::default(),
+                };
 
                 v.insert(queue);
             }
         }
 
-        self.on_change.lock().broadcast(ChangeMessage::Opened(id));
+        self.notify(id);
 
         Ok(())
     }
-    /// Call the given closure on the document queue with the given ID, if found.
+    /// Call the given closure on the document queue with the given ID, if found. For reading only, changes
+    /// go through [`Self::write`].
     pub fn inspect<F, T>(&self, id: ID, f: F) -> Option<T>
     where
         F: FnOnce(&DocumentCommandQueue) -> T,
@@ -67,56 +177,113 @@ impl Local {
         drop(read);
 
         if let Ok(true) = cursor.forward() {
-            self.on_change.lock().broadcast(ChangeMessage::Modified(id));
+            self.notify(id);
         }
 
         Some(result)
     }
+    /// Wait for the exclusive right to modify the document, in turn with any others waiting. `name` describes
+    /// the work for diagnostics - a writer kept waiting long enough that it may be deadlocked is logged along
+    /// with the holder it's waiting on.
+    ///
+    /// Tokens are cooperative, the queue itself still accepts writes from anyone. They order writers that
+    /// would otherwise contend on the queue with no guarantee of who goes first, such as a tool and the UI.
+    /// # Errors
+    /// If the document isn't open, or if this thread already holds its token.
+    pub fn write_token(&self, id: ID, name: &'static str) -> Result<WriteToken, WriteError> {
+        // Don't hold the document list while waiting, or new documents couldn't be opened meanwhile.
+        let writers = self
+            .documents
+            .read()
+            .get(&id)
+            .map(|data| data.writers.clone())
+            .ok_or(WriteError::NotFound)?;
+        let thread = std::thread::current().id();
+
+        let mut state = writers.state.lock();
+        if let Some(holder) = state.holder.filter(|holder| holder.thread == thread) {
+            tracing::error!(%id, name, holder = holder.name, "document write token taken reentrantly");
+            return Err(WriteError::Reentrant(holder.name));
+        }
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let start = std::time::Instant::now();
+        let mut warned = false;
+        while state.serving != ticket {
+            let timed_out = writers
+                .released
+                .wait_for(&mut state, WRITE_WAIT_WARNING)
+                .timed_out();
+            if timed_out && !warned && state.serving != ticket {
+                warned = true;
+                let holder = state.holder;
+                tracing::warn!(
+                    %id,
+                    name,
+                    waited = ?start.elapsed(),
+                    ahead = ticket - state.serving,
+                    holder = holder.map(|holder| holder.name),
+                    holder_thread = ?holder.map(|holder| holder.thread),
+                    held_for = ?holder.map(|holder| holder.since.elapsed()),
+                    "waiting on the document write token, possibly deadlocked",
+                );
+            }
+        }
+        if warned {
+            tracing::info!(%id, name, waited = ?start.elapsed(), "document write token acquired");
+        }
+        state.holder = Some(Holder {
+            name,
+            thread,
+            since: std::time::Instant::now(),
+        });
+        drop(state);
+        Ok(WriteToken {
+            document: id,
+            writers,
+        })
+    }
+    /// Like [`Self::inspect`], holding the document's [`WriteToken`] throughout. Prefer this for any
+    /// changes to the document, including undo and redo.
+    ///
+    /// None if the document isn't open, or if the token couldn't be taken, see [`Self::write_token`].
+    pub fn write<F, T>(&self, id: ID, name: &'static str, f: F) -> Option<T>
+    where
+        F: FnOnce(&DocumentCommandQueue) -> T,
+    {
+        let _token = self.write_token(id, name).ok()?;
+        self.inspect(id, f)
+    }
     /// Iterate over all the open documents, by ID.
     pub fn document_iter(&self) -> impl Iterator<Item = ID> {
         let ids: Vec<_> = self.documents.read().keys().copied().collect();
         ids.into_iter()
     }
-    /// Broadcast a `ProviderMessage::Modified` with the given ID to any change listeners.
+    /// Mark the document as changed to any change listeners.
     /// Ensures the ID is valid before sending.
     pub fn touch(&self, id: ID) {
         if self.documents.read().contains_key(&id) {
-            self.on_change.lock().broadcast(ChangeMessage::Modified(id));
+            self.notify(id);
         }
     }
-    /// Get a reciever of messages describing changes to the provider or it's documents.
-    /// Does not recieve old messages, use [`Self::document_iter`] to get up-to-date!
-    pub fn change_listener(&self) -> bus::BusReader<ChangeMessage> {
-        self.on_change.lock().add_rx()
+    /// Mark the document as changed in every listener, forgetting those that have been dropped.
+    fn notify(&self, id: ID) {
+        self.on_change.lock().retain(|set| {
+            let Some(set) = set.upgrade() else {
+                return false;
+            };
+            set.mark(id);
+            true
+        });
+        // The UI shows the documents too.
+        super::wake::wake(super::wake::Wake::Ui);
     }
-}
-impl Default for Local {
-    fn default() -> Self {
-        // Blocks on full, so choose a large number to avoid blocking user thread.
-        let on_change = bus::Bus::new(256);
-        Self {
-            on_change: on_change.into(),
-            documents: parking_lot::RwLock::default(),
-        }
-    }
-}
-#[derive(Copy, Clone, Debug)]
-pub enum ChangeMessage {
-    /// A new document has been made available to the provider.
-    Opened(ID),
-    /// A document has been modified, i.e. it is likely that existing listeners
-    /// will see new commands.
-    Modified(ID),
-    /// A document is no longer available.
-    Closed(ID),
-}
-impl ChangeMessage {
-    /// Gets the document this message refers to.
-    #[must_use]
-    pub fn id(&self) -> ID {
-        match self {
-            Self::Closed(id) | Self::Modified(id) | Self::Opened(id) => *id,
-        }
+    /// Get a listener for changes to the provider or it's documents.
+    /// Does not see old changes, use [`Self::document_iter`] to get up-to-date!
+    pub fn change_listener(&self) -> ChangeListener {
+        let set = Arc::<DirtySet>::default();
+        self.on_change.lock().push(Arc::downgrade(&set));
+        ChangeListener { set }
     }
 }
 
@@ -124,3 +291,72 @@ pub fn provider() -> &'static Local {
     static ONCE: std::sync::OnceLock<Local> = std::sync::OnceLock::new();
     ONCE.get_or_init(Default::default)
 }
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn changes_coalesce() {
+        let provider = super::Local::default();
+        let listener = provider.change_listener();
+        let a = provider.insert_new();
+        let b = provider.insert_new();
+        for _ in 0..1000 {
+            provider.touch(a);
+        }
+        let changed = listener.take();
+        assert_eq!(changed.len(), 2);
+        assert!(changed.contains(&a) && changed.contains(&b));
+        assert!(listener.take().is_empty());
+        assert!(listener
+            .take_timeout(std::time::Duration::from_millis(1))
+            .is_empty());
+        provider.touch(b);
+        assert_eq!(listener.wait().into_iter().collect::<Vec<_>>(), [b]);
+
+        // Dropped listeners are forgotten.
+        drop(listener);
+        provider.touch(a);
+        assert!(provider.on_change.lock().is_empty());
+    }
+    #[test]
+    fn writers_take_turns() {
+        use std::sync::Arc;
+        let provider = Arc::new(super::Local::default());
+        let document = provider.insert_new();
+        let order = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        let first = provider.write_token(document, "first").unwrap();
+        // Holding it already, asking again would never return.
+        assert!(matches!(
+            provider.write_token(document, "again"),
+            Err(super::WriteError::Reentrant("first"))
+        ));
+        let waiters: Vec<_> = (0..4)
+            .map(|idx| {
+                let (provider, order) = (provider.clone(), order.clone());
+                let waiter = std::thread::spawn(move || {
+                    let _token = provider.write_token(document, "waiter").unwrap();
+                    order.lock().push(idx);
+                });
+                // Wait for it to take its place in line.
+                while provider.documents.read()[&document]
+                    .writers
+                    .state
+                    .lock()
+                    .next_ticket
+                    < idx + 2
+                {
+                    std::thread::yield_now();
+                }
+                waiter
+            })
+            .collect();
+        assert!(order.lock().is_empty());
+        drop(first);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        // Served in the order they asked.
+        assert_eq!(*order.lock(), [0, 1, 2, 3]);
+    }
+}
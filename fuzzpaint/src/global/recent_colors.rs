@@ -0,0 +1,60 @@
+//! The colors most recently drawn with in each open document, most recent first.
+//!
+//! Fed by each stroke as it's committed, rather than by what's chosen in the color picker, so that going back
+//! to a color just painted with is one click. Paletted colors are kept by index, as they are in strokes.
+
+use fuzzpaint_core::{color::ColorOrPalette, state::document::ID};
+
+/// How many colors are remembered per document.
+pub const MAX: usize = 16;
+
+fn recent() -> &'static parking_lot::RwLock<hashbrown::HashMap<ID, Vec<ColorOrPalette>>> {
+    static RECENT: std::sync::OnceLock<
+        parking_lot::RwLock<hashbrown::HashMap<ID, Vec<ColorOrPalette>>>,
+    > = std::sync::OnceLock::new();
+    RECENT.get_or_init(Default::default)
+}
+
+/// Move the color to the front, or insert it there, forgetting the oldest past [`MAX`].
+fn hoist(colors: &mut Vec<ColorOrPalette>, color: ColorOrPalette) {
+    colors.retain(|&c| c != color);
+    colors.insert(0, color);
+    colors.truncate(MAX);
+}
+
+#[must_use]
+pub fn get(document: ID) -> Vec<ColorOrPalette> {
+    recent().read().get(&document).cloned().unwrap_or_default()
+}
+/// A stroke was drawn in this color.
+pub fn push(document: ID, color: ColorOrPalette) {
+    hoist(recent().write().entry(document).or_default(), color);
+}
+pub fn remove(document: ID) {
+    recent().write().remove(&document);
+}
+
+#[cfg(test)]
+mod test {
+    use fuzzpaint_core::color::{Color, ColorOrPalette};
+
+    #[test]
+    fn hoist() {
+        let color =
+            |v: f32| -> ColorOrPalette { Color::from_array_lossy([v, v, v, 1.0]).unwrap().into() };
+        let mut colors = Vec::new();
+        for idx in 0..super::MAX + 4 {
+            super::hoist(&mut colors, color(f32::from(u8::try_from(idx).unwrap())));
+        }
+        assert_eq!(colors.len(), super::MAX);
+        // Newest first, oldest gone.
+        assert_eq!(colors[0], color(19.0));
+        assert_eq!(colors[super::MAX - 1], color(4.0));
+
+        // Repeats move to the front rather than duplicating.
+        super::hoist(&mut colors, color(10.0));
+        assert_eq!(colors.len(), super::MAX);
+        assert_eq!(colors[0], color(10.0));
+        assert_eq!(colors.iter().filter(|&&c| c == color(10.0)).count(), 1);
+    }
+}
@@ -0,0 +1,36 @@
+//! The [ruler](fuzzpaint_core::ruler) of each open document.
+//!
+//! Like selections, rulers are not part of the document's history.
+
+use fuzzpaint_core::{ruler::Ruler, state::document::ID};
+
+#[derive(Clone, Copy)]
+pub struct Rulers {
+    pub ruler: Ruler,
+    /// Whether strokes follow the ruler. Kept separately so it can be toggled without losing its place.
+    pub enabled: bool,
+}
+
+fn rulers() -> &'static parking_lot::RwLock<hashbrown::HashMap<ID, Rulers>> {
+    static RULERS: std::sync::OnceLock<parking_lot::RwLock<hashbrown::HashMap<ID, Rulers>>> =
+        std::sync::OnceLock::new();
+    RULERS.get_or_init(Default::default)
+}
+
+#[must_use]
+pub fn get(document: ID) -> Option<Rulers> {
+    rulers().read().get(&document).copied()
+}
+/// The ruler strokes in the document should follow, if any.
+#[must_use]
+pub fn active(document: ID) -> Option<Ruler> {
+    get(document)
+        .filter(|rulers| rulers.enabled)
+        .map(|rulers| rulers.ruler)
+}
+pub fn set(document: ID, rulers: Rulers) {
+    self::rulers().write().insert(document, rulers);
+}
+pub fn remove(document: ID) {
+    rulers().write().remove(&document);
+}
@@ -0,0 +1,60 @@
+//! The active [selection](fuzzpaint_core::selection) of each open document.
+//!
+//! Selections are not part of the document's history. Only the strokes drawn within them remember them.
+
+use fuzzpaint_core::{repositories::points::PointCollectionID, state::document::ID};
+
+#[derive(Clone)]
+pub struct Selection {
+    /// The polygon, as stored in the [point repository](super::points).
+    pub id: PointCollectionID,
+    /// The same polygon, kept around for drawing the outline.
+    pub polygon: std::sync::Arc<[[f32; 2]]>,
+}
+
+fn selections() -> &'static parking_lot::RwLock<hashbrown::HashMap<ID, Selection>> {
+    static SELECTIONS: std::sync::OnceLock<parking_lot::RwLock<hashbrown::HashMap<ID, Selection>>> =
+        std::sync::OnceLock::new();
+    SELECTIONS.get_or_init(Default::default)
+}
+static SHOW_OUTLINE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+#[must_use]
+pub fn get(document: ID) -> Option<Selection> {
+    selections().read().get(&document).cloned()
+}
+/// Select the polygon in the document, storing it into the point repository, or deselect if fewer
+/// than three points are given.
+pub fn select(document: ID, polygon: Vec<[f32; 2]>) {
+    if polygon.len() < 3 {
+        deselect(document);
+        return;
+    }
+    let elements: &[u32] = bytemuck::cast_slice(&polygon);
+    // Unwrap ok - elements are exactly positions.
+    let slice =
+        fuzzpaint_core::stroke::StrokeSlice::new(elements, fuzzpaint_core::selection::ARCHETYPE)
+            .unwrap();
+    let Some(id) = super::points().insert(slice) else {
+        tracing::warn!("selection too large to store");
+        return;
+    };
+    selections().write().insert(
+        document,
+        Selection {
+            id,
+            polygon: polygon.into(),
+        },
+    );
+}
+pub fn deselect(document: ID) {
+    selections().write().remove(&document);
+}
+/// Whether to draw marching ants around the selection.
+#[must_use]
+pub fn show_outline() -> bool {
+    SHOW_OUTLINE.load(std::sync::atomic::Ordering::Relaxed)
+}
+pub fn set_show_outline(show: bool) {
+    SHOW_OUTLINE.store(show, std::sync::atomic::Ordering::Relaxed);
+}
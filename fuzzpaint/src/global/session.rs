@@ -0,0 +1,308 @@
+//! The view of each open document, and the session left behind when fuzzpaint closes.
+//!
+//! Views are not part of a document's file - they're kept here while it's open, and in the session store when
+//! fuzzpaint closes, along with which documents were open, so that they can be picked up again the next time if
+//! [`super::preferences::Startup::reopen`] is set.
+
+use crate::view_transform::{DocumentFit, DocumentTransform, ViewTransform};
+use fuzzpaint_core::state::{document::ID, graph};
+
+fn views() -> &'static parking_lot::RwLock<hashbrown::HashMap<ID, DocumentTransform>> {
+    static VIEWS: std::sync::OnceLock<
+        parking_lot::RwLock<hashbrown::HashMap<ID, DocumentTransform>>,
+    > = std::sync::OnceLock::new();
+    VIEWS.get_or_init(Default::default)
+}
+
+/// The view of the document, or None if it hasn't been shown yet.
+#[must_use]
+pub fn view(document: ID) -> Option<DocumentTransform> {
+    views().read().get(&document).copied()
+}
+pub fn set_view(document: ID, view: DocumentTransform) {
+    views().write().insert(document, view);
+}
+pub fn remove(document: ID) {
+    views().write().remove(&document);
+}
+
+/// Serde stand-in for [`DocumentTransform`]. Rotations are in radians, and positions in viewport pixels.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SavedView {
+    Fit {
+        flip_x: bool,
+        rotation: f32,
+    },
+    Transform {
+        flip_x: bool,
+        scale: f32,
+        rotation: f32,
+        x: f32,
+        y: f32,
+    },
+}
+impl Default for SavedView {
+    fn default() -> Self {
+        DocumentTransform::default().into()
+    }
+}
+impl From<DocumentTransform> for SavedView {
+    fn from(transform: DocumentTransform) -> Self {
+        match transform {
+            DocumentTransform::Fit(fit) => Self::Fit {
+                flip_x: fit.flip_x,
+                rotation: fit.rotation.0,
+            },
+            DocumentTransform::Transform(xform) => {
+                let rotation: &cgmath::Matrix2<f32> = xform.decomposed.rot.as_ref();
+                Self::Transform {
+                    flip_x: xform.flip_x,
+                    scale: xform.decomposed.scale,
+                    rotation: rotation.x.y.atan2(rotation.x.x),
+                    x: xform.decomposed.disp.x,
+                    y: xform.decomposed.disp.y,
+                }
+            }
+        }
+    }
+}
+impl SavedView {
+    /// The view, or None if it's unusable, such as from a hand-edited file.
+    #[must_use]
+    pub fn transform(self) -> Option<DocumentTransform> {
+        use cgmath::Rotation2;
+        match self {
+            Self::Fit { flip_x, rotation } => rotation.is_finite().then(|| {
+                DocumentTransform::Fit(DocumentFit {
+                    flip_x,
+                    rotation: cgmath::Rad(rotation),
+                    ..DocumentFit::default()
+                })
+            }),
+            Self::Transform {
+                flip_x,
+                scale,
+                rotation,
+                x,
+                y,
+            } => {
+                ([scale, rotation, x, y].iter().all(|v| v.is_finite()) && scale > 0.0).then(|| {
+                    DocumentTransform::Transform(ViewTransform {
+                        flip_x,
+                        decomposed: cgmath::Decomposed {
+                            scale,
+                            rot: cgmath::Basis2::from_angle(cgmath::Rad(rotation)),
+                            disp: cgmath::vec2(x, y),
+                        },
+                    })
+                })
+            }
+        }
+    }
+}
+
+/// The position of a layer in the graph, as indices of children from the top level down. Unlike IDs, which are
+/// different each time a document is opened, it's the same as long as the graph is.
+#[must_use]
+pub fn layer_path(graph: &graph::BlendGraph, layer: graph::AnyID) -> Option<Vec<usize>> {
+    fn search(
+        graph: &graph::BlendGraph,
+        children: impl Iterator<Item = graph::AnyID>,
+        layer: graph::AnyID,
+        path: &mut Vec<usize>,
+    ) -> bool {
+        for (idx, child) in children.enumerate() {
+            path.push(idx);
+            if child == layer {
+                return true;
+            }
+            if let graph::AnyID::Node(node) = child {
+                if let Some(grandchildren) = graph.iter_node(node) {
+                    let grandchildren: Vec<_> = grandchildren.map(|(id, _)| id).collect();
+                    if search(graph, grandchildren.into_iter(), layer, path) {
+                        return true;
+                    }
+                }
+            }
+            path.pop();
+        }
+        false
+    }
+    let mut path = Vec::new();
+    search(
+        graph,
+        graph.iter_top_level().map(|(id, _)| id),
+        layer,
+        &mut path,
+    )
+    .then_some(path)
+}
+/// The layer at the [path](layer_path), if there still is one.
+#[must_use]
+pub fn layer_at(graph: &graph::BlendGraph, path: &[usize]) -> Option<graph::AnyID> {
+    let (&first, rest) = path.split_first()?;
+    let mut layer = graph.iter_top_level().nth(first)?.0;
+    for &idx in rest {
+        let graph::AnyID::Node(node) = layer else {
+            return None;
+        };
+        layer = graph.iter_node(node)?.nth(idx)?.0;
+    }
+    Some(layer)
+}
+
+/// A document that was open.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct SessionDocument {
+    pub path: std::path::PathBuf,
+    /// The selected layer, see [`layer_path`].
+    #[serde(default)]
+    pub layer: Option<Vec<usize>>,
+    // Must follow the plain values.
+    #[serde(default)]
+    pub view: SavedView,
+}
+
+/// What was open when fuzzpaint last closed. Written over each time, so unlike the preferences, it's not meant
+/// to be edited.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Session {
+    /// The tool chosen.
+    pub tool: Option<crate::pen_tools::StateLayer>,
+    /// The documents open, the current one last.
+    #[serde(rename = "document")]
+    pub documents: Vec<SessionDocument>,
+}
+impl Session {
+    const FILENAME: &'static str = "session.toml";
+    #[must_use]
+    pub fn default_file_location() -> Option<std::path::PathBuf> {
+        let mut dir = super::hotkeys::preferences_dir()?;
+        dir.push(Self::FILENAME);
+        Some(dir)
+    }
+    /// The session left by the last run, to be restored if the [preferences](super::preferences::Startup::reopen)
+    /// say so, or None if they don't. Read once, so it's unaffected by this run saving over it.
+    #[must_use]
+    pub fn last() -> Option<&'static Self> {
+        static LAST: std::sync::OnceLock<Session> = std::sync::OnceLock::new();
+        if !super::preferences::Preferences::read().startup.reopen {
+            return None;
+        }
+        Some(LAST.get_or_init(Self::from_default_file))
+    }
+    /// Load from the default file location. Defaults on any error, as there's nothing the user could do about it.
+    #[must_use]
+    pub fn from_default_file() -> Self {
+        let Some(path) = Self::default_file_location() else {
+            return Self::default();
+        };
+        let file: anyhow::Result<Self> = try_block::try_block! {
+            let string = match std::fs::read_to_string(path) {
+                Ok(string) => string,
+                // Not an error, nothing has been saved yet.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+                Err(e) => return Err(e.into()),
+            };
+            Ok(toml::from_str(&string)?)
+        };
+        file.unwrap_or_else(|e| {
+            tracing::warn!("failed to load the last session: {e}");
+            Self::default()
+        })
+    }
+    /// Save the session to the default location, overwriting contents.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let mut session = super::hotkeys::preferences_dir()
+            .ok_or_else(|| anyhow::anyhow!("No preferences dir found"))?;
+        // Explicity do *not* create recursively, see `Hotkeys::save`.
+        let _ = std::fs::DirBuilder::new().create(&session);
+
+        session.push(Self::FILENAME);
+        std::fs::write(session, toml::ser::to_string_pretty(self)?)?;
+        Ok(())
+    }
+    /// The tool to start with - the one last chosen if restoring the session, otherwise the one from the
+    /// [preferences](super::preferences::Startup::tool).
+    #[must_use]
+    pub fn startup_tool() -> crate::pen_tools::StateLayer {
+        Self::last()
+            .and_then(|session| session.tool)
+            .unwrap_or_else(|| super::preferences::Preferences::read().startup.tool)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SavedView, Session, SessionDocument};
+    use fuzzpaint_core::state::graph::{BlendGraph, LeafType, Location, NodeType};
+
+    #[test]
+    fn view_roundtrip() {
+        let view = SavedView::Transform {
+            flip_x: true,
+            scale: 2.5,
+            rotation: 1.0,
+            x: -30.0,
+            y: 12.0,
+        };
+        let SavedView::Transform { rotation, .. } = SavedView::from(view.transform().unwrap())
+        else {
+            panic!("transform became a fit");
+        };
+        assert!((rotation - 1.0).abs() < 1e-5);
+        assert_eq!(
+            SavedView::from(SavedView::default().transform().unwrap()),
+            SavedView::default()
+        );
+        // Degenerate views are rejected.
+        let degenerate = SavedView::Transform {
+            flip_x: false,
+            scale: 0.0,
+            rotation: 0.0,
+            x: 0.0,
+            y: f32::NAN,
+        };
+        assert!(degenerate.transform().is_none());
+
+        let session = Session {
+            tool: Some(crate::pen_tools::StateLayer::Lasso),
+            documents: vec![SessionDocument {
+                path: "drawing.fzp".into(),
+                layer: Some(vec![1, 0]),
+                view,
+            }],
+        };
+        let string = toml::ser::to_string_pretty(&session).unwrap();
+        assert_eq!(toml::from_str::<Session>(&string).unwrap(), session);
+    }
+    #[test]
+    fn layer_paths() {
+        let mut graph = BlendGraph::default();
+        let group = graph
+            .add_node(
+                Location::IndexIntoRoot(0),
+                "Group".to_owned(),
+                NodeType::Passthrough,
+            )
+            .unwrap();
+        let leaf = graph
+            .add_leaf(
+                Location::IndexIntoNode(&group, 0),
+                "Note".to_owned(),
+                LeafType::Note,
+            )
+            .unwrap();
+        let top = graph
+            .add_leaf(Location::IndexIntoRoot(0), "Top".to_owned(), LeafType::Note)
+            .unwrap();
+        for layer in [group.into(), leaf.into(), top.into()] {
+            let path = super::layer_path(&graph, layer).unwrap();
+            assert_eq!(super::layer_at(&graph, &path), Some(layer));
+        }
+        assert_eq!(super::layer_path(&graph, leaf.into()), Some(vec![1, 0]));
+        assert_eq!(super::layer_at(&graph, &[2]), None);
+    }
+}
@@ -0,0 +1,62 @@
+//! Long running work, listed for the UI to show the progress of and offer to cancel, see
+//! [`fuzzpaint_core::progress`].
+
+use fuzzpaint_core::progress::{Progress, Task};
+
+/// A task that hasn't yet finished.
+#[derive(Clone)]
+pub struct Running {
+    /// What's being done, such as "Opening drawing.fzp".
+    pub label: String,
+    pub task: Task,
+}
+
+fn tasks() -> &'static parking_lot::Mutex<Vec<Running>> {
+    static TASKS: std::sync::OnceLock<parking_lot::Mutex<Vec<Running>>> =
+        std::sync::OnceLock::new();
+    TASKS.get_or_init(Default::default)
+}
+
+/// List a new task, returning the handle for its work to report through. It's listed until the work drops
+/// every clone of the handle.
+pub fn begin(label: impl Into<String>) -> Progress {
+    let (task, progress) = Task::new();
+    tasks().lock().push(Running {
+        label: label.into(),
+        task,
+    });
+    super::wake::wake(super::wake::Wake::Ui);
+    progress
+}
+
+/// Do `work` on a thread of its own as a listed task. The UI is woken once it finishes, with the result
+/// waiting in the returned receiver.
+pub fn spawn<T, F>(
+    label: impl Into<String>,
+    work: F,
+) -> std::io::Result<std::sync::mpsc::Receiver<T>>
+where
+    T: Send + 'static,
+    F: FnOnce(&Progress) -> T + Send + 'static,
+{
+    let label = label.into();
+    let (send, recv) = std::sync::mpsc::sync_channel(1);
+    let progress = begin(label.clone());
+    std::thread::Builder::new().name(label).spawn(move || {
+        let result = work(&progress);
+        // Unlisted before the UI hears of it.
+        drop(progress);
+        // Err if the UI stopped waiting, nothing to be done.
+        let _ = send.send(result);
+        super::wake::wake(super::wake::Wake::Ui);
+    })?;
+    Ok(recv)
+}
+
+/// Every task still running, oldest first.
+#[must_use]
+pub fn running() -> Vec<Running> {
+    let mut tasks = tasks().lock();
+    tasks.retain(|running| !running.task.is_finished());
+    tasks.clone()
+}
@@ -0,0 +1,337 @@
+//! Templates for new documents: their size, background, and starting layers.
+//!
+//! A few are built in, and the user's own are saved to the user's preferences.
+
+use super::config_file::ConfigFile;
+use fuzzpaint_core::{
+    blend::{Blend, BlendMode},
+    color::{Color, ColorOrPalette},
+};
+
+const DOCUMENTATION: &str = r#"# Fuzzpaint document templates. You may edit this file, but be aware that formatting and comments will
+# not be preserved.
+
+# Each [[template]] has a name, a width and height in pixels, and a resolution in dots per inch.
+# The optional background is an [r, g, b, a] color from 0.0 to 1.0, filling a "Background" layer
+# at the bottom. Leave it out for a transparent document.
+# Each [[template.layer]] is added on top of the last, and has a name, a kind of either "stroke"
+# or "fill", and optionally a blend mode, opacity, alpha clip, and - for fills - a color.
+# Blend modes are Normal, Add, Multiply, Screen, Darken, Lighten, or Erase.
+
+# Example:
+# [[template]]
+# name = "Comic page"
+# width = 2480
+# height = 3508
+# dpi = 300.0
+# background = [1.0, 1.0, 1.0, 1.0]
+#
+# [[template.layer]]
+# name = "Sketch"
+# kind = "stroke"
+# opacity = 0.5
+#
+# [[template.layer]]
+# name = "Inks"
+# kind = "stroke"
+
+"#;
+
+#[derive(thiserror::Error, Debug)]
+pub enum InvalidTemplate {
+    #[error("width and height must be at least one pixel")]
+    EmptySize,
+    #[error("resolution must be positive")]
+    BadResolution,
+    #[error("unknown blend mode {0:?}")]
+    BlendMode(String),
+    #[error("colors must be finite")]
+    NotFinite(#[from] fuzzpaint_core::util::FiniteF32Error),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerKind {
+    /// An empty stroke layer.
+    #[default]
+    Stroke,
+    /// A layer of a single solid color.
+    Fill,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct TemplateLayer {
+    pub name: String,
+    #[serde(default)]
+    pub kind: LayerKind,
+    /// Name of a [`BlendMode`], or `None` for the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blend: Option<String>,
+    #[serde(default = "one")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub alpha_clip: bool,
+    /// Color of a [`LayerKind::Fill`], ignored otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 4]>,
+}
+fn one() -> f32 {
+    1.0
+}
+impl TemplateLayer {
+    #[must_use]
+    pub fn stroke(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: LayerKind::Stroke,
+            blend: None,
+            opacity: 1.0,
+            alpha_clip: false,
+            color: None,
+        }
+    }
+    pub fn blend(&self) -> Result<Blend, InvalidTemplate> {
+        let mode = match &self.blend {
+            None => BlendMode::default(),
+            Some(name) => <BlendMode as strum::IntoEnumIterator>::iter()
+                .find(|mode| mode.as_ref().eq_ignore_ascii_case(name))
+                .ok_or_else(|| InvalidTemplate::BlendMode(name.clone()))?,
+        };
+        Ok(Blend {
+            mode,
+            opacity: if self.opacity.is_finite() {
+                self.opacity.clamp(0.0, 1.0)
+            } else {
+                1.0
+            },
+            alpha_clip: self.alpha_clip,
+        })
+    }
+    /// The fill color, black if unspecified.
+    pub fn color(&self) -> Result<ColorOrPalette, InvalidTemplate> {
+        Ok(match self.color {
+            Some(color) => Color::from_array_lossy(color)?.into(),
+            None => ColorOrPalette::BLACK,
+        })
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct DocumentTemplate {
+    pub name: String,
+    /// In pixels.
+    pub width: u32,
+    /// In pixels.
+    pub height: u32,
+    /// Dots per inch, used to interpret physical units.
+    pub dpi: f32,
+    /// Color of the background layer, or `None` for no background.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<[f32; 4]>,
+    /// From bottom to top, above the background.
+    #[serde(default, rename = "layer")]
+    pub layers: Vec<TemplateLayer>,
+}
+impl DocumentTemplate {
+    /// A white background and one stroke layer, like documents have always started.
+    #[must_use]
+    pub fn simple(name: &str, width: u32, height: u32, dpi: f32) -> Self {
+        Self {
+            name: name.to_owned(),
+            width,
+            height,
+            dpi,
+            background: Some(Color::WHITE.as_array()),
+            layers: vec![TemplateLayer::stroke("Stroke Layer")],
+        }
+    }
+    /// The templates shipped with the app. The first is the default.
+    #[must_use]
+    pub fn builtin() -> Vec<Self> {
+        vec![
+            Self::simple("Square", 1080, 1080, 150.0),
+            Self::simple("A4, 300 DPI", 2480, 3508, 300.0),
+            Self::simple("Letter, 300 DPI", 2550, 3300, 300.0),
+            Self::simple("Screen, 1920×1080", 1920, 1080, 96.0),
+            Self::simple("Screen, 1280×720", 1280, 720, 96.0),
+            Self::simple("Phone wallpaper", 1080, 1920, 96.0),
+        ]
+    }
+    /// Check that the template can make a document, returning the background color if any.
+    pub fn validate(&self) -> Result<Option<ColorOrPalette>, InvalidTemplate> {
+        if self.width == 0 || self.height == 0 {
+            return Err(InvalidTemplate::EmptySize);
+        }
+        if !(self.dpi.is_finite() && self.dpi > 0.0) {
+            return Err(InvalidTemplate::BadResolution);
+        }
+        for layer in &self.layers {
+            layer.blend()?;
+            layer.color()?;
+        }
+        Ok(match self.background {
+            Some(color) => Some(Color::from_array_lossy(color)?.into()),
+            None => None,
+        })
+    }
+}
+
+/// On-disk representation of the templates file. TOML needs a table at the top level.
+#[derive(serde::Deserialize)]
+pub struct TemplatesFile {
+    #[serde(default, rename = "template")]
+    templates: Vec<DocumentTemplate>,
+}
+/// Serialize a borrowed view, to avoid cloning every template.
+#[derive(serde::Serialize)]
+struct TemplatesFileRef<'a> {
+    #[serde(rename = "template")]
+    templates: &'a [DocumentTemplate],
+}
+fn to_string(templates: &[DocumentTemplate]) -> Result<String, toml::ser::Error> {
+    let string = toml::ser::to_string_pretty(&TemplatesFileRef { templates })?;
+    Ok(DOCUMENTATION.to_owned() + &string)
+}
+
+/// The user's own templates. Built-in ones are not saved, see [`DocumentTemplate::builtin`].
+pub struct Templates {
+    pub load_blocker: Option<super::hotkeys::LoadBlockReason>,
+    pub templates: Vec<DocumentTemplate>,
+}
+impl Templates {
+    /// Shared read access to the global templates.
+    pub fn read() -> parking_lot::RwLockReadGuard<'static, Self> {
+        Self::global().read()
+    }
+    /// Exclusive write access to the global templates.
+    pub fn write() -> parking_lot::RwLockWriteGuard<'static, Self> {
+        Self::global().write()
+    }
+    fn global() -> &'static parking_lot::RwLock<Self> {
+        static GLOBAL_TEMPLATES: std::sync::OnceLock<parking_lot::RwLock<Templates>> =
+            std::sync::OnceLock::new();
+
+        GLOBAL_TEMPLATES.get_or_init(|| Self::from_default_file().into())
+    }
+    /// Returns the reason for read/write blockage, if any.
+    #[must_use]
+    pub fn load_blocker(&self) -> Option<&super::hotkeys::LoadBlockReason> {
+        self.load_blocker.as_ref()
+    }
+    /// Find a template by name, built in ones first.
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<DocumentTemplate> {
+        DocumentTemplate::builtin()
+            .into_iter()
+            .find(|t| t.name == name)
+            .or_else(|| self.templates.iter().find(|t| t.name == name).cloned())
+    }
+    /// The template new documents start from, as chosen in the [preferences](super::preferences::Startup). The
+    /// first built in one if none is chosen, or if it's gone.
+    #[must_use]
+    pub fn default_template() -> DocumentTemplate {
+        let name = super::preferences::Preferences::read()
+            .startup
+            .template
+            .clone();
+        name.and_then(|name| Self::read().find(&name))
+            .unwrap_or_else(|| DocumentTemplate::builtin().swap_remove(0))
+    }
+    /// Add a template, replacing any existing template of the same name.
+    pub fn insert(&mut self, template: DocumentTemplate) {
+        if let Some(existing) = self.templates.iter_mut().find(|t| t.name == template.name) {
+            *existing = template;
+        } else {
+            self.templates.push(template);
+        }
+    }
+}
+impl ConfigFile for Templates {
+    const FILENAME: &'static str = "document_templates.toml";
+    const DESCRIPTION: &'static str = "document templates";
+    type File = TemplatesFile;
+    fn with_defaults() -> Self {
+        Self {
+            load_blocker: None,
+            templates: Vec::new(),
+        }
+    }
+    fn from_file(file: TemplatesFile) -> Result<Self, super::hotkeys::LoadBlockReason> {
+        Ok(Self {
+            load_blocker: None,
+            templates: file.templates,
+        })
+    }
+    fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        to_string(&self.templates)
+    }
+    fn set_load_blocker(&mut self, blocker: super::hotkeys::LoadBlockReason) {
+        self.load_blocker = Some(blocker);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfigFile, DocumentTemplate, LayerKind, TemplateLayer, Templates, TemplatesFile};
+    fn template() -> DocumentTemplate {
+        let mut template = DocumentTemplate::simple("Test", 100, 200, 72.0);
+        template.layers.push(TemplateLayer {
+            name: "Shade".to_owned(),
+            kind: LayerKind::Fill,
+            blend: Some("multiply".to_owned()),
+            opacity: 0.5,
+            alpha_clip: true,
+            color: Some([0.5, 0.0, 1.0, 1.0]),
+        });
+        template
+    }
+    #[test]
+    fn roundtrip() {
+        let templates = [template()];
+        let string = super::to_string(&templates).unwrap();
+        let file: TemplatesFile = toml::from_str(&string).unwrap();
+        assert_eq!(file.templates, templates);
+    }
+    #[test]
+    fn validate() {
+        for template in DocumentTemplate::builtin() {
+            assert!(template.validate().is_ok(), "{}", template.name);
+        }
+        let blend = template().layers[1].blend().unwrap();
+        assert_eq!(blend.mode, fuzzpaint_core::blend::BlendMode::Multiply);
+        assert!(blend.alpha_clip);
+
+        let mut bad = template();
+        bad.layers[1].blend = Some("Sparkle".to_owned());
+        assert!(bad.validate().is_err());
+        let bad = DocumentTemplate {
+            width: 0,
+            ..template()
+        };
+        assert!(bad.validate().is_err());
+    }
+    #[test]
+    fn insert_replaces_by_name() {
+        let mut templates = Templates::with_defaults();
+        templates.insert(template());
+        templates.insert(DocumentTemplate {
+            width: 50,
+            ..template()
+        });
+        templates.insert(DocumentTemplate {
+            name: "Other".to_owned(),
+            ..template()
+        });
+        assert_eq!(templates.templates.len(), 2);
+        assert_eq!(templates.templates[0].width, 50);
+    }
+    #[test]
+    fn find() {
+        let mut templates = Templates::with_defaults();
+        templates.insert(template());
+        assert_eq!(templates.find("Test"), Some(template()));
+        let builtin = DocumentTemplate::builtin().swap_remove(1);
+        assert_eq!(templates.find(&builtin.name), Some(builtin));
+        assert_eq!(templates.find("Missing"), None);
+    }
+}
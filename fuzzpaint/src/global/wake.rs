@@ -0,0 +1,65 @@
+//! # Wake
+//!
+//! The window's event loop sleeps until there is input or something else to do. Work on other threads that
+//! the window should show, like a new document image or a changed document, must [`wake`] it.
+//!
+//! Anything that animates without input or changes to drive it, like a spinner, keeps the window redrawing every
+//! frame by holding an [`Animation`] for as long as it's visible.
+
+/// Why the event loop was woken, sent as its user event.
+#[derive(Debug)]
+pub enum Wake {
+    /// State shown by the UI changed, so the UI should be rerun.
+    Ui,
+    /// Something the window checks for may be ready, like a new document image.
+    Poll,
+    /// Another instance handed off files, so the window should come to the front and the UI should open them.
+    Focus,
+    /// A screen reader or other assistive technology asked for something of the UI.
+    Accessibility(egui_winit::accesskit_winit::ActionRequestEvent),
+}
+impl From<egui_winit::accesskit_winit::ActionRequestEvent> for Wake {
+    fn from(event: egui_winit::accesskit_winit::ActionRequestEvent) -> Self {
+        Self::Accessibility(event)
+    }
+}
+
+static PROXY: std::sync::OnceLock<parking_lot::Mutex<winit::event_loop::EventLoopProxy<Wake>>> =
+    std::sync::OnceLock::new();
+
+/// Set the event loop to be woken. Only the first call has any effect.
+pub fn install(event_loop: winit::event_loop::EventLoopProxy<Wake>) {
+    let _ = PROXY.set(event_loop.into());
+}
+
+/// Wake the event loop, if it's installed and still running.
+pub fn wake(reason: Wake) {
+    if let Some(event_loop) = PROXY.get() {
+        // Err if the loop has exited, at which point nobody is left to care.
+        let _ = event_loop.lock().send_event(reason);
+    }
+}
+
+static ANIMATIONS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// While any of these are alive, the window redraws every frame.
+#[must_use = "the animation stops when dropped"]
+pub struct Animation(());
+impl Animation {
+    pub fn start() -> Self {
+        ANIMATIONS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        // The loop may be asleep, with nothing else to wake it for the first frame.
+        wake(Wake::Poll);
+        Self(())
+    }
+}
+impl Drop for Animation {
+    fn drop(&mut self) {
+        ANIMATIONS.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Whether any [`Animation`]s are alive.
+pub fn is_animating() -> bool {
+    ANIMATIONS.load(std::sync::atomic::Ordering::Relaxed) != 0
+}
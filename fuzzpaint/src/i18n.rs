@@ -0,0 +1,220 @@
+//! # Internationalization
+//!
+//! Interface strings are looked up by ID from [Fluent](https://projectfluent.org) resources, with [`tr`], in
+//! the language chosen in the preferences. Messages missing from that language fall back on English, which is
+//! built in. Other languages are read from `<tag>.ftl` files in the `locales` folder of the preferences
+//! directory, so that translations can be made and tried without rebuilding.
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+
+/// The built in language, complete with every message.
+pub const FALLBACK_TAG: &str = "en-US";
+const FALLBACK_SOURCE: &str = include_str!("../locales/en-US.ftl");
+
+type Bundle = FluentBundle<FluentResource>;
+
+struct Localizer {
+    /// The chosen language, or None if it's the fallback.
+    chosen: Option<(String, Bundle)>,
+    fallback: Bundle,
+}
+
+fn localizer() -> &'static parking_lot::RwLock<Localizer> {
+    static LOCALIZER: std::sync::OnceLock<parking_lot::RwLock<Localizer>> =
+        std::sync::OnceLock::new();
+    LOCALIZER.get_or_init(|| {
+        Localizer {
+            chosen: None,
+            // Unwrap ok - checked by test.
+            fallback: bundle(FALLBACK_TAG, FALLBACK_SOURCE.to_owned()).unwrap(),
+        }
+        .into()
+    })
+}
+
+fn bundle(tag: &str, source: String) -> anyhow::Result<Bundle> {
+    let language: unic_langid::LanguageIdentifier = tag.parse()?;
+    let resource = FluentResource::try_new(source).map_err(|(_, errors)| {
+        anyhow::anyhow!(
+            "{} syntax errors, first: {:?}",
+            errors.len(),
+            errors.first()
+        )
+    })?;
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // Egui shows the unicode isolation marks around arguments as boxes.
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .map_err(|errors| anyhow::anyhow!("{errors:?}"))?;
+    Ok(bundle)
+}
+
+/// Where translations other than English are read from.
+#[must_use]
+pub fn locales_dir() -> Option<std::path::PathBuf> {
+    let mut dir = crate::global::hotkeys::preferences_dir()?;
+    dir.push("locales");
+    Some(dir)
+}
+
+/// A language the interface can be shown in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Language {
+    /// Such as `en-US`.
+    pub tag: String,
+    /// The language's name for itself.
+    pub name: String,
+}
+/// Every language there's a translation for, English first.
+#[must_use]
+pub fn available() -> Vec<Language> {
+    let mut languages = vec![Language {
+        tag: FALLBACK_TAG.to_owned(),
+        name: format_in(&localizer().read().fallback, "language-name", None)
+            .unwrap_or_else(|| FALLBACK_TAG.to_owned()),
+    }];
+    let Some(entries) = locales_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return languages;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension().map_or(true, |ext| ext != "ftl") {
+            continue;
+        }
+        let Some(tag) = path.file_stem().and_then(std::ffi::OsStr::to_str) else {
+            continue;
+        };
+        // Named by what it says of itself, if it can be read.
+        let name = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|source| bundle(tag, source).ok())
+            .and_then(|bundle| format_in(&bundle, "language-name", None))
+            .unwrap_or_else(|| tag.to_owned());
+        languages.push(Language {
+            tag: tag.to_owned(),
+            name,
+        });
+    }
+    languages[1..].sort_by(|a, b| a.tag.cmp(&b.tag));
+    languages
+}
+
+/// The tag of the language the interface is shown in.
+#[must_use]
+pub fn language() -> String {
+    localizer()
+        .read()
+        .chosen
+        .as_ref()
+        .map_or_else(|| FALLBACK_TAG.to_owned(), |(tag, _)| tag.clone())
+}
+/// Show the interface in the language with the given tag from now on, or English if None. The translation is
+/// read again even if it's the current language, to pick up changes.
+/// # Errors
+/// If the translation can't be read, English is used instead.
+pub fn set_language(tag: Option<&str>) -> anyhow::Result<()> {
+    let chosen = match tag.filter(|&tag| tag != FALLBACK_TAG) {
+        None => None,
+        Some(tag) => {
+            let result = (|| -> anyhow::Result<Bundle> {
+                let path = locales_dir()
+                    .ok_or_else(|| anyhow::anyhow!("no preferences directory"))?
+                    .join(format!("{tag}.ftl"));
+                bundle(tag, std::fs::read_to_string(path)?)
+            })();
+            match result {
+                Ok(bundle) => Some((tag.to_owned(), bundle)),
+                Err(e) => {
+                    localizer().write().chosen = None;
+                    return Err(e);
+                }
+            }
+        }
+    };
+    localizer().write().chosen = chosen;
+    Ok(())
+}
+
+/// Format a message, or one of its attributes as `message.attribute`, from the bundle. None if it's missing.
+fn format_in(bundle: &Bundle, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+    let (message, attribute) = match id.split_once('.') {
+        Some((message, attribute)) => (message, Some(attribute)),
+        None => (id, None),
+    };
+    let message = bundle.get_message(message)?;
+    let pattern = match attribute {
+        Some(attribute) => message.get_attribute(attribute)?.value(),
+        None => message.value()?,
+    };
+    let mut errors = Vec::new();
+    let formatted = bundle.format_pattern(pattern, args, &mut errors);
+    if !errors.is_empty() {
+        tracing::debug!(id, ?errors, "errors formatting message");
+    }
+    Some(formatted.into_owned())
+}
+/// Format a message in the chosen language, falling back on English, and then on the ID itself.
+/// Prefer the [`tr`] macro.
+#[must_use]
+pub fn format(id: &str, args: Option<&FluentArgs>) -> String {
+    let localizer = localizer().read();
+    localizer
+        .chosen
+        .as_ref()
+        .and_then(|(_, bundle)| format_in(bundle, id, args))
+        .or_else(|| format_in(&localizer.fallback, id, args))
+        .unwrap_or_else(|| {
+            tracing::debug!(id, "missing message");
+            id.to_owned()
+        })
+}
+
+/// Look up an interface string by its ID, as `tr!("message")` or `tr!("message.attribute")`, with any
+/// arguments as `tr!("message", name = value)`.
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::format($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::format($id, Some(&args))
+    }};
+}
+pub(crate) use tr;
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn fallback_parses() {
+        super::bundle(super::FALLBACK_TAG, super::FALLBACK_SOURCE.to_owned()).unwrap();
+    }
+    #[test]
+    fn falls_back() {
+        let chosen = super::bundle("fr-FR", "menu-file = Fichier\n".to_owned()).unwrap();
+        assert_eq!(
+            super::format_in(&chosen, "menu-file", None).as_deref(),
+            Some("Fichier")
+        );
+        assert_eq!(super::format_in(&chosen, "menu-edit", None), None);
+        let fallback =
+            super::bundle(super::FALLBACK_TAG, super::FALLBACK_SOURCE.to_owned()).unwrap();
+        assert_eq!(
+            super::format_in(&fallback, "menu-file-save-as.hover", None).as_deref(),
+            Some("Save a copy, and open it for editing")
+        );
+    }
+    #[test]
+    fn arguments() {
+        let fallback =
+            super::bundle(super::FALLBACK_TAG, super::FALLBACK_SOURCE.to_owned()).unwrap();
+        let mut args = fluent_bundle::FluentArgs::new();
+        args.set("summary", "Failed");
+        args.set("count", 3);
+        assert_eq!(
+            super::format_in(&fallback, "toast-repeated", Some(&args)).as_deref(),
+            Some("Failed (×3)")
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Drive [`WinitInput`](super::WinitInput) with a script of synthetic events, without a window, and collect
+//! what the tools would have seen each frame.
+
+/// One step of an input script.
+#[derive(Clone, Copy, Debug)]
+pub enum Synthetic {
+    /// The cursor moved to this position, in physical pixels.
+    Move(f32, f32),
+    /// The raw pressure of the next move, `[0, 1]`, as reported by X11.
+    Pressure(f32),
+    /// The primary button or nib went down.
+    Press,
+    /// The primary button or nib went up.
+    Release,
+    /// The cursor left the window.
+    Leave,
+    /// The held modifier keys changed.
+    Modifiers(winit::keyboard::ModifiersState),
+    Key {
+        code: winit::keyboard::KeyCode,
+        pressed: bool,
+    },
+    /// The wheel scrolled by this many lines, positive is away from the user.
+    Scroll(f32),
+    /// The window is about to wait, ending the frame.
+    Frame,
+}
+
+/// What the tools see for one frame.
+pub struct Frame {
+    pub stylus: crate::stylus_events::StylusEventFrame,
+    pub actions: crate::actions::ActionFrame,
+}
+
+pub struct Harness {
+    input: super::WinitInput,
+    /// Kept, as listeners stop hearing of actions once it's gone.
+    _stream: crate::actions::ActionStream,
+    actions: crate::actions::ActionListener,
+    stylus: tokio::sync::broadcast::Receiver<crate::stylus_events::StylusEventFrame>,
+    /// Whether the UI takes events, as if the cursor were over it.
+    pub consumed: bool,
+}
+impl Default for Harness {
+    fn default() -> Self {
+        let (send, stream) = crate::actions::create_action_stream();
+        let input = super::WinitInput::new(send);
+        Self {
+            actions: stream.listen(),
+            stylus: input.stylus.frame_receiver(),
+            _stream: stream,
+            input,
+            consumed: false,
+        }
+    }
+}
+impl Harness {
+    /// Apply one step, returning the frame if it ended one.
+    ///
+    /// # Panics
+    /// If the frame's stylus events or actions were lost, which means too many frames went uncollected.
+    pub fn push(&mut self, event: Synthetic) -> Option<Frame> {
+        use winit::event::{ElementState, MouseButton, WindowEvent};
+        // Safety: The events only ever reach our collectors, never winit.
+        let device_id = unsafe { winit::event::DeviceId::dummy() };
+        let button = |state| WindowEvent::MouseInput {
+            device_id,
+            state,
+            button: MouseButton::Left,
+        };
+        let event = match event {
+            Synthetic::Move(x, y) => WindowEvent::CursorMoved {
+                device_id,
+                position: winit::dpi::PhysicalPosition::new(x.into(), y.into()),
+            },
+            Synthetic::Pressure(pressure) => {
+                self.input
+                    .push_device_event(&winit::event::DeviceEvent::Motion {
+                        axis: 2,
+                        value: f64::from(pressure) * 65535.0,
+                    });
+                return None;
+            }
+            Synthetic::Press => button(ElementState::Pressed),
+            Synthetic::Release => button(ElementState::Released),
+            Synthetic::Leave => WindowEvent::CursorLeft { device_id },
+            Synthetic::Modifiers(state) => WindowEvent::ModifiersChanged(state.into()),
+            Synthetic::Key { code, pressed } => {
+                // Key events can't be made outside of winit, skip straight to what they'd do.
+                if !self.consumed {
+                    self.input.actions.push_key_code(code, pressed);
+                }
+                return None;
+            }
+            Synthetic::Scroll(lines) => WindowEvent::MouseWheel {
+                device_id,
+                delta: winit::event::MouseScrollDelta::LineDelta(0.0, lines),
+                phase: winit::event::TouchPhase::Moved,
+            },
+            Synthetic::Frame => {
+                self.input.stylus.finish();
+                return Some(Frame {
+                    stylus: self.stylus.try_recv().unwrap(),
+                    actions: self.actions.frame().unwrap(),
+                });
+            }
+        };
+        self.input.push_window_event(&event, self.consumed);
+        None
+    }
+    /// Apply every step, returning the frames they ended.
+    pub fn run(&mut self, script: impl IntoIterator<Item = Synthetic>) -> Vec<Frame> {
+        script
+            .into_iter()
+            .filter_map(|event| self.push(event))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Harness, Synthetic};
+    #[test]
+    fn ui_takes_presses() {
+        let mut harness = Harness::default();
+        harness.consumed = true;
+        let frames = harness.run([
+            Synthetic::Press,
+            Synthetic::Move(1.0, 2.0),
+            Synthetic::Frame,
+        ]);
+        // Over the UI, the stylus stream hears nothing.
+        assert!(frames[0].stylus.is_empty());
+
+        // Releases still go through, so that strokes end even if they finish over the UI.
+        harness.consumed = false;
+        let down = harness.run([
+            Synthetic::Press,
+            Synthetic::Pressure(0.5),
+            Synthetic::Move(3.0, 4.0),
+            Synthetic::Frame,
+        ]);
+        assert_eq!(down[0].stylus.len(), 1);
+        assert!(down[0].stylus[0].pressed);
+        assert_eq!(down[0].stylus[0].pressure, Some(0.5));
+
+        harness.consumed = true;
+        harness.run([Synthetic::Release, Synthetic::Frame]);
+        harness.consumed = false;
+        let up = harness.run([Synthetic::Move(5.0, 6.0), Synthetic::Frame]);
+        assert!(!up[0].stylus[0].pressed);
+    }
+    #[test]
+    fn scroll_zooms() {
+        use crate::actions::Action;
+        let mut harness = Harness::default();
+        let frames = harness.run([Synthetic::Scroll(2.0), Synthetic::Frame]);
+        assert_eq!(frames[0].actions.action_trigger_count(Action::ZoomIn), 2);
+        assert_eq!(frames[0].actions.action_trigger_count(Action::ZoomOut), 0);
+    }
+    #[test]
+    fn modifiers_reach_stylus() {
+        let mut harness = Harness::default();
+        let frames = harness.run([
+            Synthetic::Modifiers(winit::keyboard::ModifiersState::SHIFT),
+            Synthetic::Move(1.0, 2.0),
+            Synthetic::Frame,
+        ]);
+        assert!(frames[0].stylus[0].shift);
+        assert!(!frames[0].stylus[0].ctrl);
+    }
+}
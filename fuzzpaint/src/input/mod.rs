@@ -0,0 +1,74 @@
+//! # Input
+//!
+//! Routes window events to the [action](crate::actions) and [stylus](crate::stylus_events) collectors, which
+//! in turn drive the [pen tools](crate::pen_tools). Kept apart from the window, so that the whole path from
+//! event to stroke can be driven without one - see [`harness`].
+
+#[cfg(test)]
+pub mod harness;
+
+pub struct WinitInput {
+    pub actions: crate::actions::winit_action_collector::WinitKeyboardActionCollector,
+    pub stylus: crate::stylus_events::WinitStylusEventCollector,
+}
+impl WinitInput {
+    #[must_use]
+    pub fn new(sender: crate::actions::ActionSender) -> Self {
+        Self {
+            actions: crate::actions::winit_action_collector::WinitKeyboardActionCollector::new(
+                sender,
+            ),
+            stylus: crate::stylus_events::WinitStylusEventCollector::default(),
+        }
+    }
+    /// Route an event of the main window. `consumed` if the UI took it, in which case it only ends
+    /// what's in progress, never starts anything.
+    pub fn push_window_event(&mut self, event: &winit::event::WindowEvent, consumed: bool) {
+        use winit::event::WindowEvent;
+        if !consumed {
+            self.actions.push_event(event);
+        }
+        match event {
+            // Even if consumed, to stay in sync.
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.stylus.set_modifiers(modifiers.state());
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.stylus.set_mouse_pressed(false);
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                // Only take if egui doesn't want it!
+                if !consumed {
+                    self.stylus.push_position((*position).into());
+                }
+            }
+            WindowEvent::MouseInput { state, .. } => {
+                let pressed = winit::event::ElementState::Pressed == *state;
+
+                if pressed {
+                    // Only take if egui doesn't want it!
+                    if !consumed {
+                        self.stylus.set_mouse_pressed(true);
+                    }
+                } else {
+                    self.stylus.set_mouse_pressed(false);
+                }
+            }
+            _ => (),
+        }
+    }
+    /// Route a raw device event.
+    pub fn push_device_event(&mut self, event: &winit::event::DeviceEvent) {
+        if let winit::event::DeviceEvent::Motion { axis: 2, value } = *event {
+            //Pressure out of 65535
+            self.stylus.set_pressure(value as f32 / 65535.0);
+            // Other axes (undocumented and X11 only)
+            // 0 -> x in display space
+            // 1 -> y in display space
+            // 2 -> pressure out of 65535, 0 if not pressed
+            // 3 -> Tilt X, degrees from vertical, + to the right
+            // 4 -> Tilt Y, degrees from vertical, + towards user
+            // 5 -> unknown, always zero (barrel rotation?)
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! # Single instance
+//!
+//! Opening a file from the file manager launches a new process. If fuzzpaint is already running, the new
+//! process instead [hands off](hand_off) its files to be opened as tabs in the running one and exits, and the
+//! running window comes to the front.
+//!
+//! The running instance [listens](listen) on a socket beside the user's preferences - a unix socket where
+//! available, or a loopback TCP port written to a file otherwise. A connection is greeted with [`GREETING`],
+//! so that a stale port now used by something else is never mistaken for fuzzpaint. Then follows a single
+//! request:
+//!
+//! | Type                    | Meaning                              |
+//! |-------------------------|--------------------------------------|
+//! | `u8`                    | 1 to open read-only, else 0          |
+//! | `u32`                   | Number of paths                      |
+//! | `[(u32, [u8]); count]`  | Length-prefixed UTF-8 absolute paths |
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+/// Sent by the running instance upon connection. Includes a version, bump it if the request changes.
+const GREETING: [u8; 8] = *b"fzpaint\x01";
+/// How long to wait on the other instance before giving up.
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+/// The largest request accepted. Far more than any reasonable number of paths.
+const MAX_REQUEST_LEN: u32 = 1 << 20;
+
+/// Files another instance handed off.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub paths: Vec<PathBuf>,
+    pub read_only: bool,
+}
+
+fn pending() -> &'static parking_lot::Mutex<Vec<Request>> {
+    static PENDING: parking_lot::Mutex<Vec<Request>> = parking_lot::const_mutex(Vec::new());
+    &PENDING
+}
+/// Take the requests handed off since the last call, for the UI to open.
+#[must_use]
+pub fn take_pending() -> Vec<Request> {
+    std::mem::take(&mut *pending().lock())
+}
+
+#[cfg(unix)]
+mod platform {
+    pub type Stream = std::os::unix::net::UnixStream;
+    pub type Listener = std::os::unix::net::UnixListener;
+    fn socket_path() -> std::io::Result<std::path::PathBuf> {
+        let mut path = dirs::runtime_dir()
+            .or_else(crate::global::hotkeys::preferences_dir)
+            .ok_or_else(|| std::io::Error::other("no directory for the socket"))?;
+        path.push("fuzzpaint.sock");
+        Ok(path)
+    }
+    pub fn connect() -> std::io::Result<Stream> {
+        Stream::connect(socket_path()?)
+    }
+    pub fn bind() -> std::io::Result<Listener> {
+        let path = socket_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match Listener::bind(&path) {
+            Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => {
+                // Left behind by an instance that didn't exit cleanly, unless it answers.
+                if Stream::connect(&path).is_ok() {
+                    return Err(err);
+                }
+                std::fs::remove_file(&path)?;
+                Listener::bind(&path)
+            }
+            result => result,
+        }
+    }
+}
+#[cfg(not(unix))]
+mod platform {
+    pub type Stream = std::net::TcpStream;
+    pub type Listener = std::net::TcpListener;
+    fn port_path() -> std::io::Result<std::path::PathBuf> {
+        let mut path = crate::global::hotkeys::preferences_dir()
+            .ok_or_else(|| std::io::Error::other("no directory for the port"))?;
+        path.push("instance-port");
+        Ok(path)
+    }
+    pub fn connect() -> std::io::Result<Stream> {
+        let port: u16 = std::fs::read_to_string(port_path()?)?
+            .trim()
+            .parse()
+            .map_err(std::io::Error::other)?;
+        Stream::connect_timeout(
+            &(std::net::Ipv4Addr::LOCALHOST, port).into(),
+            super::TIMEOUT,
+        )
+    }
+    pub fn bind() -> std::io::Result<Listener> {
+        // Another instance is already listening.
+        if connect().is_ok() {
+            return Err(std::io::ErrorKind::AddrInUse.into());
+        }
+        let listener = Listener::bind((std::net::Ipv4Addr::LOCALHOST, 0))?;
+        let path = port_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, listener.local_addr()?.port().to_string())?;
+        Ok(listener)
+    }
+}
+
+fn write_request(mut stream: impl Write, request: &Request) -> std::io::Result<()> {
+    let mut buf = vec![u8::from(request.read_only)];
+    let count = u32::try_from(request.paths.len()).map_err(std::io::Error::other)?;
+    buf.extend_from_slice(&count.to_le_bytes());
+    for path in &request.paths {
+        let path = path
+            .to_str()
+            .ok_or_else(|| std::io::Error::other("path is not UTF-8"))?;
+        let len = u32::try_from(path.len()).map_err(std::io::Error::other)?;
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(path.as_bytes());
+    }
+    stream.write_all(&buf)?;
+    stream.flush()
+}
+fn read_u32(stream: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+fn read_request(stream: impl Read) -> std::io::Result<Request> {
+    let mut stream = stream.take(MAX_REQUEST_LEN.into());
+    let mut read_only = [0];
+    stream.read_exact(&mut read_only)?;
+    let count = read_u32(&mut stream)?;
+    let mut paths = Vec::new();
+    for _ in 0..count {
+        let len = read_u32(&mut stream)?;
+        let mut path = Vec::new();
+        stream.by_ref().take(len.into()).read_to_end(&mut path)?;
+        if path.len() != len as usize {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        let path = String::from_utf8(path).map_err(std::io::Error::other)?;
+        paths.push(path.into());
+    }
+    Ok(Request {
+        paths,
+        read_only: read_only[0] != 0,
+    })
+}
+
+/// Send the paths to the running instance, if there is one. Returns whether it took them, in which case
+/// this process should exit.
+///
+/// Paths are made absolute, as the running instance may have another working directory. Nothing is handed
+/// off if any path can't be sent.
+pub fn hand_off(paths: &[PathBuf], read_only: bool) -> std::io::Result<bool> {
+    let Ok(mut stream) = platform::connect() else {
+        // Nobody listening, this is the first instance.
+        return Ok(false);
+    };
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut greeting = [0; GREETING.len()];
+    if stream.read_exact(&mut greeting).is_err() || greeting != GREETING {
+        return Ok(false);
+    }
+    let Ok(paths) = paths
+        .iter()
+        .map(std::path::absolute)
+        .collect::<std::io::Result<Vec<_>>>()
+    else {
+        return Ok(false);
+    };
+    if paths.iter().any(|path| path.to_str().is_none()) {
+        return Ok(false);
+    }
+    write_request(stream, &Request { paths, read_only })?;
+    Ok(true)
+}
+
+/// Accept files handed off by later instances, for as long as the process lives. Fails if another instance
+/// is already listening.
+pub fn listen() -> std::io::Result<()> {
+    let listener = platform::bind()?;
+    std::thread::Builder::new()
+        .name("Instance listener".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|mut stream| {
+                    stream.set_read_timeout(Some(TIMEOUT))?;
+                    stream.set_write_timeout(Some(TIMEOUT))?;
+                    stream.write_all(&GREETING)?;
+                    read_request(stream)
+                });
+                match result {
+                    Ok(request) => {
+                        tracing::info!(?request, "files handed off from another instance");
+                        pending().lock().push(request);
+                        crate::global::wake::wake(crate::global::wake::Wake::Focus);
+                    }
+                    Err(err) => tracing::warn!("bad hand off from another instance: {err}"),
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_request, write_request, Request};
+    #[test]
+    fn roundtrip_request() {
+        let request = Request {
+            paths: vec!["/a/b.fzp".into(), "/ü/🐑 c.fzp".into()],
+            read_only: true,
+        };
+        let mut buf = Vec::new();
+        write_request(&mut buf, &request).unwrap();
+        assert_eq!(read_request(buf.as_slice()).unwrap(), request);
+        // Cut short.
+        assert!(read_request(&buf[..buf.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! # Logging
+//!
+//! Installs the global `tracing` subscriber. Events are printed to the terminal, or to `log.out` in
+//! the working directory when there is no terminal, and the most recent are kept for the in-app
+//! console. Records from dependencies using the `log` crate are forwarded into `tracing`.
+
+use std::fmt::Write;
+
+/// How many events the console keeps before discarding the oldest.
+pub const CONSOLE_LEN: usize = 1024;
+
+/// A single event, as shown in the console.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub level: tracing::Level,
+    pub target: String,
+    /// Names of the spans the event occured within, outermost first, separated by `:`.
+    pub spans: String,
+    /// The message followed by any other fields, as `key=value`.
+    pub message: String,
+    /// Time since logging began.
+    pub time: std::time::Duration,
+}
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:>9.3}s {:>5} {}",
+            self.time.as_secs_f64(),
+            self.level,
+            self.target
+        )?;
+        if !self.spans.is_empty() {
+            write!(f, " {}", self.spans)?;
+        }
+        write!(f, "] {}", self.message)
+    }
+}
+
+fn console() -> &'static parking_lot::Mutex<std::collections::VecDeque<Entry>> {
+    static CONSOLE: std::sync::OnceLock<parking_lot::Mutex<std::collections::VecDeque<Entry>>> =
+        std::sync::OnceLock::new();
+    CONSOLE.get_or_init(Default::default)
+}
+fn start_time() -> std::time::Instant {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    *START.get_or_init(std::time::Instant::now)
+}
+/// Copy the kept events at or more severe than `level`, oldest first.
+///
+/// This is a copy rather than a guard, as anything logged while the buffer is locked would deadlock.
+#[must_use]
+pub fn entries(level: tracing::Level) -> Vec<Entry> {
+    console()
+        .lock()
+        .iter()
+        .filter(|entry| entry.level <= level)
+        .cloned()
+        .collect()
+}
+
+/// Collects an event's fields into a single line.
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: String,
+}
+impl tracing::field::Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{value:?}");
+            }
+            // Metadata of forwarded `log` records, redundant with the event's own.
+            name if name.starts_with("log.") => (),
+            name => {
+                let _ = write!(self.fields, " {name}={value:?}");
+            }
+        }
+    }
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        // Avoid the quotes `Debug` would add.
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+}
+
+/// Layer which records events into the [`entries`] buffer.
+struct ConsoleLayer;
+impl<S> tracing_subscriber::Layer<S> for ConsoleLayer
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| span.name())
+                    .collect::<Vec<_>>()
+                    .join(":")
+            })
+            .unwrap_or_default();
+        let metadata = event.metadata();
+
+        let entry = Entry {
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            spans,
+            message: visitor.message + &visitor.fields,
+            time: start_time().elapsed(),
+        };
+
+        let mut console = console().lock();
+        if console.len() >= CONSOLE_LEN {
+            console.pop_front();
+        }
+        console.push_back(entry);
+    }
+}
+
+/// Install the global subscriber. The default level is `debug`, overridable with `RUST_LOG`, or
+/// with `filter` if given.
+pub fn init(filter: Option<&str>) {
+    use tracing_subscriber::prelude::*;
+    // Start the clock.
+    let _ = start_time();
+
+    let builder = tracing_subscriber::EnvFilter::builder()
+        .with_default_directive(tracing_subscriber::filter::LevelFilter::DEBUG.into());
+    let filter = match filter {
+        Some(filter) => builder.parse_lossy(filter),
+        None => builder.from_env_lossy(),
+    };
+    let has_term = std::io::IsTerminal::is_terminal(&std::io::stdin());
+    let terminal = has_term.then(|| tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+    let file = if has_term {
+        None
+    } else {
+        std::fs::File::create("log.out").ok().map(|file| {
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(std::sync::Mutex::new(file))
+        })
+    };
+
+    // Fails if already set, in which case the existing one is fine.
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(terminal)
+        .with(file)
+        .with(ConsoleLayer)
+        .try_init();
+}
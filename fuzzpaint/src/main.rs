@@ -16,12 +16,23 @@ pub mod vulkano_prelude;
 pub mod window;
 use vulkano_prelude::*;
 pub mod actions;
+pub mod args;
+pub mod canvas;
+pub mod crash;
+pub mod diagnostics;
 pub mod document_viewport_proxy;
+pub mod errors;
+pub mod export;
 pub mod gizmos;
 pub mod global;
+pub mod i18n;
+pub mod input;
+pub mod instance;
+pub mod logging;
 pub mod pen_tools;
 pub mod picker;
 pub mod render_device;
+pub mod replay;
 pub mod stylus_events;
 pub mod text;
 pub mod ui;
@@ -48,7 +59,7 @@ const DOCUMENT_DIMENSION: u32 = 1080;
 /// Will it be user specified in the future?
 const DOCUMENT_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
 
-use anyhow::Result as AnyResult;
+use anyhow::{Context as _, Result as AnyResult};
 
 /// FIXME! This is temp until I can see that everything is working :3
 /// There still needs to be a way to intercommunicate between UI selections, Pen actions, and renderer preview.
@@ -56,6 +67,10 @@ use anyhow::Result as AnyResult;
 pub struct AdHocGlobals {
     pub document: fuzzpaint_core::state::document::ID,
     pub brush: fuzzpaint_core::state::StrokeBrushSettings,
+    /// The color swapped with the brush's color by [`actions::Action::ColorSwap`].
+    pub secondary_color: fuzzpaint_core::color::ColorOrPalette,
+    /// What to do when the brush is used with the stylus' eraser end.
+    pub eraser_tip: pen_tools::EraserTipMode,
     pub node: Option<fuzzpaint_core::state::graph::AnyID>,
 }
 impl AdHocGlobals {
@@ -75,44 +90,312 @@ impl AdHocGlobals {
 async fn stylus_event_collector(
     mut event_stream: tokio::sync::broadcast::Receiver<stylus_events::StylusEventFrame>,
     ui_requests: crossbeam::channel::Receiver<ui::requests::UiRequest>,
-    _: tokio::sync::mpsc::Sender<renderer::requests::RenderRequest>,
+    render_requests: tokio::sync::mpsc::Sender<renderer::requests::RenderRequest>,
     mut action_listener: actions::ActionListener,
     mut tools: pen_tools::ToolState,
     document_preview: Arc<document_viewport_proxy::Proxy>,
+    tool_overlay: Arc<document_viewport_proxy::GizmoOverlay>,
+    mut mode: replay::Mode,
 ) -> AnyResult<()> {
+    // The zoom last reported to the renderer.
+    let mut zoom = None;
+    // The document whose view is shown. Each keeps its own, swapped in when it becomes current.
+    let mut shown = None;
+    // The layer last selected by a replay, held over the interface's selection for the tools.
+    let mut replayed_layer = None;
     loop {
-        match event_stream.recv().await {
-            Ok(stylus_frame) => {
-                // We need a transform in order to do any of our work!
-                let Some(transform) = document_preview.get_view_transform().await else {
-                    continue;
-                };
-
-                // Get the actions, returning if stream closed.
-                let action_frame = match action_listener.frame() {
-                    Ok(frame) => frame,
-                    Err(e) => match e {
-                        actions::ListenError::Closed => return Ok(()),
-                        // Todo: this is recoverable!
-                        actions::ListenError::Poisoned => todo!(),
-                    },
-                };
-
-                let render = tools
-                    .process(&transform, stylus_frame, &action_frame, &ui_requests)
-                    .await;
-
-                if let Some(transform) = render.set_view {
-                    document_preview.insert_document_transform(transform).await;
+        let document = AdHocGlobals::get()
+            .read()
+            .as_ref()
+            .map(|globals| globals.document);
+        // A replay takes the place of the window's input until it's done. It advances on its own clock, as the
+        // window only sends frames while there's input.
+        let (stylus_frame, replayed) = match &mut mode {
+            replay::Mode::Replay(player) => tokio::select! {
+                replayed = player.next(document) => (None, Some(replayed)),
+                live = event_stream.recv() => (Some(live), None),
+            },
+            _ => (Some(event_stream.recv().await), None),
+        };
+        let replayed = match replayed {
+            None => None,
+            Some(Ok(Some(replayed))) => Some(replayed),
+            Some(Ok(None)) => {
+                if let replay::Mode::Replay(player) = &mode {
+                    tracing::info!("replay finished, {}", player.summary());
                 }
-                document_preview.insert_cursor(render.cursor);
-                document_preview.insert_tool_render(render.render_as);
+                mode = replay::Mode::Live;
+                replayed_layer = None;
+                continue;
             }
+            Some(Err(e)) => {
+                tracing::error!("replay failed, returning to live input: {e:#}");
+                mode = replay::Mode::Live;
+                replayed_layer = None;
+                continue;
+            }
+        };
+        let stylus_frame = match stylus_frame.transpose() {
+            Ok(stylus_frame) => stylus_frame,
             Err(tokio::sync::broadcast::error::RecvError::Lagged(num)) => {
-                log::warn!("Lost {num} stylus frames!");
+                tracing::warn!("Lost {num} stylus frames!");
+                continue;
             }
             // Stream closed, no more data to handle - we're done here!
             Err(tokio::sync::broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        if document != shown {
+            shown = document;
+            if let Some(document) = document {
+                let view = global::session::view(document).unwrap_or_default();
+                document_preview.insert_document_transform(view).await;
+            }
+        }
+        if let Some(view) = replayed.as_ref().and_then(|replayed| replayed.view) {
+            document_preview
+                .insert_document_transform(view.transform)
+                .await;
+        }
+        // We need a transform in order to do any of our work!
+        let Some(transform) = document_preview.get_view_transform().await else {
+            continue;
+        };
+        // The replay's own, as its pen positions are relative to its viewport.
+        let transform = replayed
+            .as_ref()
+            .and_then(|replayed| replayed.view)
+            .unwrap_or(transform);
+
+        // Get the actions, returning if stream closed.
+        let action_frame = match action_listener.frame() {
+            Ok(frame) => frame,
+            Err(e) => match e {
+                actions::ListenError::Closed => return Ok(()),
+                // Todo: this is recoverable!
+                actions::ListenError::Poisoned => todo!(),
+            },
+        };
+
+        // Forward those meant for the renderer, the rest are for the tools.
+        let ui_requests: Vec<_> = ui_requests
+            .try_iter()
+            .filter_map(|request| match request {
+                ui::requests::UiRequest::Document {
+                    target,
+                    request: ui::requests::DocumentRequest::Export(settings),
+                } => {
+                    let request = renderer::requests::RenderRequest::Export {
+                        document: target,
+                        settings,
+                    };
+                    if render_requests.try_send(request).is_err() {
+                        tracing::error!("renderer is busy, export dropped");
+                    }
+                    None
+                }
+                ui::requests::UiRequest::Document {
+                    target,
+                    request: ui::requests::DocumentRequest::Autocrop,
+                } => {
+                    let request = renderer::requests::RenderRequest::Autocrop { document: target };
+                    if render_requests.try_send(request).is_err() {
+                        tracing::error!("renderer is busy, trim dropped");
+                    }
+                    None
+                }
+                ui::requests::UiRequest::Document {
+                    target,
+                    request: ui::requests::DocumentRequest::ExportLayers(settings),
+                } => {
+                    let request = renderer::requests::RenderRequest::ExportLayers {
+                        document: target,
+                        settings,
+                    };
+                    if render_requests.try_send(request).is_err() {
+                        tracing::error!("renderer is busy, layer export dropped");
+                    }
+                    None
+                }
+                ui::requests::UiRequest::Document {
+                    target,
+                    request: ui::requests::DocumentRequest::ShowSavedDiff(enabled),
+                } => {
+                    let request = renderer::requests::RenderRequest::SavedDiff {
+                        document: target,
+                        enabled,
+                    };
+                    if render_requests.try_send(request).is_err() {
+                        tracing::error!("renderer is busy, saved diff toggle dropped");
+                    }
+                    None
+                }
+                ui::requests::UiRequest::Document {
+                    target,
+                    request: ui::requests::DocumentRequest::Solo(node),
+                } => {
+                    let request = renderer::requests::RenderRequest::Solo {
+                        document: target,
+                        node,
+                    };
+                    if render_requests.try_send(request).is_err() {
+                        tracing::error!("renderer is busy, solo toggle dropped");
+                    }
+                    None
+                }
+                ui::requests::UiRequest::Document {
+                    target,
+                    request: ui::requests::DocumentRequest::Timelapse(settings),
+                } => {
+                    let request = renderer::requests::RenderRequest::Timelapse {
+                        document: target,
+                        settings,
+                    };
+                    if render_requests.try_send(request).is_err() {
+                        tracing::error!("renderer is busy, timelapse dropped");
+                    }
+                    None
+                }
+                ui::requests::UiRequest::Document {
+                    target,
+                    request: ui::requests::DocumentRequest::PreviewFilter { node, filter },
+                } => {
+                    // Only a preview, a newer one will come along if this is dropped.
+                    let _ = render_requests.try_send(
+                        renderer::requests::RenderRequest::PreviewFilter {
+                            document: target,
+                            node,
+                            filter,
+                        },
+                    );
+                    None
+                }
+                request => Some(request),
+            })
+            .collect();
+
+        let is_replayed = replayed.is_some();
+        let (stylus_frame, action_frame, ui_requests) = match (replayed, stylus_frame) {
+            (Some(replayed), _) => {
+                if let Some(path) = replayed.layer {
+                    replayed_layer = Some(path);
+                }
+                let ui_requests = replayed
+                    .tool
+                    .map(|tool| ui::requests::UiRequest::SetBaseTool { tool })
+                    .into_iter()
+                    .collect();
+                (replayed.stylus, replayed.actions, ui_requests)
+            }
+            // Live input is still drained while replaying, so it doesn't pile up.
+            (None, _) if matches!(mode, replay::Mode::Replay(_)) => continue,
+            (None, Some(stylus_frame)) => (stylus_frame, action_frame, ui_requests),
+            // Only replays make frames of their own.
+            (None, None) => unreachable!(),
+        };
+        if let (Some(path), Some(document)) = (&replayed_layer, document) {
+            let layer = global::provider()
+                .inspect(document, |queue| {
+                    global::session::layer_at(queue.peek_clone_state().graph(), path)
+                })
+                .flatten();
+            if let Some(globals) = AdHocGlobals::get().write().as_mut() {
+                globals.node = layer;
+            }
+        }
+
+        let captured = match &mut mode {
+            replay::Mode::Capture(recorder) => {
+                recorder.begin(document);
+                let tool = ui_requests.iter().rev().find_map(|request| match request {
+                    ui::requests::UiRequest::SetBaseTool { tool } => Some(*tool),
+                    ui::requests::UiRequest::Document { .. } => None,
+                });
+                Some((tool, stylus_frame.clone()))
+            }
+            _ => None,
+        };
+
+        let render = tools
+            .process(&transform, stylus_frame, &action_frame, ui_requests)
+            .await;
+        if let (replay::Mode::Replay(player), true) = (&mut mode, is_replayed) {
+            player.end();
+        }
+
+        if let (replay::Mode::Capture(recorder), Some((tool, stylus))) = (&mut mode, captured) {
+            let layer = AdHocGlobals::read_clone().map(|globals| (globals.document, globals.node));
+            if let Err(e) = recorder.end(&transform, layer, tool, &stylus, action_frame.events()) {
+                tracing::error!("failed to write capture, stopping: {e:#}");
+                mode = replay::Mode::Live;
+            }
+        }
+
+        if let Some(transform) = render.set_view {
+            document_preview.insert_document_transform(transform).await;
+            if let Some(document) = shown {
+                global::session::set_view(document, transform);
+            }
+        }
+        // Toggle first, then cycle, in case both were pressed this frame.
+        let grayscale = action_frame.action_trigger_count(actions::Action::ViewportGrayscale);
+        let cycle = action_frame.action_trigger_count(actions::Action::ViewportFilterCycle);
+        if grayscale % 2 == 1 || cycle > 0 {
+            let mut filter = document_preview.view_filter();
+            if grayscale % 2 == 1 {
+                filter = if filter == document_viewport_proxy::ViewFilter::Grayscale {
+                    document_viewport_proxy::ViewFilter::None
+                } else {
+                    document_viewport_proxy::ViewFilter::Grayscale
+                };
+            }
+            for _ in 0..cycle {
+                filter = filter.next();
+            }
+            document_preview.set_view_filter(filter).await;
+        }
+        for (id, event) in render.gizmo_events {
+            gizmos::emit(id, event);
+        }
+        if let (Some((point, response)), Some(globals)) =
+            (render.sample_color, AdHocGlobals::read_clone())
+        {
+            // Dropped if busy, the tool asks again.
+            let _ = render_requests.try_send(renderer::requests::RenderRequest::SampleColor {
+                document: globals.document,
+                point,
+                response,
+            });
+        }
+        tool_overlay.insert_cursor(render.cursor);
+        tool_overlay.insert_tool_render(render.render_as);
+
+        let smart_zoom = global::preferences::Preferences::read().smart_zoom
+            && !render_device::is_software_rendering();
+        let new_zoom = match AdHocGlobals::read_clone() {
+            Some(globals) if smart_zoom => Some((
+                globals.document,
+                document_preview.zoom_region().await,
+                document_preview.zoom_lod().await,
+            )),
+            _ => None,
+        };
+        if new_zoom != zoom {
+            let (document, region, lod) = match (new_zoom, zoom) {
+                (Some(new), _) => new,
+                // Turned off or no document, unzoom the one that was.
+                (None, Some((document, ..))) => (document, None, 0),
+                // Unequal, so at least one is Some.
+                (None, None) => unreachable!(),
+            };
+            let request = renderer::requests::RenderRequest::Zoom {
+                document,
+                region,
+                lod,
+            };
+            // Retried next frame if the renderer is busy.
+            if render_requests.try_send(request).is_ok() {
+                zoom = new_zoom;
+            }
         }
     }
 }
@@ -120,44 +403,97 @@ async fn stylus_event_collector(
 //If we return, it was due to an error.
 //convert::Infallible is a quite ironic name for this useage, isn't it? :P
 fn main() -> AnyResult<()> {
-    let has_term = std::io::IsTerminal::is_terminal(&std::io::stdin());
-    // Log to a terminal, if available. Else, log to "log.out" in the working directory.
-    if has_term {
-        env_logger::Builder::new()
-            .filter_level(log::LevelFilter::Debug)
-            .parse_default_env()
-            .init();
-    } else {
-        let _ = simple_logging::log_to_file("log.out", log::LevelFilter::Debug);
+    let args = match args::Args::from_env() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{}", args::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", args::USAGE);
+        return Ok(());
     }
+    logging::init(args.log.as_deref());
+    crash::install();
     #[cfg(feature = "dhat_heap")]
     let _profiler = {
-        log::trace!("Installed dhat");
+        tracing::trace!("Installed dhat");
         dhat::Profiler::new_heap()
     };
 
+    // The benchmark and replays have nothing to do with any other instance.
+    if args.bench.is_none() && args.replay.is_none() && !args.new_instance {
+        match instance::hand_off(&args.paths, args.read_only) {
+            Ok(true) => {
+                tracing::info!("handed off to the running instance");
+                return Ok(());
+            }
+            Ok(false) => {
+                if let Err(e) = instance::listen() {
+                    tracing::warn!("failed to listen for other instances: {e}");
+                }
+            }
+            Err(e) => tracing::warn!("failed to hand off to the running instance: {e}"),
+        }
+    }
+
+    if let Err(e) = global::journals::spawn() {
+        tracing::warn!(
+            "failed to start journaling, changes won't be recoverable after a crash: {e}"
+        );
+    }
+    if let Err(e) = global::history_spool::spawn() {
+        tracing::warn!("failed to start spooling history, it will grow without bound: {e}");
+    }
+
+    // With nothing else to open, pick up where the last session left off if asked to.
+    let paths = if args.paths.is_empty() {
+        global::session::Session::last().map_or_else(Vec::new, |session| {
+            // Passing over those since moved or deleted.
+            session
+                .documents
+                .iter()
+                .map(|document| &document.path)
+                .filter(|path| path.exists())
+                .cloned()
+                .collect()
+        })
+    } else {
+        args.paths
+    };
+    // Opened from the first path, which a headless replay plays on. The rest load in no particular order.
+    let first_document = std::sync::OnceLock::new();
     let loading_succeeded = {
-        use rayon::iter::{IntoParallelIterator, ParallelIterator};
-        // Args are a simple list of paths to open at startup.
-        // Paths are OSStrings, let the system handle character encoding restrictions.
-        // Todo: Expand glob patterns on windows (on unix this is handled by shell)
-        let paths: Vec<std::path::PathBuf> = std::env::args_os().skip(1).map(Into::into).collect();
+        use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+        let read_only = args.read_only;
         // Did we have at least one success? No paths is a success.
         let had_success: std::sync::atomic::AtomicBool = paths.is_empty().into();
-        let repo = crate::global::points();
-        paths.into_par_iter().for_each(|path| {
+        paths.into_par_iter().enumerate().for_each(|(idx, path)| {
             let try_block =
                 || -> Result<fuzzpaint_core::queue::DocumentCommandQueue, std::io::Error> {
-                    fuzzpaint_core::io::read_path(&path, repo)
+                    global::file_locks::open(
+                        &path,
+                        read_only,
+                        &fuzzpaint_core::progress::Progress::detached(),
+                    )
                 };
 
             match try_block() {
                 Err(e) => {
-                    log::error!("failed to open file {path:?}: {e:#}");
+                    errors::Report::new(
+                        errors::Severity::Recoverable,
+                        i18n::tr!("open-failed", path = path.display().to_string()),
+                        &e.into(),
+                    )
+                    .send();
                 }
                 Ok(queue) => {
                     // We don't care when it's stored, so long as it gets there eventually.
                     had_success.store(true, std::sync::atomic::Ordering::Relaxed);
+                    if idx == 0 {
+                        let _ = first_document.set(queue.id());
+                    }
                     // Defaulted ID, can't fail
                     let _ = global::provider().insert(queue);
                 }
@@ -170,18 +506,91 @@ fn main() -> AnyResult<()> {
     // This should abort the startup if ran from commandline, or give a visual warning and continue
     // if using a GUI.
     if !loading_succeeded {
-        log::warn!("Failed to load any provided document.");
+        tracing::warn!("Failed to load any provided document.");
+    }
+
+    let language = global::preferences::Preferences::read().language.clone();
+    if let Err(e) = i18n::set_language(language.as_deref()) {
+        tracing::warn!("failed to load language {language:?}, showing English instead: {e:#}");
+    }
+
+    if args.no_vsync {
+        // Only for this session, the preference is left as it is.
+        render_device::force_low_latency();
     }
 
+    // Command line takes precedence.
+    let device = args
+        .device
+        .map(render_device::PreferredDevice::Index)
+        .or_else(|| {
+            global::preferences::Preferences::read()
+                .device
+                .clone()
+                .map(render_device::PreferredDevice::Name)
+        });
     let window_surface = window::Surface::new()?;
-    let (render_context, render_surface) =
-        render_device::RenderContext::new_with_window_surface(&window_surface)?;
+    let (render_context, render_surface) = render_device::RenderContext::new_with_window_surface(
+        &window_surface,
+        render_device::wants_low_latency(),
+        device.as_ref(),
+    )?;
+    diagnostics::set_device_info(render_context.device_description());
+
+    if let Some(config) = args.bench {
+        let report = renderer::bench::run(render_context, &config)?;
+        println!("{report}");
+        return Ok(());
+    }
 
-    let document_view = Arc::new(document_viewport_proxy::Proxy::new(&render_surface)?);
+    let mode = match (&args.replay, &args.capture) {
+        (Some(path), _) => {
+            let capture = replay::Capture::load(path)
+                .with_context(|| format!("failed to load capture {}", path.display()))?;
+            if args.headless {
+                let summary =
+                    replay::run_headless(&render_context, capture, first_document.get().copied())?;
+                println!("{summary}");
+                // Scripts can tell whether the bug reproduced.
+                std::process::exit(i32::from(summary.diverged > 0));
+            }
+            replay::Mode::Replay(replay::Player::new(capture, true))
+        }
+        (None, Some(path)) => match replay::Recorder::create(path) {
+            Ok(recorder) => {
+                tracing::info!("capturing input to {}", path.display());
+                replay::Mode::Capture(recorder)
+            }
+            Err(e) => {
+                tracing::error!("failed to start capture to {}: {e:#}", path.display());
+                replay::Mode::Live
+            }
+        },
+        (None, None) => replay::Mode::Live,
+    };
+
+    let document_view = Arc::new(document_viewport_proxy::Proxy::new(
+        &render_surface,
+        global::preferences::Preferences::read().preview_buffers,
+    )?);
+    let tool_overlay = Arc::new(document_viewport_proxy::GizmoOverlay::new(
+        &render_surface,
+        document_view.clone(),
+    )?);
+    let mut preview = document_viewport_proxy::ProxyStack::default();
+    preview.push(
+        document_viewport_proxy::ProxyLayer::Document,
+        document_view.clone(),
+    );
+    preview.push(
+        document_viewport_proxy::ProxyLayer::Overlay,
+        tool_overlay.clone(),
+    );
     let window_renderer = window_surface.with_render_surface(
         render_surface,
         render_context.clone(),
-        document_view.clone(),
+        preview,
+        document_view,
     )?;
 
     let event_stream = window_renderer.stylus_events();
@@ -205,6 +614,8 @@ fn main() -> AnyResult<()> {
                 let (send, recv) = tokio::sync::mpsc::channel(4);
 
                 let runtime = tokio::runtime::Builder::new_current_thread()
+                    // For background document renders.
+                    .enable_time()
                     .build()
                     .unwrap();
                 // between current_thread runtime and try_join, these tasks are
@@ -220,12 +631,17 @@ fn main() -> AnyResult<()> {
                             action_listener,
                             tools,
                             document_view,
+                            tool_overlay,
+                            mode,
                         ),
                     )
                 })
             };
             if let Err(e) = result {
-                log::error!("Helper task exited with err, runtime terminated:\n{e:?}");
+                // Documents are still held by the provider, and can be written out without the renderer.
+                errors::Report::new(errors::Severity::Fatal, i18n::tr!("renderer-stopped"), &e)
+                    .with_remedy(i18n::tr!("renderer-stopped.remedy"))
+                    .send();
             }
         })
         .unwrap();
@@ -122,12 +122,13 @@ impl Default for StrokeBuilder {
             roll: vec![],
             wheel: vec![],
             current_archetype: Archetype::POSITION,
-            packed_elements: vec![],
+            start: None,
         }
     }
 }
 /// Structure-of-arrays form of a list of [`InputPoint`]s which can then be packed into a [`StrokeSlice`].
 /// This allows for dynamic packing structure across strokes.
+#[derive(Clone)]
 pub struct StrokeBuilder {
     /// Currently required! As such, this indicates len.
     position: Vec<[f32; 2]>,
@@ -139,8 +140,8 @@ pub struct StrokeBuilder {
     wheel: Vec<f32>,
     /// Which of the vecs are active?
     current_archetype: Archetype,
-    /// On finish, write elements out to here and borrow them as a StrokeSlice.
-    packed_elements: Vec<u32>,
+    /// When the first timed point was drawn, which the times of points count from.
+    start: Option<std::time::Instant>,
 }
 impl StrokeBuilder {
     pub fn clear(&mut self) {
@@ -153,6 +154,13 @@ impl StrokeBuilder {
         self.wheel.clear();
         // Position is required.
         self.current_archetype = Archetype::POSITION;
+        self.start = None;
+    }
+    /// Time of a point drawn at `instant`, since the first point to be timed.
+    pub fn time_of(&mut self, instant: std::time::Instant) -> Microseconds {
+        let start = *self.start.get_or_insert(instant);
+        let micros = instant.saturating_duration_since(start).as_micros();
+        Microseconds(u32::try_from(micros).unwrap_or(u32::MAX))
     }
     pub fn transform(&mut self, mat: &ultraviolet::Mat3) {
         use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
@@ -234,92 +242,433 @@ impl StrokeBuilder {
             self.wheel.push(v);
         }
     }
-    /// Pack the contents and borrow them as a stroke.
-    pub fn consume(&mut self) -> StrokeSlice {
-        self.packed_elements.clear();
+    /// Distance along the stroke of each point.
+    fn arc_lengths(&self) -> Vec<f32> {
+        self.position
+            .iter()
+            .scan(
+                (0.0f32, None::<[f32; 2]>),
+                |(arclen, last_position), &position| {
+                    if let Some(last_position) = last_position.replace(position) {
+                        let delta = [
+                            last_position[0] - position[0],
+                            last_position[1] - position[1],
+                        ];
+                        *arclen += (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+                    }
+                    Some(*arclen)
+                },
+            )
+            .collect()
+    }
+    /// Speed of the pen at each point in units per second, given their `arc_lengths`. None if the points
+    /// aren't timed.
+    fn speeds(&self, arc_lengths: &[f32]) -> Option<Vec<f32>> {
+        if self.time.is_empty() {
+            return None;
+        }
+        // Several points may arrive at once, so speed is only known once time has passed. Averaged over
+        // recent points to hide jitter in their timing.
+        let mut speed = 0.0f32;
+        let mut last = (0.0f32, self.time[0]);
+        Some(
+            arc_lengths
+                .iter()
+                .zip(&self.time)
+                .map(|(&arc_length, &time)| {
+                    let (last_length, last_time) = last;
+                    if time.0 > last_time.0 {
+                        // as: precision loss is irrelevant at this scale.
+                        #[allow(clippy::cast_precision_loss)]
+                        let elapsed = (time.0 - last_time.0) as f32 / 1_000_000.0;
+                        speed = ((arc_length - last_length) / elapsed - speed).mul_add(0.5, speed);
+                        last = (arc_length, time);
+                    }
+                    speed
+                })
+                .collect(),
+        )
+    }
+    /// Thin the stroke towards its ends and where it was drawn quickly by scaling its pressure, see
+    /// [`Taper`](fuzzpaint_core::state::Taper). Lengths are in the units of the points' positions.
+    pub fn taper(&mut self, taper: fuzzpaint_core::state::Taper) {
+        let Some(factors) = self.taper_factors(taper) else {
+            return;
+        };
+        // Full pressure, if none was reported.
+        if !self.current_archetype.intersects(Archetype::PRESSURE) {
+            self.pressure
+                .resize(self.len(), InputPoint::DEFAULT_PRESSURE);
+            self.current_archetype |= Archetype::PRESSURE;
+        }
+        for (pressure, factor) in self.pressure.iter_mut().zip(factors) {
+            *pressure *= factor;
+        }
+    }
+    /// What [`Self::taper`] scales each point's pressure by, as if the stroke ended at its last point. None if
+    /// it changes nothing.
+    #[must_use]
+    pub fn taper_factors(&self, taper: fuzzpaint_core::state::Taper) -> Option<Vec<f32>> {
+        if taper.is_none() || self.is_empty() {
+            return None;
+        }
+        let arc_lengths = self.arc_lengths();
+        // Unwrap ok - not empty.
+        let length = *arc_lengths.last().unwrap();
+        let speeds = self.speeds(&arc_lengths);
+        Some(
+            arc_lengths
+                .iter()
+                .enumerate()
+                .map(|(idx, &arc_length)| {
+                    let speed = speeds.as_ref().map(|speeds| speeds[idx]);
+                    taper.factor(arc_length, length, speed)
+                })
+                .collect(),
+        )
+    }
+    /// Pack the points into elements of the returned archetype, calculating arc lengths as we go.
+    ///
+    /// Strokes may be hundreds of thousands of points long, so this is done in parallel. Prefer to call
+    /// from a worker, see [`submit`].
+    #[must_use]
+    pub fn pack(&self) -> (Vec<u32>, Archetype) {
+        use rayon::{
+            iter::{IndexedParallelIterator, ParallelIterator},
+            slice::ParallelSliceMut,
+        };
 
         let archetype = self.current_archetype | Archetype::ARC_LENGTH;
         let point_size = archetype.elements();
-        let packed_len = point_size * self.len();
-        self.packed_elements.resize(packed_len, 0);
-
-        // Write out positions + calc arclens as we go!
-        // (Positions + Arclen are required rn)
-        {
-            let mut arclen = 0.0f32;
-            let mut last_position = None;
-            for (idx, &position) in self.position.iter().enumerate() {
-                let base = idx * point_size;
-                let position_offs = archetype.offset_of(Archetype::POSITION).unwrap();
-                self.packed_elements[base + position_offs] = bytemuck::cast(position[0]);
-                self.packed_elements[base + position_offs + 1] = bytemuck::cast(position[1]);
-
-                // Calc and write arc len
-                let arclen_offs = archetype.offset_of(Archetype::ARC_LENGTH).unwrap();
-                if let Some(last_position) = last_position.replace(position) {
-                    let delta = [
-                        last_position[0] - position[0],
-                        last_position[1] - position[1],
-                    ];
-                    arclen += (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+
+        // Arc length is a running sum, the only part that can't be done per-point.
+        let arc_lengths = self.arc_lengths();
+
+        let mut packed = vec![0; point_size * self.len()];
+        packed
+            .par_chunks_exact_mut(point_size)
+            .enumerate()
+            .for_each(|(idx, point)| {
+                let mut write = |element, values: &[u32]| {
+                    if let Some(offs) = archetype.offset_of(element) {
+                        point[offs..offs + values.len()].copy_from_slice(values);
+                    }
+                };
+                // Every field besides position may be missing, in which case the archetype doesn't
+                // include it and nothing is written.
+                write(
+                    Archetype::POSITION,
+                    bytemuck::cast_slice(&self.position[idx]),
+                );
+                write(Archetype::ARC_LENGTH, &[bytemuck::cast(arc_lengths[idx])]);
+                if let Some(v) = self.time.get(idx) {
+                    write(Archetype::TIME, &[bytemuck::cast(*v)]);
                 }
-                self.packed_elements[base + arclen_offs] = bytemuck::cast(arclen);
-            }
+                if let Some(v) = self.pressure.get(idx) {
+                    write(Archetype::PRESSURE, &[bytemuck::cast(*v)]);
+                }
+                if let Some(v) = self.tilt.get(idx) {
+                    write(Archetype::TILT, bytemuck::cast_slice(v));
+                }
+                if let Some(v) = self.distance.get(idx) {
+                    write(Archetype::DISTANCE, &[bytemuck::cast(*v)]);
+                }
+                if let Some(v) = self.roll.get(idx) {
+                    write(Archetype::ROLL, &[bytemuck::cast(*v)]);
+                }
+                if let Some(v) = self.wheel.get(idx) {
+                    write(Archetype::WHEEL, &[bytemuck::cast(*v)]);
+                }
+            });
+
+        (packed, archetype)
+    }
+}
+
+/// A stroke that's been drawn, waiting to be packed and inserted into its document.
+struct FinishedStroke {
+    document: fuzzpaint_core::state::document::ID,
+    /// The stroke layer to insert into.
+    node: fuzzpaint_core::state::graph::AnyID,
+    settings: fuzzpaint_core::state::StrokeBrushSettings,
+    /// Selection to paint within, if any.
+    clip: Option<fuzzpaint_core::repositories::points::PointCollectionID>,
+    /// See [`crate::stylus_events::StylusEvent::device`].
+    device: Option<u64>,
+    /// Points in document space.
+    builder: StrokeBuilder,
+}
+/// A [`FinishedStroke`] tapered, moved into its layer's space, and packed, for the transforms it was packed
+/// with.
+struct PackedStroke {
+    inner: fuzzpaint_core::state::transform::Similarity,
+    outer: fuzzpaint_core::state::transform::Matrix,
+    elements: Vec<u32>,
+    archetype: Archetype,
+}
+impl FinishedStroke {
+    fn insert(self) -> anyhow::Result<()> {
+        let (document, settings) = (self.document, self.settings);
+        // Packing a long stroke takes a while, do it without holding up other writers to the document.
+        let packed = crate::global::provider()
+            .inspect(document, |queue| self.pack(queue))
+            .flatten();
+        let Some(result) = crate::global::provider()
+            .write(document, "brush", |queue| self.insert_into(queue, packed))
+        else {
+            anyhow::bail!("document closed before the stroke was inserted")
+        };
+        result?;
+        // Erasing and smudging don't lay down their color.
+        if !settings.is_eraser && !settings.is_smudge {
+            crate::global::recent_colors::push(document, settings.color_modulate);
         }
-        if archetype.intersects(Archetype::TIME) {
-            for (idx, &v) in self.time.iter().enumerate() {
-                let base = idx * point_size;
-                let offs = archetype.offset_of(Archetype::TIME).unwrap();
-                self.packed_elements[base + offs] = bytemuck::cast(v);
-            }
+        Ok(())
+    }
+    /// The transforms of the stroke layer to insert into, as of `queue`'s current state.
+    fn transforms(
+        &self,
+        queue: &fuzzpaint_core::queue::DocumentCommandQueue,
+    ) -> Option<(
+        fuzzpaint_core::state::transform::Similarity,
+        fuzzpaint_core::state::transform::Matrix,
+    )> {
+        use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+        let state = queue.peek_clone_state();
+        let fuzzpaint_core::state::graph::LeafType::StrokeLayer {
+            inner_transform,
+            outer_transform,
+            ..
+        } = state.graph().get(self.node)?.leaf()?
+        else {
+            return None;
+        };
+        Some((*inner_transform, *outer_transform))
+    }
+    /// Pack the stroke for its layer as it is in `queue`, without locking the queue. None if the layer isn't
+    /// a stroke layer.
+    fn pack(&self, queue: &fuzzpaint_core::queue::DocumentCommandQueue) -> Option<PackedStroke> {
+        let (inner, outer) = self.transforms(queue)?;
+        Some(self.pack_with(inner, outer))
+    }
+    fn pack_with(
+        &self,
+        inner: fuzzpaint_core::state::transform::Similarity,
+        outer: fuzzpaint_core::state::transform::Matrix,
+    ) -> PackedStroke {
+        let mut builder = self.builder.clone();
+        // In document space, as the taper lengths are.
+        builder.taper(self.settings.taper);
+        builder.transform(&TransformInfo::new(&inner, &outer).inverse);
+        let (elements, archetype) = builder.pack();
+        PackedStroke {
+            inner,
+            outer,
+            elements,
+            archetype,
         }
-        if archetype.intersects(Archetype::PRESSURE) {
-            for (idx, &v) in self.pressure.iter().enumerate() {
-                let base = idx * point_size;
-                let offs = archetype.offset_of(Archetype::PRESSURE).unwrap();
-                self.packed_elements[base + offs] = bytemuck::cast(v);
+    }
+    /// Push the stroke into its layer of `queue`, which is [`Self::document`]. `packed` is used if the layer's
+    /// transforms haven't changed since, otherwise it's packed again.
+    fn insert_into(
+        self,
+        queue: &fuzzpaint_core::queue::DocumentCommandQueue,
+        packed: Option<PackedStroke>,
+    ) -> anyhow::Result<()> {
+        queue.write_with(|write| {
+            // Find the collection to insert into.
+            let (collection_id, inner, outer) = {
+                let graph = write.graph();
+                let node = graph.get(self.node).and_then(|node| node.leaf());
+                if let Some(fuzzpaint_core::state::graph::LeafType::StrokeLayer {
+                    collection,
+                    inner_transform,
+                    outer_transform,
+                    ..
+                }) = node
+                {
+                    (*collection, *inner_transform, *outer_transform)
+                } else {
+                    anyhow::bail!("Current layer is not a valid stroke layer.")
+                }
+            };
+
+            // Get the collection
+            let mut collections = write.stroke_collections();
+            let Some(mut collection_writer) = collections.get_mut(collection_id) else {
+                anyhow::bail!("current layer references nonexistant stroke collection")
+            };
+
+            // The layer was moved while we packed, rare enough to redo it here.
+            let PackedStroke {
+                elements,
+                archetype,
+                ..
+            } = packed
+                .filter(|packed| packed.inner == inner && packed.outer == outer)
+                .unwrap_or_else(|| self.pack_with(inner, outer));
+            // Unwrap ok - packed to exactly this archetype.
+            let stroke = StrokeSlice::new(&elements, archetype).unwrap();
+            let points = crate::global::points();
+            // Too long for one collection, split into a chain of strokes drawn one after another.
+            let max_points = fuzzpaint_core::repositories::points::max_points(archetype);
+            let Some(point_collections) = stroke
+                .segments(max_points)
+                .map(|segment| points.insert(rebase_arc_length(segment).as_slice()))
+                .collect::<Option<Vec<_>>>()
+            else {
+                anyhow::bail!("stroke data too large")
+            };
+            // Destructure immutable stroke and push it.
+            // Invokes an extra ID allocation, weh
+            let metadata = fuzzpaint_core::state::stroke_collection::StrokeMetadata {
+                device: self.device,
+                ..fuzzpaint_core::state::stroke_collection::StrokeMetadata::now()
+            };
+            for point_collection in point_collections {
+                collection_writer.push_back_with_metadata(
+                    self.settings,
+                    point_collection,
+                    self.clip,
+                    metadata,
+                );
             }
+
+            Ok(())
+        })
+    }
+}
+/// A segment of a stroke, with arc lengths starting from zero as the renderer expects.
+enum Rebased<'a> {
+    Borrowed(StrokeSlice<'a>),
+    Owned(Vec<u32>, Archetype),
+}
+impl Rebased<'_> {
+    fn as_slice(&self) -> StrokeSlice<'_> {
+        match self {
+            Self::Borrowed(slice) => *slice,
+            // Unwrap ok - copied from a slice of this archetype.
+            Self::Owned(elements, archetype) => StrokeSlice::new(elements, *archetype).unwrap(),
         }
-        if archetype.intersects(Archetype::TILT) {
-            for (idx, &v) in self.tilt.iter().enumerate() {
-                let base = idx * point_size;
-                let offs = archetype.offset_of(Archetype::TILT).unwrap();
-                self.packed_elements[base + offs] = bytemuck::cast(v[0]);
-                self.packed_elements[base + offs + 1] = bytemuck::cast(v[1]);
-            }
+    }
+}
+/// Shift the arc lengths of a [segment](StrokeSlice::segments) to start from zero. Only copies if they don't
+/// already.
+fn rebase_arc_length(segment: StrokeSlice<'_>) -> Rebased<'_> {
+    let archetype = segment.archetype();
+    let (Some(offset), Some(start)) = (
+        archetype.offset_of(Archetype::ARC_LENGTH),
+        segment.first().and_then(|point| point.arc_length()),
+    ) else {
+        return Rebased::Borrowed(segment);
+    };
+    if start == 0.0 {
+        return Rebased::Borrowed(segment);
+    }
+    let mut elements = segment.elements().to_vec();
+    for point in elements.chunks_exact_mut(archetype.elements()) {
+        let arc_length: f32 = bytemuck::cast(point[offset]);
+        point[offset] = bytemuck::cast(arc_length - start);
+    }
+    Rebased::Owned(elements, archetype)
+}
+/// Pack and insert the stroke on a worker, keeping the input thread free. Strokes are inserted in the
+/// order they're submitted.
+fn submit(stroke: FinishedStroke) {
+    static WORKER: std::sync::OnceLock<crossbeam::channel::Sender<FinishedStroke>> =
+        std::sync::OnceLock::new();
+    let worker = WORKER.get_or_init(|| {
+        let (send, recv) = crossbeam::channel::unbounded::<FinishedStroke>();
+        // A single thread to keep strokes in order. Each stroke's work is spread over the rayon pool.
+        let spawned = std::thread::Builder::new()
+            .name("stroke-worker".to_owned())
+            .spawn(move || {
+                for stroke in recv {
+                    if let Err(e) = stroke.insert() {
+                        tracing::warn!("failed to insert stroke: {e:?}");
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            tracing::error!("failed to start stroke worker: {e:?}");
         }
-        if archetype.intersects(Archetype::DISTANCE) {
-            for (idx, &v) in self.distance.iter().enumerate() {
-                let base = idx * point_size;
-                let offs = archetype.offset_of(Archetype::DISTANCE).unwrap();
-                self.packed_elements[base + offs] = bytemuck::cast(v);
-            }
+        send
+    });
+    if let Err(crossbeam::channel::SendError(stroke)) = worker.send(stroke) {
+        // Worker is gone, do it ourselves.
+        if let Err(e) = stroke.insert() {
+            tracing::warn!("failed to insert stroke: {e:?}");
         }
-        if archetype.intersects(Archetype::ROLL) {
-            for (idx, &v) in self.roll.iter().enumerate() {
-                let base = idx * point_size;
-                let offs = archetype.offset_of(Archetype::ROLL).unwrap();
-                self.packed_elements[base + offs] = bytemuck::cast(v);
+    }
+}
+
+/// Keeps a stroke along the document's [ruler](fuzzpaint_core::ruler), if it has one.
+#[derive(Default)]
+struct Assist {
+    /// The ruler, latched for the whole stroke.
+    ruler: Option<fuzzpaint_core::ruler::Ruler>,
+    /// Where the stroke started, in document and viewport space.
+    start: ([f32; 2], [f32; 2]),
+    /// The guide the stroke follows, once it's gone far enough to tell which.
+    guide: Option<fuzzpaint_core::ruler::Guide>,
+}
+impl Assist {
+    /// How far, in viewport pixels, the pen must travel before its direction chooses a guide.
+    const LATCH_DISTANCE: f32 = 6.0;
+    fn begin(
+        &mut self,
+        document: fuzzpaint_core::state::document::ID,
+        start: ([f32; 2], [f32; 2]),
+    ) {
+        *self = Self {
+            ruler: crate::global::rulers::active(document),
+            start,
+            guide: None,
+        };
+    }
+    /// Constrain a new point onto the guide. Once the guide is chosen, the `positions` drawn before it
+    /// are moved onto it too.
+    fn constrain(
+        &mut self,
+        (position, viewport): ([f32; 2], [f32; 2]),
+        positions: &mut [[f32; 2]],
+    ) -> [f32; 2] {
+        let Some(ruler) = self.ruler else {
+            return position;
+        };
+        if self.guide.is_none() {
+            let (start, start_viewport) = self.start;
+            let travel = (viewport[0] - start_viewport[0]).hypot(viewport[1] - start_viewport[1]);
+            if travel < Self::LATCH_DISTANCE {
+                // Still deciding.
+                return position;
             }
-        }
-        if archetype.intersects(Archetype::WHEEL) {
-            for (idx, &v) in self.wheel.iter().enumerate() {
-                let base = idx * point_size;
-                let offs = archetype.offset_of(Archetype::WHEEL).unwrap();
-                self.packed_elements[base + offs] = bytemuck::cast(v);
+            self.guide = ruler.guide(start, position);
+            if let Some(guide) = self.guide {
+                positions
+                    .iter_mut()
+                    .for_each(|position| *position = guide.project(*position));
             }
         }
-
-        self.clear();
-        StrokeSlice::new(&self.packed_elements, archetype).unwrap()
+        self.guide.map_or(position, |guide| guide.project(position))
     }
 }
 
-// Common core between eraser and brush
+// Common core between eraser and brush. Takes the current selections and preferences rather than reading
+// them, and hands each stroke to `finish` as it ends.
+#[allow(clippy::too_many_arguments)]
 fn brush(
     is_eraser: bool,
+    selections: Option<crate::AdHocGlobals>,
+    out_of_bounds: super::OutOfBounds,
+    finish: &mut dyn FnMut(FinishedStroke),
     builder: &mut StrokeBuilder,
     transform_cache: &mut Option<TransformInfo>,
+    // Whether the current stroke was started by the eraser end of the stylus.
+    eraser_tip: &mut bool,
+    // The tool the current stroke was started by.
+    device: &mut Option<u64>,
+    assist: &mut Assist,
 
     view: &super::ViewInfo,
     stylus_input: crate::stylus_events::StylusEventFrame,
@@ -330,13 +679,37 @@ fn brush(
     let Some(crate::AdHocGlobals {
         document,
         brush,
+        secondary_color,
+        eraser_tip: eraser_tip_mode,
         node: Some(node),
-    }) = crate::AdHocGlobals::read_clone()
+    }) = selections
     else {
         // Clear and bail.
         builder.clear();
         return;
     };
+    // Settings for a stroke, depending on which end of the stylus made it.
+    let settings_for = |eraser_tip: bool| match (eraser_tip, eraser_tip_mode) {
+        (true, super::EraserTipMode::Erase) => fuzzpaint_core::state::StrokeBrushSettings {
+            is_eraser: true,
+            is_smudge: false,
+            ..brush
+        },
+        (true, super::EraserTipMode::Secondary) => fuzzpaint_core::state::StrokeBrushSettings {
+            is_eraser,
+            // The eraser tool always erases.
+            is_smudge: brush.is_smudge && !is_eraser,
+            color_modulate: secondary_color,
+            ..brush
+        },
+        (false, _) | (true, super::EraserTipMode::Ignore) => {
+            fuzzpaint_core::state::StrokeBrushSettings {
+                is_eraser,
+                is_smudge: brush.is_smudge && !is_eraser,
+                ..brush
+            }
+        }
+    };
     let Some(view_transform) = view.calculate_transform() else {
         return;
     };
@@ -347,6 +720,16 @@ fn brush(
                 return;
             };
 
+            let pos = ([pos.x, pos.y], [event.pos.0, event.pos.1]);
+            // Latch the tip and ruler for the whole stroke.
+            if builder.is_empty() {
+                *eraser_tip = event.eraser;
+                *device = event.device;
+                assist.begin(document, pos);
+            }
+            let position =
+                out_of_bounds.apply(assist.constrain(pos, &mut builder.position), view.canvas);
+
             transform_cache.get_or_insert_with(|| {
                 crate::global::provider()
                     .inspect(document, |queue| {
@@ -370,9 +753,10 @@ fn brush(
                     .unwrap_or_default()
             });
 
+            let time = event.time.map(|time| builder.time_of(time));
             builder.push(InputPoint {
-                position: [pos.x, pos.y],
-                time: None,
+                position,
+                time,
                 pressure: event.pressure,
                 tilt: event.tilt.map(|(x, y)| [x, y]),
                 distance: event.dist,
@@ -382,58 +766,20 @@ fn brush(
         } else {
             if !builder.is_empty() {
                 // Not pressed but a stroke exists - just finished, upload it!
-                // Insert the stroke into the document.
-                if let Some(Err(e)) = crate::global::provider().inspect(document, |queue| {
-                    queue.write_with(|write| {
-                        // Find the collection to insert into.
-                        let (collection_id, inner, outer) = {
-                            let graph = write.graph();
-                            let node = graph.get(node).and_then(|node| node.leaf());
-                            if let Some(fuzzpaint_core::state::graph::LeafType::StrokeLayer {
-                                collection,
-                                inner_transform,
-                                outer_transform,
-                                ..
-                            }) = node
-                            {
-                                (*collection, *inner_transform, *outer_transform)
-                            } else {
-                                anyhow::bail!("Current layer is not a valid stroke layer.")
-                            }
-                        };
-
-                        // Get the collection
-                        let mut collections = write.stroke_collections();
-                        let Some(mut collection_writer) = collections.get_mut(collection_id) else {
-                            anyhow::bail!("current layer references nonexistant stroke collection")
-                        };
-
-                        let transform = TransformInfo::new(&inner, &outer);
-                        builder.transform(&transform.inverse);
-
-                        // Pack and store it away
-                        let stroke = builder.consume();
-                        let points = crate::global::points();
-                        let Some(point_collection) = points.insert(stroke) else {
-                            anyhow::bail!("stroke data too large")
-                        };
-                        // Destructure immutable stroke and push it.
-                        // Invokes an extra ID allocation, weh
-                        collection_writer.push_back(
-                            fuzzpaint_core::state::StrokeBrushSettings { is_eraser, ..brush },
-                            point_collection,
-                        );
-
-                        Ok(())
-                    })
-                }) {
-                    builder.clear();
-                    log::warn!("failed to insert stroke: {e:?}");
-                }
+                finish(FinishedStroke {
+                    document,
+                    node,
+                    settings: settings_for(*eraser_tip),
+                    // Paint only within the selection, if any.
+                    clip: crate::global::selection::get(document).map(|selection| selection.id),
+                    device: *device,
+                    builder: std::mem::take(builder),
+                });
             }
             *transform_cache = None;
         }
     }
+    let brush = settings_for(*eraser_tip);
     render_output.render_as = if builder.is_empty() {
         render_output.cursor = Some(crate::gizmos::CursorOrInvisible::Icon(
             winit::window::CursorIcon::Crosshair,
@@ -480,7 +826,8 @@ fn brush(
                     builder,
                     base_size,
                     size_factor,
-                    if is_eraser {
+                    brush.taper,
+                    if brush.is_eraser || brush.is_smudge {
                         None
                     } else {
                         // Todo: fetch if paletted.
@@ -494,10 +841,12 @@ fn brush(
         )
     }
 }
+/// The stroke in progress, tapered as it would be if lifted now.
 fn make_trail(
     stroke: &StrokeBuilder,
     min_size: f32,
     size_factor: f32,
+    taper: fuzzpaint_core::state::Taper,
     color: Option<fuzzpaint_core::color::Color>,
 ) -> crate::gizmos::Gizmo {
     use crate::gizmos::{transform::Transform, Gizmo, MeshMode, TextureMode, Visual};
@@ -527,6 +876,20 @@ fn make_trail(
                 point.width = pressure.mul_add(size_factor, min_size);
             });
     }
+    // Thinned just as the stroke will be once finished, see [`StrokeBuilder::taper`].
+    if let Some(factors) = stroke.taper_factors(taper) {
+        let pressures = stroke
+            .pressure
+            .iter()
+            .copied()
+            .chain(std::iter::repeat(InputPoint::DEFAULT_PRESSURE));
+        points
+            .iter_mut()
+            .zip(pressures.zip(factors))
+            .for_each(|(point, (pressure, factor))| {
+                point.width = (pressure * factor).mul_add(size_factor, min_size);
+            });
+    }
 
     let texture = match color.map(|c| c.as_array()) {
         Some([r, g, b, a]) => {
@@ -615,10 +978,16 @@ impl TransformInfo {
 pub struct Brush {
     stroke: StrokeBuilder,
     transforms: Option<TransformInfo>,
+    eraser_tip: bool,
+    device: Option<u64>,
+    assist: Assist,
 }
 pub struct Eraser {
     stroke: StrokeBuilder,
     transforms: Option<TransformInfo>,
+    eraser_tip: bool,
+    device: Option<u64>,
+    assist: Assist,
 }
 
 impl super::MakePenTool for Brush {
@@ -628,6 +997,9 @@ impl super::MakePenTool for Brush {
         Ok(Box::new(Brush {
             stroke: StrokeBuilder::default(),
             transforms: None,
+            eraser_tip: false,
+            device: None,
+            assist: Assist::default(),
         }))
     }
 }
@@ -638,6 +1010,9 @@ impl super::MakePenTool for Eraser {
         Ok(Box::new(Eraser {
             stroke: StrokeBuilder::default(),
             transforms: None,
+            eraser_tip: false,
+            device: None,
+            assist: Assist::default(),
         }))
     }
 }
@@ -657,8 +1032,14 @@ impl super::PenTool for Brush {
     ) {
         brush(
             actions.is_action_held(crate::actions::Action::Erase),
+            crate::AdHocGlobals::read_clone(),
+            crate::global::preferences::Preferences::read().out_of_bounds,
+            &mut submit,
             &mut self.stroke,
             &mut self.transforms,
+            &mut self.eraser_tip,
+            &mut self.device,
+            &mut self.assist,
             view_info,
             stylus_input,
             render_output,
@@ -681,11 +1062,312 @@ impl super::PenTool for Eraser {
     ) {
         brush(
             true,
+            crate::AdHocGlobals::read_clone(),
+            crate::global::preferences::Preferences::read().out_of_bounds,
+            &mut submit,
             &mut self.stroke,
             &mut self.transforms,
+            &mut self.eraser_tip,
+            &mut self.device,
+            &mut self.assist,
             view_info,
             stylus_input,
             render_output,
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{InputPoint, StrokeBuilder};
+    use fuzzpaint_core::stroke::Archetype;
+    /// Add an empty stroke layer to the document.
+    fn stroke_layer(
+        queue: &fuzzpaint_core::queue::DocumentCommandQueue,
+    ) -> (
+        fuzzpaint_core::state::graph::LeafID,
+        fuzzpaint_core::state::stroke_collection::StrokeCollectionID,
+    ) {
+        use fuzzpaint_core::state::graph::{LeafType, Location};
+        queue.write_with(|writer| {
+            let collection = writer.stroke_collections().insert();
+            let leaf = LeafType::StrokeLayer {
+                blend: fuzzpaint_core::blend::Blend::default(),
+                collection,
+                inner_transform: fuzzpaint_core::state::transform::Similarity::default(),
+                outer_transform: fuzzpaint_core::state::transform::Matrix::default(),
+            };
+            let node = writer
+                .graph()
+                .add_leaf(leaf, Location::IndexIntoRoot(0), "Strokes")
+                .unwrap();
+            (node, collection)
+        })
+    }
+    /// A plain brush, no dynamics or taper.
+    fn settings() -> fuzzpaint_core::state::StrokeBrushSettings {
+        fuzzpaint_core::state::StrokeBrushSettings {
+            brush: fuzzpaint_core::brush::UniqueID([7; 32]),
+            color_modulate: fuzzpaint_core::color::ColorOrPalette::BLACK,
+            size_mul: fuzzpaint_core::util::FiniteF32::new(12.0).unwrap(),
+            is_eraser: false,
+            is_smudge: false,
+            orientation: fuzzpaint_core::state::StampOrientation::Random,
+            scatter: fuzzpaint_core::state::Scatter::default(),
+            dual: None,
+            taper: fuzzpaint_core::state::Taper::default(),
+            dynamics: fuzzpaint_core::state::Dynamics::default(),
+            spacing_px: fuzzpaint_core::util::FiniteF32::new(0.5).unwrap(),
+        }
+    }
+    // Exact, as every value is a small integer or a half.
+    #[allow(clippy::float_cmp, clippy::cast_precision_loss)]
+    #[test]
+    fn pack_long_stroke() {
+        // A long scribble, with a whole lot of points.
+        const LEN: usize = 100_000;
+        let mut builder = StrokeBuilder::default();
+        for idx in 0..LEN {
+            builder.push(InputPoint {
+                position: [idx as f32, 0.0],
+                time: None,
+                pressure: Some(0.5),
+                tilt: None,
+                distance: None,
+                roll: None,
+                wheel: None,
+            });
+        }
+        let (elements, archetype) = builder.pack();
+        assert_eq!(
+            archetype,
+            Archetype::POSITION | Archetype::ARC_LENGTH | Archetype::PRESSURE
+        );
+        let point_size = archetype.elements();
+        assert_eq!(elements.len(), LEN * point_size);
+
+        let last = &elements[(LEN - 1) * point_size..];
+        let get = |element| -> f32 { bytemuck::cast(last[archetype.offset_of(element).unwrap()]) };
+        assert_eq!(get(Archetype::POSITION), (LEN - 1) as f32);
+        assert_eq!(get(Archetype::ARC_LENGTH), (LEN - 1) as f32);
+        assert_eq!(get(Archetype::PRESSURE), 0.5);
+    }
+    /// Inserting a long stroke mustn't keep the document from the renderer or UI for longer than a frame.
+    #[allow(clippy::cast_precision_loss)]
+    #[test]
+    fn long_stroke_no_hitch() {
+        use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+        const LEN: usize = 100_000;
+        const FRAME: std::time::Duration = std::time::Duration::from_millis(16);
+        let queue = fuzzpaint_core::queue::DocumentCommandQueue::new();
+        let (node, collection) = stroke_layer(&queue);
+        let mut builder = StrokeBuilder::default();
+        for idx in 0..LEN {
+            builder.push(InputPoint {
+                position: [idx as f32, (idx % 7) as f32],
+                time: None,
+                pressure: Some(0.5),
+                tilt: Some([0.1, 0.2]),
+                distance: None,
+                roll: None,
+                wheel: None,
+            });
+        }
+        let stroke = super::FinishedStroke {
+            document: queue.id(),
+            node: node.into(),
+            settings: settings(),
+            clip: None,
+            device: None,
+            builder,
+        };
+
+        // Read the document as a frame would, the whole time the stroke is packed and inserted.
+        let done = std::sync::atomic::AtomicBool::new(false);
+        let (worst, packing) = std::thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                let mut worst = std::time::Duration::ZERO;
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    let start = std::time::Instant::now();
+                    drop(queue.peek_clone_state());
+                    worst = worst.max(start.elapsed());
+                }
+                worst
+            });
+            let start = std::time::Instant::now();
+            let packed = stroke.pack(&queue);
+            let packing = start.elapsed();
+            stroke.insert_into(&queue, packed).unwrap();
+            done.store(true, std::sync::atomic::Ordering::Relaxed);
+            (reader.join().unwrap(), packing)
+        });
+        println!("packed {LEN} points in {packing:?}, reads waited at most {worst:?}");
+        assert!(worst < FRAME, "document locked for {worst:?}");
+
+        let state = queue.peek_clone_state();
+        let strokes = &state.stroke_collections().get(collection).unwrap().strokes;
+        let points: usize = strokes
+            .iter()
+            .map(|stroke| {
+                crate::global::points()
+                    .summary_of(stroke.point_collection)
+                    .unwrap()
+                    .len
+            })
+            .sum();
+        // Segments share the point where they meet.
+        assert_eq!(points - (strokes.len() - 1), LEN);
+    }
+    #[test]
+    fn taper() {
+        use fuzzpaint_core::{state::Taper, stroke::Microseconds};
+        // Straight and steady, a pixel every millisecond, without pressure.
+        let stroke = || {
+            let mut builder = StrokeBuilder::default();
+            for idx in 0..=100u16 {
+                builder.push(InputPoint {
+                    position: [f32::from(idx), 0.0],
+                    time: Some(Microseconds(u32::from(idx) * 1000)),
+                    pressure: None,
+                    tilt: None,
+                    distance: None,
+                    roll: None,
+                    wheel: None,
+                });
+            }
+            builder
+        };
+
+        let mut ends = stroke();
+        ends.taper(Taper {
+            start: 10.0,
+            end: 20.0,
+            velocity: 0.0,
+        });
+        assert_eq!(ends.pressure.len(), ends.len());
+        assert!(ends.pressure[0] < f32::EPSILON);
+        assert!(ends.pressure[100] < f32::EPSILON);
+        assert!(ends.pressure[5] > 0.0 && ends.pressure[5] < 1.0);
+        // Untouched between the tapers.
+        assert!(ends.pressure[10..=80]
+            .iter()
+            .all(|&pressure| (pressure - 1.0).abs() < f32::EPSILON));
+
+        let mut fast = stroke();
+        fast.taper(Taper {
+            velocity: 0.5,
+            ..Default::default()
+        });
+        // A third of the fast speed, so thinned by a third of the half.
+        let expected = 1.0 - 0.5 * 1000.0 / Taper::FAST_SPEED;
+        assert!((fast.pressure[50] - expected).abs() < 0.001);
+    }
+    #[test]
+    fn scripted_strokes() {
+        use crate::stylus_events::{StylusEvent, StylusEventFrame};
+        use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+        // A document of our own, rather than one of the app's.
+        let queue = fuzzpaint_core::queue::DocumentCommandQueue::new();
+        let (node, collection) = stroke_layer(&queue);
+        let selections = crate::AdHocGlobals {
+            document: queue.id(),
+            brush: settings(),
+            secondary_color: fuzzpaint_core::color::ColorOrPalette::BLACK,
+            eraser_tip: super::super::EraserTipMode::default(),
+            node: Some(node.into()),
+        };
+        // Screen and document space are one and the same.
+        let view = super::super::ViewInfo {
+            transform: crate::view_transform::DocumentTransform::Transform(
+                crate::view_transform::ViewTransform::center_on(
+                    cgmath::point2(0.0, 0.0),
+                    cgmath::vec2(0.0, 0.0),
+                    cgmath::Rad(0.0),
+                    1.0,
+                ),
+            ),
+            canvas: fuzzpaint_core::state::document::Canvas::default(),
+            viewport_position: ultraviolet::Vec2::zero(),
+            viewport_size: ultraviolet::Vec2::new(100.0, 100.0),
+        };
+
+        // Drawn over two frames, lifted at the end of the second.
+        let down = |x| StylusEvent {
+            pos: (x, 10.0),
+            pressed: true,
+            pressure: Some(0.5),
+            ..StylusEvent::empty()
+        };
+        let frames = [
+            vec![down(10.0), down(20.0)],
+            vec![
+                down(30.0),
+                StylusEvent {
+                    pos: (30.0, 10.0),
+                    ..StylusEvent::empty()
+                },
+            ],
+        ];
+        let (mut builder, mut transforms, mut eraser_tip, mut device, mut assist) = (
+            StrokeBuilder::default(),
+            None,
+            false,
+            None,
+            super::Assist::default(),
+        );
+        let mut finished = Vec::new();
+        // Once with the brush, then again as if the eraser hotkey were held.
+        for is_eraser in [false, true] {
+            for frame in &frames {
+                let mut render_output = super::super::ToolRenderOutput {
+                    render_as: super::super::RenderAs::None,
+                    set_view: None,
+                    cursor: None,
+                    capture_scroll: false,
+                    gizmo_events: Vec::new(),
+                    sample_color: None,
+                };
+                super::brush(
+                    is_eraser,
+                    Some(selections.clone()),
+                    super::super::OutOfBounds::Retain,
+                    &mut |stroke| finished.push(stroke),
+                    &mut builder,
+                    &mut transforms,
+                    &mut eraser_tip,
+                    &mut device,
+                    &mut assist,
+                    &view,
+                    StylusEventFrame::new(frame.clone()),
+                    &mut render_output,
+                );
+            }
+        }
+        assert!(builder.is_empty());
+        assert_eq!(finished.len(), 2);
+        for stroke in finished {
+            let packed = stroke.pack(&queue);
+            assert!(packed.is_some());
+            stroke.insert_into(&queue, packed).unwrap();
+        }
+
+        let state = queue.peek_clone_state();
+        let strokes = &state.stroke_collections().get(collection).unwrap().strokes;
+        assert_eq!(
+            strokes
+                .iter()
+                .map(|stroke| stroke.brush.is_eraser)
+                .collect::<Vec<_>>(),
+            [false, true]
+        );
+        for stroke in strokes {
+            let summary = crate::global::points()
+                .summary_of(stroke.point_collection)
+                .unwrap();
+            assert_eq!(summary.len, 3);
+            assert!(summary
+                .arc_length
+                .is_some_and(|length| (length - 20.0).abs() < 1e-3));
+        }
+    }
+}
@@ -52,6 +52,8 @@ mod visitors {
         pub viewport_cursor: ultraviolet::Vec2,
         pub path: VisitPath,
         pub xform_stack: Vec<crate::view_transform::ViewTransform>,
+        /// The local transform of the gizmo that was hit, once found.
+        pub hit_xform: Option<crate::view_transform::ViewTransform>,
     }
     impl crate::gizmos::GizmoVisitor<VisitPath> for ClickFindVisitor {
         fn visit_collection(&mut self, gizmo: &Collection) -> ControlFlow<VisitPath> {
@@ -90,6 +92,7 @@ mod visitors {
                 .unwrap();
             // Short circuits the iteration if this returns Some
             if gizmo.hit_shape.hit([xformed_point.x, xformed_point.y]) {
+                self.hit_xform = Some(xform);
                 ControlFlow::Break(std::mem::take(&mut self.path))
             } else {
                 *self.path.indices.last_mut().unwrap() += 1;
@@ -164,10 +167,99 @@ mod visitors {
         }
     }
 }
+/// Farthest the pen may wander, in viewport pixels, for a press and release to count as a click.
+const CLICK_SLOP: f32 = 4.0;
+/// Scale of drags while precision (Ctrl) is held.
+const PRECISION: f32 = 0.1;
+/// Step of rotations while constrained (Shift), in radians.
+const ROTATION_STEP: f32 = std::f32::consts::PI / 12.0;
+
+/// Apply modifiers to a move delta. Precise scales it down, constrain keeps only its larger axis.
+fn constrain_move(delta: ultraviolet::Vec2, precise: bool, constrain: bool) -> ultraviolet::Vec2 {
+    let delta = if precise { delta * PRECISION } else { delta };
+    if !constrain {
+        delta
+    } else if delta.x.abs() >= delta.y.abs() {
+        ultraviolet::Vec2 { x: delta.x, y: 0.0 }
+    } else {
+        ultraviolet::Vec2 { x: 0.0, y: delta.y }
+    }
+}
+/// Apply modifiers to a rotation delta. Precise scales it down, constrain rounds to [`ROTATION_STEP`]s.
+fn constrain_rotation(delta: f32, precise: bool, constrain: bool) -> f32 {
+    let delta = if precise { delta * PRECISION } else { delta };
+    if constrain {
+        (delta / ROTATION_STEP).round() * ROTATION_STEP
+    } else {
+        delta
+    }
+}
+
+/// A gizmo being interacted with, from press to release.
+struct Pressed {
+    id: Option<crate::gizmos::ID>,
+    interaction: crate::gizmos::GizmoInteraction,
+    /// The gizmo's local transform as of the press, to measure turns about its origin.
+    xform: crate::view_transform::ViewTransform,
+    /// Viewport position of the press.
+    from: ultraviolet::Vec2,
+    /// Latest viewport position.
+    to: ultraviolet::Vec2,
+    /// Ctrl was held as of the latest position.
+    precise: bool,
+    /// Shift was held as of the latest position.
+    constrain: bool,
+}
+impl Pressed {
+    /// What the interaction amounted to, if anything. `base_xform` is the document's view transform.
+    /// Modifiers apply here, for every gizmo alike.
+    fn finish(
+        &self,
+        base_xform: &crate::view_transform::ViewTransform,
+    ) -> Option<crate::gizmos::GizmoEvent> {
+        use crate::gizmos::{GizmoEvent, GizmoInteraction};
+        let point = |p: ultraviolet::Vec2| cgmath::Point2 { x: p.x, y: p.y };
+        let dragged = (self.to - self.from).mag() > CLICK_SLOP;
+        let moved = || {
+            let from = base_xform.unproject(point(self.from)).ok()?;
+            let to = base_xform.unproject(point(self.to)).ok()?;
+            let delta = ultraviolet::Vec2 {
+                x: to.x - from.x,
+                y: to.y - from.y,
+            };
+            Some(GizmoEvent::Moved {
+                delta: constrain_move(delta, self.precise, self.constrain),
+            })
+        };
+        match self.interaction {
+            GizmoInteraction::None => None,
+            GizmoInteraction::Open | GizmoInteraction::MoveOpen if !dragged => {
+                Some(GizmoEvent::Opened)
+            }
+            GizmoInteraction::Open => None,
+            GizmoInteraction::Move | GizmoInteraction::MoveOpen => dragged.then(moved).flatten(),
+            GizmoInteraction::Rotate => {
+                if !dragged {
+                    return None;
+                }
+                let from = self.xform.unproject(point(self.from)).ok()?;
+                let to = self.xform.unproject(point(self.to)).ok()?;
+                let delta = to.y.atan2(to.x) - from.y.atan2(from.x);
+                // Shortest way around.
+                let delta = (delta + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                Some(GizmoEvent::Rotated {
+                    delta: constrain_rotation(delta, self.precise, self.constrain),
+                })
+            }
+        }
+    }
+}
+
 pub struct Gizmo {
     shared_collection: Option<std::sync::Arc<tokio::sync::RwLock<crate::gizmos::Collection>>>,
     cursor_latch: Option<crate::gizmos::CursorOrInvisible>,
-    clicked_path: Option<visitors::VisitPath>,
+    pressed: Option<Pressed>,
     was_pressed: bool,
 }
 
@@ -178,7 +270,7 @@ impl super::MakePenTool for Gizmo {
         Ok(Box::new(Gizmo {
             shared_collection: None,
             cursor_latch: None,
-            clicked_path: None,
+            pressed: None,
             was_pressed: false,
         }))
     }
@@ -188,7 +280,7 @@ impl super::PenTool for Gizmo {
     fn exit(&mut self) {
         self.shared_collection = None;
         self.cursor_latch = None;
-        self.clicked_path = None;
+        self.pressed = None;
         self.was_pressed = false;
     }
     async fn process(
@@ -204,13 +296,10 @@ impl super::PenTool for Gizmo {
             GizmoTree, MeshMode, MutGizmoTree, RenderShape, TextureMode, Visual,
         };
         let collection = self.shared_collection.get_or_insert_with(|| {
-            let mut collection = Collection::new(transform::Transform {
-                position: ultraviolet::Vec2 { x: 10.0, y: 10.0 },
-                origin_pinning: transform::OriginPinning::Document,
-                scale_pinning: transform::BasisPinning::Viewport,
-                rotation: 0.0,
-                rotation_pinning: transform::BasisPinning::Viewport,
-            });
+            let mut collection = Collection::new(transform::Transform::handle(ultraviolet::Vec2 {
+                x: 10.0,
+                y: 10.0,
+            }));
             let square = Gizmo {
                 grab_cursor: CursorOrInvisible::Invisible,
                 visual: Visual {
@@ -224,6 +313,7 @@ impl super::PenTool for Gizmo {
                 hit_shape: GizmoShape::None,
                 hover_cursor: CursorOrInvisible::Invisible,
                 interaction: GizmoInteraction::None,
+                id: None,
                 transform: transform::Transform::inherit_all(),
             };
             let square2 = Gizmo {
@@ -239,6 +329,7 @@ impl super::PenTool for Gizmo {
                 hit_shape: GizmoShape::None,
                 hover_cursor: CursorOrInvisible::Invisible,
                 interaction: GizmoInteraction::None,
+                id: None,
                 transform: transform::Transform {
                     origin_pinning: transform::OriginPinning::Inherit,
                     rotation_pinning: transform::BasisPinning::Document,
@@ -261,6 +352,7 @@ impl super::PenTool for Gizmo {
                 },
                 hover_cursor: CursorOrInvisible::Icon(CursorIcon::Help),
                 interaction: GizmoInteraction::Move,
+                id: Some(crate::gizmos::ID::default()),
                 transform: transform::Transform {
                     scale_pinning: transform::BasisPinning::Document,
                     ..transform::Transform::inherit_all()
@@ -280,49 +372,66 @@ impl super::PenTool for Gizmo {
                 continue;
             };
 
+            let point = ultraviolet::Vec2 {
+                x: event.pos.0,
+                y: event.pos.1,
+            };
             if event.pressed {
                 // A new press!
                 if !self.was_pressed {
                     // Perform hit test.
-                    let point = ultraviolet::Vec2 {
-                        x: event.pos.0,
-                        y: event.pos.1,
-                    };
-
                     let mut visitor = visitors::ClickFindVisitor {
                         path: visitors::VisitPath::default(),
                         viewport_cursor: point,
                         xform_stack: vec![base_xform],
+                        hit_xform: None,
                     };
 
                     // Found?
+                    self.pressed = None;
                     if let std::ops::ControlFlow::Break(path) = collection.visit_hit(&mut visitor) {
-                        self.clicked_path = Some(path);
-                    } else {
-                        self.clicked_path = None;
+                        let mut mutator_visitor = visitors::MutatorVisitor {
+                            current_path: visitors::VisitPath::default(),
+                            dest_path: &path,
+                            exec: Some(|g: &mut crate::gizmos::Gizmo| {
+                                (g.grab_cursor, g.id, g.interaction)
+                            }),
+                        };
+                        if let (
+                            std::ops::ControlFlow::Break(Some((cursor, id, interaction))),
+                            Some(xform),
+                        ) = (
+                            collection.visit_hit_mut(&mut mutator_visitor),
+                            visitor.hit_xform,
+                        ) {
+                            self.cursor_latch = Some(cursor);
+                            self.pressed = Some(Pressed {
+                                id,
+                                interaction,
+                                xform,
+                                from: point,
+                                to: point,
+                                precise: event.ctrl,
+                                constrain: event.shift,
+                            });
+                        }
                     }
                 }
-
-                if let Some(path) = self.clicked_path.as_ref() {
-                    let mut mutator_visitor = visitors::MutatorVisitor {
-                        current_path: visitors::VisitPath::default(),
-                        dest_path: path,
-                        exec: Some(|g: &mut crate::gizmos::Gizmo| {
-                            self.cursor_latch = Some(g.grab_cursor);
-                        }),
-                    };
-                    collection.visit_hit_mut(&mut mutator_visitor);
+                if let Some(pressed) = self.pressed.as_mut() {
+                    pressed.to = point;
+                    pressed.precise = event.ctrl;
+                    pressed.constrain = event.shift;
                 }
             } else {
-                // Reset the click status, if any.
-                self.clicked_path = None;
+                // Released, report what the interaction amounted to.
+                if let Some(pressed) = self.pressed.take() {
+                    if let Some((id, event)) = pressed.id.zip(pressed.finish(&base_xform)) {
+                        render_output.gizmo_events.push((id, event));
+                    }
+                }
 
                 // Not pressed. Search for hover cursor.
                 // (might run multiple times per frame, wasteful!)
-                let point = ultraviolet::Vec2 {
-                    x: event.pos.0,
-                    y: event.pos.1,
-                };
                 let mut visitor = visitors::CursorFindVisitor {
                     viewport_cursor: point,
                     xform_stack: vec![base_xform],
@@ -339,3 +448,23 @@ impl super::PenTool for Gizmo {
         render_output.cursor = self.cursor_latch;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{constrain_move, constrain_rotation, ROTATION_STEP};
+    #[test]
+    fn modifiers() {
+        let delta = ultraviolet::Vec2 { x: 10.0, y: -20.0 };
+        assert_eq!(constrain_move(delta, false, false), delta);
+        assert_eq!(
+            constrain_move(delta, false, true),
+            ultraviolet::Vec2 { x: 0.0, y: -20.0 }
+        );
+        assert!((constrain_move(delta, true, false) - delta * 0.1).mag() < 1e-6);
+
+        assert!((constrain_rotation(0.3, false, false) - 0.3).abs() < 1e-6);
+        // Nearest 15 degrees.
+        assert!((constrain_rotation(0.3, false, true) - ROTATION_STEP).abs() < 1e-6);
+        assert!(constrain_rotation(0.3, true, true).abs() < 1e-6);
+    }
+}
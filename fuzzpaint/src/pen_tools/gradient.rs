@@ -0,0 +1,283 @@
+//! Placing the endpoints of the selected [gradient leaf](fuzzpaint_core::state::graph::LeafType::Gradient).
+//! Dragging a handle moves that endpoint, dragging anywhere else lays the gradient out along the drag. Stops
+//! are marked along the line, and edited in the layer's properties.
+//!
+//! A drag is previewed by the handles alone, and written to the document once released so that it's a
+//! single step of history. The nudge actions, or scrolling over a handle, move the hovered or last dragged
+//! endpoint by single pixels - or the whole gradient, if neither.
+
+use fuzzpaint_core::{
+    gradient::Gradient as Geometry,
+    state::graph::{LeafID, LeafType},
+};
+
+/// Radius of the endpoint handles, in viewport pixels.
+const HANDLE_RADIUS: f32 = 8.0;
+/// Radius of the marks where stops lie, in viewport pixels.
+const STOP_RADIUS: f32 = 4.0;
+
+/// A circle of the given viewport-pixel radius, centered on a document point.
+fn dot_gizmo(center: [f32; 2], radius: f32, color: [u8; 4]) -> crate::gizmos::Gizmo {
+    use crate::gizmos::{transform, Gizmo, MeshMode, RenderShape, TextureMode, Visual};
+    Gizmo {
+        visual: Visual {
+            mesh: MeshMode::Shape(RenderShape::Ellipse {
+                origin: ultraviolet::Vec2 { x: 0.0, y: 0.0 },
+                radii: ultraviolet::Vec2 {
+                    x: radius,
+                    y: radius,
+                },
+                rotation: 0.0,
+            }),
+            texture: TextureMode::Solid(color),
+        },
+        transform: transform::Transform::handle(ultraviolet::Vec2 {
+            x: center[0],
+            y: center[1],
+        }),
+        ..Default::default()
+    }
+}
+/// The line between the endpoints, a mark at each stop, and the handles.
+pub fn gizmos(gradient: &Geometry) -> smallvec::SmallVec<[crate::gizmos::Gizmo; 1]> {
+    let [start, end] = [gradient.start, gradient.end];
+    let along = |t: f32| {
+        [
+            (end[0] - start[0]).mul_add(t, start[0]),
+            (end[1] - start[1]).mul_add(t, start[1]),
+        ]
+    };
+    let vertex = |pos| crate::gizmos::renderer::WideLineVertex {
+        pos,
+        color: [255; 4],
+        tex_coord: 0.0,
+        width: 1.0,
+    };
+    // Plus two for lines adjacency, continuing straight on.
+    let points = [along(-1.0), start, end, along(2.0)].map(vertex);
+    let mut gizmos = smallvec::SmallVec::new();
+    gizmos.push(crate::gizmos::Gizmo {
+        visual: crate::gizmos::Visual {
+            mesh: crate::gizmos::MeshMode::WideLineStrip(points.into()),
+            texture: crate::gizmos::TextureMode::Solid([64, 160, 255, 200]),
+        },
+        transform: crate::gizmos::transform::Transform::outline(),
+        ..Default::default()
+    });
+    gizmos.extend(
+        gradient
+            .stops
+            .iter()
+            .map(|stop| dot_gizmo(along(stop.position), STOP_RADIUS, [255, 255, 255, 200])),
+    );
+    gizmos.push(dot_gizmo(start, HANDLE_RADIUS, [64, 160, 255, 200]));
+    gizmos.push(dot_gizmo(end, HANDLE_RADIUS, [64, 160, 255, 200]));
+    gizmos
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Handle {
+    Start,
+    End,
+}
+#[derive(Copy, Clone)]
+enum Grab {
+    /// Moving the start handle, which is this far from the pen.
+    Start { offset: [f32; 2] },
+    /// Moving the end handle, which is this far from the pen.
+    End { offset: [f32; 2] },
+    /// Laying out anew, from here to the pen.
+    Drag { anchor: [f32; 2] },
+}
+/// A drag in progress, to be written once released.
+struct Editing {
+    document: fuzzpaint_core::state::document::ID,
+    leaf: LeafID,
+    grab: Grab,
+    gradient: Geometry,
+}
+/// Replace the leaf's gradient, keeping the rest of it.
+fn write(document: fuzzpaint_core::state::document::ID, leaf: LeafID, gradient: Geometry) {
+    let result = crate::global::provider().write(document, "gradient", |queue| {
+        queue.write_with(|writer| {
+            let mut graph = writer.graph();
+            let Some(LeafType::Gradient { blend, .. }) =
+                graph.get(leaf).and_then(|node| node.leaf())
+            else {
+                anyhow::bail!("selected layer is no longer a gradient")
+            };
+            let blend = *blend;
+            graph.set_leaf(leaf, LeafType::Gradient { blend, gradient })?;
+            anyhow::Ok(())
+        })
+    });
+    match result {
+        Some(Err(e)) => tracing::warn!("failed to place gradient: {e:#}"),
+        None => tracing::warn!("failed to place gradient: document closed"),
+        Some(Ok(())) => (),
+    }
+}
+
+pub struct Gradient {
+    editing: Option<Editing>,
+    /// The handle under the pen as of the last event.
+    hovered: Option<Handle>,
+    /// The endpoint last dragged, and of which leaf.
+    focused: Option<(LeafID, Handle)>,
+}
+impl super::MakePenTool for Gradient {
+    fn new_from_renderer(
+        _: &std::sync::Arc<crate::render_device::RenderContext>,
+    ) -> anyhow::Result<Box<dyn super::PenTool>> {
+        Ok(Box::new(Gradient {
+            editing: None,
+            hovered: None,
+            focused: None,
+        }))
+    }
+}
+#[async_trait::async_trait]
+impl super::PenTool for Gradient {
+    fn exit(&mut self) {
+        // Abandon the drag.
+        self.editing = None;
+        self.hovered = None;
+    }
+    async fn process(
+        &mut self,
+        view_info: &super::ViewInfo,
+        stylus_input: crate::stylus_events::StylusEventFrame,
+        actions: &crate::actions::ActionFrame,
+        _tool_output: &mut super::ToolStateOutput,
+        render_output: &mut super::ToolRenderOutput,
+    ) {
+        let Some(globals) = crate::AdHocGlobals::read_clone() else {
+            self.editing = None;
+            return;
+        };
+        let Some(transform) = view_info.calculate_transform() else {
+            return;
+        };
+        let leaf = globals.node.and_then(|node| LeafID::try_from(node).ok());
+        // The gradient as it is in the document, if one is selected.
+        let current = leaf.and_then(|leaf| {
+            crate::global::provider()
+                .inspect(globals.document, |queue| {
+                    use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+                    match queue.peek_clone_state().graph().get(leaf)?.leaf()? {
+                        LeafType::Gradient { gradient, .. } => Some(gradient.clone()),
+                        _ => None,
+                    }
+                })
+                .flatten()
+        });
+        let Some((leaf, current)) = leaf.zip(current) else {
+            // Nothing to edit.
+            self.editing = None;
+            self.hovered = None;
+            render_output.cursor = Some(crate::gizmos::CursorOrInvisible::Icon(
+                winit::window::CursorIcon::NotAllowed,
+            ));
+            return;
+        };
+        if self
+            .editing
+            .as_ref()
+            .is_some_and(|editing| editing.document != globals.document || editing.leaf != leaf)
+        {
+            // Selection changed mid-drag.
+            self.editing = None;
+        }
+
+        for event in stylus_input.iter() {
+            let Ok(pos) = transform.unproject(cgmath::Point2 {
+                x: event.pos.0,
+                y: event.pos.1,
+            }) else {
+                return;
+            };
+            let pos = [pos.x, pos.y];
+            let shown = self.editing.as_ref().map_or(&current, |e| &e.gradient);
+            let on_handle = |[x, y]: [f32; 2]| {
+                let handle = transform.project(cgmath::Point2 { x, y });
+                (handle.x - event.pos.0).hypot(handle.y - event.pos.1) <= HANDLE_RADIUS
+            };
+            let offset = |[x, y]: [f32; 2]| [x - pos[0], y - pos[1]];
+            // End takes precedence, so a gradient dragged out to nothing can be pulled apart again.
+            let hovered = if on_handle(shown.end) {
+                Some(Grab::End {
+                    offset: offset(shown.end),
+                })
+            } else if on_handle(shown.start) {
+                Some(Grab::Start {
+                    offset: offset(shown.start),
+                })
+            } else {
+                None
+            };
+            self.hovered = match hovered {
+                Some(Grab::Start { .. }) => Some(Handle::Start),
+                Some(Grab::End { .. }) => Some(Handle::End),
+                _ => None,
+            };
+            if !event.pressed {
+                if let Some(editing) = self.editing.take() {
+                    write(editing.document, editing.leaf, editing.gradient);
+                }
+                continue;
+            }
+            if self.editing.is_none() {
+                self.focused = self.hovered.map(|handle| (leaf, handle));
+            }
+
+            let editing = self.editing.get_or_insert_with(|| Editing {
+                document: globals.document,
+                leaf,
+                grab: hovered.unwrap_or(Grab::Drag { anchor: pos }),
+                gradient: current.clone(),
+            });
+            match editing.grab {
+                Grab::Start { offset } => {
+                    editing.gradient.start = [pos[0] + offset[0], pos[1] + offset[1]];
+                }
+                Grab::End { offset } => {
+                    editing.gradient.end = [pos[0] + offset[0], pos[1] + offset[1]];
+                }
+                Grab::Drag { anchor } => {
+                    editing.gradient.start = anchor;
+                    editing.gradient.end = pos;
+                }
+            }
+        }
+
+        // Not while dragging, the drag would override it anyway.
+        if let Some(offset) = super::nudge_offset(actions).filter(|_| self.editing.is_none()) {
+            let focused = self
+                .focused
+                .and_then(|(focused_leaf, handle)| (focused_leaf == leaf).then_some(handle));
+            let mut nudged = current.clone();
+            let nudge =
+                |point: &mut [f32; 2]| *point = [point[0] + offset[0], point[1] + offset[1]];
+            match self.hovered.or(focused) {
+                Some(Handle::Start) => nudge(&mut nudged.start),
+                Some(Handle::End) => nudge(&mut nudged.end),
+                None => {
+                    nudge(&mut nudged.start);
+                    nudge(&mut nudged.end);
+                }
+            }
+            write(globals.document, leaf, nudged);
+        }
+        render_output.capture_scroll = self.hovered.is_some();
+
+        let shown = self.editing.as_ref().map_or(&current, |e| &e.gradient);
+        render_output.render_as = super::RenderAs::InlineGizmos(gizmos(shown));
+        render_output.cursor = Some(crate::gizmos::CursorOrInvisible::Icon(
+            match self.editing.as_ref().map(|editing| editing.grab) {
+                Some(Grab::Start { .. } | Grab::End { .. }) => winit::window::CursorIcon::Grabbing,
+                _ if self.hovered.is_some() => winit::window::CursorIcon::Grab,
+                _ => winit::window::CursorIcon::Crosshair,
+            },
+        ));
+    }
+}
@@ -85,42 +85,53 @@ impl TolerantCurve {
     }
 }
 
+/// Marching ants around a closed loop of document-space points.
+pub fn outline_gizmo(loop_points: impl IntoIterator<Item = [f32; 2]>) -> crate::gizmos::Gizmo {
+    // push dummy to start at idx 1
+    let mut points = vec![bytemuck::Zeroable::zeroed()];
+    points.extend(
+        loop_points
+            .into_iter()
+            .map(|pos| crate::gizmos::renderer::WideLineVertex {
+                pos,
+                color: [255; 4],
+                tex_coord: 0.0,
+                width: 2.0,
+            }),
+    );
+    if points.len() < 4 {
+        // No render
+        return crate::gizmos::Gizmo::default();
+    }
+    // Close the loop, if it isn't already.
+    if points.last().map(|p| p.pos) != Some(points[1].pos) {
+        points.push(points[1]);
+    }
+
+    // plus two due to lines adjacency!
+    // No panics. Guarded by len check above.
+    points[0] = points[points.len() - 2];
+    points.push(points[2]);
+
+    let mesh = crate::gizmos::MeshMode::WideLineStrip(points.into());
+
+    crate::gizmos::Gizmo {
+        visual: crate::gizmos::Visual {
+            mesh,
+            texture: crate::gizmos::TextureMode::AntTrail,
+        },
+        transform: crate::gizmos::transform::Transform::outline(),
+        ..Default::default()
+    }
+}
+
 fn make_trail(curve: &TolerantCurve) -> crate::gizmos::Gizmo {
     if curve.len() < 3 {
         // No render
         crate::gizmos::Gizmo::default()
     } else {
         // todo: horribly inefficient lol.
-        let curve = curve.clone().into_closed_vec();
-        // plus two due to lines adjacency!
-        let mut points = Vec::with_capacity(curve.len() + 2);
-        // push dummy to start at idx 1
-        points.push(bytemuck::Zeroable::zeroed());
-        points.extend(
-            curve
-                .into_iter()
-                .map(|point| crate::gizmos::renderer::WideLineVertex {
-                    pos: point.into(),
-                    color: [255; 4],
-                    tex_coord: 0.0,
-                    width: 2.0,
-                }),
-        );
-
-        // No panics. Guarded by curve.len() >= 3
-        points[0] = *points.last().unwrap();
-        points.push(points[1]);
-
-        let mesh = crate::gizmos::MeshMode::WideLineStrip(points.into());
-
-        crate::gizmos::Gizmo {
-            visual: crate::gizmos::Visual {
-                mesh,
-                texture: crate::gizmos::TextureMode::AntTrail,
-            },
-            transform: crate::gizmos::transform::Transform::inherit_all(),
-            ..Default::default()
-        }
+        outline_gizmo(curve.clone().into_closed_vec().into_iter().map(Into::into))
     }
 }
 
@@ -168,7 +179,19 @@ impl super::PenTool for Lasso {
                     self.in_progress_hoop.as_mut()
                 }
                 (true, true) => self.in_progress_hoop.as_mut(),
-                (_, false) => None,
+                (true, false) => {
+                    // Released, the hoop becomes the selection.
+                    // Too few points (e.g. just a click) deselects.
+                    if let (Some(hoop), Some(globals)) = (
+                        self.in_progress_hoop.take(),
+                        crate::AdHocGlobals::read_clone(),
+                    ) {
+                        let polygon = hoop.into_unclosed_vec().into_iter().map(Into::into);
+                        crate::global::selection::select(globals.document, polygon.collect());
+                    }
+                    None
+                }
+                (false, false) => None,
             };
             self.is_down = input.pressed;
 
@@ -0,0 +1,104 @@
+//! A magnifier following the pen while it hovers close above the tablet, for pens that report their
+//! distance. Drawn over whatever tool is active.
+
+/// Radius of the loupe, in viewport pixels.
+const RADIUS: f32 = 64.0;
+/// How many times the document is magnified.
+const ZOOM: f32 = 3.0;
+/// Farthest the pen may hover for the loupe to show, of the `[0, 1]` range the pen reports.
+const HOVER_DISTANCE: f32 = 0.5;
+/// Points around the outline.
+const OUTLINE_RES: u16 = 48;
+
+static ENABLED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Whether to show the loupe while hovering.
+#[must_use]
+pub fn enabled() -> bool {
+    ENABLED.load(std::sync::atomic::Ordering::Relaxed)
+}
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Whether the event is of a pen hovering close enough to show the loupe. Pens that don't report their
+/// distance never are.
+#[must_use]
+pub fn is_hovering(event: &crate::stylus_events::StylusEvent) -> bool {
+    !event.pressed && event.dist.is_some_and(|dist| dist <= HOVER_DISTANCE)
+}
+
+/// The loupe and its outline, centered on a document-space point.
+#[must_use]
+pub fn gizmos(center: [f32; 2]) -> [crate::gizmos::Gizmo; 2] {
+    use crate::gizmos::{transform, Gizmo, MeshMode, RenderShape, TextureMode, Visual};
+    let transform = || {
+        transform::Transform::handle(ultraviolet::Vec2 {
+            x: center[0],
+            y: center[1],
+        })
+    };
+    let around = |idx: u16| {
+        let angle = std::f32::consts::TAU * f32::from(idx) / f32::from(OUTLINE_RES);
+        let (sin, cos) = angle.sin_cos();
+        crate::gizmos::renderer::WideLineVertex {
+            pos: [cos * RADIUS, sin * RADIUS],
+            color: [255; 4],
+            tex_coord: 0.0,
+            width: 2.0,
+        }
+    };
+    // Closed loop, plus one either end for lines adjacency.
+    let outline: Vec<_> = std::iter::once(around(OUTLINE_RES - 1))
+        .chain((0..=OUTLINE_RES).map(around))
+        .chain(std::iter::once(around(1)))
+        .collect();
+    [
+        Gizmo {
+            visual: Visual {
+                mesh: MeshMode::Shape(RenderShape::Ellipse {
+                    origin: ultraviolet::Vec2 { x: 0.0, y: 0.0 },
+                    radii: ultraviolet::Vec2 {
+                        x: RADIUS,
+                        y: RADIUS,
+                    },
+                    rotation: 0.0,
+                }),
+                texture: TextureMode::Document { zoom: ZOOM },
+            },
+            transform: transform(),
+            ..Default::default()
+        },
+        Gizmo {
+            visual: Visual {
+                mesh: MeshMode::WideLineStrip(outline.into()),
+                texture: TextureMode::Solid([0, 0, 0, 200]),
+            },
+            transform: transform(),
+            ..Default::default()
+        },
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stylus_events::StylusEvent;
+    #[test]
+    fn hovering() {
+        let near = StylusEvent {
+            dist: Some(0.1),
+            ..StylusEvent::empty()
+        };
+        assert!(super::is_hovering(&near));
+        // Touching, too far, or unknown.
+        assert!(!super::is_hovering(&StylusEvent {
+            pressed: true,
+            ..near
+        }));
+        assert!(!super::is_hovering(&StylusEvent {
+            dist: Some(0.9),
+            ..near
+        }));
+        assert!(!super::is_hovering(&StylusEvent::empty()));
+    }
+}
@@ -17,8 +17,11 @@
 mod brush;
 mod dummy;
 mod gizmo;
+mod gradient;
 mod lasso;
-mod picker;
+pub mod loupe;
+pub mod picker;
+mod ruler;
 mod viewport;
 use crate::view_transform::ViewInfo;
 trait MakePenTool {
@@ -71,6 +74,8 @@ impl ToolStateOutput {
             Transition::ToLayer(StateLayer::ViewportScrub)
         } else if actions.is_action_held(Action::Gizmo) {
             Transition::ToLayer(StateLayer::Gizmos)
+        } else if actions.is_action_held(Action::PickerHold) {
+            Transition::ToLayer(StateLayer::Picker)
         } else {
             Transition::ToBase
         }
@@ -84,6 +89,35 @@ pub struct ToolRenderOutput {
     pub set_view: Option<crate::view_transform::DocumentTransform>,
     /// Set the cursor icon to this if Some, or default if None.
     pub cursor: Option<crate::gizmos::CursorOrInvisible>,
+    /// The pen is over a handle that the scroll wheel should nudge rather than zoom the view, see
+    /// [`nudge_offset`].
+    pub capture_scroll: bool,
+    /// Finished interactions with gizmos, delivered to [`crate::gizmos::listen`]ers.
+    pub gizmo_events: Vec<(crate::gizmos::ID, crate::gizmos::GizmoEvent)>,
+    /// Read the composited color at this document-space point, see
+    /// [`crate::renderer::requests::RenderRequest::SampleColor`]. The sender is dropped if the renderer is busy.
+    pub sample_color: Option<SampleColor>,
+}
+pub type SampleColor = (
+    [f32; 2],
+    tokio::sync::oneshot::Sender<anyhow::Result<[f32; 4]>>,
+);
+/// Whether the last processed tool asked for the scroll wheel, see [`ToolRenderOutput::capture_scroll`].
+static SCROLL_CAPTURED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+/// Whether scrolling should nudge the active tool's handle instead of zooming.
+pub fn scroll_captured() -> bool {
+    SCROLL_CAPTURED.load(std::sync::atomic::Ordering::Relaxed)
+}
+/// Net movement from the nudge actions this frame, one document pixel per trigger. `None` if there was none.
+#[allow(clippy::cast_precision_loss)]
+fn nudge_offset(actions: &crate::actions::ActionFrame) -> Option<[f32; 2]> {
+    use crate::actions::Action;
+    let count = |action| actions.action_trigger_count(action) as f32;
+    let offset = [
+        count(Action::NudgeRight) - count(Action::NudgeLeft),
+        count(Action::NudgeDown) - count(Action::NudgeUp),
+    ];
+    (offset != [0.0; 2]).then_some(offset)
 }
 
 pub enum RenderAs {
@@ -96,17 +130,165 @@ pub enum RenderAs {
     /// Nothing to render.
     None,
 }
-#[derive(Copy, Clone, strum::EnumIter, Hash, PartialEq, Eq, Debug)]
+/// How the brush tool treats strokes made with the eraser end of a stylus.
+#[derive(Copy, Clone, Default, strum::EnumIter, strum::AsRefStr, PartialEq, Eq, Debug)]
+pub enum EraserTipMode {
+    /// Erase, as if the eraser tool were selected.
+    #[default]
+    Erase,
+    /// Draw with the secondary color.
+    Secondary,
+    /// No special treatment, draw as the nib would.
+    Ignore,
+}
+/// Lines along the edges of the tiles the document is updated in, over the region covering the canvas, see
+/// [`crate::diagnostics::Visualizations::TILE_BOUNDARIES`].
+fn tile_gizmos(
+    canvas: fuzzpaint_core::state::document::Canvas,
+) -> impl Iterator<Item = crate::gizmos::Gizmo> {
+    use crate::document_viewport_proxy::{DirtyTiles, DocumentRegion};
+    let region = DocumentRegion::covering(canvas);
+    // Exact, small.
+    #[allow(clippy::cast_precision_loss)]
+    let (tile, dimension) = (DirtyTiles::TILE_SIZE as f32 / region.scale(), region.size);
+    let [x, y] = [region.origin.x, region.origin.y];
+    let line = |from: [f32; 2], to: [f32; 2]| {
+        let vertex = |pos| crate::gizmos::renderer::WideLineVertex {
+            pos,
+            color: [255; 4],
+            tex_coord: 0.0,
+            width: 1.0,
+        };
+        // Plus two for lines adjacency, continuing straight on.
+        let beyond = |from: [f32; 2], to: [f32; 2]| {
+            [
+                2.0f32.mul_add(to[0], -from[0]),
+                2.0f32.mul_add(to[1], -from[1]),
+            ]
+        };
+        let points = [
+            vertex(beyond(to, from)),
+            vertex(from),
+            vertex(to),
+            vertex(beyond(from, to)),
+        ];
+        crate::gizmos::Gizmo {
+            visual: crate::gizmos::Visual {
+                mesh: crate::gizmos::MeshMode::WideLineStrip(points.into()),
+                texture: crate::gizmos::TextureMode::Solid([0, 255, 128, 160]),
+            },
+            transform: crate::gizmos::transform::Transform::outline(),
+            ..Default::default()
+        }
+    };
+    // Both edges of the document, and every edge between.
+    (0..=DirtyTiles::tiles_per_side()).flat_map(move |idx| {
+        // Exact, small.
+        #[allow(clippy::cast_precision_loss)]
+        let offset = (idx as f32 * tile).min(dimension);
+        [
+            line([x + offset, y], [x + offset, y + dimension]),
+            line([x, y + offset], [x + dimension, y + offset]),
+        ]
+    })
+}
+
+/// What becomes of stroke points drawn off the edge of the canvas.
+#[derive(
+    Copy,
+    Clone,
+    Default,
+    strum::EnumIter,
+    PartialEq,
+    Eq,
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum OutOfBounds {
+    /// Kept where they were drawn, so the stroke runs off the edge and is cut off there.
+    #[default]
+    Retain,
+    /// Pinned to the nearest edge, so the stroke runs along it.
+    Clamp,
+}
+impl OutOfBounds {
+    /// Apply to a point in document space, drawn on the given canvas.
+    #[must_use]
+    pub fn apply(
+        self,
+        [x, y]: [f32; 2],
+        canvas: fuzzpaint_core::state::document::Canvas,
+    ) -> [f32; 2] {
+        match self {
+            Self::Retain => [x, y],
+            Self::Clamp => {
+                let [min, max] = canvas.corners();
+                [x.clamp(min[0], max[0]), y.clamp(min[1], max[1])]
+            }
+        }
+    }
+}
+#[derive(
+    Copy,
+    Clone,
+    strum::EnumIter,
+    Hash,
+    PartialEq,
+    Eq,
+    Debug,
+    Default,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum StateLayer {
     Picker,
+    #[default]
     Brush,
     Eraser,
     Gizmos,
+    Gradient,
     Lasso,
+    Ruler,
     ViewportPan,
     ViewportScrub,
     ViewportRotate,
 }
+impl StateLayer {
+    /// Actions that may be held to change what this tool does, for showing as hints.
+    /// Includes the layers of [`ToolStateOutput::do_default`].
+    #[must_use]
+    pub fn modifiers(self) -> &'static [crate::actions::Action] {
+        use crate::actions::Action;
+        const DEFAULT: &[Action] = &[
+            Action::ViewportPan,
+            Action::ViewportRotate,
+            Action::ViewportScrub,
+            Action::Gizmo,
+            Action::PickerHold,
+        ];
+        match self {
+            Self::Brush => &[
+                Action::Erase,
+                Action::ViewportPan,
+                Action::ViewportRotate,
+                Action::ViewportScrub,
+                Action::Gizmo,
+                Action::PickerHold,
+            ],
+            // Already there.
+            Self::ViewportPan | Self::ViewportRotate | Self::ViewportScrub => &[],
+            Self::Picker
+            | Self::Eraser
+            | Self::Gizmos
+            | Self::Gradient
+            | Self::Lasso
+            | Self::Ruler => DEFAULT,
+        }
+    }
+}
 #[derive(Clone, Copy)]
 enum Transition {
     /// Layer this state on top the base. Note that states may not modify what
@@ -124,8 +306,16 @@ fn apply_transform_request(
 
     // all the others require more work, this one is easy.
     if matches!(view_request, DocumentViewRequest::Fit) {
-        // Todo: inherit the rotation, flip state.
-        *transform = DocumentTransform::Fit(DocumentFit::default());
+        // Keep the mirroring, it's a view setting rather than a position.
+        // Todo: inherit the rotation state.
+        let flip_x = match transform {
+            DocumentTransform::Fit(fit) => fit.flip_x,
+            DocumentTransform::Transform(xform) => xform.is_flipped(),
+        };
+        *transform = DocumentTransform::Fit(DocumentFit {
+            flip_x,
+            ..DocumentFit::default()
+        });
         return;
     }
 
@@ -164,6 +354,8 @@ fn apply_transform_request(
             let delta = angle - cur_angle;
             xform.rotate_about(view_center, cgmath::Rad(delta));
         }
+        DocumentViewRequest::FlipHorizontal => xform.flip_x_about(view_center),
+        DocumentViewRequest::FlipVertical => xform.flip_y_about(view_center),
     }
     *transform = cur_view.transform;
 }
@@ -180,14 +372,21 @@ pub struct ToolState {
     document_scrub: Box<dyn PenTool>,
     document_rotate: Box<dyn PenTool>,
     gizmos: Box<dyn PenTool>,
+    gradient: Box<dyn PenTool>,
     lasso: Box<dyn PenTool>,
+    ruler: Box<dyn PenTool>,
+
+    /// Document-space position of the last stylus event, where [`crate::actions::Action::Paste`] pastes.
+    cursor: Option<[f32; 2]>,
+    /// Whether the last stylus event was close enough to show the [`loupe`].
+    hovering: bool,
 }
 impl ToolState {
     pub fn new_from_renderer(
         context: &std::sync::Arc<crate::render_device::RenderContext>,
     ) -> anyhow::Result<Self> {
         Ok(Self {
-            base: StateLayer::Brush,
+            base: crate::global::session::Session::startup_tool(),
             layer: None,
             brush: brush::Brush::new_from_renderer(context)?,
             eraser: brush::Eraser::new_from_renderer(context)?,
@@ -196,7 +395,11 @@ impl ToolState {
             document_scrub: viewport::Scrub::new_from_renderer(context)?,
             document_rotate: viewport::Rotate::new_from_renderer(context)?,
             gizmos: gizmo::Gizmo::new_from_renderer(context)?,
+            gradient: gradient::Gradient::new_from_renderer(context)?,
             lasso: lasso::Lasso::new_from_renderer(context)?,
+            ruler: ruler::Ruler::new_from_renderer(context)?,
+            cursor: None,
+            hovering: false,
         })
     }
     /// Allow the tool to process the given stylus data and actions, optionally returning preview render commands,
@@ -206,7 +409,7 @@ impl ToolState {
         view_info: &ViewInfo,
         stylus_input: crate::stylus_events::StylusEventFrame,
         actions: &crate::actions::ActionFrame,
-        ui_requests: &crossbeam::channel::Receiver<crate::ui::requests::UiRequest>,
+        ui_requests: Vec<crate::ui::requests::UiRequest>,
     ) -> ToolRenderOutput {
         use crate::ui::requests::{DocumentRequest, UiRequest};
         // Prepare output structs
@@ -215,10 +418,13 @@ impl ToolState {
             render_as: RenderAs::None,
             set_view: None,
             cursor: None,
+            capture_scroll: false,
+            gizmo_events: Vec::new(),
+            sample_color: None,
         };
 
         // Handle ui requests
-        for request in ui_requests.try_iter() {
+        for request in ui_requests {
             match request {
                 UiRequest::Document {
                     request: DocumentRequest::View(view_request),
@@ -232,6 +438,22 @@ impl ToolState {
             }
         }
 
+        if let Some(event) = stylus_input.iter().last() {
+            self.cursor = view_info
+                .calculate_transform()
+                .and_then(|transform| {
+                    transform
+                        .unproject(cgmath::Point2 {
+                            x: event.pos.0,
+                            y: event.pos.1,
+                        })
+                        .ok()
+                })
+                .map(|pos| [pos.x, pos.y]);
+            self.hovering = loupe::is_hovering(event);
+        }
+        self.clipboard_actions(view_info, actions);
+
         // Get current tool and run
         let cur_state = self.get_current_state();
         let tool = self.tool_for_state(cur_state);
@@ -244,6 +466,45 @@ impl ToolState {
             &mut render_output,
         )
         .await;
+        SCROLL_CAPTURED.store(
+            render_output.capture_scroll,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+
+        // Show the selection's marching ants and the ruler's guides beneath whatever the tool renders, and
+        // the loupe above.
+        let document = crate::AdHocGlobals::read_clone().map(|globals| globals.document);
+        let selection = document
+            .and_then(crate::global::selection::get)
+            .filter(|_| crate::global::selection::show_outline());
+        // Shown while in use, or while being edited.
+        let rulers = document
+            .and_then(crate::global::rulers::get)
+            .filter(|rulers| rulers.enabled || cur_state == StateLayer::Ruler);
+        let loupe = self.cursor.filter(|_| self.hovering && loupe::enabled());
+        let tiles = crate::diagnostics::visualizations()
+            .contains(crate::diagnostics::Visualizations::TILE_BOUNDARIES);
+        if selection.is_some() || rulers.is_some() || loupe.is_some() || tiles {
+            if matches!(render_output.render_as, RenderAs::None) {
+                render_output.render_as = RenderAs::InlineGizmos(smallvec::SmallVec::new());
+            }
+            // Shared collections belong to the tool, leave those alone.
+            if let RenderAs::InlineGizmos(gizmos) = &mut render_output.render_as {
+                if let Some(selection) = selection {
+                    gizmos.insert(0, lasso::outline_gizmo(selection.polygon.iter().copied()));
+                }
+                if let Some(rulers) = rulers {
+                    gizmos.insert_many(0, ruler::gizmos(&rulers.ruler));
+                }
+                if tiles {
+                    gizmos.insert_many(0, tile_gizmos(view_info.canvas));
+                }
+                // Over everything else.
+                if let Some(center) = loupe {
+                    gizmos.extend(loupe::gizmos(center));
+                }
+            }
+        }
 
         // Apply output structs
         let transition = tool_output
@@ -259,6 +520,62 @@ impl ToolState {
         // return the output, let the caller handle it.
         render_output
     }
+    /// Copy and paste on the active document, pasting at the cursor or the middle of the view if the
+    /// cursor hasn't been seen yet.
+    fn clipboard_actions(&self, view_info: &ViewInfo, actions: &crate::actions::ActionFrame) {
+        use crate::actions::Action;
+        let copies = actions.action_trigger_count(Action::Copy);
+        let pastes = actions.action_trigger_count(Action::Paste);
+        let in_place = actions.action_trigger_count(Action::PasteInPlace);
+        if copies + pastes + in_place == 0 {
+            return;
+        }
+        let Some(globals) = crate::AdHocGlobals::read_clone() else {
+            return;
+        };
+        let provider = crate::global::provider();
+        if let Some(node) = globals.node.filter(|_| copies > 0) {
+            provider.inspect(globals.document, |queue| {
+                crate::global::clipboard::copy(queue, node)
+            });
+        }
+
+        let view_center = (|| {
+            let center = view_info.center();
+            let center = view_info
+                .calculate_transform()?
+                .unproject(cgmath::Point2 {
+                    x: center.x,
+                    y: center.y,
+                })
+                .ok()?;
+            Some([center.x, center.y])
+        })();
+        let at = self.cursor.or(view_center);
+        let mut selected = globals.node;
+        for at in std::iter::repeat(at)
+            .take(pastes)
+            .chain(std::iter::repeat(None).take(in_place))
+        {
+            let pasted = provider
+                .write(globals.document, "paste", |queue| {
+                    // Images copied in other programs since the last copy here take precedence.
+                    view_center
+                        .and_then(|center| {
+                            crate::global::clipboard::paste_system_image(queue, selected, center)
+                        })
+                        .or_else(|| crate::global::clipboard::paste(queue, selected, at))
+                })
+                .flatten();
+            selected = pasted.or(selected);
+        }
+        // Select what was pasted.
+        if selected != globals.node {
+            if let Some(globals) = crate::AdHocGlobals::get().write().as_mut() {
+                globals.node = selected;
+            }
+        }
+    }
     fn tool_for_state(&mut self, state: StateLayer) -> &mut dyn PenTool {
         match state {
             StateLayer::Brush => self.brush.as_mut(),
@@ -268,14 +585,25 @@ impl ToolState {
             StateLayer::ViewportScrub => self.document_scrub.as_mut(),
             StateLayer::ViewportRotate => self.document_rotate.as_mut(),
             StateLayer::Gizmos => self.gizmos.as_mut(),
+            StateLayer::Gradient => self.gradient.as_mut(),
             StateLayer::Lasso => self.lasso.as_mut(),
+            StateLayer::Ruler => self.ruler.as_mut(),
         }
     }
     fn apply_state_transition(&mut self, transition: Transition) {
-        match transition {
-            Transition::ToBase => self.layer = None,
-            Transition::ToLayer(layer) => self.layer = Some(layer),
+        let layer = match transition {
+            Transition::ToBase => None,
+            Transition::ToLayer(layer) => Some(layer),
+        };
+        // A layer held over the base is done with once released, such as the picker from
+        // `Action::PickerHold`. The base carries on where it left off.
+        if let Some(old) = self
+            .layer
+            .filter(|&old| Some(old) != layer && old != self.base)
+        {
+            self.tool_for_state(old).exit();
         }
+        self.layer = layer;
     }
     /// Set the resting state, where tools will go when no hotkey set.
     pub fn set_base_state(&mut self, state: StateLayer) {
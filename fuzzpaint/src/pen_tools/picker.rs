@@ -1,58 +1,193 @@
+//! The eyedropper. While over the document, a ring around the pen compares the brush's color, above, with
+//! the composited color beneath, below, sampled as quickly as the renderer answers. Releasing the pen takes
+//! the sampled color for the brush.
+
+/// Radius of the middle of the ring, in viewport pixels.
+const RING_RADIUS: f32 = 36.0;
+/// Width of the ring, in viewport pixels.
+const RING_WIDTH: f32 = 12.0;
+/// Points along each half of the ring.
+const RING_RES: i16 = 24;
+
+/// The latest sample and where it was taken, for the UI to read out.
+#[derive(Copy, Clone, Debug)]
+pub struct Readout {
+    /// Viewport position, in physical pixels.
+    pub pos: [f32; 2],
+    /// Linear and premultiplied.
+    pub color: [f32; 4],
+}
+static READOUT: parking_lot::Mutex<Option<Readout>> = parking_lot::const_mutex(None);
+/// The eyedropper's latest sample, if it's in use and over the document.
+#[must_use]
+pub fn readout() -> Option<Readout> {
+    *READOUT.lock()
+}
+
+/// Linear premultiplied color to straight linear bytes, opaque so that it can be compared at a glance.
+fn opaque_bytes([r, g, b, a]: [f32; 4]) -> [u8; 4] {
+    // Avoid div by zero, transparent shows as black.
+    let unmultiply = |c: f32| if a > 0.001 { c / a } else { 0.0 };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let byte = |c: f32| (unmultiply(c).clamp(0.0, 1.0) * 255.9999) as u8;
+    [byte(r), byte(g), byte(b), 255]
+}
+
+/// Half the ring, the top half if `top`, in one color.
+fn half_ring(center: [f32; 2], top: bool, color: [u8; 4]) -> crate::gizmos::Gizmo {
+    use crate::gizmos::{transform, Gizmo, MeshMode, TextureMode, Visual};
+    let start = if top { std::f32::consts::PI } else { 0.0 };
+    // One past either end for lines adjacency, continuing around.
+    let points: Vec<_> = (-1..=RING_RES + 1)
+        .map(|idx| {
+            let angle = start + std::f32::consts::PI * f32::from(idx) / f32::from(RING_RES);
+            let (sin, cos) = angle.sin_cos();
+            crate::gizmos::renderer::WideLineVertex {
+                pos: [cos * RING_RADIUS, sin * RING_RADIUS],
+                color: [255; 4],
+                tex_coord: 0.0,
+                width: RING_WIDTH,
+            }
+        })
+        .collect();
+    Gizmo {
+        visual: Visual {
+            mesh: MeshMode::WideLineStrip(points.into()),
+            texture: TextureMode::Solid(color),
+        },
+        transform: transform::Transform::handle(ultraviolet::Vec2 {
+            x: center[0],
+            y: center[1],
+        }),
+        ..Default::default()
+    }
+}
+
 pub struct Picker {
     was_down: bool,
+    /// Viewport and document position of the pen.
+    cursor: Option<([f32; 2], [f32; 2])>,
+    /// The sample asked of the renderer, if it hasn't answered yet.
+    pending: Option<tokio::sync::oneshot::Receiver<anyhow::Result<[f32; 4]>>>,
+    /// The latest answer, `None` if the pen is outside the document.
+    sampled: Option<[f32; 4]>,
 }
 impl super::MakePenTool for Picker {
     fn new_from_renderer(
         _: &std::sync::Arc<crate::render_device::RenderContext>,
     ) -> anyhow::Result<Box<dyn super::PenTool>> {
-        Ok(Box::new(Picker { was_down: false }))
+        Ok(Box::new(Picker {
+            was_down: false,
+            cursor: None,
+            pending: None,
+            sampled: None,
+        }))
+    }
+}
+impl Picker {
+    /// Give the brush the sampled color.
+    fn take(&self) {
+        let Some(color) = self.sampled else {
+            return;
+        };
+        let Ok(color) = fuzzpaint_core::color::Color::from_array_lossy(color) else {
+            return;
+        };
+        if let Some(globals) = crate::AdHocGlobals::get().write().as_mut() {
+            globals.brush.color_modulate = color.into();
+        }
     }
 }
 #[async_trait::async_trait]
 impl super::PenTool for Picker {
     fn exit(&mut self) {
         self.was_down = false;
+        self.cursor = None;
+        self.pending = None;
+        self.sampled = None;
+        *READOUT.lock() = None;
     }
     async fn process(
         &mut self,
         view_info: &super::ViewInfo,
-        _stylus_input: crate::stylus_events::StylusEventFrame,
+        stylus_input: crate::stylus_events::StylusEventFrame,
         _actions: &crate::actions::ActionFrame,
         _tool_output: &mut super::ToolStateOutput,
-        _render_output: &mut super::ToolRenderOutput,
+        render_output: &mut super::ToolRenderOutput,
     ) {
-        // Someone got bored and frustrated halfway through writing this...
-        let _requests: &mut tokio::sync::mpsc::Sender<crate::renderer::requests::RenderRequest> =
-            return;
-
-        // If we have a sampler already, track with the pen sampling everwhere where it's down.
-        // If we don't have a sampler (or lose it midway through), take the last input, and if it's down, make sampler.
-        // For now, naive impl!
-        for event in &*_stylus_input {
-            if !event.pressed && !self.was_down {
-                // Just released, take a sample!
-                let Some(globals) = crate::AdHocGlobals::read_clone() else {
-                    return;
-                };
-                let (send, response) = tokio::sync::oneshot::channel();
-                let req = crate::renderer::requests::RenderRequest::CreatePicker {
-                    document: globals.document,
-                    picker: crate::renderer::requests::PickerRequest::Composited(send),
-                    info: crate::renderer::requests::PickerInfo {
-                        input_points_per_viewport_pixel: 1.0, // TODO! We don't have access to this information at all yet.
-                        viewport: *view_info,
-                        sample_pos: ultraviolet::Vec2 {
-                            x: event.pos.0,
-                            y: event.pos.1,
-                        },
-                    },
-                };
-                let _ = _requests.send(req).await;
-                if let Ok(Err(e)) = response.await {
-                    log::trace!("{:?}", e);
-                };
+        for event in stylus_input.iter() {
+            let Some(point) = view_info.calculate_transform().and_then(|xform| {
+                xform
+                    .unproject(cgmath::Point2 {
+                        x: event.pos.0,
+                        y: event.pos.1,
+                    })
+                    .ok()
+            }) else {
+                continue;
+            };
+            self.cursor = Some(([event.pos.0, event.pos.1], [point.x, point.y]));
+            if self.was_down && !event.pressed {
+                self.take();
             }
             self.was_down = event.pressed;
         }
+
+        if let Some(pending) = &mut self.pending {
+            match pending.try_recv() {
+                Ok(Ok(color)) => {
+                    self.sampled = Some(color);
+                    self.pending = None;
+                }
+                // Outside the document.
+                Ok(Err(_)) => {
+                    self.sampled = None;
+                    self.pending = None;
+                }
+                Err(tokio::sync::oneshot::error::TryRecvError::Empty) => (),
+                // Dropped by a busy renderer, ask again.
+                Err(tokio::sync::oneshot::error::TryRecvError::Closed) => self.pending = None,
+            }
+        }
+        // One in flight at a time, asked again as soon as it's answered.
+        let Some((pos, center)) = self.cursor else {
+            return;
+        };
+        if self.pending.is_none() {
+            let (send, recv) = tokio::sync::oneshot::channel();
+            render_output.sample_color = Some((center, send));
+            self.pending = Some(recv);
+        }
+
+        *READOUT.lock() = self.sampled.map(|color| Readout { pos, color });
+        let Some(sampled) = self.sampled else {
+            return;
+        };
+        let current = crate::AdHocGlobals::read_clone()
+            // Todo: fetch if paletted.
+            .and_then(|globals| globals.brush.color_modulate.get().left())
+            .map_or([0, 0, 0, 255], |color| opaque_bytes(color.as_array()));
+        render_output.render_as = super::RenderAs::InlineGizmos(
+            [
+                half_ring(center, true, current),
+                half_ring(center, false, opaque_bytes(sampled)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn opaque_bytes() {
+        assert_eq!(
+            super::opaque_bytes([0.25, 0.0, 0.5, 0.5]),
+            [127, 0, 255, 255]
+        );
+        assert_eq!(super::opaque_bytes([0.0; 4]), [0, 0, 0, 255]);
+        // Out of range clamps.
+        assert_eq!(super::opaque_bytes([2.0, -1.0, 0.0, 1.0]), [255, 0, 0, 255]);
     }
 }
@@ -0,0 +1,222 @@
+//! Editing the document's [ruler](fuzzpaint_core::ruler). Dragging the handle moves the ruler, dragging
+//! anywhere else turns it to point along the drag. The nudge actions, or scrolling over the handle, move it
+//! by single pixels.
+
+use fuzzpaint_core::ruler::Ruler as Geometry;
+
+/// Radius of the handle, in viewport pixels.
+const HANDLE_RADIUS: f32 = 8.0;
+/// Distance between neighboring guides drawn for parallel and isometric rulers, in document pixels.
+const GUIDE_SPACING: f32 = 64.0;
+/// How many guides to draw either side of the handle.
+const GUIDE_REPEATS: i16 = 12;
+/// Length of drawn guides, long enough to cross any reasonable view of the document.
+const GUIDE_LENGTH: f32 = 16384.0;
+/// How many guides to draw radiating from a vanishing point.
+const PERSPECTIVE_RAYS: u16 = 32;
+
+/// A straight document-space line through `origin` along unit `direction`.
+fn line_gizmo(origin: [f32; 2], direction: [f32; 2]) -> crate::gizmos::Gizmo {
+    let along = |dist: f32| crate::gizmos::renderer::WideLineVertex {
+        pos: [
+            direction[0].mul_add(dist, origin[0]),
+            direction[1].mul_add(dist, origin[1]),
+        ],
+        color: [255; 4],
+        tex_coord: 0.0,
+        width: 1.0,
+    };
+    // Plus two for lines adjacency, continuing straight on.
+    let points = [
+        along(-GUIDE_LENGTH * 2.0),
+        along(-GUIDE_LENGTH),
+        along(GUIDE_LENGTH),
+        along(GUIDE_LENGTH * 2.0),
+    ];
+    crate::gizmos::Gizmo {
+        visual: crate::gizmos::Visual {
+            mesh: crate::gizmos::MeshMode::WideLineStrip(points.into()),
+            texture: crate::gizmos::TextureMode::Solid([64, 160, 255, 128]),
+        },
+        transform: crate::gizmos::transform::Transform::outline(),
+        ..Default::default()
+    }
+}
+/// A family of lines along `direction`, evenly spaced across it.
+fn parallel_gizmos(
+    origin: [f32; 2],
+    direction: [f32; 2],
+) -> impl Iterator<Item = crate::gizmos::Gizmo> {
+    let across = [-direction[1], direction[0]];
+    (-GUIDE_REPEATS..=GUIDE_REPEATS).map(move |idx| {
+        let dist = f32::from(idx) * GUIDE_SPACING;
+        line_gizmo(
+            [
+                across[0].mul_add(dist, origin[0]),
+                across[1].mul_add(dist, origin[1]),
+            ],
+            direction,
+        )
+    })
+}
+/// The guides of the ruler, and its handle.
+pub fn gizmos(ruler: &Geometry) -> Vec<crate::gizmos::Gizmo> {
+    use crate::gizmos::{transform, Gizmo, MeshMode, RenderShape, TextureMode, Visual};
+    let handle = ruler.handle();
+    let mut gizmos: Vec<_> = match *ruler {
+        Geometry::Parallel { .. } | Geometry::Isometric { .. } => ruler
+            .directions(handle)
+            .into_iter()
+            .flat_map(|direction| parallel_gizmos(handle, direction))
+            .collect(),
+        Geometry::Perspective { vanishing_point } => (0..PERSPECTIVE_RAYS)
+            .map(|idx| {
+                // Each line covers two rays, so half a turn is enough.
+                let angle = std::f32::consts::PI * f32::from(idx) / f32::from(PERSPECTIVE_RAYS);
+                let (sin, cos) = angle.sin_cos();
+                line_gizmo(vanishing_point, [cos, sin])
+            })
+            .collect(),
+    };
+    gizmos.push(Gizmo {
+        visual: Visual {
+            mesh: MeshMode::Shape(RenderShape::Ellipse {
+                origin: ultraviolet::Vec2 { x: 0.0, y: 0.0 },
+                radii: ultraviolet::Vec2 {
+                    x: HANDLE_RADIUS,
+                    y: HANDLE_RADIUS,
+                },
+                rotation: 0.0,
+            }),
+            texture: TextureMode::Solid([64, 160, 255, 200]),
+        },
+        transform: transform::Transform::handle(ultraviolet::Vec2 {
+            x: handle[0],
+            y: handle[1],
+        }),
+        ..Default::default()
+    });
+    gizmos
+}
+
+enum Grab {
+    /// Moving the handle, which is this far from the pen.
+    Handle { offset: [f32; 2] },
+    /// Turning the ruler to face the pen.
+    Rotate,
+}
+pub struct Ruler {
+    grab: Option<Grab>,
+    /// Whether the pen was over the handle as of the last event.
+    hovering: bool,
+}
+impl super::MakePenTool for Ruler {
+    fn new_from_renderer(
+        _: &std::sync::Arc<crate::render_device::RenderContext>,
+    ) -> anyhow::Result<Box<dyn super::PenTool>> {
+        Ok(Box::new(Ruler {
+            grab: None,
+            hovering: false,
+        }))
+    }
+}
+#[async_trait::async_trait]
+impl super::PenTool for Ruler {
+    fn exit(&mut self) {
+        self.grab = None;
+        self.hovering = false;
+    }
+    async fn process(
+        &mut self,
+        view_info: &super::ViewInfo,
+        stylus_input: crate::stylus_events::StylusEventFrame,
+        actions: &crate::actions::ActionFrame,
+        _tool_output: &mut super::ToolStateOutput,
+        render_output: &mut super::ToolRenderOutput,
+    ) {
+        use crate::global::rulers;
+        let Some(globals) = crate::AdHocGlobals::read_clone() else {
+            self.grab = None;
+            return;
+        };
+        let Some(transform) = view_info.calculate_transform() else {
+            return;
+        };
+        for event in stylus_input.iter() {
+            let Ok(pos) = transform.unproject(cgmath::Point2 {
+                x: event.pos.0,
+                y: event.pos.1,
+            }) else {
+                return;
+            };
+            let pos = [pos.x, pos.y];
+            let current = rulers::get(globals.document);
+            let on_handle = current.is_some_and(|current| {
+                let [x, y] = current.ruler.handle();
+                let handle = transform.project(cgmath::Point2 { x, y });
+                (handle.x - event.pos.0).hypot(handle.y - event.pos.1) <= HANDLE_RADIUS
+            });
+            self.hovering = on_handle;
+            if !event.pressed {
+                self.grab = None;
+                continue;
+            }
+
+            let mut current = current.unwrap_or(rulers::Rulers {
+                // No ruler yet, start one here.
+                ruler: Geometry::Parallel {
+                    origin: pos,
+                    angle: 0.0,
+                },
+                enabled: true,
+            });
+            let grab = self.grab.get_or_insert_with(|| {
+                let handle = current.ruler.handle();
+                if on_handle {
+                    Grab::Handle {
+                        offset: [handle[0] - pos[0], handle[1] - pos[1]],
+                    }
+                } else if current.ruler.angle_mut().is_some() {
+                    Grab::Rotate
+                } else {
+                    // Nothing to turn, bring the handle here instead.
+                    Grab::Handle { offset: [0.0; 2] }
+                }
+            });
+            match *grab {
+                Grab::Handle { offset } => {
+                    *current.ruler.handle_mut() = [pos[0] + offset[0], pos[1] + offset[1]];
+                }
+                Grab::Rotate => {
+                    let handle = current.ruler.handle();
+                    let delta = [pos[0] - handle[0], pos[1] - handle[1]];
+                    if let Some(angle) = current.ruler.angle_mut() {
+                        if delta != [0.0; 2] {
+                            *angle = delta[1].atan2(delta[0]);
+                        }
+                    }
+                }
+            }
+            rulers::set(globals.document, current);
+        }
+        // Not while dragging, the drag would override it anyway.
+        if let Some(offset) = super::nudge_offset(actions).filter(|_| self.grab.is_none()) {
+            if let Some(mut current) = rulers::get(globals.document) {
+                let handle = current.ruler.handle_mut();
+                *handle = [handle[0] + offset[0], handle[1] + offset[1]];
+                rulers::set(globals.document, current);
+            }
+        }
+        render_output.capture_scroll = self.hovering;
+
+        render_output.cursor = Some(crate::gizmos::CursorOrInvisible::Icon(
+            if matches!(self.grab, Some(Grab::Handle { .. })) {
+                winit::window::CursorIcon::Grabbing
+            } else if self.hovering {
+                winit::window::CursorIcon::Grab
+            } else {
+                winit::window::CursorIcon::Crosshair
+            },
+        ));
+    }
+}
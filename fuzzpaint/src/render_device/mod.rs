@@ -0,0 +1,1064 @@
+use crate::vulkano_prelude::*;
+use anyhow::Result as AnyResult;
+use std::sync::Arc;
+
+pub mod readback;
+
+/// Check that every index is less than the number of vertices in debug builds, nop in release mode.
+/// # Panics
+/// on failure.
+pub fn debug_assert_indices_safe<Vertex>(vertices: &[Vertex], indices: &[u16]) {
+    #[cfg(debug_assertions)]
+    if indices
+        .iter()
+        .any(|&index| usize::from(index) >= vertices.len())
+    {
+        panic!("index buffer includes invalid indices");
+    }
+}
+
+/// Which physical device to use, rather than choosing automatically.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PreferredDevice {
+    /// Index in the order the driver lists them, which may change between runs.
+    Index(usize),
+    /// The device's name, as in [`PhysicalDeviceInfo::name`].
+    Name(String),
+}
+impl std::fmt::Display for PreferredDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(idx) => write!(f, "device {idx}"),
+            Self::Name(name) => write!(f, "\"{name}\""),
+        }
+    }
+}
+/// A physical device seen while choosing one, see [`available_devices`].
+#[derive(Clone, Debug)]
+pub struct PhysicalDeviceInfo {
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    /// Whether it has the features fuzzpaint needs. Unsuitable devices can't be chosen.
+    pub suitable: bool,
+}
+static AVAILABLE_DEVICES: std::sync::OnceLock<Vec<PhysicalDeviceInfo>> = std::sync::OnceLock::new();
+static DEVICE_FALLBACK: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+/// Every physical device seen when the render context was made, in the order the driver listed them.
+/// Empty before then.
+#[must_use]
+pub fn available_devices() -> &'static [PhysicalDeviceInfo] {
+    AVAILABLE_DEVICES.get().map_or(&[], Vec::as_slice)
+}
+/// If the preferred device could not be used, a message explaining so for the user.
+#[must_use]
+pub fn device_fallback() -> Option<&'static str> {
+    DEVICE_FALLBACK.get().map(String::as_str)
+}
+static SOFTWARE_RENDERING: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+/// Whether the chosen device is a software implementation running on the CPU, such as llvmpipe.
+/// Everything works, but slowly, so expensive extras are disabled.
+#[must_use]
+pub fn is_software_rendering() -> bool {
+    SOFTWARE_RENDERING.load(std::sync::atomic::Ordering::Relaxed)
+}
+static FORCE_LOW_LATENCY: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+/// Present with low latency for the rest of the session, whatever the preferences say. Not saved.
+pub fn force_low_latency() {
+    FORCE_LOW_LATENCY.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+/// Whether windows should present with low latency, by the user's preference or as forced for the session,
+/// see [`force_low_latency`].
+#[must_use]
+pub fn wants_low_latency() -> bool {
+    FORCE_LOW_LATENCY.load(std::sync::atomic::Ordering::Relaxed)
+        || crate::global::preferences::Preferences::read().low_latency
+}
+
+/// High level description of how physical device limits translate into
+/// limits for fuzzpaint. Note that limits may still be larger than reasonable.
+pub struct HighLevelLimits {
+    pub max_document_size: [u32; 2],
+}
+impl HighLevelLimits {
+    fn from_device(device: &vk::Device) -> Self {
+        let p = device.physical_device().properties();
+        // May be greater for specific formats, but this is the guaranteed minimum for all.
+        // For now, just use this. (avoids nasty bugs if I forget to update this when I
+        // change a format somewhere.. lol)
+        let dimension = p.max_image_dimension2_d;
+        Self {
+            // Don't need to care about max_viewport* as the spec says they are >= max_framebuffer*.
+            max_document_size: [
+                dimension.min(p.max_framebuffer_width),
+                dimension.min(p.max_framebuffer_height),
+            ],
+        }
+    }
+}
+
+/// Usage of a single memory heap, in bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct HeapUsage {
+    pub size: u64,
+    /// Bytes used by this process, if the implementation reports it.
+    pub usage: Option<u64>,
+    /// Bytes this process may use before performance suffers, if the implementation reports it.
+    pub budget: Option<u64>,
+}
+
+pub struct Queue {
+    queue: Arc<vk::Queue>,
+    family_idx: u32,
+}
+impl Queue {
+    #[must_use]
+    pub fn idx(&self) -> u32 {
+        self.family_idx
+    }
+    #[must_use]
+    pub fn queue(&self) -> &Arc<vk::Queue> {
+        &self.queue
+    }
+}
+
+/// A role a queue plays, to describe which queues a resource will be used by. See [`Queues::sharing`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueueUse {
+    Graphics,
+    Compute,
+    Transfer,
+}
+
+enum QueueSrc {
+    UseGraphics,
+    Queue(Queue),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct QueueIndices {
+    /// Also present, if that was required.
+    graphics: u32,
+    graphics_can_present: bool,
+    compute: u32,
+}
+pub struct Queues {
+    graphics_queue: Queue,
+    /// Always the same as graphics if so (required by vulkano during present).
+    has_present: bool,
+    compute_queue: QueueSrc,
+}
+impl Queues {
+    #[must_use]
+    pub fn present(&self) -> Option<&Queue> {
+        self.has_present.then(|| self.graphics())
+    }
+    #[must_use]
+    pub fn graphics(&self) -> &Queue {
+        &self.graphics_queue
+    }
+    #[must_use]
+    pub fn compute(&self) -> &Queue {
+        match &self.compute_queue {
+            QueueSrc::UseGraphics => self.graphics(),
+            QueueSrc::Queue(q) => q,
+        }
+    }
+    //No transfer queues yet, just use Graphics.
+    #[must_use]
+    pub fn transfer(&self) -> &Queue {
+        self.graphics()
+    }
+    /// Whether compute has a queue of its own. It may still be of the same family as graphics, see
+    /// [`Self::has_unique_compute_family`].
+    #[must_use]
+    pub fn has_unique_compute(&self) -> bool {
+        match &self.compute_queue {
+            QueueSrc::UseGraphics => false,
+            QueueSrc::Queue(..) => true,
+        }
+    }
+    /// Whether compute is of a different family than graphics, and so resources must be shared between them.
+    #[must_use]
+    pub fn has_unique_compute_family(&self) -> bool {
+        self.compute().idx() != self.graphics().idx()
+    }
+    #[must_use]
+    pub fn get(&self, queue_use: QueueUse) -> &Queue {
+        match queue_use {
+            QueueUse::Graphics => self.graphics(),
+            QueueUse::Compute => self.compute(),
+            QueueUse::Transfer => self.transfer(),
+        }
+    }
+    /// Create a sharing object for a resource used by each of `uses`.
+    ///
+    /// Ownership belongs to a queue family, not a queue, so this is exclusive when every use falls within one
+    /// family, even if they're separate queues. Otherwise, it's always concurrent between the distinct families.
+    /// No ownership transfers are ever recorded, as vulkano's automatic synchronization has no way to express
+    /// them, so an exclusive resource must only ever be used by the one family - list every queue that touches
+    /// it in `uses`. Concurrency only costs for images, where it can prevent compression, so keep images to a
+    /// single family where possible.
+    #[must_use]
+    pub fn sharing<C>(&self, uses: &[QueueUse]) -> vk::Sharing<C>
+    where
+        C: FromIterator<u32> + IntoIterator<Item = u32>,
+    {
+        // Concurrent sharing requires the indices be unique.
+        let mut families: smallvec::SmallVec<[u32; 3]> = uses
+            .iter()
+            .map(|&queue_use| self.get(queue_use).idx())
+            .collect();
+        families.sort_unstable();
+        families.dedup();
+        if families.len() > 1 {
+            vk::Sharing::Concurrent(families.into_iter().collect())
+        } else {
+            vk::Sharing::Exclusive
+        }
+    }
+    /// Create a sharing object for compute and graphics, see [`Self::sharing`].
+    #[must_use]
+    pub fn sharing_compute_graphics<C>(&self) -> vk::Sharing<C>
+    where
+        C: FromIterator<u32> + IntoIterator<Item = u32>,
+    {
+        self.sharing(&[QueueUse::Graphics, QueueUse::Compute])
+    }
+}
+
+pub struct RenderSurface {
+    context: Arc<RenderContext>,
+    swapchain: Arc<vk::Swapchain>,
+    surface: Arc<vk::Surface>,
+    swapchain_images: Vec<Arc<vk::Image>>,
+    /// Drawn into like the swapchain images but never presented, for screenshots. See [`Self::render_targets`].
+    capture_image: Arc<vk::Image>,
+
+    swapchain_create_info: vk::SwapchainCreateInfo,
+    low_latency: bool,
+    /// Whether transparency was asked for, see [`Self::transparent`] for whether it was granted.
+    want_transparent: bool,
+}
+impl RenderSurface {
+    #[must_use]
+    pub fn extent(&self) -> [u32; 2] {
+        self.swapchain_create_info.image_extent
+    }
+    #[must_use]
+    pub fn format(&self) -> vk::Format {
+        self.swapchain_create_info.image_format
+    }
+    #[must_use]
+    pub fn swapchain(&self) -> &Arc<vk::Swapchain> {
+        &self.swapchain
+    }
+    #[must_use]
+    pub fn swapchain_images(&self) -> &[Arc<vk::Image>] {
+        &self.swapchain_images
+    }
+    /// The swapchain images followed by the capture image, which is at [`Self::capture_idx`]. Anything drawing
+    /// into the swapchain by index should prepare for each of these.
+    pub fn render_targets(&self) -> impl Iterator<Item = &Arc<vk::Image>> + '_ {
+        self.swapchain_images
+            .iter()
+            .chain(std::iter::once(&self.capture_image))
+    }
+    /// An image of the same size and format as the swapchain's, to draw frames into that aren't presented.
+    #[must_use]
+    pub fn capture_image(&self) -> &Arc<vk::Image> {
+        &self.capture_image
+    }
+    /// The index of the capture image within [`Self::render_targets`], in place of a swapchain image index.
+    #[must_use]
+    pub fn capture_idx(&self) -> u32 {
+        // Swapchains are a handful of images, not billions.
+        #[allow(clippy::cast_possible_truncation)]
+        let idx = self.swapchain_images.len() as u32;
+        idx
+    }
+    #[must_use]
+    pub fn context(&self) -> &Arc<RenderContext> {
+        &self.context
+    }
+    /// Whether the swapchain was chosen to minimize latency, see [`Self::set_low_latency`].
+    #[must_use]
+    pub fn low_latency(&self) -> bool {
+        self.low_latency
+    }
+    /// Whether the window shows through where the image is transparent, see [`Self::set_transparent`].
+    /// Color is then taken to be premultiplied by alpha.
+    #[must_use]
+    pub fn transparent(&self) -> bool {
+        self.swapchain_create_info.composite_alpha != vk::CompositeAlpha::Opaque
+    }
+    /// Opaque if not `transparent` or if the surface can't blend, otherwise premultiplied. Inherit leaves it to
+    /// the window, which is premultiplied on the platforms that allow it at all.
+    fn choose_composite_alpha(
+        capabilities: &vulkano::swapchain::SurfaceCapabilities,
+        transparent: bool,
+    ) -> vk::CompositeAlpha {
+        let supported = capabilities.supported_composite_alpha;
+        let preference: &[vk::CompositeAlpha] = if transparent {
+            &[
+                vk::CompositeAlpha::PreMultiplied,
+                vk::CompositeAlpha::Inherit,
+                vk::CompositeAlpha::Opaque,
+            ]
+        } else {
+            &[vk::CompositeAlpha::Opaque]
+        };
+        preference
+            .iter()
+            .copied()
+            .find(|&alpha| supported.into_iter().any(|other| other == alpha))
+            // We don't care!
+            .or_else(|| supported.into_iter().next())
+            .expect("Device provided no alpha modes")
+    }
+    /// Prefer replacing the queued frame (mailbox), which never tears. Otherwise FIFO is always supported, unless
+    /// low latency is wanted enough to not wait at all (immediate), at the cost of tearing.
+    fn choose_present_mode(
+        physical_device: &vk::PhysicalDevice,
+        surface: &vk::Surface,
+        low_latency: bool,
+    ) -> vk::PresentMode {
+        let modes: Vec<_> = physical_device
+            .surface_present_modes(surface, vk::SurfaceInfo::default())
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        let preference: &[vk::PresentMode] = if low_latency {
+            &[vk::PresentMode::Mailbox, vk::PresentMode::Immediate]
+        } else {
+            &[vk::PresentMode::Mailbox]
+        };
+        preference
+            .iter()
+            .copied()
+            .find(|mode| modes.contains(mode))
+            .unwrap_or(vk::PresentMode::Fifo)
+    }
+    /// Use the minimum - Only one frame will be rendered at once. Mailbox needs one more,
+    /// so that there's always an image free to render into while another is queued.
+    fn choose_image_count(
+        capabilities: &vulkano::swapchain::SurfaceCapabilities,
+        present_mode: vk::PresentMode,
+    ) -> u32 {
+        if present_mode == vk::PresentMode::Mailbox {
+            let count = capabilities.min_image_count + 1;
+            capabilities
+                .max_image_count
+                .map_or(count, |max| count.min(max))
+        } else {
+            capabilities.min_image_count
+        }
+    }
+    fn new_capture_image(
+        context: &RenderContext,
+        info: &vk::SwapchainCreateInfo,
+    ) -> AnyResult<Arc<vk::Image>> {
+        let [width, height] = info.image_extent;
+        Ok(vk::Image::new(
+            context.allocators().memory().clone(),
+            vk::ImageCreateInfo {
+                format: info.image_format,
+                extent: [width, height, 1],
+                // Drawn like the swapchain, then read back.
+                usage: info.image_usage | vk::ImageUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            vk::AllocationCreateInfo {
+                memory_type_filter: vk::MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )?)
+    }
+    fn new(
+        context: Arc<RenderContext>,
+        surface: Arc<vk::Surface>,
+        size: [u32; 2],
+        low_latency: bool,
+    ) -> AnyResult<Self> {
+        let physical_device = context.physical_device();
+
+        let surface_info = vk::SurfaceInfo::default();
+        let capabilies = physical_device.surface_capabilities(&surface, surface_info.clone())?;
+
+        let Some(&(format, color_space)) = physical_device
+            .surface_formats(&surface, surface_info)?
+            .iter()
+            // FIXME!! Find the highest BGRA swapchain format.
+            // Used to make bad assumptions about Framebuffer formats later in the code :V
+            // What really needs to happen is *whatever* format is chosen (we don't care)
+            // needs to be broadcast out and pipelines need to be remade if incompatible.
+            // No infrastructure for that at this time.
+            .find(|(format, _)| *format == vk::Format::B8G8R8A8_SRGB)
+        else {
+            return Err(anyhow::anyhow!(
+                "Device reported no supported surface formats."
+            ));
+        };
+
+        let present_mode = Self::choose_present_mode(physical_device, &surface, low_latency);
+        let image_count = Self::choose_image_count(&capabilies, present_mode);
+
+        let alpha_mode = Self::choose_composite_alpha(&capabilies, false);
+
+        let swapchain_create_info = vk::SwapchainCreateInfo {
+            min_image_count: image_count,
+            image_format: format,
+            image_color_space: color_space,
+            image_extent: size,
+            image_usage: vk::ImageUsage::COLOR_ATTACHMENT | vk::ImageUsage::TRANSFER_DST,
+            composite_alpha: alpha_mode,
+            present_mode,
+            clipped: true, // We wont read the framebuffer.
+            ..Default::default()
+        };
+
+        let (swapchain, images) = vk::Swapchain::new(
+            context.device().clone(),
+            surface.clone(),
+            swapchain_create_info.clone(),
+        )?;
+        let capture_image = Self::new_capture_image(&context, &swapchain_create_info)?;
+
+        Ok(Self {
+            context,
+            swapchain,
+            surface,
+            swapchain_images: images,
+            capture_image,
+            swapchain_create_info,
+            low_latency,
+            want_transparent: false,
+        })
+    }
+    pub fn recreate(self, new_size: Option<[u32; 2]>) -> AnyResult<Self> {
+        let mut new_info = self.swapchain_create_info;
+        if let Some(new_size) = new_size {
+            new_info.image_extent = new_size;
+        }
+        let (swapchain, swapchain_images) = self.swapchain.recreate(new_info.clone())?;
+        let capture_image = Self::new_capture_image(&self.context, &new_info)?;
+
+        Ok(Self {
+            swapchain,
+            swapchain_images,
+            capture_image,
+            swapchain_create_info: new_info,
+            ..self
+        })
+    }
+    /// Recreate with a present mode chosen for latency (`true`) or to never tear (`false`).
+    /// A no-op if already asked for, and the swapchain is kept if the present mode is the same either way.
+    pub fn set_low_latency(self, low_latency: bool) -> AnyResult<Self> {
+        if low_latency == self.low_latency {
+            return Ok(self);
+        }
+        let physical_device = self.context.physical_device();
+        let capabilities =
+            physical_device.surface_capabilities(&self.surface, vk::SurfaceInfo::default())?;
+        let present_mode = Self::choose_present_mode(physical_device, &self.surface, low_latency);
+        if present_mode == self.swapchain_create_info.present_mode {
+            return Ok(Self {
+                low_latency,
+                ..self
+            });
+        }
+
+        let mut new_info = self.swapchain_create_info.clone();
+        new_info.present_mode = present_mode;
+        new_info.min_image_count = Self::choose_image_count(&capabilities, present_mode);
+        let (swapchain, swapchain_images) = self.swapchain.recreate(new_info.clone())?;
+
+        Ok(Self {
+            swapchain,
+            swapchain_images,
+            swapchain_create_info: new_info,
+            low_latency,
+            ..self
+        })
+    }
+    /// Recreate with the window showing through transparent parts of the image (`true`) or not (`false`).
+    /// A no-op if already asked for. Not every surface can blend, so check [`Self::transparent`] afterwards.
+    pub fn set_transparent(self, transparent: bool) -> AnyResult<Self> {
+        if transparent == self.want_transparent {
+            return Ok(self);
+        }
+        let capabilities = self
+            .context
+            .physical_device()
+            .surface_capabilities(&self.surface, vk::SurfaceInfo::default())?;
+
+        let mut new_info = self.swapchain_create_info.clone();
+        new_info.composite_alpha = Self::choose_composite_alpha(&capabilities, transparent);
+        let (swapchain, swapchain_images) = self.swapchain.recreate(new_info.clone())?;
+
+        Ok(Self {
+            swapchain,
+            swapchain_images,
+            swapchain_create_info: new_info,
+            want_transparent: transparent,
+            ..self
+        })
+    }
+}
+
+pub struct Allocators {
+    command_buffer_alloc: vk::StandardCommandBufferAllocator,
+    memory_alloc: Arc<dyn vulkano::memory::allocator::MemoryAllocator>,
+    descriptor_set_alloc: vk::StandardDescriptorSetAllocator,
+}
+
+impl Allocators {
+    pub fn command_buffer(&self) -> &vk::StandardCommandBufferAllocator {
+        &self.command_buffer_alloc
+    }
+    pub fn memory(&self) -> &Arc<dyn vulkano::memory::allocator::MemoryAllocator> {
+        &self.memory_alloc
+    }
+    pub fn descriptor_set(&self) -> &vk::StandardDescriptorSetAllocator {
+        &self.descriptor_set_alloc
+    }
+}
+
+pub struct RenderContext {
+    _library: Arc<vk::VulkanLibrary>,
+    instance: Arc<vk::Instance>,
+    physical_device: Arc<vk::PhysicalDevice>,
+    high_level_limits: HighLevelLimits,
+    device: Arc<vk::Device>,
+    queues: Queues,
+
+    _debugger: Option<vulkano::instance::debug::DebugUtilsMessenger>,
+
+    allocators: Allocators,
+    readback: readback::Readback,
+}
+
+impl RenderContext {
+    pub fn new_headless() -> AnyResult<Self> {
+        unimplemented!()
+    }
+    /// Create a context able to present to the window. See [`RenderSurface::set_low_latency`]
+    /// for `low_latency`.
+    ///
+    /// `device` is used instead of the automatic choice if it is present and suitable. Otherwise,
+    /// the automatic choice is used and [`device_fallback`] describes why.
+    pub fn new_with_window_surface(
+        win: &crate::window::Surface,
+        low_latency: bool,
+        device: Option<&PreferredDevice>,
+    ) -> AnyResult<(Arc<Self>, RenderSurface)> {
+        use vulkano::instance::debug as vkDebug;
+
+        let library = vk::VulkanLibrary::new()?;
+
+        let mut required_instance_extensions = vk::Surface::required_extensions(win.event_loop());
+        required_instance_extensions.ext_debug_utils = true;
+
+        let instance = vk::Instance::new(
+            library.clone(),
+            vk::InstanceCreateInfo {
+                application_name: Some(option_env!("CARGO_PKG_NAME").unwrap_or("").to_string()),
+                application_version: vk::Version {
+                    major: option_env!("CARGO_PKG_VERSION_MAJOR")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0),
+                    minor: option_env!("CARGO_PKG_VERSION_MINOR")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0),
+                    patch: option_env!("CARGO_PKG_VERSION_PATCH")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0),
+                },
+                enabled_extensions: required_instance_extensions,
+                ..Default::default()
+            },
+        )?;
+
+        let debugger = vkDebug::DebugUtilsMessenger::new(
+            instance.clone(),
+            vkDebug::DebugUtilsMessengerCreateInfo {
+                message_severity: vkDebug::DebugUtilsMessageSeverity::ERROR
+                    | vkDebug::DebugUtilsMessageSeverity::WARNING
+                    | vkDebug::DebugUtilsMessageSeverity::INFO
+                    | vkDebug::DebugUtilsMessageSeverity::VERBOSE,
+                message_type: vkDebug::DebugUtilsMessageType::GENERAL
+                    | vkDebug::DebugUtilsMessageType::PERFORMANCE
+                    | vkDebug::DebugUtilsMessageType::VALIDATION,
+                ..vkDebug::DebugUtilsMessengerCreateInfo::user_callback(
+                    // SAFETY: the closure must not access vulkan API in any way.
+                    // Not a problem, as it simply logs to console or file and the in-app console,
+                    // none of which touch vulkan. Keep it that way!
+                    unsafe {
+                        vulkano::instance::debug::DebugUtilsMessengerCallback::new(
+                            |severity, ty, data| {
+                                let ty = match ty {
+                                    vkDebug::DebugUtilsMessageType::GENERAL => "GENERAL",
+                                    vkDebug::DebugUtilsMessageType::PERFORMANCE => "PERFORMANCE",
+                                    vkDebug::DebugUtilsMessageType::VALIDATION => "VALIDATION",
+                                    _ => "UNKNOWN",
+                                };
+                                let layer = data.message_id_name.unwrap_or("");
+                                let message = data.message;
+
+                                // Tracing levels must be constant, thus the repetition.
+                                #[allow(clippy::wildcard_in_or_patterns)]
+                                match severity {
+                                    vkDebug::DebugUtilsMessageSeverity::ERROR => {
+                                        tracing::error!(target: "vulkan", ty, layer, "{message}");
+                                    }
+                                    vkDebug::DebugUtilsMessageSeverity::WARNING => {
+                                        tracing::warn!(target: "vulkan", ty, layer, "{message}");
+                                    }
+                                    vkDebug::DebugUtilsMessageSeverity::VERBOSE => {
+                                        tracing::trace!(target: "vulkan", ty, layer, "{message}");
+                                    }
+                                    vkDebug::DebugUtilsMessageSeverity::INFO | _ => {
+                                        tracing::info!(target: "vulkan", ty, layer, "{message}");
+                                    }
+                                }
+                            },
+                        )
+                    },
+                )
+            },
+        )?;
+
+        let surface = vk::Surface::from_window(instance.clone(), win.window())?;
+        let required_device_extensions = vk::DeviceExtensions {
+            khr_swapchain: true,
+            ext_line_rasterization: true,
+            ..Default::default()
+        };
+        // Extensions needed when the physical device supports less than vulkan 1.3
+        let required_device_extensions_lt_1_3 = vk::DeviceExtensions {
+            // Promoted to core in 1.3.
+            khr_dynamic_rendering: true,
+            ..Default::default()
+        };
+
+        let Some((physical_device, queue_indices)) = Self::choose_physical_device(
+            &instance,
+            &required_device_extensions,
+            &required_device_extensions_lt_1_3,
+            Some(&surface),
+            device,
+        )?
+        else {
+            return Err(anyhow::anyhow!("Failed to find a suitable Vulkan device."));
+        };
+
+        tracing::info!(
+            "Chose physical device {} ({:?})",
+            physical_device.properties().device_name,
+            physical_device.properties().driver_info
+        );
+        let is_software = physical_device.properties().device_type == vk::PhysicalDeviceType::Cpu;
+        if is_software {
+            tracing::warn!("Rendering in software, performance will be poor");
+        }
+        SOFTWARE_RENDERING.store(is_software, std::sync::atomic::Ordering::Relaxed);
+
+        let (device, queues) = Self::create_device(
+            physical_device.clone(),
+            queue_indices,
+            &required_device_extensions,
+            &required_device_extensions_lt_1_3,
+        )?;
+
+        // We have a device! Now to create the swapchain..
+        let image_size = win.window().inner_size();
+
+        let memory_alloc: Arc<dyn vulkano::memory::allocator::MemoryAllocator> =
+            Arc::new(vk::StandardMemoryAllocator::new_default(device.clone()));
+        let readback = readback::Readback::new(
+            device.clone(),
+            queues.graphics().queue().clone(),
+            memory_alloc.clone(),
+        )?;
+        let context = Arc::new(Self {
+            allocators: Allocators {
+                command_buffer_alloc: vk::StandardCommandBufferAllocator::new(
+                    device.clone(),
+                    Default::default(),
+                ),
+                memory_alloc,
+                descriptor_set_alloc: vk::StandardDescriptorSetAllocator::new(
+                    device.clone(),
+                    vulkano::descriptor_set::allocator::StandardDescriptorSetAllocatorCreateInfo {
+                        update_after_bind: false,
+                        ..Default::default()
+                    },
+                ),
+            },
+            high_level_limits: HighLevelLimits::from_device(&device),
+            _library: library,
+            instance,
+            device,
+            physical_device,
+            queues,
+            readback,
+
+            _debugger: Some(debugger),
+        });
+        let render_surface = RenderSurface::new(
+            context.clone(),
+            surface.clone(),
+            image_size.into(),
+            // Presenting without waiting just burns more CPU time when the CPU is the GPU.
+            low_latency && !is_software,
+        )?;
+
+        Ok((context, render_surface))
+    }
+    /// Create a surface for another window of the same event loop, presented with this context.
+    /// See [`RenderSurface::set_low_latency`] for `low_latency`.
+    pub fn new_window_surface(
+        self: &Arc<Self>,
+        win: Arc<winit::window::Window>,
+        low_latency: bool,
+    ) -> AnyResult<RenderSurface> {
+        let surface = vk::Surface::from_window(self.instance.clone(), win.clone())?;
+        // The device was chosen to present to the main window, which doesn't guarantee this one.
+        let present = self
+            .queues
+            .present()
+            .ok_or_else(|| anyhow::anyhow!("Device cannot present."))?;
+        if !self
+            .physical_device
+            .surface_support(present.idx(), &surface)?
+        {
+            anyhow::bail!("Device cannot present to this window.");
+        }
+        RenderSurface::new(
+            self.clone(),
+            surface,
+            win.inner_size().into(),
+            low_latency && !is_software_rendering(),
+        )
+    }
+    fn create_device(
+        physical_device: Arc<vk::PhysicalDevice>,
+        queue_indices: QueueIndices,
+        extensions: &vk::DeviceExtensions,
+        extensions_lt_1_3: &vk::DeviceExtensions,
+    ) -> AnyResult<(Arc<vk::Device>, Queues)> {
+        //Need a graphics queue.
+        let mut graphics_queue_info = vk::QueueCreateInfo {
+            queue_family_index: queue_indices.graphics,
+            queues: vec![0.5],
+            ..Default::default()
+        };
+
+        //Todo: what if compute and present end up in the same family, aside from graphics? Unlikely :)
+        // ^^ Pfft, tell that to the future asp who spent two hours debugging a similar situation
+        let (has_unique_compute_queue, compute_queue_info) = {
+            if queue_indices.compute == queue_indices.graphics {
+                let capacity = physical_device.queue_family_properties()
+                    [graphics_queue_info.queue_family_index as usize]
+                    .queue_count;
+                //Is there room for another queue?
+                if capacity as usize > graphics_queue_info.queues.len() {
+                    graphics_queue_info.queues.push(0.5);
+
+                    //In the graphics queue family. No queue info.
+                    (true, None)
+                } else {
+                    //Share a queue with graphics.
+                    (false, None)
+                }
+            } else {
+                (
+                    true,
+                    Some(vk::QueueCreateInfo {
+                        queue_family_index: queue_indices.compute,
+                        queues: vec![0.5],
+                        ..Default::default()
+                    }),
+                )
+            }
+        };
+
+        let mut create_infos = vec![graphics_queue_info];
+
+        if let Some(compute_create_info) = compute_queue_info {
+            create_infos.push(compute_create_info);
+        }
+
+        let mut enabled_extensions = if physical_device.api_version() < vk::Version::V1_3 {
+            extensions.union(extensions_lt_1_3)
+        } else {
+            *extensions
+        };
+        // Optional, for reporting memory usage in diagnostics. Queried through a 1.1 entry point.
+        enabled_extensions.ext_memory_budget = physical_device.api_version() >= vk::Version::V1_1
+            && physical_device.supported_extensions().ext_memory_budget;
+
+        // Optional, for the wireframe visualization in diagnostics.
+        let fill_mode_non_solid = physical_device.supported_features().fill_mode_non_solid;
+        let (device, mut queues) = vk::Device::new(
+            physical_device,
+            vk::DeviceCreateInfo {
+                enabled_extensions,
+                enabled_features: vk::Features {
+                    dual_src_blend: true,
+                    dynamic_rendering: true,
+                    multi_draw_indirect: true,
+                    maintenance4: true,
+                    geometry_shader: true,
+                    fill_mode_non_solid,
+                    ..vk::Features::empty()
+                },
+                queue_create_infos: create_infos,
+                ..Default::default()
+            },
+        )?;
+
+        // Queues will be in order of create info, so this depends on the layout created by the `if` statements above.
+        let graphics_queue = Queue {
+            queue: queues.next().unwrap(),
+            family_idx: queue_indices.graphics,
+        };
+        let compute_queue = if has_unique_compute_queue {
+            QueueSrc::Queue(Queue {
+                queue: queues.next().unwrap(),
+                family_idx: queue_indices.compute,
+            })
+        } else {
+            QueueSrc::UseGraphics
+        };
+
+        assert!(queues.next().is_none());
+
+        Ok((
+            device,
+            Queues {
+                graphics_queue,
+                has_present: queue_indices.graphics_can_present,
+                compute_queue,
+            },
+        ))
+    }
+    /// Find a device that fits our needs, including the ability to present to the surface if in non-headless mode.
+    /// Horrible signature - Returns Ok(None) if no device found, Ok(Some((device, queue indices))) if suitable device found.
+    fn choose_physical_device(
+        instance: &Arc<vk::Instance>,
+        required_extensions: &vk::DeviceExtensions,
+        required_extensions_lt_1_3: &vk::DeviceExtensions,
+        compatible_surface: Option<&vk::Surface>,
+        preferred: Option<&PreferredDevice>,
+    ) -> AnyResult<Option<(Arc<vk::PhysicalDevice>, QueueIndices)>> {
+        //TODO: does not respect queue family max queue counts. This will need to be redone in some sort of
+        //multi-pass shenanigan to properly find a good queue setup. Also requires that graphics and compute queues be transfer as well.
+        let devices: Vec<_> = instance.enumerate_physical_devices()?.collect();
+        let mut suitable = devices
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, device)| {
+                use vk::QueueFlags;
+                let required_extensions = if device.api_version() < vk::Version::V1_3 {
+                    required_extensions.union(required_extensions_lt_1_3)
+                } else {
+                    *required_extensions
+                };
+                //Make sure it has what we need
+                if !device.supported_extensions().contains(&required_extensions) {
+                    return None;
+                }
+
+                let families = device.queue_family_properties();
+
+                //Find a queue that supports the requested surface, if any
+                let present_queue = compatible_surface.and_then(|surface| {
+                    families.iter().enumerate().find(|(family_idx, _)| {
+                        //Assume error is false. Todo?
+                        device
+                            .surface_support(*family_idx as u32, surface)
+                            .unwrap_or(false)
+                    })
+                });
+
+                //We needed a present queue, but none was found. Disqualify this device!
+                if compatible_surface.is_some() && present_queue.is_none() {
+                    return None;
+                }
+
+                // We need a graphics queue, always! Otherwise, disqualify.
+                // If we require present to the surface, ensure that this queue can also do it.
+                let graphics_queue = families.iter().enumerate().find(|q| {
+                    let is_graphics = q.1.queue_flags.contains(QueueFlags::GRAPHICS);
+                    let can_present = compatible_surface.is_none()
+                        || device
+                            .surface_support(q.0 as u32, compatible_surface.unwrap())
+                            .unwrap_or(false);
+                    is_graphics && can_present
+                })?;
+
+                //We need a compute queue. This can be the same as graphics, but preferably not.
+                let graphics_supports_compute =
+                    graphics_queue.1.queue_flags.contains(QueueFlags::COMPUTE);
+
+                //Find a different queue that supports compute
+                let compute_queue = families
+                    .iter()
+                    .enumerate()
+                    //Ignore the family we chose for graphics
+                    .filter(|&(idx, _)| idx != graphics_queue.0)
+                    .find(|q| q.1.queue_flags.contains(QueueFlags::COMPUTE));
+
+                //Failed to find compute queue, shared or otherwise. Disqualify!
+                if !graphics_supports_compute && compute_queue.is_none() {
+                    return None;
+                }
+
+                Some((
+                    idx,
+                    device.clone(),
+                    QueueIndices {
+                        compute: compute_queue.unwrap_or(graphics_queue).0 as u32,
+                        // Would have bailed if not!
+                        graphics_can_present: compatible_surface.is_some(),
+                        graphics: graphics_queue.0 as u32,
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let _ = AVAILABLE_DEVICES.set(
+            devices
+                .iter()
+                .enumerate()
+                .map(|(idx, device)| PhysicalDeviceInfo {
+                    name: device.properties().device_name.clone(),
+                    device_type: device.properties().device_type,
+                    suitable: suitable.iter().any(|&(this, ..)| this == idx),
+                })
+                .collect(),
+        );
+
+        if let Some(preferred) = preferred {
+            let pos = suitable
+                .iter()
+                .position(|(idx, device, _)| match preferred {
+                    PreferredDevice::Index(preferred) => idx == preferred,
+                    PreferredDevice::Name(name) => device.properties().device_name == *name,
+                });
+            if let Some(pos) = pos {
+                let (_, device, queues) = suitable.swap_remove(pos);
+                return Ok(Some((device, queues)));
+            }
+            let message = format!(
+                "The selected graphics device {preferred} is missing or unsupported. Another was chosen automatically."
+            );
+            tracing::warn!("{message}");
+            let _ = DEVICE_FALLBACK.set(message);
+        }
+
+        let res = suitable
+            .into_iter()
+            .map(|(_, device, queues)| (device, queues))
+            .min_by_key(|(device, _)| {
+                use vk::PhysicalDeviceType;
+                match device.properties().device_type {
+                    PhysicalDeviceType::DiscreteGpu => 0,
+                    PhysicalDeviceType::IntegratedGpu => 1,
+                    PhysicalDeviceType::VirtualGpu => 2,
+
+                    _ => 3,
+                }
+            });
+
+        Ok(res)
+    }
+    pub fn now(&self) -> vk::NowFuture {
+        vk::sync::now(self.device.clone())
+    }
+    pub fn physical_device(&self) -> &Arc<vk::PhysicalDevice> {
+        &self.physical_device
+    }
+    pub fn queues(&self) -> &Queues {
+        &self.queues
+    }
+    pub fn device(&self) -> &Arc<vk::Device> {
+        &self.device
+    }
+    pub fn allocators(&self) -> &Allocators {
+        &self.allocators
+    }
+    /// Copies from the device to the host, see [`readback`].
+    pub fn readback(&self) -> &readback::Readback {
+        &self.readback
+    }
+    pub fn high_level_limits(&self) -> &HighLevelLimits {
+        &self.high_level_limits
+    }
+    /// Human-readable summary of the device and driver, for bug reports.
+    #[must_use]
+    pub fn device_description(&self) -> String {
+        let properties = self.physical_device.properties();
+        format!(
+            "{} ({:?}), Vulkan {}, driver {} {} ({:#x})",
+            properties.device_name,
+            properties.device_type,
+            self.physical_device.api_version(),
+            properties.driver_name.as_deref().unwrap_or("[unknown]"),
+            properties.driver_info.as_deref().unwrap_or(""),
+            properties.driver_version,
+        )
+    }
+    /// Query the usage of each device-local memory heap. Usage and budget are only
+    /// reported if `VK_EXT_memory_budget` is available.
+    #[must_use]
+    pub fn device_local_memory(&self) -> Vec<HeapUsage> {
+        let heaps = &self.physical_device.memory_properties().memory_heaps;
+        let mut usages: Vec<_> = heaps
+            .iter()
+            .map(|heap| HeapUsage {
+                size: heap.size,
+                usage: None,
+                budget: None,
+            })
+            .collect();
+
+        if self.device.enabled_extensions().ext_memory_budget {
+            use vulkano::VulkanObject;
+            let mut budget = ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+            {
+                let mut properties =
+                    ash::vk::PhysicalDeviceMemoryProperties2::builder().push_next(&mut budget);
+                // Safety: The extension is only enabled on 1.1 devices, and the chain is well-formed.
+                unsafe {
+                    (self
+                        .physical_device
+                        .instance()
+                        .fns()
+                        .v1_1
+                        .get_physical_device_memory_properties2)(
+                        self.physical_device.handle(),
+                        &mut *properties,
+                    );
+                }
+            }
+            for (usage, (&used, &available)) in usages
+                .iter_mut()
+                .zip(budget.heap_usage.iter().zip(&budget.heap_budget))
+            {
+                usage.usage = Some(used);
+                usage.budget = Some(available);
+            }
+        }
+
+        usages
+            .into_iter()
+            .zip(heaps)
+            .filter(|(_, heap)| {
+                heap.flags
+                    .intersects(vulkano::memory::MemoryHeapFlags::DEVICE_LOCAL)
+            })
+            .map(|(usage, _)| usage)
+            .collect()
+    }
+}
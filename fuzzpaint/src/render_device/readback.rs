@@ -0,0 +1,275 @@
+//! # Readback
+//!
+//! Copies from device images into host memory, for the color picker, export, thumbnails, and the like.
+//! Requests from any thread are gathered into batches, each copied by a single command buffer, and answered
+//! through a [oneshot](tokio::sync::oneshot) channel that may be awaited or blocked on.
+//!
+//! Two staging buffers are taken in turn, so that the device copies one batch while the host reads out the
+//! one before - neither waits on the other.
+
+use crate::vulkano_prelude::*;
+use std::sync::Arc;
+
+/// Staging offsets are aligned to this, which satisfies every uncompressed format.
+const ALIGN: vk::DeviceSize = 16;
+
+/// An area of one layer of an image to read.
+#[derive(Clone)]
+pub struct Request {
+    pub image: Arc<vk::Image>,
+    pub array_layer: u32,
+    pub mip_level: u32,
+    /// Within the mip level.
+    pub offset: [u32; 2],
+    pub extent: [u32; 2],
+}
+impl Request {
+    /// The whole of the first layer.
+    #[must_use]
+    pub fn whole(image: Arc<vk::Image>) -> Self {
+        let [width, height, _] = image.extent();
+        Self {
+            image,
+            array_layer: 0,
+            mip_level: 0,
+            offset: [0; 2],
+            extent: [width, height],
+        }
+    }
+    /// Bytes of the packed texels, or `None` if the format isn't one texel per block.
+    fn len(&self) -> Option<vk::DeviceSize> {
+        let format = self.image.format();
+        if format.block_extent() != [1, 1, 1] {
+            return None;
+        }
+        format
+            .block_size()
+            .checked_mul(self.extent[0].into())?
+            .checked_mul(self.extent[1].into())
+    }
+}
+
+/// The packed texels of a [`Request`], row by row.
+pub type Response = anyhow::Result<Vec<u8>>;
+type Pending = (Request, tokio::sync::oneshot::Sender<Response>);
+
+/// Shared by everything that reads from the device, see the [module docs](self).
+pub struct Readback {
+    /// Hung up on drop, stopping the worker once it has answered what was already asked.
+    send: crossbeam::channel::Sender<Pending>,
+}
+impl Readback {
+    pub(super) fn new(
+        device: Arc<vk::Device>,
+        queue: Arc<vk::Queue>,
+        memory: Arc<dyn vulkano::memory::allocator::MemoryAllocator>,
+    ) -> anyhow::Result<Self> {
+        let (send, recv) = crossbeam::channel::unbounded();
+        let worker = Worker {
+            command_buffers: vk::StandardCommandBufferAllocator::new(
+                device.clone(),
+                Default::default(),
+            ),
+            device,
+            queue,
+            memory,
+            staging: [None, None],
+        };
+        std::thread::Builder::new()
+            .name("Readback worker".to_owned())
+            .spawn(move || worker.run(&recv))?;
+        Ok(Self { send })
+    }
+    /// Copy an area of an image into host memory.
+    ///
+    /// The copy happens some time after this call, along with any others asked for meanwhile. Writes to the
+    /// image must be complete by then, and none may be submitted until the response arrives.
+    pub fn download(&self, request: Request) -> tokio::sync::oneshot::Receiver<Response> {
+        let (send, recv) = tokio::sync::oneshot::channel();
+        if let Err(crossbeam::channel::SendError((_, send))) = self.send.send((request, send)) {
+            let _ = send.send(Err(anyhow::anyhow!("readback worker stopped")));
+        }
+        recv
+    }
+}
+
+/// A batch submitted to the device, to be answered once complete.
+struct Batch {
+    fence: vk::FenceSignalFuture<Box<dyn GpuFuture + Send>>,
+    staging: vk::Subbuffer<[u8]>,
+    /// Where in `staging` each answer lies.
+    answers: Vec<(
+        std::ops::Range<vk::DeviceSize>,
+        tokio::sync::oneshot::Sender<Response>,
+    )>,
+}
+impl Batch {
+    fn answer(self) {
+        let Self {
+            fence,
+            staging,
+            answers,
+        } = self;
+        let result = fence.wait(None);
+        // Release the staging buffer to the host.
+        drop(fence);
+        let read = result
+            .map_err(anyhow::Error::from)
+            .and_then(|()| Ok(staging.read()?));
+        match read {
+            Ok(read) => {
+                for (range, send) in answers {
+                    // Unwrap ok - the range was allocated within staging, which is host-addressable.
+                    #[allow(clippy::cast_possible_truncation)]
+                    let bytes = read
+                        .get(range.start as usize..range.end as usize)
+                        .unwrap()
+                        .to_vec();
+                    // Nobody waiting, no matter.
+                    let _ = send.send(Ok(bytes));
+                }
+            }
+            Err(e) => {
+                for (_, send) in answers {
+                    let _ = send.send(Err(anyhow::anyhow!("readback failed: {e:#}")));
+                }
+            }
+        }
+    }
+}
+
+struct Worker {
+    device: Arc<vk::Device>,
+    queue: Arc<vk::Queue>,
+    memory: Arc<dyn vulkano::memory::allocator::MemoryAllocator>,
+    command_buffers: vk::StandardCommandBufferAllocator,
+    /// Taken in turn by each batch, grown as needed.
+    staging: [Option<vk::Subbuffer<[u8]>>; 2],
+}
+impl Worker {
+    fn run(mut self, recv: &crossbeam::channel::Receiver<Pending>) {
+        let mut slot = 0;
+        let mut in_flight: Option<Batch> = None;
+        loop {
+            let mut pending = Vec::new();
+            if in_flight.is_none() {
+                // Idle, sleep until asked. Closed once the context is gone.
+                let Ok(first) = recv.recv() else {
+                    return;
+                };
+                pending.push(first);
+            }
+            // Everything that piled up meanwhile goes in one batch.
+            pending.extend(recv.try_iter());
+
+            // Submit the next into the other buffer before reading out the last, so the device keeps busy.
+            let submitted = if pending.is_empty() {
+                None
+            } else {
+                let batch = self.submit(slot, pending);
+                slot ^= 1;
+                batch
+            };
+            if let Some(batch) = in_flight.take() {
+                batch.answer();
+            }
+            in_flight = submitted;
+        }
+    }
+    /// Record and submit copies for every request into the given staging buffer. Requests that failed are
+    /// answered immediately, `None` if none remain.
+    fn submit(&mut self, slot: usize, pending: Vec<Pending>) -> Option<Batch> {
+        let mut len: vk::DeviceSize = 0;
+        let mut requests = Vec::with_capacity(pending.len());
+        for (request, send) in pending {
+            let Some(request_len) = request.len() else {
+                let _ = send.send(Err(anyhow::anyhow!(
+                    "can't read back {:?} texels",
+                    request.image.format()
+                )));
+                continue;
+            };
+            let start = len.next_multiple_of(ALIGN);
+            len = start + request_len;
+            requests.push((request, start..len, send));
+        }
+        if requests.is_empty() {
+            return None;
+        }
+
+        // Buffers can't be empty, even if every request is.
+        let result = self.staging(slot, len.max(1)).and_then(|staging| {
+            let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
+                &self.command_buffers,
+                self.queue.queue_family_index(),
+                vk::CommandBufferUsage::OneTimeSubmit,
+            )?;
+            for (request, range, _) in &requests {
+                command_buffer.copy_image_to_buffer(vk::CopyImageToBufferInfo {
+                    regions: smallvec::smallvec![vk::BufferImageCopy {
+                        buffer_offset: range.start,
+                        image_subresource: vk::ImageSubresourceLayers {
+                            aspects: vk::ImageAspects::COLOR,
+                            mip_level: request.mip_level,
+                            array_layers: request.array_layer..request.array_layer + 1,
+                        },
+                        image_offset: [request.offset[0], request.offset[1], 0],
+                        image_extent: [request.extent[0], request.extent[1], 1],
+                        // Rows left at zero - packed tightly.
+                        ..Default::default()
+                    }],
+                    ..vk::CopyImageToBufferInfo::image_buffer(
+                        request.image.clone(),
+                        staging.clone(),
+                    )
+                })?;
+            }
+            let command_buffer = command_buffer.build()?;
+            let fence = vk::sync::now(self.device.clone())
+                .then_execute(self.queue.clone(), command_buffer)?
+                .boxed_send()
+                .then_signal_fence_and_flush()?;
+            anyhow::Ok((fence, staging))
+        });
+
+        match result {
+            Ok((fence, staging)) => Some(Batch {
+                fence,
+                staging,
+                answers: requests
+                    .into_iter()
+                    .map(|(_, range, send)| (range, send))
+                    .collect(),
+            }),
+            Err(e) => {
+                for (_, _, send) in requests {
+                    let _ = send.send(Err(anyhow::anyhow!("readback failed: {e:#}")));
+                }
+                None
+            }
+        }
+    }
+    /// The staging buffer of `slot`, at least `len` bytes.
+    fn staging(&mut self, slot: usize, len: vk::DeviceSize) -> anyhow::Result<vk::Subbuffer<[u8]>> {
+        if let Some(staging) = &self.staging[slot] {
+            if staging.len() >= len {
+                return Ok(staging.clone());
+            }
+        }
+        let staging = vk::Buffer::new_slice::<u8>(
+            self.memory.clone(),
+            vk::BufferCreateInfo {
+                usage: vk::BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            vk::AllocationCreateInfo {
+                memory_type_filter: vk::MemoryTypeFilter::HOST_RANDOM_ACCESS
+                    | vk::MemoryTypeFilter::PREFER_HOST,
+                ..Default::default()
+            },
+            len,
+        )?;
+        self.staging[slot] = Some(staging.clone());
+        Ok(staging)
+    }
+}
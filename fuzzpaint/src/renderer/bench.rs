@@ -0,0 +1,300 @@
+//! # Benchmark
+//!
+//! Renders a procedurally generated document and reports how long each part took, so that changes to the
+//! render pipeline can be checked for regressions. Run with `--bench`, see [`crate::args`].
+
+use super::Renderer;
+use fuzzpaint_core::{
+    queue::DocumentCommandQueue,
+    state::{self, graph},
+    stroke::{Archetype, StrokeSlice},
+};
+use std::{sync::Arc, time::Duration};
+
+/// Shape of the generated document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Strokes in the document, spread evenly across the layers.
+    pub strokes: usize,
+    pub layers: usize,
+    /// Points in each stroke.
+    pub points: usize,
+    /// How many single-stroke edits to time after the initial render.
+    pub frames: usize,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            strokes: 10_000,
+            layers: 10,
+            points: 128,
+            frames: 60,
+        }
+    }
+}
+impl Config {
+    /// Set a field from a `key=value` pair. False if the key is unknown or the value isn't a count.
+    #[must_use]
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let field = match key {
+            "strokes" => &mut self.strokes,
+            "layers" => &mut self.layers,
+            "points" => &mut self.points,
+            "frames" => &mut self.frames,
+            _ => return false,
+        };
+        match value.parse() {
+            Ok(value) => {
+                *field = value;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Timings of one edit, from the stroke being added until the document image is composited.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    total: Duration,
+    /// Wall time of tessellating and drawing the new stroke on the GPU's clock, if timestamps are supported.
+    tessellation: Option<Duration>,
+    /// GPU time of blending the layers, if timestamps are supported.
+    composite: Option<Duration>,
+}
+
+pub struct Report {
+    config: Config,
+    /// Time to generate the document's points on the CPU.
+    generate: Duration,
+    /// Time to render the whole document from scratch.
+    initial: Duration,
+    frames: Vec<Frame>,
+}
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Min, median, max, or None if there are no samples.
+        fn summarize(mut samples: Vec<Duration>) -> Option<[Duration; 3]> {
+            samples.sort_unstable();
+            Some([
+                *samples.first()?,
+                samples[samples.len() / 2],
+                *samples.last()?,
+            ])
+        }
+        let Config {
+            strokes,
+            layers,
+            points,
+            frames,
+        } = self.config;
+        writeln!(
+            f,
+            "{strokes} strokes of {points} points across {layers} layers, {frames} frames"
+        )?;
+        writeln!(f, "generate: {:.2?}", self.generate)?;
+        writeln!(f, "initial render: {:.2?}", self.initial)?;
+
+        let rows: [(&str, fn(&Frame) -> Option<Duration>); 3] = [
+            ("frame", |frame| Some(frame.total)),
+            ("tessellation (gpu wall)", |frame| frame.tessellation),
+            ("composite (gpu)", |frame| frame.composite),
+        ];
+        for (name, get) in rows {
+            match summarize(self.frames.iter().filter_map(get).collect()) {
+                Some([min, median, max]) => writeln!(
+                    f,
+                    "{name}: min {min:.2?}, median {median:.2?}, max {max:.2?}"
+                )?,
+                None => writeln!(f, "{name}: not measured")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tiny deterministic noise, so that runs are comparable.
+struct XorShift(u32);
+impl XorShift {
+    /// In `[0, 1)`
+    // Top 24 bits, exactly representable.
+    #[allow(clippy::cast_precision_loss)]
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Generate a looping scribble somewhere on the document, and store it.
+fn scribble(
+    rand: &mut XorShift,
+    points: usize,
+) -> anyhow::Result<fuzzpaint_core::repositories::points::PointCollectionID> {
+    #[allow(clippy::cast_precision_loss)]
+    let dimension = crate::DOCUMENT_DIMENSION as f32;
+    let center = [rand.next_f32() * dimension, rand.next_f32() * dimension];
+    let radius = rand.next_f32() * dimension / 8.0 + 4.0;
+    let turns = rand.next_f32() * 4.0 + 1.0;
+
+    let archetype = Archetype::POSITION | Archetype::ARC_LENGTH | Archetype::PRESSURE;
+    let mut elements = Vec::with_capacity(points * archetype.elements());
+    let mut arc_length = 0.0f32;
+    let mut last = None::<[f32; 2]>;
+    for idx in 0..points {
+        #[allow(clippy::cast_precision_loss)]
+        let t = idx as f32 / points.max(2).saturating_sub(1) as f32;
+        let angle = t * turns * std::f32::consts::TAU;
+        let position = [
+            center[0] + angle.cos() * radius * (1.0 - t * 0.5),
+            center[1] + angle.sin() * radius,
+        ];
+        if let Some(last) = last.replace(position) {
+            arc_length +=
+                ((position[0] - last[0]).powi(2) + (position[1] - last[1]).powi(2)).sqrt();
+        }
+        // Lift off towards the end, like a real stroke.
+        let pressure = 1.0 - t * t;
+        elements.extend([position[0], position[1], arc_length, pressure].map(f32::to_bits));
+    }
+    // Unwrap ok - packed to exactly this archetype.
+    let slice = StrokeSlice::new(&elements, archetype).unwrap();
+    crate::global::points()
+        .insert(slice)
+        .ok_or_else(|| anyhow::anyhow!("stroke data too large"))
+}
+
+fn brush() -> state::StrokeBrushSettings {
+    state::StrokeBrushSettings {
+        is_eraser: false,
+        is_smudge: false,
+        brush: fuzzpaint_core::brush::UniqueID([0; 32]),
+        color_modulate: fuzzpaint_core::color::ColorOrPalette::BLACK,
+        size_mul: fuzzpaint_core::util::FiniteF32::new(12.0).unwrap(),
+        orientation: state::StampOrientation::Random,
+        scatter: state::Scatter::default(),
+        dual: None,
+        taper: state::Taper::default(),
+        dynamics: state::Dynamics::default(),
+        spacing_px: fuzzpaint_core::util::FiniteF32::new(0.5).unwrap(),
+    }
+}
+
+/// Build the document described by the config, returning it along with the collection of each layer.
+fn generate(
+    config: &Config,
+) -> anyhow::Result<(
+    DocumentCommandQueue,
+    Vec<state::stroke_collection::StrokeCollectionID>,
+)> {
+    let layers = config.layers.max(1);
+    let mut rand = XorShift(0x2545_F491);
+    let mut graph = graph::BlendGraph::default();
+    let mut stroke_state = state::stroke_collection::StrokeCollectionState::default();
+    let mut collections = Vec::with_capacity(layers);
+
+    for layer in 0..layers {
+        let collection = crate::FuzzID::default();
+        stroke_state.0.insert(
+            collection,
+            state::stroke_collection::StrokeCollection::default(),
+        );
+        graph.add_leaf(
+            graph::Location::IndexIntoRoot(0),
+            format!("Bench {layer}"),
+            graph::LeafType::StrokeLayer {
+                blend: fuzzpaint_core::blend::Blend::default(),
+                collection,
+                inner_transform: state::transform::Similarity::default(),
+                outer_transform: state::transform::Matrix::default(),
+            },
+        )?;
+        collections.push(collection);
+    }
+
+    let queue = DocumentCommandQueue::from_state(
+        state::document::Document {
+            name: "Benchmark".to_owned(),
+            ..Default::default()
+        },
+        graph,
+        stroke_state,
+        state::palette::Palette::default(),
+    );
+    queue.write_with(|writer| {
+        let mut stroke_collections = writer.stroke_collections();
+        for (layer, &collection) in collections.iter().enumerate() {
+            // Unwrap ok - inserted above.
+            let mut collection = stroke_collections.get_mut(collection).unwrap();
+            // Spread the remainder over the first layers.
+            let count = config.strokes / layers + usize::from(layer < config.strokes % layers);
+            for _ in 0..count {
+                collection.push_back(brush(), scribble(&mut rand, config.points)?, None);
+            }
+        }
+        anyhow::Ok(())
+    })?;
+    Ok((queue, collections))
+}
+
+/// Generate the document, render it from scratch, then time adding strokes to it one at a time.
+pub fn run(
+    context: Arc<crate::render_device::RenderContext>,
+    config: &Config,
+) -> anyhow::Result<Report> {
+    use crate::diagnostics::{self, GpuPass};
+    // Gpu timers are only taken while enabled, and waiting on them makes each render synchronous.
+    diagnostics::set_enabled(true);
+
+    let start = std::time::Instant::now();
+    let (queue, collections) = generate(config)?;
+    let generate = start.elapsed();
+
+    let id = queue.id();
+    crate::global::provider()
+        .insert(queue)
+        .map_err(|_| anyhow::anyhow!("benchmark document already exists"))?;
+    let mut renderer = Renderer::new(context)?;
+
+    let start = std::time::Instant::now();
+    let _ = renderer.update_one(id, None)?;
+    let initial = start.elapsed();
+
+    let mut rand = XorShift(0x9E37_79B9);
+    let mut frames = Vec::with_capacity(config.frames);
+    for frame in 0..config.frames {
+        let collection = collections[frame % collections.len()];
+        let points = scribble(&mut rand, config.points)?;
+
+        let start = std::time::Instant::now();
+        crate::global::provider()
+            .write(id, "bench", |queue| {
+                queue.write_with(|writer| {
+                    let mut collections = writer.stroke_collections();
+                    let Some(mut collection) = collections.get_mut(collection) else {
+                        anyhow::bail!("benchmark layer is missing");
+                    };
+                    collection.push_back(brush(), points, None);
+                    Ok(())
+                })
+            })
+            .ok_or_else(|| anyhow::anyhow!("benchmark document closed"))??;
+        let _ = renderer.update_one(id, None)?;
+        let total = start.elapsed();
+
+        let timings = diagnostics::timings().read();
+        frames.push(Frame {
+            total,
+            tessellation: timings.gpu(GpuPass::Tessellation),
+            composite: timings.gpu(GpuPass::Composite),
+        });
+    }
+
+    Ok(Report {
+        config: *config,
+        generate,
+        initial,
+        frames,
+    })
+}
@@ -16,7 +16,15 @@ macro_rules! blend_noclip {
             ],
             path: "src/shaders/blend_no_clip.frag",
         }
-        crate::renderer::blender::BlendLogic::Arbitrary(load)
+        const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source {
+                defines: &[("EXPR", $blend_expr)],
+                ..crate::renderer::shader_reload::Source::new(
+                    "src/shaders/blend_no_clip.frag",
+                    crate::renderer::shader_reload::Kind::Fragment,
+                )
+            };
+        crate::renderer::blender::BlendLogic::Arbitrary(load, &SOURCE)
     }};
 }
 /// Providing a "quoted" GLSL snippit, accepting an opaque RGBA `vec4 c_src` and premultiplied `vec4 c_dst`,
@@ -31,30 +39,32 @@ macro_rules! blend_clip {
             ],
             path: "src/shaders/blend_clip.frag",
         }
-        crate::renderer::blender::BlendLogic::Arbitrary(load)
+        const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source {
+                defines: &[("EXPR", $blend_expr)],
+                ..crate::renderer::shader_reload::Source::new(
+                    "src/shaders/blend_clip.frag",
+                    crate::renderer::shader_reload::Kind::Fragment,
+                )
+            };
+        crate::renderer::blender::BlendLogic::Arbitrary(load, &SOURCE)
     }};
 }
 
-/// `fn` to load a shader module on a given device. Equivalent to the signature of the vulkano-generated `<shader>::load`.
-type BlendLoader =
-    fn(Arc<vk::Device>) -> Result<Arc<vk::ShaderModule>, vk::Validated<vk::VulkanError>>;
-
 enum BlendLogic {
     /// The blend can be represented as a standard blend function. Hardware accelerated, pipelinable, and coherent. Nice :3
     Simple(vk::AttachmentBlend),
     /// Provide a Load function for a shader to compute `A (+) B` for arbitrary blend logic. Still pipeliend, but noncoherent 3:
-    Arbitrary(BlendLoader),
+    Arbitrary(
+        super::shader_reload::Loader,
+        &'static super::shader_reload::Source,
+    ),
 }
 impl From<vk::AttachmentBlend> for BlendLogic {
     fn from(value: vk::AttachmentBlend) -> Self {
         Self::Simple(value)
     }
 }
-impl From<BlendLoader> for BlendLogic {
-    fn from(value: BlendLoader) -> Self {
-        Self::Arbitrary(value)
-    }
-}
 impl BlendLogic {
     /// Get the logic needed to perform a blend.
     fn of(blend: BlendMode, clip: bool) -> Self {
@@ -291,6 +301,8 @@ pub struct NestedBlendInvocation {
     operations: Vec<(BlendImageSource, Blend)>,
     clear_destination: bool,
     destination_image: Arc<vk::ImageView>,
+    /// Applied to the destination after all operations.
+    filter: Option<fuzzpaint_core::filter::Filter>,
 }
 /// Source for a blend operation.
 pub enum BlendImageSource {
@@ -392,6 +404,9 @@ pub struct BlendInvocationBuilder {
     // Top of list = first operation.
     // Invariant - none if the (perhaps nested) image memory aliases the `destination_image`
     operations: Vec<(BlendImageSource, Blend)>,
+    filter: Option<fuzzpaint_core::filter::Filter>,
+    /// See [`Self::filter_scale`].
+    filter_scale: f32,
 }
 impl BlendInvocationBuilder {
     /// Blend the given image onto the cumulative results of all previous blend operations.
@@ -412,6 +427,17 @@ impl BlendInvocationBuilder {
     pub fn reverse(&mut self) {
         self.operations.reverse();
     }
+    /// Apply a filter to the destination once all blend operations are complete.
+    /// The destination must have `STORAGE` usage.
+    pub fn filter(&mut self, filter: fuzzpaint_core::filter::Filter) {
+        self.filter = Some(filter);
+    }
+    /// Set the texels of the destination's full resolution per document pixel, which filters measured in
+    /// document pixels are scaled by, see [`crate::document_viewport_proxy::DocumentRegion::scale`]. Only that
+    /// of the builder that's built counts, and applies to every nested invocation. Defaults to one.
+    pub fn filter_scale(&mut self, scale: f32) {
+        self.filter_scale = scale;
+    }
     /// Build the invocation. This handle can be used in other blend invocations as a source,
     /// or it may be provided to [`BlendEngine::submit`] to begin device execution of the blend operation.
     ///
@@ -423,6 +449,7 @@ impl BlendInvocationBuilder {
             operations: self.operations,
             clear_destination: self.clear_destination,
             destination_image: self.destination_image,
+            filter: self.filter,
         }
     }
     /// Compile all the blend operations into an executable form. This is a costly operation, and the
@@ -433,10 +460,12 @@ impl BlendInvocationBuilder {
     pub fn build(self) -> anyhow::Result<BlendInvocation> {
         BlendInvocation::compile(
             self.engine,
+            self.filter_scale,
             NestedBlendInvocation {
                 operations: self.operations,
                 clear_destination: self.clear_destination,
                 destination_image: self.destination_image,
+                filter: self.filter,
             },
         )
     }
@@ -450,7 +479,11 @@ pub struct BlendInvocation {
     commands: Vec<Vec<Arc<vk::PrimaryAutoCommandBuffer>>>,
 }
 impl BlendInvocation {
-    fn compile(engine: Arc<BlendEngine>, from: NestedBlendInvocation) -> anyhow::Result<Self> {
+    fn compile(
+        engine: Arc<BlendEngine>,
+        filter_scale: f32,
+        from: NestedBlendInvocation,
+    ) -> anyhow::Result<Self> {
         // First, Collect resources recursively.
         fn recurse(
             engine: &BlendEngine,
@@ -474,7 +507,7 @@ impl BlendInvocation {
                         engine.feedback_pass.clone(),
                         vk::FramebufferCreateInfo {
                             attachments: vec![dest.clone()],
-                            extent: [dest.image().extent()[0], dest.image().extent()[1]],
+                            extent: super::view_extent(dest),
                             ..Default::default()
                         },
                     )?,
@@ -643,6 +676,7 @@ impl BlendInvocation {
                     // Compile the task into a command buffer.
                     let res = Self::compile_nonrecurse(
                         &engine,
+                        filter_scale,
                         &task,
                         &pipes,
                         &framebuffers,
@@ -678,6 +712,7 @@ impl BlendInvocation {
     /// Assumes pipes, framebuffers, and descriptors are completely filled.
     fn compile_nonrecurse(
         engine: &BlendEngine,
+        filter_scale: f32,
         op: &NestedBlendInvocation,
         pipes: &hashbrown::HashMap<(BlendMode, bool), CompiledBlend>,
         framebuffers: &hashbrown::HashMap<
@@ -711,6 +746,8 @@ impl BlendInvocation {
 
         let (framebuffer, feedback_descriptor) =
             framebuffers.get(&op.destination_image.handle()).unwrap();
+        // Of the level being blended into, smaller when zoomed out.
+        let extent = super::view_extent(&op.destination_image);
 
         let render_pass_begin = vk::RenderPassBeginInfo {
             render_pass: engine.feedback_pass.clone(),
@@ -726,10 +763,7 @@ impl BlendInvocation {
                 smallvec::smallvec![vk::Viewport {
                     depth_range: 0.0..=1.0,
                     offset: [0.0; 2],
-                    extent: [
-                        op.destination_image.image().extent()[0] as f32,
-                        op.destination_image.image().extent()[1] as f32,
-                    ],
+                    extent: [extent[0] as f32, extent[1] as f32],
                 }],
             )?;
 
@@ -755,10 +789,7 @@ impl BlendInvocation {
                 }],
                 smallvec::smallvec![vulkano::command_buffer::ClearRect {
                     offset: [0; 2],
-                    extent: [
-                        op.destination_image.image().extent()[0],
-                        op.destination_image.image().extent()[1],
-                    ],
+                    extent,
                     array_layers: 0..1,
                 }],
             )?;
@@ -853,6 +884,12 @@ impl BlendInvocation {
             had_write = true;
         }
         commands.end_render_pass(vk::SubpassEndInfo::default())?;
+        // An empty destination is unaffected by any filter, so this is skipped above.
+        if let Some(filter) = op.filter.filter(|filter| !filter.is_identity()) {
+            engine
+                .filters
+                .record(&mut commands, &op.destination_image, filter, filter_scale)?;
+        }
         Ok(commands.build()?)
     }
     /// Execute the entire blend tree.
@@ -913,6 +950,7 @@ pub struct BlendEngine {
     /// Vulkan requires that any desciptor set that is statically-used be in a fully valid state, even if it is
     /// dynamically unused. Use this for such cases.
     dummy_image_descriptor: Arc<vk::PersistentDescriptorSet>,
+    filters: super::filter::FilterEngine,
 }
 impl BlendEngine {
     /// Compile the blend logic for a given mode. Does *not* access the mode cache or check if it was already compiled.
@@ -956,10 +994,11 @@ impl BlendEngine {
 
                 Ok(CompiledBlend::SimpleCoherent(pipe))
             }
-            BlendLogic::Arbitrary(load) => {
-                let shader = load(self.context.device().clone())?
-                    .entry_point("main")
-                    .ok_or_else(|| anyhow::anyhow!("entry point `main` not found"))?;
+            BlendLogic::Arbitrary(load, source) => {
+                let shader =
+                    super::shader_reload::load(self.context.device().clone(), source, load)?
+                        .entry_point("main")
+                        .ok_or_else(|| anyhow::anyhow!("entry point `main` not found"))?;
 
                 let pipe = vk::GraphicsPipeline::new(
                     self.context.device().clone(),
@@ -1160,6 +1199,7 @@ impl BlendEngine {
             Self::make_dummy_image_descriptor(&context, feedback_layout.set_layouts()[0].clone())?;
 
         Ok(Self {
+            filters: super::filter::FilterEngine::new(context.clone())?,
             context,
             feedback_layout,
             fullscreen_vert,
@@ -1182,6 +1222,8 @@ impl BlendEngine {
             clear_destination,
             destination_image,
             operations: Vec::new(),
+            filter: None,
+            filter_scale: 1.0,
         }
     }
 }
@@ -0,0 +1,225 @@
+//! Compute passes comparing document images tile-by-tile:
+//! * Highlighting what changed since the document was last saved, by tinting the tiles of the preview that
+//!   differ from a render of its saved state.
+//! * Finding which tiles changed since the document was last presented, so that the viewport only redraws
+//!   when something visible changed. See [`crate::document_viewport_proxy::DirtyTiles`].
+
+use crate::vulkano_prelude::*;
+use std::sync::Arc;
+
+mod shaders {
+    pub mod saved {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/shaders/saved_diff.comp",
+        }
+    }
+    pub mod dirty {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/shaders/dirty_tiles.comp",
+        }
+    }
+}
+
+/// `local_size` of the saved shader in X and Y, and so the size of each highlighted tile.
+const SAVED_TILE_SIZE: u32 = 16;
+/// `local_size` of the dirty shader in X and Y.
+const DIRTY_WORKGROUP_SIZE: u32 = 8;
+/// Most masks kept for reuse. One per present in flight, and then some.
+const MASK_POOL: usize = 4;
+
+pub struct DiffEngine {
+    context: Arc<crate::render_device::RenderContext>,
+    /// Set 0: storage images of the present document, the saved document, and the destination.
+    layout: Arc<vk::PipelineLayout>,
+    pipeline: Arc<vk::ComputePipeline>,
+    /// Set 0: storage images of the present and previous document, and a storage buffer of tile flags.
+    dirty_layout: Arc<vk::PipelineLayout>,
+    dirty: Arc<vk::ComputePipeline>,
+    /// Masks handed out by [`Self::dirty_mask`], reused once nothing else holds them.
+    masks: parking_lot::Mutex<Vec<vk::Subbuffer<[u32]>>>,
+}
+impl DiffEngine {
+    /// Make a layout of the given descriptor types in set 0, in order.
+    fn make_layout(
+        device: Arc<vk::Device>,
+        bindings: &[vk::DescriptorType],
+    ) -> anyhow::Result<Arc<vk::PipelineLayout>> {
+        let set = vk::DescriptorSetLayout::new(
+            device.clone(),
+            vk::DescriptorSetLayoutCreateInfo {
+                bindings: bindings
+                    .iter()
+                    .zip(0..)
+                    .map(|(&ty, binding)| {
+                        (
+                            binding,
+                            vk::DescriptorSetLayoutBinding {
+                                descriptor_count: 1,
+                                stages: vk::ShaderStages::COMPUTE,
+                                ..vk::DescriptorSetLayoutBinding::descriptor_type(ty)
+                            },
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+        )?;
+        Ok(vk::PipelineLayout::new(
+            device,
+            vk::PipelineLayoutCreateInfo {
+                set_layouts: vec![set],
+                ..Default::default()
+            },
+        )?)
+    }
+    pub fn new(context: Arc<crate::render_device::RenderContext>) -> anyhow::Result<Self> {
+        use vk::DescriptorType::{StorageBuffer, StorageImage};
+        let device = context.device();
+        let layout = Self::make_layout(device.clone(), &[StorageImage; 3])?;
+        let dirty_layout =
+            Self::make_layout(device.clone(), &[StorageImage, StorageImage, StorageBuffer])?;
+
+        let pipeline = |entry: vk::EntryPoint, layout: &Arc<vk::PipelineLayout>| {
+            vk::ComputePipeline::new(
+                device.clone(),
+                None,
+                vk::ComputePipelineCreateInfo::stage_layout(
+                    vk::PipelineShaderStageCreateInfo::new(entry),
+                    layout.clone(),
+                ),
+            )
+        };
+        let saved = pipeline(
+            shaders::saved::load(device.clone())?
+                .entry_point("main")
+                .unwrap(),
+            &layout,
+        )?;
+        let dirty = pipeline(
+            shaders::dirty::load(device.clone())?
+                .entry_point("main")
+                .unwrap(),
+            &dirty_layout,
+        )?;
+        Ok(Self {
+            context,
+            layout,
+            pipeline: saved,
+            dirty_layout,
+            dirty,
+            masks: Vec::new().into(),
+        })
+    }
+    /// Write each image as a storage image in `General` layout, from binding zero onwards.
+    fn image_writes<'a>(
+        images: impl IntoIterator<Item = &'a Arc<vk::ImageView>> + 'a,
+    ) -> impl Iterator<Item = vk::WriteDescriptorSet> + 'a {
+        images.into_iter().zip(0..).map(|(image, binding)| {
+            vk::WriteDescriptorSet::image_view_with_layout(
+                binding,
+                vulkano::descriptor_set::DescriptorImageViewInfo {
+                    image_view: image.clone(),
+                    image_layout: vk::ImageLayout::General,
+                },
+            )
+        })
+    }
+    /// Record commands to tint the tiles of `destination` where `current` and `saved` differ. All three
+    /// must be the same size and have `STORAGE` usage. Must be recorded outside of a render pass.
+    pub fn record_saved(
+        &self,
+        commands: &mut vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>,
+        current: &Arc<vk::ImageView>,
+        saved: &Arc<vk::ImageView>,
+        destination: &Arc<vk::ImageView>,
+    ) -> anyhow::Result<()> {
+        let [width, height, _] = current.image().extent();
+        let descriptor = vk::PersistentDescriptorSet::new(
+            self.context.allocators().descriptor_set(),
+            self.layout.set_layouts()[0].clone(),
+            Self::image_writes([current, saved, destination]),
+            [],
+        )?;
+        commands
+            .bind_pipeline_compute(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                vk::PipelineBindPoint::Compute,
+                self.layout.clone(),
+                0,
+                descriptor,
+            )?
+            .dispatch([
+                width.div_ceil(SAVED_TILE_SIZE),
+                height.div_ceil(SAVED_TILE_SIZE),
+                1,
+            ])?;
+        Ok(())
+    }
+    /// A host-readable mask for [`Self::record_dirty`], with one flag per tile. Reuses one that was read and
+    /// released, allocating only if all are still in use.
+    pub fn dirty_mask(&self) -> anyhow::Result<vk::Subbuffer<[u32]>> {
+        let mut masks = self.masks.lock();
+        // Held only by the pool, so neither the device nor the proxy still needs it.
+        if let Some(free) = masks
+            .iter()
+            .find(|mask| Arc::strong_count(mask.buffer()) == 1)
+        {
+            return Ok(free.clone());
+        }
+        let tiles = crate::document_viewport_proxy::DirtyTiles::tiles_per_side();
+        let mask = vk::Buffer::new_slice(
+            self.context.allocators().memory().clone(),
+            vk::BufferCreateInfo {
+                usage: vk::BufferUsage::STORAGE_BUFFER | vk::BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            vk::AllocationCreateInfo {
+                memory_type_filter: vk::MemoryTypeFilter::HOST_RANDOM_ACCESS
+                    | vk::MemoryTypeFilter::PREFER_HOST,
+                ..Default::default()
+            },
+            u64::from(tiles) * u64::from(tiles),
+        )?;
+        if masks.len() < MASK_POOL {
+            masks.push(mask.clone());
+        }
+        Ok(mask)
+    }
+    /// Record commands to flag each tile of `mask` where `current` and `previous` differ, see
+    /// [`crate::document_viewport_proxy::DirtyTiles::from_mask`]. Both must be document-sized and have
+    /// `STORAGE` usage. Must be recorded outside of a render pass.
+    pub fn record_dirty(
+        &self,
+        commands: &mut vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>,
+        current: &Arc<vk::ImageView>,
+        previous: &Arc<vk::ImageView>,
+        mask: &vk::Subbuffer<[u32]>,
+    ) -> anyhow::Result<()> {
+        let [width, height, _] = current.image().extent();
+        let descriptor = vk::PersistentDescriptorSet::new(
+            self.context.allocators().descriptor_set(),
+            self.dirty_layout.set_layouts()[0].clone(),
+            Self::image_writes([current, previous]).chain(std::iter::once(
+                vk::WriteDescriptorSet::buffer(2, mask.clone()),
+            )),
+            [],
+        )?;
+        commands
+            .fill_buffer(mask.clone(), 0)?
+            .bind_pipeline_compute(self.dirty.clone())?
+            .bind_descriptor_sets(
+                vk::PipelineBindPoint::Compute,
+                self.dirty_layout.clone(),
+                0,
+                descriptor,
+            )?
+            .dispatch([
+                width.div_ceil(DIRTY_WORKGROUP_SIZE),
+                height.div_ceil(DIRTY_WORKGROUP_SIZE),
+                1,
+            ])?;
+        Ok(())
+    }
+}
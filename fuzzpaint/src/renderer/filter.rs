@@ -0,0 +1,292 @@
+//! Compute passes implementing [`Filter`]s, applied in-place to the image of a filtered group
+//! after its children are blended.
+
+use crate::vulkano_prelude::*;
+use fuzzpaint_core::{filter::Filter, state::graph};
+use std::sync::Arc;
+
+mod shaders {
+    pub mod blur {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/shaders/filter_blur.comp",
+        }
+    }
+    pub mod adjust {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/shaders/filter_adjust.comp",
+        }
+    }
+}
+
+/// `local_size` of both shaders, in X and Y.
+const WORKGROUP_SIZE: u32 = 8;
+/// Values of `mode` in the adjust shader.
+const MODE_BRIGHTNESS_CONTRAST: u32 = 0;
+const MODE_HUE_SATURATION: u32 = 1;
+
+pub struct FilterEngine {
+    context: Arc<crate::render_device::RenderContext>,
+    /// Set 0: binding 0 is the source storage image, binding 1 the destination.
+    blur_layout: Arc<vk::PipelineLayout>,
+    blur: Arc<vk::ComputePipeline>,
+    /// Set 0: binding 0 is the storage image to adjust.
+    adjust_layout: Arc<vk::PipelineLayout>,
+    adjust: Arc<vk::ComputePipeline>,
+}
+impl FilterEngine {
+    /// Make a layout of `images` storage images in set 0, and a push constant of the given size.
+    fn make_layout(
+        device: Arc<vk::Device>,
+        images: u32,
+        push_constant_size: usize,
+    ) -> anyhow::Result<Arc<vk::PipelineLayout>> {
+        let image_binding = vk::DescriptorSetLayoutBinding {
+            descriptor_count: 1,
+            stages: vk::ShaderStages::COMPUTE,
+            ..vk::DescriptorSetLayoutBinding::descriptor_type(vk::DescriptorType::StorageImage)
+        };
+        let bindings = (0..images)
+            .map(|binding| (binding, image_binding.clone()))
+            .collect();
+        let set = vk::DescriptorSetLayout::new(
+            device.clone(),
+            vk::DescriptorSetLayoutCreateInfo {
+                bindings,
+                ..Default::default()
+            },
+        )?;
+        Ok(vk::PipelineLayout::new(
+            device,
+            vk::PipelineLayoutCreateInfo {
+                set_layouts: vec![set],
+                push_constant_ranges: vec![vk::PushConstantRange {
+                    stages: vk::ShaderStages::COMPUTE,
+                    offset: 0,
+                    size: push_constant_size.try_into()?,
+                }],
+                ..Default::default()
+            },
+        )?)
+    }
+    pub fn new(context: Arc<crate::render_device::RenderContext>) -> anyhow::Result<Self> {
+        let device = context.device();
+        let blur_layout = Self::make_layout(
+            device.clone(),
+            2,
+            std::mem::size_of::<shaders::blur::Pass>(),
+        )?;
+        let adjust_layout = Self::make_layout(
+            device.clone(),
+            1,
+            std::mem::size_of::<shaders::adjust::Adjust>(),
+        )?;
+
+        let pipeline = |entry: vk::EntryPoint, layout: &Arc<vk::PipelineLayout>| {
+            vk::ComputePipeline::new(
+                device.clone(),
+                None,
+                vk::ComputePipelineCreateInfo::stage_layout(
+                    vk::PipelineShaderStageCreateInfo::new(entry),
+                    layout.clone(),
+                ),
+            )
+        };
+        let blur = pipeline(
+            shaders::blur::load(device.clone())?
+                .entry_point("main")
+                .unwrap(),
+            &blur_layout,
+        )?;
+        let adjust = pipeline(
+            shaders::adjust::load(device.clone())?
+                .entry_point("main")
+                .unwrap(),
+            &adjust_layout,
+        )?;
+
+        Ok(Self {
+            context,
+            blur_layout,
+            blur,
+            adjust_layout,
+            adjust,
+        })
+    }
+    /// Descriptor set binding the given images, in order, in `General` layout.
+    fn storage_descriptor(
+        &self,
+        layout: &vk::PipelineLayout,
+        images: &[&Arc<vk::ImageView>],
+    ) -> anyhow::Result<Arc<vk::PersistentDescriptorSet>> {
+        Ok(vk::PersistentDescriptorSet::new(
+            self.context.allocators().descriptor_set(),
+            layout.set_layouts()[0].clone(),
+            images.iter().zip(0..).map(|(&image, binding)| {
+                vk::WriteDescriptorSet::image_view_with_layout(
+                    binding,
+                    vulkano::descriptor_set::DescriptorImageViewInfo {
+                        image_view: image.clone(),
+                        image_layout: vk::ImageLayout::General,
+                    },
+                )
+            }),
+            [],
+        )?)
+    }
+    /// Record commands to apply the filter to `image`, which must have `STORAGE` usage. `scale` is the texels
+    /// of the image's full resolution per document pixel, see [`crate::document_viewport_proxy::DocumentRegion::scale`].
+    /// Must be recorded outside of a render pass.
+    pub fn record(
+        &self,
+        commands: &mut vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>,
+        image: &Arc<vk::ImageView>,
+        filter: Filter,
+        scale: f32,
+    ) -> anyhow::Result<()> {
+        // Of the level being filtered, smaller when zoomed out.
+        let [width, height] = super::view_extent(image);
+        let level = image.subresource_range().mip_levels.start;
+        let dispatch = [
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        ];
+
+        match filter.clamped() {
+            Filter::GaussianBlur { radius } => {
+                // Radius is in document pixels, the image may be of only a region of the document and at a lower
+                // level of detail.
+                let radius = radius * scale / f32::from(1u8 << level);
+                // Separable, so blur horizontally into scratch and then vertically back.
+                // The scratch is kept alive by the command buffer.
+                let scratch = vk::Image::new(
+                    self.context.allocators().memory().clone(),
+                    vk::ImageCreateInfo {
+                        usage: vk::ImageUsage::STORAGE,
+                        extent: [width, height, 1],
+                        format: crate::DOCUMENT_FORMAT,
+                        ..Default::default()
+                    },
+                    vk::AllocationCreateInfo {
+                        memory_type_filter: vk::MemoryTypeFilter::PREFER_DEVICE,
+                        ..Default::default()
+                    },
+                )?;
+                let scratch = vk::ImageView::new_default(scratch)?;
+                let horizontal = self.storage_descriptor(&self.blur_layout, &[image, &scratch])?;
+                let vertical = self.storage_descriptor(&self.blur_layout, &[&scratch, image])?;
+
+                // Radius is three standard deviations, past which the weights are negligible.
+                let sigma = radius / 3.0;
+                #[allow(clippy::cast_possible_truncation)]
+                let kernel_radius = radius.ceil() as i32;
+
+                commands.bind_pipeline_compute(self.blur.clone())?;
+                for (direction, descriptor) in [([1, 0], horizontal), ([0, 1], vertical)] {
+                    commands
+                        .bind_descriptor_sets(
+                            vk::PipelineBindPoint::Compute,
+                            self.blur_layout.clone(),
+                            0,
+                            descriptor,
+                        )?
+                        .push_constants(
+                            self.blur_layout.clone(),
+                            0,
+                            shaders::blur::Pass {
+                                direction,
+                                sigma,
+                                radius: kernel_radius,
+                            },
+                        )?
+                        .dispatch(dispatch)?;
+                }
+            }
+            Filter::BrightnessContrast {
+                brightness,
+                contrast,
+            } => self.record_adjust(
+                commands,
+                image,
+                dispatch,
+                shaders::adjust::Adjust {
+                    mode: MODE_BRIGHTNESS_CONTRAST,
+                    a: brightness,
+                    b: contrast,
+                    c: 0.0,
+                },
+            )?,
+            Filter::HueSaturation {
+                hue,
+                saturation,
+                lightness,
+            } => self.record_adjust(
+                commands,
+                image,
+                dispatch,
+                shaders::adjust::Adjust {
+                    mode: MODE_HUE_SATURATION,
+                    a: hue.to_radians(),
+                    b: saturation,
+                    c: lightness,
+                },
+            )?,
+        }
+        Ok(())
+    }
+    fn record_adjust(
+        &self,
+        commands: &mut vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>,
+        image: &Arc<vk::ImageView>,
+        dispatch: [u32; 3],
+        adjust: shaders::adjust::Adjust,
+    ) -> anyhow::Result<()> {
+        let descriptor = self.storage_descriptor(&self.adjust_layout, &[image])?;
+        commands
+            .bind_pipeline_compute(self.adjust.clone())?
+            .bind_descriptor_sets(
+                vk::PipelineBindPoint::Compute,
+                self.adjust_layout.clone(),
+                0,
+                descriptor,
+            )?
+            .push_constants(self.adjust_layout.clone(), 0, adjust)?
+            .dispatch(dispatch)?;
+        Ok(())
+    }
+}
+
+/// How far past the edge of a region compositing the graph samples from, in document pixels. Blurs of nested
+/// groups add up, so this is the furthest any line of nesting reaches. Regions padded by this much composite
+/// the same as the whole document does within them.
+#[must_use]
+pub fn reach(graph: &graph::BlendGraph) -> f32 {
+    fn reach_of<'a>(
+        graph: &graph::BlendGraph,
+        children: impl Iterator<Item = (graph::AnyID, &'a graph::NodeData)>,
+    ) -> f32 {
+        children
+            .filter(|(_, data)| !data.is_deleted())
+            .map(|(id, data)| {
+                let own = match data
+                    .node()
+                    .and_then(graph::NodeType::filter)
+                    .map(Filter::clamped)
+                {
+                    Some(Filter::GaussianBlur { radius }) => radius,
+                    _ => 0.0,
+                };
+                let nested = match id {
+                    graph::AnyID::Node(node) => graph
+                        .iter_node(node)
+                        .map_or(0.0, |children| reach_of(graph, children)),
+                    graph::AnyID::Leaf(_) => 0.0,
+                };
+                own + nested
+            })
+            .fold(0.0, f32::max)
+    }
+    reach_of(graph, graph.iter_top_level())
+}
@@ -1,7 +1,101 @@
-use fuzzpaint_core::stroke::Archetype;
+use fuzzpaint_core::{
+    state::{DynamicsInput, StampOrientation, Taper},
+    stroke::Archetype,
+};
 
 use crate::vulkano_prelude::*;
 use std::sync::Arc;
+
+/// Smudge strokes are drawn this many stamps at a time, each pass sampling the layer as the last pass left it.
+/// Stamps pick up color from where the stamp this many before them lay, which is always in an earlier pass.
+pub const SMUDGE_STAMPS_PER_PASS: u32 = 4;
+
+/// Where a stroke's stamps may land, as the tessellation shader places them, so that each smudge pass need
+/// only snapshot the part of the layer it touches.
+pub struct StampReach {
+    /// Arc length and position of each point, after the inner transform.
+    points: Vec<(f32, [f32; 2])>,
+    /// Arc length between stamps.
+    density: f32,
+    /// Furthest that a stamp's corners, or the color it picks up, may lie from where along the stroke it's
+    /// centered.
+    reach: f32,
+}
+impl StampReach {
+    /// `None` if the stroke's points aren't resident.
+    #[must_use]
+    pub fn new(
+        stroke: &fuzzpaint_core::state::stroke_collection::ImmutableStroke,
+        inner_transform: &fuzzpaint_core::state::transform::Similarity,
+    ) -> Option<Self> {
+        let lock = crate::global::points()
+            .try_get(stroke.point_collection)
+            .ok()?;
+        let slice = lock.get();
+        let matrix = fuzzpaint_core::state::transform::Matrix::from(*inner_transform);
+        let scale = inner_transform.scale();
+        let points = (0..slice.len())
+            .filter_map(|idx| {
+                let point = slice.get(idx)?;
+                Some((
+                    point.arc_length()? * scale,
+                    matrix.transform_point(point.position()?),
+                ))
+            })
+            .collect();
+
+        let brush = &stroke.brush;
+        let density = brush.spacing_px.get();
+        // The largest a stamp may grow, and its scatter. The corners of the square stamp lie root two further.
+        let radius = density.max(brush.size_mul.get() * 0.5);
+        // Small constant, exact.
+        #[allow(clippy::cast_precision_loss)]
+        let drag = if brush.is_smudge {
+            density * SMUDGE_STAMPS_PER_PASS as f32
+        } else {
+            0.0
+        };
+        Some(Self {
+            points,
+            density,
+            reach: radius.mul_add(
+                std::f32::consts::SQRT_2 + 2.0 * brush.scatter.position,
+                drag,
+            ),
+        })
+    }
+    /// Bounds of the numbered stamps and where they pick up color from, as the least and greatest corner
+    /// after the inner transform. `None` if the stroke has no points.
+    #[must_use]
+    pub fn bounds(&self, stamps: std::ops::Range<u32>) -> Option<[[f32; 2]; 2]> {
+        // Precision loss is irrelevant at the scale of a stroke.
+        #[allow(clippy::cast_precision_loss)]
+        let (start, end) = (
+            stamps.start as f32 * self.density,
+            stamps.end.saturating_sub(1).max(stamps.start) as f32 * self.density,
+        );
+        let last = self.points.len().checked_sub(1)?;
+        // The points either side of the first and last stamp.
+        let before = self
+            .points
+            .partition_point(|&(arc_length, _)| arc_length <= start)
+            .saturating_sub(1);
+        let after = self
+            .points
+            .partition_point(|&(arc_length, _)| arc_length < end)
+            .min(last);
+        let (mut min, mut max) = ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]);
+        for &(_, [x, y]) in &self.points[before.min(after)..=before.max(after)] {
+            min = [min[0].min(x), min[1].min(y)];
+            max = [max[0].max(x), max[1].max(y)];
+        }
+        Some([
+            [min[0] - self.reach, min[1] - self.reach],
+            [max[0] + self.reach, max[1] + self.reach],
+        ])
+    }
+}
+
 pub mod interface {
     #[derive(super::vk::Vertex, super::vk::BufferContents, Copy, Clone, Debug)]
     // Match align with GLSL std430.
@@ -15,8 +109,20 @@ pub mod interface {
         pub color: [f32; 4],
         #[format(R32_SFLOAT)]
         pub erase: f32,
-        #[format(R32G32B32_SFLOAT)]
-        pub pad: [f32; 3],
+        /// Rotation of the stamp, in radians from the x axis towards the y axis.
+        #[format(R32_SFLOAT)]
+        pub angle: f32,
+        /// For smudging, the offset from where color is picked up to the stamp.
+        #[format(R32G32_SFLOAT)]
+        pub drag: [f32; 2],
+        /// Coordinates into the dual stamp's texture, which may fall outside `0..=1` to repeat it.
+        #[format(R32G32_SFLOAT)]
+        pub dual_uv: [f32; 2],
+        /// Layer of the brush texture array to use as the dual stamp, or negative for none.
+        #[format(R32_SFLOAT)]
+        pub dual_layer: f32,
+        #[format(R32_SFLOAT)]
+        pub pad: f32,
     }
     pub type OutputStrokeInfo = vulkano::command_buffer::DrawIndirectCommand;
 }
@@ -27,6 +133,11 @@ mod shaders {
             ty: "compute",
             path: "./src/shaders/tessellate_stamp.comp",
         }
+        pub const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source::new(
+                "src/shaders/tessellate_stamp.comp",
+                crate::renderer::shader_reload::Kind::Compute,
+            );
     }
 }
 
@@ -36,6 +147,8 @@ pub struct TessOutput<Future: GpuFuture> {
     pub indirects: vk::Subbuffer<[interface::OutputStrokeInfo]>,
     /// Where each indirect came from. E.g., the sixth indirect comes from the sixth stroke in this list.
     pub sources: Vec<fuzzpaint_core::state::stroke_collection::ImmutableStroke>,
+    /// The vertices set aside for each of `sources`, six per stamp. Those left unwritten are degenerate.
+    pub ranges: Vec<std::ops::Range<u32>>,
 }
 
 pub struct GpuStampTess {
@@ -108,13 +221,17 @@ impl GpuStampTess {
             .max_compute_work_group_invocations
             .min(properties.max_compute_work_group_size[0]);
 
-        let shader = shaders::tessellate::load(context.device().clone())?;
+        let shader = super::shader_reload::load(
+            context.device().clone(),
+            &shaders::tessellate::SOURCE,
+            shaders::tessellate::load,
+        )?;
         // Specialize workgroup shape
         let mut specialize =
             ahash::HashMap::with_capacity_and_hasher(1, ahash::RandomState::default());
         specialize.insert(0, work_size.into());
         let entry = shader.specialize(specialize)?.entry_point("main").unwrap();
-        log::info!("Tess workgroup size: {}", work_size);
+        tracing::info!(work_size, "created tessellation pipeline");
 
         let (layout, input_descriptor, output_descriptor) =
             Self::make_layout(context.device().clone())?;
@@ -142,6 +259,8 @@ impl GpuStampTess {
     ///
     /// If `take_scratch` is set, will attempt to use the `residual` buffer for as much as possible, depending
     /// on the underlying buffer's `usage`.
+    #[allow(clippy::too_many_lines)]
+    #[tracing::instrument(level = "trace", skip_all)]
     pub fn tess_batch(
         &self,
         batch: &crate::renderer::stroke_batcher::StrokeBatch,
@@ -150,7 +269,6 @@ impl GpuStampTess {
         // TODO: implement.
         _take_scratch: bool,
     ) -> anyhow::Result<Option<TessOutput<impl GpuFuture>>> {
-        #![allow(clippy::too_many_lines)]
         let mut group_index_counter = 0;
         let mut vertex_output_index_counter = 0;
 
@@ -160,6 +278,7 @@ impl GpuStampTess {
         // For each info, how many workgroups are dispatched for it?
         let mut num_groups_per_info = Vec::with_capacity(batch.allocs.len());
         let mut sources = Vec::new();
+        let mut ranges = Vec::new();
 
         let input_infos = vk::Buffer::from_iter(
             self.context.allocators().memory().clone(),
@@ -179,19 +298,40 @@ impl GpuStampTess {
                     .contains(Archetype::POSITION | Archetype::ARC_LENGTH));
 
                 let density = alloc.src.brush.spacing_px.get();
-                // If not found, ignore by claiming 0 stamps.
+                // If not found, ignore by claiming 0 stamps. Likewise if it's not finite, which would
+                // otherwise saturate to billions of stamps.
                 let num_expected_stamps = alloc
                     .summary
                     .arc_length
                     .map(|arc_length| arc_length * distance_scale)
+                    .filter(|arc_length| arc_length.is_finite())
                     .map_or(0, |arc_length| (arc_length / density).ceil() as u32);
 
+                // Small constant, exact.
+                #[allow(clippy::cast_precision_loss)]
+                let smudge_drag = if alloc.src.brush.is_smudge {
+                    density * SMUDGE_STAMPS_PER_PASS as f32
+                } else {
+                    0.0
+                };
+                let dynamics = alloc.src.brush.dynamics;
+                let input = |input| match input {
+                    DynamicsInput::None => 0,
+                    DynamicsInput::Pressure => 1,
+                    DynamicsInput::Tilt => 2,
+                    DynamicsInput::Speed => 3,
+                    DynamicsInput::Wheel => 4,
+                };
                 let num_points = alloc.summary.len as u32;
                 let num_expected_verts = num_expected_stamps * 6;
                 let num_groups = num_expected_stamps.div_ceil(self.work_size);
 
                 if num_groups != 0 {
                     sources.push(alloc.src);
+                    ranges.push(
+                        vertex_output_index_counter
+                            ..vertex_output_index_counter + num_expected_verts,
+                    );
                 }
 
                 let info = shaders::tessellate::InputStrokeInfo {
@@ -213,16 +353,50 @@ impl GpuStampTess {
                     density,
                     size_mul: alloc.src.brush.size_mul.get().into(),
                     is_eraser: if alloc.src.brush.is_eraser { 1.0 } else { 0.0 },
+                    smudge_drag,
+                    orientation: match alloc.src.brush.orientation {
+                        StampOrientation::Random => 0,
+                        StampOrientation::Direction => 1,
+                        StampOrientation::Tilt => 2,
+                    },
+                    scatter_position: alloc.src.brush.scatter.position,
+                    scatter_size: alloc.src.brush.scatter.size,
+                    scatter_angle: alloc.src.brush.scatter.angle,
+                    dual_scale: alloc.src.brush.dual.map_or(1.0, |dual| dual.scale),
+                    // Dual stamps of brushes without a texture are left out.
+                    dual_layer: alloc
+                        .src
+                        .brush
+                        .dual
+                        .and_then(|dual| {
+                            super::stroke_renderer::BUILTIN_BRUSHES
+                                .iter()
+                                .position(|&brush| brush == dual.brush)
+                        })
+                        .and_then(|layer| i32::try_from(layer).ok())
+                        .unwrap_or(-1),
+                    size_input: input(dynamics.size.input),
+                    size_min: dynamics.size.min,
+                    size_gamma: dynamics.size.gamma,
+                    flow_input: input(dynamics.flow.input),
+                    flow_min: dynamics.flow.min,
+                    flow_gamma: dynamics.flow.gamma,
+                    opacity_input: input(dynamics.opacity.input),
+                    opacity_min: dynamics.opacity.min,
+                    opacity_gamma: dynamics.opacity.gamma,
+                    fast_speed: Taper::FAST_SPEED,
+                    pad_0: 0.0,
+                    pad_1: 0.0,
                 };
 
                 num_groups_per_info.push(num_groups);
                 group_index_counter += num_groups;
                 vertex_output_index_counter += num_expected_verts;
 
-                // Returning just info here results in misaligned structures.
+                // This must stay a multiple of 16 bytes long, else it's misaligned in the buffer and needs
+                // `vulkano::padded::Padded` to the next multiple.
                 // This bug took SO long to find, thank you Marc I owe you my life.
-                // the `12` magic comes from expansion of `inputStrokeInfo`
-                vulkano::padded::Padded::<_, 12>::from(info)
+                info
             }),
         )?;
 
@@ -263,6 +437,8 @@ impl GpuStampTess {
                 usage: vk::BufferUsage::STORAGE_BUFFER
                     | vk::BufferUsage::INDIRECT_BUFFER
                     | vk::BufferUsage::TRANSFER_DST,
+                // Written here, read by graphics to draw.
+                sharing: self.context.queues().sharing_compute_graphics(),
                 ..Default::default()
             },
             vk::AllocationCreateInfo {
@@ -274,7 +450,11 @@ impl GpuStampTess {
         let output_verts = vk::Buffer::new_slice::<interface::OutputStrokeVertex>(
             self.context.allocators().memory().clone(),
             vk::BufferCreateInfo {
-                usage: vk::BufferUsage::STORAGE_BUFFER | vk::BufferUsage::VERTEX_BUFFER,
+                // Transfer dest for clearing
+                usage: vk::BufferUsage::STORAGE_BUFFER
+                    | vk::BufferUsage::VERTEX_BUFFER
+                    | vk::BufferUsage::TRANSFER_DST,
+                sharing: self.context.queues().sharing_compute_graphics(),
                 ..Default::default()
             },
             vk::AllocationCreateInfo {
@@ -312,6 +492,9 @@ impl GpuStampTess {
 
         command_buffer
             .fill_buffer(output_infos.clone().reinterpret(), 0u32)?
+            // Stamps past the end of a stroke are never written. Zeroed, they're degenerate and draw nothing
+            // when drawn by range rather than indirectly.
+            .fill_buffer(output_verts.clone().reinterpret(), 0u32)?
             .bind_pipeline_compute(self.pipeline.clone())?
             .push_constants(
                 self.layout.clone(),
@@ -334,10 +517,10 @@ impl GpuStampTess {
             .dispatch([group_index_counter, 1, 1])?;
         let command_buffer = command_buffer.build()?;
 
-        log::trace!(
-            "Dispatched {} tessellation workgroups for {} strokes",
-            group_index_counter,
-            batch.allocs.len()
+        tracing::trace!(
+            workgroups = group_index_counter,
+            strokes = batch.allocs.len(),
+            "dispatched tessellation"
         );
 
         let future = vk::sync::now(self.context.device().clone())
@@ -352,6 +535,7 @@ impl GpuStampTess {
             vertices: output_verts,
             indirects: output_infos,
             sources,
+            ranges,
         }))
     }
 }
@@ -0,0 +1,237 @@
+//! Drawing [gradient leaves](fuzzpaint_core::state::graph::LeafType::Gradient), evaluated per-pixel by a
+//! fragment shader filling the leaf.
+
+use crate::{document_viewport_proxy::DocumentRegion, vulkano_prelude::*};
+use fuzzpaint_core::{
+    color::Color,
+    gradient::{Gradient, GradientShape},
+    state::palette::Palette,
+};
+use std::sync::Arc;
+
+mod shaders {
+    /// Fills the viewport. Call with three vertices.
+    pub mod vert {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                void main() {
+                    // fullscreen tri
+                    vec2 pos = vec2(
+                        float((gl_VertexIndex & 1) * 4 - 1),
+                        float((gl_VertexIndex & 2) * 2 - 1)
+                    );
+                    gl_Position = vec4(pos, 0.0, 1.0);
+                }
+            "
+        }
+    }
+    pub mod frag {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "src/shaders/gradient.frag",
+        }
+    }
+}
+
+/// Values of `shape` in the shader.
+const SHAPE_LINEAR: u32 = 0;
+const SHAPE_RADIAL: u32 = 1;
+
+pub struct GradientLeafRenderer {
+    context: Arc<crate::render_device::RenderContext>,
+    /// Set 0: binding 0 is the storage buffer of stops.
+    layout: Arc<vk::PipelineLayout>,
+    pipeline: Arc<vk::GraphicsPipeline>,
+}
+impl GradientLeafRenderer {
+    pub fn new(context: Arc<crate::render_device::RenderContext>) -> anyhow::Result<Self> {
+        let device = context.device();
+        let set = vk::DescriptorSetLayout::new(
+            device.clone(),
+            vk::DescriptorSetLayoutCreateInfo {
+                bindings: [(
+                    0,
+                    vk::DescriptorSetLayoutBinding {
+                        descriptor_count: 1,
+                        stages: vk::ShaderStages::FRAGMENT,
+                        ..vk::DescriptorSetLayoutBinding::descriptor_type(
+                            vk::DescriptorType::StorageBuffer,
+                        )
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+        )?;
+        let layout = vk::PipelineLayout::new(
+            device.clone(),
+            vk::PipelineLayoutCreateInfo {
+                set_layouts: vec![set],
+                push_constant_ranges: vec![vk::PushConstantRange {
+                    stages: vk::ShaderStages::FRAGMENT,
+                    offset: 0,
+                    size: std::mem::size_of::<shaders::frag::Gradient>().try_into()?,
+                }],
+                ..Default::default()
+            },
+        )?;
+        let vert = shaders::vert::load(device.clone())?
+            .entry_point("main")
+            .unwrap();
+        let frag = shaders::frag::load(device.clone())?
+            .entry_point("main")
+            .unwrap();
+        let pipeline = vk::GraphicsPipeline::new(
+            device.clone(),
+            None,
+            vk::GraphicsPipelineCreateInfo {
+                stages: smallvec::smallvec![
+                    vk::PipelineShaderStageCreateInfo::new(vert),
+                    vk::PipelineShaderStageCreateInfo::new(frag),
+                ],
+                // Data generated by vertex iteself.
+                vertex_input_state: Some(vk::VertexInputState::new()),
+                input_assembly_state: Some(vk::InputAssemblyState::default()),
+                rasterization_state: Some(vk::RasterizationState::default()),
+                // Overwrites the leaf entirely, no blending.
+                color_blend_state: Some(vk::ColorBlendState::with_attachment_states(
+                    1,
+                    vk::ColorBlendAttachmentState::default(),
+                )),
+                multisample_state: Some(vk::MultisampleState::default()),
+                // Viewport dynamic, scissor irrelevant.
+                viewport_state: Some(vk::ViewportState::default()),
+                dynamic_state: [vk::DynamicState::Viewport].into_iter().collect(),
+                subpass: Some(vk::PipelineSubpassType::BeginRendering(
+                    vk::PipelineRenderingCreateInfo {
+                        color_attachment_formats: vec![Some(crate::DOCUMENT_FORMAT)],
+                        ..Default::default()
+                    },
+                )),
+                ..vk::GraphicsPipelineCreateInfo::layout(layout.clone())
+            },
+        )?;
+        Ok(Self {
+            context,
+            layout,
+            pipeline,
+        })
+    }
+    /// Draw the gradient into the leaf, with palette colors looked up in `palette`. Only the `region` of the
+    /// document is drawn.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn draw(
+        &self,
+        gradient: &Gradient,
+        palette: &Palette,
+        into: &super::LeafRenderData,
+        region: DocumentRegion,
+    ) -> anyhow::Result<vk::FenceSignalFuture<Box<dyn vk::sync::GpuFuture>>> {
+        let gradient = gradient.clone().clamped();
+        // Buffers can't be empty. The shader reads none of it with no stops.
+        let stops = if gradient.stops.is_empty() {
+            vec![shaders::frag::Stop {
+                color: [0.0; 4],
+                position: 0.0,
+            }]
+        } else {
+            gradient
+                .stops
+                .iter()
+                .map(|stop| shaders::frag::Stop {
+                    // Dereference possibly paletted color
+                    color: stop
+                        .color
+                        .get()
+                        .left_or_else(|pal_idx| palette.get(pal_idx).unwrap_or(Color::TRANSPARENT))
+                        .as_array(),
+                    position: stop.position,
+                })
+                .collect()
+        };
+        let stops = vk::Buffer::from_iter(
+            self.context.allocators().memory().clone(),
+            vk::BufferCreateInfo {
+                usage: vk::BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            vk::AllocationCreateInfo {
+                memory_type_filter: vk::MemoryTypeFilter::PREFER_DEVICE
+                    | vk::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            stops,
+        )?;
+        let descriptor = vk::PersistentDescriptorSet::new(
+            self.context.allocators().descriptor_set(),
+            self.layout.set_layouts()[0].clone(),
+            [vk::WriteDescriptorSet::buffer(0, stops)],
+            [],
+        )?;
+
+        let dimension = crate::DOCUMENT_DIMENSION as f32;
+        let constants = shaders::frag::Gradient {
+            shape: match gradient.shape {
+                GradientShape::Linear => SHAPE_LINEAR,
+                GradientShape::Radial => SHAPE_RADIAL,
+            },
+            // At most `MAX_STOPS`, fits.
+            stop_count: gradient.stops.len().try_into().unwrap(),
+            start: gradient.start,
+            end: gradient.end,
+            origin: [region.origin.x, region.origin.y],
+            pixel_size: region.size / dimension,
+            height: dimension,
+        };
+
+        let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
+            self.context.allocators().command_buffer(),
+            self.context.queues().graphics().idx(),
+            vk::CommandBufferUsage::OneTimeSubmit,
+        )?;
+        command_buffer
+            .begin_rendering(vk::RenderingInfo {
+                color_attachments: vec![Some(vk::RenderingAttachmentInfo {
+                    // Every pixel is written.
+                    load_op: vk::AttachmentLoadOp::DontCare,
+                    store_op: vk::AttachmentStoreOp::Store,
+                    ..vk::RenderingAttachmentInfo::image_view(into.view.clone())
+                })],
+                contents: vk::SubpassContents::Inline,
+                depth_attachment: None,
+                ..Default::default()
+            })?
+            .set_viewport(
+                0,
+                smallvec::smallvec![vk::Viewport {
+                    depth_range: 0.0..=1.0,
+                    offset: [0.0; 2],
+                    extent: [dimension; 2],
+                }],
+            )?
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                vk::PipelineBindPoint::Graphics,
+                self.layout.clone(),
+                0,
+                descriptor,
+            )?
+            .push_constants(self.layout.clone(), 0, constants)?
+            .draw(3, 1, 0, 0)?
+            .end_rendering()?;
+
+        Ok(self
+            .context
+            .now()
+            .then_execute(
+                self.context.queues().graphics().queue().clone(),
+                command_buffer.build()?,
+            )?
+            .boxed()
+            .then_signal_fence_and_flush()?)
+    }
+}
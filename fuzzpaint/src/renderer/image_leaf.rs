@@ -0,0 +1,235 @@
+//! Drawing [image leaves](fuzzpaint_core::state::graph::LeafType::Image) from the document's assets.
+//!
+//! Images are decoded and uploaded once, then blitted into the leaf. Blits can't rotate, so only the scale
+//! and translation of the leaf's transform are honored.
+
+use crate::{document_viewport_proxy::DocumentRegion, vulkano_prelude::*};
+use fuzzpaint_core::{brush::UniqueID, io::asset::AssetLinks, state::transform::Matrix};
+use std::sync::Arc;
+use vulkano::half::f16;
+
+fn srgb_to_linear(srgb: f32) -> f32 {
+    if srgb <= 0.040_45 {
+        srgb / 12.92
+    } else {
+        ((srgb + 0.055) / 1.055).powf(2.4)
+    }
+}
+/// Convert an sRGB texel with straight alpha into the renderer's linear, premultiplied format.
+fn from_srgb8([r, g, b, a]: [u8; 4]) -> [f16; 4] {
+    let alpha = f32::from(a) / 255.0;
+    let [r, g, b] = [r, g, b].map(|c| srgb_to_linear(f32::from(c) / 255.0) * alpha);
+    [r, g, b, alpha].map(f16::from_f32)
+}
+
+/// Find the part of a blit along one axis that lands within the destination, where source coordinate `s` lands
+/// at `scale * s + offset`. Returns the source and destination ranges, of which the source may be reversed
+/// to mirror it. None if nothing lands within.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn clip_axis(len: u32, scale: f32, offset: f32, dimension: u32) -> Option<([u32; 2], [u32; 2])> {
+    if scale == 0.0 || !scale.is_finite() || !offset.is_finite() {
+        return None;
+    }
+    let ends = [offset, scale * len as f32 + offset];
+    let lo = ends[0].min(ends[1]).max(0.0).round();
+    let hi = ends[0].max(ends[1]).min(dimension as f32).round();
+    if lo >= hi {
+        return None;
+    }
+    // Clamped, so the casts are exact.
+    let source = |dest: f32| ((dest - offset) / scale).round().clamp(0.0, len as f32) as u32;
+    let mut src = [source(lo), source(hi)];
+    if src[0] == src[1] {
+        // Magnified so far that less than a texel is visible. Blit the nearest one.
+        let texel = src[0].min(len - 1);
+        src = if scale > 0.0 {
+            [texel, texel + 1]
+        } else {
+            [texel + 1, texel]
+        };
+    }
+    Some((src, [lo as u32, hi as u32]))
+}
+
+struct Uploaded {
+    image: Arc<vk::Image>,
+    /// Image pixels per texel, where the image was too large for the device and was shrunk to fit.
+    scale: f32,
+}
+
+pub struct ImageLeafRenderer {
+    context: Arc<crate::render_device::RenderContext>,
+    /// Assets are identified by their contents, so these are never outdated.
+    uploaded: parking_lot::Mutex<hashbrown::HashMap<UniqueID, Arc<Uploaded>>>,
+}
+impl ImageLeafRenderer {
+    pub fn new(context: Arc<crate::render_device::RenderContext>) -> Self {
+        Self {
+            context,
+            uploaded: parking_lot::Mutex::default(),
+        }
+    }
+    /// Decode the asset and record its upload, or fetch it if it was uploaded before.
+    #[allow(clippy::cast_precision_loss)]
+    fn upload(
+        &self,
+        assets: &AssetLinks,
+        id: UniqueID,
+        command_buffer: &mut vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>,
+    ) -> anyhow::Result<Arc<Uploaded>> {
+        if let Some(uploaded) = self.uploaded.lock().get(&id) {
+            return Ok(uploaded.clone());
+        }
+        let mut decoded = image::load_from_memory(&assets.load(id)?)?.into_rgba8();
+        let width = decoded.width();
+        let max = self
+            .context
+            .device()
+            .physical_device()
+            .properties()
+            .max_image_dimension2_d;
+        let longest = decoded.width().max(decoded.height());
+        if longest > max {
+            // Shrink uniformly, so that one scale covers both axes.
+            let shrink = |len: u32| (u64::from(len) * u64::from(max) / u64::from(longest)).max(1);
+            // Shrunk to at most `max`, fits.
+            decoded = image::imageops::thumbnail(
+                &decoded,
+                shrink(decoded.width()).try_into().unwrap(),
+                shrink(decoded.height()).try_into().unwrap(),
+            );
+        }
+        let image = vk::Image::new(
+            self.context.allocators().memory().clone(),
+            vk::ImageCreateInfo {
+                extent: [decoded.width(), decoded.height(), 1],
+                format: crate::DOCUMENT_FORMAT,
+                usage: vk::ImageUsage::TRANSFER_DST | vk::ImageUsage::TRANSFER_SRC,
+                // Uploaded and blitted on graphics.
+                sharing: self
+                    .context
+                    .queues()
+                    .sharing(&[crate::render_device::QueueUse::Graphics]),
+                ..Default::default()
+            },
+            vk::AllocationCreateInfo {
+                memory_type_filter: vk::MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )?;
+        let stage = vk::Buffer::from_iter(
+            self.context.allocators().memory().clone(),
+            vk::BufferCreateInfo {
+                usage: vk::BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            vk::AllocationCreateInfo {
+                memory_type_filter: vk::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            decoded.pixels().map(|pixel| from_srgb8(pixel.0)),
+        )?;
+        command_buffer.copy_buffer_to_image(vk::CopyBufferToImageInfo::buffer_image(
+            stage,
+            image.clone(),
+        ))?;
+
+        let uploaded = Arc::new(Uploaded {
+            scale: width as f32 / decoded.width() as f32,
+            image,
+        });
+        self.uploaded.lock().insert(id, uploaded.clone());
+        Ok(uploaded)
+    }
+    /// Draw the image asset into the leaf, placed by `outer_transform`. Only the `region` of the document is drawn.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn draw(
+        &self,
+        assets: &AssetLinks,
+        id: UniqueID,
+        outer_transform: &Matrix,
+        into: &super::LeafRenderData,
+        region: DocumentRegion,
+    ) -> anyhow::Result<vk::FenceSignalFuture<Box<dyn vk::sync::GpuFuture>>> {
+        let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
+            self.context.allocators().command_buffer(),
+            self.context.queues().graphics().idx(),
+            vk::CommandBufferUsage::OneTimeSubmit,
+        )?;
+        command_buffer.clear_color_image(vk::ClearColorImageInfo {
+            clear_value: [0.0; 4].into(),
+            ..vk::ClearColorImageInfo::image(into.image.clone())
+        })?;
+
+        let uploaded = self.upload(assets, id, &mut command_buffer)?;
+        let [width, height, _] = uploaded.image.extent();
+        // Same projection as strokes use, where the top of the region is the bottom row of the image.
+        let dimension = crate::DOCUMENT_DIMENSION;
+        let pixels_per_document = dimension as f32 / region.size;
+        let [[scale_x, _], [_, scale_y], [x, y]] = outer_transform.elements;
+        let blit_x = clip_axis(
+            width,
+            scale_x * uploaded.scale * pixels_per_document,
+            (x - region.origin.x) * pixels_per_document,
+            dimension,
+        );
+        let blit_y = clip_axis(
+            height,
+            -scale_y * uploaded.scale * pixels_per_document,
+            dimension as f32 - (y - region.origin.y) * pixels_per_document,
+            dimension,
+        );
+        if let (Some((src_x, dst_x)), Some((src_y, dst_y))) = (blit_x, blit_y) {
+            let subresource = vk::ImageSubresourceLayers {
+                array_layers: 0..1,
+                aspects: vk::ImageAspects::COLOR,
+                mip_level: 0,
+            };
+            command_buffer.blit_image(vk::BlitImageInfo {
+                filter: vk::Filter::Linear,
+                regions: smallvec::smallvec![vk::ImageBlit {
+                    src_subresource: subresource.clone(),
+                    dst_subresource: subresource,
+                    src_offsets: [[src_x[0], src_y[0], 0], [src_x[1], src_y[1], 1]],
+                    dst_offsets: [[dst_x[0], dst_y[0], 0], [dst_x[1], dst_y[1], 1]],
+                    ..Default::default()
+                }],
+                ..vk::BlitImageInfo::images(uploaded.image.clone(), into.image.clone())
+            })?;
+        }
+
+        Ok(self
+            .context
+            .now()
+            .then_execute(
+                self.context.queues().graphics().queue().clone(),
+                command_buffer.build()?,
+            )?
+            .boxed()
+            .then_signal_fence_and_flush()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::clip_axis;
+    #[test]
+    fn clip_blit() {
+        // Entirely within.
+        assert_eq!(clip_axis(100, 1.0, 10.0, 1080), Some(([0, 100], [10, 110])));
+        // Hanging off both ends, magnified.
+        assert_eq!(clip_axis(100, 2.0, -50.0, 100), Some(([25, 75], [0, 100])));
+        // Mirrored.
+        assert_eq!(
+            clip_axis(100, -1.0, 100.0, 1080),
+            Some(([100, 0], [0, 100]))
+        );
+        // Entirely outside.
+        assert_eq!(clip_axis(100, 1.0, 2000.0, 1080), None);
+        assert_eq!(clip_axis(100, 0.0, 0.0, 1080), None);
+    }
+}
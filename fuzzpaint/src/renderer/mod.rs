@@ -1,23 +1,62 @@
+pub mod bench;
 mod blender;
+mod diff;
+mod filter;
 mod gpu_tess;
+mod gradient_leaf;
+mod image_leaf;
 pub mod picker;
 pub mod requests;
+mod shader_reload;
 mod stroke_batcher;
 
+use crate::i18n::tr;
 use fuzzpaint_core::{
     queue::{self, state_reader::CommandQueueStateReader},
-    state::{self, graph},
+    state::{self, document::Canvas, graph},
 };
 use std::sync::Arc;
 use vulkano::command_buffer::{CopyImageInfo, ImageCopy};
 
-use crate::vulkano_prelude::*;
+use crate::{document_viewport_proxy::DocumentRegion, vulkano_prelude::*};
+
+/// Levels of detail of each layer image, each half the size of the last. Zoomed far out, the document is
+/// composited from a smaller level, see [`Renderer::set_zoom`].
+pub const LOD_LEVELS: u32 = 5;
+
+/// Views of each level of detail of the image, see [`LOD_LEVELS`].
+fn lod_views(image: &Arc<vk::Image>) -> anyhow::Result<Box<[Arc<vk::ImageView>]>> {
+    (0..image.mip_levels())
+        .map(|level| {
+            Ok(vk::ImageView::new(
+                image.clone(),
+                vk::ImageViewCreateInfo {
+                    subresource_range: vk::ImageSubresourceRange {
+                        aspects: vk::ImageAspects::COLOR,
+                        mip_levels: level..level + 1,
+                        array_layers: 0..1,
+                    },
+                    ..vk::ImageViewCreateInfo::from_image(image)
+                },
+            )?)
+        })
+        .collect()
+}
+/// Size of the first mip level of the view.
+fn view_extent(view: &vk::ImageView) -> [u32; 2] {
+    let [width, height, _] = view.image().extent();
+    let level = view.subresource_range().mip_levels.start;
+    [(width >> level).max(1), (height >> level).max(1)]
+}
 
 struct GraphImages {
     leaves: hashbrown::HashMap<graph::LeafID, LeafRenderData>,
     nodes: hashbrown::HashMap<graph::NodeID, NodeRenderData>,
 }
 
+/// Filters by the node they override.
+type FilterPreviews = hashbrown::HashMap<graph::NodeID, fuzzpaint_core::filter::Filter>;
+
 struct PerDocumentData {
     listener: queue::DocumentCommandListener,
     /// Cached images of each of the nodes of the graph.
@@ -25,6 +64,67 @@ struct PerDocumentData {
     /// precompiled blend operations, invalided when the graph changes.
     compiled_blend: Option<blender::BlendInvocation>,
     render_target: NodeRenderData,
+    /// Filter parameters being edited, used in place of the graph's until the graph next changes.
+    filter_previews: FilterPreviews,
+    /// The node composited in place of the whole graph, see [`Renderer::set_solo`].
+    solo: Option<graph::AnyID>,
+    /// Whether reference leaves are composited. They're left out of exports.
+    references: bool,
+    /// Stroke layers left undrawn by an abandoned render, to be drawn by the next one.
+    pending_strokes:
+        hashbrown::HashMap<state::stroke_collection::StrokeCollectionID, StrokeChanges>,
+    /// For replaying history, how many commands each update advances by rather than catching up to the present.
+    /// Zero holds the render where it is.
+    replay_step: Option<usize>,
+    /// Copy of `render_target` as last copied into the preview, to find what changed since. Allocated on
+    /// first present.
+    presented: Option<NodeRenderData>,
+    /// The area of the document drawn into the images.
+    region: DocumentRegion,
+    /// Whether `region` covers the document's canvas, moving along with it. Otherwise, it's fixed.
+    follows_canvas: bool,
+    /// The document's canvas as of the last update.
+    canvas: Canvas,
+    /// How far past the region the graph samples from as of its last compile, see [`filter::reach`].
+    reach: f32,
+    /// The level of detail the document was last composited at, see [`LOD_LEVELS`].
+    lod: u32,
+    /// Leaves drawn since their levels of detail were last made.
+    stale_lods: hashbrown::HashSet<graph::LeafID>,
+}
+/// How a stroke layer must be redrawn.
+enum StrokeChanges {
+    // Strokes were added
+    Add(Vec<state::stroke_collection::ImmutableStrokeID>),
+    // Big change, redraw from scratch.
+    Invalidated,
+}
+
+/// Lets a render be abandoned when its document changes again before it finishes, as the result
+/// would be immediately outdated.
+#[derive(Default)]
+struct Cancellation {
+    /// The document currently being rendered, if any.
+    target: parking_lot::Mutex<Option<state::document::ID>>,
+    cancelled: std::sync::atomic::AtomicBool,
+}
+impl Cancellation {
+    /// Start watching for changes to the document.
+    fn begin(&self, id: state::document::ID) {
+        *self.target.lock() = Some(id);
+        self.cancelled
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+    }
+    /// A change notification was received for the document.
+    fn notify(&self, id: state::document::ID) {
+        if *self.target.lock() == Some(id) {
+            self.cancelled
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::Relaxed)
+    }
 }
 
 /// Dispatches render work to engines to create document images.
@@ -32,19 +132,827 @@ struct PerDocumentData {
 struct Renderer {
     engines: Engines,
     data: hashbrown::HashMap<state::document::ID, PerDocumentData>,
+    /// Brackets stroke rendering on the graphics queue, and so includes waiting on tessellation from the
+    /// compute queue and on the host between layers - see [`crate::diagnostics::GpuPass::Tessellation`]. None
+    /// if timestamps are unsupported.
+    tessellation_timer: Option<crate::diagnostics::GpuTimer>,
+    composite_timer: Option<crate::diagnostics::GpuTimer>,
+    /// Documents showing what changed since they were saved, with a render of their saved state once made.
+    saved_diffs: hashbrown::HashMap<state::document::ID, Option<PerDocumentData>>,
+    /// The document whose untinted image is in the preview, which later presents can be compared against.
+    last_presented: Option<state::document::ID>,
+    /// The region the view of a document is zoomed into, see [`DocumentRegion::for_view`].
+    zoom: Option<(state::document::ID, DocumentRegion)>,
+    /// The level of detail the view of a document is zoomed out to, see
+    /// [`crate::document_viewport_proxy::lod_for_view`].
+    lod: Option<(state::document::ID, u32)>,
+    /// The zoomed region drawn at full resolution, once made, and the region that was asked for. The render is
+    /// padded past it by as far as filters sample, see [`filter::reach`]. Kept up-to-date alongside the
+    /// document's own render, which is still needed for everything else.
+    zoomed: Option<(state::document::ID, DocumentRegion, PerDocumentData)>,
+    /// Documents showing just one node of their graph, see [`Self::set_solo`].
+    solos: hashbrown::HashMap<state::document::ID, graph::AnyID>,
+    /// The part of a document in view, as least and greatest corner in document space, see
+    /// [`Self::set_visible`].
+    visible: Option<(state::document::ID, [[f32; 2]; 2])>,
+}
+/// A document image copied into the preview, see [`Renderer::present_one`].
+struct Presented {
+    fence: vk::FenceSignalFuture<Box<dyn vk::sync::GpuFuture + Send>>,
+    /// Flags of the tiles that changed since the previous present, if they can be known, see
+    /// [`crate::document_viewport_proxy::ImageGuard::set_dirty_tiles`].
+    dirty_mask: Option<vk::Subbuffer<[u32]>>,
+    region: DocumentRegion,
+    canvas: Canvas,
+}
+/// The canvas split into regions of the document, each drawn to an image as large as the document's own, to be
+/// joined with [`crate::export::stitch`].
+#[derive(Copy, Clone, Debug)]
+struct CanvasTiles {
+    canvas: Canvas,
+    /// Texels per document pixel.
+    factor: u32,
+    /// Texels wide and tall of the joined image.
+    size: [u32; 2],
+    /// Texels each tile overlaps its neighbours by on every side.
+    margin: u32,
+    /// Tiles wide and tall.
+    across: [u32; 2],
+}
+impl CanvasTiles {
+    /// Tiles of the canvas at `factor` texels per document pixel, overlapping by as far as filters `reach`,
+    /// in document pixels.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn new(canvas: Canvas, factor: u32, reach: f32) -> Self {
+        let size = canvas
+            .size
+            .map(|side| ((side * factor as f32).round() as u32).max(1));
+        // Filters sample past the edge of each tile, so tiles overlap by as far as they reach, in texels, and only
+        // their insides are kept. Past a quarter, there'd be more overlap than inside. A canvas that fits the
+        // image exactly is drawn whole, as it is shown.
+        let margin = if size == [crate::DOCUMENT_DIMENSION; 2] {
+            0
+        } else {
+            ((reach * factor as f32).ceil() as u32).min(crate::DOCUMENT_DIMENSION / 4)
+        };
+        let inner = crate::DOCUMENT_DIMENSION - 2 * margin;
+        Self {
+            canvas,
+            factor,
+            size,
+            margin,
+            across: size.map(|side| side.div_ceil(inner)),
+        }
+    }
+    fn len(&self) -> u32 {
+        self.across[0] * self.across[1]
+    }
+    /// The region of the document drawn for the tile, in rows of increasing y each of increasing x.
+    #[allow(clippy::cast_precision_loss)]
+    fn region(&self, idx: u32) -> DocumentRegion {
+        let inner = crate::DOCUMENT_DIMENSION - 2 * self.margin;
+        // Document pixels from the canvas' origin to the start of the tile's inside, less the margin.
+        let offset = |tile: u32| ((tile * inner) as f32 - self.margin as f32) / self.factor as f32;
+        DocumentRegion {
+            origin: cgmath::point2(
+                self.canvas.origin[0] + offset(idx % self.across[0]),
+                self.canvas.origin[1] + offset(idx / self.across[0]),
+            ),
+            size: crate::DOCUMENT_DIMENSION as f32 / self.factor as f32,
+        }
+    }
+    /// Join the downloaded images of every tile, in order, into one of [`Self::size`].
+    fn stitch(
+        &self,
+        tiles: &[Vec<[vulkano::half::f16; 4]>],
+    ) -> anyhow::Result<Vec<[vulkano::half::f16; 4]>> {
+        crate::export::stitch(
+            tiles,
+            crate::DOCUMENT_DIMENSION,
+            self.across,
+            self.margin,
+            self.size,
+        )
+    }
 }
 impl Renderer {
     fn new(context: Arc<crate::render_device::RenderContext>) -> anyhow::Result<Self> {
+        let timer = |pass| {
+            crate::diagnostics::GpuTimer::new(
+                context.clone(),
+                context.queues().graphics().queue().clone(),
+                pass,
+            )
+            .unwrap_or_else(|e| {
+                tracing::warn!(?pass, error = ?e, "failed to create GPU timer");
+                None
+            })
+        };
         Ok(Self {
+            tessellation_timer: timer(crate::diagnostics::GpuPass::Tessellation),
+            composite_timer: timer(crate::diagnostics::GpuPass::Composite),
             engines: Engines::new(context)?,
             data: hashbrown::HashMap::new(),
+            saved_diffs: hashbrown::HashMap::new(),
+            last_presented: None,
+            zoom: None,
+            lod: None,
+            zoomed: None,
+            solos: hashbrown::HashMap::new(),
+            visible: None,
         })
     }
-    fn render_one(
+    /// Rebuild the engines with shaders as they are now, dropping every render made with the old ones.
+    fn reload_shaders(&mut self) -> anyhow::Result<()> {
+        tracing::info!("reloading shaders");
+        self.engines = Engines::new(self.engines.context.clone())?;
+        self.forget_rendered();
+        Ok(())
+    }
+    /// Drop everything drawn so far, so that every document is drawn from scratch on its next update.
+    fn forget_rendered(&mut self) {
+        self.data.clear();
+        self.zoomed = None;
+        self.last_presented = None;
+        for saved in self.saved_diffs.values_mut() {
+            *saved = None;
+        }
+    }
+    /// Copy the document's image, as of the last [`Self::update_one`], into the preview, after `after`.
+    /// If the view is zoomed in, only the visible region is copied, redrawn at the preview's resolution.
+    #[tracing::instrument(level = "debug", skip(self, into, after))]
+    fn present_one(
         &mut self,
         id: state::document::ID,
         into: &Arc<vk::ImageView>,
-    ) -> anyhow::Result<vk::FenceSignalFuture<Box<dyn vk::sync::GpuFuture + Send>>> {
+        after: Box<dyn vk::sync::GpuFuture + Send>,
+    ) -> anyhow::Result<Presented> {
+        // The saved diff compares whole images, so it's never zoomed.
+        let zoom = self
+            .zoom
+            .filter(|&(zoom_id, _)| zoom_id == id && !self.saved_diffs.contains_key(&id));
+        if let Some((_, region)) = zoom {
+            return self.present_zoomed(id, region, into, after);
+        }
+        let data = self
+            .data
+            .get_mut(&id)
+            .ok_or_else(|| anyhow::anyhow!("document has not been rendered"))?;
+        if data.lod != 0 {
+            // Not the full image, so later presents can't be compared against it.
+            self.last_presented = None;
+            let fence = self.engines.copy_lod_to_preview_proxy(data, into, after)?;
+            return Ok(Presented {
+                fence,
+                dirty_mask: None,
+                region: data.region,
+                canvas: data.canvas,
+            });
+        }
+        let saved = match self.saved_diffs.get_mut(&id) {
+            Some(saved) => {
+                // Rendered once per save, the saved state never changes otherwise. Compared texel by texel, so
+                // over the same region as the present state.
+                let is_current = saved.as_ref().is_some_and(|saved| {
+                    saved.listener.is_at_saved() == Ok(true) && saved.region == data.region
+                });
+                if !is_current {
+                    let listener = crate::global::provider()
+                        .inspect(id, queue::DocumentCommandQueue::listen_from_saved)
+                        .ok_or_else(|| anyhow::anyhow!("document closed"))?;
+                    *saved = Some(self.engines.new_render_from_scrach(
+                        listener,
+                        Some(0),
+                        Some(data.region),
+                        self.solos.get(&id).copied(),
+                        true,
+                    )?);
+                }
+                saved.as_ref()
+            }
+            None => None,
+        };
+        // Tinted images can't be compared against, nor can another document's.
+        let previous = self.last_presented.take();
+        if saved.is_none() {
+            self.last_presented = Some(id);
+        }
+        let dirty_mask = if data.presented.is_some() && previous == Some(id) && saved.is_none() {
+            Some(self.engines.diff.dirty_mask()?)
+        } else {
+            None
+        };
+        if data.presented.is_none() {
+            data.presented = Some(self.engines.strokes.cleared_node_data()?);
+        }
+        let fence = self.engines.copy_document_to_preview_proxy(
+            data,
+            saved,
+            dirty_mask.as_ref(),
+            into,
+            after,
+        )?;
+        Ok(Presented {
+            fence,
+            dirty_mask,
+            region: data.region,
+            canvas: data.canvas,
+        })
+    }
+    /// Bring the render of the zoomed region up-to-date, and copy it into the preview.
+    fn present_zoomed(
+        &mut self,
+        id: state::document::ID,
+        region: DocumentRegion,
+        into: &Arc<vk::ImageView>,
+        after: Box<dyn vk::sync::GpuFuture + Send>,
+    ) -> anyhow::Result<Presented> {
+        let is_current = self
+            .zoomed
+            .as_ref()
+            .is_some_and(|(zoomed_id, requested, _)| *zoomed_id == id && *requested == region);
+        if is_current {
+            // Unwrap ok - just checked.
+            let (_, _, data) = self.zoomed.as_mut().unwrap();
+            if let Err(e) = Self::update_data(&self.engines, None, None, data, None, None, 0) {
+                self.zoomed = None;
+                return Err(e);
+            }
+            // Blurred further since, so the edges would sample past what was drawn.
+            if (data.region.size - region.size) / 2.0 < data.reach {
+                self.zoomed = None;
+            }
+        } else {
+            // Every leaf is drawn anew at the new scale. Drop the old render first, to conserve memory.
+            self.zoomed = None;
+        }
+        if self.zoomed.is_none() {
+            let (listener, reach) = crate::global::provider()
+                .inspect(id, |queue| {
+                    let reach = filter::reach(queue.peek_clone_state().graph());
+                    (queue.listen_from_now(), reach)
+                })
+                .ok_or_else(|| anyhow::anyhow!("document closed"))?;
+            // Filters sample past the edge, draw what they'd see there too.
+            let data = self.engines.new_render_from_scrach(
+                listener,
+                None,
+                Some(region.padded(reach)),
+                self.solos.get(&id).copied(),
+                true,
+            )?;
+            self.zoomed = Some((id, region, data));
+        }
+        // Unwrap ok - made current above.
+        let (_, _, data) = self.zoomed.as_ref().unwrap();
+        // A different region of the document, so later presents can't be compared against it.
+        self.last_presented = None;
+        let fence = self
+            .engines
+            .copy_document_to_preview_proxy(data, None, None, into, after)?;
+        Ok(Presented {
+            fence,
+            dirty_mask: None,
+            region: data.region,
+            canvas: data.canvas,
+        })
+    }
+    /// Set the region the document's view is zoomed into, or None if it isn't zoomed in far enough for it to
+    /// matter, and the level of detail it's zoomed out to. Returns whether the document needs to be drawn
+    /// again.
+    fn set_zoom(
+        &mut self,
+        id: state::document::ID,
+        region: Option<DocumentRegion>,
+        lod: u32,
+    ) -> bool {
+        let zoom = region.map(|region| (id, region));
+        let lod = (lod != 0).then_some((id, lod.min(LOD_LEVELS - 1)));
+        if self.zoom == zoom && self.lod == lod {
+            return false;
+        }
+        self.zoom = zoom;
+        self.lod = lod;
+        if zoom.is_none() {
+            // Not needed until zoomed in again, likely elsewhere.
+            self.zoomed = None;
+        }
+        true
+    }
+    /// Whether the strokes of the collection's layer that are about to be drawn may land within `visible`, as
+    /// least and greatest corner in document space. True if it can't be told, such as when their points aren't
+    /// resident.
+    fn strokes_in_view(
+        reader: &impl queue::state_reader::CommandQueueStateReader,
+        collection: state::stroke_collection::StrokeCollectionID,
+        layer_changes: &StrokeChanges,
+        [min, max]: [[f32; 2]; 2],
+    ) -> bool {
+        let Some((inner_transform, outer_transform)) =
+            reader
+                .graph()
+                .iter()
+                .find_map(|(_, data)| match data.leaf()? {
+                    graph::LeafType::StrokeLayer {
+                        collection: this_collection,
+                        inner_transform,
+                        outer_transform,
+                        ..
+                    } if *this_collection == collection => Some((inner_transform, outer_transform)),
+                    _ => None,
+                })
+        else {
+            // Removed, there's nothing to draw.
+            return false;
+        };
+        let Some(strokes) = reader.stroke_collections().get(collection) else {
+            return false;
+        };
+        let in_view = |stroke: &state::stroke_collection::ImmutableStroke| {
+            let Some(reach) = gpu_tess::StampReach::new(stroke, inner_transform) else {
+                return true;
+            };
+            let Some([low, high]) = reach.bounds(0..u32::MAX) else {
+                return false;
+            };
+            let (mut stroke_min, mut stroke_max) = ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]);
+            for corner in [low, [high[0], low[1]], [low[0], high[1]], high] {
+                let [x, y] = outer_transform.transform_point(corner);
+                stroke_min = [stroke_min[0].min(x), stroke_min[1].min(y)];
+                stroke_max = [stroke_max[0].max(x), stroke_max[1].max(y)];
+            }
+            stroke_min[0] < max[0]
+                && stroke_min[1] < max[1]
+                && stroke_max[0] > min[0]
+                && stroke_max[1] > min[1]
+        };
+        match layer_changes {
+            StrokeChanges::Add(which) => {
+                which.iter().filter_map(|&id| strokes.get(id)).any(in_view)
+            }
+            StrokeChanges::Invalidated => strokes.iter_active().any(in_view),
+        }
+    }
+    /// Set the part of the document in view, as a position and size in document space, or None if it isn't
+    /// known. Layers in view are drawn first, and a render cut short by a newer change is still shown if only
+    /// layers out of view were left undone.
+    fn set_visible(
+        &mut self,
+        id: state::document::ID,
+        visible: Option<(cgmath::Point2<f32>, cgmath::Vector2<f32>)>,
+    ) {
+        self.visible = visible
+            .map(|(min, extent)| (id, [[min.x, min.y], [min.x + extent.x, min.y + extent.y]]));
+    }
+    /// The level of detail to composite the document at. Only ever reduced for the document in view, and not
+    /// while it's zoomed in or compared against its saved state, which need the whole image.
+    fn lod_of(&self, id: state::document::ID) -> u32 {
+        match self.lod {
+            Some((lod_id, lod))
+                if lod_id == id && self.zoom.is_none() && !self.saved_diffs.contains_key(&id) =>
+            {
+                lod
+            }
+            _ => 0,
+        }
+    }
+    /// Start or stop highlighting what changed in the document since it was saved.
+    /// Returns whether the document needs to be presented again.
+    fn set_saved_diff(&mut self, id: state::document::ID, enabled: bool) -> bool {
+        if enabled {
+            let newly = !self.saved_diffs.contains_key(&id);
+            self.saved_diffs.entry(id).or_insert(None);
+            newly
+        } else {
+            self.saved_diffs.remove(&id).is_some()
+        }
+    }
+    /// Download the up-to-date document and write it out on a background thread.
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn export(
+        &mut self,
+        id: state::document::ID,
+        settings: crate::export::ExportSettings,
+    ) -> anyhow::Result<()> {
+        let name = settings
+            .path
+            .file_name()
+            .map_or_else(
+                || settings.path.to_string_lossy(),
+                std::ffi::OsStr::to_string_lossy,
+            )
+            .into_owned();
+        let progress = crate::global::tasks::begin(format!("Exporting {name}"));
+        // The export must be of the latest state at full resolution, there is no newer render to defer to.
+        let _ = self.update_at(id, None, 0)?;
+        let factor = settings.supersample.factor();
+        let (mut listeners, has_references, (metadata, dpi), tiles) = crate::global::provider()
+            .inspect(id, |queue| {
+                let state = queue.peek_clone_state();
+                let has_references = state
+                    .graph()
+                    .iter()
+                    .any(|(_, data)| data.is_reference() && !data.is_deleted());
+                let metadata = (
+                    state.document().metadata.read().clone(),
+                    state.document().viewport.dpi(),
+                );
+                let tiles = CanvasTiles::new(
+                    state.document().viewport.canvas(),
+                    factor,
+                    filter::reach(state.graph()),
+                );
+                // One for each render, all of the same state.
+                let listeners: Vec<_> = (0..tiles.len()).map(|_| queue.listen_from_now()).collect();
+                (listeners, has_references, metadata, tiles)
+            })
+            .ok_or_else(|| anyhow::anyhow!("document closed"))?;
+        let own = self
+            .data
+            .get(&id)
+            .filter(|data| factor == 1 && data.region == DocumentRegion::covering(tiles.canvas));
+        let texels = match own {
+            // The document's own render already covers the canvas at full resolution, and nothing's hidden from
+            // or added to it.
+            Some(data) if !self.solos.contains_key(&id) && !has_references => {
+                let texels = self.engines.download_document(data).await?;
+                // The canvas is at the render's least corner.
+                crate::export::stitch(&[texels], crate::DOCUMENT_DIMENSION, [1, 1], 0, tiles.size)?
+            }
+            // Otherwise it's drawn in tiles as large as the document's own image, each a region of the canvas,
+            // and joined.
+            _ => {
+                let mut images = Vec::with_capacity(listeners.len());
+                for (idx, listener) in (0u32..).zip(listeners.drain(..)) {
+                    let data = self.engines.new_render_from_scrach(
+                        listener,
+                        None,
+                        Some(tiles.region(idx)),
+                        None,
+                        false,
+                    )?;
+                    images.push(self.engines.download_document(&data).await?);
+                }
+                tiles.stitch(&images)?
+            }
+        };
+        // Encoding and compression are slow, don't hold up rendering.
+        std::thread::spawn(move || {
+            let start = std::time::Instant::now();
+            match crate::export::write(&texels, tiles.size, &settings, &metadata, dpi, &progress) {
+                Ok(()) => tracing::info!(
+                    path = ?settings.path,
+                    "exported in {}ms",
+                    start.elapsed().as_millis()
+                ),
+                Err(e) if e.is::<fuzzpaint_core::progress::Cancelled>() => {
+                    tracing::info!(path = ?settings.path, "cancelled export");
+                }
+                Err(e) => crate::errors::Report::new(
+                    crate::errors::Severity::Recoverable,
+                    tr!("export-failed", path = settings.path.display().to_string()),
+                    &e,
+                )
+                .send(),
+            }
+        });
+        Ok(())
+    }
+    /// Fit the canvas to the up-to-date document's content within it, as it would be exported, see
+    /// [`crate::canvas::content_region`].
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn autocrop(&mut self, id: state::document::ID) -> anyhow::Result<()> {
+        let listener = crate::global::provider()
+            .inspect(id, queue::DocumentCommandQueue::listen_from_now)
+            .ok_or_else(|| anyhow::anyhow!("document closed"))?;
+        // Drawn apart from the document's own render, which may be zoomed in or showing references.
+        let data = self
+            .engines
+            .new_render_from_scrach(listener, None, None, None, false)?;
+        let texels = self.engines.download_document(&data).await?;
+        let (region, canvas) = (data.region, data.canvas);
+        let content = crate::canvas::content_region(&texels, crate::DOCUMENT_DIMENSION)?
+            .map(|texels| {
+                let to_document = |[x, y]: [f32; 2]| {
+                    [
+                        region.origin.x + x / region.scale(),
+                        region.origin.y + y / region.scale(),
+                    ]
+                };
+                texels.map(to_document)
+            })
+            .and_then(|[min, max]| {
+                // Whatever's drawn past the canvas stays hidden.
+                let [canvas_min, canvas_max] = canvas.corners();
+                let min = [min[0].max(canvas_min[0]), min[1].max(canvas_min[1])];
+                let max = [max[0].min(canvas_max[0]), max[1].min(canvas_max[1])];
+                (min[0] < max[0] && min[1] < max[1]).then_some([min, max])
+            });
+        match content {
+            Some(region) => crate::canvas::set_canvas(id, crate::canvas::covering(region)),
+            None => {
+                tracing::info!("nothing drawn to trim to");
+                Ok(())
+            }
+        }
+    }
+    /// Render each top-level node of the up-to-date document on its own, and the whole document if asked, then
+    /// write them out on a background thread. Each is of the canvas, at one texel per pixel. Reference leaves
+    /// aren't exported.
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn export_layers(
+        &mut self,
+        id: state::document::ID,
+        settings: crate::export::LayerExportSettings,
+    ) -> anyhow::Result<()> {
+        let progress =
+            crate::global::tasks::begin(format!("Exporting layers to {}", settings.dir.display()));
+        let (layers, listeners, (metadata, dpi), tiles) = crate::global::provider()
+            .inspect(id, |queue| {
+                let state = queue.peek_clone_state();
+                let layers: Vec<_> = state
+                    .graph()
+                    .iter_top_level()
+                    .filter(|(_, data)| !data.is_deleted() && !data.is_reference())
+                    .map(|(node, data)| (node, data.name().to_owned()))
+                    .collect();
+                let metadata = (
+                    state.document().metadata.read().clone(),
+                    state.document().viewport.dpi(),
+                );
+                let tiles = CanvasTiles::new(
+                    state.document().viewport.canvas(),
+                    1,
+                    filter::reach(state.graph()),
+                );
+                // One for each tile of each render, all of the same state.
+                let renders = layers.len() + usize::from(settings.composite);
+                let listeners: Vec<_> = (0..renders * tiles.len() as usize)
+                    .map(|_| queue.listen_from_now())
+                    .collect();
+                (layers, listeners, metadata, tiles)
+            })
+            .ok_or_else(|| anyhow::anyhow!("document closed"))?;
+        let mut listeners = listeners.into_iter();
+        // Each node on its own, then the whole document if asked.
+        let solos: Vec<_> = layers
+            .iter()
+            .map(|&(node, _)| Some(node))
+            .chain(settings.composite.then_some(None))
+            .collect();
+        let mut images = Vec::with_capacity(solos.len());
+        for solo in solos {
+            let mut parts = Vec::with_capacity(tiles.len() as usize);
+            for (idx, listener) in (0..tiles.len()).zip(listeners.by_ref()) {
+                let data = self.engines.new_render_from_scrach(
+                    listener,
+                    None,
+                    Some(tiles.region(idx)),
+                    solo,
+                    false,
+                )?;
+                parts.push(self.engines.download_document(&data).await?);
+            }
+            images.push(tiles.stitch(&parts)?);
+        }
+        let composite = settings.composite.then(|| images.pop()).flatten();
+        let images: Vec<_> = layers
+            .into_iter()
+            .map(|(_, name)| name)
+            .zip(images)
+            .collect();
+        // Encoding and compression are slow, don't hold up rendering.
+        std::thread::spawn(move || {
+            let start = std::time::Instant::now();
+            match crate::export::write_layers(
+                &images,
+                composite.as_deref(),
+                tiles.size,
+                &settings,
+                &metadata,
+                dpi,
+                &progress,
+            ) {
+                Ok(()) => tracing::info!(
+                    dir = ?settings.dir,
+                    "exported {} layers in {}ms",
+                    images.len(),
+                    start.elapsed().as_millis()
+                ),
+                Err(e) if e.is::<fuzzpaint_core::progress::Cancelled>() => {
+                    tracing::info!(dir = ?settings.dir, "cancelled layer export");
+                }
+                Err(e) => crate::errors::Report::new(
+                    crate::errors::Severity::Recoverable,
+                    tr!(
+                        "export-layers-failed",
+                        directory = settings.dir.display().to_string()
+                    ),
+                    &e,
+                )
+                .send(),
+            }
+        });
+        Ok(())
+    }
+    /// Replay the document's history from the start, handing a frame to a background thread to encode every
+    /// [`crate::export::TimelapseSettings::interval`] commands. The document's own render is left untouched.
+    ///
+    /// Every frame is of the canvas as it is now, at the resolution of the document's own image.
+    ///
+    /// Blocks rendering of every document until the replay reaches the present.
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn timelapse(
+        &mut self,
+        id: state::document::ID,
+        settings: crate::export::TimelapseSettings,
+    ) -> anyhow::Result<()> {
+        let (trimmed, canvas) = crate::global::provider()
+            .inspect(id, |queue| {
+                (
+                    queue.is_trimmed(),
+                    queue.peek_clone_state().document().viewport.canvas(),
+                )
+            })
+            .ok_or_else(|| anyhow::anyhow!("document closed"))?;
+        // Trimmed history is replayed from the spool, onto a queue of its own kept for as long as the replay.
+        let rebuilt = if trimmed {
+            crate::global::history_spool::rebuild(id)?
+        } else {
+            None
+        };
+        let (listener, (commands, _)) = match &rebuilt {
+            Some(queue) => (queue.listen_from_start(), queue.history_depth()),
+            None => crate::global::provider()
+                .inspect(id, |queue| {
+                    (queue.listen_from_start(), queue.history_depth())
+                })
+                .ok_or_else(|| anyhow::anyhow!("document closed"))?,
+        };
+        if !crate::global::history_spool::is_complete(id) {
+            tracing::info!(
+                "oldest history was deleted, the timelapse starts from the oldest step kept"
+            );
+        }
+        // Fixed, rather than following the canvas as it changes through history.
+        let region = DocumentRegion::covering(canvas);
+        // Video frames must be of even size.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let size = canvas
+            .size
+            .map(|side| (((side * region.scale()).round() as u32) & !1).max(2));
+        let mut encoder = crate::export::TimelapseEncoder::new(&settings, size)?;
+        let progress = crate::global::tasks::begin("Exporting timelapse");
+        // The replay is kept apart from the document's data, under an ID no document has.
+        let replay_id = state::document::ID::default();
+        let data = self.engines.new_render_from_scrach(
+            listener,
+            Some(settings.interval.get()),
+            Some(region),
+            None,
+            false,
+        )?;
+        self.data.insert(replay_id, data);
+        // Few frames in flight, they're large.
+        let (send, recv) = std::sync::mpsc::sync_channel::<Vec<[vulkano::half::f16; 4]>>(2);
+        let path = settings.path.clone();
+        std::thread::spawn(move || {
+            let start = std::time::Instant::now();
+            let result = recv
+                .into_iter()
+                // The canvas is at the image's least corner.
+                .try_for_each(|frame| {
+                    let frame = crate::export::stitch(
+                        &[frame],
+                        crate::DOCUMENT_DIMENSION,
+                        [1, 1],
+                        0,
+                        size,
+                    )?;
+                    encoder.push(&frame)
+                })
+                .and_then(|()| encoder.finish());
+            match result {
+                Ok(frames) => tracing::info!(
+                    ?path,
+                    "exported {frames} frame timelapse in {}ms",
+                    start.elapsed().as_millis()
+                ),
+                Err(e) => crate::errors::Report::new(
+                    crate::errors::Severity::Recoverable,
+                    tr!("export-timelapse-failed", path = path.display().to_string()),
+                    &e,
+                )
+                .send(),
+            }
+        });
+
+        // The start of history, then one for every step. The last step may be short. The start is empty unless
+        // the document was opened with content or its oldest history has been deleted.
+        let frames = commands.div_ceil(settings.interval.get());
+        progress.set_total(frames as u64 + 1);
+        let result = async {
+            for frame in 0..=frames {
+                progress.check()?;
+                if frame != 0 {
+                    let _ = self.update_one(replay_id, None)?;
+                }
+                let data = self
+                    .data
+                    .get(&replay_id)
+                    .ok_or_else(|| anyhow::anyhow!("document closed"))?;
+                let texels = self.engines.download_document(data).await?;
+                // Encoder failed, it will report why.
+                send.send(texels)
+                    .map_err(|_| anyhow::anyhow!("timelapse encoder stopped"))?;
+                progress.advance(1);
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        self.data.remove(&replay_id);
+        // Hang up, letting the encoder finish. If cancelled, with the frames so far.
+        drop(send);
+        match result {
+            Err(e) if e.is::<fuzzpaint_core::progress::Cancelled>() => {
+                tracing::info!("cancelled timelapse");
+                Ok(())
+            }
+            result => result,
+        }
+    }
+    /// Render the filter node with the given parameters, without them being committed to the document.
+    /// Returns whether the document needs to be redrawn.
+    fn preview_filter(
+        &mut self,
+        id: state::document::ID,
+        node: graph::NodeID,
+        filter: fuzzpaint_core::filter::Filter,
+    ) -> bool {
+        let Some(data) = self.data.get_mut(&id) else {
+            // Not rendered yet, so there's nothing to preview over.
+            return false;
+        };
+        if data.filter_previews.get(&node) == Some(&filter) {
+            return false;
+        }
+        data.filter_previews.insert(node, filter);
+        // Needs recompile.
+        let _ = data.compiled_blend.take();
+        true
+    }
+    /// Composite just the node and its children, as if nothing else were in the graph, or the whole graph
+    /// again if `None`. Only the view changes, exports are always of the whole document.
+    /// Returns whether the document needs to be redrawn.
+    fn set_solo(&mut self, id: state::document::ID, node: Option<graph::AnyID>) -> bool {
+        let old = match node {
+            Some(node) => self.solos.insert(id, node),
+            None => self.solos.remove(&id),
+        };
+        if old == node {
+            return false;
+        }
+        let zoomed = self
+            .zoomed
+            .as_mut()
+            .filter(|(zoomed_id, ..)| *zoomed_id == id)
+            .map(|(.., data)| data);
+        for data in self.data.get_mut(&id).into_iter().chain(zoomed) {
+            data.solo = node;
+            // Needs recompile.
+            let _ = data.compiled_blend.take();
+        }
+        if let Some(saved) = self.saved_diffs.get_mut(&id) {
+            // Rendered anew, as it's never otherwise updated.
+            *saved = None;
+        }
+        true
+    }
+    /// Bring the document's render data up-to-date with its state.
+    ///
+    /// If `cancel` is given and it is notified of a newer change partway through, the remaining layers are left for
+    /// the next update and `Break` is returned. The render data is then not fit to be shown.
+    fn update_one(
+        &mut self,
+        id: state::document::ID,
+        cancel: Option<&Cancellation>,
+    ) -> anyhow::Result<std::ops::ControlFlow<()>> {
+        self.update_at(id, cancel, self.lod_of(id))
+    }
+    /// [`Self::update_one`], compositing at the given level of detail.
+    fn update_at(
+        &mut self,
+        id: state::document::ID,
+        cancel: Option<&Cancellation>,
+        lod: u32,
+    ) -> anyhow::Result<std::ops::ControlFlow<()>> {
+        let visible = self
+            .visible
+            .and_then(|(visible_id, visible)| (visible_id == id).then_some(visible));
         let data = self.data.entry(id);
         // Get the document data to update.
         let data = match data {
@@ -59,40 +967,64 @@ impl Renderer {
                     anyhow::bail!("Document deleted before render worker reached it");
                 };
 
-                let data = v.insert(self.engines.new_render_from_scrach(listener)?);
-
-                // Then copy to the preview.
-                return self
-                    .engines
-                    .copy_document_to_preview_proxy(data, into)
-                    .map_err(Into::into);
+                let data = v.insert(self.engines.new_render_from_scrach(
+                    listener,
+                    None,
+                    None,
+                    self.solos.get(&id).copied(),
+                    true,
+                )?);
+                if lod == 0 {
+                    return Ok(std::ops::ControlFlow::Continue(()));
+                }
+                // Composited at full resolution, do it again at the lower level.
+                data
             }
         };
 
+        let result = Self::update_data(
+            &self.engines,
+            self.tessellation_timer.as_ref(),
+            self.composite_timer.as_ref(),
+            data,
+            cancel,
+            visible,
+            lod,
+        );
+        if result.is_err() {
+            // Destroy the render data, to be redrawn from scratch if the document is still there.
+            // Could be closed, or a thrashed document state D:
+            self.data.remove(&id);
+            self.engines.strokes.clips_maybe_dropped();
+        }
+        self.engines.strokes.forget_dropped_clips();
+        result
+    }
+    /// Draw the changes to the document since the render data was last updated, and composite them at the
+    /// given level of detail. See [`Self::update_one`]. Stroke layers within `visible` are drawn first, see
+    /// [`Self::set_visible`].
+    #[allow(clippy::too_many_arguments)]
+    fn update_data(
+        engines: &Engines,
+        tessellation_timer: Option<&crate::diagnostics::GpuTimer>,
+        composite_timer: Option<&crate::diagnostics::GpuTimer>,
+        data: &mut PerDocumentData,
+        cancel: Option<&Cancellation>,
+        visible: Option<[[f32; 2]; 2]>,
+        lod: u32,
+    ) -> anyhow::Result<std::ops::ControlFlow<()>> {
         // Forward the listener state.
-        let changes = match data.listener.forward_clone_state() {
-            Ok(changes) => changes,
-            Err(e) => {
-                // Destroy the render data, report the error.
-                // Could be closed, or a thrashed document state D:
-                self.data.remove(&id);
-                return Err(e.into());
-            }
-        };
+        let changes = match data.replay_step {
+            Some(step) => data.listener.forward_clone_state_by(step),
+            None => data.listener.forward_clone_state(),
+        }?;
         let graph = changes.graph();
 
-        // Draw just the changes!
-        enum StrokeChanges {
-            // Strokes were added
-            Add(Vec<state::stroke_collection::ImmutableStrokeID>),
-            // Big change, redraw from scratch.
-            Invalidated,
-        }
-
-        let mut stroke_changes =
-            hashbrown::HashMap::<state::stroke_collection::StrokeCollectionID, StrokeChanges>::new(
-            );
+        // Draw just the changes! Including any left over from an abandoned render.
+        let mut stroke_changes = std::mem::take(&mut data.pending_strokes);
         let mut graph_invalidated = false;
+        // Image and gradient leaves to redraw.
+        let mut image_changes = hashbrown::HashSet::<graph::LeafID>::new();
 
         let mut analyze_change = |change| -> std::ops::ControlFlow<()> {
             use fuzzpaint_core::commands::{
@@ -146,19 +1078,32 @@ impl Renderer {
                         graph::LeafType::StrokeLayer { collection, .. } => {
                             let _ = stroke_changes.insert(*collection, StrokeChanges::Invalidated);
                         }
+                        graph::LeafType::Image { .. } => {
+                            image_changes.insert(*target);
+                        }
                         _ => unimplemented!(),
                     }
                 }
+                // Could've replaced the image or gradient.
+                DoUndo::Do(Command::Graph(GraphCommand::LeafTyChanged { target, .. }))
+                | DoUndo::Undo(Command::Graph(GraphCommand::LeafTyChanged { target, .. })) => {
+                    image_changes.insert(*target);
+                    graph_invalidated = true;
+                }
                 // All other modifications require graph rebuild.
                 DoUndo::Do(Command::Graph(_)) | DoUndo::Undo(Command::Graph(_)) => {
                     graph_invalidated = true;
                 }
-                // Palettes influence the blend graph and possibly every stroke layer. Uh oh.
+                // Palettes influence the blend graph and possibly every stroke and gradient layer. Uh oh.
                 // Invalidate everything, and make this better future me!!!
                 DoUndo::Do(Command::Palette(_)) | DoUndo::Undo(Command::Palette(_)) => {
                     for &key in changes.stroke_collections().0.keys() {
                         let _ = stroke_changes.insert(key, StrokeChanges::Invalidated);
                     }
+                    image_changes.extend(graph.iter().filter_map(|(id, node)| match node.leaf() {
+                        Some(graph::LeafType::Gradient { .. }) => graph::LeafID::try_from(id).ok(),
+                        _ => None,
+                    }));
                     graph_invalidated = true;
                     // Invalidated literally everything lmao, no need to keep looking at deltas.
                     return std::ops::ControlFlow::Break(());
@@ -166,6 +1111,8 @@ impl Renderer {
                 // Commands must be externally flattened.
                 DoUndo::Do(Command::Meta(MetaCommand::Scope(..)))
                 | DoUndo::Undo(Command::Meta(MetaCommand::Scope(..))) => unreachable!(),
+                // Moves the region, if it follows the canvas. Checked against the state, below.
+                DoUndo::Do(Command::Document(_)) | DoUndo::Undo(Command::Document(_)) => (),
                 // No influence on rendering.
                 DoUndo::Do(Command::Meta(_) | Command::Dummy)
                 | DoUndo::Undo(Command::Meta(_) | Command::Dummy) => (),
@@ -201,33 +1148,95 @@ impl Renderer {
             }
         }
 
+        data.canvas = changes.document().viewport.canvas();
+        let covering = DocumentRegion::covering(data.canvas);
+        if data.follows_canvas && data.region != covering {
+            // Everything is drawn anew over the canvas' new region.
+            data.region = covering;
+            // Nor can the image be compared against the last one shown.
+            data.presented = None;
+            for &key in changes.stroke_collections().0.keys() {
+                let _ = stroke_changes.insert(key, StrokeChanges::Invalidated);
+            }
+            image_changes.extend(graph.iter().filter_map(|(id, node)| match node.leaf() {
+                Some(graph::LeafType::Image { .. } | graph::LeafType::Gradient { .. }) => {
+                    graph::LeafID::try_from(id).ok()
+                }
+                _ => None,
+            }));
+            graph_invalidated = true;
+        }
+
         let mut fences = vec![];
 
         if graph_invalidated {
-            log::trace!("Scouring allocations");
+            tracing::trace!("scouring allocations");
             // Needs recompile.
             let _ = data.compiled_blend.take();
-            self.engines
-                .allocate_prune_graph(&mut data.graph_render_data, changes.graph())?;
+            // Edits were committed or superseded.
+            data.filter_previews.clear();
+            let allocated =
+                engines.allocate_prune_graph(&mut data.graph_render_data, changes.graph())?;
+            data.stale_lods.extend(allocated.iter().copied());
+            image_changes.extend(allocated);
         }
 
-        for (collection, stroke_changes) in stroke_changes {
-            let (graph_id, leaf) = changes
-                .graph()
-                .iter()
-                .find_map(|(id, data)| {
-                    // If this node is a stroke layer with our same collection ID, then we found it!
-                    let this_leaf = data.leaf().filter(|leaf| match leaf {
-                        graph::LeafType::StrokeLayer {
-                            collection: this_leaf,
-                            ..
-                        } => collection == *this_leaf,
-                        _ => false,
-                    });
+        let tessellation_timer = tessellation_timer
+            .filter(|_| crate::diagnostics::enabled() && !stroke_changes.is_empty());
+        if let Some(timer) = tessellation_timer {
+            log_timer_error(timer.submit_begin());
+        }
 
-                    this_leaf.map(|leaf| (id, leaf))
-                })
-                .ok_or_else(|| anyhow::anyhow!("delta references non-existent node"))?;
+        // Layers out of view, which are drawn last and may be left undone without holding up the rest. Only
+        // worth finding if the render may be cut short.
+        let out_of_view: hashbrown::HashSet<_> = visible
+            .filter(|_| cancel.is_some())
+            .map(|[min, max]| {
+                // Filters may pull in what lies just out of view.
+                let reach = filter::reach(changes.graph());
+                let visible = [
+                    [min[0] - reach, min[1] - reach],
+                    [max[0] + reach, max[1] + reach],
+                ];
+                stroke_changes
+                    .iter()
+                    .filter(|(collection, layer_changes)| {
+                        !Self::strokes_in_view(&changes, **collection, layer_changes, visible)
+                    })
+                    .map(|(&collection, _)| collection)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut stroke_changes: Vec<_> = stroke_changes.into_iter().collect();
+        stroke_changes.sort_by_key(|(collection, _)| out_of_view.contains(collection));
+
+        let mut stroke_changes = stroke_changes.into_iter();
+        let mut is_first = true;
+        while let Some((collection, layer_changes)) = stroke_changes.next() {
+            // Always make progress, so constant changes can't starve the render entirely.
+            if !std::mem::take(&mut is_first) && cancel.is_some_and(Cancellation::is_cancelled) {
+                tracing::trace!("abandoning outdated render");
+                data.pending_strokes.insert(collection, layer_changes);
+                data.pending_strokes.extend(stroke_changes);
+                break;
+            }
+
+            let Some((graph_id, leaf)) = changes.graph().iter().find_map(|(id, data)| {
+                // If this node is a stroke layer with our same collection ID, then we found it!
+                let this_leaf = data.leaf().filter(|leaf| match leaf {
+                    graph::LeafType::StrokeLayer {
+                        collection: this_leaf,
+                        ..
+                    } => collection == *this_leaf,
+                    _ => false,
+                });
+
+                this_leaf.map(|leaf| (id, leaf))
+            }) else {
+                // Left over from an abandoned render, and the layer has since been removed.
+                // Nothing to draw into!
+                continue;
+            };
 
             let graph::LeafType::StrokeLayer {
                 blend,
@@ -255,7 +1264,7 @@ impl Renderer {
                 .get(*collection)
                 .ok_or_else(|| anyhow::anyhow!("delta references non-existent collection"))?;
 
-            let which = match &stroke_changes {
+            let which = match &layer_changes {
                 StrokeChanges::Add(which) => {
                     // Draw selected.
                     Some(which.as_slice())
@@ -266,22 +1275,81 @@ impl Renderer {
                 }
             };
 
-            if let Some(fence) = self.engines.stroke_layer(
+            if let Some(fence) = engines.stroke_layer(
                 collection,
                 inner_transform,
                 outer_transform,
                 changes.palette(),
                 render_data,
+                data.region,
                 which,
             )? {
                 fences.push(fence);
             }
+            data.stale_lods.insert(graph_id);
+        }
+
+        for id in image_changes {
+            let leaf = changes.graph().get(id).and_then(graph::NodeData::leaf);
+            let render_data = || {
+                data.graph_render_data
+                    .leaves
+                    .get(&id)
+                    .ok_or_else(|| anyhow::anyhow!("missing render data for {id:?}"))
+            };
+            match leaf {
+                Some(graph::LeafType::Image {
+                    image,
+                    outer_transform,
+                    ..
+                }) => fences.push(engines.images.draw(
+                    &changes.document().assets.read(),
+                    *image,
+                    outer_transform,
+                    render_data()?,
+                    data.region,
+                )?),
+                Some(graph::LeafType::Gradient { gradient, .. }) => {
+                    fences.push(engines.gradients.draw(
+                        gradient,
+                        changes.palette(),
+                        render_data()?,
+                        data.region,
+                    )?)
+                }
+                // Neither, or since removed.
+                _ => continue,
+            }
+            data.stale_lods.insert(id);
         }
 
         for fence in fences {
             // Blegh. No way to do better express this with current vulkano sync.
             fence.wait(None)?;
         }
+        if let Some(timer) = tessellation_timer {
+            log_timer_error(timer.submit_end_and_publish());
+        }
+        if data
+            .pending_strokes
+            .keys()
+            .any(|collection| !out_of_view.contains(collection))
+        {
+            // Don't bother compositing, it'll be redone with the newer state. If only layers out of view were left,
+            // what's in view is up-to-date and worth showing meanwhile.
+            return Ok(std::ops::ControlFlow::Break(()));
+        }
+
+        if data.lod != lod {
+            // Needs recompile, with views of the new level.
+            data.lod = lod;
+            let _ = data.compiled_blend.take();
+        }
+        if lod != 0 && !data.stale_lods.is_empty() {
+            let leaves = &data.graph_render_data.leaves;
+            // Removed leaves are skipped.
+            engines.generate_lods(data.stale_lods.drain().filter_map(|id| leaves.get(&id)))?;
+        }
 
         // This has to be *after* stroke render, for some reason, or the layers don't show up at all.
         // Probably something wrong with the internal layout transitions. ;;;w;;;
@@ -289,23 +1357,42 @@ impl Renderer {
         let compiled_blend = match &mut data.compiled_blend {
             Some(c) => c,
             None => {
-                log::trace!("Recompiling blend graph");
+                tracing::trace!("recompiling blend graph");
                 // Drop old one before building anew, to conserve mem. This could be delta'd instead to re-use old work, todo.
                 let _ = data.compiled_blend.take();
-                let invocation = self.engines.compile_blend_graph(
+                data.reach = filter::reach(changes.graph());
+                let invocation = engines.compile_blend_graph(
                     changes.graph(),
                     &data.graph_render_data,
+                    &data.filter_previews,
+                    data.solo,
+                    data.references,
                     changes.palette(),
                     &data.render_target,
+                    lod,
+                    data.region.scale(),
                 )?;
 
                 data.compiled_blend.insert(invocation)
             }
         };
 
+        let composite_timer = composite_timer.filter(|_| crate::diagnostics::enabled());
+        if let Some(timer) = composite_timer {
+            log_timer_error(timer.submit_begin());
+        }
         compiled_blend.execute()?;
+        if let Some(timer) = composite_timer {
+            log_timer_error(timer.submit_end_and_publish());
+        }
 
-        self.engines.copy_document_to_preview_proxy(data, into)
+        Ok(std::ops::ControlFlow::Continue(()))
+    }
+}
+/// Timings are only diagnostic, and shouldn't fail a render.
+fn log_timer_error(result: anyhow::Result<()>) {
+    if let Err(e) = result {
+        tracing::warn!(error = ?e, "GPU timer failed");
     }
 }
 /// Struct that contains all the compiled GPU logic.
@@ -314,7 +1401,10 @@ struct Engines {
     strokes: stroke_renderer::StrokeLayerRenderer,
     text_builder: crate::text::Builder,
     text: crate::text::renderer::monochrome::Renderer,
+    images: image_leaf::ImageLeafRenderer,
+    gradients: gradient_leaf::GradientLeafRenderer,
     blend: Arc<blender::BlendEngine>,
+    diff: diff::DiffEngine,
 }
 impl Engines {
     fn new(context: Arc<crate::render_device::RenderContext>) -> anyhow::Result<Self> {
@@ -325,43 +1415,66 @@ impl Engines {
                 context.allocators().memory().clone(),
             )?,
             text: crate::text::renderer::monochrome::Renderer::new(context.clone())?,
+            images: image_leaf::ImageLeafRenderer::new(context.clone()),
+            gradients: gradient_leaf::GradientLeafRenderer::new(context.clone())?,
+            diff: diff::DiffEngine::new(context.clone())?,
             strokes: stroke_renderer::StrokeLayerRenderer::new(context)?,
         })
     }
-    /// Compile a GPU blend invocation for blending a document into an image.
-    /// The `graph_render_data` should be fully populated with allocated images for any nodes or leaves that make use of images.
+    /// Compile a GPU blend invocation for blending a document into an image, at the given level of detail of
+    /// every image. The `graph_render_data` should be fully populated with allocated images for any nodes or
+    /// leaves that make use of images. If `solo` is a node in the graph, it alone is blended. Reference leaves are
+    /// skipped unless `references` is set.
     ///
     /// Reuse this invocation as much as possible!
+    #[allow(clippy::too_many_arguments)]
     fn compile_blend_graph(
         &self,
         graph: &graph::BlendGraph,
         graph_render_data: &GraphImages,
+        filter_previews: &FilterPreviews,
+        solo: Option<graph::AnyID>,
+        references: bool,
         palette: &state::palette::Palette,
         into: &NodeRenderData,
+        lod: u32,
+        scale: f32,
     ) -> anyhow::Result<blender::BlendInvocation> {
         use graph::{LeafType, NodeID, NodeType};
         /// Insert a single node (possibly recursing) into the builder.
+        #[allow(clippy::too_many_arguments)]
         fn insert_blend(
             blend_engine: &Arc<blender::BlendEngine>,
             builder: &mut blender::BlendInvocationBuilder,
             graph_render_data: &GraphImages,
+            filter_previews: &FilterPreviews,
+            references: bool,
             graph: &graph::BlendGraph,
             palette: &state::palette::Palette,
+            lod: u32,
 
             id: graph::AnyID,
             data: &graph::NodeData,
         ) -> anyhow::Result<()> {
+            if data.is_reference() && !references {
+                return Ok(());
+            }
             match (data.leaf(), data.node()) {
                 // Pre-rendered leaves
                 (
-                    Some(LeafType::StrokeLayer { blend, .. } | LeafType::Text { blend, .. }),
+                    Some(
+                        LeafType::StrokeLayer { blend, .. }
+                        | LeafType::Text { blend, .. }
+                        | LeafType::Image { blend, .. }
+                        | LeafType::Gradient { blend, .. },
+                    ),
                     None,
                 ) => {
                     let view = graph_render_data
                         .leaves
                         .get(&graph::LeafID::try_from(id).unwrap())
                         .ok_or_else(|| anyhow::anyhow!("blend data not found for leaf {id:?}"))?
-                        .view
+                        .lod(lod)
                         .clone();
                     builder.then_blend(blender::BlendImageSource::Immediate(view), *blend)?;
                 }
@@ -382,29 +1495,42 @@ impl Engines {
                         blend_engine,
                         builder,
                         graph_render_data,
+                        filter_previews,
+                        references,
                         graph,
                         palette,
+                        lod,
                         id.try_into().unwrap(),
                     )?;
                 }
-                // Grouped blend - add children to a new blend worker.
-                (None, Some(NodeType::GroupedBlend(blend))) => {
-                    let handle = blend_for_node(
+                // Grouped blend - add children to a new blend worker, filtering the result if requested.
+                (
+                    None,
+                    Some(node @ (NodeType::GroupedBlend(blend) | NodeType::Filtered { blend, .. })),
+                ) => {
+                    let node_id = graph::NodeID::try_from(id).unwrap();
+                    let mut group = blend_for_node(
                         blend_engine,
                         graph_render_data,
+                        filter_previews,
+                        references,
                         graph,
                         palette,
-                        id.try_into().unwrap(),
+                        lod,
+                        node_id,
                         graph_render_data
                             .nodes
-                            .get(&graph::NodeID::try_from(id).unwrap())
+                            .get(&node_id)
                             .ok_or_else(|| anyhow::anyhow!("blend data not found for group {id:?}"))
                             .unwrap()
-                            .view
+                            .lod(lod)
                             .clone(),
                         true,
                     )?;
-                    builder.then_blend(handle.into(), *blend)?;
+                    if let Some(filter) = filter_previews.get(&node_id).copied().or(node.filter()) {
+                        group.filter(filter);
+                    }
+                    builder.then_blend(group.nest().into(), *blend)?;
                 }
                 // Invalid states
                 (Some(_), Some(_)) | (None, None) => unreachable!(),
@@ -413,12 +1539,16 @@ impl Engines {
         }
 
         /// Recursively add children into existing blend builder.
+        #[allow(clippy::too_many_arguments)]
         fn blend_for_passthrough(
             blend_engine: &Arc<blender::BlendEngine>,
             builder: &mut blender::BlendInvocationBuilder,
             graph_render_data: &GraphImages,
+            filter_previews: &FilterPreviews,
+            references: bool,
             graph: &graph::BlendGraph,
             palette: &state::palette::Palette,
+            lod: u32,
             node: NodeID,
         ) -> anyhow::Result<()> {
             let iter = graph
@@ -429,8 +1559,11 @@ impl Engines {
                     blend_engine,
                     builder,
                     graph_render_data,
+                    filter_previews,
+                    references,
                     graph,
                     palette,
+                    lod,
                     id,
                     data,
                 )?;
@@ -438,17 +1571,21 @@ impl Engines {
             Ok(())
         }
 
-        /// Recursively build a blend invocation for the node, or None for root.
+        /// Recursively build a blend of the node's children, ready to be nested.
+        #[allow(clippy::too_many_arguments)]
         fn blend_for_node(
             blend_engine: &Arc<blender::BlendEngine>,
             graph_render_data: &GraphImages,
+            filter_previews: &FilterPreviews,
+            references: bool,
             graph: &graph::BlendGraph,
             palette: &state::palette::Palette,
+            lod: u32,
             node: NodeID,
 
             into_image: Arc<vk::ImageView>,
             clear_image: bool,
-        ) -> anyhow::Result<blender::NestedBlendInvocation> {
+        ) -> anyhow::Result<blender::BlendInvocationBuilder> {
             let iter = graph
                 .iter_node(node)
                 .ok_or_else(|| anyhow::anyhow!("Node not found"))?;
@@ -459,8 +1596,11 @@ impl Engines {
                     blend_engine,
                     &mut builder,
                     graph_render_data,
+                    filter_previews,
+                    references,
                     graph,
                     palette,
+                    lod,
                     id,
                     data,
                 )?;
@@ -468,31 +1608,55 @@ impl Engines {
 
             // We traverse top-down, we need to blend bottom-up
             builder.reverse();
-            Ok(builder.nest())
+            Ok(builder)
         }
 
-        let mut top_level_blend = self.blend.clone().start(into.view.clone(), true);
+        let mut top_level_blend = self.blend.clone().start(into.lod(lod).clone(), true);
+        // Deleted since it was chosen, show everything.
+        let solo = solo.and_then(|id| {
+            graph
+                .get(id)
+                .filter(|data| !data.is_deleted())
+                .map(|data| (id, data))
+        });
+        let top_level: Box<dyn Iterator<Item = _>> = match solo {
+            Some(solo) => Box::new(std::iter::once(solo)),
+            None => Box::new(graph.iter_top_level()),
+        };
         // Walk the tree in tree-order, building up a blend operation.
-        for (id, data) in graph.iter_top_level() {
+        for (id, data) in top_level {
             insert_blend(
                 &self.blend,
                 &mut top_level_blend,
                 graph_render_data,
+                filter_previews,
+                references,
                 graph,
                 palette,
+                lod,
                 id,
                 data,
             )?;
         }
         // We traverse top-down, we need to blend bottom-up
         top_level_blend.reverse();
+        top_level_blend.filter_scale(scale);
 
         top_level_blend.build()
     }
     /// Render a document from scratch into a newly allocated document data.
+    /// If `replay_step` is given, the listener's current state is drawn instead of the present, see
+    /// [`PerDocumentData::replay_step`]. Only the `region` of the document is drawn, filling the images, or if
+    /// None the region covering the document's canvas, following it as it changes.
+    /// If `solo` is given, it's composited as if it were the only node. Reference leaves are composited only if
+    /// `references` is set.
     fn new_render_from_scrach(
         &self,
         listener: queue::DocumentCommandListener,
+        replay_step: Option<usize>,
+        region: Option<DocumentRegion>,
+        solo: Option<graph::AnyID>,
+        references: bool,
     ) -> anyhow::Result<PerDocumentData> {
         let mut data = PerDocumentData {
             listener,
@@ -502,23 +1666,50 @@ impl Engines {
                 nodes: hashbrown::HashMap::new(),
             },
             render_target: self.strokes.cleared_node_data()?,
+            filter_previews: hashbrown::HashMap::new(),
+            solo,
+            references,
+            pending_strokes: hashbrown::HashMap::new(),
+            replay_step,
+            presented: None,
+            // Placeholders, set from the state below.
+            region: DocumentRegion::covering(Canvas::default()),
+            follows_canvas: region.is_none(),
+            canvas: Canvas::default(),
+            reach: 0.0,
+            lod: 0,
+            stale_lods: hashbrown::HashSet::new(),
         };
 
         // Observe concrete document state.
-        let reader = data.listener.forward_clone_state()?;
+        let reader = if replay_step.is_some() {
+            data.listener.forward_clone_state_by(0)?
+        } else {
+            data.listener.forward_clone_state()?
+        };
+        data.canvas = reader.document().viewport.canvas();
+        data.region = region.unwrap_or(DocumentRegion::covering(data.canvas));
 
         // Allocate blend and leaf images.
         self.allocate_prune_graph(&mut data.graph_render_data, reader.graph())?;
+        data.stale_lods
+            .extend(data.graph_render_data.leaves.keys().copied());
 
         // Draw leaves.
         self.leaves_from_scratch(&data, &reader)?;
 
         // Compile blending logic on the GPU.
+        data.reach = filter::reach(reader.graph());
         let invocation = self.compile_blend_graph(
             reader.graph(),
             &data.graph_render_data,
+            &data.filter_previews,
+            data.solo,
+            data.references,
             reader.palette(),
             &data.render_target,
+            0,
+            data.region.scale(),
         )?;
 
         // Execute blending!
@@ -531,6 +1722,7 @@ impl Engines {
     /// Otherwise, the buffer is cleared and all active (not undone) strokes from the collection are drawn.
     ///
     /// Ok(None) represents a success that does not need host-side waiting, `Ok(Some(fence))` requires synchronization before the operation is complete.
+    #[allow(clippy::too_many_arguments)]
     fn stroke_layer(
         &self,
         collection: &state::stroke_collection::StrokeCollection,
@@ -538,6 +1730,7 @@ impl Engines {
         outer_transform: &state::transform::Matrix,
         palette: &state::palette::Palette,
         data: &LeafRenderData,
+        region: DocumentRegion,
         which: Option<&[state::stroke_collection::ImmutableStrokeID]>,
     ) -> anyhow::Result<Option<vk::FenceSignalFuture<Box<dyn vk::sync::GpuFuture>>>> {
         enum EitherIter<
@@ -593,6 +1786,7 @@ impl Engines {
                 inner_transform,
                 outer_transform,
                 data,
+                region,
                 clear,
             )
             .map(|()| None)
@@ -607,10 +1801,15 @@ impl Engines {
         // Fixme: text builder needs inner mutability.
         // Self::render_text(&self.context, &mut self.text_builder, renderer, image, px_per_em, text)
     }
+    /// If `saved` is given, the tiles that differ from it are highlighted in the copy. If `dirty_mask` is
+    /// given, the tiles that differ from the last present are flagged in it. Executes after `after`.
     fn copy_document_to_preview_proxy(
         &self,
         document_data: &PerDocumentData,
+        saved: Option<&PerDocumentData>,
+        dirty_mask: Option<&vk::Subbuffer<[u32]>>,
         into: &Arc<vk::ImageView>,
+        after: Box<dyn vk::sync::GpuFuture + Send>,
     ) -> anyhow::Result<vk::FenceSignalFuture<Box<dyn vk::sync::GpuFuture + Send>>> {
         let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
             self.context.allocators().command_buffer(),
@@ -640,10 +1839,82 @@ impl Engines {
                 into.image().clone(),
             )
         })?;
+        if let Some(saved) = saved {
+            self.diff.record_saved(
+                &mut command_buffer,
+                &document_data.render_target.view,
+                &saved.render_target.view,
+                into,
+            )?;
+        }
+        if let Some(presented) = &document_data.presented {
+            if let Some(dirty_mask) = dirty_mask {
+                self.diff.record_dirty(
+                    &mut command_buffer,
+                    &document_data.render_target.view,
+                    &presented.view,
+                    dirty_mask,
+                )?;
+            }
+            // Becomes the image to compare the next present against.
+            command_buffer.copy_image(CopyImageInfo::images(
+                document_data.render_target.image.clone(),
+                presented.image.clone(),
+            ))?;
+        }
 
         let command_buffer = command_buffer.build()?;
 
-        Ok(vk::sync::now(self.context.device().clone())
+        Ok(after
+            .then_execute(
+                self.context.queues().graphics().queue().clone(),
+                command_buffer,
+            )?
+            .boxed_send()
+            .then_signal_fence_and_flush()?)
+    }
+    /// Copy the document's image at its reduced level of detail into the preview, scaled up to fill it.
+    /// Executes after `after`.
+    fn copy_lod_to_preview_proxy(
+        &self,
+        document_data: &PerDocumentData,
+        into: &Arc<vk::ImageView>,
+        after: Box<dyn vk::sync::GpuFuture + Send>,
+    ) -> anyhow::Result<vk::FenceSignalFuture<Box<dyn vk::sync::GpuFuture + Send>>> {
+        let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
+            self.context.allocators().command_buffer(),
+            self.context.queues().graphics().idx(),
+            vk::CommandBufferUsage::OneTimeSubmit,
+        )?;
+        let [width, height] = view_extent(document_data.render_target.lod(document_data.lod));
+        command_buffer.blit_image(vk::BlitImageInfo {
+            regions: smallvec::smallvec![vk::ImageBlit {
+                src_subresource: vk::ImageSubresourceLayers {
+                    aspects: vk::ImageAspects::COLOR,
+                    mip_level: document_data.lod,
+                    array_layers: 0..1,
+                },
+                src_offsets: [[0; 3], [width, height, 1]],
+                dst_subresource: vk::ImageSubresourceLayers {
+                    aspects: vk::ImageAspects::COLOR,
+                    mip_level: 0,
+                    array_layers: into.subresource_range().array_layers.clone(),
+                },
+                dst_offsets: [
+                    [0; 3],
+                    [crate::DOCUMENT_DIMENSION, crate::DOCUMENT_DIMENSION, 1],
+                ],
+                ..Default::default()
+            }],
+            filter: vk::Filter::Linear,
+            ..vk::BlitImageInfo::images(
+                document_data.render_target.image.clone(),
+                into.image().clone(),
+            )
+        })?;
+        let command_buffer = command_buffer.build()?;
+
+        Ok(after
             .then_execute(
                 self.context.queues().graphics().queue().clone(),
                 command_buffer,
@@ -651,6 +1922,131 @@ impl Engines {
             .boxed_send()
             .then_signal_fence_and_flush()?)
     }
+    /// Downsample each leaf's full resolution image into its levels of detail, waiting until complete.
+    fn generate_lods<'a>(
+        &self,
+        leaves: impl Iterator<Item = &'a LeafRenderData>,
+    ) -> anyhow::Result<()> {
+        let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
+            self.context.allocators().command_buffer(),
+            self.context.queues().graphics().idx(),
+            vk::CommandBufferUsage::OneTimeSubmit,
+        )?;
+        for leaf in leaves {
+            // Each level from the one before, each blit depending on the last.
+            for (from, to) in leaf.lods.iter().zip(leaf.lods.iter().skip(1)) {
+                let (from_extent, to_extent) = (view_extent(from), view_extent(to));
+                command_buffer.blit_image(vk::BlitImageInfo {
+                    // Reading and writing the same image, so neither can be in a transfer layout.
+                    src_image_layout: vk::ImageLayout::General,
+                    dst_image_layout: vk::ImageLayout::General,
+                    regions: smallvec::smallvec![vk::ImageBlit {
+                        src_subresource: vk::ImageSubresourceLayers {
+                            aspects: vk::ImageAspects::COLOR,
+                            mip_level: from.subresource_range().mip_levels.start,
+                            array_layers: 0..1,
+                        },
+                        src_offsets: [[0; 3], [from_extent[0], from_extent[1], 1]],
+                        dst_subresource: vk::ImageSubresourceLayers {
+                            aspects: vk::ImageAspects::COLOR,
+                            mip_level: to.subresource_range().mip_levels.start,
+                            array_layers: 0..1,
+                        },
+                        dst_offsets: [[0; 3], [to_extent[0], to_extent[1], 1]],
+                        ..Default::default()
+                    }],
+                    filter: vk::Filter::Linear,
+                    ..vk::BlitImageInfo::images(leaf.image.clone(), leaf.image.clone())
+                })?;
+            }
+        }
+        let command_buffer = command_buffer.build()?;
+
+        self.context
+            .now()
+            .then_execute(
+                self.context.queues().graphics().queue().clone(),
+                command_buffer,
+            )?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+        Ok(())
+    }
+    /// The composited color at a document-space point, as last rendered. Read from the zoomed render if it
+    /// covers the point, as that's what's on screen, otherwise from the level of detail last composited.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    async fn sample_color(
+        &self,
+        id: state::document::ID,
+        point: [f32; 2],
+    ) -> anyhow::Result<[f32; 4]> {
+        let zoomed = self
+            .zoomed
+            .as_ref()
+            .filter(|(zoomed_id, _, data)| {
+                let region = data.region;
+                *zoomed_id == id
+                    && (region.origin.x..region.origin.x + region.size).contains(&point[0])
+                    && (region.origin.y..region.origin.y + region.size).contains(&point[1])
+            })
+            .map(|(.., data)| data);
+        let (data, lod) = match zoomed {
+            Some(data) => (data, 0),
+            None => {
+                let data = self
+                    .data
+                    .get(&id)
+                    .ok_or_else(|| anyhow::anyhow!("document has not been rendered"))?;
+                (data, data.lod)
+            }
+        };
+        // Texels of the level per document pixel.
+        let scale = data.region.scale() / (1u32 << lod) as f32;
+        let texel = [
+            (point[0] - data.region.origin.x) * scale,
+            (point[1] - data.region.origin.y) * scale,
+        ];
+        let extent = view_extent(data.render_target.lod(lod));
+        // Also excludes NaN, so the casts below are in range.
+        let in_bounds = |texel: f32, extent: u32| texel >= 0.0 && texel < extent as f32;
+        if !(in_bounds(texel[0], extent[0]) && in_bounds(texel[1], extent[1])) {
+            anyhow::bail!("outside the document");
+        }
+        let bytes = self
+            .engines
+            .context
+            .readback()
+            .download(crate::render_device::readback::Request {
+                image: data.render_target.image.clone(),
+                array_layer: 0,
+                mip_level: lod,
+                offset: [texel[0] as u32, texel[1] as u32],
+                extent: [1, 1],
+            })
+            .await
+            .map_err(|_| anyhow::anyhow!("readback worker stopped"))??;
+        let texel: [vulkano::half::f16; 4] = bytemuck::pod_read_unaligned(&bytes);
+        Ok(texel.map(vulkano::half::f16::to_f32))
+    }
+    /// Copy the document's composited image into host memory.
+    async fn download_document(
+        &self,
+        document_data: &PerDocumentData,
+    ) -> anyhow::Result<Vec<[vulkano::half::f16; 4]>> {
+        let bytes = self
+            .context
+            .readback()
+            .download(crate::render_device::readback::Request::whole(
+                document_data.render_target.image.clone(),
+            ))
+            .await
+            .map_err(|_| anyhow::anyhow!("readback worker stopped"))??;
+        Ok(bytemuck::pod_collect_to_vec(&bytes))
+    }
     /// Renders every leaf, does not execute blend.
     fn leaves_from_scratch(
         &self,
@@ -697,15 +2093,52 @@ impl Engines {
                         outer_transform,
                         reader.palette(),
                         data,
-                        None,
-                    )? {
-                        fences.push(fence);
-                    };
+                        document_data.region,
+                        None,
+                    )? {
+                        fences.push(fence);
+                    };
+                }
+                // Render stroke image
+                Some(LeafType::Text {
+                    text, px_per_em, ..
+                }) => {
+                    let data =
+                        document_data
+                            .graph_render_data
+                            .leaves
+                            .get(&id)
+                            .ok_or_else(|| {
+                                anyhow::anyhow!(
+                                "Expected image to be created by allocate_prune_graph for {id:?}"
+                            )
+                            })?;
+                    fences.push(self.text_layer(text, *px_per_em, data)?);
+                }
+                Some(LeafType::Image {
+                    image,
+                    outer_transform,
+                    ..
+                }) => {
+                    let data =
+                        document_data
+                            .graph_render_data
+                            .leaves
+                            .get(&id)
+                            .ok_or_else(|| {
+                                anyhow::anyhow!(
+                                "Expected image to be created by allocate_prune_graph for {id:?}"
+                            )
+                            })?;
+                    fences.push(self.images.draw(
+                        &reader.document().assets.read(),
+                        *image,
+                        outer_transform,
+                        data,
+                        document_data.region,
+                    )?);
                 }
-                // Render stroke image
-                Some(LeafType::Text {
-                    text, px_per_em, ..
-                }) => {
+                Some(LeafType::Gradient { gradient, .. }) => {
                     let data =
                         document_data
                             .graph_render_data
@@ -716,7 +2149,12 @@ impl Engines {
                                 "Expected image to be created by allocate_prune_graph for {id:?}"
                             )
                             })?;
-                    fences.push(self.text_layer(text, *px_per_em, data)?);
+                    fences.push(self.gradients.draw(
+                        gradient,
+                        reader.palette(),
+                        data,
+                        document_data.region,
+                    )?);
                 }
                 // No rendering or lazily rendered.
                 Some(LeafType::SolidColor { .. } | LeafType::Note) | None => (),
@@ -774,9 +2212,9 @@ impl Engines {
 
         let (face, plan) = FACE.get_or_init(|| {
             let face = rustybuzz::Face::from_slice(face_data, 0).expect("bad face");
-            println!(
-                "{:#?}",
-                face.variation_axes().into_iter().collect::<Vec<_>>()
+            tracing::debug!(
+                axes = ?face.variation_axes().into_iter().collect::<Vec<_>>(),
+                "loaded face"
             );
             let plan = rustybuzz::ShapePlan::new(
                 &face,
@@ -836,18 +2274,25 @@ impl Engines {
     }
     /// Creates images for all nodes which require rendering, drops node images that are deleted, etc.
     /// Only fails when graphics device is out-of-memory
+    /// Returns the leaves that were newly allocated, whose contents are undefined.
     fn allocate_prune_graph(
         &self,
         graph_render_data: &mut GraphImages,
         graph: &graph::BlendGraph,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<Vec<graph::LeafID>> {
         let mut retain_nodes = hashbrown::HashSet::<graph::NodeID>::new();
         let mut retain_leaves = hashbrown::HashSet::<graph::LeafID>::new();
+        let mut allocated = Vec::new();
         for (id, node) in graph.iter() {
             let render_type = match (node.leaf(), node.node()) {
-                // Stroke and text have images.
+                // Stroke, text, image, and gradient have images.
                 (
-                    Some(graph::LeafType::StrokeLayer { .. } | graph::LeafType::Text { .. }),
+                    Some(
+                        graph::LeafType::StrokeLayer { .. }
+                        | graph::LeafType::Text { .. }
+                        | graph::LeafType::Image { .. }
+                        | graph::LeafType::Gradient { .. },
+                    ),
                     None,
                 ) => {
                     let id = id.try_into().unwrap();
@@ -858,10 +2303,14 @@ impl Engines {
                         graph_render_data.leaves.entry(id)
                     {
                         v.insert(self.strokes.uninit_leaf_data()?);
+                        allocated.push(id);
                     }
                 }
                 // Blend groups need an image.
-                (None, Some(graph::NodeType::GroupedBlend(..))) => {
+                (
+                    None,
+                    Some(graph::NodeType::GroupedBlend(..) | graph::NodeType::Filtered { .. }),
+                ) => {
                     let id = id.try_into().unwrap();
                     // Mark it as used, so that it wont get dealloc'd
                     retain_nodes.insert(id);
@@ -884,17 +2333,24 @@ impl Engines {
             .nodes
             .retain(|id, _| retain_nodes.contains(id));
 
-        Ok(())
+        Ok(allocated)
     }
 }
+/// How often documents other than the active one are brought up-to-date, so they're ready when switched to
+/// without taking time away from the active one.
+const BACKGROUND_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
 async fn render_changes(
     renderer: Arc<crate::render_device::RenderContext>,
+    mut request_reciever: tokio::sync::mpsc::Receiver<requests::RenderRequest>,
     document_preview: Arc<crate::document_viewport_proxy::Proxy>,
 ) -> anyhow::Result<()> {
     // Sync -> Async bridge for change notification. Bleh..
     let (send, mut changes_recv) = tokio::sync::mpsc::unbounded_channel();
     let exit_flag = Arc::new(std::sync::atomic::AtomicBool::new(false));
     let exit_flag_move = exit_flag.clone();
+    let cancellation = Arc::new(Cancellation::default());
+    let cancellation_move = cancellation.clone();
     let _thread = std::thread::spawn(move || {
         let mut change_listener = crate::global::provider().change_listener();
         loop {
@@ -903,22 +2359,13 @@ async fn render_changes(
                 return;
             }
             // Poll every so often, so an assertion of the exit flag is not missed.
-            match change_listener.recv_timeout(std::time::Duration::from_millis(250)) {
-                Ok(change) => {
-                    // Got a change. Broadcast this one (and all others that are ready now)
-                    if send.send(change.id()).is_err() {
-                        // Disconnected!
-                        return;
-                    }
-                    while let Ok(change) = change_listener.try_recv() {
-                        if send.send(change.id()).is_err() {
-                            // Disconnected!
-                            return;
-                        }
-                    }
+            // Every change since the last poll is reported at once.
+            for id in change_listener.take_timeout(std::time::Duration::from_millis(250)) {
+                cancellation_move.notify(id);
+                if send.send(id).is_err() {
+                    // Disconnected!
+                    return;
                 }
-                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => (),
-                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return,
             }
         }
     });
@@ -927,9 +2374,20 @@ async fn render_changes(
 
     let mut changes: Vec<_> = crate::global::provider().document_iter().collect();
     let mut renderer = Renderer::new(renderer)?;
+    // Documents invalidated by a request rather than by a change.
+    let mut requested_redraw = None;
+    // Changed documents that were not active at the time.
+    let mut background = hashbrown::HashSet::new();
+    let mut next_background = tokio::time::Instant::now();
+    // Every document needs redrawing with new shaders, see [`shader_reload`].
+    let mut redraw_all = false;
 
     loop {
-        let changes = async {
+        changes.extend(requested_redraw.take());
+        if std::mem::take(&mut redraw_all) {
+            changes.extend(crate::global::provider().document_iter());
+        }
+        let next_changes = async {
             // Already has some! Report immediately.
             if !changes.is_empty() {
                 return Some(&mut changes);
@@ -943,7 +2401,38 @@ async fn render_changes(
             Some(&mut changes)
         };
 
-        let Some(changes) = changes.await else {
+        let changes = tokio::select! {
+            changes = next_changes => changes,
+            // Disabled once closed, as there's nothing more to serve.
+            Some(request) = request_reciever.recv() => {
+                requested_redraw = requests::handle(&mut renderer, request).await;
+                continue;
+            }
+            () = shader_reload::changed() => {
+                renderer.reload_shaders()?;
+                redraw_all = true;
+                continue;
+            }
+            () = crate::diagnostics::layer_visualizations_changed() => {
+                renderer.forget_rendered();
+                redraw_all = true;
+                continue;
+            }
+            // Only when idle, and at most once per interval. Too slow to bother with in software.
+            () = tokio::time::sleep_until(next_background), if !background.is_empty() && !crate::render_device::is_software_rendering() => {
+                // Unwrap ok - checked by the guard.
+                let id = *background.iter().next().unwrap();
+                background.remove(&id);
+                cancellation.begin(id);
+                // Not shown, so the result is not needed. Abandoned layers are picked up next time.
+                if let Err(e) = renderer.update_one(id, Some(&cancellation)) {
+                    tracing::debug!(error = ?e, "background document failed to update");
+                }
+                next_background = tokio::time::Instant::now() + BACKGROUND_INTERVAL;
+                continue;
+            }
+        };
+        let Some(changes) = changes else {
             // Channel closed
             return Ok(());
         };
@@ -952,16 +2441,45 @@ async fn render_changes(
         //renderer.render(&changed)?;
         // No current doc, skip rendering.
         let Some(selections) = crate::AdHocGlobals::read_clone() else {
-            changes.clear();
+            background.extend(changes.drain(..));
             continue;
         };
+        // Switched to a document with changes that were left for later.
+        if background.remove(&selections.document) {
+            changes.push(selections.document);
+        }
+        // The active document always goes first, the rest wait their turn.
+        background.extend(
+            changes
+                .iter()
+                .copied()
+                .filter(|&id| id != selections.document),
+        );
         // Rerender, if requested
         if changes.contains(&selections.document) {
-            let write = document_preview.write().await;
-
-            let fence = renderer.render_one(selections.document, &write)?;
+            let visible = document_preview
+                .get_view_transform()
+                .await
+                .and_then(|view| view.document_space_aabb());
+            renderer.set_visible(selections.document, visible);
+            // Any changes arriving from now on are not guaranteed to be seen by this render.
+            cancellation.begin(selections.document);
+            let update = renderer.update_one(selections.document, Some(&cancellation))?;
+            if update.is_continue() {
+                let mut write = document_preview.write().await;
+
+                // Chains onto any unshown work still writing the image, rather than waiting for it.
+                let after = write.take_after();
+                let presented = renderer.present_one(selections.document, &write, after)?;
+                if let Some(dirty_mask) = presented.dirty_mask {
+                    write.set_dirty_tiles(dirty_mask);
+                }
+                write.set_region(presented.region);
+                write.set_canvas(presented.canvas);
 
-            write.submit_with_fence(fence);
+                write.submit_with_fence(presented.fence);
+            }
+            // Otherwise, a newer change is already waiting to be rendered.
         }
         changes.clear();
     }
@@ -971,56 +2489,134 @@ pub async fn render_worker(
     request_reciever: tokio::sync::mpsc::Receiver<requests::RenderRequest>,
     document_preview: Arc<crate::document_viewport_proxy::Proxy>,
 ) -> anyhow::Result<()> {
-    tokio::try_join!(
-        async {
-            requests::handler(request_reciever).await;
-            Ok(())
-        },
-        render_changes(renderer, document_preview),
-    )
-    .map(|_| ())
+    // Requests are served between renders, as they need the render data.
+    render_changes(renderer, request_reciever, document_preview).await
 }
 
 /// Data managed by the renderer for a layer leaf, e.g. Stroke layers, text layers, ect.
 pub struct LeafRenderData {
     image: Arc<vk::Image>,
+    /// The full resolution image, the first of `lods`.
     pub view: Arc<vk::ImageView>,
+    /// Each level of detail, see [`LOD_LEVELS`]. Only made up-to-date when needed.
+    lods: Box<[Arc<vk::ImageView>]>,
+}
+impl LeafRenderData {
+    fn lod(&self, level: u32) -> &Arc<vk::ImageView> {
+        &self.lods[level as usize]
+    }
 }
 /// Data managed by the renderer for a layer node, i.e. blend groups. Can be used as the target for blending.
 pub struct NodeRenderData {
     image: Arc<vk::Image>,
+    /// The full resolution image, the first of `lods`.
     pub view: Arc<vk::ImageView>,
+    /// Each level of detail, see [`LOD_LEVELS`]. Only the one last blended into is up-to-date.
+    lods: Box<[Arc<vk::ImageView>]>,
+}
+impl NodeRenderData {
+    fn lod(&self, level: u32) -> &Arc<vk::ImageView> {
+        &self.lods[level as usize]
+    }
 }
 mod stroke_renderer {
 
-    use crate::{renderer::gpu_tess, vulkano_prelude::*};
+    use crate::{document_viewport_proxy::DocumentRegion, renderer::gpu_tess, vulkano_prelude::*};
     use anyhow::Result as AnyResult;
     use cgmath::Zero;
-    use fuzzpaint_core::state;
+    use fuzzpaint_core::{repositories::points::PointCollectionID, state};
     use std::sync::Arc;
     mod vert {
         vulkano_shaders::shader! {
             ty: "vertex",
             path: "src/shaders/stamp.vert",
         }
+        pub const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source::new(
+                "src/shaders/stamp.vert",
+                crate::renderer::shader_reload::Kind::Vertex,
+            );
     }
     mod frag {
         vulkano_shaders::shader! {
             ty: "fragment",
             path: "src/shaders/stamp.frag",
         }
+        pub const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source::new(
+                "src/shaders/stamp.frag",
+                crate::renderer::shader_reload::Kind::Fragment,
+            );
+    }
+    mod smudge_frag {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "src/shaders/smudge.frag",
+        }
+        pub const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source::new(
+                "src/shaders/smudge.frag",
+                crate::renderer::shader_reload::Kind::Fragment,
+            );
+    }
+    mod wireframe_frag {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "src/shaders/wireframe.frag",
+        }
+        pub const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source::new(
+                "src/shaders/wireframe.frag",
+                crate::renderer::shader_reload::Kind::Fragment,
+            );
+    }
+    mod overdraw_frag {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "src/shaders/overdraw.frag",
+        }
+        pub const SOURCE: crate::renderer::shader_reload::Source =
+            crate::renderer::shader_reload::Source::new(
+                "src/shaders/overdraw.frag",
+                crate::renderer::shader_reload::Kind::Fragment,
+            );
     }
 
+    /// Brushes with textures built in, in the order of their layers in the brush texture array.
+    pub const BUILTIN_BRUSHES: [fuzzpaint_core::brush::UniqueID; 2] = [
+        fuzzpaint_core::brush::UniqueID([0; 32]),
+        fuzzpaint_core::brush::UniqueID([
+            1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0,
+        ]),
+    ];
+
     pub struct StrokeLayerRenderer {
         context: Arc<crate::render_device::RenderContext>,
         texture_descriptors: fuzzpaint_core::brush::UniqueIDMap<Arc<vk::PersistentDescriptorSet>>,
         gpu_tess: super::gpu_tess::GpuStampTess,
         pipeline: Arc<vk::GraphicsPipeline>,
+        /// Draws smudge strokes, sampling a snapshot of the layer in a third set.
+        smudge_pipeline: Arc<vk::GraphicsPipeline>,
+        /// Outlines stamps, see [`crate::diagnostics::Visualizations::WIREFRAME`]. None if the device can't
+        /// draw lines from triangles.
+        wireframe_pipeline: Option<Arc<vk::GraphicsPipeline>>,
+        /// Counts stamps, see [`crate::diagnostics::Visualizations::OVERDRAW`].
+        overdraw_pipeline: Arc<vk::GraphicsPipeline>,
+        clip_sampler: Arc<vk::Sampler>,
+        /// Masks rasterized from selections, and under `None` the mask for strokes without a clip, covering
+        /// everything. Selections are immutable, so these never go stale, but are forgotten once no stroke
+        /// refers to them, see [`Self::forget_dropped_clips`].
+        clip_descriptors: parking_lot::Mutex<
+            hashbrown::HashMap<Option<PointCollectionID>, Arc<vk::PersistentDescriptorSet>>,
+        >,
+        /// Set when a clip may have lost its last stroke, checked by [`Self::forget_dropped_clips`].
+        clips_maybe_dropped: std::sync::atomic::AtomicBool,
     }
     impl StrokeLayerRenderer {
         pub fn new(context: Arc<crate::render_device::RenderContext>) -> AnyResult<Self> {
             // Begin uploading a brush image in the background while we continue setup
-            let (image_a, image_b, sampler, _defer) = {
+            let (image_a, image_b, image_all, sampler, dual_sampler, _defer) = {
                 let brush_a = image::load_from_memory(include_bytes!("../../brushes/splotch.png"))?
                     .into_luma8();
                 let mut brush_b = image::load_from_memory(include_bytes!(
@@ -1043,7 +2639,11 @@ mod stroke_renderer {
                         usage: vk::ImageUsage::SAMPLED
                             | vk::ImageUsage::TRANSFER_DST
                             | vk::ImageUsage::TRANSFER_SRC,
-
+                        // Uploaded and mipmapped by transfer, sampled by graphics.
+                        sharing: context.queues().sharing(&[
+                            crate::render_device::QueueUse::Transfer,
+                            crate::render_device::QueueUse::Graphics,
+                        ]),
                         ..Default::default()
                     },
                     vk::AllocationCreateInfo {
@@ -1172,6 +2772,20 @@ mod stroke_renderer {
                     },
                 )?;
 
+                // Every layer, for dual stamps to pick from by index.
+                let view_all = vk::ImageView::new(
+                    device_image.clone(),
+                    vk::ImageViewCreateInfo {
+                        component_mapping: vk::ComponentMapping {
+                            a: vk::ComponentSwizzle::Red,
+                            r: vk::ComponentSwizzle::Red,
+                            b: vk::ComponentSwizzle::Red,
+                            g: vk::ComponentSwizzle::Red,
+                        },
+                        ..vk::ImageViewCreateInfo::from_image(&device_image)
+                    },
+                )?;
+
                 let sampler = vk::Sampler::new(
                     context.device().clone(),
                     vk::SamplerCreateInfo {
@@ -1181,23 +2795,57 @@ mod stroke_renderer {
                         ..Default::default()
                     },
                 )?;
+                // Dual stamps smaller than the stamp tile across it.
+                let dual_sampler = vk::Sampler::new(
+                    context.device().clone(),
+                    vk::SamplerCreateInfo {
+                        min_filter: vk::Filter::Linear,
+                        mag_filter: vk::Filter::Linear,
+                        mipmap_mode: vulkano::image::sampler::SamplerMipmapMode::Linear,
+                        address_mode: [vulkano::image::sampler::SamplerAddressMode::Repeat; 3],
+                        ..Default::default()
+                    },
+                )?;
 
                 (
                     view_a,
                     view_b,
+                    view_all,
                     sampler,
+                    dual_sampler,
                     // synchronizing at the end of init so other setup can happen in parallel.
                     defer::defer(move || fence.wait(None).unwrap()),
                 )
             };
 
-            let frag = frag::load(context.device().clone())?;
-            let vert = vert::load(context.device().clone())?;
+            let frag =
+                super::shader_reload::load(context.device().clone(), &frag::SOURCE, frag::load)?;
+            let smudge_frag = super::shader_reload::load(
+                context.device().clone(),
+                &smudge_frag::SOURCE,
+                smudge_frag::load,
+            )?;
+            let vert =
+                super::shader_reload::load(context.device().clone(), &vert::SOURCE, vert::load)?;
             // Unwraps ok here, using GLSL where "main" is the only allowed entry point.
             let frag = frag.entry_point("main").unwrap();
+            let smudge_frag = smudge_frag.entry_point("main").unwrap();
+            let wireframe_frag = super::shader_reload::load(
+                context.device().clone(),
+                &wireframe_frag::SOURCE,
+                wireframe_frag::load,
+            )?
+            .entry_point("main")
+            .unwrap();
+            let overdraw_frag = super::shader_reload::load(
+                context.device().clone(),
+                &overdraw_frag::SOURCE,
+                overdraw_frag::load,
+            )?
+            .entry_point("main")
+            .unwrap();
             let vert = vert.entry_point("main").unwrap();
 
-            let frag_stage = vk::PipelineShaderStageCreateInfo::new(frag);
             let vert_stage = vk::PipelineShaderStageCreateInfo::new(vert.clone());
             // DualSrcBlend (~75% coverage) is used to control whether to erase or draw on a per-fragment basis
             // [1.0; 4] = draw, [0.0; 4] = erase.
@@ -1216,10 +2864,26 @@ mod stroke_renderer {
                 };
                 vk::ColorBlendState::with_attachment_states(1, blend_states)
             };
+            // Each fragment adds to what's there, for counting.
+            let additive = {
+                let blend = vk::AttachmentBlend {
+                    src_alpha_blend_factor: vk::BlendFactor::One,
+                    src_color_blend_factor: vk::BlendFactor::One,
+                    dst_alpha_blend_factor: vk::BlendFactor::One,
+                    dst_color_blend_factor: vk::BlendFactor::One,
+                    alpha_blend_op: vk::BlendOp::Add,
+                    color_blend_op: vk::BlendOp::Add,
+                };
+                let blend_states = vk::ColorBlendAttachmentState {
+                    blend: Some(blend),
+                    ..Default::default()
+                };
+                vk::ColorBlendState::with_attachment_states(1, blend_states)
+            };
 
             let matrix_push_constant = vk::PushConstantRange {
                 offset: 0,
-                stages: vk::ShaderStages::VERTEX,
+                stages: vk::ShaderStages::VERTEX | vk::ShaderStages::FRAGMENT,
                 size: std::mem::size_of::<vert::Matrix>() as u32,
             };
 
@@ -1242,84 +2906,323 @@ mod stroke_renderer {
                 },
             )?;
 
+            // The brush's texture, then every brush texture for the dual stamp to pick from.
+            let brush_layout = vk::DescriptorSetLayout::new(
+                context.device().clone(),
+                vk::DescriptorSetLayoutCreateInfo {
+                    bindings: (0..2)
+                        .map(|binding| {
+                            (
+                                binding,
+                                vk::DescriptorSetLayoutBinding {
+                                    descriptor_count: 1,
+                                    stages: vk::ShaderStages::FRAGMENT,
+                                    ..vk::DescriptorSetLayoutBinding::descriptor_type(
+                                        vk::DescriptorType::CombinedImageSampler,
+                                    )
+                                },
+                            )
+                        })
+                        .collect(),
+                    ..Default::default()
+                },
+            )?;
+
             let layout = vk::PipelineLayout::new(
                 context.device().clone(),
                 vk::PipelineLayoutCreateInfo {
                     push_constant_ranges: vec![matrix_push_constant],
-                    set_layouts: vec![image_sampler_layout],
+                    // Brush textures, then clip mask, a single sampled image.
+                    set_layouts: vec![brush_layout.clone(), image_sampler_layout.clone()],
                     ..Default::default()
                 },
             )?;
-
-            let pipeline = vk::GraphicsPipeline::new(
+            // Same sets, plus the snapshot of the layer to pick up color from. Shares the first two.
+            let smudge_layout = vk::PipelineLayout::new(
                 context.device().clone(),
-                None,
-                vk::GraphicsPipelineCreateInfo {
-                    color_blend_state: Some(premul_dyn_constants),
-                    input_assembly_state: Some(vk::InputAssemblyState {
-                        topology: vk::PrimitiveTopology::TriangleList,
-                        primitive_restart_enable: false,
-                        ..Default::default()
-                    }),
-                    multisample_state: Some(vk::MultisampleState::default()),
-                    rasterization_state: Some(vk::RasterizationState {
-                        cull_mode: vk::CullMode::None,
-                        ..Default::default()
-                    }),
-                    vertex_input_state: Some(
-                        super::gpu_tess::interface::OutputStrokeVertex::per_vertex()
-                            .definition(&vert.info().input_interface)?,
-                    ),
-                    viewport_state: Some(vk::ViewportState::default()),
-                    subpass: Some(vk::PipelineSubpassType::BeginRendering(
-                        vk::PipelineRenderingCreateInfo {
-                            color_attachment_formats: vec![Some(crate::DOCUMENT_FORMAT)],
+                vk::PipelineLayoutCreateInfo {
+                    push_constant_ranges: vec![matrix_push_constant],
+                    set_layouts: vec![
+                        brush_layout,
+                        image_sampler_layout.clone(),
+                        image_sampler_layout,
+                    ],
+                    ..Default::default()
+                },
+            )?;
+            // Visualizations sample nothing.
+            let visualization_layout = vk::PipelineLayout::new(
+                context.device().clone(),
+                vk::PipelineLayoutCreateInfo {
+                    push_constant_ranges: vec![matrix_push_constant],
+                    ..Default::default()
+                },
+            )?;
+
+            use vulkano::pipeline::graphics::rasterization::PolygonMode;
+            // Stamping and smudging differ only in fragment shader and layout. The smudge shader uses the
+            // same dual-source blend to mix the layer towards the color picked up. Visualizations differ in
+            // blend and in how triangles are filled, too.
+            let make_pipeline = |frag: vk::EntryPoint,
+                                 layout: Arc<vk::PipelineLayout>,
+                                 color_blend_state: &vk::ColorBlendState,
+                                 polygon_mode: PolygonMode|
+             -> AnyResult<Arc<vk::GraphicsPipeline>> {
+                Ok(vk::GraphicsPipeline::new(
+                    context.device().clone(),
+                    None,
+                    vk::GraphicsPipelineCreateInfo {
+                        color_blend_state: Some(color_blend_state.clone()),
+                        input_assembly_state: Some(vk::InputAssemblyState {
+                            topology: vk::PrimitiveTopology::TriangleList,
+                            primitive_restart_enable: false,
                             ..Default::default()
-                        },
-                    )),
-                    dynamic_state: [vk::DynamicState::Viewport].into_iter().collect(),
-                    stages: smallvec::smallvec![vert_stage, frag_stage,],
-                    ..vk::GraphicsPipelineCreateInfo::layout(layout)
+                        }),
+                        multisample_state: Some(vk::MultisampleState::default()),
+                        rasterization_state: Some(vk::RasterizationState {
+                            cull_mode: vk::CullMode::None,
+                            polygon_mode,
+                            ..Default::default()
+                        }),
+                        vertex_input_state: Some(
+                            super::gpu_tess::interface::OutputStrokeVertex::per_vertex()
+                                .definition(&vert.info().input_interface)?,
+                        ),
+                        viewport_state: Some(vk::ViewportState::default()),
+                        subpass: Some(vk::PipelineSubpassType::BeginRendering(
+                            vk::PipelineRenderingCreateInfo {
+                                color_attachment_formats: vec![Some(crate::DOCUMENT_FORMAT)],
+                                ..Default::default()
+                            },
+                        )),
+                        // Set to the extent of the image being drawn into, see `draw`.
+                        dynamic_state: [vk::DynamicState::Viewport, vk::DynamicState::Scissor]
+                            .into_iter()
+                            .collect(),
+                        stages: smallvec::smallvec![
+                            vert_stage.clone(),
+                            vk::PipelineShaderStageCreateInfo::new(frag),
+                        ],
+                        ..vk::GraphicsPipelineCreateInfo::layout(layout)
+                    },
+                )?)
+            };
+            let pipeline = make_pipeline(frag, layout, &premul_dyn_constants, PolygonMode::Fill)?;
+            let smudge_pipeline = make_pipeline(
+                smudge_frag,
+                smudge_layout,
+                &premul_dyn_constants,
+                PolygonMode::Fill,
+            )?;
+            let wireframe_pipeline = if context.device().enabled_features().fill_mode_non_solid {
+                Some(make_pipeline(
+                    wireframe_frag,
+                    visualization_layout.clone(),
+                    &premul_dyn_constants,
+                    PolygonMode::Line,
+                )?)
+            } else {
+                tracing::info!("wireframe visualization unsupported by this device");
+                None
+            };
+            let overdraw_pipeline = make_pipeline(
+                overdraw_frag,
+                visualization_layout,
+                &additive,
+                PolygonMode::Fill,
+            )?;
+            let brush_descriptor = |image: Arc<vk::ImageView>| {
+                vk::PersistentDescriptorSet::new(
+                    context.allocators().descriptor_set(),
+                    pipeline.layout().set_layouts()[0].clone(),
+                    [
+                        vk::WriteDescriptorSet::image_view_sampler(0, image, sampler.clone()),
+                        vk::WriteDescriptorSet::image_view_sampler(
+                            1,
+                            image_all.clone(),
+                            dual_sampler.clone(),
+                        ),
+                    ],
+                    [],
+                )
+            };
+            let descriptor_set_a = brush_descriptor(image_a)?;
+            let descriptor_set_b = brush_descriptor(image_b)?;
+
+            let tess = super::gpu_tess::GpuStampTess::new(context.clone())?;
+
+            let clip_sampler = vk::Sampler::new(
+                context.device().clone(),
+                vk::SamplerCreateInfo {
+                    min_filter: vk::Filter::Linear,
+                    mag_filter: vk::Filter::Linear,
+                    address_mode: [vulkano::image::sampler::SamplerAddressMode::ClampToEdge; 3],
+                    ..Default::default()
+                },
+            )?;
+            Ok(Self {
+                context,
+                pipeline,
+                smudge_pipeline,
+                wireframe_pipeline,
+                overdraw_pipeline,
+                gpu_tess: tess,
+                clip_sampler,
+                clip_descriptors: parking_lot::Mutex::default(),
+                clips_maybe_dropped: std::sync::atomic::AtomicBool::new(false),
+                texture_descriptors: BUILTIN_BRUSHES
+                    .into_iter()
+                    .zip([descriptor_set_a, descriptor_set_b])
+                    .collect(),
+            })
+        }
+        /// Record the upload of a coverage mask into `command_buffer`, returning a descriptor set binding it as
+        /// the clip mask. Usable by commands recorded after it, or submitted after it on the graphics queue.
+        fn mask_descriptor(
+            &self,
+            command_buffer: &mut vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>,
+            [width, height]: [u32; 2],
+            coverage: &[u8],
+        ) -> AnyResult<Arc<vk::PersistentDescriptorSet>> {
+            let context = &self.context;
+            let image = vk::Image::new(
+                context.allocators().memory().clone(),
+                vk::ImageCreateInfo {
+                    extent: [width, height, 1],
+                    format: vk::Format::R8_UNORM,
+                    usage: vk::ImageUsage::SAMPLED | vk::ImageUsage::TRANSFER_DST,
+                    // Uploaded and sampled alongside the strokes, on graphics.
+                    sharing: context
+                        .queues()
+                        .sharing(&[crate::render_device::QueueUse::Graphics]),
+                    ..Default::default()
+                },
+                vk::AllocationCreateInfo {
+                    memory_type_filter: vk::MemoryTypeFilter::PREFER_DEVICE,
+                    ..Default::default()
+                },
+            )?;
+            let stage = vk::Buffer::from_iter(
+                context.allocators().memory().clone(),
+                vk::BufferCreateInfo {
+                    usage: vk::BufferUsage::TRANSFER_SRC,
+                    ..Default::default()
+                },
+                vk::AllocationCreateInfo {
+                    memory_type_filter: vk::MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
                 },
+                coverage.iter().copied(),
             )?;
-            let descriptor_set_a = vk::PersistentDescriptorSet::new(
+            command_buffer.copy_buffer_to_image(vk::CopyBufferToImageInfo::buffer_image(
+                stage,
+                image.clone(),
+            ))?;
+
+            Ok(vk::PersistentDescriptorSet::new(
                 context.allocators().descriptor_set(),
-                pipeline.layout().set_layouts()[0].clone(),
+                self.pipeline.layout().set_layouts()[1].clone(),
                 [vk::WriteDescriptorSet::image_view_sampler(
                     0,
-                    image_a,
-                    sampler.clone(),
+                    vk::ImageView::new_default(image)?,
+                    self.clip_sampler.clone(),
                 )],
                 [],
+            )?)
+        }
+        /// Get the clip mask descriptor for strokes drawn within `clip`. If it's new, it's rasterized and its
+        /// upload recorded into `command_buffer`, which must be outside of rendering.
+        fn clip_descriptor(
+            &self,
+            clip: Option<PointCollectionID>,
+            command_buffer: &mut vk::AutoCommandBufferBuilder<vk::PrimaryAutoCommandBuffer>,
+        ) -> AnyResult<Arc<vk::PersistentDescriptorSet>> {
+            if let Some(descriptor) = self.clip_descriptors.lock().get(&clip) {
+                return Ok(descriptor.clone());
+            }
+            let Some(id) = clip else {
+                let descriptor = self.mask_descriptor(command_buffer, [1, 1], &[u8::MAX])?;
+                self.clip_descriptors
+                    .lock()
+                    .insert(None, descriptor.clone());
+                return Ok(descriptor);
+            };
+            let polygon = crate::global::points()
+                .try_get(id)
+                .ok()
+                .and_then(|points| fuzzpaint_core::selection::polygon(points.get()))
+                .ok_or_else(|| anyhow::anyhow!("stroke clipped to an unknown selection"))?;
+            let coverage = fuzzpaint_core::selection::rasterize(
+                &polygon,
+                crate::DOCUMENT_DIMENSION,
+                crate::DOCUMENT_DIMENSION,
+            );
+            let descriptor =
+                self.mask_descriptor(command_buffer, [crate::DOCUMENT_DIMENSION; 2], &coverage)?;
+            self.clip_descriptors
+                .lock()
+                .insert(clip, descriptor.clone());
+            Ok(descriptor)
+        }
+        /// Note that strokes may have been removed, or a document closed, taking the last use of a clip with them.
+        pub fn clips_maybe_dropped(&self) {
+            self.clips_maybe_dropped
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+        /// Forget the masks of clips that no stroke of an open document, undone or not, is drawn within anymore,
+        /// and that the clipboard doesn't hold. Only looks if [`Self::clips_maybe_dropped`] since the last call.
+        pub fn forget_dropped_clips(&self) {
+            if !self
+                .clips_maybe_dropped
+                .swap(false, std::sync::atomic::Ordering::Relaxed)
+                || self.clip_descriptors.lock().len() <= 1
+            {
+                return;
+            }
+            use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+            let provider = crate::global::provider();
+            let mut live = hashbrown::HashSet::new();
+            for document in provider.document_iter() {
+                provider.inspect(document, |queue| {
+                    let state = queue.peek_clone_state();
+                    for collection in state.stroke_collections().0.values() {
+                        live.extend(collection.strokes.iter().filter_map(|stroke| stroke.clip));
+                    }
+                });
+            }
+            let points = crate::global::points();
+            self.clip_descriptors.lock().retain(|clip, _| match clip {
+                None => true,
+                Some(clip) => live.contains(clip) || points.share_count(*clip) != 0,
+            });
+        }
+        /// Allocate an image to copy a layer into for smudging, and the descriptor sampling it.
+        fn smudge_snapshot(&self) -> AnyResult<(Arc<vk::Image>, Arc<vk::PersistentDescriptorSet>)> {
+            let image = vk::Image::new(
+                self.context.allocators().memory().clone(),
+                vk::ImageCreateInfo {
+                    usage: vk::ImageUsage::SAMPLED | vk::ImageUsage::TRANSFER_DST,
+                    extent: [crate::DOCUMENT_DIMENSION, crate::DOCUMENT_DIMENSION, 1],
+                    format: crate::DOCUMENT_FORMAT,
+                    ..Default::default()
+                },
+                vk::AllocationCreateInfo {
+                    memory_type_filter: vk::MemoryTypeFilter::PREFER_DEVICE,
+                    ..Default::default()
+                },
             )?;
-            let descriptor_set_b = vk::PersistentDescriptorSet::new(
-                context.allocators().descriptor_set(),
-                pipeline.layout().set_layouts()[0].clone(),
+            let descriptor = vk::PersistentDescriptorSet::new(
+                self.context.allocators().descriptor_set(),
+                self.smudge_pipeline.layout().set_layouts()[2].clone(),
                 [vk::WriteDescriptorSet::image_view_sampler(
-                    0, image_b, sampler,
+                    0,
+                    vk::ImageView::new_default(image.clone())?,
+                    // Clamped, as pickups near the edge reach outside.
+                    self.clip_sampler.clone(),
                 )],
                 [],
             )?;
-
-            let tess = super::gpu_tess::GpuStampTess::new(context.clone())?;
-
-            Ok(Self {
-                context,
-                pipeline,
-                gpu_tess: tess,
-                texture_descriptors: [
-                    (fuzzpaint_core::brush::UniqueID([0; 32]), descriptor_set_a),
-                    (
-                        fuzzpaint_core::brush::UniqueID([
-                            1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                            0, 0, 0, 0, 0, 0, 0, 0,
-                        ]),
-                        descriptor_set_b,
-                    ),
-                ]
-                .into_iter()
-                .collect(),
-            })
+            Ok((image, descriptor))
         }
         /// Allocate a new `LeafRenderData`, initial contents are undefined.
         pub fn uninit_leaf_data(&self) -> anyhow::Result<super::LeafRenderData> {
@@ -1333,12 +3236,17 @@ mod stroke_renderer {
                     vk::ImageUsage::COLOR_ATTACHMENT
                         // Source for blending from..
                         | vk::ImageUsage::SAMPLED
-                        // For color clearing..
-                        | vk::ImageUsage::TRANSFER_DST,
+                        // For color clearing, and downsampling into levels of detail..
+                        | vk::ImageUsage::TRANSFER_DST
+                        | vk::ImageUsage::TRANSFER_SRC,
                     extent: [crate::DOCUMENT_DIMENSION, crate::DOCUMENT_DIMENSION, 1],
                     array_layers: 1,
-                    mip_levels: 1,
-                    sharing: self.context.queues().sharing_compute_graphics(),
+                    mip_levels: super::LOD_LEVELS,
+                    // Drawn, cleared, and blended all on graphics.
+                    sharing: self
+                        .context
+                        .queues()
+                        .sharing(&[crate::render_device::QueueUse::Graphics]),
                     format: crate::DOCUMENT_FORMAT,
                     ..Default::default()
                 },
@@ -1347,9 +3255,13 @@ mod stroke_renderer {
                     ..Default::default()
                 },
             )?;
-            let view = vk::ImageView::new_default(image.clone())?;
+            let lods = super::lod_views(&image)?;
 
-            Ok(super::LeafRenderData { image, view })
+            Ok(super::LeafRenderData {
+                image,
+                view: lods[0].clone(),
+                lods,
+            })
         }
         /// Allocate a new `NodeRenderData`, initial contents are eagerly cleared.
         pub fn cleared_node_data(&self) -> anyhow::Result<super::NodeRenderData> {
@@ -1363,14 +3275,20 @@ mod stroke_renderer {
                      | vk::ImageUsage::INPUT_ATTACHMENT
                         // Source for blending from..
                         | vk::ImageUsage::SAMPLED
+                        // Filtering in-place..
+                        | vk::ImageUsage::STORAGE
                         // For color clearing..
                         | vk::ImageUsage::TRANSFER_DST
                         // For blitting to preview proxy image.
                         | vk::ImageUsage::TRANSFER_SRC,
                     extent: [crate::DOCUMENT_DIMENSION, crate::DOCUMENT_DIMENSION, 1],
                     array_layers: 1,
-                    mip_levels: 1,
-                    sharing: self.context.queues().sharing_compute_graphics(),
+                    mip_levels: super::LOD_LEVELS,
+                    // Blended, filtered, diffed, and downloaded all on graphics.
+                    sharing: self
+                        .context
+                        .queues()
+                        .sharing(&[crate::render_device::QueueUse::Graphics]),
                     format: crate::DOCUMENT_FORMAT,
                     ..Default::default()
                 },
@@ -1379,7 +3297,7 @@ mod stroke_renderer {
                     ..Default::default()
                 },
             )?;
-            let view = vk::ImageView::new_default(image.clone())?;
+            let lods = super::lod_views(&image)?;
 
             // Commit hackery. There is a validation error that vulkano triggers when the uninitialized image
             // gets assumed to be `General` layout during blending. I'm not sure why this occurs, but this gives
@@ -1391,8 +3309,13 @@ mod stroke_renderer {
             )?;
             cb.clear_color_image(vk::ClearColorImageInfo {
                 clear_value: [0.0; 4].into(),
-                regions: smallvec::smallvec![view.subresource_range().clone(),],
-                ..vk::ClearColorImageInfo::image(view.image().clone())
+                // Every level, as any may be blended into.
+                regions: smallvec::smallvec![vk::ImageSubresourceRange {
+                    aspects: vk::ImageAspects::COLOR,
+                    mip_levels: 0..super::LOD_LEVELS,
+                    array_layers: 0..1,
+                }],
+                ..vk::ClearColorImageInfo::image(image.clone())
             })?;
 
             let cb = cb.build()?;
@@ -1403,7 +3326,11 @@ mod stroke_renderer {
                 .then_signal_fence_and_flush()?
                 .wait(None)?;
 
-            Ok(super::NodeRenderData { image, view })
+            Ok(super::NodeRenderData {
+                image,
+                view: lods[0].clone(),
+                lods,
+            })
         }
         pub fn draw(
             &self,
@@ -1411,13 +3338,28 @@ mod stroke_renderer {
             inner_transform: &state::transform::Similarity,
             outer_transform: &state::transform::Matrix,
             renderbuf: &super::LeafRenderData,
+            region: DocumentRegion,
             mut clear: bool,
         ) -> AnyResult<()> {
-            // Apply projection
-            let mut matrix = cgmath::Matrix4::from_scale(2.0 / crate::DOCUMENT_DIMENSION as f32);
+            if clear {
+                // Drawn from scratch, likely as strokes were removed.
+                self.clips_maybe_dropped();
+            }
+            // Apply projection, of only the region onto the whole image.
+            let mut matrix = cgmath::Matrix4::from_scale(2.0 / region.size);
             matrix.y *= -1.0;
-            matrix.w.x -= 1.0;
-            matrix.w.y += 1.0;
+            matrix.w.x -= 1.0 + 2.0 * region.origin.x / region.size;
+            matrix.w.y += 1.0 + 2.0 * region.origin.y / region.size;
+            // The clip mask spans the whole document, so framebuffer coordinates are mapped back out of the
+            // region. The image is as large as the document, so this is the identity for the whole region.
+            let dimension = crate::DOCUMENT_DIMENSION as f32;
+            let clip_scale = region.size / (dimension * dimension);
+            let clip_transform = [
+                clip_scale,
+                clip_scale,
+                region.origin.x / dimension,
+                (dimension - region.origin.y - region.size) / dimension,
+            ];
 
             // Apply outer transform
             matrix = matrix
@@ -1447,8 +3389,20 @@ mod stroke_renderer {
                 self.context.allocators().memory().clone(),
                 65536,
                 vk::BufferUsage::STORAGE_BUFFER,
-                vulkano::sync::Sharing::Exclusive,
+                // Written by the host, read only by tessellation.
+                self.context
+                    .queues()
+                    .sharing(&[crate::render_device::QueueUse::Compute]),
             )?;
+            // Allocated once a smudge is drawn, see `smudge_snapshot`.
+            let mut snapshot = None;
+            // Latched for the whole layer, changes draw every layer again anyway.
+            let visualizations = crate::diagnostics::visualizations();
+            let overdraw = visualizations.contains(crate::diagnostics::Visualizations::OVERDRAW);
+            let wireframe = self
+                .wireframe_pipeline
+                .as_ref()
+                .filter(|_| visualizations.contains(crate::diagnostics::Visualizations::WIREFRAME));
             batch.batch(strokes.iter().copied(), |batch| -> AnyResult<_> {
 
                 let mut command_buffer = vk::AutoCommandBufferBuilder::primary(
@@ -1460,8 +3414,9 @@ mod stroke_renderer {
                 let Some(gpu_tess::TessOutput {
                     ready_after,
                     vertices,
-                    mut indirects,
+                    indirects,
                     sources,
+                    ranges,
                 }) = self.gpu_tess.tess_batch(batch, inner_transform, true)? else {
                     // Nothing to render. Still honor the clear.
                     if clear {
@@ -1491,77 +3446,208 @@ mod stroke_renderer {
                     return Ok(super::stroke_batcher::SyncOutput::Immediate);
                 };
 
-                let mut sources = &sources[..];
-                let mut next_indirects_by_brush_id = || -> Option<(fuzzpaint_core::brush::UniqueID, vk::Subbuffer<[vulkano::command_buffer::DrawIndirectCommand]>)> {
-                    let id = sources.first()?.brush.brush;
-                    let first_differ = sources[1..].iter().position(|source| source.brush.brush != id);
-
-                    if let Some(idx) = first_differ {
-                        // Position refers to index in 1..
-                        // Convert to index in 0..
-                        let idx = idx + 1;
-
-                        sources = &sources[idx..];
-                        let (taken_indirects, left_indirects) = indirects.clone().split_at(idx as u64);
-                        indirects = left_indirects;
-
-                        Some((id, taken_indirects))
-                    } else {
-                        sources = &[];
-                        // Take the rest.
-                        Some((id, indirects.clone()))
-                    }
+                // Strokes sharing a brush, clip, and mode can be drawn together.
+                let mut next_source = 0;
+                let mut next_group = || -> Option<((fuzzpaint_core::brush::UniqueID, Option<PointCollectionID>, bool), std::ops::Range<usize>)> {
+                    let key_of = |source: &state::stroke_collection::ImmutableStroke| (source.brush.brush, source.clip, source.brush.is_smudge);
+                    let start = next_source;
+                    let key = key_of(sources.get(start)?);
+                    let end = sources[start + 1..]
+                        .iter()
+                        .position(|source| key_of(source) != key)
+                        // Position refers to index in start + 1..
+                        .map_or(sources.len(), |idx| start + 1 + idx);
+                    next_source = end;
+                    Some((key, start..end))
                 };
-
-                command_buffer
-                    .begin_rendering(vk::RenderingInfo {
-                        color_attachments: vec![Some(vk::RenderingAttachmentInfo {
-                            clear_value: if clear {
-                                Some([0.0, 0.0, 0.0, 0.0].into())
-                            } else {
-                                None
-                            },
-                            load_op: if clear {
-                                vk::AttachmentLoadOp::Clear
-                            } else {
-                                vk::AttachmentLoadOp::Load
-                            },
-                            store_op: vk::AttachmentStoreOp::Store,
-                            ..vk::RenderingAttachmentInfo::image_view(renderbuf.view.clone())
-                        })],
-                        contents: vk::SubpassContents::Inline,
-                        depth_attachment: None,
-                        ..Default::default()
-                    })?
-                    .bind_pipeline_graphics(self.pipeline.clone())?
-                    .push_constants(
-                        self.pipeline.layout().clone(),
-                        0,
-                        Into::<[[f32; 4]; 4]>::into(matrix),
-                    )?
-                    .bind_vertex_buffers(0, vertices)?;
-
-                // Ensure only the first loop clears.
-                clear = false;
-
-                // Group together commands by brush ID and draw them!
-                while let Some((brush_id, indirects)) = next_indirects_by_brush_id() {
+                let push_matrix = || vert::Matrix {
+                    mvp: matrix.into(),
+                    clip_transform,
+                };
+                let rendering_info = |clear: bool| vk::RenderingInfo {
+                    color_attachments: vec![Some(vk::RenderingAttachmentInfo {
+                        clear_value: if clear {
+                            Some([0.0, 0.0, 0.0, 0.0].into())
+                        } else {
+                            None
+                        },
+                        load_op: if clear {
+                            vk::AttachmentLoadOp::Clear
+                        } else {
+                            vk::AttachmentLoadOp::Load
+                        },
+                        store_op: vk::AttachmentStoreOp::Store,
+                        ..vk::RenderingAttachmentInfo::image_view(renderbuf.view.clone())
+                    })],
+                    contents: vk::SubpassContents::Inline,
+                    depth_attachment: None,
+                    ..Default::default()
+                };
+                // Stamps are clipped to the image, which spans exactly the document region. Those off the
+                // edge of the document are cut off there.
+                let [width, height] = super::view_extent(&renderbuf.view);
+                let viewport = || smallvec::smallvec![vk::Viewport {
+                    offset: [0.0; 2],
+                    extent: [width as f32, height as f32],
+                    depth_range: 0.0..=1.0,
+                }];
+                let scissor = || smallvec::smallvec![vk::Scissor {
+                    offset: [0; 2],
+                    extent: [width, height],
+                }];
+
+                // Masks are uploaded ahead of rendering, as copies can't be recorded within it.
+                let mut clips = hashbrown::HashMap::new();
+                if !overdraw {
+                    for source in &sources {
+                        if let hashbrown::hash_map::Entry::Vacant(vacant) = clips.entry(source.clip) {
+                            vacant.insert(self.clip_descriptor(source.clip, &mut command_buffer)?);
+                        }
+                    }
+                }
+                // Whether stamps are being drawn, started lazily as smudges need rendering to end.
+                let mut rendering = false;
+                // Every stamp counted in place of paint, smudges included.
+                if overdraw {
+                    command_buffer
+                        .begin_rendering(rendering_info(clear))?
+                        .bind_pipeline_graphics(self.overdraw_pipeline.clone())?
+                        .set_viewport(0, viewport())?
+                        .set_scissor(0, scissor())?
+                        .push_constants(self.overdraw_pipeline.layout().clone(), 0, push_matrix())?
+                        .bind_vertex_buffers(0, vertices.clone())?
+                        .draw_indirect(indirects.clone())?;
+                    clear = false;
+                    rendering = true;
+                }
+                // Group together commands by brush ID, clip, and mode and draw them!
+                while let Some(((brush_id, clip, smudge), group)) = next_group().filter(|_| !overdraw) {
                     let Some(descriptor) = self.texture_descriptors
                         .get(&brush_id)
                         .cloned() else {
                             continue
                         };
+                    let clip = clips[&clip].clone();
+                    if !smudge {
+                        if !std::mem::replace(&mut rendering, true) {
+                            command_buffer
+                                .begin_rendering(rendering_info(clear))?
+                                .bind_pipeline_graphics(self.pipeline.clone())?
+                                .set_viewport(0, viewport())?
+                                .set_scissor(0, scissor())?
+                                .push_constants(self.pipeline.layout().clone(), 0, push_matrix())?
+                                .bind_vertex_buffers(0, vertices.clone())?;
+                            // Ensure only the first pass clears.
+                            clear = false;
+                        }
+                        command_buffer
+                        .bind_descriptor_sets(
+                            vk::PipelineBindPoint::Graphics,
+                            self.pipeline.layout().clone(),
+                            0,
+                            (descriptor, clip),
+                        )?
+                        .draw_indirect(indirects.clone().slice(group.start as u64..group.end as u64))?;
+                        continue;
+                    }
+
+                    // Smudges read the layer they draw into. Ping-pong between the layer and a snapshot of
+                    // it, drawing a few stamps per pass, each pass sampling what the last left behind.
+                    if std::mem::take(&mut rendering) {
+                        command_buffer.end_rendering()?;
+                    }
+                    if std::mem::take(&mut clear) {
+                        command_buffer.clear_color_image(vk::ClearColorImageInfo {
+                            clear_value: [0.0; 4].into(),
+                            regions: smallvec::smallvec![renderbuf.view.subresource_range().clone()],
+                            ..vk::ClearColorImageInfo::image(renderbuf.image.clone())
+                        })?;
+                    }
+                    let (snapshot_image, snapshot_descriptor) = match snapshot.clone() {
+                        Some(snapshot) => snapshot,
+                        None => snapshot.insert(self.smudge_snapshot()?).clone(),
+                    };
+                    let pass_vertices = 6 * super::gpu_tess::SMUDGE_STAMPS_PER_PASS;
+                    let subresource = vk::ImageSubresourceLayers {
+                        aspects: vk::ImageAspects::COLOR,
+                        mip_level: renderbuf.view.subresource_range().mip_levels.start,
+                        array_layers: renderbuf.view.subresource_range().array_layers.clone(),
+                    };
+                    for (source, range) in sources[group.clone()].iter().zip(&ranges[group]) {
+                        // Without its points, each pass snapshots the whole layer.
+                        let reach = super::gpu_tess::StampReach::new(source, inner_transform);
+                        for first in range.clone().step_by(pass_vertices as usize) {
+                            let stamp = (first - range.start) / 6;
+                            let region = match &reach {
+                                Some(reach) => {
+                                    let Some(region) = reach
+                                        .bounds(stamp..stamp + super::gpu_tess::SMUDGE_STAMPS_PER_PASS)
+                                        .and_then(|bounds| texel_bounds(&matrix, bounds, [width, height]))
+                                    else {
+                                        // Lands entirely off the image.
+                                        continue;
+                                    };
+                                    region
+                                }
+                                None => ([0; 2], [width, height]),
+                            };
+                            // Only what this pass touches, rather than the whole layer every few stamps.
+                            let ([x, y], [region_width, region_height]) = region;
+                            command_buffer.copy_image(vk::CopyImageInfo {
+                                regions: smallvec::smallvec![vulkano::command_buffer::ImageCopy {
+                                    src_subresource: subresource.clone(),
+                                    src_offset: [x, y, 0],
+                                    dst_subresource: vk::ImageSubresourceLayers {
+                                        array_layers: 0..1,
+                                        mip_level: 0,
+                                        ..subresource.clone()
+                                    },
+                                    dst_offset: [x, y, 0],
+                                    extent: [region_width, region_height, 1],
+                                    ..Default::default()
+                                }],
+                                ..vk::CopyImageInfo::images(
+                                    renderbuf.image.clone(),
+                                    snapshot_image.clone(),
+                                )
+                            })?;
+                            command_buffer
+                                .begin_rendering(rendering_info(false))?
+                                .bind_pipeline_graphics(self.smudge_pipeline.clone())?
+                                .set_viewport(0, viewport())?
+                                .set_scissor(0, scissor())?
+                                .push_constants(self.smudge_pipeline.layout().clone(), 0, push_matrix())?
+                                .bind_vertex_buffers(0, vertices.clone())?
+                                .bind_descriptor_sets(
+                                    vk::PipelineBindPoint::Graphics,
+                                    self.smudge_pipeline.layout().clone(),
+                                    0,
+                                    (descriptor.clone(), clip.clone(), snapshot_descriptor.clone()),
+                                )?
+                                .draw(pass_vertices.min(range.end - first), 1, first, 0)?
+                                .end_rendering()?;
+                        }
+                    }
+                }
+
+                // Over the top of everything in the batch.
+                if let Some(wireframe) = wireframe {
+                    if !std::mem::replace(&mut rendering, true) {
+                        command_buffer.begin_rendering(rendering_info(clear))?;
+                        clear = false;
+                    }
                     command_buffer
-                    .bind_descriptor_sets(
-                        vk::PipelineBindPoint::Graphics,
-                        self.pipeline.layout().clone(),
-                        0,
-                        descriptor,
-                    )?
-                    .draw_indirect(indirects)?;
+                        .bind_pipeline_graphics(wireframe.clone())?
+                        .set_viewport(0, viewport())?
+                        .set_scissor(0, scissor())?
+                        .push_constants(wireframe.layout().clone(), 0, push_matrix())?
+                        .bind_vertex_buffers(0, vertices.clone())?
+                        .draw_indirect(indirects.clone())?;
                 }
 
-                command_buffer.end_rendering()?;
+                if rendering {
+                    command_buffer.end_rendering()?;
+                }
 
                 let command_buffer = command_buffer.build()?;
 
@@ -1615,4 +3701,38 @@ mod stroke_renderer {
             Ok(())
         }
     }
+    /// The texels of an image of `extent` that `bounds`, the least and greatest corner, cover once projected
+    /// by `matrix`. Returned as an offset and extent, or `None` if they cover none.
+    fn texel_bounds(
+        matrix: &cgmath::Matrix4<f32>,
+        [min, max]: [[f32; 2]; 2],
+        extent: [u32; 2],
+    ) -> Option<([u32; 2], [u32; 2])> {
+        let (mut least, mut greatest) = ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]);
+        for [x, y] in [min, [max[0], min[1]], [min[0], max[1]], max] {
+            // Affine, no need to divide by w.
+            let projected = *matrix * cgmath::vec4(x, y, 0.0, 1.0);
+            for (axis, ndc) in [projected.x, projected.y].into_iter().enumerate() {
+                // Device coordinates span two, texels the extent.
+                #[allow(clippy::cast_precision_loss)]
+                let texel = (ndc + 1.0) * 0.5 * extent[axis] as f32;
+                least[axis] = least[axis].min(texel);
+                greatest[axis] = greatest[axis].max(texel);
+            }
+        }
+        // The texels from the first covered to one past the last, along an axis.
+        let span = |axis: usize| {
+            // as: clamped into the image first.
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let clamp = |texel: f32| texel.clamp(0.0, extent[axis] as f32) as u32;
+            let (start, end) = (clamp(least[axis].floor()), clamp(greatest[axis].ceil()));
+            (start < end).then_some((start, end - start))
+        };
+        let ((x, width), (y, height)) = (span(0)?, span(1)?);
+        Some(([x, y], [width, height]))
+    }
 }
@@ -39,17 +39,125 @@ pub enum RenderRequest {
         picker: PickerRequest,
         info: PickerInfo,
     },
+    /// Read the composited color at a document-space point, as last rendered. Linear and premultiplied.
+    /// Errs if the point lies outside the render.
+    SampleColor {
+        document: fuzzpaint_core::state::document::ID,
+        point: [f32; 2],
+        response: RequestResponse<anyhow::Result<[f32; 4]>>,
+    },
+    /// Render the document up-to-date and write it to an image file.
+    /// Success or failure is reported to the log.
+    Export {
+        document: fuzzpaint_core::state::document::ID,
+        settings: crate::export::ExportSettings,
+    },
+    /// Fit the canvas to what's drawn on the document, see [`crate::canvas`].
+    /// Success or failure is reported to the log.
+    Autocrop {
+        document: fuzzpaint_core::state::document::ID,
+    },
+    /// Render each top-level layer on its own and write them to image files.
+    /// Success or failure is reported to the log.
+    ExportLayers {
+        document: fuzzpaint_core::state::document::ID,
+        settings: crate::export::LayerExportSettings,
+    },
+    /// Replay the document's history into an image sequence or video.
+    /// Success or failure is reported to the log.
+    Timelapse {
+        document: fuzzpaint_core::state::document::ID,
+        settings: crate::export::TimelapseSettings,
+    },
+    /// Start or stop highlighting the tiles of the document that changed since it was last saved.
+    SavedDiff {
+        document: fuzzpaint_core::state::document::ID,
+        enabled: bool,
+    },
+    /// The view of the document is zoomed into this region, or None if it isn't zoomed in far enough to
+    /// need redrawing at a higher resolution. Zoomed out, `lod` is the level of detail that suffices for it,
+    /// see [`crate::document_viewport_proxy::lod_for_view`].
+    Zoom {
+        document: fuzzpaint_core::state::document::ID,
+        region: Option<crate::document_viewport_proxy::DocumentRegion>,
+        lod: u32,
+    },
+    /// Show only this node of the document's graph, or all of it again if None.
+    Solo {
+        document: fuzzpaint_core::state::document::ID,
+        node: Option<fuzzpaint_core::state::graph::AnyID>,
+    },
+    /// Render a filter node with uncommitted parameters, until the document's graph next changes.
+    PreviewFilter {
+        document: fuzzpaint_core::state::document::ID,
+        node: fuzzpaint_core::state::graph::NodeID,
+        filter: fuzzpaint_core::filter::Filter,
+    },
 }
-pub(super) async fn handler(mut recv: tokio::sync::mpsc::Receiver<RenderRequest>) {
-    // Live as long as there are requests to serve
-    while let Some(recv) = recv.recv().await {
-        // Placeholder - fail out every request x3
-        let RenderRequest::CreatePicker { picker, .. } = recv;
-
-        match picker {
+/// Serve the request, returning a document if it needs to be redrawn as a result.
+pub(super) async fn handle(
+    renderer: &mut super::Renderer,
+    request: RenderRequest,
+) -> Option<fuzzpaint_core::state::document::ID> {
+    match request {
+        // Placeholder - fail out every picker request x3
+        RenderRequest::CreatePicker { picker, .. } => match picker {
             PickerRequest::Composited(response) | PickerRequest::Rendered(_, response) => {
                 let _ = response.send(Err(CreatePickerError::Uninhabited));
+                None
+            }
+        },
+        RenderRequest::SampleColor {
+            document,
+            point,
+            response,
+        } => {
+            // Nobody waiting, no matter.
+            let _ = response.send(renderer.sample_color(document, point).await);
+            None
+        }
+        RenderRequest::Export { document, settings } => {
+            if let Err(e) = renderer.export(document, settings).await {
+                tracing::error!("failed to export document: {e:#}");
             }
+            None
+        }
+        RenderRequest::Autocrop { document } => {
+            if let Err(e) = renderer.autocrop(document).await {
+                tracing::error!("failed to trim canvas: {e:#}");
+            }
+            // Redrawn once the change comes through the queue.
+            None
+        }
+        RenderRequest::ExportLayers { document, settings } => {
+            if let Err(e) = renderer.export_layers(document, settings).await {
+                tracing::error!("failed to export layers: {e:#}");
+            }
+            None
+        }
+        RenderRequest::Timelapse { document, settings } => {
+            if let Err(e) = renderer.timelapse(document, settings).await {
+                tracing::error!("failed to export timelapse: {e:#}");
+            }
+            None
+        }
+        RenderRequest::SavedDiff { document, enabled } => renderer
+            .set_saved_diff(document, enabled)
+            .then_some(document),
+        RenderRequest::Zoom {
+            document,
+            region,
+            lod,
+        } => renderer.set_zoom(document, region, lod).then_some(document),
+        RenderRequest::Solo { document, node } => {
+            renderer.set_solo(document, node).then_some(document)
         }
+        RenderRequest::PreviewFilter {
+            document,
+            node,
+            filter,
+        } => renderer
+            .preview_filter(document, node, filter)
+            .then_some(document),
     }
 }
@@ -0,0 +1,155 @@
+//! # Shader reload
+//!
+//! With the `shader_reload` feature in a debug build, brush and blend shaders are compiled from their sources
+//! on disk instead of using the copies built into the binary, and the renderer rebuilds its pipelines whenever
+//! one is saved. Iterating on a shader then only takes a save, rather than a rebuild of the whole crate.
+//!
+//! Only the shader's code is reloaded - its interface, such as push constants and bindings, is still that of the
+//! built-in, so changes to that still need a rebuild. Shaders that fail to compile fall back to the built-in.
+
+use crate::vulkano_prelude::*;
+use std::sync::Arc;
+
+/// `fn` to load a built-in shader, the vulkano-generated `<shader>::load`.
+pub type Loader =
+    fn(Arc<vk::Device>) -> Result<Arc<vk::ShaderModule>, vk::Validated<vk::VulkanError>>;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+/// Where a built-in shader was compiled from, mirroring the arguments of its `vulkano_shaders::shader!`.
+#[derive(Clone, Copy, Debug)]
+pub struct Source {
+    /// Relative to the crate's manifest.
+    pub path: &'static str,
+    pub kind: Kind,
+    pub defines: &'static [(&'static str, &'static str)],
+}
+impl Source {
+    #[must_use]
+    pub const fn new(path: &'static str, kind: Kind) -> Self {
+        Self {
+            path,
+            kind,
+            defines: &[],
+        }
+    }
+}
+
+/// Load the shader from `source` if reloading, else the `builtin`.
+pub fn load(
+    device: Arc<vk::Device>,
+    source: &Source,
+    builtin: Loader,
+) -> anyhow::Result<Arc<vk::ShaderModule>> {
+    #[cfg(all(debug_assertions, feature = "shader_reload"))]
+    match disk::compile(device.clone(), source) {
+        Ok(module) => return Ok(module),
+        Err(e) => tracing::error!(path = source.path, "failed to reload shader: {e:#}"),
+    }
+    #[cfg(not(all(debug_assertions, feature = "shader_reload")))]
+    let _ = source;
+    Ok(builtin(device)?)
+}
+
+/// Completes once a shader [loaded](load) from disk has changed since, and pipelines using it should be
+/// rebuilt. Never completes if not reloading.
+pub async fn changed() {
+    #[cfg(all(debug_assertions, feature = "shader_reload"))]
+    disk::CHANGED.notified().await;
+    #[cfg(not(all(debug_assertions, feature = "shader_reload")))]
+    std::future::pending::<()>().await;
+}
+
+#[cfg(all(debug_assertions, feature = "shader_reload"))]
+mod disk {
+    use super::{vk, Arc, Kind, Source};
+    use std::path::PathBuf;
+
+    /// How often sources are checked for changes.
+    const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+    pub static CHANGED: tokio::sync::Notify = tokio::sync::Notify::const_new();
+    /// Every source loaded so far, with its modification time as of loading.
+    static WATCHED: parking_lot::Mutex<Vec<(PathBuf, Option<std::time::SystemTime>)>> =
+        parking_lot::const_mutex(Vec::new());
+
+    fn modified(path: &std::path::Path) -> Option<std::time::SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+    /// Notify [`CHANGED`] when `path` next changes.
+    fn watch(path: PathBuf) {
+        static POLLER: std::sync::Once = std::sync::Once::new();
+        POLLER.call_once(|| {
+            let spawned = std::thread::Builder::new()
+                .name("Shader watcher".to_owned())
+                .spawn(|| loop {
+                    std::thread::sleep(POLL_INTERVAL);
+                    let mut changed = false;
+                    for (path, last) in WATCHED.lock().iter_mut() {
+                        let now = modified(path);
+                        if now != *last {
+                            tracing::info!(path = %path.display(), "shader changed");
+                            *last = now;
+                            changed = true;
+                        }
+                    }
+                    if changed {
+                        CHANGED.notify_one();
+                    }
+                });
+            if let Err(e) = spawned {
+                tracing::error!("failed to watch shaders: {e}");
+            }
+        });
+        let mut watched = WATCHED.lock();
+        let modified = modified(&path);
+        match watched.iter_mut().find(|(other, _)| *other == path) {
+            Some((_, last)) => *last = modified,
+            None => watched.push((path, modified)),
+        }
+    }
+    pub fn compile(
+        device: Arc<vk::Device>,
+        source: &Source,
+    ) -> anyhow::Result<Arc<vk::ShaderModule>> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(source.path);
+        // Watch before reading, so a save in between isn't missed.
+        watch(path.clone());
+        let text = std::fs::read_to_string(&path)?;
+
+        let compiler =
+            shaderc::Compiler::new().ok_or_else(|| anyhow::anyhow!("failed to start shaderc"))?;
+        let mut options = shaderc::CompileOptions::new()
+            .ok_or_else(|| anyhow::anyhow!("failed to start shaderc"))?;
+        for (name, value) in source.defines {
+            options.add_macro_definition(name, Some(value));
+        }
+        let kind = match source.kind {
+            Kind::Vertex => shaderc::ShaderKind::Vertex,
+            Kind::Fragment => shaderc::ShaderKind::Fragment,
+            Kind::Compute => shaderc::ShaderKind::Compute,
+        };
+        let artifact =
+            compiler.compile_into_spirv(&text, kind, source.path, "main", Some(&options))?;
+        if artifact.get_num_warnings() != 0 {
+            tracing::warn!(path = source.path, "{}", artifact.get_warning_messages());
+        }
+        // Safety: Valid SPIR-V, straight from shaderc. Its interface is trusted to match the built-in,
+        // as documented by the module.
+        let module = unsafe {
+            vk::ShaderModule::new(
+                device,
+                vulkano::shader::ShaderModuleCreateInfo::new(artifact.as_binary()),
+            )
+        }?;
+        tracing::debug!(path = source.path, "compiled shader from disk");
+        Ok(module)
+    }
+}
@@ -0,0 +1,598 @@
+//! # Replay
+//!
+//! Capturing the input of a session to a file with `--capture`, to be attached to a bug report, and replaying it
+//! with `--replay` to reproduce the bug, see [`crate::args`].
+//!
+//! Captures are anonymous. They hold the pen's motion within the viewport, the actions performed, the view, the
+//! tool and layer chosen, and the kinds of change each frame made to the document - but none of the document's
+//! contents, names, or paths. The rest of the interface isn't captured, so a replay draws with the brush chosen
+//! when it starts, and should be started on a copy of the document the capture was made with.
+//!
+//! Each frame is appended to the file as it's processed, so a capture survives a crash up to the frame before.
+
+use crate::actions::{Action, ActionEvent};
+use crate::global::session::{self, SavedView};
+use crate::pen_tools::StateLayer;
+use crate::stylus_events::StylusEvent;
+use crate::view_transform::ViewInfo;
+use fuzzpaint_core::{
+    queue::{deltas::Delta, DocumentCommandListener},
+    state::document::ID,
+};
+
+/// Version of the capture format, bumped on any change that older replays can't read.
+pub const VERSION: u32 = 1;
+/// Seed of the IDs allocated by a [headless](run_headless) replay, see [`fuzzpaint_core::id::with_seed`].
+const REPLAY_SEED: u32 = 1;
+
+/// A [`StylusEvent`], with its time relative to the start of the capture.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    /// Position in viewport pixels.
+    pub pos: [f32; 2],
+    #[serde(default)]
+    pub pressed: bool,
+    pub pressure: Option<f32>,
+    pub tilt: Option<[f32; 2]>,
+    pub dist: Option<f32>,
+    #[serde(default)]
+    pub eraser: bool,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub shift: bool,
+    /// Seconds since the capture started.
+    pub time: Option<f64>,
+}
+impl Event {
+    #[must_use]
+    pub fn capture(event: &StylusEvent, start: std::time::Instant) -> Self {
+        Self {
+            pos: [event.pos.0, event.pos.1],
+            pressed: event.pressed,
+            pressure: event.pressure,
+            tilt: event.tilt.map(|(x, y)| [x, y]),
+            dist: event.dist,
+            eraser: event.eraser,
+            ctrl: event.ctrl,
+            shift: event.shift,
+            time: event
+                .time
+                .map(|time| time.saturating_duration_since(start).as_secs_f64()),
+        }
+    }
+    /// The event, as if the capture started at `start`.
+    #[must_use]
+    pub fn replay(&self, start: std::time::Instant) -> StylusEvent {
+        StylusEvent {
+            pos: (self.pos[0], self.pos[1]),
+            pressed: self.pressed,
+            pressure: self.pressure,
+            tilt: self.tilt.map(|[x, y]| (x, y)),
+            dist: self.dist,
+            eraser: self.eraser,
+            ctrl: self.ctrl,
+            shift: self.shift,
+            time: self
+                .time
+                .and_then(|time| std::time::Duration::try_from_secs_f64(time).ok())
+                .map(|time| start + time),
+            // Replayed strokes weren't drawn with any tool.
+            device: None,
+        }
+    }
+}
+
+/// Serde stand-in for [`ViewInfo`].
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    /// Viewport pixels.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    // Must follow the plain values.
+    pub transform: SavedView,
+}
+impl From<&ViewInfo> for View {
+    fn from(view: &ViewInfo) -> Self {
+        Self {
+            position: [view.viewport_position.x, view.viewport_position.y],
+            size: [view.viewport_size.x, view.viewport_size.y],
+            transform: view.transform.into(),
+        }
+    }
+}
+impl View {
+    /// The view, or None if it's unusable, such as from a hand-edited file. The canvas isn't recorded, and is
+    /// left as the default.
+    #[must_use]
+    pub fn view_info(&self) -> Option<ViewInfo> {
+        Some(ViewInfo {
+            transform: self.transform.transform()?,
+            canvas: fuzzpaint_core::state::document::Canvas::default(),
+            viewport_position: self.position.into(),
+            viewport_size: self.size.into(),
+        })
+    }
+}
+
+/// One frame of input, and what it did.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct Frame {
+    /// Seconds since the capture started.
+    pub at: f64,
+    /// The tool chosen from the interface during the frame.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<StateLayer>,
+    /// The selected layer, see [`session::layer_path`], if it changed since the frame before. Empty if none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<(ActionEvent, Action)>,
+    /// The kinds of change the frame made to the document, see [`change_kind`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+    // Must follow the plain values.
+    /// The view, if it changed since the frame before.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view: Option<View>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stylus: Vec<Event>,
+}
+
+/// A whole capture file.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Capture {
+    pub version: u32,
+    #[serde(default, rename = "frame")]
+    pub frames: Vec<Frame>,
+}
+impl Capture {
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let capture: Self = toml::from_str(&std::fs::read_to_string(path)?)?;
+        if capture.version != VERSION {
+            anyhow::bail!("capture is version {}, expected {VERSION}", capture.version);
+        }
+        Ok(capture)
+    }
+}
+
+/// A name for the kind of change, leaving out what was changed.
+#[must_use]
+pub fn change_kind(delta: &Delta) -> &'static str {
+    match delta {
+        Delta::GraphChanged => "graph",
+        Delta::BlendChanged { .. } => "blend",
+        Delta::NodeChanged(_) => "node",
+        Delta::StrokeAdded { .. } => "stroke_added",
+        Delta::StrokeRemoved { .. } => "stroke_removed",
+        Delta::StrokeChanged { .. } => "stroke_changed",
+        Delta::PaletteChanged(_) => "palette",
+        Delta::CanvasChanged(_) => "canvas",
+    }
+}
+
+/// Watches the changes made to the current document during each frame.
+#[derive(Default)]
+struct Changes {
+    listener: Option<(ID, DocumentCommandListener)>,
+}
+impl Changes {
+    /// Start a frame on the document, passing over changes made since the last, which were made by the
+    /// interface rather than the input.
+    fn begin(&mut self, document: Option<ID>) {
+        let Some(document) = document else {
+            self.listener = None;
+            return;
+        };
+        match &mut self.listener {
+            Some((id, listener)) if *id == document => {
+                let _ = listener.forward();
+            }
+            _ => {
+                self.listener = crate::global::provider()
+                    .inspect(document, |queue| (document, queue.listen_from_now()));
+            }
+        }
+    }
+    /// The kinds of change since [`Self::begin`].
+    fn end(&mut self) -> Vec<String> {
+        let Some((_, listener)) = &mut self.listener else {
+            return Vec::new();
+        };
+        match listener.forward_deltas() {
+            Ok(deltas) => deltas
+                .iter()
+                .map(|delta| change_kind(delta).to_owned())
+                .collect(),
+            // Closed during the frame.
+            Err(_) => {
+                self.listener = None;
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// The selected layer of the document as a [path](session::layer_path), empty if there is none.
+fn layer_path(document: ID, layer: Option<fuzzpaint_core::state::graph::AnyID>) -> Vec<usize> {
+    layer
+        .and_then(|layer| {
+            crate::global::provider()
+                .inspect(document, |queue| {
+                    session::layer_path(queue.peek_clone_state().graph(), layer)
+                })
+                .flatten()
+        })
+        .unwrap_or_default()
+}
+
+/// Appends each frame to a capture file.
+pub struct Recorder {
+    file: std::io::BufWriter<std::fs::File>,
+    start: std::time::Instant,
+    changes: Changes,
+    /// The last written, to write only changes.
+    view: Option<View>,
+    layer: Option<Vec<usize>>,
+}
+impl Recorder {
+    /// Start a capture at the path, overwriting anything there.
+    pub fn create(path: &std::path::Path) -> anyhow::Result<Self> {
+        use std::io::Write;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "version = {VERSION}")?;
+        file.flush()?;
+        Ok(Self {
+            file,
+            start: std::time::Instant::now(),
+            changes: Changes::default(),
+            view: None,
+            layer: None,
+        })
+    }
+    /// A frame of input is about to be processed.
+    pub fn begin(&mut self, document: Option<ID>) {
+        self.changes.begin(document);
+    }
+    /// The frame begun with [`Self::begin`] was processed. `layer` is the one selected during it.
+    pub fn end(
+        &mut self,
+        view: &ViewInfo,
+        layer: Option<(ID, Option<fuzzpaint_core::state::graph::AnyID>)>,
+        tool: Option<StateLayer>,
+        stylus: &[StylusEvent],
+        actions: &[(ActionEvent, Action)],
+    ) -> anyhow::Result<()> {
+        use std::io::Write;
+        let changes = self.changes.end();
+        // Nothing happened, nothing to reproduce.
+        if stylus.is_empty() && actions.is_empty() && tool.is_none() && changes.is_empty() {
+            return Ok(());
+        }
+        let view = View::from(view);
+        let layer = layer.map(|(document, layer)| layer_path(document, layer));
+        let frame = Frame {
+            at: self.start.elapsed().as_secs_f64(),
+            tool,
+            layer: (layer != self.layer).then(|| layer.clone().unwrap_or_default()),
+            actions: actions.to_vec(),
+            changes,
+            view: (Some(view) != self.view).then_some(view),
+            stylus: stylus
+                .iter()
+                .map(|event| Event::capture(event, self.start))
+                .collect(),
+        };
+        self.view = Some(view);
+        self.layer = layer;
+
+        // Appended as its own table, which is still one valid document.
+        #[derive(serde::Serialize)]
+        struct Append<'a> {
+            frame: [&'a Frame; 1],
+        }
+        write!(
+            self.file,
+            "\n{}",
+            toml::to_string(&Append { frame: [&frame] })?
+        )?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// The input of a frame being replayed.
+pub struct Replayed {
+    pub stylus: crate::stylus_events::StylusEventFrame,
+    pub actions: crate::actions::ActionFrame,
+    /// The view at this frame, if known.
+    pub view: Option<ViewInfo>,
+    /// The tool chosen from the interface during this frame.
+    pub tool: Option<StateLayer>,
+    /// The layer selected, if it changed since the frame before. Empty if none.
+    pub layer: Option<Vec<usize>>,
+}
+
+/// How closely a replay followed its capture.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub frames: usize,
+    /// Frames that changed the document differently than when captured.
+    pub diverged: usize,
+    /// Index of the first that diverged.
+    pub first_divergence: Option<usize>,
+}
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "replayed {} frames", self.frames)?;
+        match self.first_divergence {
+            Some(first) => write!(
+                f,
+                ", {} of which changed the document differently than when captured, first at frame {first}",
+                self.diverged
+            ),
+            None => write!(f, ", all changing the document as they did when captured"),
+        }
+    }
+}
+
+/// Plays a capture back, frame by frame.
+pub struct Player {
+    frames: std::iter::Peekable<std::iter::Enumerate<std::vec::IntoIter<Frame>>>,
+    start: std::time::Instant,
+    /// Whether to wait for each frame's time, or replay as fast as they're taken.
+    realtime: bool,
+    // Actions are pushed through a stream of their own, to come out as frames just as the window's do.
+    action_sender: crate::actions::ActionSender,
+    action_listener: crate::actions::ActionListener,
+    _action_stream: crate::actions::ActionStream,
+    changes: Changes,
+    view: Option<ViewInfo>,
+    /// The frame last taken, and the changes it made when captured.
+    current: Option<(usize, Vec<String>)>,
+    summary: Summary,
+}
+impl Player {
+    #[must_use]
+    pub fn new(capture: Capture, realtime: bool) -> Self {
+        let (action_sender, action_stream) = crate::actions::create_action_stream();
+        Self {
+            frames: capture.frames.into_iter().enumerate().peekable(),
+            start: std::time::Instant::now(),
+            realtime,
+            action_sender,
+            action_listener: action_stream.listen(),
+            _action_stream: action_stream,
+            changes: Changes::default(),
+            view: None,
+            current: None,
+            summary: Summary::default(),
+        }
+    }
+    /// The next frame of input, to be processed on `document` and followed by [`Self::end`], or None once all
+    /// have been replayed.
+    ///
+    /// Cancel safe, a frame isn't taken until its time has come.
+    pub async fn next(&mut self, document: Option<ID>) -> anyhow::Result<Option<Replayed>> {
+        if self.realtime {
+            let at = self
+                .frames
+                .peek()
+                .and_then(|(_, frame)| std::time::Duration::try_from_secs_f64(frame.at).ok());
+            if let Some(at) = at {
+                tokio::time::sleep_until((self.start + at).into()).await;
+            }
+        }
+        let Some((idx, frame)) = self.frames.next() else {
+            return Ok(None);
+        };
+        for (event, action) in frame.actions {
+            match event {
+                ActionEvent::Press => self.action_sender.press(action),
+                ActionEvent::Repeat => self.action_sender.repeat(action),
+                ActionEvent::Release => self.action_sender.release(action),
+                ActionEvent::Shadowed => self.action_sender.shadow(action),
+                ActionEvent::Unshadowed => self.action_sender.unshadow(action),
+            }
+        }
+        let actions = self.action_listener.frame()?;
+        if let Some(view) = frame.view {
+            match view.view_info() {
+                Some(view) => self.view = Some(view),
+                None => tracing::warn!("unusable view in frame {idx}, keeping the last"),
+            }
+        }
+        if let Some(view) = self.view.as_mut() {
+            // Fit to the canvas as it is now, which earlier frames may have changed.
+            view.canvas = document
+                .and_then(|document| {
+                    crate::global::provider().inspect(document, |queue| {
+                        use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+                        queue.peek_clone_state().document().viewport.canvas()
+                    })
+                })
+                .unwrap_or_default();
+        }
+        let stylus = frame
+            .stylus
+            .iter()
+            .map(|event| event.replay(self.start))
+            .collect();
+
+        self.changes.begin(document);
+        self.current = Some((idx, frame.changes));
+        Ok(Some(Replayed {
+            stylus: crate::stylus_events::StylusEventFrame::new(stylus),
+            actions,
+            view: self.view,
+            tool: frame.tool,
+            layer: frame.layer,
+        }))
+    }
+    /// The frame from [`Self::next`] was processed. Compares what it did with what it did when captured.
+    pub fn end(&mut self) {
+        let Some((idx, expected)) = self.current.take() else {
+            return;
+        };
+        let changes = self.changes.end();
+        self.summary.frames += 1;
+        if changes != expected {
+            tracing::warn!(
+                "frame {idx} diverged from the capture, made {changes:?} rather than {expected:?}"
+            );
+            self.summary.diverged += 1;
+            self.summary.first_divergence.get_or_insert(idx);
+        }
+    }
+    #[must_use]
+    pub fn summary(&self) -> Summary {
+        self.summary
+    }
+}
+
+/// What's done with the input as it's processed.
+pub enum Mode {
+    /// Processed as usual.
+    Live,
+    Capture(Recorder),
+    /// The window's input is ignored until the replay is done.
+    Replay(Player),
+}
+
+/// Replay without showing the window, as quickly as the tools allow, on `document`, the first given on the
+/// command line. Returns how closely it followed the capture.
+pub fn run_headless(
+    context: &std::sync::Arc<crate::render_device::RenderContext>,
+    capture: Capture,
+    document: Option<ID>,
+) -> anyhow::Result<Summary> {
+    let Some(document) = document else {
+        anyhow::bail!("replaying without a window needs the document the capture was made on")
+    };
+    *crate::AdHocGlobals::get().write() = Some(crate::AdHocGlobals {
+        document,
+        brush: crate::ui::startup_brush(),
+        secondary_color: fuzzpaint_core::color::ColorOrPalette::WHITE,
+        eraser_tip: crate::pen_tools::EraserTipMode::default(),
+        node: None,
+    });
+    let mut tools = crate::pen_tools::ToolState::new_from_renderer(context)?;
+    let mut player = Player::new(capture, false);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()?;
+    // Tools run on this thread, so the layers and strokes they make get the same IDs each replay.
+    fuzzpaint_core::id::with_seed(REPLAY_SEED, || {
+        runtime.block_on(async {
+            while let Some(replayed) = player.next(Some(document)).await? {
+                if let Some(path) = replayed.layer {
+                    let layer = crate::global::provider()
+                        .inspect(document, |queue| {
+                            session::layer_at(queue.peek_clone_state().graph(), &path)
+                        })
+                        .flatten();
+                    if let Some(globals) = crate::AdHocGlobals::get().write().as_mut() {
+                        globals.node = layer;
+                    }
+                }
+                let Some(view) = replayed.view else {
+                    // Nothing can be drawn without knowing where.
+                    player.end();
+                    continue;
+                };
+                let requests = replayed
+                    .tool
+                    .map(|tool| crate::ui::requests::UiRequest::SetBaseTool { tool })
+                    .into_iter()
+                    .collect();
+                let _ = tools
+                    .process(&view, replayed.stylus, &replayed.actions, requests)
+                    .await;
+                player.end();
+            }
+            anyhow::Ok(())
+        })
+    })??;
+    Ok(player.summary())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Capture, Event, Frame, View, VERSION};
+    use crate::actions::{Action, ActionEvent};
+
+    #[test]
+    fn appended_frames() {
+        let frame = |at: f64| Frame {
+            at,
+            tool: Some(crate::pen_tools::StateLayer::Lasso),
+            layer: Some(vec![1, 0]),
+            actions: vec![(ActionEvent::Press, Action::Undo)],
+            changes: vec!["stroke_added".to_owned()],
+            view: Some(View {
+                position: [0.0, 20.0],
+                size: [800.0, 600.0],
+                transform: crate::global::session::SavedView::default(),
+            }),
+            stylus: vec![Event {
+                pos: [10.0, 12.5],
+                pressed: true,
+                pressure: Some(0.5),
+                tilt: None,
+                dist: None,
+                eraser: false,
+                ctrl: true,
+                shift: false,
+                time: Some(at),
+            }],
+        };
+        #[derive(serde::Serialize)]
+        struct Append<'a> {
+            frame: [&'a Frame; 1],
+        }
+        // Written as the recorder does, a header followed by each frame on its own.
+        let mut file = format!("version = {VERSION}\n");
+        for at in [0.0, 0.5] {
+            file.push('\n');
+            file.push_str(
+                &toml::to_string(&Append {
+                    frame: [&frame(at)],
+                })
+                .unwrap(),
+            );
+        }
+        // Frames with nothing but the time.
+        file.push_str(&format!(
+            "\n{}",
+            toml::to_string(&Append {
+                frame: [&Frame::default()]
+            })
+            .unwrap()
+        ));
+        let capture: Capture = toml::from_str(&file).unwrap();
+        assert_eq!(capture.version, VERSION);
+        assert_eq!(
+            capture.frames,
+            vec![frame(0.0), frame(0.5), Frame::default()]
+        );
+    }
+    #[test]
+    fn event_times() {
+        let start = std::time::Instant::now();
+        let event = crate::stylus_events::StylusEvent {
+            time: Some(start + std::time::Duration::from_millis(250)),
+            ..crate::stylus_events::StylusEvent::empty()
+        };
+        let captured = Event::capture(&event, start);
+        assert!((captured.time.unwrap() - 0.25).abs() < 1e-9);
+        // Replayed relative to a new start.
+        let later = start + std::time::Duration::from_secs(60);
+        let replayed = captured.replay(later);
+        assert_eq!(
+            replayed.time.unwrap().duration_since(later),
+            std::time::Duration::from_millis(250)
+        );
+    }
+}
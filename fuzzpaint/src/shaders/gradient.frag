@@ -0,0 +1,70 @@
+#version 460
+// Fills the leaf with a gradient, evaluated per-pixel. Mirrors `fuzzpaint_core::gradient::Gradient::position_of`.
+
+struct Stop {
+    // Premultiplied, linear.
+    float color[4];
+    float position;
+};
+// Sorted by position.
+layout(set = 0, binding = 0, std430) readonly restrict buffer Stops {
+    Stop stops[];
+};
+
+#define SHAPE_LINEAR 0
+#define SHAPE_RADIAL 1
+
+layout(push_constant) uniform Gradient {
+    uint shape;
+    uint stop_count;
+    // In document pixels.
+    vec2 start;
+    vec2 end;
+    // The document point at the bottom-left corner of the image, and the document pixels per image pixel.
+    vec2 origin;
+    float pixel_size;
+    // Height of the image in pixels.
+    float height;
+};
+
+layout(location = 0) out vec4 out_color;
+
+vec4 color_of(uint idx) {
+    return vec4(stops[idx].color[0], stops[idx].color[1], stops[idx].color[2], stops[idx].color[3]);
+}
+
+void main() {
+    if (stop_count == 0) {
+        out_color = vec4(0.0);
+        return;
+    }
+    // Same projection as strokes, where the top of the region is the bottom row of the image.
+    vec2 point = origin + vec2(gl_FragCoord.x, height - gl_FragCoord.y) * pixel_size;
+
+    vec2 axis = end - start;
+    vec2 offset = point - start;
+    float axis_len_sq = dot(axis, axis);
+    float t;
+    if (axis_len_sq <= 1.1920929e-7) {
+        t = 1.0;
+    } else if (shape == SHAPE_LINEAR) {
+        t = dot(offset, axis) / axis_len_sq;
+    } else {
+        t = sqrt(dot(offset, offset) / axis_len_sq);
+    }
+
+    // Find the pair of stops surrounding t, holding the end colors past either end.
+    if (t <= stops[0].position) {
+        out_color = color_of(0);
+        return;
+    }
+    for (uint i = 1; i < stop_count; ++i) {
+        if (t < stops[i].position) {
+            float from = stops[i - 1].position;
+            float span = stops[i].position - from;
+            out_color = mix(color_of(i - 1), color_of(i), (t - from) / span);
+            return;
+        }
+    }
+    out_color = color_of(stop_count - 1);
+}
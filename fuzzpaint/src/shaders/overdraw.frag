@@ -0,0 +1,9 @@
+#version 460
+// Counts the stamps covering each fragment, see `Visualizations::OVERDRAW`. Blended additively, so a pixel
+// beneath ten stamps is full red, turning yellow and then white beneath more as the channels saturate in turn.
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    out_color = vec4(0.1, 0.03, 0.01, 0.1);
+}
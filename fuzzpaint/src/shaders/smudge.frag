@@ -0,0 +1,40 @@
+#version 460
+layout(set = 0, binding = 0) uniform sampler2DArray brush_tex;
+// Every brush texture, for the dual stamp to pick from, see stamp.frag.
+layout(set = 0, binding = 1) uniform sampler2DArray dual_tex;
+// Coverage of the selection the stroke is clipped to, see stamp.frag.
+layout(set = 1, binding = 0) uniform sampler2D clip_mask;
+// Copy of the layer as the last pass left it.
+layout(set = 2, binding = 0) uniform sampler2D snapshot;
+
+layout(push_constant) uniform Matrix {
+    mat4 mvp;
+    vec4 clip_transform;
+} push_matrix;
+
+layout(location = 0) in vec4 color;
+layout(location = 2) in vec2 uv;
+// Offset from where color is picked up to the stamp, in normalized device coordinates.
+layout(location = 3) in vec2 drag;
+layout(location = 4) in vec2 dual_uv;
+layout(location = 5) flat in float dual_layer;
+
+layout(location = 0, index = 0) out vec4 out_color;
+layout(location = 0, index = 1) out vec4 out_constants;
+
+void main() {
+    vec2 clip_uv = gl_FragCoord.xy * push_matrix.clip_transform.xy + push_matrix.clip_transform.zw;
+    float clip = texture(clip_mask, clip_uv).r;
+    // Device coordinates span two, UVs one.
+    vec2 pickup_uv = gl_FragCoord.xy / vec2(textureSize(snapshot, 0)) - drag * 0.5;
+    vec4 pickup = texture(snapshot, pickup_uv);
+    // Flow is the strength of the smudge.
+    float dual = dual_layer < 0.0 ? 1.0 : texture(dual_tex, vec3(dual_uv, dual_layer)).a;
+    float strength = color.a * texture(brush_tex, vec3(uv, 0.0)).a * clip * dual;
+
+    // With the dual-source blend, mixes the layer towards the picked up color by `strength`:
+    // rgb = pickup.rgb * strength + dst.rgb * (1 - strength)
+    // a = pickup.a * strength + dst.a * (1 - strength)
+    out_color = vec4(pickup.rgb * strength, strength);
+    out_constants = vec4(1.0, 1.0, 1.0, pickup.a);
+}
@@ -1,9 +1,24 @@
 #version 460
 layout(set = 0, binding = 0) uniform sampler2DArray brush_tex;
+// Every brush texture, for the dual stamp to pick from.
+layout(set = 0, binding = 1) uniform sampler2DArray dual_tex;
+// Coverage of the selection the stroke is clipped to, spanning the whole document.
+// Unclipped strokes get a single white texel.
+layout(set = 1, binding = 0) uniform sampler2D clip_mask;
+
+layout(push_constant) uniform Matrix {
+    mat4 mvp;
+    // Scale and offset from framebuffer coordinates to clip mask UV, as the layer may cover only
+    // part of the document.
+    vec4 clip_transform;
+} push_matrix;
 
 layout(location = 0) in vec4 color;
 layout(location = 1) in vec4 blend_constants;
 layout(location = 2) in vec2 uv;
+layout(location = 4) in vec2 dual_uv;
+// Negative for no dual stamp.
+layout(location = 5) flat in float dual_layer;
 
 // Output color
 layout(location = 0, index = 0) out vec4 out_color;
@@ -11,6 +26,9 @@ layout(location = 0, index = 0) out vec4 out_color;
 layout(location = 0, index = 1) out vec4 out_constants;
 
 void main() {
-    out_color = color * texture(brush_tex, vec3(uv, 0.0));
+    vec2 clip_uv = gl_FragCoord.xy * push_matrix.clip_transform.xy + push_matrix.clip_transform.zw;
+    float clip = texture(clip_mask, clip_uv).r;
+    float dual = dual_layer < 0.0 ? 1.0 : texture(dual_tex, vec3(dual_uv, dual_layer)).a;
+    out_color = color * texture(brush_tex, vec3(uv, 0.0)) * clip * dual;
     out_constants = blend_constants;
-}
\ No newline at end of file
+}
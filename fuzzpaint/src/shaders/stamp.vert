@@ -2,21 +2,34 @@
 
 layout(push_constant) uniform Matrix {
     mat4 mvp;
+    // Unused here, see stamp.frag.
+    vec4 clip_transform;
 } push_matrix;
 
 layout(location = 0) in vec2 pos;
 layout(location = 1) in vec2 uv;
 layout(location = 2) in vec4 color;
 layout(location = 3) in float erase;
+layout(location = 4) in vec2 drag;
+layout(location = 5) in vec2 dual_uv;
+layout(location = 6) in float dual_layer;
 
 layout(location = 0) out vec4 out_color;
 layout(location = 1) out vec4 blend_constants;
 layout(location = 2) out vec2 out_uv;
+// Unused by stamp.frag, see smudge.frag.
+layout(location = 3) out vec2 out_drag;
+layout(location = 4) out vec2 out_dual_uv;
+layout(location = 5) flat out float out_dual_layer;
 
 void main() {
     out_color = color;
     blend_constants = 1.0 - erase.xxxx;
     out_uv = uv;
+    out_dual_uv = dual_uv;
+    out_dual_layer = dual_layer;
+    // Just the direction, no translation.
+    out_drag = (push_matrix.mvp * vec4(drag, 0.0, 0.0)).xy;
 
     vec4 position_2d = push_matrix.mvp * vec4(pos, 0.0, 1.0);
     gl_Position = vec4(position_2d.xy, 0.0, 1.0);
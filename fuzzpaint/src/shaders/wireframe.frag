@@ -0,0 +1,12 @@
+#version 460
+// Outlines of stamps, see `Visualizations::WIREFRAME`. Drawn over the strokes with the stamp blend, without
+// sampling the brush, whose edges are transparent.
+
+layout(location = 0, index = 0) out vec4 out_color;
+layout(location = 0, index = 1) out vec4 out_constants;
+
+void main() {
+    // Pre-multiplied magenta, rarely a color strokes are drawn in.
+    out_color = vec4(0.8, 0.0, 0.8, 0.8);
+    out_constants = vec4(1.0);
+}
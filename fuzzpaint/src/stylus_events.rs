@@ -71,6 +71,16 @@ pub struct StylusEvent {
     pub pressure: Option<f32>,
     pub tilt: Option<(f32, f32)>,
     pub dist: Option<f32>,
+    /// The event came from the eraser end of a stylus.
+    pub eraser: bool,
+    /// Control was held, asking for precision.
+    pub ctrl: bool,
+    /// Shift was held, asking for constraint.
+    pub shift: bool,
+    /// When the event was received, if known.
+    pub time: Option<std::time::Instant>,
+    /// Serial number of the tablet tool the event came from, if it reports one.
+    pub device: Option<u64>,
 }
 impl StylusEvent {
     #[must_use]
@@ -81,6 +91,11 @@ impl StylusEvent {
             pressure: None,
             tilt: None,
             dist: None,
+            eraser: false,
+            ctrl: false,
+            shift: false,
+            time: None,
+            device: None,
         }
     }
 }
@@ -114,9 +129,497 @@ impl StylusAxes for StylusEvent {
     }
 }
 
+/// Remaps the pressure reported by a tablet, as different devices need to be pressed very differently hard.
+/// Pressure becomes `(raw / saturation) ^ gamma`, clamped to `[0, 1]`.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PressureCurve {
+    /// Raw pressure that reads as full pressure. Always within [`Self::SATURATION_RANGE`].
+    pub saturation: f32,
+    /// Above one, light strokes become lighter. Always within [`Self::GAMMA_RANGE`].
+    pub gamma: f32,
+}
+impl Default for PressureCurve {
+    fn default() -> Self {
+        Self {
+            saturation: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+impl PressureCurve {
+    pub const SATURATION_RANGE: std::ops::RangeInclusive<f32> = 0.05..=1.0;
+    pub const GAMMA_RANGE: std::ops::RangeInclusive<f32> = 0.2..=5.0;
+    /// Where a natural light, medium, and heavy stroke land after [`Self::fit`].
+    pub const TARGETS: [f32; 3] = [0.2, 0.5, 0.9];
+    #[must_use]
+    pub fn apply(self, raw: f32) -> f32 {
+        (raw / self.saturation).clamp(0.0, 1.0).powf(self.gamma)
+    }
+    /// Clamp the parameters into range, defaulting any that aren't finite.
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let clamp = |value: f32, range: std::ops::RangeInclusive<f32>| {
+            if value.is_finite() {
+                value.clamp(*range.start(), *range.end())
+            } else {
+                1.0
+            }
+        };
+        Self {
+            saturation: clamp(self.saturation, Self::SATURATION_RANGE),
+            gamma: clamp(self.gamma, Self::GAMMA_RANGE),
+        }
+    }
+    /// Fit a curve taking the typical raw pressure of the user's light, medium, and heavy strokes to
+    /// [`Self::TARGETS`]. None unless the pressures are increasing.
+    #[must_use]
+    pub fn fit(typical: [f32; 3]) -> Option<Self> {
+        let [light, medium, heavy] = typical;
+        if !(light > 0.0 && light < medium && medium < heavy && heavy.is_finite()) {
+            return None;
+        }
+        // Least squares through the log of both sides, where the curve is a line:
+        // ln(target) = gamma * ln(raw) - gamma * ln(saturation)
+        let xs = typical.map(f32::ln);
+        let ys = Self::TARGETS.map(f32::ln);
+        let mean = |values: [f32; 3]| values.iter().sum::<f32>() / 3.0;
+        let (mean_x, mean_y) = (mean(xs), mean(ys));
+        let (covariance, variance) =
+            xs.iter()
+                .zip(&ys)
+                .fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+                    (
+                        (x - mean_x).mul_add(y - mean_y, covariance),
+                        (x - mean_x).mul_add(x - mean_x, variance),
+                    )
+                });
+        let gamma = covariance / variance;
+        let saturation = (mean_x - mean_y / gamma).exp();
+        Some(Self { saturation, gamma }.sanitized())
+    }
+}
+/// Pressure made up for input that reports none, such as a mouse, so that its strokes needn't be flat.
+/// Pressure starts at zero and fades in over the start of a stroke, and fades out as the pointer speeds up,
+/// giving tapered flicks.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SimulatedPressure {
+    /// Pressure of a slow, settled stroke. Always within `[0, 1]`.
+    pub pressure: f32,
+    /// Milliseconds for pressure to fade in from zero, or 0 to start at full. Always within
+    /// [`Self::FADE_IN_RANGE`].
+    pub fade_in_ms: u32,
+    /// Pointer speed, in pixels per second, at which pressure fades out entirely, or 0 to ignore speed.
+    /// Always within [`Self::FADE_SPEED_RANGE`].
+    pub fade_speed: f32,
+}
+impl Default for SimulatedPressure {
+    /// Constant full pressure.
+    fn default() -> Self {
+        Self {
+            pressure: 1.0,
+            fade_in_ms: 0,
+            fade_speed: 0.0,
+        }
+    }
+}
+impl SimulatedPressure {
+    pub const FADE_IN_RANGE: std::ops::RangeInclusive<u32> = 0..=1000;
+    pub const FADE_SPEED_RANGE: std::ops::RangeInclusive<f32> = 0.0..=20_000.0;
+    /// Pressure `elapsed` into a stroke, with the pointer moving at `speed` pixels per second.
+    #[must_use]
+    pub fn apply(self, elapsed: std::time::Duration, speed: f32) -> f32 {
+        let fade_in = if self.fade_in_ms == 0 {
+            1.0
+        } else {
+            // as: precision loss is irrelevant at this scale.
+            #[allow(clippy::cast_precision_loss)]
+            let fade_in = elapsed.as_secs_f32() * 1000.0 / self.fade_in_ms as f32;
+            fade_in.min(1.0)
+        };
+        let fade_out = if self.fade_speed > 0.0 {
+            (1.0 - speed / self.fade_speed).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        self.pressure * fade_in * fade_out
+    }
+    /// Clamp the parameters into range, defaulting any that aren't finite.
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let defaults = Self::default();
+        Self {
+            pressure: if self.pressure.is_finite() {
+                self.pressure.clamp(0.0, 1.0)
+            } else {
+                defaults.pressure
+            },
+            fade_in_ms: self.fade_in_ms.min(*Self::FADE_IN_RANGE.end()),
+            fade_speed: if self.fade_speed.is_finite() {
+                self.fade_speed.clamp(
+                    *Self::FADE_SPEED_RANGE.start(),
+                    *Self::FADE_SPEED_RANGE.end(),
+                )
+            } else {
+                defaults.fade_speed
+            },
+        }
+    }
+}
+/// A stroke without reported pressure, as tracked for [`SimulatedPressure`].
+#[derive(Clone, Copy, Debug)]
+struct SimulatedStroke {
+    start: std::time::Instant,
+    last_pos: (f32, f32),
+    last_time: std::time::Instant,
+    /// Pixels per second, averaged over recent events to hide jitter in their timing.
+    speed: f32,
+}
+impl SimulatedStroke {
+    fn new(pos: (f32, f32), now: std::time::Instant) -> Self {
+        Self {
+            start: now,
+            last_pos: pos,
+            last_time: now,
+            speed: 0.0,
+        }
+    }
+    fn advance(&mut self, pos: (f32, f32), now: std::time::Instant) {
+        let elapsed = now.saturating_duration_since(self.last_time).as_secs_f32();
+        // Several events may land at once, speed is only known once time has passed.
+        if elapsed > 0.0 {
+            let distance = (pos.0 - self.last_pos.0).hypot(pos.1 - self.last_pos.1);
+            self.speed = (distance / elapsed - self.speed).mul_add(0.5, self.speed);
+            self.last_pos = pos;
+            self.last_time = now;
+        }
+    }
+}
+
+/// Evens out noisy tablet pressure over time, leaving position be. An exponential moving average, started
+/// afresh with each stroke so that it doesn't lag in from zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PressureFilter {
+    /// Time for the smoothed pressure to close most (1 - 1/e) of the gap to a new reading. Zero disables
+    /// smoothing.
+    pub time_constant: std::time::Duration,
+    /// The smoothed pressure, and when it was last updated. None at the start of a stroke.
+    state: Option<(f32, std::time::Instant)>,
+}
+impl PressureFilter {
+    #[must_use]
+    pub fn new(time_constant: std::time::Duration) -> Self {
+        Self {
+            time_constant,
+            state: None,
+        }
+    }
+    /// Forget past readings, such as when the stylus lifts.
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+    /// Take in a reading taken at `now`, returning the smoothed pressure.
+    pub fn apply(&mut self, raw: f32, now: std::time::Instant) -> f32 {
+        let smoothed = match self.state {
+            Some((last, then)) if !self.time_constant.is_zero() => {
+                let elapsed = now.saturating_duration_since(then).as_secs_f32();
+                // Weighted by time rather than per-reading, so it behaves the same at any report rate.
+                let weight = 1.0 - (-elapsed / self.time_constant.as_secs_f32()).exp();
+                (raw - last).mul_add(weight, last)
+            }
+            _ => raw,
+        };
+        self.state = Some((smoothed, now));
+        smoothed
+    }
+}
+
+/// What the surface of a tablet is mapped onto.
+#[derive(
+    Copy,
+    Clone,
+    Default,
+    strum::EnumIter,
+    PartialEq,
+    Eq,
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingTarget {
+    /// Every monitor, as the system maps it.
+    #[default]
+    Desktop,
+    /// Fuzzpaint's window.
+    Window,
+    /// The document, wherever it's shown in the viewport.
+    Canvas,
+}
+/// How the pen moves the pointer.
+#[derive(
+    Copy,
+    Clone,
+    Default,
+    strum::EnumIter,
+    PartialEq,
+    Eq,
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingMode {
+    /// Each point of the tablet is a point of the target.
+    #[default]
+    Absolute,
+    /// The pointer moves as the pen does, like a mouse, and stays put while the pen is lifted away.
+    Relative,
+}
+/// Remaps tablet positions, which the system maps onto the whole desktop, onto some other target.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TabletMapping {
+    pub target: MappingTarget,
+    pub mode: MappingMode,
+    /// Width over height of the tablet's active area. Always within [`Self::ASPECT_RANGE`].
+    pub tablet_aspect: f32,
+    /// In absolute mode, shrink the target to the tablet's aspect ratio, so that circles stay round.
+    pub keep_aspect: bool,
+    /// In relative mode, pointer travel per pen travel. Always within [`Self::SPEED_RANGE`].
+    pub speed: f32,
+}
+impl Default for TabletMapping {
+    /// As the system maps it.
+    fn default() -> Self {
+        Self {
+            target: MappingTarget::Desktop,
+            mode: MappingMode::Absolute,
+            tablet_aspect: 1.6,
+            keep_aspect: false,
+            speed: 1.0,
+        }
+    }
+}
+impl TabletMapping {
+    pub const ASPECT_RANGE: std::ops::RangeInclusive<f32> = 0.25..=4.0;
+    pub const SPEED_RANGE: std::ops::RangeInclusive<f32> = 0.1..=4.0;
+    /// Whether positions are left as the system maps them.
+    #[must_use]
+    pub fn passes_through(self) -> bool {
+        self.mode == MappingMode::Absolute
+            && self.target == MappingTarget::Desktop
+            && !self.keep_aspect
+    }
+    /// Clamp the parameters into range, defaulting any that aren't finite.
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let defaults = Self::default();
+        let clamp = |value: f32, range: std::ops::RangeInclusive<f32>, default: f32| {
+            if value.is_finite() {
+                value.clamp(*range.start(), *range.end())
+            } else {
+                default
+            }
+        };
+        Self {
+            tablet_aspect: clamp(
+                self.tablet_aspect,
+                Self::ASPECT_RANGE,
+                defaults.tablet_aspect,
+            ),
+            speed: clamp(self.speed, Self::SPEED_RANGE, defaults.speed),
+            ..self
+        }
+    }
+}
+/// A parallelogram, `origin` being one corner and `x` and `y` the sides leaving it. Rotated when the canvas
+/// is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MappingArea {
+    pub origin: [f32; 2],
+    pub x: [f32; 2],
+    pub y: [f32; 2],
+}
+impl MappingArea {
+    #[must_use]
+    pub fn rect(min: [f32; 2], size: [f32; 2]) -> Self {
+        Self {
+            origin: min,
+            x: [size[0], 0.0],
+            y: [0.0, size[1]],
+        }
+    }
+    /// The point `[u, v]` of the way along each side.
+    #[must_use]
+    pub fn at(&self, [u, v]: [f32; 2]) -> [f32; 2] {
+        [
+            self.y[0].mul_add(v, self.x[0].mul_add(u, self.origin[0])),
+            self.y[1].mul_add(v, self.x[1].mul_add(u, self.origin[1])),
+        ]
+    }
+    /// How far along each side the point is, the inverse of [`Self::at`]. None if the area is degenerate.
+    #[must_use]
+    pub fn local(&self, point: [f32; 2]) -> Option<[f32; 2]> {
+        let determinant = self.x[0] * self.y[1] - self.x[1] * self.y[0];
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let [dx, dy] = [point[0] - self.origin[0], point[1] - self.origin[1]];
+        Some([
+            (dx * self.y[1] - dy * self.y[0]) / determinant,
+            (dy * self.x[0] - dx * self.x[1]) / determinant,
+        ])
+    }
+    /// The nearest point within the area.
+    #[must_use]
+    pub fn clamp(&self, point: [f32; 2]) -> [f32; 2] {
+        self.local(point)
+            .map_or(point, |uv| self.at(uv.map(|t| t.clamp(0.0, 1.0))))
+    }
+    /// The largest area of the given width over height that fits centered within this one.
+    #[must_use]
+    pub fn letterboxed(&self, aspect: f32) -> Self {
+        let width = self.x[0].hypot(self.x[1]);
+        let height = self.y[0].hypot(self.y[1]);
+        if !(width > 0.0 && height > 0.0 && aspect > 0.0) {
+            return *self;
+        }
+        // Shrink whichever side is too long, keeping the center.
+        let (scale_x, scale_y) = if width / height > aspect {
+            (aspect * height / width, 1.0)
+        } else {
+            (1.0, width / (aspect * height))
+        };
+        let origin = self.at([(1.0 - scale_x) / 2.0, (1.0 - scale_y) / 2.0]);
+        Self {
+            origin,
+            x: self.x.map(|c| c * scale_x),
+            y: self.y.map(|c| c * scale_y),
+        }
+    }
+}
+/// Where each [`MappingTarget`] currently is, in the window's physical pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct MappingGeometry {
+    /// None where the desktop's layout is unknown, such as on Wayland.
+    pub desktop: Option<MappingArea>,
+    pub window: MappingArea,
+    /// None while no document is shown.
+    pub canvas: Option<MappingArea>,
+}
+impl MappingGeometry {
+    #[must_use]
+    pub fn get(&self, target: MappingTarget) -> Option<MappingArea> {
+        match target {
+            MappingTarget::Desktop => self.desktop,
+            MappingTarget::Window => Some(self.window),
+            MappingTarget::Canvas => self.canvas,
+        }
+    }
+}
+/// Applies a [`TabletMapping`] to the positions reported by a tablet.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TabletMapper {
+    mapping: TabletMapping,
+    /// The last position reported, for relative mode. None while the pen is away.
+    last: Option<[f32; 2]>,
+    /// The pointer, in relative mode.
+    cursor: Option<[f32; 2]>,
+}
+impl TabletMapper {
+    #[must_use]
+    pub fn mapping(&self) -> TabletMapping {
+        self.mapping
+    }
+    pub fn set_mapping(&mut self, mapping: TabletMapping) {
+        if mapping != self.mapping {
+            *self = Self {
+                mapping,
+                ..Self::default()
+            };
+        }
+    }
+    /// The pen left proximity, its next position starts a fresh relative motion.
+    pub fn leave(&mut self) {
+        self.last = None;
+    }
+    /// Remap a position, from where the system maps it to where the mapping does.
+    ///
+    /// Absolute mapping relies on knowing where the desktop is. Where it isn't, positions are left be. Either
+    /// way, positions are only reported while the pen is over the window as the system maps it, so mapping
+    /// onto the window or canvas works best with the window maximized.
+    pub fn map(&mut self, position: [f32; 2], geometry: &MappingGeometry) -> [f32; 2] {
+        let target = geometry.get(self.mapping.target);
+        match self.mapping.mode {
+            MappingMode::Absolute => {
+                let (Some(desktop), Some(target)) = (geometry.desktop, target) else {
+                    return position;
+                };
+                let target = if self.mapping.keep_aspect {
+                    target.letterboxed(self.mapping.tablet_aspect)
+                } else {
+                    target
+                };
+                desktop.local(position).map_or(position, |uv| target.at(uv))
+            }
+            MappingMode::Relative => {
+                let target = target.unwrap_or(geometry.window);
+                let delta = self.last.map_or([0.0; 2], |last| {
+                    [
+                        (position[0] - last[0]) * self.mapping.speed,
+                        (position[1] - last[1]) * self.mapping.speed,
+                    ]
+                });
+                let cursor = self.cursor.unwrap_or(position);
+                let cursor = target.clamp([cursor[0] + delta[0], cursor[1] + delta[1]]);
+                self.last = Some(position);
+                self.cursor = Some(cursor);
+                cursor
+            }
+        }
+    }
+}
+
+/// The typical pressure of a stroke, the median of its samples. None if there are none.
+#[must_use]
+pub fn typical_pressure(samples: &mut [f32]) -> Option<f32> {
+    samples.sort_unstable_by(f32::total_cmp);
+    samples.get(samples.len() / 2).copied()
+}
+
+/// Bits of the latest pressure reported by a tablet, before any [`PressureCurve`].
+static RAW_PRESSURE: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+/// Note the pressure reported by a tablet, see [`raw_pressure`].
+pub fn record_raw_pressure(pressure: f32) {
+    RAW_PRESSURE.store(pressure.to_bits(), std::sync::atomic::Ordering::Relaxed);
+}
+/// The latest pressure reported by a tablet, before any [`PressureCurve`]. This is seen even when the
+/// stylus is over the UI, which otherwise has no notion of pressure, for calibration.
+#[must_use]
+pub fn raw_pressure() -> f32 {
+    f32::from_bits(RAW_PRESSURE.load(std::sync::atomic::Ordering::Relaxed))
+}
+
 pub struct WinitStylusEventCollector {
     mouse_pressed: bool,
+    eraser: bool,
+    /// See [`StylusEvent::device`].
+    device: Option<u64>,
+    ctrl: bool,
+    shift: bool,
+    /// Raw pressure of the next event.
     pressure: Option<f32>,
+    /// Distance from the surface, kept until changed.
+    distance: Option<f32>,
+    pressure_curve: PressureCurve,
+    /// Applied to raw pressure, before the [`PressureCurve`].
+    pressure_filter: PressureFilter,
+    /// Applied in place of pressure when there is none.
+    simulated_pressure: SimulatedPressure,
+    /// The pressed stroke, if it has no reported pressure yet.
+    simulated_stroke: Option<SimulatedStroke>,
     events: Vec<StylusEvent>,
 
     frame_channel: tokio::sync::broadcast::Sender<StylusEventFrame>,
@@ -126,21 +629,51 @@ impl Default for WinitStylusEventCollector {
         let (sender, _) = tokio::sync::broadcast::channel(32);
         Self {
             mouse_pressed: false,
+            eraser: false,
+            device: None,
+            ctrl: false,
+            shift: false,
             events: Vec::new(),
             frame_channel: sender,
             pressure: None,
+            distance: None,
+            pressure_curve: PressureCurve::default(),
+            pressure_filter: PressureFilter::default(),
+            simulated_pressure: SimulatedPressure::default(),
+            simulated_stroke: None,
         }
     }
 }
 impl WinitStylusEventCollector {
     pub fn push_position(&mut self, pos: (f32, f32)) {
+        let now = std::time::Instant::now();
+        let pressure = match self.pressure {
+            Some(raw) => {
+                // A tablet after all.
+                self.simulated_stroke = None;
+                self.pressure_curve
+                    .apply(self.pressure_filter.apply(raw, now))
+            }
+            None if self.mouse_pressed => {
+                let stroke = self
+                    .simulated_stroke
+                    .get_or_insert_with(|| SimulatedStroke::new(pos, now));
+                stroke.advance(pos, now);
+                self.simulated_pressure
+                    .apply(now.saturating_duration_since(stroke.start), stroke.speed)
+            }
+            None => 0.0,
+        };
         let event = StylusEvent {
             pos,
             pressed: self.mouse_pressed,
-            pressure: Some(
-                self.pressure
-                    .unwrap_or(if self.mouse_pressed { 1.0 } else { 0.0 }),
-            ),
+            eraser: self.eraser,
+            device: self.device,
+            ctrl: self.ctrl,
+            shift: self.shift,
+            dist: self.distance,
+            pressure: Some(pressure),
+            time: Some(now),
             ..StylusEvent::empty()
         };
 
@@ -148,10 +681,45 @@ impl WinitStylusEventCollector {
 
         self.events.push(event);
     }
+    /// Set the raw pressure of the next event, to be remapped by the [`PressureCurve`].
     pub fn set_pressure(&mut self, pressure: f32) {
+        record_raw_pressure(pressure);
         self.pressure = Some(pressure);
     }
+    /// Set the distance of the stylus from the surface, `[0, 1]`, or `None` if unknown. Only tablets
+    /// report it, winit has no such axis.
+    pub fn set_distance(&mut self, distance: Option<f32>) {
+        self.distance = distance.map(|distance| distance.clamp(0.0, 1.0));
+    }
+    pub fn set_pressure_curve(&mut self, curve: PressureCurve) {
+        self.pressure_curve = curve;
+    }
+    pub fn set_simulated_pressure(&mut self, simulated: SimulatedPressure) {
+        self.simulated_pressure = simulated;
+    }
+    /// Set the time constant of the [`PressureFilter`], zero to disable it.
+    pub fn set_pressure_smoothing(&mut self, time_constant: std::time::Duration) {
+        self.pressure_filter.time_constant = time_constant;
+    }
+    /// Set whether following events come from the eraser end of a stylus.
+    pub fn set_eraser(&mut self, eraser: bool) {
+        self.eraser = eraser;
+    }
+    /// Set the serial number of the tablet tool following events come from, if any.
+    pub fn set_device(&mut self, device: Option<u64>) {
+        self.device = device;
+    }
+    /// Set the keyboard modifiers held during following events.
+    pub fn set_modifiers(&mut self, modifiers: winit::keyboard::ModifiersState) {
+        self.ctrl = modifiers.control_key();
+        self.shift = modifiers.shift_key();
+    }
     pub fn set_mouse_pressed(&mut self, pressed: bool) {
+        // Each stroke is smoothed on its own.
+        if pressed != self.mouse_pressed {
+            self.pressure_filter.reset();
+            self.simulated_stroke = None;
+        }
         self.mouse_pressed = pressed;
         if !pressed {
             self.pressure = None;
@@ -200,9 +768,140 @@ pub struct StylusEventFrameInner {
 #[derive(Clone)]
 pub struct StylusEventFrame(std::sync::Arc<StylusEventFrameInner>);
 
+impl StylusEventFrame {
+    /// A frame of events that didn't come from a device, such as those of a [replay](crate::replay).
+    #[must_use]
+    pub fn new(events: Vec<StylusEvent>) -> Self {
+        Self(std::sync::Arc::new(StylusEventFrameInner { events }))
+    }
+}
 impl std::ops::Deref for StylusEventFrame {
     type Target = [StylusEvent];
     fn deref(&'_ self) -> &'_ Self::Target {
         &self.0.events
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        MappingArea, MappingGeometry, MappingMode, MappingTarget, PressureCurve, PressureFilter,
+        SimulatedPressure, TabletMapper, TabletMapping,
+    };
+    fn assert_near(a: [f32; 2], b: [f32; 2]) {
+        assert!(
+            (a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3,
+            "{a:?} != {b:?}"
+        );
+    }
+    #[test]
+    fn fit_pressure() {
+        let curve = PressureCurve {
+            saturation: 0.8,
+            gamma: 2.0,
+        };
+        // Raw pressures that this curve takes exactly to the targets.
+        let typical =
+            PressureCurve::TARGETS.map(|target| target.powf(1.0 / curve.gamma) * curve.saturation);
+        let fit = PressureCurve::fit(typical).unwrap();
+        assert!((fit.saturation - curve.saturation).abs() < 1e-4);
+        assert!((fit.gamma - curve.gamma).abs() < 1e-4);
+
+        assert_eq!(PressureCurve::fit([0.5, 0.5, 0.9]), None);
+        assert_eq!(PressureCurve::fit([0.0, 0.5, 0.9]), None);
+        // Default leaves pressure be.
+        assert!((PressureCurve::default().apply(0.3) - 0.3).abs() < f32::EPSILON);
+    }
+    #[test]
+    fn filter_pressure() {
+        let start = std::time::Instant::now();
+        let constant = std::time::Duration::from_millis(20);
+        let mut filter = PressureFilter::new(constant);
+        // The first reading passes through.
+        assert!((filter.apply(0.5, start) - 0.5).abs() < f32::EPSILON);
+        // One time constant later, most of the way to a new reading.
+        let smoothed = filter.apply(1.0, start + constant);
+        let expected = 1.0 - 0.5 / std::f32::consts::E;
+        assert!((smoothed - expected).abs() < 1e-4);
+        // Starts afresh.
+        filter.reset();
+        assert!((filter.apply(0.1, start + constant * 2) - 0.1).abs() < f32::EPSILON);
+        // Disabled, readings pass through.
+        let mut filter = PressureFilter::default();
+        filter.apply(0.5, start);
+        assert!((filter.apply(1.0, start + constant) - 1.0).abs() < f32::EPSILON);
+    }
+    #[test]
+    fn simulate_pressure() {
+        let second = std::time::Duration::from_secs(1);
+        // Default is as pressing a mouse always was.
+        assert!((SimulatedPressure::default().apply(second, 1000.0) - 1.0).abs() < f32::EPSILON);
+        let simulated = SimulatedPressure {
+            pressure: 0.8,
+            fade_in_ms: 100,
+            fade_speed: 1000.0,
+        };
+        assert!(simulated.apply(std::time::Duration::ZERO, 0.0).abs() < f32::EPSILON);
+        assert!((simulated.apply(second / 20, 0.0) - 0.4).abs() < 1e-4);
+        assert!((simulated.apply(second, 0.0) - 0.8).abs() < 1e-4);
+        assert!((simulated.apply(second, 500.0) - 0.4).abs() < 1e-4);
+        assert!(simulated.apply(second, 2000.0).abs() < f32::EPSILON);
+    }
+    #[test]
+    fn mapping_area() {
+        // Rotated a quarter turn, as the canvas may be.
+        let area = MappingArea {
+            origin: [100.0, 0.0],
+            x: [0.0, 100.0],
+            y: [-50.0, 0.0],
+        };
+        assert_near(area.at([0.5, 0.5]), [75.0, 50.0]);
+        assert_near(area.local([75.0, 50.0]).unwrap(), [0.5, 0.5]);
+        assert_near(area.clamp([200.0, 50.0]), [100.0, 50.0]);
+        // Two wide by one tall, fit to a square in its middle.
+        let wide = MappingArea::rect([0.0, 0.0], [200.0, 100.0]).letterboxed(1.0);
+        assert_eq!(wide, MappingArea::rect([50.0, 0.0], [100.0, 100.0]));
+        assert!(MappingArea::rect([0.0, 0.0], [0.0, 10.0])
+            .local([0.0, 0.0])
+            .is_none());
+    }
+    #[test]
+    fn map_tablet() {
+        let geometry = MappingGeometry {
+            desktop: Some(MappingArea::rect([-100.0, -100.0], [400.0, 200.0])),
+            window: MappingArea::rect([0.0, 0.0], [200.0, 100.0]),
+            canvas: Some(MappingArea::rect([50.0, 0.0], [100.0, 100.0])),
+        };
+        let mut mapper = TabletMapper::default();
+        assert!(mapper.mapping().passes_through());
+        assert_near(mapper.map([10.0, 20.0], &geometry), [10.0, 20.0]);
+
+        // The middle of the desktop is the middle of the canvas, its far corner the canvas's.
+        mapper.set_mapping(TabletMapping {
+            target: MappingTarget::Canvas,
+            ..TabletMapping::default()
+        });
+        assert_near(mapper.map([100.0, 0.0], &geometry), [100.0, 50.0]);
+        assert_near(mapper.map([300.0, 100.0], &geometry), [150.0, 100.0]);
+        // Unknown desktop, left be.
+        let unknown = MappingGeometry {
+            desktop: None,
+            ..geometry
+        };
+        assert_near(mapper.map([300.0, 100.0], &unknown), [300.0, 100.0]);
+
+        // Moves by how far the pen did, halved, and no further than the window.
+        mapper.set_mapping(TabletMapping {
+            target: MappingTarget::Window,
+            mode: MappingMode::Relative,
+            speed: 0.5,
+            ..TabletMapping::default()
+        });
+        assert_near(mapper.map([10.0, 10.0], &geometry), [10.0, 10.0]);
+        assert_near(mapper.map([30.0, 10.0], &geometry), [20.0, 10.0]);
+        // Lifting the pen away and back down elsewhere leaves the pointer be.
+        mapper.leave();
+        assert_near(mapper.map([150.0, 90.0], &geometry), [20.0, 10.0]);
+        assert_near(mapper.map([150.0, 500.0], &geometry), [20.0, 100.0]);
+    }
+}
@@ -917,7 +917,7 @@ pub mod color {
         ) -> anyhow::Result<Arc<vk::PrimaryAutoCommandBuffer>> {
             // We accept either color mode. Would be more efficient if the monochrome pipe was used, tho.
             if matches!(output.color_mode, super::OutputColor::Solid(_)) {
-                log::debug!("using expensive color pipe for monochrome text!");
+                tracing::debug!("using expensive color pipe for monochrome text!");
             }
             // Even if none to draw, we can still clear and return.
             let instances_indirects = super::predraw_upload(&self.context, output)?;
@@ -0,0 +1,97 @@
+//! Modal for relinking the assets of a document whose linked files have gone missing.
+
+use super::ResponseExt;
+use fuzzpaint_core::{
+    brush::UniqueID,
+    io::asset::{AssetError, AssetLinks},
+};
+
+struct Missing {
+    id: UniqueID,
+    name: String,
+    /// Why the last attempt to relink failed, if any.
+    error: Option<String>,
+}
+
+pub struct RelinkModal {
+    document_name: String,
+    assets: std::sync::Arc<parking_lot::RwLock<AssetLinks>>,
+    missing: Vec<Missing>,
+}
+impl RelinkModal {
+    /// Create a modal for the document's missing assets, or None if nothing is missing.
+    #[must_use]
+    pub fn new(
+        document_name: String,
+        assets: std::sync::Arc<parking_lot::RwLock<AssetLinks>>,
+    ) -> Option<Self> {
+        let missing: Vec<_> = assets
+            .read()
+            .missing()
+            .map(|(id, link)| Missing {
+                id,
+                name: link.name.clone(),
+                error: None,
+            })
+            .collect();
+        (!missing.is_empty()).then_some(Self {
+            document_name,
+            assets,
+            missing,
+        })
+    }
+}
+impl super::Modal for RelinkModal {
+    type Cancel = ();
+    type Confirm = ();
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-relink-assets";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        ui.label(format!(
+            "Some files used by \"{}\" could not be found. Locate them to restore the textures and images that use them.",
+            self.document_name
+        ));
+        ui.separator();
+
+        let mut relinked = None;
+        for (idx, missing) in self.missing.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(&missing.name);
+                if ui.button("Locate...").clicked() {
+                    let Some(path) = rfd::FileDialog::new()
+                        .set_file_name(&missing.name)
+                        .pick_file()
+                    else {
+                        return;
+                    };
+                    match self.assets.write().relink(missing.id, &path) {
+                        Ok(()) => relinked = Some(idx),
+                        Err(AssetError::Mismatched) => {
+                            missing.error =
+                                Some("The file's contents differ from the original.".to_owned());
+                        }
+                        Err(err) => missing.error = Some(err.to_string()),
+                    }
+                }
+            });
+            if let Some(error) = &missing.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        }
+        if let Some(idx) = relinked {
+            self.missing.remove(idx);
+        }
+        ui.separator();
+
+        if self.missing.is_empty() {
+            return super::modal::Response::Confirm(());
+        }
+        if ui.button("Skip").clicked_or_escape() {
+            return super::modal::Response::Cancel(());
+        }
+        super::modal::Response::Continue
+    }
+}
@@ -1,6 +1,7 @@
 use fuzzpaint_core::brush::{Brush, Texture, UniqueID};
 
 use super::ResponseExt;
+use crate::global::config_file::ConfigFile;
 
 const FULL_UV: egui::Rect = egui::Rect {
     min: egui::Pos2::ZERO,
@@ -41,7 +42,7 @@ impl super::Modal for CreationModal {
     type Cancel = ();
     type Confirm = CreationOutput;
     type Error = ();
-    const NAME: &'static str = "Create Brush";
+    const NAME: &'static str = "modal-create-brush";
     fn do_ui(
         &mut self,
         ui: &mut egui::Ui,
@@ -96,7 +97,7 @@ impl super::Modal for CreationModal {
 
                         match try_load() {
                             Ok(image) => self.texture = Some(image),
-                            Err(err) => log::error!("Failed to load image: {err}"),
+                            Err(err) => tracing::error!("Failed to load image: {err}"),
                         }
                     }
                 }
@@ -533,3 +534,137 @@ pub struct Preloaded {
 
 /// Provides a brush selection drawer with many brushes loaded dynamically.
 pub struct Bin {}
+
+/// Save, apply, and share presets of the brush settings.
+pub fn presets(ui: &mut egui::Ui, brush: &mut fuzzpaint_core::state::StrokeBrushSettings) {
+    use crate::global::brush_presets::{self, BrushPreset, BrushPresets};
+    egui::CollapsingHeader::new("Presets").show(ui, |ui| {
+        // (selected index, name field)
+        let state_marker = ui.id().with("brush-presets");
+        let (mut selected, mut new_name) = ui
+            .memory(|w| w.data.get_temp::<(Option<usize>, String)>(state_marker))
+            .unwrap_or_default();
+
+        let mut presets = BrushPresets::write();
+        if let Some(error) = presets.load_blocker().map(ToString::to_string) {
+            ui.label(
+                egui::RichText::new(format!(
+                    "Failed to load brush presets, they will not be saved. {error}"
+                ))
+                .color(ui.style().visuals.error_fg_color),
+            );
+            if ui.small_button("Retry").clicked() {
+                *presets = BrushPresets::from_default_file();
+            }
+        }
+        // Selection may have been invalidated by a reload or delete.
+        selected = selected.filter(|&idx| idx < presets.presets.len());
+
+        egui::ComboBox::from_id_source(state_marker)
+            .selected_text(
+                selected
+                    .and_then(|idx| presets.presets.get(idx))
+                    .map_or("None", |preset| preset.name.as_str()),
+            )
+            .show_ui(ui, |ui| {
+                for (idx, preset) in presets.presets.iter().enumerate() {
+                    ui.selectable_value(&mut selected, Some(idx), &preset.name);
+                }
+            });
+
+        let mut delete = false;
+        if let Some(preset) = selected.and_then(|idx| presets.presets.get(idx)) {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Apply")
+                    .on_hover_text("Use this preset's brush, size, and spacing")
+                    .clicked()
+                {
+                    if let Err(e) = preset.apply(brush) {
+                        tracing::error!(preset = %preset.name, "failed to apply brush preset: {e}");
+                    }
+                }
+                delete = ui.button("Delete").clicked();
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut new_name)
+                    .hint_text("New preset name")
+                    .desired_width(100.0),
+            );
+            let can_create = !new_name.trim().is_empty();
+            if ui
+                .add_enabled(can_create, egui::Button::new(super::PLUS_ICON.to_string()))
+                .on_hover_text("Save the current brush settings under this name")
+                .clicked()
+            {
+                let name = std::mem::take(&mut new_name).trim().to_owned();
+                presets.merge([BrushPreset::from_settings(name.clone(), brush)]);
+                selected = presets
+                    .presets
+                    .iter()
+                    .position(|preset| preset.name == name);
+                save_presets(&presets);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Import...")
+                .on_hover_text("Add the presets from a pack, replacing any of the same name")
+                .clicked()
+            {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Brush presets", &[BrushPresets::PACK_EXTENSION])
+                    .pick_file()
+                {
+                    match brush_presets::import_pack(&path) {
+                        Ok(pack) => {
+                            presets.merge(pack);
+                            save_presets(&presets);
+                        }
+                        Err(e) => tracing::error!("failed to import brush presets: {e:#}"),
+                    }
+                }
+            }
+            if ui
+                .add_enabled(!presets.presets.is_empty(), egui::Button::new("Export..."))
+                .on_hover_text("Save all presets into a pack to share")
+                .clicked()
+            {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Brush presets", &[BrushPresets::PACK_EXTENSION])
+                    .set_file_name(format!("brushes.{}", BrushPresets::PACK_EXTENSION))
+                    .save_file()
+                {
+                    if let Err(e) = brush_presets::export_pack(&path, &presets.presets) {
+                        tracing::error!("failed to export brush presets: {e:#}");
+                    }
+                }
+            }
+        });
+
+        if delete {
+            if let Some(idx) = selected.take() {
+                presets.presets.remove(idx);
+                save_presets(&presets);
+            }
+        }
+
+        ui.memory_mut(|w| {
+            w.data
+                .insert_temp::<(Option<usize>, String)>(state_marker, (selected, new_name));
+        });
+    });
+}
+/// Save the presets, unless the file failed to load (we'd clobber it!)
+fn save_presets(presets: &crate::global::brush_presets::BrushPresets) {
+    if presets.load_blocker().is_some() {
+        return;
+    }
+    if let Err(e) = presets.save() {
+        tracing::error!("failed to save brush presets: {e:#}");
+    }
+}
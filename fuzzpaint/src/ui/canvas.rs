@@ -0,0 +1,80 @@
+//! Changing the size of the canvas, see [`crate::canvas`].
+
+use super::ResponseExt;
+use crate::canvas::Anchor;
+use crate::i18n::tr;
+use fuzzpaint_core::state::document::Canvas;
+
+/// Choose the new canvas by its size in document pixels, and where the current canvas sits within it.
+pub struct CanvasSizeModal {
+    current: Canvas,
+    size: [f32; 2],
+    anchor: Anchor,
+}
+impl CanvasSizeModal {
+    /// Start from the document's current canvas.
+    #[must_use]
+    pub fn new(current: Canvas) -> Self {
+        Self {
+            current,
+            size: current.size,
+            anchor: Anchor::Center,
+        }
+    }
+}
+impl super::Modal for CanvasSizeModal {
+    type Cancel = ();
+    type Confirm = Canvas;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-canvas-size";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        // Past this, the preview's image is spread too thin over the canvas to see much.
+        #[allow(clippy::cast_precision_loss)]
+        let max = 16.0 * crate::DOCUMENT_DIMENSION as f32;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.size[0])
+                    .clamp_range(1.0..=max)
+                    .prefix(format!("{} ", tr!("canvas-size-width")))
+                    .suffix(" px")
+                    .max_decimals(0),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.size[1])
+                    .clamp_range(1.0..=max)
+                    .prefix(format!("{} ", tr!("canvas-size-height")))
+                    .suffix(" px")
+                    .max_decimals(0),
+            );
+        });
+        ui.label(tr!("canvas-size-anchor"));
+        egui::Grid::new("canvas-anchor").show(ui, |ui| {
+            for (idx, anchor) in <Anchor as strum::IntoEnumIterator>::iter().enumerate() {
+                ui.selectable_value(&mut self.anchor, anchor, anchor.arrow());
+                if idx % 3 == 2 {
+                    ui.end_row();
+                }
+            }
+        });
+        ui.label(tr!("canvas-size-note"));
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button(tr!("canvas-size-apply")).clicked_or_enter() {
+                return super::modal::Response::Confirm(crate::canvas::resized(
+                    self.current,
+                    self.size,
+                    self.anchor,
+                ));
+            }
+            if ui.button(tr!("canvas-size-cancel")).clicked_or_escape() {
+                return super::modal::Response::Cancel(());
+            }
+            super::modal::Response::Continue
+        })
+        .inner
+    }
+}
@@ -1,3 +1,4 @@
+use crate::global::config_file::ConfigFile;
 use egui::Color32;
 use either::Either;
 use fuzzpaint_core::{
@@ -6,6 +7,7 @@ use fuzzpaint_core::{
 };
 
 const GROW_FACTOR: f32 = 1.25;
+const SWAP_ICON: char = '⇄';
 
 /// If the contrast between two colors is too low, choose a stroke color to contrast both.
 fn contrasting_stroke(
@@ -184,6 +186,73 @@ impl egui::Widget for ColorSquare {
     }
 }
 
+/// Squares showing the primary and secondary colors. Clicking the secondary or the swap icon swaps them.
+pub fn primary_secondary(
+    ui: &mut egui::Ui,
+    primary: &mut ColorOrPalette,
+    secondary: &mut ColorOrPalette,
+    palette: &fuzzpaint_core::state::palette::Palette,
+) -> egui::Response {
+    let square = |color: ColorOrPalette| ColorSquare {
+        color: color
+            .get()
+            .left_or_else(|idx| palette.get(idx).unwrap_or(FColor::TRANSPARENT)),
+        icon: color.is_palette().then_some(super::PALETTE_ICON),
+        selected: false,
+    };
+    ui.horizontal(|ui| {
+        let primary_response = ui.add(square(*primary)).on_hover_text("Primary color");
+        let secondary_response = ui.add(square(*secondary)).on_hover_text("Secondary color");
+        let swap_response = ui
+            .add(IconSquare { icon: SWAP_ICON })
+            .on_hover_text("Swap primary and secondary colors");
+
+        let mut response = primary_response | secondary_response.clone() | swap_response.clone();
+        if secondary_response.clicked() || swap_response.clicked() {
+            std::mem::swap(primary, secondary);
+            response.mark_changed();
+        }
+        response
+    })
+    .inner
+}
+
+/// A row of the colors strokes were recently drawn with, see [`crate::global::recent_colors`]. Clicking one
+/// makes it the primary color.
+pub fn recent_strokes(
+    ui: &mut egui::Ui,
+    primary: &mut ColorOrPalette,
+    recent: &[ColorOrPalette],
+    palette: &fuzzpaint_core::state::palette::Palette,
+) -> egui::Response {
+    let mut changed = false;
+    let mut response = ui
+        .horizontal_wrapped(|ui| {
+            ui.add(IconSquare {
+                icon: super::HISTORY_ICON,
+            })
+            .on_hover_text("Colors of recent strokes");
+            for &color in recent {
+                let square = ColorSquare {
+                    color: color
+                        .get()
+                        .left_or_else(|idx| palette.get(idx).unwrap_or(FColor::TRANSPARENT)),
+                    icon: color.is_palette().then_some(super::PALETTE_ICON),
+                    selected: color == *primary,
+                };
+                if ui.add(square).clicked() {
+                    *primary = color;
+                    changed = true;
+                }
+            }
+        })
+        .response;
+    if changed {
+        response.mark_changed();
+    }
+    response
+}
+
 /// An icon of identical layout to [`ColorSquare`] that provides a simple icon.
 pub struct IconSquare {
     icon: char,
@@ -292,8 +361,6 @@ pub struct ColorPalette<'w, 'a: 'w, Writer> {
     max_history: Option<usize>,
     history_scope: HistoryScope,
     in_flux: bool,
-    /// Swap top two colors in the history.
-    do_swap: bool,
     id: Option<egui::Id>,
 }
 impl<'w, 'a: 'w, Writer> ColorPalette<'w, 'a, Writer> {
@@ -308,7 +375,6 @@ impl<'w, 'a: 'w, Writer> ColorPalette<'w, 'a, Writer> {
             max_history: None,
             history_scope: HistoryScope::Local,
             in_flux: false,
-            do_swap: false,
             id: None,
         }
     }
@@ -340,14 +406,6 @@ impl<'w, 'a: 'w, Writer> ColorPalette<'w, 'a, Writer> {
             ..self
         }
     }
-    /// Swap the first and second most recent colors.
-    #[must_use]
-    pub fn swap(self, swap: bool) -> Self {
-        Self {
-            do_swap: swap,
-            ..self
-        }
-    }
 }
 pub struct ColorPaletteResponse {
     pub dereferenced_color: FColor,
@@ -560,20 +618,6 @@ impl<
                                 }
                             });
                         }
-                        // Perform swap
-                        if self.do_swap {
-                            ui.data_mut(|mem| {
-                                let palette =
-                                    mem.get_temp_mut_or_default::<ColorPaletteState>(palette_id);
-                                let Some(&swap_with) = palette.history.get(1) else {
-                                    // History too smol to swap :3
-                                    return;
-                                };
-                                changed = true;
-                                palette.hoist(swap_with);
-                                *self.color = swap_with;
-                        });
-                        }
                     });
             })
             .response
@@ -956,3 +1000,205 @@ pub fn picker_dock(ctx: &egui::Context, hsva: &mut egui::ecolor::HsvaGamma) -> P
         })
         .inner
 }
+
+/// Numeric entry for a color, either as unmultiplied sRGB bytes or as HSV, each with alpha.
+pub fn numeric_entry(ui: &mut egui::Ui, hsva: &mut egui::ecolor::HsvaGamma) -> PickerResponse {
+    #[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+    enum Mode {
+        #[default]
+        Rgba,
+        Hsva,
+    }
+    let mode_marker = ui.id().with("numeric-mode");
+    let mut mode = ui
+        .memory(|w| w.data.get_temp::<Mode>(mode_marker))
+        .unwrap_or_default();
+
+    let mut in_flux = false;
+    let mut changed = false;
+    let mut response = ui
+        .vertical(|ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut mode, Mode::Rgba, "RGBA");
+                ui.selectable_value(&mut mode, Mode::Hsva, "HSVA");
+            });
+            ui.horizontal(|ui| {
+                // Dragging or typing into a field is an ongoing change, don't spam the history.
+                let mut track = |response: egui::Response| {
+                    in_flux |= response.dragged() || response.has_focus();
+                    changed |= response.changed();
+                };
+                match mode {
+                    Mode::Rgba => {
+                        let mut srgba = egui::ecolor::Hsva::from(*hsva).to_srgba_unmultiplied();
+                        for (channel, name) in srgba.iter_mut().zip(["R ", "G ", "B ", "A "]) {
+                            track(
+                                ui.add(
+                                    egui::DragValue::new(channel)
+                                        .prefix(name)
+                                        .clamp_range(0..=255),
+                                ),
+                            );
+                        }
+                        if changed {
+                            let new: egui::ecolor::HsvaGamma =
+                                egui::ecolor::Hsva::from_srgba_unmultiplied(srgba).into();
+                            // Grays have no hue, keep the old one instead of snapping to red.
+                            let h = if new.s == 0.0 { hsva.h } else { new.h };
+                            *hsva = egui::ecolor::HsvaGamma { h, ..new };
+                        }
+                    }
+                    Mode::Hsva => {
+                        let mut degrees = hsva.h * 360.0;
+                        track(
+                            ui.add(
+                                egui::DragValue::new(&mut degrees)
+                                    .prefix("H ")
+                                    .suffix("°")
+                                    .max_decimals(1)
+                                    .clamp_range(0.0..=360.0),
+                            ),
+                        );
+                        hsva.h = degrees / 360.0;
+                        for (channel, name) in [&mut hsva.s, &mut hsva.v, &mut hsva.a]
+                            .into_iter()
+                            .zip(["S ", "V ", "A "])
+                        {
+                            let mut percent = *channel * 100.0;
+                            track(
+                                ui.add(
+                                    egui::DragValue::new(&mut percent)
+                                        .prefix(name)
+                                        .suffix("%")
+                                        .max_decimals(1)
+                                        .clamp_range(0.0..=100.0),
+                                ),
+                            );
+                            *channel = percent / 100.0;
+                        }
+                    }
+                }
+            });
+        })
+        .response;
+
+    ui.memory_mut(|w| w.data.insert_temp::<Mode>(mode_marker, mode));
+
+    if changed {
+        response.mark_changed();
+    }
+    PickerResponse { in_flux, response }
+}
+
+/// Collapsible list of the user's named palettes, which can be added into the document's palette or
+/// created from it.
+pub fn named_palettes<Writer>(
+    ui: &mut egui::Ui,
+    palette: &mut fuzzpaint_core::state::palette::writer::Writer<'_, Writer>,
+) where
+    Writer: fuzzpaint_core::queue::writer::CommandWrite<
+        fuzzpaint_core::state::palette::commands::Command,
+    >,
+{
+    use crate::global::palettes::{NamedPalette, Palettes};
+    egui::CollapsingHeader::new("Named palettes").show(ui, |ui| {
+        // (selected index, name field)
+        let state_marker = ui.id().with("named-palettes");
+        let (mut selected, mut new_name) = ui
+            .memory(|w| w.data.get_temp::<(Option<usize>, String)>(state_marker))
+            .unwrap_or_default();
+
+        let mut palettes = Palettes::write();
+        if let Some(error) = palettes.load_blocker().map(ToString::to_string) {
+            ui.label(
+                egui::RichText::new(format!(
+                    "Failed to load palettes, they will not be saved. {error}"
+                ))
+                .color(ui.style().visuals.error_fg_color),
+            );
+            if ui.small_button("Retry").clicked() {
+                *palettes = Palettes::from_default_file();
+            }
+        }
+        // Selection may have been invalidated by a reload or delete.
+        selected = selected.filter(|&idx| idx < palettes.palettes.len());
+
+        egui::ComboBox::from_id_source(state_marker)
+            .selected_text(
+                selected
+                    .and_then(|idx| palettes.palettes.get(idx))
+                    .map_or("None", |named| named.name.as_str()),
+            )
+            .show_ui(ui, |ui| {
+                for (idx, named) in palettes.palettes.iter().enumerate() {
+                    ui.selectable_value(&mut selected, Some(idx), &named.name);
+                }
+            });
+
+        let mut delete = false;
+        if let Some(named) = selected.and_then(|idx| palettes.palettes.get(idx)) {
+            ui.horizontal_wrapped(|ui| {
+                for color in named.iter_colors() {
+                    ui.add(ColorSquare {
+                        color,
+                        ..Default::default()
+                    });
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Add to document")
+                    .on_hover_text("Add these colors to the document's palette")
+                    .clicked()
+                {
+                    for color in named.iter_colors() {
+                        let _ = palette.insert(color);
+                    }
+                }
+                delete = ui.button("Delete").clicked();
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut new_name)
+                    .hint_text("New palette name")
+                    .desired_width(100.0),
+            );
+            let can_create = !new_name.trim().is_empty();
+            if ui
+                .add_enabled(can_create, egui::Button::new(super::PLUS_ICON.to_string()))
+                .on_hover_text("Save the document's palette under this name")
+                .clicked()
+            {
+                palettes.palettes.push(NamedPalette {
+                    name: std::mem::take(&mut new_name).trim().to_owned(),
+                    colors: palette.iter().map(|(_, color)| color.as_array()).collect(),
+                });
+                selected = Some(palettes.palettes.len() - 1);
+                save_palettes(&palettes);
+            }
+        });
+
+        if delete {
+            if let Some(idx) = selected.take() {
+                palettes.palettes.remove(idx);
+                save_palettes(&palettes);
+            }
+        }
+
+        ui.memory_mut(|w| {
+            w.data
+                .insert_temp::<(Option<usize>, String)>(state_marker, (selected, new_name));
+        });
+    });
+}
+/// Save the palettes, unless the file failed to load (we'd clobber it!)
+fn save_palettes(palettes: &crate::global::palettes::Palettes) {
+    if palettes.load_blocker().is_some() {
+        return;
+    }
+    if let Err(e) = palettes.save() {
+        tracing::error!("failed to save palettes: {e:#}");
+    }
+}
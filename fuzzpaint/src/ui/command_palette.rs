@@ -0,0 +1,163 @@
+//! Modal listing every [`Action`] with its hotkey, searchable by name, to run any of them without
+//! remembering where it lives.
+
+use crate::actions::Action;
+
+/// Score how well `query` matches `text`, or `None` if it doesn't. Every character of the query must
+/// appear in order, ignoring case. Runs of consecutive characters and characters starting a word score
+/// higher.
+fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let mut query = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .peekable();
+    let mut score = 0;
+    // Score of the current run of matches.
+    let mut run = 0;
+    let mut previous = None::<char>;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let Some(&next) = query.peek() else {
+            break;
+        };
+        if c == next {
+            query.next();
+            run += 1;
+            score += run;
+            if previous.map_or(true, |previous| !previous.is_alphanumeric()) {
+                score += 2;
+            }
+        } else {
+            run = 0;
+        }
+        previous = Some(c);
+    }
+    query.peek().is_none().then_some(score)
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    query: String,
+    /// Index into the filtered list.
+    selected: usize,
+    /// Whether the search box has been focused, done once on open.
+    focused: bool,
+}
+impl CommandPalette {
+    /// Actions matching the query, best first. Unfiltered and in declaration order when empty.
+    fn matches(&self) -> Vec<Action> {
+        let mut scored: Vec<_> = <Action as strum::IntoEnumIterator>::iter()
+            .filter_map(|action| {
+                let category = action.category();
+                // Either may match, preferring the name.
+                let score = fuzzy_score(&self.query, &action.name())
+                    .map(|score| score * 2)
+                    .or_else(|| fuzzy_score(&self.query, &category.name()))?;
+                Some((score, action))
+            })
+            .collect();
+        // Stable, so ties stay in declaration order.
+        scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+        scored.into_iter().map(|(_, action)| action).collect()
+    }
+}
+impl super::Modal for CommandPalette {
+    type Cancel = ();
+    type Confirm = Action;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-command-palette";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        let search = ui.add(
+            egui::TextEdit::singleline(&mut self.query)
+                .hint_text("Search commands")
+                .desired_width(f32::INFINITY),
+        );
+        if !std::mem::replace(&mut self.focused, true) {
+            search.request_focus();
+        }
+        if search.changed() {
+            self.selected = 0;
+        }
+        let (up, down, enter, escape) = ui.input(|input| {
+            (
+                input.key_pressed(egui::Key::ArrowUp),
+                input.key_pressed(egui::Key::ArrowDown),
+                input.key_pressed(egui::Key::Enter),
+                input.key_pressed(egui::Key::Escape),
+            )
+        });
+        if escape {
+            return super::modal::Response::Cancel(());
+        }
+
+        let matches = self.matches();
+        if down {
+            self.selected = (self.selected + 1).min(matches.len().saturating_sub(1));
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        let mut chosen = None;
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("command-palette")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (idx, &action) in matches.iter().enumerate() {
+                            let key = super::hotkey_text(action);
+                            // Held actions do nothing when run by a single press.
+                            let response = ui
+                                .add_enabled(
+                                    !action.is_hold(),
+                                    egui::SelectableLabel::new(idx == self.selected, action.name()),
+                                )
+                                .on_disabled_hover_text("Only works while its hotkey is held");
+                            if idx == self.selected {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                chosen = Some(action);
+                            }
+                            ui.label(egui::RichText::new(action.category().name()).weak());
+                            ui.label(egui::RichText::new(key.unwrap_or_default()).monospace());
+                            ui.end_row();
+                        }
+                    });
+            });
+        if matches.is_empty() {
+            ui.label(egui::RichText::new("No matching commands").italics().weak());
+        }
+        if enter {
+            chosen = matches
+                .get(self.selected)
+                .copied()
+                .filter(|action| !action.is_hold());
+        }
+
+        match chosen {
+            Some(action) => super::modal::Response::Confirm(action),
+            None => super::modal::Response::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fuzzy_score;
+    #[test]
+    fn fuzzy() {
+        assert!(fuzzy_score("", "Undo").is_some());
+        assert!(fuzzy_score("ZMIN", "Zoom in").is_some());
+        assert!(fuzzy_score("nz", "Zoom in").is_none());
+        // Word starts and runs beat scattered letters.
+        assert!(fuzzy_score("rv", "Rotate view") > fuzzy_score("rv", "Flip view horizontally"));
+        assert!(fuzzy_score("lay", "Layer up") > fuzzy_score("lay", "Toggle always on top"));
+    }
+}
@@ -0,0 +1,65 @@
+//! Panel listing recent log events from [`crate::logging`].
+
+use egui::{RichText, Ui};
+
+const LEVELS: [tracing::Level; 5] = [
+    tracing::Level::ERROR,
+    tracing::Level::WARN,
+    tracing::Level::INFO,
+    tracing::Level::DEBUG,
+    tracing::Level::TRACE,
+];
+
+fn level_color(ui: &Ui, level: tracing::Level) -> egui::Color32 {
+    let visuals = ui.visuals();
+    match level {
+        tracing::Level::ERROR => visuals.error_fg_color,
+        tracing::Level::WARN => visuals.warn_fg_color,
+        tracing::Level::INFO => visuals.text_color(),
+        _ => visuals.weak_text_color(),
+    }
+}
+
+pub fn console_panel(ui: &mut Ui) {
+    // Least severe level to show.
+    let filter_id = egui::Id::new("console-level");
+    let mut filter = ui
+        .data(|data| data.get_temp::<tracing::Level>(filter_id))
+        .unwrap_or(tracing::Level::INFO);
+
+    ui.horizontal(|ui| {
+        ui.label("Console");
+        egui::ComboBox::from_id_source(filter_id)
+            .selected_text(filter.as_str())
+            .show_ui(ui, |ui| {
+                for level in LEVELS {
+                    ui.selectable_value(&mut filter, level, level.as_str());
+                }
+            });
+        if ui
+            .button("Copy diagnostics report")
+            .on_hover_text("Copy application, device, and recent warning info for a bug report")
+            .clicked()
+        {
+            let report = crate::diagnostics::report();
+            ui.output_mut(|output| output.copied_text = report);
+        }
+    });
+    ui.data_mut(|data| data.insert_temp(filter_id, filter));
+    ui.separator();
+
+    let entries = crate::logging::entries(filter);
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    egui::ScrollArea::both()
+        .auto_shrink([false, false])
+        .stick_to_bottom(true)
+        .show_rows(ui, row_height, entries.len(), |ui, rows| {
+            for entry in &entries[rows] {
+                let color = level_color(ui, entry.level);
+                ui.add(
+                    egui::Label::new(RichText::new(entry.to_string()).monospace().color(color))
+                        .wrap(false),
+                );
+            }
+        });
+}
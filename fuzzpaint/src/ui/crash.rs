@@ -0,0 +1,83 @@
+//! Modal offering the reports left by crashes, see [`crate::crash`].
+
+use super::ResponseExt;
+use crate::crash::Bundle;
+
+struct Crash {
+    bundle: Bundle,
+    /// Why the last attempt to delete it failed, if any.
+    error: Option<String>,
+}
+
+pub struct CrashModal {
+    crashes: Vec<Crash>,
+}
+impl CrashModal {
+    /// Create a modal for the crashes the user hasn't been told of, or None if there are none.
+    #[must_use]
+    pub fn new() -> Option<Self> {
+        let crashes: Vec<_> = crate::crash::unseen()
+            .into_iter()
+            .map(|bundle| Crash {
+                bundle,
+                error: None,
+            })
+            .collect();
+        (!crashes.is_empty()).then_some(Self { crashes })
+    }
+}
+impl super::Modal for CrashModal {
+    type Cancel = ();
+    type Confirm = std::convert::Infallible;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-crash";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        ui.label("Fuzzpaint crashed the last time it ran. Recovery copies of the open documents were saved, and a report was written.");
+        ui.label("If you'd like to help fix it, copy the report into a bug report. It is never sent anywhere by itself.");
+        ui.separator();
+
+        let mut deleted = None;
+        for (idx, crash) in self.crashes.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                // The first line is the panic message.
+                let summary = crash.bundle.report.lines().next().unwrap_or_default();
+                ui.label(summary)
+                    .on_hover_text(crash.bundle.path.display().to_string());
+                if ui.button("Copy report").clicked() {
+                    let report = crash.bundle.report.clone();
+                    ui.output_mut(|output| output.copied_text = report);
+                }
+                if ui.button("Delete").clicked() {
+                    match crash.bundle.discard() {
+                        Ok(()) => deleted = Some(idx),
+                        Err(e) => crash.error = Some(e.to_string()),
+                    }
+                }
+            });
+            if let Some(error) = &crash.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        }
+        if let Some(idx) = deleted {
+            self.crashes.remove(idx);
+        }
+        ui.separator();
+
+        if self.crashes.is_empty() {
+            return super::modal::Response::Cancel(());
+        }
+        // Kept on disk, but not offered again.
+        if ui.button("Close").clicked_or_escape() {
+            for crash in &self.crashes {
+                if let Err(err) = crash.bundle.mark_seen() {
+                    tracing::warn!("failed to mark crash report seen: {err}");
+                }
+            }
+            return super::modal::Response::Cancel(());
+        }
+        super::modal::Response::Continue
+    }
+}
@@ -0,0 +1,149 @@
+//! Overlay window showing the measurements collected by [`crate::diagnostics`].
+
+use crate::diagnostics::{self, CpuPhase, GpuPass};
+use egui::Ui;
+
+/// Frame time at the top of the history graph, unless a frame took longer.
+const GRAPH_MIN_SCALE: std::time::Duration = std::time::Duration::from_micros(16_667);
+const GRAPH_HEIGHT: f32 = 40.0;
+
+fn millis(duration: std::time::Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Show the overlay, if diagnostics are enabled. Closing the window disables them.
+pub fn overlay(ctx: &egui::Context) {
+    let mut open = diagnostics::enabled();
+    if !open {
+        return;
+    }
+    egui::Window::new("Diagnostics")
+        .open(&mut open)
+        .resizable(false)
+        .default_pos(ctx.screen_rect().right_top() + egui::vec2(-250.0, 40.0))
+        .show(ctx, |ui| {
+            let timings = diagnostics::timings().read();
+            cpu_section(ui, &timings);
+            ui.separator();
+            gpu_section(ui, &timings);
+            ui.separator();
+            memory_section(ui, &timings);
+        });
+    if !open {
+        diagnostics::set_enabled(false);
+    }
+}
+
+fn cpu_section(ui: &mut Ui, timings: &diagnostics::Timings) {
+    ui.strong("CPU");
+    let frames = timings.cpu_frames();
+    let count = frames.len();
+    let latest = timings.cpu_frames().last().copied().unwrap_or_default();
+    let mut sum = diagnostics::CpuFrame::default();
+    for frame in frames {
+        for phase in <CpuPhase as strum::IntoEnumIterator>::iter() {
+            sum.add(phase, frame.get(phase));
+        }
+    }
+    // Clamp to avoid div by zero before the first frame.
+    let count_u32 = u32::try_from(count.max(1)).unwrap_or(u32::MAX);
+
+    egui::Grid::new("diagnostics-cpu")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("");
+            ui.label("Latest");
+            ui.label(format!("Average of {count}"));
+            ui.end_row();
+            for phase in <CpuPhase as strum::IntoEnumIterator>::iter() {
+                ui.label(phase.as_ref());
+                ui.label(millis(latest.get(phase)));
+                ui.label(millis(sum.get(phase) / count_u32));
+                ui.end_row();
+            }
+            ui.strong("Total");
+            ui.strong(millis(latest.total()));
+            ui.strong(millis(sum.total() / count_u32));
+            ui.end_row();
+        });
+
+    frame_graph(ui, timings);
+}
+
+/// Bar graph of recent frame totals, newest on the right.
+fn frame_graph(ui: &mut Ui, timings: &diagnostics::Timings) {
+    let width = ui.available_width();
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, GRAPH_HEIGHT), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+    let scale = timings
+        .cpu_frames()
+        .map(diagnostics::CpuFrame::total)
+        .max()
+        .unwrap_or_default()
+        .max(GRAPH_MIN_SCALE)
+        .as_secs_f32();
+    #[allow(clippy::cast_precision_loss)]
+    let bar_width = rect.width() / diagnostics::HISTORY_LEN as f32;
+    let color = visuals.widgets.active.bg_fill;
+    // Right-align, so the graph scrolls in from the right as it fills.
+    let skip = diagnostics::HISTORY_LEN.saturating_sub(timings.cpu_frames().len());
+    for (idx, frame) in timings.cpu_frames().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let left = rect.left() + (idx + skip) as f32 * bar_width;
+        let height = frame.total().as_secs_f32() / scale * rect.height();
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(left, rect.bottom() - height),
+                egui::pos2(left + bar_width, rect.bottom()),
+            ),
+            0.0,
+            color,
+        );
+    }
+    // Mark the target frame time, if it's in view.
+    let target = GRAPH_MIN_SCALE.as_secs_f32() / scale;
+    let y = rect.bottom() - target * rect.height();
+    painter.hline(rect.x_range(), y, visuals.widgets.noninteractive.fg_stroke);
+}
+
+fn gpu_section(ui: &mut Ui, timings: &diagnostics::Timings) {
+    ui.strong("GPU");
+    egui::Grid::new("diagnostics-gpu")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for pass in <GpuPass as strum::IntoEnumIterator>::iter() {
+                ui.label(pass.as_ref());
+                ui.label(timings.gpu(pass).map_or_else(|| "-".to_owned(), millis));
+                ui.end_row();
+            }
+        });
+}
+
+fn memory_section(ui: &mut Ui, timings: &diagnostics::Timings) {
+    ui.strong("Memory");
+    let point_resident_usage = crate::global::points().resident_usage();
+    ui.label(format!(
+        "Point repository: {}/{}",
+        human_bytes::human_bytes(point_resident_usage.0 as f64),
+        human_bytes::human_bytes(point_resident_usage.1 as f64),
+    ));
+    for (idx, heap) in timings.device_memory().iter().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let size = human_bytes::human_bytes(heap.size as f64);
+        #[allow(clippy::cast_precision_loss)]
+        let text = match (heap.usage, heap.budget) {
+            (Some(usage), Some(budget)) => format!(
+                "VRAM heap {idx}: {}/{} ({size} total)",
+                human_bytes::human_bytes(usage as f64),
+                human_bytes::human_bytes(budget as f64),
+            ),
+            _ => format!("VRAM heap {idx}: {size} (usage unavailable)"),
+        };
+        ui.label(text);
+    }
+}
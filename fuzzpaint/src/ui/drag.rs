@@ -4,7 +4,14 @@ impl egui::Widget for Handle {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
         let interact_height = ui.style().spacing.interact_size.y;
         let size = egui::vec2(interact_height * 2.0 / 3.0, interact_height);
-        let response = ui.allocate_response(size, egui::Sense::drag());
+        // Mouse only, so it's left out of keyboard focus order.
+        let response = ui.allocate_response(
+            size,
+            egui::Sense {
+                focusable: false,
+                ..egui::Sense::drag()
+            },
+        );
 
         // Paint six dots, a somewhat universal drag icon.
         let painter = ui.painter();
@@ -0,0 +1,300 @@
+//! Modals for choosing how and where to export the document as an image, its layers as images of their own, or
+//! its history as a timelapse.
+
+use super::ResponseExt;
+use crate::export::{
+    ExportFormat, ExportSettings, LayerExportSettings, Supersample, TimelapseOutput,
+    TimelapseSettings,
+};
+use crate::i18n::tr;
+use fuzzpaint_core::units::{Length, Resolution, Unit};
+
+pub struct ExportModal {
+    /// Used to suggest a file name.
+    document_name: String,
+    /// The document's resolution, which the scale multiplies.
+    dpi: f32,
+    /// Pixels wide and tall of the document's canvas, which the scale multiplies too.
+    canvas: [f32; 2],
+    format: ExportFormat,
+    scale: f32,
+    supersample: Supersample,
+    auto_increment: bool,
+}
+impl ExportModal {
+    /// Start from the document's previous export settings, if any.
+    #[must_use]
+    pub fn new(
+        document_name: String,
+        dpi: f32,
+        canvas: [f32; 2],
+        last: Option<&ExportSettings>,
+    ) -> Self {
+        Self {
+            document_name,
+            dpi,
+            canvas,
+            format: last.map_or_else(ExportFormat::default, |last| last.format),
+            scale: last.map_or(1.0, |last| last.scale),
+            supersample: last.map_or_else(Supersample::default, |last| last.supersample),
+            auto_increment: last.map_or(false, |last| last.auto_increment),
+        }
+    }
+    /// Ask the user where to write, forcing the format's extension.
+    fn pick_path(&self) -> Option<std::path::PathBuf> {
+        let extension = self.format.extension();
+        let mut path = rfd::FileDialog::new()
+            .add_filter(self.format.as_ref(), &[extension])
+            .set_file_name(format!("{}.{extension}", self.document_name))
+            .save_file()?;
+        if path.extension().and_then(std::ffi::OsStr::to_str) != Some(extension) {
+            path.set_extension(extension);
+        }
+        Some(path)
+    }
+}
+impl super::Modal for ExportModal {
+    type Cancel = ();
+    type Confirm = ExportSettings;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-export";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        egui::ComboBox::from_label("Format")
+            .selected_text(self.format.as_ref())
+            .show_ui(ui, |ui| {
+                for format in <ExportFormat as strum::IntoEnumIterator>::iter() {
+                    ui.selectable_value(&mut self.format, format, format.as_ref());
+                }
+            });
+        ui.add(
+            egui::Slider::new(&mut self.scale, ExportSettings::SCALE_RANGE)
+                .text("Scale")
+                .suffix("×")
+                .max_decimals(2),
+        );
+        // Scaling keeps the physical size, so the resolution scales with it.
+        let mut dpi = self.dpi * self.scale;
+        if ui
+            .add(
+                egui::DragValue::new(&mut dpi)
+                    .prefix("Resolution: ")
+                    .suffix(" DPI")
+                    .max_decimals(1),
+            )
+            .on_hover_text("Scales the image to reach this resolution at the document's size")
+            .changed()
+        {
+            self.scale = (dpi / self.dpi).clamp(
+                *ExportSettings::SCALE_RANGE.start(),
+                *ExportSettings::SCALE_RANGE.end(),
+            );
+        }
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let [width_px, height_px] = self
+            .canvas
+            .map(|side| ((side * self.scale).round() as u32).max(1));
+        let unit = crate::global::preferences::Preferences::read().units;
+        let pixels = format!("{width_px} × {height_px} px");
+        let size = if unit == Unit::Pixel {
+            pixels
+        } else {
+            let [width, height] = self
+                .canvas
+                .map(|side| unit.of(Length::Logical(side), Resolution::Dpi(self.dpi)));
+            let decimals = unit.decimals();
+            let suffix = unit.suffix();
+            format!("{pixels}, {width:.decimals$} × {height:.decimals$} {suffix}")
+        };
+        ui.label(size);
+        egui::ComboBox::from_label("Supersample")
+            .selected_text(self.supersample.as_ref())
+            .show_ui(ui, |ui| {
+                for supersample in <Supersample as strum::IntoEnumIterator>::iter() {
+                    ui.selectable_value(&mut self.supersample, supersample, supersample.as_ref());
+                }
+            })
+            .response
+            .on_hover_text("Redraws the document at a higher resolution before scaling, for crisper edges when scaling up. Slower to export");
+        ui.checkbox(&mut self.auto_increment, "Auto-increment file name")
+            .on_hover_text("Each \"Export again\" writes the next of name_v001, name_v002, ...");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("Export...").clicked_or_enter() {
+                if let Some(path) = self.pick_path() {
+                    return super::modal::Response::Confirm(ExportSettings {
+                        path,
+                        format: self.format,
+                        scale: self.scale,
+                        supersample: self.supersample,
+                        auto_increment: self.auto_increment,
+                    });
+                }
+            }
+            if ui.button("Cancel").clicked_or_escape() {
+                return super::modal::Response::Cancel(());
+            }
+            super::modal::Response::Continue
+        })
+        .inner
+    }
+}
+
+pub struct LayerExportModal {
+    /// Used to suggest a folder name.
+    document_name: String,
+    trim: bool,
+    composite: bool,
+}
+impl LayerExportModal {
+    /// Start from the document's previous layer export settings, if any.
+    #[must_use]
+    pub fn new(document_name: String, last: Option<&LayerExportSettings>) -> Self {
+        Self {
+            document_name,
+            trim: last.map_or(false, |last| last.trim),
+            composite: last.map_or(true, |last| last.composite),
+        }
+    }
+}
+impl super::Modal for LayerExportModal {
+    type Cancel = ();
+    type Confirm = LayerExportSettings;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-export-layers";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        ui.label("Each top-level layer or group is written to a PNG named after it.");
+        ui.checkbox(&mut self.trim, "Trim to content")
+            .on_hover_text(format!(
+                "Crop each to what's drawn on it, listing where each sits in {}. Empty layers are skipped",
+                crate::export::MANIFEST_NAME
+            ));
+        ui.checkbox(&mut self.composite, "Include composite")
+            .on_hover_text(format!(
+                "Also write the whole document, as {}.png",
+                crate::export::COMPOSITE_NAME
+            ));
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("Export...").clicked_or_enter() {
+                if let Some(dir) = rfd::FileDialog::new()
+                    .set_file_name(format!("{} layers", self.document_name))
+                    .pick_folder()
+                {
+                    return super::modal::Response::Confirm(LayerExportSettings {
+                        dir,
+                        trim: self.trim,
+                        composite: self.composite,
+                    });
+                }
+            }
+            if ui.button("Cancel").clicked_or_escape() {
+                return super::modal::Response::Cancel(());
+            }
+            super::modal::Response::Continue
+        })
+        .inner
+    }
+}
+
+pub struct TimelapseModal {
+    /// Used to suggest a file name.
+    document_name: String,
+    /// The document's oldest history has been deleted, so the timelapse can't start from the beginning.
+    trimmed: bool,
+    video: bool,
+    fps: u32,
+    interval: std::num::NonZeroUsize,
+}
+impl TimelapseModal {
+    #[must_use]
+    pub fn new(document_name: String, trimmed: bool) -> Self {
+        Self {
+            document_name,
+            trimmed,
+            video: true,
+            fps: 30,
+            // Unwrap ok - not zero.
+            interval: std::num::NonZeroUsize::new(1).unwrap(),
+        }
+    }
+    /// Ask the user where to write. A video is a file, an image sequence is a folder of them.
+    fn pick_path(&self) -> Option<std::path::PathBuf> {
+        let name = tr!("timelapse-file-name", name = self.document_name.as_str());
+        if !self.video {
+            return rfd::FileDialog::new().set_file_name(name).pick_folder();
+        }
+        let mut path = rfd::FileDialog::new()
+            .add_filter("MP4", &["mp4"])
+            .set_file_name(format!("{name}.mp4"))
+            .save_file()?;
+        if path.extension().and_then(std::ffi::OsStr::to_str) != Some("mp4") {
+            path.set_extension("mp4");
+        }
+        Some(path)
+    }
+}
+impl super::Modal for TimelapseModal {
+    type Cancel = ();
+    type Confirm = TimelapseSettings;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-export-timelapse";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.video, true, tr!("timelapse-video"))
+                .on_hover_text(tr!("timelapse-video.hover"));
+            ui.radio_value(&mut self.video, false, tr!("timelapse-sequence"));
+        });
+        ui.add_enabled(
+            self.video,
+            egui::Slider::new(&mut self.fps, 1..=60).text(tr!("timelapse-fps")),
+        );
+        let mut interval = self.interval.get();
+        ui.add(
+            egui::Slider::new(&mut interval, 1..=100)
+                .text(tr!("timelapse-interval"))
+                .logarithmic(true),
+        );
+        self.interval = std::num::NonZeroUsize::new(interval).unwrap_or(self.interval);
+        if self.trimmed {
+            ui.label(tr!("timelapse-trimmed"));
+        }
+        ui.label(tr!("timelapse-canvas-paused"));
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button(tr!("timelapse-export")).clicked_or_enter() {
+                if let Some(path) = self.pick_path() {
+                    return super::modal::Response::Confirm(TimelapseSettings {
+                        path,
+                        output: if self.video {
+                            TimelapseOutput::Mp4 { fps: self.fps }
+                        } else {
+                            TimelapseOutput::ImageSequence
+                        },
+                        interval: self.interval,
+                    });
+                }
+            }
+            if ui.button(tr!("timelapse-cancel")).clicked_or_escape() {
+                return super::modal::Response::Cancel(());
+            }
+            super::modal::Response::Continue
+        })
+        .inner
+    }
+}
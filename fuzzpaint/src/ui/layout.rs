@@ -0,0 +1,210 @@
+//! Arrangement of the main window's panels into docks along the edges of the viewport.
+
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    strum::AsRefStr,
+    strum::EnumIter,
+    Hash,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    Debug,
+)]
+pub enum Panel {
+    Layers,
+    Brush,
+    Colors,
+    History,
+    Navigator,
+    Tools,
+    Stats,
+    Console,
+    #[strum(serialize = "About this document")]
+    About,
+    #[strum(serialize = "Stroke inspector")]
+    Strokes,
+}
+
+#[derive(strum::AsRefStr, strum::EnumIter, Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Dock {
+    Left,
+    Right,
+    Bottom,
+    Hidden,
+}
+
+/// Which panels are shown in which docks, and in what order.
+///
+/// The first panel of each dock takes all the space not claimed by the panels after it.
+/// Panels not listed in any dock are hidden, as are the console, document info, and stroke inspector by
+/// default.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(default)]
+pub struct Layout {
+    pub left: Vec<Panel>,
+    pub right: Vec<Panel>,
+    pub bottom: Vec<Panel>,
+}
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            left: vec![Panel::Colors, Panel::Brush, Panel::Tools, Panel::Stats],
+            right: vec![Panel::Layers, Panel::History],
+            bottom: vec![Panel::Navigator],
+        }
+    }
+}
+impl Layout {
+    /// Get the panels of the given dock, or `None` for [`Dock::Hidden`].
+    #[must_use]
+    pub fn panels(&self, dock: Dock) -> Option<&[Panel]> {
+        match dock {
+            Dock::Left => Some(&self.left),
+            Dock::Right => Some(&self.right),
+            Dock::Bottom => Some(&self.bottom),
+            Dock::Hidden => None,
+        }
+    }
+    fn panels_mut(&mut self, dock: Dock) -> Option<&mut Vec<Panel>> {
+        match dock {
+            Dock::Left => Some(&mut self.left),
+            Dock::Right => Some(&mut self.right),
+            Dock::Bottom => Some(&mut self.bottom),
+            Dock::Hidden => None,
+        }
+    }
+    /// Find which dock the panel lives in.
+    #[must_use]
+    pub fn dock_of(&self, panel: Panel) -> Dock {
+        [Dock::Left, Dock::Right, Dock::Bottom]
+            .into_iter()
+            .find(|&dock| {
+                self.panels(dock)
+                    .is_some_and(|panels| panels.contains(&panel))
+            })
+            .unwrap_or(Dock::Hidden)
+    }
+    #[must_use]
+    pub fn is_visible(&self, panel: Panel) -> bool {
+        self.dock_of(panel) != Dock::Hidden
+    }
+    /// Move the panel to the end of the given dock, removing it from where it was before.
+    /// Moving to the dock it's already in is a no-op.
+    pub fn move_to(&mut self, panel: Panel, dock: Dock) {
+        if self.dock_of(panel) == dock {
+            return;
+        }
+        self.remove(panel);
+        if let Some(panels) = self.panels_mut(dock) {
+            panels.push(panel);
+        }
+    }
+    /// Shift the panel by `offset` places within its dock, clamping to the ends.
+    pub fn shift(&mut self, panel: Panel, offset: isize) {
+        let Some(panels) = self.panels_mut(self.dock_of(panel)) else {
+            return;
+        };
+        // Unwrap ok - found by `dock_of`.
+        let from = panels.iter().position(|&p| p == panel).unwrap();
+        let to = from
+            .saturating_add_signed(offset)
+            .min(panels.len().saturating_sub(1));
+        let panel = panels.remove(from);
+        panels.insert(to, panel);
+    }
+    fn remove(&mut self, panel: Panel) {
+        for panels in [&mut self.left, &mut self.right, &mut self.bottom] {
+            panels.retain(|&p| p != panel);
+        }
+    }
+    /// Remove duplicate panels, keeping the first occurance. A hand-edited file could
+    /// otherwise show a panel twice, which egui would not appreciate.
+    #[must_use]
+    pub fn deduplicated(mut self) -> Self {
+        let mut seen = hashbrown::HashSet::new();
+        for panels in [&mut self.left, &mut self.right, &mut self.bottom] {
+            panels.retain(|&p| seen.insert(p));
+        }
+        self
+    }
+}
+
+/// Menu contents for moving every panel between docks and resetting the layout.
+/// Returns true if the layout was changed.
+pub fn view_menu(ui: &mut egui::Ui, layout: &mut Layout) -> bool {
+    let mut changed = false;
+    for panel in <Panel as strum::IntoEnumIterator>::iter() {
+        ui.menu_button(panel.as_ref(), |ui| {
+            changed |= dock_menu(ui, layout, panel);
+        });
+    }
+    ui.separator();
+    if ui.button("Reset layout").clicked() {
+        *layout = Layout::default();
+        changed = true;
+        ui.close_menu();
+    }
+    changed
+}
+
+/// Menu contents for moving a single panel. Returns true if the layout was changed.
+pub fn dock_menu(ui: &mut egui::Ui, layout: &mut Layout, panel: Panel) -> bool {
+    let current = layout.dock_of(panel);
+    let mut changed = false;
+    for dock in <Dock as strum::IntoEnumIterator>::iter() {
+        if ui.radio(current == dock, dock.as_ref()).clicked() {
+            layout.move_to(panel, dock);
+            changed = true;
+            ui.close_menu();
+        }
+    }
+    if current != Dock::Hidden {
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("⏶").on_hover_text("Move earlier").clicked() {
+                layout.shift(panel, -1);
+                changed = true;
+            }
+            if ui.button("⏷").on_hover_text("Move later").clicked() {
+                layout.shift(panel, 1);
+                changed = true;
+            }
+        });
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Dock, Layout, Panel};
+    #[test]
+    fn move_and_shift() {
+        let mut layout = Layout::default();
+        layout.move_to(Panel::Layers, Dock::Left);
+        assert_eq!(layout.dock_of(Panel::Layers), Dock::Left);
+        assert_eq!(layout.left.last(), Some(&Panel::Layers));
+        assert!(!layout.right.contains(&Panel::Layers));
+
+        // Clamps at the front.
+        layout.shift(Panel::Layers, -100);
+        assert_eq!(layout.left.first(), Some(&Panel::Layers));
+
+        layout.move_to(Panel::Layers, Dock::Hidden);
+        assert!(!layout.is_visible(Panel::Layers));
+        // Hidden panels can't shift, but shouldn't panic.
+        layout.shift(Panel::Layers, 1);
+    }
+    #[test]
+    fn deduplicate() {
+        let layout = Layout {
+            left: vec![Panel::Stats, Panel::Stats],
+            right: vec![Panel::Stats, Panel::Layers],
+            bottom: vec![],
+        }
+        .deduplicated();
+        assert_eq!(layout.left, [Panel::Stats]);
+        assert_eq!(layout.right, [Panel::Layers]);
+    }
+}
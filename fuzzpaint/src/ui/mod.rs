@@ -1,10 +1,28 @@
+mod assets;
 mod brush_ui;
+mod canvas;
 mod color_palette;
+mod command_palette;
+mod console;
+mod crash;
+mod diagnostics;
 mod drag;
+mod export;
+pub mod layout;
 mod modal;
+mod new_document;
+mod progress;
+mod properties;
+mod recover;
 pub mod requests;
+mod restroke;
 mod settings;
+mod stroke_inspector;
+mod toasts;
 
+use crate::global::config_file::ConfigFile;
+use crate::global::templates::{DocumentTemplate, LayerKind};
+use crate::i18n::tr;
 use modal::Modal;
 
 use egui::{RichText, Ui};
@@ -15,6 +33,7 @@ use fuzzpaint_core::{
     io,
     queue::{self, state_reader::CommandQueueStateReader},
     state,
+    units::{Length, Resolution, Unit},
     util::FiniteF32,
 };
 
@@ -22,7 +41,10 @@ const STROKE_LAYER_ICON: &str = "✏";
 const TEXT_LAYER_ICON: &str = "🗛";
 const NOTE_LAYER_ICON: &str = "🖹";
 const FILL_LAYER_ICON: &str = "⬛";
+const IMAGE_LAYER_ICON: &str = "🖼";
+const GRADIENT_LAYER_ICON: &str = "◧";
 const GROUP_ICON: &str = "🗀";
+const FILTER_ICON: &str = "◐";
 const SCISSOR_ICON: &str = "✂";
 const PLUS_ICON: char = '➕';
 const PALETTE_ICON: char = '🎨';
@@ -31,6 +53,11 @@ const HOME_ICON: char = '🏠';
 const PIN_ICON: char = '📌';
 const ALPHA_ICON: &str = "α";
 const RESET_ICON: &str = "⟲";
+const SOLO_ICON: &str = "◎";
+const REFERENCE_ICON: &str = "👁";
+
+/// How far the view rotates per press of [`crate::actions::Action::ViewportRotateCW`] and CCW.
+const VIEW_ROTATE_STEP_DEGREES: f32 = 15.0;
 
 /// Justify `(available_size, size, margin)` -> `(size', margin')`, such that `count` elements
 /// will fill available space completely.
@@ -82,6 +109,7 @@ trait ResponseExt {
         action: crate::actions::Action,
     ) -> Self;
     fn clicked_or_escape(self) -> bool;
+    fn clicked_or_enter(self) -> bool;
 }
 impl ResponseExt for egui::Response {
     fn or_action_clicked(
@@ -113,11 +141,35 @@ impl ResponseExt for egui::Response {
     fn clicked_or_escape(self) -> bool {
         self.clicked() || self.ctx.input(|input| input.key_pressed(egui::Key::Escape))
     }
+    /// Returns true if [`egui::Response::clicked`], or `Enter` is pressed while nothing has focus, useful for
+    /// confirm buttons. A focused widget takes `Enter` for itself, and a single line text edit gives up focus to
+    /// it, so typing into a field and pressing `Enter` confirms.
+    fn clicked_or_enter(self) -> bool {
+        self.clicked()
+            || (self.enabled
+                && self.ctx.memory(|memory| memory.focus().is_none())
+                && self.ctx.input(|input| input.key_pressed(egui::Key::Enter)))
+    }
 }
 
 enum CurrentModal {
     BrushCreation(brush_ui::CreationModal),
     Settings(settings::Settings),
+    /// Exporting the given document.
+    Export(state::document::ID, export::ExportModal),
+    /// Resizing the canvas of the given document.
+    CanvasSize(state::document::ID, canvas::CanvasSizeModal),
+    /// Exporting the layers of the given document.
+    ExportLayers(state::document::ID, export::LayerExportModal),
+    /// Exporting a timelapse of the given document.
+    Timelapse(state::document::ID, export::TimelapseModal),
+    RelinkAssets(assets::RelinkModal),
+    NewDocument(new_document::NewDocumentModal),
+    CommandPalette(command_palette::CommandPalette),
+    Recover(recover::RecoverModal),
+    Crash(crash::CrashModal),
+    /// Editing the metadata of the given document.
+    Properties(state::document::ID, properties::PropertiesModal),
 }
 
 enum CloseState {
@@ -130,8 +182,29 @@ enum CloseState {
 struct PerDocumentData {
     id: state::document::ID,
     graph_selection: Option<state::graph::AnyID>,
+    /// Further nodes selected alongside `graph_selection`, to be changed all at once.
+    graph_also_selected: Vec<state::graph::AnyID>,
     graph_focused_subtree: Option<state::graph::NodeID>,
     name: String,
+    /// Settings of the most recent export, repeated by [`crate::actions::Action::ExportAgain`].
+    last_export: Option<crate::export::ExportSettings>,
+    /// Settings of the most recent layer export, to start the next from.
+    last_layer_export: Option<crate::export::LayerExportSettings>,
+    /// Only show layers whose name or tag contains this, if not empty.
+    layer_search: String,
+    /// Highlight what changed since the document was saved.
+    show_saved_diff: bool,
+    /// The node shown alone, as if nothing else were in the graph.
+    solo: Option<state::graph::AnyID>,
+}
+impl PerDocumentData {
+    /// Every selected node, the primary selection first.
+    fn selected_nodes(&self) -> Vec<state::graph::AnyID> {
+        self.graph_selection
+            .into_iter()
+            .chain(self.graph_also_selected.iter().copied())
+            .collect()
+    }
 }
 pub struct MainUI {
     // Modal layers, in order. (There is no better way to represent this state, I have considered greatly!)
@@ -147,6 +220,28 @@ pub struct MainUI {
     picker_color: egui::ecolor::HsvaGamma,
     picker_in_flux: bool,
     picker_changed: bool,
+    /// The user closed the warning shown when rendering in software.
+    software_warning_dismissed: bool,
+    /// Whether the reference window should be open.
+    reference_window: bool,
+    /// Whether screenshots include the UI, or only the viewport.
+    screenshot_ui: bool,
+    /// A screenshot asked for this frame, taken after the next, once the menu that asked for it is gone.
+    screenshot_pending: Option<crate::export::ScreenshotSettings>,
+    /// A screenshot of the frame just drawn, see [`Self::take_screenshot`].
+    screenshot: Option<crate::export::ScreenshotSettings>,
+    /// Documents being read in the background, see [`Self::poll_opening`].
+    opening: Vec<(
+        std::path::PathBuf,
+        std::sync::mpsc::Receiver<std::io::Result<queue::DocumentCommandQueue>>,
+    )>,
+    toasts: toasts::Toasts,
+    /// What the about panel last showed, see [`about_panel`].
+    about: Option<AboutStatistics>,
+    /// The tool last chosen, shown in the hint bar. Mirrors the pen tools' own state.
+    base_tool: crate::pen_tools::StateLayer,
+    /// Runs actions chosen from the command palette.
+    action_sender: crate::actions::ActionSender,
 
     requests_send: crossbeam::channel::Sender<requests::UiRequest>,
     requests_recv: crossbeam::channel::Receiver<requests::UiRequest>,
@@ -154,25 +249,37 @@ pub struct MainUI {
 }
 impl MainUI {
     #[must_use]
-    pub fn new(action_listener: crate::actions::ActionListener) -> Self {
+    pub fn new(
+        action_listener: crate::actions::ActionListener,
+        action_sender: crate::actions::ActionSender,
+    ) -> Self {
         let documents = crate::global::provider().document_iter();
         let documents: Vec<_> = documents
             .map(|id| PerDocumentData {
                 id,
                 graph_focused_subtree: None,
                 graph_selection: None,
+                graph_also_selected: Vec::new(),
                 name: "Unknown".into(),
+                last_export: None,
+                last_layer_export: None,
+                layer_search: String::new(),
+                show_saved_diff: false,
+                solo: None,
             })
             .collect();
         let cur_document = documents.last().map(|doc| doc.id);
 
         let (requests_send, requests_recv) = crossbeam::channel::unbounded();
-        Self {
+        let mut this = Self {
             close_state: CloseState::None,
             documents,
             cur_document,
 
-            modal: None,
+            // Offer to recover what was lost to a crash, before anything else.
+            modal: recover::RecoverModal::new()
+                .map(CurrentModal::Recover)
+                .or_else(|| crash::CrashModal::new().map(CurrentModal::Crash)),
             picker_color: egui::ecolor::HsvaGamma {
                 h: 0.0,
                 s: 0.0,
@@ -180,11 +287,103 @@ impl MainUI {
                 a: 1.0,
             },
             picker_in_flux: false,
+            software_warning_dismissed: false,
+            reference_window: false,
+            screenshot_ui: true,
+            screenshot_pending: None,
+            screenshot: None,
+            opening: Vec::new(),
+            toasts: toasts::Toasts::default(),
+            about: None,
             picker_changed: false,
+            base_tool: crate::global::session::Session::startup_tool(),
+            action_sender,
 
             requests_send,
             requests_recv,
             action_listener,
+        };
+        if let Some(session) = crate::global::session::Session::last() {
+            this.restore_session(session);
+        }
+        if this.documents.is_empty()
+            && crate::global::preferences::Preferences::read()
+                .startup
+                .new_document
+        {
+            this.new_document(&crate::global::templates::Templates::default_template());
+        }
+        this
+    }
+    /// Pick up the views and selected layers of the documents reopened from the last session, and which of them
+    /// was current.
+    fn restore_session(&mut self, session: &crate::global::session::Session) {
+        let provider = crate::global::provider();
+        // Where each document was in the session, or after all of them if it wasn't.
+        let mut order = hashbrown::HashMap::new();
+        for interface in &mut self.documents {
+            let restored = provider.inspect(interface.id, |queue| {
+                let state = queue.peek_clone_state();
+                let path = state.document().path.as_ref()?;
+                let (idx, saved) = session
+                    .documents
+                    .iter()
+                    .enumerate()
+                    .find(|(_, saved)| &saved.path == path)?;
+                let layer = saved
+                    .layer
+                    .as_deref()
+                    .and_then(|layer| crate::global::session::layer_at(state.graph(), layer));
+                Some((idx, layer, saved.view))
+            });
+            let Some((idx, layer, view)) = restored.flatten() else {
+                continue;
+            };
+            order.insert(interface.id, idx);
+            interface.graph_selection = layer;
+            if let Some(view) = view.transform() {
+                crate::global::session::set_view(interface.id, view);
+            }
+        }
+        self.documents
+            .sort_by_key(|interface| order.get(&interface.id).copied().unwrap_or(usize::MAX));
+        self.cur_document = self.documents.last().map(|interface| interface.id);
+    }
+    /// Note the open documents in the [session store](crate::global::session), the current one last, along with
+    /// their views, selected layers, and the tool, to be restored next time if the user chose to.
+    pub fn remember_session(&self) {
+        let provider = crate::global::provider();
+        let mut documents: Vec<_> = self.documents.iter().collect();
+        if let Some(current) = self.cur_document {
+            documents.sort_by_key(|interface| interface.id == current);
+        }
+        let documents = documents
+            .into_iter()
+            .filter_map(|interface| {
+                provider
+                    .inspect(interface.id, |queue| {
+                        let state = queue.peek_clone_state();
+                        let path = state.document().path.clone()?;
+                        let layer = interface.graph_selection.and_then(|layer| {
+                            crate::global::session::layer_path(state.graph(), layer)
+                        });
+                        Some(crate::global::session::SessionDocument {
+                            path,
+                            layer,
+                            view: crate::global::session::view(interface.id)
+                                .unwrap_or_default()
+                                .into(),
+                        })
+                    })
+                    .flatten()
+            })
+            .collect();
+        let session = crate::global::session::Session {
+            tool: Some(self.base_tool),
+            documents,
+        };
+        if let Err(e) = session.save() {
+            tracing::warn!("failed to save the session: {e:#}");
         }
     }
     /// Marks that a close has been requested by the windower
@@ -192,20 +391,53 @@ impl MainUI {
         // Close unconditionally if
         // * A close was requested again even though the modal is up.
         //   (Either we crashed and the modal isn't seen or the user *really* wants us to close lol)
-        // * No open documents to save anyway.
+        // * No documents have unsaved changes.
         self.close_state =
-            if matches!(self.close_state, CloseState::Modal) || self.documents.is_empty() {
+            if matches!(self.close_state, CloseState::Modal) || self.dirty_documents().is_empty() {
                 CloseState::Confirmed
             } else {
-                // There are open docs, prompt
+                // There are unsaved changes, prompt
                 CloseState::Modal
             }
     }
+    /// Open documents with changes since they were last saved.
+    fn dirty_documents(&self) -> Vec<&PerDocumentData> {
+        let provider = crate::global::provider();
+        self.documents
+            .iter()
+            .filter(|interface| {
+                provider
+                    .inspect(interface.id, queue::DocumentCommandQueue::is_dirty)
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
     /// Returns true if the app should close.
     #[must_use]
     pub fn should_close(&self) -> bool {
         matches!(self.close_state, CloseState::Confirmed)
     }
+    /// Whether the user wants the reference window open.
+    #[must_use]
+    pub fn reference_window(&self) -> bool {
+        self.reference_window
+    }
+    /// The reference window was opened or closed outside of the UI.
+    pub fn set_reference_window(&mut self, open: bool) {
+        self.reference_window = open;
+    }
+    /// A screenshot the user asked for, to be taken of the frame the UI last drew.
+    pub fn take_screenshot(&mut self) -> Option<crate::export::ScreenshotSettings> {
+        self.screenshot.take()
+    }
+    /// Take a screenshot once the UI has drawn another frame.
+    fn request_screenshot(&mut self, ctx: &egui::Context, target: crate::export::ScreenshotTarget) {
+        self.screenshot_pending = Some(crate::export::ScreenshotSettings {
+            target,
+            ui: self.screenshot_ui,
+        });
+        ctx.request_repaint();
+    }
     /// Returns true if a top-level modal exists asking whether to close the app.
     #[must_use]
     fn modal_enable(&self) -> bool {
@@ -224,6 +456,12 @@ impl MainUI {
     /// Returns the size of the document's viewport space - that is, the size of the rect not covered by any side/top/bottom panels.
     /// None if a full-screen menu is shown.
     pub fn ui(&mut self, ctx: &egui::Context) -> Option<(ultraviolet::Vec2, ultraviolet::Vec2)> {
+        for request in crate::instance::take_pending() {
+            self.open_paths(request.paths, request.read_only);
+        }
+        self.poll_opening();
+        // Asked for last frame, this one will be captured.
+        self.screenshot = self.screenshot_pending.take();
         // Close modal, on top of everything.
         if self.modal_enable() {
             self.do_close_modal(ctx);
@@ -233,7 +471,33 @@ impl MainUI {
         self.do_modal(ctx, !self.modal_enable());
 
         // Show, but disable if modal exists.
-        self.main_ui(ctx, !self.background_enable())
+        let viewport = self.main_ui(ctx, !self.background_enable());
+        // Floats above everything, and doesn't affect the viewport.
+        diagnostics::overlay(ctx);
+        picker_readout(ctx);
+        progress::show(ctx);
+        match self.toasts.show(ctx) {
+            toasts::Response::SaveRecovery => self.save_recovery_copies(),
+            toasts::Response::None => (),
+        }
+        viewport
+    }
+    /// Write a copy of every open document into a new recovery directory, without marking them saved.
+    fn save_recovery_copies(&mut self) {
+        let documents: Vec<_> = self
+            .documents
+            .iter()
+            .map(|document| (document.id, document.name.clone()))
+            .collect();
+        match save_recovery_copies(&documents) {
+            Ok(directory) => self.toasts.recovered(directory),
+            Err(e) => crate::errors::Report::new(
+                crate::errors::Severity::DataLoss,
+                tr!("recovery-directory-failed"),
+                &e,
+            )
+            .send(),
+        }
     }
     fn get_cur_interface(&mut self) -> Option<&mut PerDocumentData> {
         // Get the document's interface, or reset to none if not found.
@@ -251,19 +515,56 @@ impl MainUI {
         }
     }
     fn do_close_modal(&mut self, ctx: &egui::Context) {
-        let clicked_elsewhere = egui::Window::new("Exit")
+        let clicked_elsewhere = egui::Window::new(tr!("close-title"))
             .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
             .collapsible(false)
             .show(ctx, |ui| {
-                ui.label("There are unsaved documents. Do you really want to exit?");
+                let dirty: Vec<_> = self
+                    .dirty_documents()
+                    .into_iter()
+                    .map(|interface| (interface.id, interface.name.clone()))
+                    .collect();
+                ui.label(tr!("close-unsaved"));
+                for (_, name) in &dirty {
+                    ui.label(format!("• {name}"));
+                }
                 ui.horizontal(|ui| {
-                    // On first run-thru it would be nice for this cancel button to auto-focus itself.
-                    if ui.button("Cancel").clicked_or_escape() {
-                        self.close_state = CloseState::None;
+                    if ui.button(tr!("close-save-all")).clicked() {
+                        let mut failed = false;
+                        for (id, name) in &dirty {
+                            let path = match document_path(*id) {
+                                Some(path) => path,
+                                // Untitled, ask where it goes. Cancelling any cancels the close.
+                                None => {
+                                    let Some(path) = ask_save_path(name) else {
+                                        failed = true;
+                                        break;
+                                    };
+                                    path
+                                }
+                            };
+                            if let Err(e) = save_document_to(*id, &path) {
+                                crate::errors::Report::new(
+                                    crate::errors::Severity::DataLoss,
+                                    tr!("close-save-failed", name = name.as_str()),
+                                    &e,
+                                )
+                                .send();
+                                failed = true;
+                            }
+                        }
+                        // Stay open so that the user can decide what to do with the failed or skipped documents.
+                        if !failed {
+                            self.close_state = CloseState::Confirmed;
+                        }
                     }
-                    if ui.button("Exit").clicked() {
+                    if ui.button(tr!("close-discard")).clicked() {
                         self.close_state = CloseState::Confirmed;
                     }
+                    // On first run-thru it would be nice for this cancel button to auto-focus itself.
+                    if ui.button(tr!("close-cancel")).clicked_or_escape() {
+                        self.close_state = CloseState::None;
+                    }
                 });
             })
             .map_or(false, |resp| resp.response.clicked_elsewhere());
@@ -282,9 +583,28 @@ impl MainUI {
         let title = match modal {
             CurrentModal::BrushCreation(_) => brush_ui::CreationModal::NAME,
             CurrentModal::Settings(_) => settings::Settings::NAME,
+            CurrentModal::Export(..) => export::ExportModal::NAME,
+            CurrentModal::CanvasSize(..) => canvas::CanvasSizeModal::NAME,
+            CurrentModal::ExportLayers(..) => export::LayerExportModal::NAME,
+            CurrentModal::Timelapse(..) => export::TimelapseModal::NAME,
+            CurrentModal::RelinkAssets(_) => assets::RelinkModal::NAME,
+            CurrentModal::NewDocument(_) => new_document::NewDocumentModal::NAME,
+            CurrentModal::CommandPalette(_) => command_palette::CommandPalette::NAME,
+            CurrentModal::Recover(_) => recover::RecoverModal::NAME,
+            CurrentModal::Crash(_) => crash::CrashModal::NAME,
+            CurrentModal::Properties(..) => properties::PropertiesModal::NAME,
         };
+        let title = tr!(title);
 
         let mut is_open = true;
+        let mut export = None;
+        let mut export_layers = None;
+        let mut canvas_size = None;
+        let mut timelapse = None;
+        let mut new_document = None;
+        let mut run_action = None;
+        let mut recovered = None;
+        let mut properties = None;
 
         let cancelled = egui::Window::new(title)
             .collapsible(false)
@@ -293,52 +613,234 @@ impl MainUI {
             .show(ctx, |ui| match modal {
                 CurrentModal::BrushCreation(b) => b.do_ui(ui).closed(),
                 CurrentModal::Settings(s) => s.do_ui(ui).closed(),
+                CurrentModal::Export(document, e) => match e.do_ui(ui) {
+                    modal::Response::Confirm(settings) => {
+                        export = Some((*document, settings));
+                        true
+                    }
+                    response => response.closed(),
+                },
+                CurrentModal::CanvasSize(document, c) => match c.do_ui(ui) {
+                    modal::Response::Confirm(canvas) => {
+                        canvas_size = Some((*document, canvas));
+                        true
+                    }
+                    response => response.closed(),
+                },
+                CurrentModal::ExportLayers(document, e) => match e.do_ui(ui) {
+                    modal::Response::Confirm(settings) => {
+                        export_layers = Some((*document, settings));
+                        true
+                    }
+                    response => response.closed(),
+                },
+                CurrentModal::Timelapse(document, t) => match t.do_ui(ui) {
+                    modal::Response::Confirm(settings) => {
+                        timelapse = Some((*document, settings));
+                        true
+                    }
+                    response => response.closed(),
+                },
+                CurrentModal::RelinkAssets(r) => r.do_ui(ui).closed(),
+                CurrentModal::NewDocument(n) => match n.do_ui(ui) {
+                    modal::Response::Confirm(template) => {
+                        new_document = Some(template);
+                        true
+                    }
+                    response => response.closed(),
+                },
+                CurrentModal::CommandPalette(c) => match c.do_ui(ui) {
+                    modal::Response::Confirm(action) => {
+                        run_action = Some(action);
+                        true
+                    }
+                    response => response.closed(),
+                },
+                CurrentModal::Recover(r) => match r.do_ui(ui) {
+                    modal::Response::Confirm(queue) => {
+                        recovered = Some(queue);
+                        // Stay open for the rest.
+                        r.is_empty()
+                    }
+                    response => response.closed(),
+                },
+                CurrentModal::Crash(c) => c.do_ui(ui).closed(),
+                CurrentModal::Properties(document, p) => match p.do_ui(ui) {
+                    modal::Response::Confirm((metadata, resolution)) => {
+                        properties = Some((*document, metadata, resolution));
+                        true
+                    }
+                    response => response.closed(),
+                },
             })
             .and_then(|resp| resp.inner)
             .unwrap_or(false);
 
         // Closed :3
         if !is_open || cancelled {
-            self.modal = None;
+            // Crashes are told of once recovering from them is dealt with.
+            let recovering = matches!(self.modal, Some(CurrentModal::Recover(_)));
+            self.modal = recovering
+                .then(crash::CrashModal::new)
+                .flatten()
+                .map(CurrentModal::Crash);
+        }
+        if let Some((document, settings)) = export {
+            self.export_document(document, settings);
+        }
+        if let Some((document, canvas)) = canvas_size {
+            if let Err(e) = crate::canvas::set_canvas(document, canvas) {
+                tracing::warn!("failed to resize canvas: {e:#}");
+            }
+        }
+        if let Some((document, metadata, resolution)) = properties {
+            if let Err(e) = properties::apply(document, metadata, resolution) {
+                tracing::warn!("failed to set document properties: {e:#}");
+            }
+        }
+        if let Some((target, settings)) = export_layers {
+            let _ = self.requests_send.send(requests::UiRequest::Document {
+                target,
+                request: requests::DocumentRequest::ExportLayers(settings.clone()),
+            });
+            if let Some(interface) = self.documents.iter_mut().find(|doc| doc.id == target) {
+                interface.last_layer_export = Some(settings);
+            }
+        }
+        if let Some((target, settings)) = timelapse {
+            let _ = self.requests_send.send(requests::UiRequest::Document {
+                target,
+                request: requests::DocumentRequest::Timelapse(settings),
+            });
+        }
+        if let Some(template) = new_document {
+            self.new_document(&template);
+        }
+        if let Some(action) = run_action {
+            self.action_sender.oneshot(action);
+        }
+        if let Some(queue) = recovered {
+            let id = queue.id();
+            if self.add_document(queue) {
+                self.cur_document = Some(id);
+            }
+        }
+    }
+    /// Export the document, remembering the settings for [`crate::actions::Action::ExportAgain`].
+    fn export_document(
+        &mut self,
+        document: state::document::ID,
+        settings: crate::export::ExportSettings,
+    ) {
+        let _ = self.requests_send.send(requests::UiRequest::Document {
+            target: document,
+            request: requests::DocumentRequest::Export(settings.clone()),
+        });
+        if let Some(interface) = self.documents.iter_mut().find(|doc| doc.id == document) {
+            interface.last_export = Some(settings);
+        }
+    }
+    /// Show the export modal for the current document.
+    fn open_export_modal(&mut self) {
+        if let Some(interface) = self.get_cur_interface() {
+            let dpi = document_resolution(Some(interface.id)).into_dpi();
+            let canvas = crate::global::provider()
+                .inspect(interface.id, |queue| {
+                    queue.peek_clone_state().document().viewport.canvas()
+                })
+                .unwrap_or_default();
+            let modal = export::ExportModal::new(
+                interface.name.clone(),
+                dpi,
+                canvas.size,
+                interface.last_export.as_ref(),
+            );
+            self.modal = Some(CurrentModal::Export(interface.id, modal));
         }
     }
-    fn new_document(&mut self) {
-        // When making a new document, start out with a white bg and stroke layer.
+    /// Repeat the current document's last export, or ask for settings if it has never been exported.
+    fn export_again(&mut self) {
+        let Some(interface) = self.get_cur_interface() else {
+            return;
+        };
+        let document = interface.id;
+        let next = interface
+            .last_export
+            .as_ref()
+            .map(crate::export::ExportSettings::next);
+        match next {
+            Some(settings) => self.export_document(document, settings),
+            None => self.open_export_modal(),
+        }
+    }
+    /// Show the modal for choosing a new document's template.
+    fn open_new_document_modal(&mut self) {
+        self.modal = Some(CurrentModal::NewDocument(
+            new_document::NewDocumentModal::default(),
+        ));
+    }
+    /// Make a document from a template, which should be [validated](DocumentTemplate::validate).
+    #[allow(clippy::cast_precision_loss)]
+    fn new_document(&mut self, template: &DocumentTemplate) {
+        // Start out with the template's layers.
         // (These additions are not included in the history, but that's Okay!)
         let mut graph = fuzzpaint_core::state::graph::BlendGraph::default();
-        let _ = graph.add_leaf(
-            state::graph::Location::IndexIntoRoot(0),
-            "Background".to_owned(),
-            state::graph::LeafType::SolidColor {
-                blend: Blend::default(),
-                source: fuzzpaint_core::color::ColorOrPalette::WHITE,
-            },
-        );
-
         let mut stroke_collection =
             fuzzpaint_core::state::stroke_collection::StrokeCollectionState::default();
-        let new_collection = crate::FuzzID::default();
-        stroke_collection.0.insert(
-            new_collection,
-            state::stroke_collection::StrokeCollection::default(),
-        );
-
-        // Insert the stroke layer we just allocated a collection for.
-        let stroke_layer = graph
-            .add_leaf(
+        if let Ok(Some(background)) = template.validate() {
+            let _ = graph.add_leaf(
                 state::graph::Location::IndexIntoRoot(0),
-                "Stroke Layer".to_owned(),
-                state::graph::LeafType::StrokeLayer {
+                "Background".to_owned(),
+                state::graph::LeafType::SolidColor {
                     blend: Blend::default(),
-                    collection: new_collection,
-                    inner_transform: state::transform::Similarity::default(),
-                    outer_transform: state::transform::Matrix::default(),
+                    source: background,
                 },
-            )
-            .ok();
-        if stroke_layer.is_none() {
-            // Uh oh, failed to make that layer. Remove the collection to not leave it orphaned.
-            stroke_collection.0.clear();
+            );
+        }
+
+        // Select the topmost stroke layer, ready to draw.
+        let mut stroke_layer = None;
+        for layer in &template.layers {
+            let blend = layer.blend().unwrap_or_default();
+            let leaf = match layer.kind {
+                LayerKind::Stroke => {
+                    let new_collection = crate::FuzzID::default();
+                    stroke_collection.0.insert(
+                        new_collection,
+                        state::stroke_collection::StrokeCollection::default(),
+                    );
+                    state::graph::LeafType::StrokeLayer {
+                        blend,
+                        collection: new_collection,
+                        inner_transform: state::transform::Similarity::default(),
+                        outer_transform: state::transform::Matrix::default(),
+                    }
+                }
+                LayerKind::Fill => state::graph::LeafType::SolidColor {
+                    blend,
+                    source: layer
+                        .color()
+                        .unwrap_or(fuzzpaint_core::color::ColorOrPalette::BLACK),
+                },
+            };
+            let collection = match &leaf {
+                state::graph::LeafType::StrokeLayer { collection, .. } => Some(*collection),
+                _ => None,
+            };
+            match graph.add_leaf(
+                state::graph::Location::IndexIntoRoot(0),
+                layer.name.clone(),
+                leaf,
+            ) {
+                Ok(id) if collection.is_some() => stroke_layer = Some(id),
+                Ok(_) => (),
+                Err(_) => {
+                    // Uh oh, failed to make that layer. Remove the collection to not leave it orphaned.
+                    if let Some(collection) = collection {
+                        stroke_collection.0.remove(&collection);
+                    }
+                }
+            }
         }
 
         let name = "New Document".to_owned();
@@ -347,6 +849,15 @@ impl MainUI {
         let new_doc = queue::DocumentCommandQueue::from_state(
             state::document::Document {
                 name: name.clone(),
+                viewport: state::document::Viewport {
+                    size: [template.width, template.height]
+                        .map(|px| fuzzpaint_core::units::Length::Logical(px as f32)),
+                    resolution: fuzzpaint_core::units::Resolution::Dpi(template.dpi.clamp(
+                        *state::document::Viewport::DPI_RANGE.start(),
+                        *state::document::Viewport::DPI_RANGE.end(),
+                    )),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
             graph,
@@ -355,13 +866,21 @@ impl MainUI {
         );
 
         let new_id = new_doc.id();
+        // Before anything else can change it.
+        crate::global::journals::start_untitled(&new_doc);
         // Can't fail, this is a newly allocated ID so it's unqieu
         let _ = crate::global::provider().insert(new_doc);
         let interface = PerDocumentData {
             id: new_id,
             graph_focused_subtree: None,
             graph_selection: stroke_layer.map(Into::into),
+            graph_also_selected: Vec::new(),
             name,
+            last_export: None,
+            last_layer_export: None,
+            layer_search: String::new(),
+            show_saved_diff: false,
+            solo: None,
         };
         let _ = self.requests_send.send(requests::UiRequest::Document {
             target: new_id,
@@ -370,36 +889,144 @@ impl MainUI {
         self.cur_document = Some(new_id);
         self.documents.push(interface);
     }
-    fn open_documents(&mut self) {
+    /// Ask for documents to open, without allowing changes to them if `read_only`.
+    fn open_documents(&mut self, read_only: bool) {
         // Synchronous and bad just for now.
         if let Some(files) = rfd::FileDialog::new().pick_files() {
-            let point_repository = crate::global::points();
-            let provider = crate::global::provider();
-
-            // Keep track of the last successful loaded id
-            let mut recent_success = None;
-            for file in files {
-                match io::read_path(file, point_repository) {
-                    Ok(doc) => {
-                        let id = doc.id();
-                        if provider.insert(doc).is_ok() {
-                            recent_success = Some(id);
-                            self.documents.push(PerDocumentData {
-                                id,
-                                graph_focused_subtree: None,
-                                graph_selection: None,
-                                name: "Unknown".into(),
-                            });
-                        }
+            self.open_paths(files, read_only);
+        }
+    }
+    /// Open each of the files as a document in the background. See [`Self::poll_opening`].
+    fn open_paths(&mut self, files: Vec<std::path::PathBuf>, read_only: bool) {
+        for file in files {
+            let name = file
+                .file_name()
+                .map_or_else(|| file.to_string_lossy(), std::ffi::OsStr::to_string_lossy)
+                .into_owned();
+            let path = file.clone();
+            let spawned = crate::global::tasks::spawn(format!("Opening {name}"), move |progress| {
+                crate::global::file_locks::open(&path, read_only, progress)
+            });
+            match spawned {
+                Ok(recv) => self.opening.push((file, recv)),
+                Err(e) => crate::errors::Report::new(
+                    crate::errors::Severity::Recoverable,
+                    tr!("open-failed", path = file.display().to_string()),
+                    &e.into(),
+                )
+                .send(),
+            }
+        }
+    }
+    /// Add the documents that finished opening, selecting the last to succeed.
+    fn poll_opening(&mut self) {
+        // Keep track of the last successful loaded id
+        let mut recent_success = None;
+        let mut finished = Vec::new();
+        self.opening.retain(|(file, recv)| match recv.try_recv() {
+            Ok(result) => {
+                finished.push((file.clone(), result));
+                false
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => true,
+            // The worker panicked.
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false,
+        });
+        for (file, result) in finished {
+            match result {
+                Ok(doc) => {
+                    let id = doc.id();
+                    let state = doc.peek_clone_state();
+                    let document = state.document();
+                    // Offer to relink the first document with missing files, one modal at a time.
+                    if self.modal.is_none() {
+                        self.modal = assets::RelinkModal::new(
+                            document.name.clone(),
+                            document.assets.clone(),
+                        )
+                        .map(CurrentModal::RelinkAssets);
+                    }
+                    if self.add_document(doc) {
+                        recent_success = Some(id);
                     }
-                    Err(e) => log::error!("Failed to load: {e:#}"),
                 }
+                Err(e) if fuzzpaint_core::progress::Cancelled::is_cause_of(&e) => {
+                    tracing::info!(path = %file.display(), "cancelled opening");
+                }
+                Err(e) => crate::errors::Report::new(
+                    crate::errors::Severity::Recoverable,
+                    tr!("open-failed", path = file.display().to_string()),
+                    &e.into(),
+                )
+                .send(),
             }
-            // Select last one, if any succeeded.
-            if let Some(new_doc) = recent_success {
-                self.cur_document = Some(new_doc);
+        }
+        // Select last one, if any succeeded.
+        if let Some(new_doc) = recent_success {
+            self.cur_document = Some(new_doc);
+        }
+    }
+    /// Give a document a tab of its own, returning false if it was already open.
+    fn add_document(&mut self, queue: queue::DocumentCommandQueue) -> bool {
+        let id = queue.id();
+        if crate::global::provider().insert(queue).is_err() {
+            return false;
+        }
+        self.documents.push(PerDocumentData {
+            id,
+            graph_focused_subtree: None,
+            graph_selection: None,
+            graph_also_selected: Vec::new(),
+            name: "Unknown".into(),
+            last_export: None,
+            last_layer_export: None,
+            layer_search: String::new(),
+            show_saved_diff: false,
+            solo: None,
+        });
+        true
+    }
+    /// Save the current document in the background, if any.
+    fn save_current(&self) {
+        let Some(current) = self.cur_document else {
+            return;
+        };
+        std::thread::spawn(move || {
+            if let Err(e) = save_document(current) {
+                crate::errors::Report::new(
+                    crate::errors::Severity::DataLoss,
+                    tr!("save-document-failed"),
+                    &e,
+                )
+                .send();
             }
+        });
+    }
+    /// Ask where to save a copy of the document, then open the copy in its own tab. This is how read-only
+    /// documents are edited.
+    fn save_document_as(&mut self, document: state::document::ID) {
+        let name = self
+            .documents
+            .iter()
+            .find(|interface| interface.id == document)
+            .map_or("Untitled", |interface| interface.name.as_str());
+        let Some(path) = ask_save_path(name) else {
+            return;
+        };
+        let (backups, history) = {
+            let preferences = crate::global::preferences::Preferences::read();
+            (preferences.backups, preferences.saved_history)
+        };
+        if let Err(e) = write_document(document, &path, backups, history) {
+            crate::errors::Report::new(
+                crate::errors::Severity::DataLoss,
+                tr!("save-failed", path = path.display().to_string()),
+                &e,
+            )
+            .send();
+            return;
         }
+        self.open_paths(vec![path], false);
     }
     /// Render just self. Modals and insets handled separately.
     fn main_ui(
@@ -420,11 +1047,47 @@ impl MainUI {
             ));
         };
         let interface = self.get_cur_interface().cloned();
+        if enabled {
+            window_actions(&action_frame);
+            if action_frame.action_trigger_count(crate::actions::Action::CommandPalette) > 0 {
+                self.modal = Some(CurrentModal::CommandPalette(
+                    command_palette::CommandPalette::default(),
+                ));
+            }
+            if action_frame.action_trigger_count(crate::actions::Action::New) > 0 {
+                self.open_new_document_modal();
+            }
+            if action_frame.action_trigger_count(crate::actions::Action::Open) > 0 {
+                self.open_documents(false);
+            }
+            if action_frame.action_trigger_count(crate::actions::Action::Save) > 0 {
+                self.save_current();
+            }
+            if action_frame.action_trigger_count(crate::actions::Action::SaveAs) > 0 {
+                if let Some(current) = self.cur_document {
+                    self.save_document_as(current);
+                }
+            }
+        }
 
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             ui.set_enabled(enabled);
             self.menu_bar(ui);
         });
+        if crate::render_device::is_software_rendering() && !self.software_warning_dismissed {
+            egui::TopBottomPanel::top("software-warning").show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(
+                        egui::RichText::new(tr!("banner-software"))
+                            .color(ui.style().visuals.warn_fg_color),
+                    );
+                    ui.label(tr!("banner-software.remedy"));
+                    if ui.button(tr!("banner-dismiss")).clicked() {
+                        self.software_warning_dismissed = true;
+                    }
+                });
+            });
+        }
 
         if self.cur_document.is_none() {
             // No document view open, show a splash.
@@ -441,57 +1104,85 @@ impl MainUI {
             None
         } else {
             // A document is open, show the main view.
-            egui::TopBottomPanel::bottom("nav_bar").show(ctx, |ui| {
-                ui.set_enabled(enabled);
-                if let Some(interface) = interface {
-                    Self::nav_bar(ui, interface.id, &self.requests_send, &action_frame);
+            let layout = crate::global::preferences::Preferences::read()
+                .layout
+                .clone();
+            // Hotkeys that would otherwise be handled by a panel, so they still work when it's hidden.
+            if let Some(document) = self.cur_document {
+                Self::document_actions(document, &self.requests_send, &action_frame);
+            }
+            if enabled && action_frame.action_trigger_count(crate::actions::Action::ExportAgain) > 0
+            {
+                self.export_again();
+            }
+            if enabled && action_frame.action_trigger_count(crate::actions::Action::Screenshot) > 0
+            {
+                self.request_screenshot(ctx, crate::export::ScreenshotTarget::Clipboard);
+            }
+            if !layout.is_visible(layout::Panel::Tools) {
+                if let Some(tool) = tool_hotkeys(&action_frame, &self.requests_send) {
+                    self.base_tool = tool;
                 }
-            });
-            egui::SidePanel::right("layers").show(ctx, |ui| {
+            }
+            egui::TopBottomPanel::bottom("hint-bar").show(ctx, |ui| {
                 ui.set_enabled(enabled);
-                ui.label("Layers");
-                ui.separator();
-                if let Some(interface) = self.get_cur_interface() {
-                    layers_panel(ui, interface);
-
-                    // Update selections.
-                    let mut globals = crate::AdHocGlobals::get().write();
-                    let old_brush = globals.take().map(|globals| globals.brush);
-                    *globals = Some(crate::AdHocGlobals {
-                        document: interface.id,
-                        brush: old_brush.unwrap_or(state::StrokeBrushSettings {
-                            is_eraser: false,
-                            brush: fuzzpaint_core::brush::UniqueID([0; 32]),
-                            color_modulate: fcolor::ColorOrPalette::BLACK,
-                            size_mul: FiniteF32::new(10.0).unwrap(),
-                            spacing_px: FiniteF32::new(0.5).unwrap(),
-                        }),
-                        node: interface.graph_selection,
-                    });
-                }
+                hint_bar(ui, self.base_tool);
             });
+            for dock in [
+                layout::Dock::Bottom,
+                layout::Dock::Right,
+                layout::Dock::Left,
+            ] {
+                // Unwrap ok - only `Hidden` has no panels.
+                let panels = layout.panels(dock).unwrap();
+                self.dock(ctx, dock, panels, enabled, &action_frame);
+            }
+            self.update_globals(&action_frame);
 
-            egui::SidePanel::left("inspector")
-                .resizable(true)
-                .show(ctx, |ui| {
-                    ui.set_enabled(enabled);
-                    // Stats at bottom
-                    egui::TopBottomPanel::bottom("stats-panel").show_inside(ui, stats_panel);
-                    // Toolbox above that
-                    egui::TopBottomPanel::bottom("tools-panel")
-                        .show_inside(ui, |ui| tools_panel(ui, &action_frame, &self.requests_send));
-                    // Brush panel takes the rest
-                    self.colors_panel(ui, self.cur_document, &action_frame);
-                });
             egui::TopBottomPanel::top("document-bar").show(ctx, |ui| {
                 ui.set_enabled(enabled);
                 self.document_bar(ui);
             });
+            if let Some(document) = self.cur_document.filter(|&document| {
+                crate::global::provider()
+                    .inspect(document, queue::DocumentCommandQueue::is_read_only)
+                    .unwrap_or(false)
+            }) {
+                let salvaged = crate::global::file_locks::salvaged(document);
+                egui::TopBottomPanel::top("read-only").show(ctx, |ui| {
+                    ui.set_enabled(enabled);
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(
+                            egui::RichText::new(tr!("banner-read-only"))
+                                .color(ui.style().visuals.warn_fg_color),
+                        );
+                        if salvaged.is_some() {
+                            ui.label(tr!("banner-read-only.salvaged"));
+                        } else {
+                            ui.label(tr!("banner-read-only.opened"));
+                        }
+                        if ui.button(tr!("banner-save-as")).clicked() {
+                            self.save_document_as(document);
+                        }
+                    });
+                    if let Some(skipped) = salvaged.filter(|skipped| !skipped.is_empty()) {
+                        ui.collapsing(tr!("banner-skipped", count = skipped.len()), |ui| {
+                            for part in &skipped {
+                                ui.label(part);
+                            }
+                        });
+                    }
+                });
+            }
 
-            {
+            // The picker feeds into the colors panel, so it's only useful alongside it.
+            if layout.is_visible(layout::Panel::Colors) {
                 let response = color_palette::picker_dock(ctx, &mut self.picker_color);
                 self.picker_changed = response.response.changed();
                 self.picker_in_flux = response.in_flux;
+            } else {
+                self.picker_changed = false;
+                self.picker_in_flux = false;
             }
 
             let viewport = ctx.available_rect();
@@ -506,79 +1197,421 @@ impl MainUI {
             ))
         }
     }
+    /// Show the panels of one dock. The first panel takes all the space left over by the rest.
+    fn dock(
+        &mut self,
+        ctx: &egui::Context,
+        dock: layout::Dock,
+        panels: &[layout::Panel],
+        enabled: bool,
+        action_frame: &crate::actions::ActionFrame,
+    ) {
+        let Some((&first, rest)) = panels.split_first() else {
+            return;
+        };
+        let contents = |ui: &mut Ui| {
+            ui.set_enabled(enabled);
+            // Stack the rest along the far edge, in reverse so that they read in order.
+            for &panel in rest.iter().rev() {
+                let id = egui::Id::new(("dock-panel", panel));
+                if dock == layout::Dock::Bottom {
+                    egui::SidePanel::right(id)
+                        .resizable(true)
+                        .show_inside(ui, |ui| self.panel(ui, panel, action_frame));
+                } else {
+                    egui::TopBottomPanel::bottom(id)
+                        .resizable(true)
+                        .show_inside(ui, |ui| self.panel(ui, panel, action_frame));
+                }
+            }
+            self.panel(ui, first, action_frame);
+        };
+        let id = egui::Id::new(("dock", dock));
+        match dock {
+            layout::Dock::Left => {
+                egui::SidePanel::left(id)
+                    .resizable(true)
+                    .show(ctx, contents);
+            }
+            layout::Dock::Right => {
+                egui::SidePanel::right(id)
+                    .resizable(true)
+                    .show(ctx, contents);
+            }
+            layout::Dock::Bottom => {
+                egui::TopBottomPanel::bottom(id).show(ctx, contents);
+            }
+            layout::Dock::Hidden => (),
+        }
+    }
+    fn panel(
+        &mut self,
+        ui: &mut Ui,
+        panel: layout::Panel,
+        action_frame: &crate::actions::ActionFrame,
+    ) {
+        match panel {
+            layout::Panel::Layers => {
+                ui.label(tr!("layers-title"));
+                ui.separator();
+                let requests_send = self.requests_send.clone();
+                if let Some(interface) = self.get_cur_interface() {
+                    layers_panel(ui, interface, &requests_send);
+                }
+            }
+            layout::Panel::Brush => self.brush_panel(ui),
+            layout::Panel::Colors => self.colors_panel(ui, self.cur_document),
+            layout::Panel::History => history_panel(ui, self.cur_document),
+            layout::Panel::Navigator => {
+                if let Some(document) = self.cur_document {
+                    Self::nav_bar(ui, document, &self.requests_send);
+                }
+            }
+            layout::Panel::Tools => {
+                if let Some(tool) = tools_panel(ui, action_frame, &self.requests_send) {
+                    self.base_tool = tool;
+                }
+            }
+            layout::Panel::Stats => stats_panel(ui),
+            layout::Panel::Console => console::console_panel(ui),
+            layout::Panel::About => about_panel(ui, self.cur_document, &mut self.about),
+            layout::Panel::Strokes => {
+                let layer = self
+                    .get_cur_interface()
+                    .and_then(|interface| Some((interface.id, interface.graph_selection?)));
+                stroke_inspector::panel(ui, layer);
+            }
+        }
+    }
+    /// Sync the [`crate::AdHocGlobals`] with the current document and selection, and apply brush hotkeys.
+    fn update_globals(&mut self, actions: &crate::actions::ActionFrame) {
+        use az::SaturatingAs;
+
+        let Some(interface) = self.get_cur_interface() else {
+            return;
+        };
+        let mut globals = crate::AdHocGlobals::get().write();
+        let old = globals.take();
+        let globals = globals.insert(crate::AdHocGlobals {
+            document: interface.id,
+            brush: old.as_ref().map_or_else(startup_brush, |old| old.brush),
+            secondary_color: old
+                .as_ref()
+                .map_or(fcolor::ColorOrPalette::WHITE, |old| old.secondary_color),
+            eraser_tip: old.as_ref().map(|old| old.eraser_tip).unwrap_or_default(),
+            node: interface.graph_selection,
+        });
+        let brush = &mut globals.brush;
+
+        // Swap primary and secondary if requested.
+        if actions.action_trigger_count(crate::actions::Action::ColorSwap) % 2 == 1 {
+            std::mem::swap(&mut brush.color_modulate, &mut globals.secondary_color);
+        }
+        // Apply size up/down actions
+        // - for down, + for up
+        let size_steps = actions
+            .action_trigger_count(crate::actions::Action::BrushSizeUp)
+            .saturating_as::<i32>()
+            .saturating_sub(
+                actions
+                    .action_trigger_count(crate::actions::Action::BrushSizeDown)
+                    .saturating_as(),
+            );
+        if size_steps != 0 {
+            // Usually editors supply some kind of snapping here to snap to
+            // common values instead. Todo!
+            let factor = 2.0f32.powf(size_steps as f32 / 4.0);
+            if let Ok(size_mul) = FiniteF32::new(brush.size_mul.get() * factor) {
+                brush.size_mul = size_mul;
+            }
+            if let Ok(spacing_px) = FiniteF32::new(brush.spacing_px.get() * factor) {
+                brush.spacing_px = spacing_px;
+            }
+        }
+    }
     /// File, Edit, ect
     fn menu_bar(&mut self, ui: &mut Ui) {
         ui.horizontal_wrapped(|ui| {
             ui.label(egui::RichText::new("🐑").font(egui::FontId::proportional(20.0)))
                 .on_hover_text("Baa");
             egui::menu::bar(ui, |ui| {
-                ui.menu_button("File", |ui| {
-                    let add_button = |ui: &mut Ui, label, shortcut| -> egui::Response {
-                        let mut button = egui::Button::new(label);
-                        if let Some(shortcut) = shortcut {
-                            button = button.shortcut_text(shortcut);
+                ui.menu_button(tr!("menu-file"), |ui| {
+                    use crate::actions::Action;
+                    // Labelled by the message, with the shortcut of the action it does.
+                    let add_button = |ui: &mut Ui, id: &str, action| -> egui::Response {
+                        let shortcut = hotkey_text(action).unwrap_or_default();
+                        ui.add(egui::Button::new(tr!(id)).shortcut_text(shortcut))
+                    };
+                    if add_button(ui, "menu-file-new", Action::New).clicked() {
+                        self.open_new_document_modal();
+                    };
+                    if add_button(ui, "menu-file-save", Action::Save).clicked() {
+                        self.save_current();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.cur_document.is_some(),
+                            egui::Button::new(tr!("menu-file-save-as"))
+                                .shortcut_text(hotkey_text(Action::SaveAs).unwrap_or_default()),
+                        )
+                        .on_hover_text(tr!("menu-file-save-as.hover"))
+                        .clicked()
+                    {
+                        if let Some(current) = self.cur_document {
+                            self.save_document_as(current);
+                        }
+                        ui.close_menu();
+                    }
+                    if add_button(ui, "menu-file-open", Action::Open).clicked() {
+                        self.open_documents(false);
+                    }
+                    if ui
+                        .button(tr!("menu-file-open-read-only"))
+                        .on_hover_text(tr!("menu-file-open-read-only.hover"))
+                        .clicked()
+                    {
+                        self.open_documents(true);
+                        ui.close_menu();
+                    }
+                    //let _ = add_button(ui, "Open as new", None);
+                    let has_document = self.cur_document.is_some();
+                    if ui
+                        .add_enabled(has_document, egui::Button::new(tr!("menu-file-export")))
+                        .clicked()
+                    {
+                        self.open_export_modal();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            has_document,
+                            egui::Button::new(tr!("menu-file-export-again")).shortcut_text(
+                                hotkey_text(Action::ExportAgain).unwrap_or_default(),
+                            ),
+                        )
+                        .on_hover_text(tr!("menu-file-export-again.hover"))
+                        .clicked()
+                    {
+                        self.export_again();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            has_document,
+                            egui::Button::new(tr!("menu-file-export-layers")),
+                        )
+                        .on_hover_text(tr!("menu-file-export-layers.hover"))
+                        .clicked()
+                    {
+                        if let Some(interface) = self.get_cur_interface() {
+                            let modal = export::LayerExportModal::new(
+                                interface.name.clone(),
+                                interface.last_layer_export.as_ref(),
+                            );
+                            self.modal = Some(CurrentModal::ExportLayers(interface.id, modal));
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            has_document,
+                            egui::Button::new(tr!("menu-file-export-timelapse")),
+                        )
+                        .on_hover_text(tr!("menu-file-export-timelapse.hover"))
+                        .clicked()
+                    {
+                        if let Some(interface) = self.get_cur_interface() {
+                            let trimmed = !crate::global::history_spool::is_complete(interface.id);
+                            let modal =
+                                export::TimelapseModal::new(interface.name.clone(), trimmed);
+                            self.modal = Some(CurrentModal::Timelapse(interface.id, modal));
+                        }
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(has_document, egui::Button::new(tr!("menu-file-properties")))
+                        .on_hover_text(tr!("menu-file-properties.hover"))
+                        .clicked()
+                    {
+                        if let Some(document) = self.cur_document {
+                            let modal = crate::global::provider().inspect(document, |queue| {
+                                let state = queue.peek_clone_state();
+                                let document = state.document();
+                                properties::PropertiesModal::new(
+                                    document.metadata.read().clone(),
+                                    &document.viewport,
+                                )
+                            });
+                            self.modal =
+                                modal.map(|modal| CurrentModal::Properties(document, modal));
+                        }
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button(tr!("menu-edit"), |ui| {
+                    let selection = self
+                        .cur_document
+                        .filter(|&document| crate::global::selection::get(document).is_some());
+                    if ui
+                        .add_enabled(
+                            selection.is_some(),
+                            egui::Button::new(tr!("menu-edit-deselect")),
+                        )
+                        .clicked()
+                    {
+                        if let Some(document) = selection {
+                            crate::global::selection::deselect(document);
+                        }
+                        ui.close_menu();
+                    }
+                    if let Some(document) = self.cur_document {
+                        ui.menu_button(tr!("menu-edit-ruler"), |ui| ruler_menu(ui, document));
+                    } else {
+                        ui.add_enabled(false, egui::Button::new(tr!("menu-edit-ruler")));
+                    }
+                    ui.separator();
+                    let layer = self
+                        .get_cur_interface()
+                        .and_then(|interface| Some((interface.id, interface.graph_selection?)));
+                    let brush = crate::AdHocGlobals::read_clone().map(|globals| globals.brush);
+                    restroke::menu(ui, layer, brush);
+                    ui.separator();
+                    let document = self.cur_document;
+                    if ui
+                        .add_enabled(
+                            document.is_some(),
+                            egui::Button::new(tr!("menu-edit-canvas-size")),
+                        )
+                        .on_hover_text(tr!("menu-edit-canvas-size.hover"))
+                        .clicked()
+                    {
+                        self.modal = document.and_then(|document| {
+                            let current = crate::global::provider().inspect(document, |queue| {
+                                queue.peek_clone_state().document().viewport.canvas()
+                            })?;
+                            Some(CurrentModal::CanvasSize(
+                                document,
+                                canvas::CanvasSizeModal::new(current),
+                            ))
+                        });
+                        ui.close_menu();
+                    }
+                    let selected = selection.and_then(|document| {
+                        Some((document, crate::canvas::selection_region(document)?))
+                    });
+                    if ui
+                        .add_enabled(
+                            selected.is_some(),
+                            egui::Button::new(tr!("menu-edit-crop-to-selection")),
+                        )
+                        .on_hover_text(tr!("menu-edit-crop-to-selection.hover"))
+                        .clicked()
+                    {
+                        if let Some((document, region)) = selected {
+                            if let Err(e) =
+                                crate::canvas::set_canvas(document, crate::canvas::covering(region))
+                            {
+                                tracing::warn!("failed to crop canvas: {e:#}");
+                            }
                         }
-                        ui.add(button)
-                    };
-                    if add_button(ui, "New", Some("Ctrl+N")).clicked() {
-                        self.new_document();
-                    };
-                    if add_button(ui, "Save", Some("Ctrl+S")).clicked() {
-                        // Dirty testing implementation!
-                        if let Some(current) = self.cur_document {
-                            std::thread::spawn(move || {
-                                if let Some(reader) = crate::global::provider()
-                                    .inspect(current, queue::DocumentCommandQueue::peek_clone_state)
-                                {
-                                    let repo = crate::global::points();
-
-                                    let try_block = || -> anyhow::Result<()> {
-                                        let mut path = dirs::document_dir().unwrap();
-                                        path.push("temp.fzp");
-                                        let file = std::fs::File::create(path)?;
-
-                                        let start = std::time::Instant::now();
-                                        io::write_into(&reader, repo, &file)?;
-                                        let duration = start.elapsed();
-
-                                        file.sync_all()?;
-                                        if let Some(size) =
-                                            file.metadata().ok().map(|meta| meta.len())
-                                        {
-                                            let size = size as f64;
-                                            let speed = size / duration.as_secs_f64();
-                                            log::info!(
-                                                "Wrote {} in {}us ({}/s)",
-                                                human_bytes::human_bytes(size),
-                                                duration.as_micros(),
-                                                human_bytes::human_bytes(speed)
-                                            );
-                                        } else {
-                                            log::info!("Wrote in {}us", duration.as_micros());
-                                        }
-                                        Ok(())
-                                    };
-
-                                    if let Err(e) = try_block() {
-                                        log::error!("Failed to write document: {e:?}");
-                                    }
-                                }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            document.is_some(),
+                            egui::Button::new(tr!("menu-edit-trim-canvas")),
+                        )
+                        .on_hover_text(tr!("menu-edit-trim-canvas.hover"))
+                        .clicked()
+                    {
+                        if let Some(document) = document {
+                            let _ = self.requests_send.send(requests::UiRequest::Document {
+                                target: document,
+                                request: requests::DocumentRequest::Autocrop,
                             });
                         }
+                        ui.close_menu();
                     }
-                    // let _ = add_button(ui, "Save as", Some("Ctrl+Shift+S"));
-                    if add_button(ui, "Open", Some("Ctrl+O")).clicked() {
-                        self.open_documents();
-                    }
-                    //let _ = add_button(ui, "Open as new", None);
-                    //let _ = add_button(ui, "Export", None);
-                });
-                ui.menu_button("Edit", |ui| {
-                    if ui.button("Settings").clicked() {
+                    ui.separator();
+                    if ui.button(tr!("menu-edit-settings")).clicked() {
                         self.modal = Some(CurrentModal::Settings(settings::Settings::default()));
                         ui.close_menu();
                     }
                 });
+                ui.menu_button(tr!("menu-view"), |ui| {
+                    let mut diagnostics = crate::diagnostics::enabled();
+                    if ui
+                        .checkbox(&mut diagnostics, tr!("menu-view-diagnostics"))
+                        .changed()
+                    {
+                        crate::diagnostics::set_enabled(diagnostics);
+                    }
+                    ui.menu_button(tr!("menu-view-visualize"), visualizations_menu);
+                    let mut outline = crate::global::selection::show_outline();
+                    if ui
+                        .checkbox(&mut outline, tr!("menu-view-selection-outline"))
+                        .changed()
+                    {
+                        crate::global::selection::set_show_outline(outline);
+                    }
+                    let mut loupe = crate::pen_tools::loupe::enabled();
+                    if ui
+                        .checkbox(&mut loupe, tr!("menu-view-hover-loupe"))
+                        .on_hover_text(tr!("menu-view-hover-loupe.hover"))
+                        .changed()
+                    {
+                        crate::pen_tools::loupe::set_enabled(loupe);
+                    }
+                    ui.checkbox(
+                        &mut self.reference_window,
+                        tr!("menu-view-reference-window"),
+                    )
+                    .on_hover_text(tr!("menu-view-reference-window.hover"));
+                    ui.menu_button(tr!("menu-view-screenshot"), |ui| {
+                        ui.checkbox(&mut self.screenshot_ui, tr!("menu-view-screenshot-ui"))
+                            .on_hover_text(tr!("menu-view-screenshot-ui.hover"));
+                        let target = if ui.button(tr!("menu-view-screenshot-clipboard")).clicked() {
+                            Some(crate::export::ScreenshotTarget::Clipboard)
+                        } else if ui.button(tr!("menu-view-screenshot-save")).clicked() {
+                            rfd::FileDialog::new()
+                                .add_filter("PNG", &["png"])
+                                .set_file_name("screenshot.png")
+                                .save_file()
+                                .map(crate::export::ScreenshotTarget::File)
+                        } else {
+                            None
+                        };
+                        if let Some(target) = target {
+                            self.request_screenshot(ui.ctx(), target);
+                            ui.close_menu();
+                        }
+                    });
+                    let saved_diff = self.get_cur_interface().and_then(|interface| {
+                        ui.checkbox(&mut interface.show_saved_diff, tr!("menu-view-saved-diff"))
+                            .on_hover_text(tr!("menu-view-saved-diff.hover"))
+                            .changed()
+                            .then_some((interface.id, interface.show_saved_diff))
+                    });
+                    if let Some((target, enabled)) = saved_diff {
+                        let _ = self.requests_send.send(requests::UiRequest::Document {
+                            target,
+                            request: requests::DocumentRequest::ShowSavedDiff(enabled),
+                        });
+                    }
+                    ui.separator();
+                    let mut preferences = crate::global::preferences::Preferences::write();
+                    let window = ui
+                        .menu_button(tr!("menu-view-window"), |ui| {
+                            window_menu(ui, &mut preferences.window)
+                        })
+                        .inner
+                        .unwrap_or(false);
+                    let layout = layout::view_menu(ui, &mut preferences.layout);
+                    if window || layout {
+                        save_preferences(&preferences);
+                    }
+                });
             });
         });
     }
@@ -679,7 +1712,7 @@ impl MainUI {
                     };
 
                     if big_button(ui, a, "➕ New").clicked() {
-                        self.new_document();
+                        self.open_new_document_modal();
                     }
                     if big_button(ui, b, "🗀 Open").clicked() {
                         self.open_documents();
@@ -704,8 +1737,18 @@ impl MainUI {
                 }
                 // Then show, a clicakble header for each document.
                 let mut deleted_ids = smallvec::SmallVec::<[state::document::ID; 1]>::new();
+                let provider = crate::global::provider();
                 for PerDocumentData { id, name, .. } in &self.documents {
                     let id = *id;
+                    // Mark documents with unsaved changes.
+                    let title = if provider
+                        .inspect(id, queue::DocumentCommandQueue::is_dirty)
+                        .unwrap_or(false)
+                    {
+                        format!("{name} •")
+                    } else {
+                        name.clone()
+                    };
                     egui::containers::Frame::group(ui.style())
                         .outer_margin(egui::Margin::symmetric(0.0, 0.0))
                         .inner_margin(egui::Margin::symmetric(0.0, 0.0))
@@ -721,7 +1764,7 @@ impl MainUI {
                         })
                         .show(ui, |ui| {
                             let middle_click_delete = ui
-                                .selectable_value(&mut self.cur_document, Some(id), name)
+                                .selectable_value(&mut self.cur_document, Some(id), title)
                                 .clicked_by(egui::PointerButton::Middle);
                             if ui
                                 .add(egui::Button::new("✖").small().frame(false))
@@ -742,12 +1785,19 @@ impl MainUI {
                 }
                 self.documents
                     .retain(|interface| !deleted_ids.contains(&interface.id));
+                for id in deleted_ids {
+                    crate::global::journals::finish(id);
+                    crate::global::history_spool::finish(id);
+                    crate::global::file_locks::release(id);
+                    crate::global::session::remove(id);
+                    crate::global::recent_colors::remove(id);
+                }
                 // Finally, show an add button.
                 if ui
                     .add(egui::Button::new(PLUS_ICON.to_string()).frame(false))
                     .clicked()
                 {
-                    self.new_document();
+                    self.open_new_document_modal();
                 }
             });
         });
@@ -757,7 +1807,6 @@ impl MainUI {
         ui: &mut Ui,
         document: state::document::ID,
         requests: &crossbeam::channel::Sender<requests::UiRequest>,
-        frame: &crate::actions::ActionFrame,
     ) {
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
             // Everything here is shown in reverse order!
@@ -796,18 +1845,6 @@ impl MainUI {
                     ),
                 });
             }
-            // Handle Scroll wheel
-            // future: configurable scroll direction and speed.
-            // FIXME: respect cursor position.
-            let scroll_zoom_cmds = frame.action_trigger_count(crate::actions::Action::ZoomIn)
-                as f32
-                - frame.action_trigger_count(crate::actions::Action::ZoomOut) as f32;
-            let _ = requests.send(requests::UiRequest::Document {
-                target: document,
-                request: requests::DocumentRequest::View(requests::DocumentViewRequest::ZoomBy(
-                    1.25f32.powf(scroll_zoom_cmds),
-                )),
-            });
 
             ui.add(egui::Separator::default().vertical());
 
@@ -850,41 +1887,81 @@ impl MainUI {
             let undo = egui::Button::new("⮪");
             let redo = egui::Button::new("⮫");
 
-            // Accept undo/redo actions
-            let mut undos = frame.action_trigger_count(crate::actions::Action::Undo);
-            let mut redos = frame.action_trigger_count(crate::actions::Action::Redo);
-
             // RTL - add in reverse :P
             if ui.add(redo).clicked() {
-                redos += 1;
+                crate::global::provider().write(document, "redo", |document| document.redo_n(1));
             };
             if ui.add(undo).clicked() {
-                undos += 1;
+                crate::global::provider().write(document, "undo", |document| document.undo_n(1));
             };
-            // Submit undo/redos as requested.
-            if redos != 0 {
-                crate::global::provider().inspect(document, |document| document.redo_n(redos));
-            }
-            if undos != 0 {
-                crate::global::provider().inspect(document, |document| document.undo_n(undos));
-            }
         });
     }
-
-    fn colors_panel(
-        &mut self,
-        ui: &mut Ui,
-        current_doc: Option<state::document::ID>,
-        actions: &crate::actions::ActionFrame,
+    /// Handle view and history hotkeys for the document, independent of whether the navigator is shown.
+    fn document_actions(
+        document: state::document::ID,
+        requests: &crossbeam::channel::Sender<requests::UiRequest>,
+        frame: &crate::actions::ActionFrame,
     ) {
-        use az::SaturatingAs;
+        // Handle Scroll wheel
+        // future: configurable scroll direction and speed.
+        // FIXME: respect cursor position.
+        let scroll_zoom_cmds = frame.action_trigger_count(crate::actions::Action::ZoomIn) as f32
+            - frame.action_trigger_count(crate::actions::Action::ZoomOut) as f32;
+        let _ = requests.send(requests::UiRequest::Document {
+            target: document,
+            request: requests::DocumentRequest::View(requests::DocumentViewRequest::ZoomBy(
+                1.25f32.powf(scroll_zoom_cmds),
+            )),
+        });
+
+        // Mirroring. An even count cancels out.
+        let view_request = |request| {
+            let _ = requests.send(requests::UiRequest::Document {
+                target: document,
+                request: requests::DocumentRequest::View(request),
+            });
+        };
+        if frame.action_trigger_count(crate::actions::Action::ViewportFlipHorizontal) % 2 == 1 {
+            view_request(requests::DocumentViewRequest::FlipHorizontal);
+        }
+        if frame.action_trigger_count(crate::actions::Action::ViewportFlipVertical) % 2 == 1 {
+            view_request(requests::DocumentViewRequest::FlipVertical);
+        }
+        // Stepped rotation. Positive is clockwise on screen, as Y points down.
+        let rotate_steps = frame.action_trigger_count(crate::actions::Action::ViewportRotateCW)
+            as f32
+            - frame.action_trigger_count(crate::actions::Action::ViewportRotateCCW) as f32;
+        if rotate_steps != 0.0 {
+            view_request(requests::DocumentViewRequest::RotateBy(
+                (rotate_steps * VIEW_ROTATE_STEP_DEGREES).to_radians(),
+            ));
+        }
 
+        // Accept undo/redo actions
+        let undos = frame.action_trigger_count(crate::actions::Action::Undo);
+        let redos = frame.action_trigger_count(crate::actions::Action::Redo);
+        // Submit undo/redos as requested.
+        if redos != 0 {
+            crate::global::provider().write(document, "redo", |document| document.redo_n(redos));
+        }
+        if undos != 0 {
+            crate::global::provider().write(document, "undo", |document| document.undo_n(undos));
+        }
+    }
+
+    fn colors_panel(&mut self, ui: &mut Ui, current_doc: Option<state::document::ID>) {
         let mut globals = crate::AdHocGlobals::get().write();
-        if let Some(brush) = globals.as_mut().map(|globals| &mut globals.brush) {
+        if let Some(globals) = globals.as_mut() {
+            let brush = &mut globals.brush;
             if let Some(current_doc) = current_doc {
+                // Type in a color exactly, feeding back into the picker.
+                let numeric = color_palette::numeric_entry(ui, &mut self.picker_color);
+                self.picker_changed |= numeric.response.changed();
+                let numeric_in_flux = numeric.in_flux;
+                ui.separator();
                 // Show palette
                 // Between AdHocGlobals and this, two locks are held. Recipe for a deadlock.
-                crate::global::provider().inspect(current_doc, |doc| {
+                crate::global::provider().write(current_doc, "palette", |doc| {
                     // Unfortunately this is the second `write_with` this frame. I need a way for this to work better..
                     // A retained mode UI is probably the solution as well as just a good idea for the future.
                     doc.write_with(|w| {
@@ -898,19 +1975,31 @@ impl MainUI {
                                     .into();
                         }
 
+                        color_palette::primary_secondary(
+                            ui,
+                            &mut brush.color_modulate,
+                            &mut globals.secondary_color,
+                            &palette,
+                        );
+                        let recent = crate::global::recent_colors::get(current_doc);
+                        if !recent.is_empty() {
+                            color_palette::recent_strokes(
+                                ui,
+                                &mut brush.color_modulate,
+                                &recent,
+                                &palette,
+                            );
+                        }
+                        color_palette::named_palettes(ui, &mut palette);
+
                         // Small buttons with color history, pins, and palettes.
                         let palette_response = color_palette::ColorPalette::new(
                             &mut brush.color_modulate,
                             &mut palette,
                         )
                         .scope(color_palette::HistoryScope::Local)
-                        .in_flux(self.picker_in_flux)
+                        .in_flux(self.picker_in_flux || numeric_in_flux)
                         .id_source(current_doc)
-                        .swap(
-                            // Swap top colors if requested.
-                            actions.action_trigger_count(crate::actions::Action::ColorSwap) % 2
-                                == 1,
-                        )
                         .max_history(64)
                         .show(ui);
 
@@ -928,7 +2017,12 @@ impl MainUI {
                     });
                 });
             }
-
+        }
+    }
+    fn brush_panel(&mut self, ui: &mut Ui) {
+        let mut globals = crate::AdHocGlobals::get().write();
+        if let Some(globals) = globals.as_mut() {
+            let brush = &mut globals.brush;
             ui.horizontal(|ui| {
                 ui.label("Brush");
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -943,44 +2037,43 @@ impl MainUI {
             ui.selectable_value(&mut brush.brush.0[0], 0, "Test brush A");
             ui.selectable_value(&mut brush.brush.0[0], 1, "Test brush B");
 
+            // Stored in pixels, shown in the preferred unit.
+            let unit = crate::global::preferences::Preferences::read().units;
+            let resolution = document_resolution(self.cur_document);
+            let to_unit = |px: f32| unit.of(Length::Logical(px), resolution);
+            let to_px = |value: f32| unit.length(value).into_logical(resolution);
+            let decimals = unit.decimals().max(2);
+
             let mut size_mul = brush.size_mul.get();
             let mut spacing_px = brush.spacing_px.get();
-            // Apply size up/down actions
-            // - for down, + for up
-            'size_steps: {
-                let size_steps = actions
-                    .action_trigger_count(crate::actions::Action::BrushSizeUp)
-                    .saturating_as::<i32>()
-                    .saturating_sub(
-                        actions
-                            .action_trigger_count(crate::actions::Action::BrushSizeDown)
-                            .saturating_as(),
-                    );
-                if size_steps == 0 {
-                    break 'size_steps;
-                }
-                // Usually editors supply some kind of snapping here to snap to
-                // common values instead. Todo!
-                let factor = 2.0f32.powf(size_steps as f32 / 4.0);
-                size_mul *= factor;
-                spacing_px *= factor;
-            }
-            ui.add(
-                egui::Slider::new(&mut spacing_px, 0.25..=10.0)
-                    .text("Spacing")
-                    .suffix("px")
-                    .max_decimals(2)
-                    .clamp_to_range(false),
-            );
+            let mut spacing = to_unit(spacing_px);
+            if ui
+                .add(
+                    egui::Slider::new(&mut spacing, to_unit(0.25)..=to_unit(10.0))
+                        .text("Spacing")
+                        .suffix(unit.suffix())
+                        .max_decimals(decimals)
+                        .clamp_to_range(false),
+                )
+                .changed()
+            {
+                spacing_px = to_px(spacing);
+            }
             // Prevent negative
             spacing_px = spacing_px.max(0.1);
-            ui.add(
-                egui::Slider::new(&mut size_mul, spacing_px..=50.0)
-                    .text("Size")
-                    .suffix("px")
-                    .max_decimals(2)
-                    .clamp_to_range(false),
-            );
+            let mut size = to_unit(size_mul);
+            if ui
+                .add(
+                    egui::Slider::new(&mut size, to_unit(spacing_px)..=to_unit(50.0))
+                        .text("Size")
+                        .suffix(unit.suffix())
+                        .max_decimals(decimals)
+                        .clamp_to_range(false),
+                )
+                .changed()
+            {
+                size_mul = to_px(size);
+            }
             // Prevent negative
             size_mul = size_mul.max(0.1);
 
@@ -990,42 +2083,420 @@ impl MainUI {
             if let Ok(spacing_px) = FiniteF32::new(spacing_px) {
                 brush.spacing_px = spacing_px;
             }
+            ui.checkbox(&mut brush.is_smudge, "Smudge").on_hover_text(
+                "Drag the layer's colors along the stroke. Opacity sets the strength.",
+            );
+            egui::ComboBox::from_label("Orientation")
+                .selected_text(brush.orientation.as_ref())
+                .show_ui(ui, |ui| {
+                    for orientation in <state::StampOrientation as strum::IntoEnumIterator>::iter()
+                    {
+                        ui.selectable_value(
+                            &mut brush.orientation,
+                            orientation,
+                            orientation.as_ref(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("Which way each stamp of the brush is turned");
+            egui::CollapsingHeader::new("Scatter").show(ui, |ui| {
+                let scatter = &mut brush.scatter;
+                ui.add(
+                    egui::Slider::new(&mut scatter.position, state::Scatter::POSITION_RANGE)
+                        .text("Position"),
+                )
+                .on_hover_text("Furthest each stamp strays from the stroke, in diameters");
+                ui.add(egui::Slider::new(&mut scatter.size, 0.0..=1.0).text("Size"))
+                    .on_hover_text("Most each stamp shrinks");
+                let mut degrees = scatter.angle.to_degrees();
+                if ui
+                    .add(
+                        egui::Slider::new(&mut degrees, 0.0..=180.0)
+                            .text("Angle")
+                            .suffix("°"),
+                    )
+                    .on_hover_text("Furthest each stamp turns either way")
+                    .changed()
+                {
+                    scatter.angle = degrees.to_radians();
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut enabled = brush.dual.is_some();
+                ui.checkbox(&mut enabled, "Dual")
+                    .on_hover_text("Multiply a second brush texture into every stamp");
+                if enabled != brush.dual.is_some() {
+                    brush.dual = enabled.then_some(state::DualStamp {
+                        brush: fuzzpaint_core::brush::UniqueID([0; 32]),
+                        scale: 0.5,
+                    });
+                }
+                if let Some(dual) = &mut brush.dual {
+                    ui.selectable_value(&mut dual.brush.0[0], 0, "A");
+                    ui.selectable_value(&mut dual.brush.0[0], 1, "B");
+                    ui.add(
+                        egui::Slider::new(&mut dual.scale, state::DualStamp::SCALE_RANGE)
+                            .text("Scale")
+                            .logarithmic(true),
+                    )
+                    .on_hover_text("Size of the second texture relative to the stamp");
+                }
+            });
+            egui::CollapsingHeader::new("Taper").show(ui, |ui| {
+                let taper = &mut brush.taper;
+                for (length, label, hover) in [
+                    (
+                        &mut taper.start,
+                        "Start",
+                        "Length over which strokes thicken from their start",
+                    ),
+                    (
+                        &mut taper.end,
+                        "End",
+                        "Length over which strokes thin to their end",
+                    ),
+                ] {
+                    let mut value = to_unit(*length);
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut value, 0.0..=to_unit(200.0))
+                                .text(label)
+                                .suffix(unit.suffix())
+                                .max_decimals(decimals),
+                        )
+                        .on_hover_text(hover)
+                        .changed()
+                    {
+                        *length = to_px(value);
+                    }
+                }
+                ui.add(egui::Slider::new(&mut taper.velocity, 0.0..=1.0).text("Velocity"))
+                    .on_hover_text("How much strokes thin where they're drawn quickly");
+                *taper = taper.sanitized();
+            });
+            egui::CollapsingHeader::new("Dynamics").show(ui, |ui| {
+                let dynamics = &mut brush.dynamics;
+                for (response, label) in [
+                    (&mut dynamics.size, "Size"),
+                    (&mut dynamics.flow, "Flow"),
+                    (&mut dynamics.opacity, "Opacity"),
+                ] {
+                    egui::ComboBox::from_label(label)
+                        .selected_text(response.input.as_ref())
+                        .show_ui(ui, |ui| {
+                            for input in
+                                <state::DynamicsInput as strum::IntoEnumIterator>::iter()
+                            {
+                                ui.selectable_value(&mut response.input, input, input.as_ref());
+                            }
+                        })
+                        .response
+                        .on_hover_text("What it follows as the stroke is drawn");
+                    if response.input != state::DynamicsInput::None {
+                        ui.indent(label, |ui| {
+                            ui.add(egui::Slider::new(&mut response.min, 0.0..=1.0).text("Min"))
+                                .on_hover_text("How much is left with no input");
+                            ui.add(
+                                egui::Slider::new(
+                                    &mut response.gamma,
+                                    state::Response::GAMMA_RANGE,
+                                )
+                                .text("Gamma")
+                                .logarithmic(true),
+                            )
+                            .on_hover_text(
+                                "Above one, it responds mostly towards full input. Below one, towards none.",
+                            );
+                        });
+                    }
+                }
+                *dynamics = dynamics.sanitized();
+            });
+
+            egui::ComboBox::from_label("Eraser tip")
+                .selected_text(globals.eraser_tip.as_ref())
+                .show_ui(ui, |ui| {
+                    for mode in <crate::pen_tools::EraserTipMode as strum::IntoEnumIterator>::iter()
+                    {
+                        ui.selectable_value(&mut globals.eraser_tip, mode, mode.as_ref());
+                    }
+                })
+                .response
+                .on_hover_text("What strokes made with the stylus' eraser end do");
+
+            ui.separator();
+            brush_ui::presets(ui, &mut globals.brush);
+        }
+    }
+}
+/// Toggle the developer [visualizations](crate::diagnostics::Visualizations).
+fn visualizations_menu(ui: &mut Ui) {
+    use crate::diagnostics::Visualizations;
+    let mut visualizations = crate::diagnostics::visualizations();
+    let mut changed = false;
+    for (flag, id) in [
+        (Visualizations::WIREFRAME, "menu-view-visualize-wireframe"),
+        (Visualizations::TILE_BOUNDARIES, "menu-view-visualize-tiles"),
+        (Visualizations::OVERDRAW, "menu-view-visualize-overdraw"),
+    ] {
+        let mut shown = visualizations.contains(flag);
+        if ui
+            .checkbox(&mut shown, tr!(id))
+            .on_hover_text(tr!(&format!("{id}.hover")))
+            .changed()
+        {
+            visualizations.set(flag, shown);
+            changed = true;
+        }
+    }
+    if changed {
+        crate::diagnostics::set_visualizations(visualizations);
+    }
+}
+/// The resolution at which the document's pixels are converted to physical units, or the default for a new
+/// document if there's none.
+fn document_resolution(document: Option<state::document::ID>) -> Resolution {
+    document
+        .and_then(|document| {
+            crate::global::provider().inspect(document, |queue| {
+                queue.peek_clone_state().document().viewport.resolution
+            })
+        })
+        .unwrap_or_else(|| state::document::Viewport::default().resolution)
+}
+/// Edit a length stored in pixels, shown in `unit`. Dragging moves it by a pixel per point.
+fn length_drag_value(
+    ui: &mut Ui,
+    px: &mut f32,
+    unit: Unit,
+    resolution: Resolution,
+) -> egui::Response {
+    let mut value = unit.of(Length::Logical(*px), resolution);
+    let response = ui.add(
+        egui::DragValue::new(&mut value)
+            .speed(unit.of(Length::Logical(1.0), resolution))
+            .suffix(unit.suffix())
+            .max_decimals(unit.decimals()),
+    );
+    // Only when changed, so the round trip doesn't creep.
+    if response.changed() {
+        *px = unit.length(value).into_logical(resolution);
+    }
+    response
+}
+/// Choose and toggle the document's [ruler](fuzzpaint_core::ruler). Its geometry is edited with the ruler tool.
+fn ruler_menu(ui: &mut Ui, document: fuzzpaint_core::state::document::ID) {
+    use crate::global::rulers;
+    use fuzzpaint_core::ruler::Ruler;
+    let current = rulers::get(document);
+    let mut enabled = current.is_some_and(|current| current.enabled);
+    if ui
+        .add_enabled(
+            current.is_some(),
+            egui::Checkbox::new(&mut enabled, "Enabled"),
+        )
+        .on_hover_text("Keep strokes along the ruler's guides")
+        .changed()
+    {
+        if let Some(current) = current {
+            rulers::set(document, rulers::Rulers { enabled, ..current });
+        }
+    }
+    ui.separator();
+    // Keep the handle where it was, or start in the middle of the canvas.
+    let handle = current.map_or_else(
+        || {
+            let canvas = crate::global::provider()
+                .inspect(document, |queue| {
+                    queue.peek_clone_state().document().viewport.canvas()
+                })
+                .unwrap_or_default();
+            let [min, max] = canvas.corners();
+            [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0]
+        },
+        |current| current.ruler.handle(),
+    );
+    let kinds = [
+        (
+            "Parallel",
+            Ruler::Parallel {
+                origin: handle,
+                angle: 0.0,
+            },
+        ),
+        (
+            "Perspective",
+            Ruler::Perspective {
+                vanishing_point: handle,
+            },
+        ),
+        (
+            "Isometric",
+            Ruler::Isometric {
+                origin: handle,
+                angle: 0.0,
+            },
+        ),
+    ];
+    for (name, ruler) in kinds {
+        let selected = current.is_some_and(|current| {
+            std::mem::discriminant(&current.ruler) == std::mem::discriminant(&ruler)
+        });
+        if ui.radio(selected, name).clicked() && !selected {
+            rulers::set(
+                document,
+                rulers::Rulers {
+                    ruler,
+                    enabled: true,
+                },
+            );
+        }
+    }
+    if let Some(mut current) = current {
+        ui.separator();
+        let unit = crate::global::preferences::Preferences::read().units;
+        let resolution = document_resolution(Some(document));
+        let mut changed = false;
+        let [x, y] = current.ruler.handle_mut();
+        ui.horizontal(|ui| {
+            ui.label("X");
+            changed |= length_drag_value(ui, x, unit, resolution).changed();
+            ui.label("Y");
+            changed |= length_drag_value(ui, y, unit, resolution).changed();
+        });
+        if let Some(angle) = current.ruler.angle_mut() {
+            changed |= ui.drag_angle(angle).changed();
+        }
+        if changed {
+            rulers::set(document, current);
         }
     }
+    ui.separator();
+    if ui
+        .add_enabled(current.is_some(), egui::Button::new("Remove"))
+        .clicked()
+    {
+        rulers::remove(document);
+        ui.close_menu();
+    }
 }
 /// For any tool, `(icon string, tooltip, opt_hotkey)`
+/// The icon of the tool, the Fluent id of its name, and the action that switches to it if any.
 fn tool_button_for(
     tool: crate::pen_tools::StateLayer,
 ) -> (&'static str, &'static str, Option<crate::actions::Action>) {
     use crate::{actions::Action, pen_tools::StateLayer};
     match tool {
-        StateLayer::Brush => (STROKE_LAYER_ICON, "Brush", Some(Action::Brush)),
-        StateLayer::Picker => ("✒", "Picker", Some(Action::Picker)),
-        StateLayer::Gizmos => ("⌖", "Gizmos", Some(Action::Gizmo)),
-        StateLayer::Lasso => ("?", "Lasso", Some(Action::Lasso)),
+        StateLayer::Brush => (STROKE_LAYER_ICON, "tool-brush", Some(Action::Brush)),
+        StateLayer::Picker => ("✒", "tool-picker", Some(Action::Picker)),
+        StateLayer::Gizmos => ("⌖", "tool-gizmos", Some(Action::Gizmo)),
+        StateLayer::Lasso => ("?", "tool-lasso", Some(Action::Lasso)),
+        StateLayer::Ruler => ("📏", "tool-ruler", Some(Action::Ruler)),
+        StateLayer::Gradient => (GRADIENT_LAYER_ICON, "tool-gradient", None),
         // NO action for these! pen_tools takes care of it without latching.
         // TODO: that's a weird mixing of roles lol
-        StateLayer::Eraser => ("?", "Eraser", None),
-        StateLayer::ViewportPan => ("✋", "Pan View", None),
-        StateLayer::ViewportRotate => ("🔃", "Rotate View", None),
-        StateLayer::ViewportScrub => ("🔍", "Scrub View", None),
+        StateLayer::Eraser => ("?", "tool-eraser", None),
+        StateLayer::ViewportPan => ("✋", "tool-pan", None),
+        StateLayer::ViewportRotate => ("🔃", "tool-rotate", None),
+        StateLayer::ViewportScrub => ("🔍", "tool-scrub", None),
     }
 }
-fn tools_panel(
-    ui: &mut Ui,
-    action_frame: &crate::actions::ActionFrame,
-    requests: &crossbeam::channel::Sender<requests::UiRequest>,
-) {
+/// Tools shown in the toolbox, in rows.
+const TOOL_GROUPS: [&[crate::pen_tools::StateLayer]; 3] = {
     use crate::pen_tools::StateLayer;
-    const TOOL_GROUPS: [&[StateLayer]; 3] = [
+    [
         &[StateLayer::Brush, StateLayer::Eraser, StateLayer::Picker],
-        &[StateLayer::Lasso, StateLayer::Gizmos],
+        &[
+            StateLayer::Lasso,
+            StateLayer::Ruler,
+            StateLayer::Gradient,
+            StateLayer::Gizmos,
+        ],
         &[
             StateLayer::ViewportPan,
             StateLayer::ViewportRotate,
             StateLayer::ViewportScrub,
         ],
-    ];
+    ]
+};
+/// The first keyboard hotkey bound to the action, if any.
+fn hotkey_text(action: crate::actions::Action) -> Option<String> {
+    crate::global::hotkeys::Hotkeys::read()
+        .actions_to_keys
+        .0
+        .get(&action)?
+        .keyboard
+        .first()
+        .map(crate::actions::hotkeys::KeyboardHotkey::to_string)
+}
+/// The eyedropper's sample in sRGB, beside its ring around the pen.
+fn picker_readout(ctx: &egui::Context) {
+    let Some(readout) = crate::pen_tools::picker::readout() else {
+        return;
+    };
+    let [r, g, b, a] = readout.color;
+    let [r, g, b, a] = egui::Rgba::from_rgba_premultiplied(r, g, b, a).to_srgba_unmultiplied();
+    // Physical to logical, then clear of the ring.
+    let pixels_per_point = ctx.pixels_per_point();
+    let pos = egui::pos2(
+        readout.pos[0] / pixels_per_point + 48.0,
+        readout.pos[1] / pixels_per_point + 16.0,
+    );
+    egui::Area::new(egui::Id::new("picker-readout"))
+        .fixed_pos(pos)
+        .order(egui::Order::Tooltip)
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(egui::RichText::new(format!("R {r:>3}  G {g:>3}  B {b:>3}")).monospace());
+                if a < 255 {
+                    ui.label(egui::RichText::new(format!("A {a:>3}")).monospace());
+                }
+            });
+        });
+}
+/// A line of the keys that may be held to change what the tool does.
+fn hint_bar(ui: &mut Ui, tool: crate::pen_tools::StateLayer) {
+    let (_, name, _) = tool_button_for(tool);
+    ui.horizontal_wrapped(|ui| {
+        ui.label(RichText::new(tr!(name)).strong());
+        for &action in tool.modifiers() {
+            let Some(key) = hotkey_text(action) else {
+                continue;
+            };
+            ui.separator();
+            ui.label(RichText::new(key).monospace());
+            ui.label(RichText::new(action.name()).weak());
+        }
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if let Some(key) = hotkey_text(crate::actions::Action::CommandPalette) {
+                ui.label(RichText::new(tr!("hint-all-commands", key = key)).weak());
+            }
+        });
+    });
+}
+/// Switch tools by hotkey, for when no [`tools_panel`] is shown to do it. Returns the tool switched to, if any.
+fn tool_hotkeys(
+    action_frame: &crate::actions::ActionFrame,
+    requests: &crossbeam::channel::Sender<requests::UiRequest>,
+) -> Option<crate::pen_tools::StateLayer> {
+    let mut chosen = None;
+    for &tool in TOOL_GROUPS.iter().copied().flatten() {
+        let (_, _, opt_action) = tool_button_for(tool);
+        if opt_action.is_some_and(|action| action_frame.action_trigger_count(action) > 0) {
+            let _ = requests.send(requests::UiRequest::SetBaseTool { tool });
+            chosen = Some(tool);
+        }
+    }
+    chosen
+}
+/// Returns the tool switched to, if any.
+fn tools_panel(
+    ui: &mut Ui,
+    action_frame: &crate::actions::ActionFrame,
+    requests: &crossbeam::channel::Sender<requests::UiRequest>,
+) -> Option<crate::pen_tools::StateLayer> {
     // size, grows to justify
     const BTN_BASE_SIZE: f32 = 20.0;
     const ICON_SIZE: f32 = 15.0;
@@ -1041,6 +2512,7 @@ fn tools_panel(
     let font_height = ICON_SIZE / ui.ctx().pixels_per_point();
     let font = egui::FontId::monospace(font_height);
 
+    let mut chosen = None;
     for tool_group in TOOL_GROUPS {
         ui.horizontal_wrapped(|ui| {
             for &tool in tool_group {
@@ -1049,7 +2521,7 @@ fn tools_panel(
                 let button = egui::Button::new(egui::RichText::new(icon).font(font.clone()))
                     .min_size(egui::Vec2::splat(button_size));
                 // Add button. Trigger if button clicked or action occured.
-                let response = ui.add(button).on_hover_text(tooltip);
+                let response = ui.add(button).on_hover_text(tr!(tooltip));
                 let response = if let Some(action) = opt_action {
                     response.or_action_clicked(action_frame, action)
                 } else {
@@ -1057,10 +2529,12 @@ fn tools_panel(
                 };
                 if response.clicked() {
                     let _ = requests.send(requests::UiRequest::SetBaseTool { tool });
+                    chosen = Some(tool);
                 }
             }
         });
     }
+    chosen
 }
 /// Edit a leaf layer's data. If modifications were made that should be pushed to the queue,
 /// `true` is returned.
@@ -1075,6 +2549,15 @@ fn leaf_props_panel(
     let write = match leaf {
         // Nothing to show
         LeafType::Note => false,
+        LeafType::Image { .. } => {
+            // Nothing interactible
+            ui.label(
+                egui::RichText::new(tr!("layer-raster-image"))
+                    .italics()
+                    .weak(),
+            );
+            false
+        }
         // Color picker
         LeafType::SolidColor { source, .. } => {
             let mut globals = crate::AdHocGlobals::get().write();
@@ -1093,7 +2576,7 @@ fn leaf_props_panel(
                             ..Default::default()
                         },
                     )
-                    .on_hover_text("Use this color")
+                    .on_hover_text(tr!("layer-use-color"))
                     .clicked()
                 {
                     match &mut current_color {
@@ -1102,11 +2585,11 @@ fn leaf_props_panel(
                         None => unreachable!(),
                     }
                 }
-                ui.label("Fill color");
+                ui.label(tr!("layer-fill-color"));
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if ui
-                        .button("Replace")
-                        .on_hover_text("Replace layer color with active color")
+                        .button(tr!("layer-replace-color"))
+                        .on_hover_text(tr!("layer-replace-color.hover"))
                         .clicked()
                     {
                         match current_color {
@@ -1123,6 +2606,13 @@ fn leaf_props_panel(
             })
             .inner
         }
+        LeafType::Gradient { gradient, .. } => match gradient_props(ui, leaf_id, gradient) {
+            Some(new) => {
+                *gradient = new;
+                true
+            }
+            None => false,
+        },
         LeafType::StrokeLayer {
             collection,
             inner_transform,
@@ -1131,12 +2621,12 @@ fn leaf_props_panel(
         } => {
             // Nothing interactible, but display some infos
             ui.label(
-                egui::RichText::new(format!(
-                    "{} stroke items from {}",
-                    stroke_collections
+                egui::RichText::new(tr!(
+                    "layer-stroke-info",
+                    count = stroke_collections
                         .get(*collection)
                         .map_or(0, |collection| collection.strokes.len()),
-                    *collection,
+                    collection = collection.to_string(),
                 ))
                 .italics()
                 .weak(),
@@ -1202,8 +2692,10 @@ fn layer_buttons(
             Stroke,
             Text,
             Fill,
+            Gradient,
             Note,
             Group,
+            Filter,
         }
         let new_layer_button = egui::ComboBox::from_id_source("layer-add")
             .selected_text(PLUS_ICON.to_string())
@@ -1214,7 +2706,9 @@ fn layer_buttons(
                 if ui
                     // A bit of an abuse of shortcut text. Screenreaders hate her!
                     // Unsure of how to better achieve the effect...
-                    .add(egui::Button::new("Stroke Layer").shortcut_text(STROKE_LAYER_ICON))
+                    .add(
+                        egui::Button::new(tr!("layer-new-stroke")).shortcut_text(STROKE_LAYER_ICON),
+                    )
                     .clicked()
                 {
                     selection = Some(NewLayerType::Stroke);
@@ -1222,36 +2716,51 @@ fn layer_buttons(
                 if ui
                     .add_enabled(
                         false,
-                        egui::Button::new("Text Layer").shortcut_text(TEXT_LAYER_ICON),
+                        egui::Button::new(tr!("layer-new-text")).shortcut_text(TEXT_LAYER_ICON),
                     )
                     .clicked()
                 {
                     selection = Some(NewLayerType::Text);
                 }
                 if ui
-                    .add(egui::Button::new("Fill Layer").shortcut_text(FILL_LAYER_ICON))
+                    .add(egui::Button::new(tr!("layer-new-fill")).shortcut_text(FILL_LAYER_ICON))
                     .clicked()
                 {
                     selection = Some(NewLayerType::Fill);
                 }
                 if ui
-                    .add(egui::Button::new("Note").shortcut_text(NOTE_LAYER_ICON))
+                    .add(
+                        egui::Button::new(tr!("layer-new-gradient"))
+                            .shortcut_text(GRADIENT_LAYER_ICON),
+                    )
+                    .clicked()
+                {
+                    selection = Some(NewLayerType::Gradient);
+                }
+                if ui
+                    .add(egui::Button::new(tr!("layer-new-note")).shortcut_text(NOTE_LAYER_ICON))
                     .clicked()
                 {
                     selection = Some(NewLayerType::Note);
                 }
                 ui.separator();
                 if ui
-                    .add(egui::Button::new("Blend Group").shortcut_text(GROUP_ICON))
+                    .add(egui::Button::new(tr!("layer-new-group")).shortcut_text(GROUP_ICON))
                     .clicked()
                 {
                     selection = Some(NewLayerType::Group);
                 }
+                if ui
+                    .add(egui::Button::new(tr!("layer-new-filter")).shortcut_text(FILTER_ICON))
+                    .clicked()
+                {
+                    selection = Some(NewLayerType::Filter);
+                }
 
                 selection
             });
 
-        new_layer_button.response.on_hover_text("Add layer");
+        new_layer_button.response.on_hover_text(tr!("layer-new"));
 
         // Option<Option<...>>, but we only care when Some(Some(...))
         let new_layer = new_layer_button.inner.flatten();
@@ -1269,6 +2778,7 @@ fn layer_buttons(
                     None => state::graph::Location::IndexIntoRoot(0),
                 },
             };
+            interface.graph_also_selected.clear();
             interface.graph_selection = match new_layer {
                 NewLayerType::Stroke => {
                     let new_stroke_collection = writer.stroke_collections().insert();
@@ -1282,7 +2792,7 @@ fn layer_buttons(
                                 outer_transform: state::transform::Matrix::default(),
                             },
                             addition_location,
-                            "Stroke Layer".to_string(),
+                            tr!("layer-name-stroke"),
                         )
                         .ok()
                         .map(Into::into)
@@ -1295,10 +2805,33 @@ fn layer_buttons(
                             source: fcolor::ColorOrPalette::WHITE,
                         },
                         addition_location,
-                        "Fill".to_string(),
+                        tr!("layer-name-fill"),
                     )
                     .ok()
                     .map(Into::into),
+                NewLayerType::Gradient => {
+                    // Across the middle of the canvas, place it with the gradient tool.
+                    let [min, max] = writer.document().viewport.canvas().corners();
+                    let middle = (min[1] + max[1]) / 2.0;
+                    writer
+                        .graph()
+                        .add_leaf(
+                            state::graph::LeafType::Gradient {
+                                blend: Blend::default(),
+                                gradient: fuzzpaint_core::gradient::Gradient::new(
+                                    fuzzpaint_core::gradient::GradientShape::Linear,
+                                    [min[0], middle],
+                                    [max[0], middle],
+                                    fcolor::ColorOrPalette::BLACK,
+                                    fcolor::ColorOrPalette::WHITE,
+                                ),
+                            },
+                            addition_location,
+                            tr!("layer-name-gradient"),
+                        )
+                        .ok()
+                        .map(Into::into)
+                }
                 NewLayerType::Text => writer
                     .graph()
                     .add_leaf(
@@ -1309,7 +2842,7 @@ fn layer_buttons(
                             outer_transform: state::transform::Matrix::default(),
                         },
                         addition_location,
-                        "Text".to_string(),
+                        tr!("layer-name-text"),
                     )
                     .ok()
                     .map(Into::into),
@@ -1318,7 +2851,7 @@ fn layer_buttons(
                     .add_leaf(
                         state::graph::LeafType::Note,
                         addition_location,
-                        "Note".to_string(),
+                        tr!("layer-name-note"),
                     )
                     .ok()
                     .map(Into::into),
@@ -1327,7 +2860,19 @@ fn layer_buttons(
                     .add_node(
                         state::graph::NodeType::GroupedBlend(Blend::default()),
                         addition_location,
-                        "Group".to_string(),
+                        tr!("layer-name-group"),
+                    )
+                    .ok()
+                    .map(Into::into),
+                NewLayerType::Filter => writer
+                    .graph()
+                    .add_node(
+                        state::graph::NodeType::Filtered {
+                            blend: Blend::default(),
+                            filter: fuzzpaint_core::filter::Filter::default(),
+                        },
+                        addition_location,
+                        tr!("layer-name-filter"),
                     )
                     .ok()
                     .map(Into::into),
@@ -1340,28 +2885,305 @@ fn layer_buttons(
 
         let merge_button = egui::Button::new("⤵");
         ui.add_enabled(false, merge_button)
-            .on_hover_text("Merge down");
+            .on_hover_text(tr!("layer-merge-down"));
+
+        if ui
+            .add_enabled(interface.graph_selection.is_some(), egui::Button::new("✖"))
+            .on_hover_text(tr!("layer-delete"))
+            .clicked()
+        {
+            // Explicitly ignore error.
+            let _ = graph.delete_many(&interface.selected_nodes());
+            interface.graph_selection = None;
+            interface.graph_also_selected.clear();
+        };
+    });
+}
+/// A change to every selected layer at once.
+enum BulkChange {
+    Opacity(f32),
+    Mode(BlendMode),
+    Group,
+}
+/// Properties of several selected layers, changed all at once. Shows `blend`, that of the primary selection.
+fn bulk_props(ui: &mut Ui, count: usize, blend: Blend) -> Option<BulkChange> {
+    ui.label(tr!("layers-selected", count = count));
+    let mut change = None;
+    ui.horizontal(|ui| {
+        latch::latch(ui, "bulk-opacity", blend.opacity, |ui, opacity| {
+            let response = ui.add(
+                egui::DragValue::new(opacity)
+                    .fixed_decimals(2)
+                    .speed(0.01)
+                    .clamp_range(0.0..=1.0),
+            );
+            // do NOT report "finished" mid-drag, only when it's complete!
+            if response.drag_released() {
+                latch::Latch::Finish
+            } else if response.dragged() {
+                latch::Latch::Continue
+            } else {
+                latch::Latch::None
+            }
+        })
+        .on_finish(|opacity| change = Some(BulkChange::Opacity(opacity)));
 
+        egui::ComboBox::new("bulk-blend-mode", "")
+            .selected_text(blend.mode.as_ref())
+            .show_ui(ui, |ui| {
+                for blend_mode in <BlendMode as strum::IntoEnumIterator>::iter() {
+                    if ui
+                        .selectable_label(blend.mode == blend_mode, blend_mode.as_ref())
+                        .clicked()
+                    {
+                        change = Some(BulkChange::Mode(blend_mode));
+                    }
+                }
+            });
         if ui
-            .add_enabled(interface.graph_selection.is_some(), egui::Button::new("✖"))
-            .on_hover_text("Delete layer")
+            .button(format!("{GROUP_ICON} {}", tr!("layers-group")))
+            .on_hover_text(tr!("layers-group.hover"))
             .clicked()
         {
-            // Explicitly ignore error.
-            let _ = graph.delete(interface.graph_selection.unwrap());
-            interface.graph_selection = None;
-        };
+            change = Some(BulkChange::Group);
+        }
     });
+    change
 }
-/// Modify an inner transform, returning a new transform when a change is submitted.
+/// Modify a filter, returning a new filter when a change is submitted.
+/// Changes still in progress are passed to `preview`.
+fn filter_props(
+    ui: &mut Ui,
+    filter: fuzzpaint_core::filter::Filter,
+    preview: impl FnOnce(fuzzpaint_core::filter::Filter),
+) -> Option<fuzzpaint_core::filter::Filter> {
+    use fuzzpaint_core::filter::{Filter, FilterKind};
+    latch::latch(ui, ui.id().with("filter"), filter, |ui, filter| {
+        // Are interactions ongoing?
+        let mut active = false;
+        // Has anything changed?
+        let mut changed = false;
+
+        egui::ComboBox::from_label(tr!("layer-filter"))
+            .selected_text(filter.kind().as_ref())
+            .show_ui(ui, |ui| {
+                for kind in <FilterKind as strum::IntoEnumIterator>::iter() {
+                    let selected = filter.kind() == kind;
+                    if ui.selectable_label(selected, kind.as_ref()).clicked() && !selected {
+                        *filter = kind.identity();
+                        changed = true;
+                    }
+                }
+            });
+
+        let mut slider = |ui: &mut Ui,
+                          value: &mut f32,
+                          range: std::ops::RangeInclusive<f32>,
+                          text: &str,
+                          suffix: &str| {
+            let response = ui.add(egui::Slider::new(value, range).text(text).suffix(suffix));
+            // Try to derive a status from the response - this is just a heuristic, blegh.
+            active |= response.has_focus() | response.dragged();
+            changed |= response.changed() | response.lost_focus() || response.drag_released();
+        };
+        match filter {
+            Filter::GaussianBlur { radius } => {
+                slider(
+                    ui,
+                    radius,
+                    Filter::BLUR_RADIUS_RANGE,
+                    &tr!("layer-filter-radius"),
+                    "px",
+                );
+            }
+            Filter::BrightnessContrast {
+                brightness,
+                contrast,
+            } => {
+                slider(
+                    ui,
+                    brightness,
+                    Filter::ADJUST_RANGE,
+                    &tr!("layer-filter-brightness"),
+                    "",
+                );
+                slider(
+                    ui,
+                    contrast,
+                    Filter::ADJUST_RANGE,
+                    &tr!("layer-filter-contrast"),
+                    "",
+                );
+            }
+            Filter::HueSaturation {
+                hue,
+                saturation,
+                lightness,
+            } => {
+                slider(ui, hue, Filter::HUE_RANGE, &tr!("layer-filter-hue"), "°");
+                slider(
+                    ui,
+                    saturation,
+                    Filter::ADJUST_RANGE,
+                    &tr!("layer-filter-saturation"),
+                    "",
+                );
+                slider(
+                    ui,
+                    lightness,
+                    Filter::ADJUST_RANGE,
+                    &tr!("layer-filter-lightness"),
+                    "",
+                );
+            }
+        }
+
+        if active {
+            preview(*filter);
+        }
+        match (changed, active) {
+            (_, true) => latch::Latch::Continue,
+            (true, false) => latch::Latch::Finish,
+            (false, false) => latch::Latch::None,
+        }
+    })
+    .result()
+}
+/// Modify a gradient's shape and stops, returning a new gradient when a change is submitted.
+/// Its endpoints are placed on the canvas, by the gradient tool.
+fn gradient_props(
+    ui: &mut Ui,
+    leaf_id: state::graph::LeafID,
+    gradient: &fuzzpaint_core::gradient::Gradient,
+) -> Option<fuzzpaint_core::gradient::Gradient> {
+    use fuzzpaint_core::gradient::{Gradient, GradientShape};
+    let mut globals = crate::AdHocGlobals::get().write();
+    let mut current_color = globals.as_mut().map(|g| &mut g.brush.color_modulate);
+
+    latch::latch(
+        ui,
+        (leaf_id, "gradient"),
+        gradient.clone(),
+        |ui, gradient| {
+            // Are interactions ongoing?
+            let mut active = false;
+            // Has anything changed?
+            let mut changed = false;
+
+            egui::ComboBox::from_label(tr!("layer-gradient-shape"))
+                .selected_text(gradient.shape.as_ref())
+                .show_ui(ui, |ui| {
+                    for shape in <GradientShape as strum::IntoEnumIterator>::iter() {
+                        changed |= ui
+                            .selectable_value(&mut gradient.shape, shape, shape.as_ref())
+                            .changed();
+                    }
+                });
+            ui.label(
+                egui::RichText::new(tr!("layer-gradient-place"))
+                    .italics()
+                    .weak(),
+            );
+
+            let can_remove = gradient.stops.len() > 1;
+            let mut remove = None;
+            for (idx, stop) in gradient.stops.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    // Add a preview button that also allows the user to select this color.
+                    if ui
+                        .add_enabled(
+                            current_color.is_some(),
+                            color_palette::ColorSquare {
+                                // Todo: Display paletted.
+                                color: stop.color.get().left_or(fcolor::Color::BLACK),
+                                icon: stop.color.is_palette().then_some(PALETTE_ICON),
+                                ..Default::default()
+                            },
+                        )
+                        .on_hover_text(tr!("layer-use-color"))
+                        .clicked()
+                    {
+                        if let Some(current) = &mut current_color {
+                            **current = stop.color;
+                        }
+                    }
+                    let response = ui.add(egui::Slider::new(&mut stop.position, 0.0..=1.0));
+                    // Try to derive a status from the response - this is just a heuristic, blegh.
+                    active |= response.has_focus() | response.dragged();
+                    changed |=
+                        response.changed() | response.lost_focus() || response.drag_released();
+                    if ui
+                        .add_enabled(
+                            current_color.is_some(),
+                            egui::Button::new(tr!("layer-replace-color")).small(),
+                        )
+                        .on_hover_text(tr!("layer-gradient-replace.hover"))
+                        .clicked()
+                    {
+                        if let Some(current) = &current_color {
+                            stop.color = **current;
+                            changed = true;
+                        }
+                    }
+                    if ui
+                        .add_enabled(can_remove, egui::Button::new("✖").small())
+                        .on_hover_text(tr!("layer-gradient-remove-stop"))
+                        .clicked()
+                    {
+                        remove = Some(idx);
+                    }
+                });
+            }
+            if let Some(idx) = remove {
+                gradient.stops.remove(idx);
+                changed = true;
+            }
+            if ui
+                .add_enabled(
+                    gradient.stops.len() < Gradient::MAX_STOPS,
+                    egui::Button::new(tr!("layer-gradient-add-stop")),
+                )
+                .clicked()
+            {
+                // Halfway across the widest gap.
+                let mut positions: Vec<_> =
+                    gradient.stops.iter().map(|stop| stop.position).collect();
+                positions.sort_by(f32::total_cmp);
+                let edges: Vec<_> = std::iter::once(0.0)
+                    .chain(positions)
+                    .chain(std::iter::once(1.0))
+                    .collect();
+                let position = edges
+                    .windows(2)
+                    .max_by(|a, b| (a[1] - a[0]).total_cmp(&(b[1] - b[0])))
+                    .map_or(0.5, |gap| (gap[0] + gap[1]) / 2.0);
+                gradient.insert_stop(position);
+                changed = true;
+            }
+
+            match (changed, active) {
+                (_, true) => latch::Latch::Continue,
+                (true, false) => latch::Latch::Finish,
+                (false, false) => latch::Latch::None,
+            }
+        },
+    )
+    .result()
+    .map(Gradient::clamped)
+}
+/// Modify an inner transform, returning a new transform when a change is submitted. Flips are about the middle
+/// of the canvas.
 fn inner_transform(
     ui: &mut Ui,
     inner: state::transform::Similarity,
+    canvas: state::document::Canvas,
 ) -> Option<state::transform::Similarity> {
     let reset = ui
         .horizontal(|ui| {
-            ui.label("Inner Transform");
-            ui.small_button(RESET_ICON).on_hover_text("Reset").clicked()
+            ui.label(tr!("layer-inner-transform"));
+            ui.small_button(RESET_ICON)
+                .on_hover_text(tr!("layer-transform-reset"))
+                .clicked()
         })
         .inner;
 
@@ -1382,7 +3204,7 @@ fn inner_transform(
 
             let response = ui.add(
                 egui::Slider::new(&mut scale, 0.01..=10.0)
-                    .text("Scale")
+                    .text(tr!("layer-transform-scale"))
                     .suffix("x")
                     .clamp_to_range(true)
                     .logarithmic(true),
@@ -1394,27 +3216,30 @@ fn inner_transform(
 
             ui.horizontal(|ui| {
                 let mut flip = inner.hflip();
-                let flip_changed = ui.checkbox(&mut flip, "Flip").changed();
+                let flip_changed = ui
+                    .checkbox(&mut flip, tr!("layer-transform-flip"))
+                    .changed();
                 changed |= flip_changed;
                 if flip_changed {
-                    // Mirror the origin around the x = DOCUMENT_SIZE / 2.0 line.
+                    // Mirror the origin around the middle of the canvas.
                     // Just a convinience since that's the most intuitive behavior, as opposed to
                     // the default confusing behavior of mirroring across the left edge.
                     // Since scale happens *before* translate, we don't need to worry about scale for this maths uwu
-                    inner.translation[0] = crate::DOCUMENT_DIMENSION as f32 - inner.translation[0];
+                    let [min, max] = canvas.corners();
+                    inner.translation[0] = min[0] + max[0] - inner.translation[0];
                 }
                 inner.set_hflip(flip);
 
                 ui.separator();
 
-                ui.label("Rotate:");
+                ui.label(tr!("layer-transform-rotate"));
                 let response = ui.drag_angle(&mut inner.rotation);
                 active |= response.has_focus() | response.dragged();
                 changed |= response.changed() | response.lost_focus() || response.drag_released();
             });
 
             ui.horizontal(|ui| {
-                ui.label("Position:");
+                ui.label(tr!("layer-transform-position"));
                 let response = ui.add(
                     egui::DragValue::new(&mut inner.translation[0])
                         .speed(1.0)
@@ -1448,8 +3273,10 @@ fn outer_transform(
 ) -> Option<state::transform::Matrix> {
     let reset = ui
         .horizontal(|ui| {
-            ui.label("Outer Transform");
-            ui.small_button(RESET_ICON).on_hover_text("Reset").clicked()
+            ui.label(tr!("layer-outer-transform"));
+            ui.small_button(RESET_ICON)
+                .on_hover_text(tr!("layer-transform-reset"))
+                .clicked()
         })
         .inner;
     ui.separator();
@@ -1470,7 +3297,7 @@ fn outer_transform(
             let mut scale = outer.elements[0][0];
             let response = ui.add(
                 egui::Slider::new(&mut scale, 0.01..=10.0)
-                    .text("Scale")
+                    .text(tr!("layer-transform-scale"))
                     .suffix("x")
                     .clamp_to_range(true)
                     .logarithmic(true),
@@ -1486,7 +3313,7 @@ fn outer_transform(
                 // Convert skew values to angles, then back. For ease of use!
                 // E.g., a "vertical" (how to unambiguously name skews?!?) skew of 45 deg means the
                 // local X axis is skewed vertically into a 45 deg angle with the global Y axis
-                ui.label("Skew:");
+                ui.label(tr!("layer-transform-skew"));
                 let mut skew_angle = (outer.elements[1][0] / original_scale).atan();
                 if !skew_angle.is_finite() {
                     skew_angle = 0.0;
@@ -1528,10 +3355,19 @@ fn outer_transform(
     .inner
 }
 /// Side panel showing layer add buttons, layer tree, and layer options
-fn layers_panel(ui: &mut Ui, interface: &mut PerDocumentData) {
-    crate::global::provider().inspect(interface.id, |queue| {
+fn layers_panel(
+    ui: &mut Ui,
+    interface: &mut PerDocumentData,
+    requests_send: &crossbeam::channel::Sender<requests::UiRequest>,
+) {
+    let document = interface.id;
+    crate::global::provider().write(document, "layers", |queue| {
         queue.write_with(|writer| {
             let graph = writer.graph();
+            // Forget those deleted or undone since.
+            interface
+                .graph_also_selected
+                .retain(|&id| graph.get(id).is_some_and(|data| !data.is_deleted()));
             // Node properties editor panel, at the bottom. Shown only when a node is selected.
             // Must occur before the graph rendering to prevent ui overflow :V
             let node_props = interface
@@ -1539,17 +3375,56 @@ fn layers_panel(ui: &mut Ui, interface: &mut PerDocumentData) {
                 // Ignore if there is a yanked node.
                 .and_then(|node| graph.get(node))
                 .cloned();
+            // With several selected, their shared properties are shown instead, starting from the primary's.
+            let bulk_blend = (!interface.graph_also_selected.is_empty()).then(|| {
+                node_props
+                    .as_ref()
+                    .and_then(state::graph::NodeData::blend)
+                    .unwrap_or_default()
+            });
 
+            egui::TopBottomPanel::bottom("BulkLayerProperties").show_animated_inside(
+                ui,
+                bulk_blend.is_some(),
+                |ui| {
+                    let count = interface.graph_also_selected.len() + 1;
+                    let Some(change) = bulk_props(ui, count, bulk_blend.unwrap_or_default()) else {
+                        return;
+                    };
+                    let selected = interface.selected_nodes();
+                    let mut graph = writer.graph();
+                    // Explicitly ignore errors, as with single layers.
+                    match change {
+                        BulkChange::Opacity(opacity) => {
+                            let _ =
+                                graph.change_blends(&selected, |blend| Blend { opacity, ..blend });
+                        }
+                        BulkChange::Mode(mode) => {
+                            let _ = graph.change_blends(&selected, |blend| Blend { mode, ..blend });
+                        }
+                        BulkChange::Group => {
+                            if let Ok(group) = graph.group(
+                                &selected,
+                                state::graph::NodeType::GroupedBlend(Blend::default()),
+                                tr!("layer-name-group"),
+                            ) {
+                                interface.graph_selection = Some(group.into());
+                                interface.graph_also_selected.clear();
+                            }
+                        }
+                    }
+                },
+            );
             egui::TopBottomPanel::bottom("LayerProperties").show_animated_inside(
                 ui,
-                node_props.is_some(),
+                node_props.is_some() && bulk_blend.is_none(),
                 |ui| {
                     // Unwraps OK - guarded by show condition.
                     let node_props = node_props.unwrap();
                     let id = interface.graph_selection.unwrap();
                     ui.horizontal(|ui| {
                         ui.label(egui::RichText::new(icon_of_node(&node_props)).monospace());
-                        ui.label(format!("{} properties", node_props.name()));
+                        ui.label(tr!("layer-properties", name = node_props.name()));
                     });
                     match id {
                         state::graph::AnyID::Leaf(leaf_id) => {
@@ -1559,7 +3434,8 @@ fn layers_panel(ui: &mut Ui, interface: &mut PerDocumentData) {
                                 unreachable!();
                             };
                             if let Some(xform) = leaf.inner_transform_mut() {
-                                if let Some(inner) = inner_transform(ui, *xform) {
+                                let canvas = writer.document().viewport.canvas();
+                                if let Some(inner) = inner_transform(ui, *xform, canvas) {
                                     let _ = writer.graph().set_inner_transform(leaf_id, inner);
                                 }
                             }
@@ -1577,8 +3453,26 @@ fn layers_panel(ui: &mut Ui, interface: &mut PerDocumentData) {
                                 let _ = writer.graph().set_leaf(leaf_id, leaf);
                             }
                         }
-                        state::graph::AnyID::Node(_n) => {
-                            // Todo!
+                        state::graph::AnyID::Node(node_id) => {
+                            if let Some(&state::graph::NodeType::Filtered { blend, filter }) =
+                                node_props.node()
+                            {
+                                let preview = |filter| {
+                                    let _ = requests_send.send(requests::UiRequest::Document {
+                                        target: document,
+                                        request: requests::DocumentRequest::PreviewFilter {
+                                            node: node_id,
+                                            filter,
+                                        },
+                                    });
+                                };
+                                if let Some(filter) = filter_props(ui, filter, preview) {
+                                    let _ = writer.graph().set_node(
+                                        node_id,
+                                        state::graph::NodeType::Filtered { blend, filter },
+                                    );
+                                }
+                            }
                         }
                     }
                 },
@@ -1599,18 +3493,54 @@ fn layers_panel(ui: &mut Ui, interface: &mut PerDocumentData) {
                         interface.graph_focused_subtree = None;
                     }
                     ui.label(
-                        egui::RichText::new(format!(
-                            "Subtree of {}",
-                            interface
+                        egui::RichText::new(tr!(
+                            "layers-subtree",
+                            name = interface
                                 .graph_focused_subtree
                                 .as_ref()
                                 .and_then(|subtree| graph.get(*subtree))
-                                .map_or("Unknown", |data| data.name())
+                                .map_or_else(
+                                    || tr!("layers-unknown"),
+                                    |data| data.name().to_owned()
+                                )
+                        ))
+                        .italics(),
+                    );
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label("🔍");
+                ui.add(
+                    egui::TextEdit::singleline(&mut interface.layer_search)
+                        .hint_text(tr!("layers-search")),
+                );
+                if !interface.layer_search.is_empty() && ui.small_button(RESET_ICON).clicked() {
+                    interface.layer_search.clear();
+                }
+            });
+            let old_solo = interface.solo;
+            if let Some(solo) = interface.solo {
+                ui.horizontal(|ui| {
+                    if ui
+                        .small_button("⬅")
+                        .on_hover_text(tr!("layers-show-everything"))
+                        .clicked()
+                    {
+                        interface.solo = None;
+                    }
+                    ui.label(
+                        egui::RichText::new(tr!(
+                            "layers-solo",
+                            name = graph.get(solo).map_or_else(
+                                || tr!("layers-unknown"),
+                                |data| data.name().to_owned()
+                            )
                         ))
                         .italics(),
                     );
                 });
             }
+            let search = interface.layer_search.to_lowercase();
             latch::latch(ui, "dnd-state", None, |ui, dnd_state| {
                 egui::ScrollArea::new([false, true])
                     .auto_shrink([false, true])
@@ -1620,8 +3550,11 @@ fn layers_panel(ui: &mut Ui, interface: &mut PerDocumentData) {
                             &mut graph,
                             interface.graph_focused_subtree,
                             &mut interface.graph_selection,
+                            &mut interface.graph_also_selected,
                             &mut interface.graph_focused_subtree,
+                            &mut interface.solo,
                             dnd_state,
+                            &search,
                         );
                     });
 
@@ -1661,9 +3594,353 @@ fn layers_panel(ui: &mut Ui, interface: &mut PerDocumentData) {
                         }
                     },
                 );
-            })
+            });
+            if interface.solo != old_solo {
+                let _ = requests_send.send(requests::UiRequest::Document {
+                    target: document,
+                    request: requests::DocumentRequest::Solo(interface.solo),
+                });
+            }
+        });
+    });
+}
+/// Statistics of a document, collected again only once its history moves.
+struct AboutStatistics {
+    document: state::document::ID,
+    listener: queue::DocumentCommandListener,
+    statistics: queue::state_reader::DocumentStatistics,
+}
+/// Panel summarizing the contents of the document.
+fn about_panel(
+    ui: &mut Ui,
+    current_doc: Option<state::document::ID>,
+    cache: &mut Option<AboutStatistics>,
+) {
+    ui.label("About this document");
+    ui.separator();
+    let Some(document) = current_doc else {
+        return;
+    };
+    let repo = crate::global::points();
+    // Any change at all may move the history counts, so there's no need to look closer at the deltas.
+    let stale = match cache {
+        Some(cache) if cache.document == document => cache
+            .listener
+            .forward_deltas()
+            .map_or(true, |deltas| !deltas.is_empty()),
+        _ => true,
+    };
+    if stale {
+        *cache = crate::global::provider().inspect(document, |queue| AboutStatistics {
+            document,
+            listener: queue.listen_from_now(),
+            statistics: queue.statistics(repo),
+        });
+    }
+    let Some(AboutStatistics { statistics, .. }) = cache.as_ref() else {
+        return;
+    };
+
+    egui::Grid::new("about-document")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Stroke layers");
+            ui.label(statistics.layers.len().to_string());
+            ui.end_row();
+            ui.label("Strokes");
+            ui.label(statistics.strokes.to_string());
+            ui.end_row();
+            ui.label("Points");
+            ui.label(statistics.points.to_string());
+            ui.end_row();
+            ui.label("Point data");
+            ui.label(human_bytes::human_bytes(statistics.point_bytes as f64));
+            ui.end_row();
+            if let Some((undos, redos)) = statistics.history {
+                ui.label("History");
+                ui.label(format!("{undos} undo, {redos} redo"));
+                ui.end_row();
+            }
         });
+
+    ui.collapsing("Layers", |ui| {
+        egui::Grid::new("about-document-layers")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Name");
+                ui.strong("Strokes");
+                ui.strong("Points");
+                ui.end_row();
+                for layer in &statistics.layers {
+                    ui.label(&layer.name);
+                    ui.label(layer.strokes.to_string());
+                    ui.label(layer.points.to_string());
+                    ui.end_row();
+                }
+            });
+    });
+    // The repository is shared by every open document.
+    let (usage, capacity) = repo.resident_usage();
+    ui.label(
+        RichText::new(format!(
+            "Point repository, all documents: {}/{}",
+            human_bytes::human_bytes(usage as f64),
+            human_bytes::human_bytes(capacity as f64),
+        ))
+        .weak(),
+    );
+}
+/// Panel listing the steps of the document's history, jumping to any that are clicked.
+fn history_panel(ui: &mut Ui, current_doc: Option<state::document::ID>) {
+    ui.horizontal(|ui| {
+        ui.label(HISTORY_ICON.to_string());
+        ui.label("History");
+    });
+    ui.separator();
+    let Some(document) = current_doc else {
+        return;
+    };
+    let Some((undos, redos)) =
+        crate::global::provider().inspect(document, queue::DocumentCommandQueue::history_depth)
+    else {
+        return;
+    };
+
+    let mut jump_to = None;
+    let row_height = ui.spacing().interact_size.y;
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, true])
+        .show_rows(ui, row_height, undos + redos + 1, |ui, steps| {
+            for step in steps {
+                let text = if step == 0 {
+                    RichText::new("Start")
+                } else {
+                    RichText::new(format!("Step {step}"))
+                };
+                // Steps that have been undone are dimmed.
+                let text = if step > undos { text.weak() } else { text };
+                if ui.selectable_label(step == undos, text).clicked() {
+                    jump_to = Some(step);
+                }
+            }
+        });
+    if let Some(step) = jump_to {
+        crate::global::provider().write(document, "history", |queue| match step.cmp(&undos) {
+            std::cmp::Ordering::Less => queue.undo_n(undos - step),
+            std::cmp::Ordering::Greater => queue.redo_n(step - undos),
+            std::cmp::Ordering::Equal => (),
+        });
+    }
+}
+/// Ask where to save a document of the given name, with the extension added if the user left it out.
+/// None if cancelled.
+fn ask_save_path(name: &str) -> Option<std::path::PathBuf> {
+    // Synchronous and bad just for now.
+    let mut path = rfd::FileDialog::new()
+        .add_filter("Fuzzpaint document", &["fzp"])
+        .set_file_name(format!("{name}.fzp"))
+        .save_file()?;
+    if path.extension().is_none() {
+        path.set_extension("fzp");
+    }
+    Some(path)
+}
+/// The file the document was opened from or last saved to, None if untitled or not found.
+fn document_path(document: state::document::ID) -> Option<std::path::PathBuf> {
+    crate::global::provider()
+        .inspect(document, |queue| {
+            queue.peek_clone_state().document().path.clone()
+        })
+        .flatten()
+}
+/// Write the document to the file it was opened from and mark it as saved. Blocks until the write is complete.
+/// Fails if the document is read-only, as the file may be open elsewhere.
+fn save_document(document: state::document::ID) -> anyhow::Result<()> {
+    // Dirty testing implementation! New documents are always written to the same file.
+    let path = match document_path(document) {
+        Some(path) => path,
+        None => dirs::document_dir()
+            .ok_or_else(|| anyhow::anyhow!("no document directory"))?
+            .join("temp.fzp"),
+    };
+    save_document_to(document, &path)
+}
+/// Write the document to `path` and mark it as saved, like [`save_document`].
+fn save_document_to(document: state::document::ID, path: &std::path::Path) -> anyhow::Result<()> {
+    let read_only = crate::global::provider()
+        .inspect(document, queue::DocumentCommandQueue::is_read_only)
+        .ok_or_else(|| anyhow::anyhow!("document not found"))?;
+    if read_only {
+        anyhow::bail!("the document is read-only, use Save as to edit a copy");
+    }
+    let (backups, history) = {
+        let preferences = crate::global::preferences::Preferences::read();
+        (preferences.backups, preferences.saved_history)
+    };
+    let (reader, ids) = write_document(document, path, backups, history)?;
+    // The state that was written, even if more changes have been made since.
+    let provider = crate::global::provider();
+    provider.write(document, "save", |queue| {
+        queue.mark_saved(&reader);
+        // Changes from here on are journaled on top of the new save.
+        if let Some(ids) = ids {
+            crate::global::journals::start(queue, path, ids);
+        }
     });
+    // Not a command, so listeners won't otherwise hear of it.
+    provider.touch(document);
+    Ok(())
+}
+/// A new directory to write recovery copies into, named for the time so that earlier copies aren't overwritten.
+fn recovery_directory() -> anyhow::Result<std::path::PathBuf> {
+    let mut path = dirs::document_dir().ok_or_else(|| anyhow::anyhow!("no document directory"))?;
+    path.push("fuzzpaint recovery");
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    path.push(since_epoch.as_secs().to_string());
+    std::fs::create_dir_all(&path)?;
+    Ok(path)
+}
+/// Write a copy of each of the `(document, name)`s into a new recovery directory, without marking them saved,
+/// returning the directory. Documents that fail to write are reported, and the rest are still written.
+pub fn save_recovery_copies(
+    documents: &[(state::document::ID, String)],
+) -> anyhow::Result<std::path::PathBuf> {
+    let directory = recovery_directory()?;
+    for (idx, (document, name)) in documents.iter().enumerate() {
+        // Names needn't be unique, nor valid paths.
+        let file_name: String = name
+            .chars()
+            .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+            .collect();
+        let path = directory.join(format!("{idx} {file_name}.fzp"));
+        // Only the present, recovery copies should be as quick and simple as possible.
+        if let Err(e) = write_document(*document, &path, 0, 0) {
+            crate::errors::Report::new(
+                crate::errors::Severity::DataLoss,
+                tr!("recovery-save-failed", name = name.as_str()),
+                &e,
+            )
+            .send();
+        }
+    }
+    tracing::info!(?directory, "saved recovery copies");
+    Ok(directory)
+}
+/// Write the document's present state to `path` along with up to `history` steps of undo and redo, returning
+/// the state that was written and the IDs its history was written with. The previous file is replaced only once the write succeeds, with up to
+/// `backups` previous versions kept beside it.
+fn write_document(
+    document: state::document::ID,
+    path: &std::path::Path,
+    backups: usize,
+    history: usize,
+) -> anyhow::Result<(
+    queue::state_reader::CommandQueueCloneLock,
+    Option<io::history::FileIds>,
+)> {
+    let (reader, history) = crate::global::provider()
+        .inspect(document, |queue| queue.peek_history(history))
+        .ok_or_else(|| anyhow::anyhow!("document not found"))??;
+    let repo = crate::global::points();
+
+    let start = std::time::Instant::now();
+    let (size, ids) = io::safe_save::save(path, backups, |file| -> anyhow::Result<_> {
+        let ids = io::write_into(&reader, Some(&history), repo, file, path.parent())?;
+        Ok((file.metadata().ok().map(|meta| meta.len()), ids))
+    })?;
+    let duration = start.elapsed();
+
+    if let Some(size) = size {
+        let size = size as f64;
+        let speed = size / duration.as_secs_f64();
+        tracing::info!(
+            "Wrote {} in {}us ({}/s)",
+            human_bytes::human_bytes(size),
+            duration.as_micros(),
+            human_bytes::human_bytes(speed)
+        );
+    } else {
+        tracing::info!("Wrote in {}us", duration.as_micros());
+    }
+    Ok((reader, ids))
+}
+/// Checkboxes for each [`crate::window::WindowOptions`], returning true if any changed.
+fn window_menu(ui: &mut Ui, options: &mut crate::window::WindowOptions) -> bool {
+    let transparent = ui
+        .checkbox(&mut options.transparent, "Transparent")
+        .on_hover_text("Show the windows beneath through the canvas, for tracing.");
+    let on_top = ui.checkbox(&mut options.always_on_top, "Always on top");
+    let borderless = ui.checkbox(&mut options.borderless, "Borderless");
+    transparent.changed() || on_top.changed() || borderless.changed()
+}
+/// Toggle the [`crate::window::WindowOptions`] by hotkey.
+fn window_actions(frame: &crate::actions::ActionFrame) {
+    use crate::actions::Action;
+    let toggled = |action| frame.action_trigger_count(action) % 2 == 1;
+    let (transparent, on_top, borderless) = (
+        toggled(Action::WindowTransparent),
+        toggled(Action::WindowOnTop),
+        toggled(Action::WindowBorderless),
+    );
+    if !(transparent || on_top || borderless) {
+        return;
+    }
+    let mut preferences = crate::global::preferences::Preferences::write();
+    preferences.window.transparent ^= transparent;
+    preferences.window.always_on_top ^= on_top;
+    preferences.window.borderless ^= borderless;
+    save_preferences(&preferences);
+}
+/// The brush to start with, from the [startup preset](crate::global::preferences::Startup::brush_preset) if
+/// there is one.
+pub fn startup_brush() -> state::StrokeBrushSettings {
+    let mut brush = state::StrokeBrushSettings {
+        is_eraser: false,
+        is_smudge: false,
+        brush: fuzzpaint_core::brush::UniqueID([0; 32]),
+        color_modulate: fcolor::ColorOrPalette::BLACK,
+        size_mul: FiniteF32::new(10.0).unwrap(),
+        orientation: state::StampOrientation::Random,
+        scatter: state::Scatter::default(),
+        dual: None,
+        taper: state::Taper::default(),
+        dynamics: state::Dynamics::default(),
+        spacing_px: FiniteF32::new(0.5).unwrap(),
+    };
+    let name = crate::global::preferences::Preferences::read()
+        .startup
+        .brush_preset
+        .clone();
+    if let Some(name) = name {
+        let presets = crate::global::brush_presets::BrushPresets::read();
+        match presets.presets.iter().find(|preset| preset.name == name) {
+            Some(preset) => {
+                if let Err(e) = preset.apply(&mut brush) {
+                    tracing::warn!("failed to apply startup brush preset {name:?}: {e}");
+                }
+            }
+            None => tracing::warn!("startup brush preset {name:?} not found"),
+        }
+    }
+    brush
+}
+fn save_preferences(preferences: &crate::global::preferences::Preferences) {
+    if let Some(blocker) = preferences.load_blocker() {
+        tracing::warn!("not saving preferences, as the file failed to load: {blocker}");
+        return;
+    }
+    if let Err(e) = preferences.save() {
+        crate::errors::Report::new(
+            crate::errors::Severity::Recoverable,
+            tr!("preferences-save-failed"),
+            &e,
+        )
+        .send();
+    }
 }
 /// Panel showing debug stats
 fn stats_panel(ui: &mut Ui) {
@@ -1685,9 +3962,12 @@ fn icon_of_node(node: &state::graph::NodeData) -> &'static str {
         (Some(LeafType::StrokeLayer { .. }), None) => STROKE_LAYER_ICON,
         (Some(LeafType::Text { .. }), None) => TEXT_LAYER_ICON,
         (Some(LeafType::Note), None) => NOTE_LAYER_ICON,
+        (Some(LeafType::Image { .. }), None) => IMAGE_LAYER_ICON,
+        (Some(LeafType::Gradient { .. }), None) => GRADIENT_LAYER_ICON,
 
         // Groups
         (None, Some(NodeType::Passthrough | NodeType::GroupedBlend(..))) => GROUP_ICON,
+        (None, Some(NodeType::Filtered { .. })) => FILTER_ICON,
         // Invalid states
         (Some(..), Some(..)) | (None, None) => UNKNOWN,
     }
@@ -1798,7 +4078,7 @@ fn ui_layer_blend(
                     &mut blend.alpha_clip,
                     egui::RichText::new(ALPHA_ICON).monospace().strong(),
                 )
-                .on_hover_text("Alpha clip")
+                .on_hover_text(tr!("layer-alpha-clip"))
                 .clicked();
 
             // do NOT report "finished" mid-drag, only when it's complete!
@@ -1849,7 +4129,7 @@ fn ui_passthrough_or_blend(
                         &mut blend.alpha_clip,
                         egui::RichText::new(ALPHA_ICON).monospace().strong(),
                     )
-                    .on_hover_text("Alpha clip")
+                    .on_hover_text(tr!("layer-alpha-clip"))
                     .changed();
                 finished |= changed;
                 // do NOT report "finished" mid-drag, only when it's complete!
@@ -1869,10 +4149,12 @@ fn ui_passthrough_or_blend(
                 .selected_text(
                     blend
                         .map(|blend| blend.mode.as_ref().to_string())
-                        .unwrap_or("Passthrough".to_string()),
+                        .unwrap_or_else(|| tr!("layer-passthrough")),
                 )
                 .show_ui(ui, |ui| {
-                    changed |= ui.selectable_value(blend, None, "Passthrough").clicked();
+                    changed |= ui
+                        .selectable_value(blend, None, tr!("layer-passthrough"))
+                        .clicked();
                     ui.separator();
                     for blend_mode in <BlendMode as strum::IntoEnumIterator>::iter() {
                         let select_value = Some(Blend {
@@ -1923,6 +4205,64 @@ impl DndState {
     }
 }
 
+/// Whether the node's name or tag contains the lowercase `search`, or any of its children's do.
+/// Groups are kept for context around matching children.
+fn search_matches(graph: &state::graph::BlendGraph, id: state::graph::AnyID, search: &str) -> bool {
+    let Some(data) = graph.get(id) else {
+        return false;
+    };
+    if data.name().to_lowercase().contains(search)
+        || (data.tag() != state::graph::ColorTag::None
+            && data.tag().as_ref().to_lowercase().contains(search))
+    {
+        return true;
+    }
+    match id {
+        state::graph::AnyID::Node(node) => graph
+            .iter_node(node)
+            .into_iter()
+            .flatten()
+            .any(|(child, _)| search_matches(graph, child, search)),
+        state::graph::AnyID::Leaf(_) => false,
+    }
+}
+/// A colored chip showing the layer's tag, which can be clicked to change it.
+fn tag_chip(ui: &mut Ui, id: state::graph::AnyID, tag: &mut state::graph::ColorTag) {
+    let size = ui.spacing().interact_size.y * egui::vec2(0.6, 1.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    let radius = size.x / 2.0;
+    match tag.rgb() {
+        Some([r, g, b]) => {
+            ui.painter()
+                .circle_filled(rect.center(), radius, egui::Color32::from_rgb(r, g, b));
+        }
+        None => {
+            ui.painter().circle_stroke(
+                rect.center(),
+                radius - 1.0,
+                egui::Stroke::new(1.0, ui.visuals().weak_text_color()),
+            );
+        }
+    }
+    let hover = if *tag == state::graph::ColorTag::None {
+        tr!("layer-tag")
+    } else {
+        tag.as_ref().to_owned()
+    };
+    let response = response.on_hover_text(hover);
+
+    let popup_id = egui::Id::new((id, "tag-popup"));
+    if response.clicked() {
+        ui.memory_mut(|memory| memory.toggle_popup(popup_id));
+    }
+    egui::popup_below_widget(ui, popup_id, &response, |ui| {
+        ui.set_min_width(80.0);
+        for option in <state::graph::ColorTag as strum::IntoEnumIterator>::iter() {
+            ui.selectable_value(tag, option, option.as_ref());
+        }
+    });
+}
+#[allow(clippy::too_many_arguments)]
 fn graph_edit_recurse<
     // Well that's.... not great...
     W: queue::writer::CommandWrite<state::graph::commands::Command>,
@@ -1931,8 +4271,14 @@ fn graph_edit_recurse<
     graph: &mut state::graph::writer::GraphWriter<'_, W>,
     parent: Option<state::graph::NodeID>,
     selected_node: &mut Option<state::graph::AnyID>,
+    // Further selected nodes, for changing many at once.
+    also_selected: &mut Vec<state::graph::AnyID>,
     focused_node: &mut Option<state::graph::NodeID>,
+    // The node shown alone, if any.
+    solo: &mut Option<state::graph::AnyID>,
     dnd_state: &mut Option<DndState>,
+    // Lowercase search query, or empty to show everything.
+    search: &str,
 ) {
     let node_ids: Vec<_> = match parent {
         Some(root) => graph.iter_node(root).unwrap().map(|(id, _)| id).collect(),
@@ -1943,6 +4289,9 @@ fn graph_edit_recurse<
     let mut is_empty = true;
     // Iterate!
     for id in node_ids {
+        if !search.is_empty() && !search_matches(graph, id, search) {
+            continue;
+        }
         is_empty = false;
 
         let dnd_target = DroppedAt::Before(id);
@@ -1967,7 +4316,7 @@ fn graph_edit_recurse<
             // Drag-n-drop handle
             let dragged = ui
                 .add(drag::Handle)
-                .on_hover_text("Drag to reorder")
+                .on_hover_text(tr!("layer-drag"))
                 .dragged();
             if !dragged && dnd_state.is_some_and(|dnd| dnd.drag_target == id) {
                 // No longer dragged and we were the target, end drag.
@@ -1985,17 +4334,70 @@ fn graph_edit_recurse<
             let icon = icon_of_node(data);
             // Selection radio button + toggle function.
             let is_selected = *selected_node == Some(id);
-            if ui
-                .selectable_label(is_selected, egui::RichText::new(icon).monospace())
-                .clicked()
-            {
-                if is_selected {
-                    *selected_node = None;
+            let is_also_selected = also_selected.contains(&id);
+            // Icons mean nothing read aloud, name them for screen readers.
+            let layer_name = data.name.clone();
+            let select_response = ui
+                .selectable_label(
+                    is_selected || is_also_selected,
+                    egui::RichText::new(icon).monospace(),
+                )
+                .on_hover_text(tr!("layer-select.hover"));
+            select_response.widget_info(|| {
+                egui::WidgetInfo::selected(
+                    egui::WidgetType::SelectableLabel,
+                    is_selected || is_also_selected,
+                    tr!("layer-select", name = layer_name.as_str()),
+                )
+            });
+            if select_response.clicked() {
+                if ui.input(|input| input.modifiers.command) {
+                    // Add to or remove from the selection.
+                    if is_selected {
+                        *selected_node = also_selected.pop();
+                    } else if is_also_selected {
+                        also_selected.retain(|&other| other != id);
+                    } else if selected_node.is_none() {
+                        *selected_node = Some(id);
+                    } else {
+                        also_selected.push(id);
+                    }
                 } else {
-                    *selected_node = Some(id);
+                    also_selected.clear();
+                    if is_selected {
+                        *selected_node = None;
+                    } else {
+                        *selected_node = Some(id);
+                    }
                 }
             }
 
+            tag_chip(ui, id, graph.tag_mut(id).unwrap());
+            let is_solo = *solo == Some(id);
+            let solo_response = ui
+                .selectable_label(is_solo, SOLO_ICON)
+                .on_hover_text(tr!("layer-solo.hover"));
+            solo_response.widget_info(|| {
+                egui::WidgetInfo::selected(egui::WidgetType::Checkbox, is_solo, tr!("layer-solo"))
+            });
+            if solo_response.clicked() {
+                *solo = if is_solo { None } else { Some(id) };
+            }
+            // Only leaves can be references.
+            if let Some(reference) = graph.reference_mut(id) {
+                let response = ui
+                    .toggle_value(reference, REFERENCE_ICON)
+                    .on_hover_text(tr!("layer-reference.hover"));
+                let is_reference = *reference;
+                response.widget_info(|| {
+                    egui::WidgetInfo::selected(
+                        egui::WidgetType::Checkbox,
+                        is_reference,
+                        tr!("layer-reference"),
+                    )
+                });
+            }
+
             let name = graph.name_mut(id).unwrap();
 
             // Fetch from last frame - are we hovered?
@@ -2029,7 +4431,7 @@ fn graph_edit_recurse<
                 };
                 // Option to focus this subtree:
                 header_response.inner.context_menu(|ui| {
-                    if ui.button("Focus Subtree").clicked() {
+                    if ui.button(tr!("layer-focus-subtree")).clicked() {
                         *focused_node = Some(node_id);
                     }
                 });
@@ -2060,19 +4462,24 @@ fn graph_edit_recurse<
                     });
 
                 // display children!
-                egui::CollapsingHeader::new(egui::RichText::new("Children").italics().weak())
-                    .id_source(id)
-                    .default_open(true)
-                    .show(ui, |ui| {
-                        graph_edit_recurse(
-                            ui,
-                            graph,
-                            Some(node_id),
-                            selected_node,
-                            focused_node,
-                            dnd_state,
-                        );
-                    });
+                egui::CollapsingHeader::new(
+                    egui::RichText::new(tr!("layer-children")).italics().weak(),
+                )
+                .id_source(id)
+                .default_open(true)
+                .show(ui, |ui| {
+                    graph_edit_recurse(
+                        ui,
+                        graph,
+                        Some(node_id),
+                        selected_node,
+                        also_selected,
+                        focused_node,
+                        solo,
+                        dnd_state,
+                        search,
+                    );
+                });
             }
             (None, None) => (),
             (Some(_), Some(_)) => panic!("Node is both a leaf and node???"),
@@ -2096,9 +4503,13 @@ fn graph_edit_recurse<
         }
 
         ui.label(
-            egui::RichText::new("Nothing here... Add some layers!")
-                .italics()
-                .weak(),
+            egui::RichText::new(if search.is_empty() {
+                tr!("layers-empty")
+            } else {
+                tr!("layers-no-match")
+            })
+            .italics()
+            .weak(),
         );
     } else {
         let target = DroppedAt::LastChild(parent);
@@ -14,6 +14,7 @@ pub trait Modal {
     type Cancel;
     type Confirm;
     type Error;
+    /// Fluent id of the title of the modal's window, see [`crate::i18n::tr`].
     const NAME: &'static str;
     fn do_ui(&mut self, ui: &mut egui::Ui) -> Response<Self::Cancel, Self::Confirm, Self::Error>;
 }
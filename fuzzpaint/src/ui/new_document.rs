@@ -0,0 +1,201 @@
+//! Modal for choosing the template of a new document, and saving templates of one's own.
+
+use super::ResponseExt;
+use crate::global::config_file::ConfigFile;
+use crate::global::templates::{DocumentTemplate, Templates};
+
+pub struct NewDocumentModal {
+    /// The template as edited so far.
+    template: DocumentTemplate,
+    has_background: bool,
+    background: egui::Rgba,
+    /// Why the template can't be used, if it can't.
+    error: Option<String>,
+}
+impl Default for NewDocumentModal {
+    fn default() -> Self {
+        let mut this = Self {
+            template: Templates::default_template(),
+            has_background: false,
+            background: egui::Rgba::WHITE,
+            error: None,
+        };
+        this.select(this.template.clone());
+        this
+    }
+}
+impl NewDocumentModal {
+    /// Start editing from the given template.
+    fn select(&mut self, template: DocumentTemplate) {
+        self.has_background = template.background.is_some();
+        if let Some([r, g, b, a]) = template.background {
+            self.background = egui::Rgba::from_rgba_premultiplied(r, g, b, a);
+        }
+        self.template = template;
+        self.error = None;
+    }
+    /// The template with the edits applied, or an error message.
+    fn finish(&self) -> Result<DocumentTemplate, String> {
+        let template = DocumentTemplate {
+            background: self.has_background.then(|| self.background.to_array()),
+            ..self.template.clone()
+        };
+        template.validate().map_err(|e| e.to_string())?;
+        Ok(template)
+    }
+    fn save_template(&mut self) {
+        let template = match self.finish() {
+            Ok(template) => template,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        let mut templates = Templates::write();
+        templates.insert(template);
+        // Don't clobber a file the user needs to fix.
+        if let Some(blocker) = templates.load_blocker() {
+            self.error = Some(format!(
+                "Not saved, the templates file failed to load: {blocker}"
+            ));
+            return;
+        }
+        if let Err(e) = templates.save() {
+            tracing::error!("failed to save document templates: {e:#}");
+            self.error = Some(format!("Failed to save: {e}"));
+        }
+    }
+    fn template_list(&mut self, ui: &mut egui::Ui) {
+        let mut selected = None;
+        let mut delete = None;
+        ui.label("Presets");
+        for template in DocumentTemplate::builtin() {
+            let response = ui.selectable_label(self.template == template, &template.name);
+            if response.clicked() {
+                selected = Some(template);
+            }
+        }
+        {
+            let templates = Templates::read();
+            if !templates.templates.is_empty() {
+                ui.separator();
+                ui.label("Custom");
+            }
+            for (idx, template) in templates.templates.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .selectable_label(self.template == *template, &template.name)
+                        .clicked()
+                    {
+                        selected = Some(template.clone());
+                    }
+                    if ui.small_button("✖").on_hover_text("Delete").clicked() {
+                        delete = Some(idx);
+                    }
+                });
+            }
+        }
+        if let Some(template) = selected {
+            self.select(template);
+        }
+        if let Some(idx) = delete {
+            let mut templates = Templates::write();
+            templates.templates.remove(idx);
+            if templates.load_blocker().is_none() {
+                if let Err(e) = templates.save() {
+                    tracing::error!("failed to save document templates: {e:#}");
+                }
+            }
+        }
+    }
+}
+impl super::Modal for NewDocumentModal {
+    type Cancel = ();
+    type Confirm = DocumentTemplate;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-new-document";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| self.template_list(ui));
+            ui.separator();
+            ui.vertical(|ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut self.template.name);
+                });
+                ui.add(
+                    egui::DragValue::new(&mut self.template.dpi)
+                        .clamp_range(1.0..=2400.0)
+                        .suffix(" DPI")
+                        .prefix("Resolution: "),
+                );
+                // Stored in pixels, entered in the preferred unit at the resolution above.
+                let unit = crate::global::preferences::Preferences::read().units;
+                let resolution = fuzzpaint_core::units::Resolution::Dpi(self.template.dpi);
+                for (label, dimension) in [
+                    ("Width: ", &mut self.template.width),
+                    ("Height: ", &mut self.template.height),
+                ] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        #[allow(clippy::cast_precision_loss)]
+                        let mut px = *dimension as f32;
+                        if super::length_drag_value(ui, &mut px, unit, resolution).changed() {
+                            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                            let px = px.round().clamp(1.0, 16384.0) as u32;
+                            *dimension = px;
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.has_background, "Background");
+                    ui.add_enabled_ui(self.has_background, |ui| {
+                        egui::widgets::color_picker::color_edit_button_rgba(
+                            ui,
+                            &mut self.background,
+                            egui::widgets::color_picker::Alpha::OnlyBlend,
+                        );
+                    });
+                });
+                ui.label(format!("Layers: {}", self.template.layers.len()))
+                    .on_hover_text(
+                        self.template
+                            .layers
+                            .iter()
+                            .rev()
+                            .map(|layer| layer.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    );
+                if ui
+                    .button("Save as template")
+                    .on_hover_text("Replaces any custom template of the same name")
+                    .clicked()
+                {
+                    self.save_template();
+                }
+            });
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("Create").clicked_or_enter() {
+                match self.finish() {
+                    Ok(template) => return super::modal::Response::Confirm(template),
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            if ui.button("Cancel").clicked_or_escape() {
+                return super::modal::Response::Cancel(());
+            }
+            super::modal::Response::Continue
+        })
+        .inner
+    }
+}
@@ -0,0 +1,54 @@
+//! The dialog showing the progress of long running work listed in [`crate::global::tasks`], with the
+//! option to cancel it.
+
+/// Tasks quicker than this are never shown, sparing a dialog flashing up and away.
+const SHOW_AFTER: std::time::Duration = std::time::Duration::from_millis(400);
+/// How often the dialog is redrawn while it's shown.
+const REFRESH: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Show the tasks that have been running a while, if any.
+pub fn show(ctx: &egui::Context) {
+    let running = crate::global::tasks::running();
+    if running.is_empty() {
+        return;
+    }
+    let shown: Vec<_> = running
+        .iter()
+        .filter(|running| running.task.elapsed() >= SHOW_AFTER)
+        .collect();
+    if shown.is_empty() {
+        // Check back for when the first may be shown.
+        let soonest = running
+            .iter()
+            .map(|running| SHOW_AFTER.saturating_sub(running.task.elapsed()))
+            .min()
+            .unwrap_or(SHOW_AFTER);
+        ctx.request_repaint_after(soonest);
+        return;
+    }
+    ctx.request_repaint_after(REFRESH);
+
+    egui::Window::new("Working...")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            for running in shown {
+                ui.label(&running.label);
+                ui.horizontal(|ui| {
+                    let bar = match running.task.fraction() {
+                        Some(fraction) => egui::ProgressBar::new(fraction).show_percentage(),
+                        // Indeterminate, so show that it's alive at least.
+                        None => egui::ProgressBar::new(0.0).animate(true),
+                    };
+                    ui.add(bar.desired_width(240.0));
+                    if running.task.is_cancelled() {
+                        ui.add_enabled(false, egui::Button::new("Cancelling..."));
+                    } else if ui.button("Cancel").clicked() {
+                        running.task.cancel();
+                    }
+                });
+            }
+        });
+}
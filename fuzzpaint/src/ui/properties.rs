@@ -0,0 +1,110 @@
+//! Modal for editing a document's [metadata](fuzzpaint_core::state::document::Metadata) and resolution.
+
+use super::ResponseExt;
+use crate::i18n::tr;
+use fuzzpaint_core::{
+    state::document::{Metadata, Viewport, ID},
+    units::Resolution,
+};
+
+pub struct PropertiesModal {
+    /// The edited copy, applied on save.
+    metadata: Metadata,
+    /// Dots per inch, see [`Viewport::resolution`].
+    dpi: f32,
+    /// Pixels wide and tall of the document's canvas.
+    canvas: [f32; 2],
+}
+impl PropertiesModal {
+    #[must_use]
+    pub fn new(metadata: Metadata, viewport: &Viewport) -> Self {
+        Self {
+            metadata,
+            dpi: viewport.dpi(),
+            canvas: viewport.canvas().size,
+        }
+    }
+}
+impl super::Modal for PropertiesModal {
+    type Cancel = ();
+    /// The edited metadata and resolution, to [`apply`].
+    type Confirm = (Metadata, Resolution);
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-properties";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        egui::Grid::new("document-properties")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(tr!("properties-title"));
+                ui.text_edit_singleline(&mut self.metadata.title);
+                ui.end_row();
+                ui.label(tr!("properties-author"));
+                ui.text_edit_singleline(&mut self.metadata.author);
+                ui.end_row();
+                ui.label(tr!("properties-description"));
+                ui.text_edit_multiline(&mut self.metadata.description);
+                ui.end_row();
+                ui.label(tr!("properties-resolution"));
+                ui.add(
+                    egui::DragValue::new(&mut self.dpi)
+                        .clamp_range(Viewport::DPI_RANGE)
+                        .speed(1.0)
+                        .suffix(format!(" {}", tr!("properties-resolution-unit"))),
+                )
+                .on_hover_text(tr!("properties-resolution.hover"));
+                ui.end_row();
+                ui.label(tr!("properties-size"));
+                let [width, height] = self.canvas.map(|pixels| pixels / self.dpi);
+                ui.label(tr!(
+                    "properties-size-value",
+                    width = format!("{width:.2}"),
+                    height = format!("{height:.2}"),
+                    width_cm = format!("{:.1}", width * 2.54),
+                    height_cm = format!("{:.1}", height * 2.54),
+                ));
+                ui.end_row();
+            });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button(tr!("properties-save")).clicked_or_enter() {
+                return super::modal::Response::Confirm((
+                    self.metadata.clone(),
+                    Resolution::Dpi(self.dpi),
+                ));
+            }
+            if ui.button(tr!("properties-cancel")).clicked_or_escape() {
+                return super::modal::Response::Cancel(());
+            }
+            super::modal::Response::Continue
+        })
+        .inner
+    }
+}
+
+/// Set the document's metadata and resolution. The resolution is a part of history and can be undone, the
+/// metadata isn't but still leaves the document unsaved.
+///
+/// Fails if the document is closed or read-only, or the resolution is out of range.
+pub fn apply(document: ID, metadata: Metadata, resolution: Resolution) -> anyhow::Result<()> {
+    let Some(result) = crate::global::provider().write(document, "properties", |queue| {
+        use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+        let shared = queue.peek_clone_state().document().metadata.clone();
+        let mut current = shared.write();
+        if *current != metadata {
+            *current = metadata;
+            queue.mark_changed();
+        }
+        drop(current);
+        queue.write_with(|writer| writer.document().set_resolution(resolution))
+    }) else {
+        anyhow::bail!("document closed or read-only")
+    };
+    result.map_err(|()| anyhow::anyhow!("resolution out of range"))?;
+    // Metadata isn't a part of history, so let listeners know of it directly.
+    crate::global::provider().touch(document);
+    Ok(())
+}
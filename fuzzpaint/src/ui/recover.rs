@@ -0,0 +1,100 @@
+//! Modal offering to recover the changes journaled before a crash, see [`crate::global::journals`].
+
+use super::ResponseExt;
+use crate::global::journals::Leftover;
+
+struct Journal {
+    leftover: Leftover,
+    /// Why the last attempt to recover failed, if any.
+    error: Option<String>,
+}
+
+pub struct RecoverModal {
+    journals: Vec<Journal>,
+}
+impl RecoverModal {
+    /// Create a modal for the journals left behind by a crash, or None if there are none.
+    #[must_use]
+    pub fn new() -> Option<Self> {
+        let journals: Vec<_> = crate::global::journals::leftover()
+            .into_iter()
+            .map(|leftover| Journal {
+                leftover,
+                error: None,
+            })
+            .collect();
+        (!journals.is_empty()).then_some(Self { journals })
+    }
+    /// Whether every journal has been recovered or discarded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.journals.is_empty()
+    }
+}
+impl super::Modal for RecoverModal {
+    type Cancel = ();
+    /// A recovered document, to be opened. The modal may be shown again for the rest.
+    type Confirm = fuzzpaint_core::queue::DocumentCommandQueue;
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "modal-recover";
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        ui.label("Fuzzpaint closed unexpectedly. Changes made since these documents were last saved can be recovered.");
+        ui.separator();
+
+        let mut done = None;
+        let mut recovered = None;
+        for (idx, journal) in self.journals.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let name = journal
+                    .leftover
+                    .base
+                    .as_deref()
+                    .and_then(std::path::Path::file_name)
+                    .map_or_else(|| "Untitled".into(), std::ffi::OsStr::to_string_lossy);
+                ui.label(format!("{name} - {} changes", journal.leftover.changes))
+                    .on_hover_ui(|ui| {
+                        if let Some(base) = &journal.leftover.base {
+                            ui.label(base.display().to_string());
+                        }
+                    });
+                if ui.button("Recover").clicked() {
+                    match crate::global::journals::recover(&journal.leftover) {
+                        Ok(queue) => {
+                            done = Some(idx);
+                            recovered = Some(queue);
+                        }
+                        Err(e) => journal.error = Some(format!("{e:#}")),
+                    }
+                }
+                if ui.button("Discard").clicked() {
+                    match journal.leftover.discard() {
+                        Ok(()) => done = Some(idx),
+                        Err(e) => journal.error = Some(e.to_string()),
+                    }
+                }
+            });
+            if let Some(error) = &journal.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        }
+        if let Some(idx) = done {
+            self.journals.remove(idx);
+        }
+        ui.separator();
+
+        if let Some(queue) = recovered {
+            return super::modal::Response::Confirm(queue);
+        }
+        if self.journals.is_empty() {
+            return super::modal::Response::Cancel(());
+        }
+        // Left for the next time the app starts.
+        if ui.button("Later").clicked_or_escape() {
+            return super::modal::Response::Cancel(());
+        }
+        super::modal::Response::Continue
+    }
+}
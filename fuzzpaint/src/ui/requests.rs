@@ -34,6 +34,10 @@ pub enum DocumentViewRequest {
     RotateBy(f32),
     /// Set the absolute rotation, in radians from +X CCW.
     RotateTo(f32),
+    /// Mirror the view left-to-right. Does not affect the document.
+    FlipHorizontal,
+    /// Mirror the view top-to-bottom. Does not affect the document.
+    FlipVertical,
 }
 /// Request that applies to a specific document
 #[derive(Debug, Clone)]
@@ -54,4 +58,22 @@ pub enum DocumentRequest {
     Save,
     /// Save the document to the given path
     SaveCopy(std::path::PathBuf),
+    /// Write the composited document to an image file.
+    Export(crate::export::ExportSettings),
+    /// Fit the canvas to what's drawn on the document.
+    Autocrop,
+    /// Write each top-level layer to an image file of its own.
+    ExportLayers(crate::export::LayerExportSettings),
+    /// Start or stop highlighting what changed since the document was saved.
+    ShowSavedDiff(bool),
+    /// Replay the document's history into a process video or image sequence.
+    Timelapse(crate::export::TimelapseSettings),
+    /// Show only this node and its children, as if it were alone in the graph, or everything again if None.
+    /// Only the view changes.
+    Solo(Option<fuzzpaint_core::state::graph::AnyID>),
+    /// Show the filter node with these parameters while they're being edited, without committing them.
+    PreviewFilter {
+        node: fuzzpaint_core::state::graph::NodeID,
+        filter: fuzzpaint_core::filter::Filter,
+    },
 }
@@ -0,0 +1,145 @@
+//! Changing the brush or color of strokes already drawn. Strokes are kept as their points and settings rather
+//! than as pixels, so they're simply redrawn with whatever they're changed to.
+
+use crate::errors::{Report, Severity};
+use crate::i18n::tr;
+use fuzzpaint_core::state::{
+    document::ID,
+    graph::{AnyID, LeafType, NodeData},
+    transform::Matrix,
+    StrokeBrushSettings,
+};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Change {
+    /// Everything but the color, which each stroke keeps, as it does whether it erases or smudges.
+    Brush,
+    /// Just the color. Erasing and smudging strokes lay down no color, and are left alone.
+    Color,
+}
+impl Change {
+    fn apply(self, stroke: StrokeBrushSettings, to: &StrokeBrushSettings) -> StrokeBrushSettings {
+        match self {
+            Self::Brush => StrokeBrushSettings {
+                color_modulate: stroke.color_modulate,
+                is_eraser: stroke.is_eraser,
+                is_smudge: stroke.is_smudge,
+                ..*to
+            },
+            Self::Color => StrokeBrushSettings {
+                color_modulate: to.color_modulate,
+                ..stroke
+            },
+        }
+    }
+    fn applies_to(self, stroke: &StrokeBrushSettings) -> bool {
+        match self {
+            Self::Brush => true,
+            Self::Color => !stroke.is_eraser && !stroke.is_smudge,
+        }
+    }
+}
+
+/// Change the strokes of the stroke layer which touch the document's [selection](crate::global::selection), or
+/// all of them if nothing is selected, as one step of history. Returns how many were changed.
+pub fn restroke(
+    document: ID,
+    layer: AnyID,
+    change: Change,
+    to: &StrokeBrushSettings,
+) -> anyhow::Result<usize> {
+    let selection = crate::global::selection::get(document);
+    let Some(result) = crate::global::provider().write(document, "restroke", |queue| {
+        queue.write_with(|writer| {
+            let (collection, transform) = {
+                let graph = writer.graph();
+                match graph.get(layer).and_then(NodeData::leaf) {
+                    Some(LeafType::StrokeLayer {
+                        collection,
+                        inner_transform,
+                        outer_transform,
+                        ..
+                    }) => (
+                        *collection,
+                        Matrix::from(*inner_transform).then(outer_transform),
+                    ),
+                    _ => anyhow::bail!("not a stroke layer"),
+                }
+            };
+            let mut collections = writer.stroke_collections();
+            let Some(mut collection) = collections.get_mut(collection) else {
+                anyhow::bail!("layer references nonexistant stroke collection")
+            };
+            let points = crate::global::points();
+            // Selections are in document space, strokes in the layer's.
+            let targets: Vec<_> = collection
+                .iter_active()
+                .filter(|stroke| change.applies_to(&stroke.brush))
+                .filter(|stroke| {
+                    let Some(selection) = &selection else {
+                        return true;
+                    };
+                    points.try_get(stroke.point_collection).is_ok_and(|points| {
+                        fuzzpaint_core::selection::touches(
+                            &selection.polygon,
+                            points.get(),
+                            &transform,
+                        )
+                    })
+                })
+                .map(|stroke| stroke.id)
+                .collect();
+            collection.change_brushes(&targets, |brush| change.apply(brush, to))?;
+            Ok(targets.len())
+        })
+    }) else {
+        anyhow::bail!("document closed or read-only")
+    };
+    result
+}
+
+/// Entries for the edit menu, changing the strokes of the layer to the brush.
+pub fn menu(ui: &mut egui::Ui, target: Option<(ID, AnyID)>, brush: Option<StrokeBrushSettings>) {
+    // Only stroke layers have strokes to change.
+    let target = target.filter(|&(document, layer)| {
+        crate::global::provider()
+            .inspect(document, |queue| {
+                matches!(
+                    queue
+                        .peek_clone_state()
+                        .graph()
+                        .get(layer)
+                        .and_then(NodeData::leaf),
+                    Some(LeafType::StrokeLayer { .. })
+                )
+            })
+            .unwrap_or(false)
+    });
+    let enabled = target.is_some() && brush.is_some();
+    for (change, label, hover) in [
+        (
+            Change::Brush,
+            tr!("menu-edit-restroke-brush"),
+            tr!("menu-edit-restroke-brush.hover"),
+        ),
+        (
+            Change::Color,
+            tr!("menu-edit-restroke-color"),
+            tr!("menu-edit-restroke-color.hover"),
+        ),
+    ] {
+        if ui
+            .add_enabled(enabled, egui::Button::new(label))
+            .on_hover_text(hover)
+            .clicked()
+        {
+            if let (Some((document, layer)), Some(brush)) = (target, brush) {
+                match restroke(document, layer, change, &brush) {
+                    Ok(count) => Report::info(tr!("restroke-changed", count = count)).send(),
+                    Err(e) => Report::new(Severity::Recoverable, tr!("restroke-failed"), &e).send(),
+                }
+            }
+            ui.close_menu();
+        }
+    }
+}
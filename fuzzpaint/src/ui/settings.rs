@@ -1,18 +1,85 @@
+use crate::global::config_file::ConfigFile;
+use crate::i18n::tr;
+
 pub struct Settings {
     // LoadBlockError is !Clone (and can't be, oopsie) so use a string.
     hotkeys_error: Option<String>,
     hotkeys: crate::actions::hotkeys::ActionsToKeys,
     /// When adding a new hotkey, remember exactly where we're adding it.
     new_hotkey: Option<NewHotkeyState>,
+    /// See [`crate::global::preferences::Preferences::language`]
+    language: Option<String>,
+    /// Translations found when the settings were opened.
+    languages: Vec<crate::i18n::Language>,
+    /// User multiplier on the UI scale, see [`crate::global::preferences::Preferences::ui_scale`]
+    ui_scale: f32,
+    /// See [`crate::global::preferences::Preferences::low_latency`]
+    low_latency: bool,
+    /// See [`crate::global::preferences::Preferences::smart_zoom`]
+    smart_zoom: bool,
+    /// See [`crate::global::preferences::Preferences::preview_buffers`]
+    preview_buffers: u32,
+    /// See [`crate::global::preferences::Preferences::backups`]
+    backups: usize,
+    /// See [`crate::global::preferences::Preferences::saved_history`]
+    saved_history: usize,
+    /// See [`crate::global::preferences::Preferences::history_memory_mb`]
+    history_memory_mb: u32,
+    /// See [`crate::global::preferences::Preferences::history_spool_mb`]
+    history_spool_mb: u32,
+    /// See [`crate::global::preferences::Preferences::device`]
+    device: Option<String>,
+    /// See [`crate::global::preferences::Preferences::window`]
+    window: crate::window::WindowOptions,
+    /// See [`crate::global::preferences::Preferences::document_edge`]
+    document_edge: crate::document_viewport_proxy::DocumentEdge,
+    /// See [`crate::global::preferences::Preferences::pressure_curve`]
+    pressure_curve: crate::stylus_events::PressureCurve,
+    /// See [`crate::global::preferences::Preferences::pressure_smoothing_ms`]
+    pressure_smoothing_ms: u32,
+    /// See [`crate::global::preferences::Preferences::out_of_bounds`]
+    out_of_bounds: crate::pen_tools::OutOfBounds,
+    /// See [`crate::global::preferences::Preferences::units`]
+    units: fuzzpaint_core::units::Unit,
+    /// See [`crate::global::preferences::Preferences::mouse_pressure`]
+    mouse_pressure: crate::stylus_events::SimulatedPressure,
+    /// See [`crate::global::preferences::Preferences::tablet_mapping`]
+    tablet_mapping: crate::stylus_events::TabletMapping,
+    /// See [`crate::global::preferences::Preferences::startup`]
+    startup: crate::global::preferences::Startup,
+    /// The pressure calibration in progress, if any.
+    calibration: Option<Calibration>,
     pane: Pane,
 }
 impl Default for Settings {
     fn default() -> Self {
         let hotkeys = crate::global::hotkeys::Hotkeys::read();
+        let preferences = crate::global::preferences::Preferences::read();
         Self {
             hotkeys_error: hotkeys.load_blocker().map(ToString::to_string),
             hotkeys: hotkeys.actions_to_keys.clone(),
             new_hotkey: None,
+            language: preferences.language.clone(),
+            languages: crate::i18n::available(),
+            ui_scale: preferences.ui_scale,
+            low_latency: preferences.low_latency,
+            smart_zoom: preferences.smart_zoom,
+            preview_buffers: preferences.preview_buffers,
+            backups: preferences.backups,
+            saved_history: preferences.saved_history,
+            history_memory_mb: preferences.history_memory_mb,
+            history_spool_mb: preferences.history_spool_mb,
+            device: preferences.device.clone(),
+            window: preferences.window,
+            document_edge: preferences.document_edge,
+            pressure_curve: preferences.pressure_curve,
+            pressure_smoothing_ms: preferences.pressure_smoothing_ms,
+            mouse_pressure: preferences.mouse_pressure,
+            tablet_mapping: preferences.tablet_mapping,
+            out_of_bounds: preferences.out_of_bounds,
+            units: preferences.units,
+            startup: preferences.startup.clone(),
+            calibration: None,
             pane: Pane::default(),
         }
     }
@@ -39,28 +106,447 @@ impl Settings {
         if let Err(e) = try_save() {
             self.hotkeys_error = Some(e);
         }
+
+        // Read again even if unchanged, to pick up edits to the translation.
+        if let Err(e) = crate::i18n::set_language(self.language.as_deref()) {
+            crate::errors::Report::new(
+                crate::errors::Severity::Recoverable,
+                tr!(
+                    "settings-language-failed",
+                    language = self.language.clone().unwrap_or_default()
+                ),
+                &e,
+            )
+            .send();
+        }
+        let mut preferences = crate::global::preferences::Preferences::write();
+        preferences.language.clone_from(&self.language);
+        preferences.ui_scale = self.ui_scale;
+        preferences.low_latency = self.low_latency;
+        preferences.smart_zoom = self.smart_zoom;
+        preferences.preview_buffers = self.preview_buffers;
+        preferences.backups = self.backups;
+        preferences.saved_history = self.saved_history;
+        preferences.history_memory_mb = self.history_memory_mb;
+        preferences.history_spool_mb = self.history_spool_mb;
+        preferences.device.clone_from(&self.device);
+        preferences.window = self.window;
+        preferences.document_edge = self.document_edge;
+        preferences.pressure_curve = self.pressure_curve;
+        preferences.pressure_smoothing_ms = self.pressure_smoothing_ms;
+        preferences.mouse_pressure = self.mouse_pressure;
+        preferences.tablet_mapping = self.tablet_mapping;
+        preferences.out_of_bounds = self.out_of_bounds;
+        preferences.units = self.units;
+        preferences.startup.clone_from(&self.startup);
+        super::save_preferences(&preferences);
     }
-    fn hotkey_ui(
-        &mut self,
-        ui: &mut egui::Ui,
-    ) -> super::modal::Response<(), (), std::convert::Infallible> {
+    fn interface_ui(&mut self, ui: &mut egui::Ui) {
+        self.language_ui(ui);
+        self.units_ui(ui);
+        let range = crate::global::preferences::Preferences::UI_SCALE_RANGE;
+        ui.add(
+            egui::Slider::new(&mut self.ui_scale, range)
+                .text(tr!("settings-ui-scale"))
+                .suffix("×")
+                .max_decimals(2),
+        );
+        // Show what the OS asks for, which the scale above is multiplied with.
+        if let Some(native) = ui.ctx().native_pixels_per_point() {
+            ui.label(
+                egui::RichText::new(tr!(
+                    "settings-display-scale",
+                    native = format!("{native:.2}"),
+                    total = format!("{:.2}", native * self.ui_scale),
+                ))
+                .weak(),
+            );
+        }
+        ui.add_enabled(
+            !crate::render_device::is_software_rendering(),
+            egui::Checkbox::new(&mut self.low_latency, tr!("settings-low-latency")),
+        )
+        .on_hover_text(tr!("settings-low-latency.hover"))
+        .on_disabled_hover_text(tr!("settings-software-unavailable"));
+        ui.add_enabled(
+            !crate::render_device::is_software_rendering(),
+            egui::Checkbox::new(&mut self.smart_zoom, tr!("settings-sharp-zoom")),
+        )
+        .on_hover_text(tr!("settings-sharp-zoom.hover"))
+        .on_disabled_hover_text(tr!("settings-software-unavailable"));
+        ui.checkbox(
+            &mut self.document_edge.antialias,
+            tr!("settings-smooth-edge"),
+        )
+        .on_hover_text(tr!("settings-smooth-edge.hover"));
+        ui.checkbox(
+            &mut self.document_edge.border,
+            tr!("settings-document-border"),
+        )
+        .on_hover_text(tr!("settings-document-border.hover"));
+        ui.add(
+            egui::Slider::new(
+                &mut self.preview_buffers,
+                crate::global::preferences::Preferences::PREVIEW_BUFFERS_RANGE,
+            )
+            .text(tr!("settings-preview-buffers")),
+        )
+        .on_hover_text(tr!("settings-preview-buffers.hover"));
+        if crate::global::preferences::Preferences::read().preview_buffers != self.preview_buffers {
+            ui.label(egui::RichText::new(tr!("settings-restart")).weak());
+        }
+        ui.add(
+            egui::Slider::new(
+                &mut self.backups,
+                crate::global::preferences::Preferences::BACKUPS_RANGE,
+            )
+            .text(tr!("settings-backups")),
+        )
+        .on_hover_text(tr!("settings-backups.hover"));
+        ui.add(
+            egui::Slider::new(
+                &mut self.saved_history,
+                crate::global::preferences::Preferences::SAVED_HISTORY_RANGE,
+            )
+            .text(tr!("settings-saved-history")),
+        )
+        .on_hover_text(tr!("settings-saved-history.hover"));
+        ui.add(
+            egui::Slider::new(
+                &mut self.history_memory_mb,
+                crate::global::preferences::Preferences::HISTORY_MEMORY_RANGE,
+            )
+            .logarithmic(true)
+            .suffix(" MB")
+            .text(tr!("settings-history-memory")),
+        )
+        .on_hover_text(tr!("settings-history-memory.hover"));
+        ui.add(
+            egui::Slider::new(
+                &mut self.history_spool_mb,
+                crate::global::preferences::Preferences::HISTORY_SPOOL_RANGE,
+            )
+            .logarithmic(true)
+            .suffix(" MB")
+            .text(tr!("settings-history-spool")),
+        )
+        .on_hover_text(tr!("settings-history-spool.hover"));
+        self.out_of_bounds_ui(ui);
+        ui.collapsing(tr!("settings-window"), |ui| {
+            super::window_menu(ui, &mut self.window)
+        });
+        ui.collapsing(tr!("settings-startup"), |ui| self.startup_ui(ui));
+        self.device_ui(ui);
+    }
+    fn startup_ui(&mut self, ui: &mut egui::Ui) {
+        use crate::global::{brush_presets::BrushPresets, templates::Templates};
+        use crate::pen_tools::StateLayer;
+        let startup = &mut self.startup;
+        egui::ComboBox::new("startup-tool", tr!("settings-startup-tool"))
+            .selected_text(super::tool_button_for(startup.tool).1)
+            .show_ui(ui, |ui| {
+                for tool in <StateLayer as strum::IntoEnumIterator>::iter() {
+                    let (icon, name, _) = super::tool_button_for(tool);
+                    ui.selectable_value(&mut startup.tool, tool, format!("{icon} {name}"));
+                }
+            });
+        let plain = tr!("settings-startup-brush-none");
+        egui::ComboBox::new("startup-brush", tr!("settings-startup-brush"))
+            .selected_text(startup.brush_preset.as_deref().unwrap_or(&plain))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut startup.brush_preset, None, &plain);
+                for preset in &BrushPresets::read().presets {
+                    ui.selectable_value(
+                        &mut startup.brush_preset,
+                        Some(preset.name.clone()),
+                        &preset.name,
+                    );
+                }
+            });
+        let default_template = Templates::default_template();
+        egui::ComboBox::new("startup-template", tr!("settings-startup-template"))
+            .selected_text(
+                startup
+                    .template
+                    .as_deref()
+                    .unwrap_or(&default_template.name),
+            )
+            .show_ui(ui, |ui| {
+                let builtin = crate::global::templates::DocumentTemplate::builtin();
+                let templates = Templates::read();
+                for (idx, template) in builtin.iter().chain(&templates.templates).enumerate() {
+                    // The first built in one is chosen by default.
+                    let name = (idx != 0).then(|| template.name.clone());
+                    ui.selectable_value(&mut startup.template, name, &template.name);
+                }
+            })
+            .response
+            .on_hover_text(tr!("settings-startup-template.hover"));
+        ui.checkbox(&mut startup.reopen, tr!("settings-startup-reopen"))
+            .on_hover_text(tr!("settings-startup-reopen.hover"));
+        ui.checkbox(
+            &mut startup.new_document,
+            tr!("settings-startup-new-document"),
+        )
+        .on_hover_text(tr!("settings-startup-new-document.hover"));
+    }
+    fn language_ui(&mut self, ui: &mut egui::Ui) {
+        let selected = self
+            .language
+            .as_deref()
+            .unwrap_or(crate::i18n::FALLBACK_TAG);
+        let selected_name = self
+            .languages
+            .iter()
+            .find(|language| language.tag == selected)
+            .map_or(selected, |language| language.name.as_str())
+            .to_owned();
+        egui::ComboBox::new("language", tr!("settings-language"))
+            .selected_text(selected_name)
+            .show_ui(ui, |ui| {
+                for (idx, language) in self.languages.iter().enumerate() {
+                    // The first is the built in language, chosen by default.
+                    let tag = (idx != 0).then(|| language.tag.clone());
+                    ui.selectable_value(&mut self.language, tag, &language.name)
+                        .on_hover_text(&language.tag);
+                }
+            });
+    }
+    fn units_ui(&mut self, ui: &mut egui::Ui) {
+        use fuzzpaint_core::units::Unit;
+        let name = |unit: Unit| match unit {
+            Unit::Pixel => tr!("settings-units-pixel"),
+            Unit::Millimeter => tr!("settings-units-millimeter"),
+            Unit::Inch => tr!("settings-units-inch"),
+        };
+        egui::ComboBox::new("units", tr!("settings-units"))
+            .selected_text(name(self.units))
+            .show_ui(ui, |ui| {
+                for unit in <Unit as strum::IntoEnumIterator>::iter() {
+                    ui.selectable_value(&mut self.units, unit, name(unit));
+                }
+            })
+            .response
+            .on_hover_text(tr!("settings-units.hover"));
+    }
+    fn out_of_bounds_ui(&mut self, ui: &mut egui::Ui) {
+        use crate::pen_tools::OutOfBounds;
+        let name = |mode: OutOfBounds| match mode {
+            OutOfBounds::Retain => tr!("settings-out-of-bounds-retain"),
+            OutOfBounds::Clamp => tr!("settings-out-of-bounds-clamp"),
+        };
+        egui::ComboBox::new("out-of-bounds", tr!("settings-out-of-bounds"))
+            .selected_text(name(self.out_of_bounds))
+            .show_ui(ui, |ui| {
+                for mode in <OutOfBounds as strum::IntoEnumIterator>::iter() {
+                    ui.selectable_value(&mut self.out_of_bounds, mode, name(mode));
+                }
+            })
+            .response
+            .on_hover_text(tr!("settings-out-of-bounds.hover"));
+    }
+    fn device_ui(&mut self, ui: &mut egui::Ui) {
+        let devices = crate::render_device::available_devices();
+        let automatic = tr!("settings-device-automatic");
+        egui::ComboBox::new("graphics-device", tr!("settings-device"))
+            .selected_text(self.device.as_deref().unwrap_or(&automatic))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.device, None, &automatic);
+                for device in devices {
+                    ui.add_enabled_ui(device.suitable, |ui| {
+                        ui.selectable_value(
+                            &mut self.device,
+                            Some(device.name.clone()),
+                            &device.name,
+                        )
+                        .on_hover_text(format!("{:?}", device.device_type))
+                        .on_disabled_hover_text(tr!("settings-device-unsuitable"));
+                    });
+                }
+            });
+        // A preference for a device that is gone, keep it visible so it isn't silently lost.
+        if let Some(missing) = self
+            .device
+            .as_deref()
+            .filter(|name| !devices.iter().any(|device| device.name == *name))
+        {
+            ui.label(egui::RichText::new(tr!("settings-device-missing", device = missing)).weak());
+        }
+        if let Some(fallback) = crate::render_device::device_fallback() {
+            ui.label(egui::RichText::new(fallback).color(ui.style().visuals.error_fg_color));
+        }
+        let current = crate::global::preferences::Preferences::read()
+            .device
+            .clone();
+        if current != self.device {
+            ui.label(egui::RichText::new(tr!("settings-restart")).weak());
+        }
+    }
+    fn tablet_ui(&mut self, ui: &mut egui::Ui) {
+        use crate::stylus_events::PressureCurve;
+        ui.add(
+            egui::Slider::new(
+                &mut self.pressure_curve.saturation,
+                PressureCurve::SATURATION_RANGE,
+            )
+            .text(tr!("settings-full-pressure")),
+        )
+        .on_hover_text(tr!("settings-full-pressure.hover"));
+        ui.add(
+            egui::Slider::new(&mut self.pressure_curve.gamma, PressureCurve::GAMMA_RANGE)
+                .text(tr!("settings-pressure-curve"))
+                .logarithmic(true),
+        )
+        .on_hover_text(tr!("settings-pressure-curve.hover"));
+        pressure_curve_plot(ui, self.pressure_curve);
+        ui.add(
+            egui::Slider::new(
+                &mut self.pressure_smoothing_ms,
+                crate::global::preferences::Preferences::PRESSURE_SMOOTHING_RANGE,
+            )
+            .text(tr!("settings-pressure-smoothing"))
+            .suffix("ms"),
+        )
+        .on_hover_text(tr!("settings-pressure-smoothing.hover"));
+
+        let Some(calibration) = self.calibration.as_mut() else {
+            ui.horizontal(|ui| {
+                if ui.button(tr!("settings-calibrate")).clicked() {
+                    self.calibration = Some(Calibration::default());
+                }
+                if ui.button(tr!("settings-reset")).clicked() {
+                    self.pressure_curve = PressureCurve::default();
+                }
+            });
+            return;
+        };
+        ui.separator();
+        match calibration.step(ui) {
+            CalibrationStep::Continue => (),
+            CalibrationStep::Cancelled => self.calibration = None,
+            CalibrationStep::Finished(curve) => {
+                self.pressure_curve = curve;
+                self.calibration = None;
+            }
+        }
+    }
+    fn mapping_ui(&mut self, ui: &mut egui::Ui) {
+        use crate::stylus_events::{MappingMode, MappingTarget, TabletMapping};
+        let name = |target: MappingTarget| match target {
+            MappingTarget::Desktop => tr!("settings-tablet-mapping-desktop"),
+            MappingTarget::Window => tr!("settings-tablet-mapping-window"),
+            MappingTarget::Canvas => tr!("settings-tablet-mapping-canvas"),
+        };
+        let mapping = &mut self.tablet_mapping;
+        egui::ComboBox::new("tablet-mapping", tr!("settings-tablet-mapping"))
+            .selected_text(name(mapping.target))
+            .show_ui(ui, |ui| {
+                for target in <MappingTarget as strum::IntoEnumIterator>::iter() {
+                    ui.selectable_value(&mut mapping.target, target, name(target));
+                }
+            })
+            .response
+            .on_hover_text(tr!("settings-tablet-mapping.hover"));
+        ui.horizontal(|ui| {
+            ui.radio_value(
+                &mut mapping.mode,
+                MappingMode::Absolute,
+                tr!("settings-tablet-mapping-absolute"),
+            )
+            .on_hover_text(tr!("settings-tablet-mapping-absolute.hover"));
+            ui.radio_value(
+                &mut mapping.mode,
+                MappingMode::Relative,
+                tr!("settings-tablet-mapping-relative"),
+            )
+            .on_hover_text(tr!("settings-tablet-mapping-relative.hover"));
+        });
+        let relative = mapping.mode == MappingMode::Relative;
+        ui.add_enabled(
+            relative,
+            egui::Slider::new(&mut mapping.speed, TabletMapping::SPEED_RANGE)
+                .text(tr!("settings-tablet-mapping-speed"))
+                .logarithmic(true),
+        )
+        .on_hover_text(tr!("settings-tablet-mapping-speed.hover"));
+        ui.add_enabled(
+            !relative,
+            egui::Checkbox::new(
+                &mut mapping.keep_aspect,
+                tr!("settings-tablet-mapping-keep-aspect"),
+            ),
+        )
+        .on_hover_text(tr!("settings-tablet-mapping-keep-aspect.hover"));
+        ui.add_enabled(
+            !relative && mapping.keep_aspect,
+            egui::Slider::new(&mut mapping.tablet_aspect, TabletMapping::ASPECT_RANGE)
+                .text(tr!("settings-tablet-mapping-aspect"))
+                .logarithmic(true),
+        )
+        .on_hover_text(tr!("settings-tablet-mapping-aspect.hover"));
+        mapping_preview(ui, mapping);
+        if ui.button(tr!("settings-reset")).clicked() {
+            *mapping = TabletMapping::default();
+        }
+    }
+    fn mouse_ui(&mut self, ui: &mut egui::Ui) {
+        use crate::stylus_events::SimulatedPressure;
+        ui.label(tr!("settings-mouse-pressure"));
+        ui.add(
+            egui::Slider::new(&mut self.mouse_pressure.pressure, 0.0..=1.0)
+                .text(tr!("settings-mouse-pressure-amount")),
+        );
+        ui.add(
+            egui::Slider::new(
+                &mut self.mouse_pressure.fade_in_ms,
+                SimulatedPressure::FADE_IN_RANGE,
+            )
+            .text(tr!("settings-mouse-fade-in"))
+            .suffix("ms"),
+        )
+        .on_hover_text(tr!("settings-mouse-fade-in.hover"));
+        ui.add(
+            egui::Slider::new(
+                &mut self.mouse_pressure.fade_speed,
+                SimulatedPressure::FADE_SPEED_RANGE,
+            )
+            .text(tr!("settings-mouse-fade-speed"))
+            .logarithmic(true)
+            .suffix("px/s"),
+        )
+        .on_hover_text(tr!("settings-mouse-fade-speed.hover"));
+        if ui.button(tr!("settings-reset")).clicked() {
+            self.mouse_pressure = SimulatedPressure::default();
+        }
+    }
+    fn hotkey_ui(&mut self, ui: &mut egui::Ui) {
         // Show an error banner.
         if let Some(error) = self.hotkeys_error.clone() {
             ui.with_layout(
-                egui::Layout::left_to_right(egui::Align::Min).with_main_justify(true).with_main_wrap(true),
+                egui::Layout::left_to_right(egui::Align::Min)
+                    .with_main_justify(true)
+                    .with_main_wrap(true),
                 |ui| {
                     ui.label(
-                        egui::RichText::new("An error occured reading the settings file. Defaults have been used. To prevent data loss, the file will not be overwritten.")
+                        egui::RichText::new(tr!("settings-hotkeys-error"))
                             .color(ui.style().visuals.error_fg_color),
                     );
                     ui.end_row();
                     // Use monospace as toml errors use ascii art
                     ui.label(egui::RichText::new(error).monospace());
                     ui.end_row();
-                    if ui.button("Retry").on_hover_text("Try loading the file again.").clicked() {
+                    if ui
+                        .button(tr!("settings-hotkeys-retry"))
+                        .on_hover_text(tr!("settings-hotkeys-retry.hover"))
+                        .clicked()
+                    {
                         self.hard_reload();
                     }
-                    if ui.button("⚠ Allow overwrite").on_hover_text("Ignore the error, allowing replacing the erroneous file with new values. Existing settings data will be lost!").clicked() {
+                    if ui
+                        .button(tr!("settings-hotkeys-overwrite"))
+                        .on_hover_text(tr!("settings-hotkeys-overwrite.hover"))
+                        .clicked()
+                    {
                         // Just clear the error. This removes the banner and signifies that the file is writable again.
                         self.hotkeys_error = None;
                     }
@@ -79,7 +565,7 @@ impl Settings {
                     .show(ui, |ui| {
                         for action in <crate::actions::Action as strum::IntoEnumIterator>::iter() {
                             // First column, with the name of the action being assigned.
-                            ui.label(action.as_ref());
+                            ui.label(action.name());
                             // Second column, with a bunch of buttons for changing existing binds and adding new ones
                             ui.with_layout(
                                 egui::Layout::top_down_justified(egui::Align::Min),
@@ -173,19 +659,56 @@ impl Settings {
         if let Some(path) = crate::global::hotkeys::Hotkeys::default_file_location() {
             ui.label(egui::RichText::new(path.to_string_lossy()).weak());
         }
+    }
+}
+
+impl super::Modal for Settings {
+    const NAME: &'static str = "modal-settings";
+    type Cancel = ();
+    type Confirm = ();
+    type Error = std::convert::Infallible;
+    fn do_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.pane, Pane::Hotkeys, tr!("settings-pane-hotkeys"));
+            ui.selectable_value(
+                &mut self.pane,
+                Pane::Interface,
+                tr!("settings-pane-interface"),
+            );
+            ui.selectable_value(&mut self.pane, Pane::Tablet, tr!("settings-pane-tablet"));
+        });
+        ui.separator();
+        match self.pane {
+            Pane::Hotkeys => self.hotkey_ui(ui),
+            Pane::Interface => self.interface_ui(ui),
+            Pane::Tablet => {
+                self.tablet_ui(ui);
+                ui.separator();
+                self.mapping_ui(ui);
+                ui.separator();
+                self.mouse_ui(ui);
+            }
+        }
+        ui.separator();
 
         // Ok and cancel buttons at the bottom of the window
         ui.horizontal(|ui| {
             if ui
-                .add_enabled(self.hotkeys_error.is_none(), egui::Button::new("Ok"))
-                .on_disabled_hover_text("Cannot write settings while an error is present.")
+                .add_enabled(
+                    self.hotkeys_error.is_none(),
+                    egui::Button::new(tr!("settings-ok")),
+                )
+                .on_disabled_hover_text(tr!("settings-ok.disabled-hover"))
                 .clicked()
             {
                 // No error, safe to save!
                 self.save();
                 return super::modal::Response::Confirm(());
             }
-            if ui.button("Close").clicked() {
+            if ui.button(tr!("settings-close")).clicked() {
                 return super::modal::Response::Cancel(());
             }
             super::modal::Response::Continue
@@ -194,31 +717,170 @@ impl Settings {
     }
 }
 
-impl super::Modal for Settings {
-    const NAME: &'static str = "Settings";
-    type Cancel = ();
-    type Confirm = ();
-    type Error = std::convert::Infallible;
-    fn do_ui(
-        &mut self,
-        ui: &mut egui::Ui,
-    ) -> super::modal::Response<Self::Cancel, Self::Confirm, Self::Error> {
-        match self.pane {
-            Pane::Hotkeys => self.hotkey_ui(ui),
-        }
-    }
-}
-
 #[derive(PartialEq, Eq)]
 struct NewHotkeyState {
     action: crate::actions::Action,
     index: usize,
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
 enum Pane {
     #[default]
     Hotkeys,
+    Interface,
+    Tablet,
+}
+
+/// Draw the curve over a square, raw pressure to the right and remapped pressure upwards.
+fn pressure_curve_plot(ui: &mut egui::Ui, curve: crate::stylus_events::PressureCurve) {
+    const SEGMENTS: u8 = 32;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(120.0, 120.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    painter.rect_stroke(rect, 0.0, visuals.widgets.noninteractive.bg_stroke);
+    let points = (0..=SEGMENTS)
+        .map(|idx| {
+            let raw = f32::from(idx) / f32::from(SEGMENTS);
+            rect.lerp_inside(egui::vec2(raw, 1.0 - curve.apply(raw)))
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(2.0, visuals.selection.bg_fill),
+    ));
+}
+
+/// Draw the desktop, with the window and canvas within it, highlighting where the tablet maps to. Clicking one
+/// maps the tablet to it.
+fn mapping_preview(ui: &mut egui::Ui, mapping: &mut crate::stylus_events::TabletMapping) {
+    use crate::stylus_events::{MappingArea, MappingMode, MappingTarget};
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(240.0, 135.0), egui::Sense::click());
+    let window = egui::Rect::from_min_max(
+        rect.lerp_inside(egui::vec2(0.1, 0.15)),
+        rect.lerp_inside(egui::vec2(0.9, 0.95)),
+    );
+    let canvas =
+        egui::Rect::from_center_size(window.center(), egui::Vec2::splat(window.height() * 0.7));
+    let areas = [
+        (MappingTarget::Desktop, rect),
+        (MappingTarget::Window, window),
+        (MappingTarget::Canvas, canvas),
+    ];
+    let response = response.on_hover_text(tr!("settings-tablet-mapping-preview"));
+    if let Some(pos) = response
+        .interact_pointer_pos()
+        .filter(|_| response.clicked())
+    {
+        // The innermost under the pointer.
+        if let Some(&(target, _)) = areas.iter().rev().find(|(_, area)| area.contains(pos)) {
+            mapping.target = target;
+        }
+    }
+
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    for (_, area) in areas {
+        painter.rect_stroke(area, 0.0, visuals.widgets.noninteractive.bg_stroke);
+    }
+    let Some(&(_, target)) = areas.iter().find(|(target, _)| *target == mapping.target) else {
+        return;
+    };
+    let mapped = if mapping.keep_aspect && mapping.mode == MappingMode::Absolute {
+        let area = MappingArea::rect(
+            [target.min.x, target.min.y],
+            [target.width(), target.height()],
+        )
+        .letterboxed(mapping.tablet_aspect);
+        let [min, max] = [area.at([0.0; 2]), area.at([1.0; 2])];
+        egui::Rect::from_min_max(egui::pos2(min[0], min[1]), egui::pos2(max[0], max[1]))
+    } else {
+        target
+    };
+    painter.rect_filled(mapped, 0.0, visuals.selection.bg_fill.gamma_multiply(0.5));
+    painter.rect_stroke(
+        mapped,
+        0.0,
+        egui::Stroke::new(2.0, visuals.selection.bg_fill),
+    );
+}
+
+enum CalibrationStep {
+    Continue,
+    Cancelled,
+    Finished(crate::stylus_events::PressureCurve),
+}
+/// Records the user's natural light, medium, then heavy strokes, to fit a
+/// [`crate::stylus_events::PressureCurve`] to.
+#[derive(Default)]
+struct Calibration {
+    /// Typical raw pressure of each finished stroke weight.
+    typical: Vec<f32>,
+    /// Raw pressures of the stroke weight being recorded.
+    samples: Vec<f32>,
+    /// The last attempt didn't make sense, e.g. heavy strokes were lighter than light ones.
+    failed: bool,
+}
+impl Calibration {
+    const WEIGHTS: [&'static str; 3] = ["light", "medium", "heavy"];
+    /// Samples needed for each stroke weight, a few strokes' worth.
+    const MIN_SAMPLES: usize = 60;
+    fn step(&mut self, ui: &mut egui::Ui) -> CalibrationStep {
+        if self.failed {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                "Those strokes didn't get heavier, let's try again.",
+            );
+        }
+        let weight = Self::WEIGHTS[self.typical.len()];
+        ui.label(format!(
+            "Scribble some {weight} strokes in the box, as you naturally would."
+        ));
+        let (rect, response) =
+            ui.allocate_exact_size(egui::vec2(ui.available_width(), 120.0), egui::Sense::drag());
+        ui.painter()
+            .rect_stroke(rect, 0.0, ui.visuals().widgets.noninteractive.bg_stroke);
+        if response.dragged() {
+            let pressure = crate::stylus_events::raw_pressure();
+            if pressure > 0.0 {
+                self.samples.push(pressure);
+            }
+        }
+        ui.label(
+            egui::RichText::new(format!("{}/{}", self.samples.len(), Self::MIN_SAMPLES)).weak(),
+        );
+
+        let last = self.typical.len() + 1 == Self::WEIGHTS.len();
+        let mut step = CalibrationStep::Continue;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    self.samples.len() >= Self::MIN_SAMPLES,
+                    egui::Button::new(if last { "Finish" } else { "Next" }),
+                )
+                .clicked()
+            {
+                // Unwrap ok - there are at least MIN_SAMPLES.
+                let typical = crate::stylus_events::typical_pressure(&mut self.samples).unwrap();
+                self.samples.clear();
+                self.typical.push(typical);
+                if let Ok(typical) = <[f32; 3]>::try_from(self.typical.as_slice()) {
+                    match crate::stylus_events::PressureCurve::fit(typical) {
+                        Some(curve) => step = CalibrationStep::Finished(curve),
+                        None => {
+                            *self = Self {
+                                failed: true,
+                                ..Self::default()
+                            };
+                        }
+                    }
+                }
+            }
+            if ui.button("Cancel").clicked() {
+                step = CalibrationStep::Cancelled;
+            }
+        });
+        step
+    }
 }
 
 fn egui_key_to_winit_key(key: egui::Key) -> winit::keyboard::KeyCode {
@@ -0,0 +1,105 @@
+//! A debug panel listing the strokes of the selected layer, and where each came from. See
+//! [`StrokeMetadata`].
+
+use crate::i18n::tr;
+use fuzzpaint_core::state::{
+    document::ID,
+    graph::{AnyID, LeafType, NodeData},
+    stroke_collection::StrokeMetadata,
+};
+
+/// Roughly how long ago a stroke was created, at `created` milliseconds since the unix epoch.
+fn age(created: u64, now: std::time::SystemTime) -> String {
+    let created = std::time::UNIX_EPOCH + std::time::Duration::from_millis(created);
+    match now.duration_since(created) {
+        Ok(age) => match age.as_secs() {
+            secs @ 0..=59 => tr!("inspector-age-seconds", count = secs),
+            secs @ 60..=3599 => tr!("inspector-age-minutes", count = secs / 60),
+            secs @ 3600..=86399 => tr!("inspector-age-hours", count = secs / 3600),
+            secs => tr!("inspector-age-days", count = secs / 86400),
+        },
+        // Clocks disagree, such as with a stroke drawn on another machine.
+        Err(_) => tr!("inspector-age-future"),
+    }
+}
+
+fn describe(metadata: &StrokeMetadata, now: std::time::SystemTime) -> String {
+    let created = metadata.created.map_or_else(
+        || tr!("inspector-unknown-time"),
+        |created| age(created, now),
+    );
+    let author = metadata.author.map_or_else(
+        || tr!("inspector-unknown-author"),
+        |author| tr!("inspector-author", author = format!("{author:x}")),
+    );
+    let device = metadata.device.map_or_else(
+        || tr!("inspector-unknown-device"),
+        |device| tr!("inspector-device", device = format!("{device:x}")),
+    );
+    tr!(
+        "inspector-stroke",
+        created = created,
+        author = author,
+        device = device
+    )
+}
+
+/// List the strokes of the layer, newest first.
+pub fn panel(ui: &mut egui::Ui, target: Option<(ID, AnyID)>) {
+    use fuzzpaint_core::queue::state_reader::CommandQueueStateReader;
+    ui.label(tr!("inspector-title"));
+    ui.separator();
+    let Some((document, layer)) = target else {
+        ui.label(tr!("inspector-no-layer"));
+        return;
+    };
+    let strokes = crate::global::provider()
+        .inspect(document, |queue| {
+            let state = queue.peek_clone_state();
+            let Some(LeafType::StrokeLayer { collection, .. }) =
+                state.graph().get(layer).and_then(NodeData::leaf)
+            else {
+                return None;
+            };
+            state
+                .stroke_collections()
+                .get(*collection)
+                .map(|collection| collection.iter_active().copied().collect::<Vec<_>>())
+        })
+        .flatten();
+    let Some(strokes) = strokes else {
+        ui.label(tr!("inspector-not-strokes"));
+        return;
+    };
+    ui.label(tr!("inspector-count", count = strokes.len()));
+
+    let now = std::time::SystemTime::now();
+    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+    egui::ScrollArea::vertical().show_rows(ui, row_height, strokes.len(), |ui, rows| {
+        for (idx, stroke) in strokes
+            .iter()
+            .enumerate()
+            .rev()
+            .skip(rows.start)
+            .take(rows.len())
+        {
+            ui.label(tr!(
+                "inspector-row",
+                index = idx,
+                description = describe(&stroke.metadata, now)
+            ))
+            .on_hover_text(format!("{:#?}", stroke.metadata));
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn age() {
+        let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(100_000);
+        assert_eq!(super::age(99_990_000, now), "10s ago");
+        assert_eq!(super::age(100_000_000 - 7_200_000, now), "2h ago");
+        assert_eq!(super::age(100_001_000, now), "in the future");
+    }
+}
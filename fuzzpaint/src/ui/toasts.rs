@@ -0,0 +1,160 @@
+//! Toasts and dialogs showing the errors reported through [`crate::errors`].
+
+use crate::errors::{Report, Severity};
+use crate::i18n::tr;
+use egui::{RichText, Ui};
+
+/// How long a recoverable error or information is shown for. The rest stay until dismissed.
+const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(8);
+/// The most toasts shown at once, the oldest are dropped beyond this.
+const MAX_TOASTS: usize = 5;
+
+struct Toast {
+    report: Report,
+    /// How many times this same error was reported, as errors on every frame would otherwise flood the screen.
+    count: usize,
+    /// When it disappears on its own, if ever.
+    expires: Option<std::time::Instant>,
+}
+
+/// What the user asked for from the toasts.
+pub enum Response {
+    None,
+    /// Save a copy of every open document somewhere safe, without marking them saved.
+    SaveRecovery,
+}
+
+#[derive(Default)]
+pub struct Toasts {
+    toasts: Vec<Toast>,
+    /// The first fatal error, shown as a dialog until dismissed.
+    fatal: Option<Report>,
+    /// Where recovery copies were saved to, once they have been.
+    recovered: Option<std::path::PathBuf>,
+}
+impl Toasts {
+    fn push(&mut self, report: Report) {
+        if report.severity == Severity::Fatal {
+            self.fatal.get_or_insert(report);
+            return;
+        }
+        let expires = (report.severity <= Severity::Recoverable)
+            .then(|| std::time::Instant::now() + TOAST_DURATION);
+        if let Some(same) = self.toasts.iter_mut().find(|toast| {
+            toast.report.summary == report.summary && toast.report.detail == report.detail
+        }) {
+            same.count += 1;
+            same.expires = expires;
+            return;
+        }
+        self.toasts.push(Toast {
+            report,
+            count: 1,
+            expires,
+        });
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+    /// Recovery copies were written to this directory.
+    pub fn recovered(&mut self, directory: std::path::PathBuf) {
+        self.recovered = Some(directory);
+    }
+    pub fn show(&mut self, ctx: &egui::Context) -> Response {
+        for report in crate::errors::take() {
+            self.push(report);
+        }
+        let now = std::time::Instant::now();
+        self.toasts
+            .retain(|toast| toast.expires.map_or(true, |expires| expires > now));
+        if let Some(next) = self.toasts.iter().filter_map(|toast| toast.expires).min() {
+            ctx.request_repaint_after(next - now);
+        }
+
+        egui::Area::new(egui::Id::new("error-toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                self.toasts.retain(|toast| !toast_ui(ui, toast));
+            });
+
+        self.fatal_dialog(ctx)
+    }
+    fn fatal_dialog(&mut self, ctx: &egui::Context) -> Response {
+        let Some(fatal) = &self.fatal else {
+            return Response::None;
+        };
+        let mut response = Response::None;
+        let mut dismissed = false;
+        egui::Window::new(tr!("fatal-title"))
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                ui.label(RichText::new(&fatal.summary).color(ui.visuals().error_fg_color));
+                if let Some(remedy) = &fatal.remedy {
+                    ui.label(remedy);
+                }
+                ui.collapsing(tr!("fatal-details"), |ui| {
+                    ui.label(RichText::new(&fatal.detail).monospace());
+                });
+                if let Some(recovered) = &self.recovered {
+                    ui.label(tr!(
+                        "fatal-recovered",
+                        directory = recovered.display().to_string()
+                    ));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("fatal-save-recovery")).clicked() {
+                        response = Response::SaveRecovery;
+                    }
+                    if ui.button(tr!("fatal-dismiss")).clicked() {
+                        dismissed = true;
+                    }
+                });
+            });
+        if dismissed {
+            self.fatal = None;
+        }
+        response
+    }
+}
+
+/// Show one toast. True if it was dismissed.
+fn toast_ui(ui: &mut Ui, toast: &Toast) -> bool {
+    let color = match toast.report.severity {
+        Severity::Info => ui.visuals().text_color(),
+        Severity::Recoverable => ui.visuals().warn_fg_color,
+        Severity::DataLoss | Severity::Fatal => ui.visuals().error_fg_color,
+    };
+    egui::Frame::popup(ui.style())
+        .show(ui, |ui| {
+            ui.set_max_width(320.0);
+            let mut dismissed = false;
+            ui.horizontal(|ui| {
+                let summary = if toast.count > 1 {
+                    tr!(
+                        "toast-repeated",
+                        summary = toast.report.summary.as_str(),
+                        count = toast.count
+                    )
+                } else {
+                    toast.report.summary.clone()
+                };
+                let summary = ui.label(RichText::new(summary).strong().color(color));
+                if !toast.report.detail.is_empty() {
+                    summary.on_hover_text(&toast.report.detail);
+                }
+                dismissed = ui
+                    .small_button("✖")
+                    .on_hover_text(tr!("toast-dismiss"))
+                    .clicked();
+            });
+            if let Some(remedy) = &toast.report.remedy {
+                ui.label(remedy);
+            }
+            dismissed
+        })
+        .inner
+}
@@ -1,4 +1,5 @@
 use cgmath::prelude::*;
+use fuzzpaint_core::state::document::Canvas;
 
 type Decomposed2 = cgmath::Decomposed<cgmath::Vector2<f32>, cgmath::Basis2<f32>>;
 
@@ -6,9 +7,9 @@ type Decomposed2 = cgmath::Decomposed<cgmath::Vector2<f32>, cgmath::Basis2<f32>>
 /// (vertical flipping can be achieved by horizontal flip and rotate 180*)
 #[derive(Clone, Copy, Debug)]
 pub struct ViewTransform {
-    // Marker flag for flipping on the x axis. cgmath::Decomposed cannot represent this.
-    // todo: keeping it simple by not implementing this yet.
-    // flip_x: bool,
+    /// Marker flag for flipping on the local x axis, applied before `decomposed`.
+    /// `cgmath::Decomposed` cannot represent this.
+    pub flip_x: bool,
 
     // current convention is to position based on top-left corner. This is an
     // implementation detail however!
@@ -28,17 +29,31 @@ impl ViewTransform {
     /// Doesn't differentiate between horizontal and vertical flipping.
     #[must_use]
     pub fn is_flipped(&self) -> bool {
-        false //self.flip_x
+        self.flip_x
+    }
+    /// Apply the flip, if any, to a local point.
+    fn flip_local(&self, mut local_point: cgmath::Point2<f32>) -> cgmath::Point2<f32> {
+        if self.flip_x {
+            local_point.x = -local_point.x;
+        }
+        local_point
     }
     /// Flip the view horizontally about this center in viewspace such that the x-coordinate of the center
-    /// remains in the same spot in the viewport after rotating.
-    pub fn flip_x_about(&mut self, _view_center: cgmath::Point2<f32>) {
-        todo!()
-        // let local_center = self.unproject(view_center);
-        // transform such that center is at 0,0
-        // flip
-        // transform back
-        //todo!()
+    /// remains in the same spot in the viewport after flipping.
+    pub fn flip_x_about(&mut self, view_center: cgmath::Point2<f32>) {
+        // Mirroring the view about a vertical line is `M * (disp + R * F * local)`.
+        // A mirror conjugates a rotation into its inverse, `M * R = R^-1 * M`, so the mirror can be
+        // moved past the rotation and folded into the flip flag, leaving the displacement mirrored.
+        self.decomposed.rot = self.decomposed.rot.invert();
+        self.decomposed.disp.x = 2.0 * view_center.x - self.decomposed.disp.x;
+        self.flip_x = !self.flip_x;
+    }
+    /// Flip the view vertically about this center in viewspace such that the y-coordinate of the center
+    /// remains in the same spot in the viewport after flipping.
+    pub fn flip_y_about(&mut self, view_center: cgmath::Point2<f32>) {
+        // A vertical mirror is a horizontal mirror followed by a half-turn.
+        self.flip_x_about(view_center);
+        self.rotate_about(view_center, cgmath::Rad(std::f32::consts::PI));
     }
     /// Rotate about this center in viewspace such that the center remains in the same spot in the viewport after rotating.
     pub fn rotate_about(&mut self, view_center: cgmath::Point2<f32>, rotate: cgmath::Rad<f32>) {
@@ -69,16 +84,18 @@ impl ViewTransform {
         &self,
         view_point: cgmath::Point2<f32>,
     ) -> Result<cgmath::Point2<f32>, TransformError> {
-        Ok(self
-            .decomposed
-            .inverse_transform()
-            .ok_or(TransformError::Uninvertable)?
-            .transform_point(view_point))
+        Ok(self.flip_local(
+            self.decomposed
+                .inverse_transform()
+                .ok_or(TransformError::Uninvertable)?
+                .transform_point(view_point),
+        ))
     }
     /// Convert this point in local space to view space
     #[must_use]
     pub fn project(&self, local_point: cgmath::Point2<f32>) -> cgmath::Point2<f32> {
-        self.decomposed.transform_point(local_point)
+        self.decomposed
+            .transform_point(self.flip_local(local_point))
     }
     /// Create a transform where the document's center is located at `view_center`
     #[must_use]
@@ -92,6 +109,7 @@ impl ViewTransform {
         let disp = view_center.to_vec() - scale * rot.rotate_vector(document_size / 2.0);
 
         Self {
+            flip_x: false,
             decomposed: Decomposed2 { scale, rot, disp },
         }
     }
@@ -113,13 +131,24 @@ impl ViewTransform {
 
 impl From<ViewTransform> for cgmath::Matrix3<f32> {
     fn from(value: ViewTransform) -> Self {
-        value.decomposed.into()
+        let mat3: Self = value.decomposed.into();
+        if value.flip_x {
+            #[rustfmt::skip]
+            let flip = Self::new(
+                -1.0, 0.0, 0.0,
+                0.0, 1.0, 0.0,
+                0.0, 0.0, 1.0,
+            );
+            mat3 * flip
+        } else {
+            mat3
+        }
     }
 }
 impl From<ViewTransform> for cgmath::Matrix4<f32> {
     #[rustfmt::skip]
     fn from(value: ViewTransform) -> Self {
-        let mat3 = cgmath::Matrix3::<f32>::from(value.decomposed);
+        let mat3 = cgmath::Matrix3::<f32>::from(value);
         // Is this the same op as mat3.into()?
         // found out - it's NOT! keep doin this :>
         Self {
@@ -139,15 +168,16 @@ pub struct DocumentFit {
 }
 
 impl DocumentFit {
-    /// Make a transform from the given document size and viewport rect (pos, size)
+    /// Make a transform fitting the given canvas into the viewport rect (pos, size)
     /// Returns `None` if the resulting scale is too small to be reasonably caclulated or used.
     #[must_use]
     pub fn make_transform(
         &self,
-        document_size: cgmath::Vector2<f32>,
+        canvas: Canvas,
         view_pos: cgmath::Point2<f32>,
         view_size: cgmath::Vector2<f32>,
     ) -> Option<ViewTransform> {
+        let document_size = cgmath::Vector2::from(canvas.size);
         // rotate two rays. These will give us the max bounds of the rotated document.
         // probably a easier and less literal way to do this x3
         let bottom_right_corner_ray = document_size / 2.0;
@@ -188,12 +218,19 @@ impl DocumentFit {
         if document_scale < 0.001 {
             None
         } else {
-            Some(ViewTransform::center_on(
-                view_pos_margin + view_size_margin / 2.0,
-                document_size,
+            let view_center = view_pos_margin + view_size_margin / 2.0;
+            // Centered on the middle of the canvas, wherever it is in the document.
+            let [min, max] = canvas.corners();
+            let mut transform = ViewTransform::center_on(
+                view_center,
+                cgmath::vec2(min[0] + max[0], min[1] + max[1]),
                 self.rotation,
                 document_scale,
-            ))
+            );
+            if self.flip_x {
+                transform.flip_x_about(view_center);
+            }
+            Some(transform)
         }
     }
     #[must_use]
@@ -246,6 +283,8 @@ impl DocumentTransform {
 #[derive(Clone, Copy)]
 pub struct ViewInfo {
     pub transform: crate::view_transform::DocumentTransform,
+    /// The canvas of the document in view, which a [`DocumentTransform::Fit`] fits.
+    pub canvas: Canvas,
     pub viewport_position: ultraviolet::Vec2,
     pub viewport_size: ultraviolet::Vec2,
 }
@@ -261,10 +300,7 @@ impl ViewInfo {
     pub fn calculate_transform(&self) -> Option<crate::view_transform::ViewTransform> {
         match &self.transform {
             crate::view_transform::DocumentTransform::Fit(f) => f.make_transform(
-                cgmath::Vector2 {
-                    x: crate::DOCUMENT_DIMENSION as f32,
-                    y: crate::DOCUMENT_DIMENSION as f32,
-                },
+                self.canvas,
                 cgmath::Point2 {
                     x: self.viewport_position.x,
                     y: self.viewport_position.y,
@@ -295,6 +331,7 @@ impl ViewInfo {
             viewport_position: self.viewport_position * factor,
             viewport_size: self.viewport_size * factor,
             transform: self.transform.with_scale_factor(factor),
+            canvas: self.canvas,
         }
     }
     /// Calculate the position and size of the AABB the viewport covers, in document space.
@@ -347,3 +384,86 @@ impl ViewInfo {
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::ViewTransform;
+    fn assert_near(a: cgmath::Point2<f32>, b: cgmath::Point2<f32>) {
+        assert!(
+            (a.x - b.x).abs() < 1e-3 && (a.y - b.y).abs() < 1e-3,
+            "{a:?} != {b:?}"
+        );
+    }
+    fn transform() -> ViewTransform {
+        ViewTransform::center_on(
+            cgmath::point2(200.0, 100.0),
+            cgmath::vec2(50.0, 80.0),
+            cgmath::Rad(0.7),
+            2.5,
+        )
+    }
+    #[test]
+    fn flip_roundtrip() {
+        let center = cgmath::point2(120.0, 40.0);
+        let local = cgmath::point2(13.0, -7.0);
+        for flip_y in [false, true] {
+            let mut xform = transform();
+            let before = xform.project(local);
+            if flip_y {
+                xform.flip_y_about(center);
+            } else {
+                xform.flip_x_about(center);
+            }
+            assert!(xform.is_flipped());
+            let after = xform.project(local);
+            // Mirrored about the center in view space.
+            let expected = if flip_y {
+                cgmath::point2(before.x, 2.0 * center.y - before.y)
+            } else {
+                cgmath::point2(2.0 * center.x - before.x, before.y)
+            };
+            assert_near(after, expected);
+            // Input maps back through the inverse onto the same document point.
+            assert_near(xform.unproject(after).unwrap(), local);
+            // And the matrix used for drawing agrees.
+            let mat: cgmath::Matrix3<f32> = xform.into();
+            let projected = mat * cgmath::vec3(local.x, local.y, 1.0);
+            assert_near(cgmath::point2(projected.x, projected.y), after);
+        }
+    }
+    #[test]
+    fn double_flip_is_identity() {
+        let center = cgmath::point2(10.0, 10.0);
+        let local = cgmath::point2(3.0, 4.0);
+        let mut xform = transform();
+        let before = xform.project(local);
+        xform.flip_x_about(center);
+        xform.flip_x_about(center);
+        assert!(!xform.is_flipped());
+        assert_near(xform.project(local), before);
+    }
+    #[test]
+    fn fit_centers_canvas() {
+        let canvas = super::Canvas {
+            origin: [-100.0, 50.0],
+            size: [400.0, 200.0],
+        };
+        let fit = super::DocumentFit {
+            margin: 0.0,
+            ..Default::default()
+        };
+        let xform = fit
+            .make_transform(canvas, cgmath::point2(0.0, 0.0), cgmath::vec2(800.0, 600.0))
+            .unwrap();
+        // Wider than the view, so its width decides the scale.
+        assert!((xform.view_points_per_document_point() - 2.0).abs() < 1e-5);
+        assert_near(
+            xform.project(cgmath::point2(-100.0, 50.0)),
+            cgmath::point2(0.0, 100.0),
+        );
+        assert_near(
+            xform.project(cgmath::point2(300.0, 250.0)),
+            cgmath::point2(800.0, 500.0),
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::egui_impl;
+use crate::i18n::tr;
 use crate::render_device;
 use crate::vulkano_prelude::*;
 
@@ -6,20 +7,45 @@ use std::sync::Arc;
 
 use anyhow::Result as AnyResult;
 
+/// How often to check whether a submitted document image is ready to be shown.
+const FENCE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(2);
+/// How often to check for tablet events while a tool is in proximity, as not every platform wakes the
+/// event loop for them.
+const TABLET_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(4);
+
+/// How the main window sits among others, such that it may be used as a tracing overlay atop other
+/// applications. See [`crate::global::preferences::Preferences::window`].
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct WindowOptions {
+    /// Show the windows beneath through the canvas, where the document is transparent.
+    pub transparent: bool,
+    /// Keep above all other windows.
+    pub always_on_top: bool,
+    /// Hide the title bar and border.
+    pub borderless: bool,
+}
+
 pub struct Surface {
-    event_loop: winit::event_loop::EventLoop<()>,
+    event_loop: winit::event_loop::EventLoop<crate::global::wake::Wake>,
     win: Arc<winit::window::Window>,
 }
 impl Surface {
     pub fn new() -> AnyResult<Self> {
         const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
 
-        let event_loop = winit::event_loop::EventLoopBuilder::default().build()?;
+        let event_loop = winit::event_loop::EventLoopBuilder::with_user_event().build()?;
+        crate::global::wake::install(event_loop.create_proxy());
+        // Some platforms, X11 among them, only allow transparency to be chosen when creating the window.
+        // Ask for it always, and turn it off until asked for, see `Renderer::apply_window_options`.
         let win = winit::window::WindowBuilder::default()
             .with_title(format!("Fuzzpaint v{}", VERSION.unwrap_or("[unknown]")))
             .with_min_inner_size(winit::dpi::LogicalSize::new(500u32, 500u32))
-            .with_transparent(false)
+            .with_transparent(true)
+            // Shown once the UI is ready to be read by assistive technology, see `with_render_surface`.
+            .with_visible(false)
             .build(&event_loop)?;
+        win.set_transparent(false);
 
         let win = Arc::new(win);
 
@@ -28,16 +54,19 @@ impl Surface {
     pub fn window(&self) -> Arc<winit::window::Window> {
         self.win.clone()
     }
-    pub fn event_loop(&self) -> &winit::event_loop::EventLoop<()> {
+    pub fn event_loop(&self) -> &winit::event_loop::EventLoop<crate::global::wake::Wake> {
         &self.event_loop
     }
     pub fn with_render_surface(
         self,
         render_surface: render_device::RenderSurface,
         render_context: Arc<render_device::RenderContext>,
-        preview_renderer: Arc<dyn crate::document_viewport_proxy::PreviewRenderProxy>,
+        preview_renderer: crate::document_viewport_proxy::ProxyStack,
+        document_view: Arc<crate::document_viewport_proxy::Proxy>,
     ) -> anyhow::Result<Renderer> {
-        let egui_ctx = egui_impl::Ctx::new(self.win.as_ref(), &render_surface)?;
+        let mut egui_ctx = egui_impl::Ctx::new(self.win.as_ref(), &render_surface)?;
+        egui_ctx.init_accesskit(self.win.as_ref(), self.event_loop.create_proxy());
+        self.win.set_visible(true);
 
         let tablet_manager = octotablet::Builder::new()
             .emulate_tool_from_mouse(false)
@@ -46,7 +75,19 @@ impl Surface {
 
         let (send, stream) = crate::actions::create_action_stream();
 
+        let egui_timer = crate::diagnostics::GpuTimer::new(
+            render_context.clone(),
+            render_context.queues().graphics().queue().clone(),
+            crate::diagnostics::GpuPass::Egui,
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!("failed to create UI timer: {e:?}");
+            None
+        });
+
+        let monitors = monitor_bounds(&self.win);
         Ok(Renderer {
+            monitors,
             win: self.win,
             render_surface: Some(render_surface),
             swapchain_generation: 0,
@@ -55,19 +96,142 @@ impl Surface {
             last_frame_fence: None,
             egui_ctx,
             tablet_manager,
-            ui: crate::ui::MainUI::new(stream.listen()),
+            ui: crate::ui::MainUI::new(stream.listen(), send.clone()),
             enable_document_view: true,
+            viewport: (ultraviolet::Vec2::zero(), ultraviolet::Vec2::zero()),
+            window_options: WindowOptions::default(),
             preview_renderer,
-            action_collector:
-                crate::actions::winit_action_collector::WinitKeyboardActionCollector::new(send),
+            document_view,
+            reference: None,
+            input: crate::input::WinitInput::new(send),
             action_stream: stream,
-            stylus_events: crate::stylus_events::WinitStylusEventCollector::default(),
+            tool_in_proximity: false,
+            tablet_mapper: crate::stylus_events::TabletMapper::default(),
+            frame_stats: crate::diagnostics::CpuFrame::default(),
+            egui_timer,
+        })
+    }
+}
+
+/// The union of every monitor's bounds, as `[min_x, min_y, max_x, max_y]` physical pixels of the desktop, or None
+/// if there are none.
+#[allow(clippy::cast_precision_loss)]
+fn monitor_bounds(win: &winit::window::Window) -> Option<[f32; 4]> {
+    win.available_monitors()
+        .map(|monitor| {
+            let (pos, size) = (monitor.position(), monitor.size());
+            [
+                pos.x as f32,
+                pos.y as f32,
+                pos.x as f32 + size.width as f32,
+                pos.y as f32 + size.height as f32,
+            ]
+        })
+        .reduce(|a, b| {
+            [
+                a[0].min(b[0]),
+                a[1].min(b[1]),
+                a[2].max(b[2]),
+                a[3].max(b[3]),
+            ]
+        })
+}
+
+/// A second OS window mirroring the document, zoomed to fit and ignoring all input.
+/// See [`crate::document_viewport_proxy::ReferenceView`].
+struct ReferenceWindow {
+    win: Arc<winit::window::Window>,
+    /// Always Some, see [`Renderer::render_surface`].
+    render_surface: Option<render_device::RenderSurface>,
+    view: crate::document_viewport_proxy::ReferenceView,
+    last_frame_fence: Option<vk::sync::future::FenceSignalFuture<Box<dyn GpuFuture>>>,
+}
+impl ReferenceWindow {
+    fn new(
+        target: &winit::event_loop::EventLoopWindowTarget<crate::global::wake::Wake>,
+        render_context: &Arc<render_device::RenderContext>,
+        document_view: Arc<crate::document_viewport_proxy::Proxy>,
+    ) -> AnyResult<Self> {
+        let win = winit::window::WindowBuilder::default()
+            .with_title("Fuzzpaint reference")
+            .with_min_inner_size(winit::dpi::LogicalSize::new(100u32, 100u32))
+            .with_inner_size(winit::dpi::LogicalSize::new(500u32, 500u32))
+            .with_transparent(false)
+            .build(target)?;
+        let win = Arc::new(win);
+        let render_surface = render_context
+            .new_window_surface(win.clone(), crate::render_device::wants_low_latency())?;
+        let view =
+            crate::document_viewport_proxy::ReferenceView::new(document_view, &render_surface)?;
+        win.request_redraw();
+
+        Ok(Self {
+            win,
+            render_surface: Some(render_surface),
+            view,
+            last_frame_fence: None,
         })
     }
+    fn recreate_surface(&mut self) -> AnyResult<()> {
+        let size = self.win.inner_size().into();
+        let new_surface = self.render_surface.take().unwrap().recreate(Some(size))?;
+        self.view.surface_changed(&new_surface);
+        self.render_surface = Some(new_surface);
+        Ok(())
+    }
+    /// Wait for the last frame to finish using the document image.
+    fn wait(&mut self) -> AnyResult<()> {
+        if let Some(fence) = self.last_frame_fence.take() {
+            fence.wait(None)?;
+        }
+        Ok(())
+    }
+    fn paint(&mut self) -> AnyResult<()> {
+        self.wait()?;
+        let render_surface = self.render_surface.as_ref().unwrap();
+        let (idx, suboptimal, image_future) =
+            match vk::acquire_next_image(render_surface.swapchain().clone(), None) {
+                Err(vk::Validated::Error(vk::VulkanError::OutOfDate)) => {
+                    self.recreate_surface()?;
+                    self.win.request_redraw();
+                    return Ok(());
+                }
+                Err(e) => anyhow::bail!("Reference surface image acquire failed! {e:?}"),
+                Ok(acquired) => acquired,
+            };
+        // Safety: The main window waits on this frame before reading the proxy again.
+        let commands = unsafe { self.view.render(idx) }?;
+
+        let context = render_surface.context();
+        let future = image_future
+            .then_execute(context.queues().graphics().queue().clone(), commands)?
+            .then_swapchain_present(
+                context.queues().present().unwrap().queue().clone(),
+                vk::SwapchainPresentInfo::swapchain_image_index(
+                    render_surface.swapchain().clone(),
+                    idx,
+                ),
+            )
+            .boxed()
+            .then_signal_fence_and_flush()?;
+        self.last_frame_fence = Some(future);
+
+        if suboptimal {
+            self.recreate_surface()?;
+        }
+        Ok(())
+    }
+}
+
+/// A swapchain image, ready to be painted.
+struct AcquiredImage {
+    idx: u32,
+    suboptimal: bool,
+    future: vulkano::swapchain::SwapchainAcquireFuture,
 }
 
 pub struct Renderer {
-    event_loop: Option<winit::event_loop::EventLoop<()>>,
+    event_loop: Option<winit::event_loop::EventLoop<crate::global::wake::Wake>>,
     win: Arc<winit::window::Window>,
     /// Always Some. This is to allow it to be take-able to be remade.
     /// Could None represent a temporary loss of surface that can be recovered from?
@@ -77,17 +241,36 @@ pub struct Renderer {
     ui: crate::ui::MainUI,
 
     enable_document_view: bool,
+    /// Position and size of the document's viewport, in physical pixels.
+    viewport: (ultraviolet::Vec2, ultraviolet::Vec2),
+    /// The options the window currently has, see [`Self::apply_window_options`].
+    window_options: WindowOptions,
 
-    action_collector: crate::actions::winit_action_collector::WinitKeyboardActionCollector,
+    input: crate::input::WinitInput,
     action_stream: crate::actions::ActionStream,
     // May be None on unsupported platforms.
     tablet_manager: Option<octotablet::Manager>,
-    stylus_events: crate::stylus_events::WinitStylusEventCollector,
+    /// Whether a tablet tool is hovering or touching, in which case tablet events are polled for.
+    tool_in_proximity: bool,
+    /// See [`crate::global::preferences::Preferences::tablet_mapping`].
+    tablet_mapper: crate::stylus_events::TabletMapper,
+    /// See [`monitor_bounds`]. Cached, as listing monitors is slow on some platforms, and refreshed whenever the
+    /// window moves, resizes or changes scale, as it may have moved between monitors.
+    monitors: Option<[f32; 4]>,
     swapchain_generation: u32,
 
     last_frame_fence: Option<vk::sync::future::FenceSignalFuture<Box<dyn GpuFuture>>>,
 
-    preview_renderer: Arc<dyn crate::document_viewport_proxy::PreviewRenderProxy>,
+    /// Every layer drawn beneath the UI.
+    preview_renderer: crate::document_viewport_proxy::ProxyStack,
+    /// The document layer of `preview_renderer`, for the reference window to show.
+    document_view: Arc<crate::document_viewport_proxy::Proxy>,
+    reference: Option<ReferenceWindow>,
+
+    /// CPU timings accumulated since the last frame.
+    frame_stats: crate::diagnostics::CpuFrame,
+    /// None if the queue doesn't support timestamps.
+    egui_timer: Option<crate::diagnostics::GpuTimer>,
 }
 impl Renderer {
     pub fn window(&self) -> Arc<winit::window::Window> {
@@ -102,7 +285,7 @@ impl Renderer {
     pub fn stylus_events(
         &self,
     ) -> tokio::sync::broadcast::Receiver<crate::stylus_events::StylusEventFrame> {
-        self.stylus_events.frame_receiver()
+        self.input.stylus.frame_receiver()
     }
     pub fn render_surface(&self) -> &render_device::RenderSurface {
         //this will ALWAYS be Some. The option is for taking from a mutable reference for recreation.
@@ -110,11 +293,15 @@ impl Renderer {
     }
     /// Recreate surface after loss or out-of-date. Todo: This only handles out-of-date and resize.
     pub fn recreate_surface(&mut self) -> AnyResult<()> {
-        let new_surface = self
-            .render_surface
-            .take()
-            .unwrap()
-            .recreate(Some(self.window().inner_size().into()))?;
+        let size = self.window().inner_size().into();
+        self.replace_surface(|surface| surface.recreate(Some(size)))
+    }
+    /// Rebuild the surface with `rebuild`, and notify everything that renders to it.
+    fn replace_surface(
+        &mut self,
+        rebuild: impl FnOnce(render_device::RenderSurface) -> AnyResult<render_device::RenderSurface>,
+    ) -> AnyResult<()> {
+        let new_surface = rebuild(self.render_surface.take().unwrap())?;
 
         self.egui_ctx.replace_surface(&new_surface)?;
 
@@ -153,140 +340,121 @@ impl Renderer {
             use winit::event::{Event, WindowEvent};
             match event {
                 Event::WindowEvent { event, window_id } if window_id == self.window().id() => {
+                    // Redraws are timed by phase instead.
+                    let input_start = (!matches!(event, WindowEvent::RedrawRequested))
+                        .then(std::time::Instant::now);
                     let consumed = self
                         .egui_ctx
                         .push_winit_event(&self.window(), &event)
                         .consumed;
-                    if !consumed {
-                        self.action_collector.push_event(&event);
-                    }
+                    self.input.push_window_event(&event, consumed);
                     match event {
                         WindowEvent::CloseRequested => {
                             // Mark the UI, allowing it to veto this close.
                             self.ui.close_requested();
                         }
                         WindowEvent::Resized(..) => {
+                            self.monitors = monitor_bounds(&self.win);
                             self.recreate_surface().expect("Failed to rebuild surface");
                         }
-                        WindowEvent::CursorLeft { .. } => {
-                            self.stylus_events.set_mouse_pressed(false);
-                        }
-                        WindowEvent::CursorMoved { position, .. } => {
-                            // Only take if egui doesn't want it!
-                            if !consumed {
-                                self.stylus_events.push_position(position.into());
-                            }
-                        }
-                        WindowEvent::MouseInput { state, .. } => {
-                            let pressed = winit::event::ElementState::Pressed == state;
-
-                            if pressed {
-                                // Only take if egui doesn't want it!
-                                if !consumed {
-                                    self.stylus_events.set_mouse_pressed(true);
-                                }
-                            } else {
-                                self.stylus_events.set_mouse_pressed(false);
-                            }
+                        WindowEvent::Moved(..) | WindowEvent::ScaleFactorChanged { .. } => {
+                            self.monitors = monitor_bounds(&self.win);
                         }
                         WindowEvent::RedrawRequested => {
-                            // run UI logics
-                            if self.egui_ctx.take_wants_update() {
-                                self.do_ui();
-                            }
-                            // Overwrite the Egui provided cursor over the doc area.
-                            self.apply_document_cursor();
-
-                            // Render and present the updated UI
-                            if let Err(e) = self.paint() {
-                                log::error!("{e:?}");
+                            if let Err(e) = self.redraw() {
+                                crate::errors::Report::gpu(tr!("window-draw-failed"), &e).send();
                             };
+                            crate::diagnostics::push_cpu_frame(std::mem::take(
+                                &mut self.frame_stats,
+                            ));
                         }
                         _ => (),
                     }
+                    if let Some(start) = input_start {
+                        self.frame_stats
+                            .add(crate::diagnostics::CpuPhase::Input, start.elapsed());
+                    }
+                }
+                Event::WindowEvent { event, window_id }
+                    if self
+                        .reference
+                        .as_ref()
+                        .is_some_and(|reference| reference.win.id() == window_id) =>
+                {
+                    // Unwrap ok - checked by the guard.
+                    let reference = self.reference.as_mut().unwrap();
+                    let result = match event {
+                        WindowEvent::CloseRequested => {
+                            self.ui.set_reference_window(false);
+                            Ok(())
+                        }
+                        WindowEvent::Resized(..) => reference.recreate_surface(),
+                        WindowEvent::RedrawRequested => reference.paint(),
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = result {
+                        crate::errors::Report::gpu(tr!("reference-window-failed"), &e).send();
+                        self.ui.set_reference_window(false);
+                    }
+                }
+                Event::DeviceEvent { event, .. } => self.input.push_device_event(&event),
+                Event::UserEvent(crate::global::wake::Wake::Ui) => {
+                    self.egui_ctx.request_update();
                 }
-                Event::DeviceEvent {
-                    event: winit::event::DeviceEvent::Motion { axis: 2, value },
-                    ..
-                } => {
-                    //Pressure out of 65535
-                    self.stylus_events.set_pressure(value as f32 / 65535.0);
-                    // Other axes (undocumented and X11 only)
-                    // 0 -> x in display space
-                    // 1 -> y in display space
-                    // 2 -> pressure out of 65535, 0 if not pressed
-                    // 3 -> Tilt X, degrees from vertical, + to the right
-                    // 4 -> Tilt Y, degrees from vertical, + towards user
-                    // 5 -> unknown, always zero (barrel rotation?)
+                Event::UserEvent(crate::global::wake::Wake::Focus) => {
+                    self.window().focus_window();
+                    self.egui_ctx.request_update();
                 }
+                Event::UserEvent(crate::global::wake::Wake::Accessibility(event)) => {
+                    if event.window_id == self.window().id() {
+                        self.egui_ctx.push_accesskit_request(event.request);
+                    }
+                }
+                // Checked below.
+                Event::UserEvent(crate::global::wake::Wake::Poll) => (),
                 Event::AboutToWait => {
                     // The UI has requested the app exit. Do so!
                     if self.ui.should_close() {
+                        self.ui.remember_session();
+                        // Closing on purpose, there's nothing to recover.
+                        crate::global::journals::finish_all();
+                        crate::global::history_spool::finish_all();
                         target.exit();
                         // No need to redraw.
                         return;
                     }
 
-                    let has_tablet_update = if let Some(tab_events) =
-                        self.tablet_manager.as_mut().and_then(|m| m.pump().ok())
-                    {
-                        let mut has_tablet_update = false;
-                        for event in tab_events {
-                            if let octotablet::events::Event::Tool { event, tool } = event {
-                                // If the event isn't emulated from some other device, send the event to winit_egui
-                                // so that the stylus can be used to interact with the egui layers.
-                                if !matches!(tool.tool_type, Some(octotablet::tool::Type::Emulated))
-                                {
-                                    // Safety: we must not pass the returned event deviceID into any winit functions.
-                                    if let Some(winit_event) = unsafe {
-                                        crate::stylus_events::winit_event_from_octotablet(
-                                            &event,
-                                            self.win.scale_factor(),
-                                        )
-                                    } {
-                                        // Safety: Looking into the code of this, there is no path where the device ID is taken and given to winit.
-                                        // If that occurs, it's UB - MAKE SURE TO CHECK BEFORE UPDATING VERS ;3
-                                        let ignore = self
-                                            .egui_ctx
-                                            .push_winit_event(&self.win, &winit_event)
-                                            .consumed;
-
-                                        // Egui ate the event, skip further processing.
-                                        if ignore {
-                                            continue;
-                                        };
-                                    }
-                                }
-
-                                // Wasn't consumed, forward it to the event stream for the tools to use.
-                                match event {
-                                    octotablet::events::ToolEvent::Pose(p) => {
-                                        if let Some(p) = p.pressure.get() {
-                                            self.stylus_events.set_pressure(p);
-                                        }
-                                        self.stylus_events
-                                            .push_position((p.position[0], p.position[1]));
-
-                                        has_tablet_update = true;
-                                    }
-                                    octotablet::events::ToolEvent::Up
-                                    | octotablet::events::ToolEvent::Out => {
-                                        self.stylus_events.set_mouse_pressed(false);
-                                        has_tablet_update = true;
-                                    }
-                                    octotablet::events::ToolEvent::Down => {
-                                        self.stylus_events.set_mouse_pressed(true);
-                                        has_tablet_update = true;
-                                    }
-                                    _ => (),
-                                };
-                            }
-                        }
-                        has_tablet_update
-                    } else {
-                        false
-                    };
+                    if let Err(e) = self.apply_window_options() {
+                        crate::errors::Report::gpu(tr!("window-options-failed"), &e).send();
+                    }
+
+                    // Open or close the reference window to match the UI.
+                    if self.ui.reference_window() != self.reference.is_some() {
+                        self.reference = if self.ui.reference_window() {
+                            ReferenceWindow::new(
+                                target,
+                                &self.render_context,
+                                self.document_view.clone(),
+                            )
+                            .map_err(|e| {
+                                crate::errors::Report::gpu(tr!("reference-window-open-failed"), &e)
+                                    .send();
+                                self.ui.set_reference_window(false);
+                            })
+                            .ok()
+                        } else {
+                            None
+                        };
+                    }
 
+                    let input_start = std::time::Instant::now();
+                    let has_tablet_update = self.pump_tablet();
+                    self.frame_stats
+                        .add(crate::diagnostics::CpuPhase::Input, input_start.elapsed());
+
+                    if crate::global::wake::is_animating() {
+                        self.egui_ctx.request_update();
+                    }
                     // Request draw if any interactive element wants it (UI, document, or tablet)
                     if has_tablet_update
                         || self.egui_ctx.peek_wants_update()
@@ -297,68 +465,372 @@ impl Renderer {
                     }
 
                     // End stylus frame
-                    self.stylus_events.finish();
+                    self.input.stylus.finish();
 
-                    // Wait. We'll be notified when to redraw UI, but the document preview or octotablet could assert
-                    // an update at any time! Thus, we must poll. U_U
-                    target.set_control_flow(winit::event_loop::ControlFlow::wait_duration(
-                        std::time::Duration::from_millis(50),
-                    ));
+                    // Sleep until woken by input or by a change elsewhere, see `crate::global::wake`.
+                    // Some things can't wake us - a GPU fence, tablet events on some platforms, or a
+                    // repaint egui scheduled for later - so check for those soon, but only while they're due.
+                    let now = std::time::Instant::now();
+                    let wake_at = [
+                        self.egui_ctx.next_repaint(),
+                        self.preview_renderer
+                            .is_pending()
+                            .then(|| now + FENCE_POLL_INTERVAL),
+                        (self.tablet_manager.is_some() && self.tool_in_proximity)
+                            .then(|| now + TABLET_POLL_INTERVAL),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .min();
+                    target.set_control_flow(match wake_at {
+                        Some(time) => winit::event_loop::ControlFlow::WaitUntil(time),
+                        None => winit::event_loop::ControlFlow::Wait,
+                    });
                 }
                 _ => (),
             }
         })
     }
+    /// Where each target of the [tablet mapping](crate::stylus_events::TabletMapping) currently is, in
+    /// physical pixels relative to the window.
+    fn tablet_geometry(&self) -> crate::stylus_events::MappingGeometry {
+        use crate::stylus_events::MappingArea;
+        let size = self.win.inner_size();
+        #[allow(clippy::cast_precision_loss)]
+        let window = MappingArea::rect([0.0; 2], [size.width as f32, size.height as f32]);
+        // Monitors and the window are placed in the same space, where the platform says where either is.
+        #[allow(clippy::cast_precision_loss)]
+        let desktop = self.win.inner_position().ok().and_then(|window_pos| {
+            self.monitors.map(|[min_x, min_y, max_x, max_y]| {
+                MappingArea::rect(
+                    [min_x - window_pos.x as f32, min_y - window_pos.y as f32],
+                    [max_x - min_x, max_y - min_y],
+                )
+            })
+        });
+        let canvas = self.document_view.get_view_transform_sync().map(|view| {
+            let [min, max] = self.document_view.canvas().corners();
+            let project = |x, y| {
+                let point = view.project(cgmath::Point2 { x, y });
+                [point.x, point.y]
+            };
+            let origin = project(min[0], min[1]);
+            let [x, y] = [project(max[0], min[1]), project(min[0], max[1])]
+                .map(|corner| [corner[0] - origin[0], corner[1] - origin[1]]);
+            MappingArea { origin, x, y }
+        });
+        crate::stylus_events::MappingGeometry {
+            desktop,
+            window,
+            canvas,
+        }
+    }
+    /// Forward pending tablet events to egui and the stylus event stream.
+    /// Returns true if any events reached the stylus stream.
+    fn pump_tablet(&mut self) -> bool {
+        let geometry =
+            (!self.tablet_mapper.mapping().passes_through()).then(|| self.tablet_geometry());
+        if let Some(tab_events) = self.tablet_manager.as_mut().and_then(|m| m.pump().ok()) {
+            let mut has_tablet_update = false;
+            for event in tab_events {
+                if let octotablet::events::Event::Tool { mut event, tool } = event {
+                    self.tool_in_proximity = !matches!(event, octotablet::events::ToolEvent::Out);
+                    match &mut event {
+                        octotablet::events::ToolEvent::Pose(pose) => {
+                            if let Some(geometry) = &geometry {
+                                // Mapped in physical pixels, as the geometry is.
+                                #[allow(clippy::cast_possible_truncation)]
+                                let scale_factor = self.win.scale_factor() as f32;
+                                let physical = pose.position.map(|c| c * scale_factor);
+                                pose.position = self
+                                    .tablet_mapper
+                                    .map(physical, geometry)
+                                    .map(|c| c / scale_factor);
+                            }
+                        }
+                        octotablet::events::ToolEvent::Out => self.tablet_mapper.leave(),
+                        _ => (),
+                    }
+                    // Calibration needs pressure even while the stylus is over the UI.
+                    if let octotablet::events::ToolEvent::Pose(pose) = &event {
+                        if let Some(pressure) = pose.pressure.get() {
+                            crate::stylus_events::record_raw_pressure(pressure);
+                        }
+                    }
+                    // If the event isn't emulated from some other device, send the event to winit_egui
+                    // so that the stylus can be used to interact with the egui layers.
+                    if !matches!(tool.tool_type, Some(octotablet::tool::Type::Emulated)) {
+                        // Safety: we must not pass the returned event deviceID into any winit functions.
+                        if let Some(winit_event) = unsafe {
+                            crate::stylus_events::winit_event_from_octotablet(
+                                &event,
+                                self.win.scale_factor(),
+                            )
+                        } {
+                            // Safety: Looking into the code of this, there is no path where the device ID is taken and given to winit.
+                            // If that occurs, it's UB - MAKE SURE TO CHECK BEFORE UPDATING VERS ;3
+                            let ignore = self
+                                .egui_ctx
+                                .push_winit_event(&self.win, &winit_event)
+                                .consumed;
+
+                            // Egui ate the event, skip further processing.
+                            if ignore {
+                                continue;
+                            };
+                        }
+                    }
+
+                    // Wasn't consumed, forward it to the event stream for the tools to use.
+                    // After leaving proximity, further events come from some other device.
+                    let out = matches!(event, octotablet::events::ToolEvent::Out);
+                    self.input.stylus.set_eraser(
+                        !out && matches!(tool.tool_type, Some(octotablet::tool::Type::Eraser)),
+                    );
+                    self.input
+                        .stylus
+                        .set_device(tool.hardware_serial.filter(|_| !out));
+                    match event {
+                        octotablet::events::ToolEvent::Pose(p) => {
+                            if let Some(p) = p.pressure.get() {
+                                self.input.stylus.set_pressure(p);
+                            }
+                            self.input.stylus.set_distance(p.distance.get());
+                            // Octotablet reports logical pixels, the tools work in physical.
+                            #[allow(clippy::cast_possible_truncation)]
+                            let scale_factor = self.win.scale_factor() as f32;
+                            self.input.stylus.push_position((
+                                p.position[0] * scale_factor,
+                                p.position[1] * scale_factor,
+                            ));
+
+                            has_tablet_update = true;
+                        }
+                        octotablet::events::ToolEvent::Out => {
+                            self.input.stylus.set_mouse_pressed(false);
+                            self.input.stylus.set_distance(None);
+                            has_tablet_update = true;
+                        }
+                        octotablet::events::ToolEvent::Up => {
+                            self.input.stylus.set_mouse_pressed(false);
+                            has_tablet_update = true;
+                        }
+                        octotablet::events::ToolEvent::Down => {
+                            self.input.stylus.set_mouse_pressed(true);
+                            has_tablet_update = true;
+                        }
+                        _ => (),
+                    };
+                }
+            }
+            has_tablet_update
+        } else {
+            false
+        }
+    }
+    /// Run the UI logic and paint a frame.
+    ///
+    /// In low latency mode the swapchain image is acquired first, as that's where the wait for the
+    /// display happens. Input that arrived during the wait is then collected, so that the freshest
+    /// stroke data makes it into this frame rather than the next.
+    fn redraw(&mut self) -> AnyResult<()> {
+        self.apply_low_latency()?;
+        let low_latency = self.render_surface().low_latency();
+
+        let mut acquired = None;
+        if low_latency {
+            let Some(image) = self.acquire()? else {
+                return Ok(());
+            };
+            acquired = Some(image);
+
+            let input_start = std::time::Instant::now();
+            if self.pump_tablet() {
+                self.input.stylus.finish();
+            }
+            self.frame_stats
+                .add(crate::diagnostics::CpuPhase::Input, input_start.elapsed());
+        }
+
+        // run UI logics
+        let ui_start = std::time::Instant::now();
+        if self.egui_ctx.take_wants_update() {
+            self.do_ui();
+        }
+        // Overwrite the Egui provided cursor over the doc area.
+        self.apply_document_cursor();
+        self.frame_stats
+            .add(crate::diagnostics::CpuPhase::Ui, ui_start.elapsed());
+
+        let image = match acquired {
+            Some(image) => image,
+            None => {
+                let Some(image) = self.acquire()? else {
+                    return Ok(());
+                };
+                image
+            }
+        };
+        // Render and present the updated UI
+        self.paint(image)?;
+        if let Some(settings) = self.ui.take_screenshot() {
+            if let Err(e) = self.capture(settings) {
+                crate::errors::Report::gpu(tr!("screenshot-failed"), &e).send();
+            }
+        }
+
+        if low_latency {
+            // Don't start on the next frame until this one is done, so that it reflects the
+            // newest input possible when it does start.
+            if let Some(fence) = self.last_frame_fence.as_ref() {
+                fence.wait(None)?;
+            }
+        }
+        Ok(())
+    }
+    /// Recreate the swapchain if the user's latency preference changed.
+    fn apply_low_latency(&mut self) -> AnyResult<()> {
+        // See `RenderContext::new_with_window_surface`
+        let low_latency = crate::render_device::wants_low_latency()
+            && !crate::render_device::is_software_rendering();
+        if self.render_surface().low_latency() == low_latency {
+            return Ok(());
+        }
+        self.replace_surface(|surface| surface.set_low_latency(low_latency))
+    }
+    /// Apply the user's [`WindowOptions`], if they changed.
+    fn apply_window_options(&mut self) -> AnyResult<()> {
+        let options = crate::global::preferences::Preferences::read().window;
+        if self.window_options == options {
+            return Ok(());
+        }
+        // Set first, so that a failure below isn't retried every event.
+        let old = std::mem::replace(&mut self.window_options, options);
+        if old.always_on_top != options.always_on_top {
+            self.win.set_window_level(if options.always_on_top {
+                winit::window::WindowLevel::AlwaysOnTop
+            } else {
+                winit::window::WindowLevel::Normal
+            });
+        }
+        if old.borderless != options.borderless {
+            self.win.set_decorations(!options.borderless);
+        }
+        if old.transparent != options.transparent {
+            self.win.set_transparent(options.transparent);
+            self.replace_surface(|surface| surface.set_transparent(options.transparent))?;
+            if options.transparent && !self.render_surface().transparent() {
+                tracing::warn!("window surface can't be transparent");
+            }
+        }
+        self.win.request_redraw();
+        Ok(())
+    }
     fn do_ui(&mut self) {
+        {
+            let preferences = crate::global::preferences::Preferences::read();
+            self.egui_ctx.set_zoom_factor(preferences.ui_scale);
+            self.input
+                .stylus
+                .set_pressure_curve(preferences.pressure_curve);
+            self.input
+                .stylus
+                .set_simulated_pressure(preferences.mouse_pressure);
+            self.input
+                .stylus
+                .set_pressure_smoothing(std::time::Duration::from_millis(
+                    preferences.pressure_smoothing_ms.into(),
+                ));
+            self.tablet_mapper.set_mapping(preferences.tablet_mapping);
+        }
         let viewport = self
             .egui_ctx
             .update(self.win.as_ref(), |ctx| self.ui.ui(ctx));
 
+        // Changes when the user's scale does, or the window moves to a monitor of different DPI.
+        let pixels_per_point = self.egui_ctx.pixels_per_point();
+        crate::gizmos::transform::set_viewport_scale(pixels_per_point);
+
         // Todo: only change if... actually changed :P
-        if let Some(viewport) = viewport {
+        if let Some((position, size)) = viewport {
             self.enable_document_view = true;
+            // The UI works in points, while the document works in physical pixels.
+            self.viewport = (position * pixels_per_point, size * pixels_per_point);
             self.preview_renderer
-                .viewport_changed(viewport.0, viewport.1);
+                .viewport_changed(self.viewport.0, self.viewport.1);
         } else {
             self.enable_document_view = false;
         }
     }
-    fn paint(&mut self) -> AnyResult<()> {
-        let (idx, suboptimal, image_future) =
-            match vk::acquire_next_image(self.render_surface().swapchain().clone(), None) {
-                Err(vk::Validated::Error(vk::VulkanError::OutOfDate)) => {
-                    log::info!("Swapchain unusable. Recreating");
-                    //We cannot draw on this surface as-is. Recreate and request another try next frame.
-                    //TODO: Race condition, somehow! Surface is recreated with an out-of-date size.
-                    self.recreate_surface()?;
-                    self.window().request_redraw();
-                    return Ok(());
-                }
-                Err(e) => {
-                    //Todo. Many of these errors are recoverable!
-                    anyhow::bail!("Surface image acquire failed! {e:?}");
-                }
-                Ok(r) => r,
-            };
+    /// Acquire the next swapchain image to paint into. `None` if the swapchain had to be
+    /// recreated, in which case another redraw is requested.
+    fn acquire(&mut self) -> AnyResult<Option<AcquiredImage>> {
+        match vk::acquire_next_image(self.render_surface().swapchain().clone(), None) {
+            Err(vk::Validated::Error(vk::VulkanError::OutOfDate)) => {
+                tracing::info!("Swapchain unusable. Recreating");
+                //We cannot draw on this surface as-is. Recreate and request another try next frame.
+                //TODO: Race condition, somehow! Surface is recreated with an out-of-date size.
+                self.recreate_surface()?;
+                self.window().request_redraw();
+                Ok(None)
+            }
+            Err(e) => {
+                //Todo. Many of these errors are recoverable!
+                anyhow::bail!("Surface image acquire failed! {e:?}");
+            }
+            Ok((idx, suboptimal, future)) => Ok(Some(AcquiredImage {
+                idx,
+                suboptimal,
+                future,
+            })),
+        }
+    }
+    fn paint(&mut self, image: AcquiredImage) -> AnyResult<()> {
+        let AcquiredImage {
+            idx,
+            suboptimal,
+            future: image_future,
+        } = image;
 
         // Print a warning if swapchain image future is dropped. Per a dire warning in the comments of vulkano,
         // dropping futures can result in that swapchain image being lost forever...!
-        let bail_warning = defer::defer(|| log::warn!("Dropped swapchain future."));
+        let bail_warning = defer::defer(|| tracing::warn!("Dropped swapchain future."));
 
         //Wait for previous frame to end. (required for safety of preview render proxy)
         self.last_frame_fence.take().map(|fence| fence.wait(None));
+        // Likewise for the reference window, which shows the same images.
+        if let Some(reference) = self.reference.as_mut() {
+            reference.wait()?;
+        }
+
+        // Collect the measurements of earlier frames that have finished.
+        if crate::diagnostics::enabled() {
+            if let Some(Err(e)) = self.egui_timer.as_ref().map(|timer| timer.publish()) {
+                tracing::warn!("failed to read UI timer: {e:?}");
+            }
+            crate::diagnostics::set_device_memory(self.render_context.device_local_memory());
+        }
+
+        let record_start = std::time::Instant::now();
 
         let preview_commands = self.enable_document_view.then(|| unsafe {
             self.preview_renderer.render(
                 self.render_surface.as_ref().unwrap().swapchain_images()[idx as usize].clone(),
                 idx,
+                crate::document_viewport_proxy::ImageState::Undefined,
             )
         });
+        if self.enable_document_view {
+            if let Some(reference) = &self.reference {
+                // May have swapped to a new image.
+                reference.win.request_redraw();
+            }
+        }
         let preview_commands = match preview_commands {
             Some(Ok(commands)) => commands,
             None => smallvec::SmallVec::new(),
             Some(Err(e)) => {
-                log::warn!("Failed to build preview commands {e:?}");
+                tracing::warn!("Failed to build preview commands {e:?}");
                 smallvec::SmallVec::new()
             }
         };
@@ -369,6 +841,10 @@ impl Renderer {
             // If there are none, instruct egui renderer to clear it first.
             .build_commands(idx, preview_commands.is_empty());
 
+        self.frame_stats
+            .add(crate::diagnostics::CpuPhase::Record, record_start.elapsed());
+        let submit_start = std::time::Instant::now();
+
         let render_complete = match commands {
             Some((Some(transfer), draw)) => {
                 let transfer_future = self
@@ -398,12 +874,7 @@ impl Renderer {
                         .boxed();
                 }
 
-                future
-                    .then_execute(
-                        self.render_context.queues().graphics().queue().clone(),
-                        draw,
-                    )?
-                    .boxed()
+                self.execute_ui_draw(future, draw)?
             }
             Some((None, draw)) => {
                 let mut future = image_future.boxed();
@@ -416,12 +887,7 @@ impl Renderer {
                         )?
                         .boxed();
                 }
-                future
-                    .then_execute(
-                        self.render_context.queues().graphics().queue().clone(),
-                        draw,
-                    )?
-                    .boxed()
+                self.execute_ui_draw(future, draw)?
             }
             None => anyhow::bail!("no commands submitted"),
         };
@@ -453,6 +919,114 @@ impl Renderer {
             self.recreate_surface().unwrap();
         }
 
+        self.frame_stats
+            .add(crate::diagnostics::CpuPhase::Submit, submit_start.elapsed());
+
         Ok(())
     }
+    /// Draw the frame just painted again, into the surface's capture image rather than the swapchain, then
+    /// download and write it on a background thread. Without the UI, only the viewport is kept.
+    fn capture(&mut self, settings: crate::export::ScreenshotSettings) -> AnyResult<()> {
+        if !settings.ui && !self.enable_document_view {
+            anyhow::bail!("no viewport to capture");
+        }
+        // The preview's images are in use until the frame completes.
+        if let Some(fence) = self.last_frame_fence.take() {
+            fence.wait(None)?;
+        }
+        let surface = self.render_surface.as_ref().unwrap();
+        let (image, idx, extent) = (
+            surface.capture_image().clone(),
+            surface.capture_idx(),
+            surface.extent(),
+        );
+
+        let preview_commands = if self.enable_document_view {
+            // Safety: the last frame was waited on above, and this is waited on before the next.
+            unsafe {
+                self.preview_renderer.render(
+                    image.clone(),
+                    idx,
+                    crate::document_viewport_proxy::ImageState::Undefined,
+                )?
+            }
+        } else {
+            smallvec::SmallVec::new()
+        };
+        let ui_commands = if settings.ui {
+            self.egui_ctx
+                .build_capture_commands(idx, preview_commands.is_empty())?
+        } else {
+            None
+        };
+        if preview_commands.is_empty() && ui_commands.is_none() {
+            anyhow::bail!("nothing to capture");
+        }
+        let queue = self.render_context.queues().graphics().queue().clone();
+        let mut future = self.render_context.now().boxed();
+        for buffer in preview_commands.into_iter().chain(ui_commands) {
+            future = future.then_execute(queue.clone(), buffer)?.boxed();
+        }
+        future.then_signal_fence_and_flush()?.wait(None)?;
+
+        let mut request = crate::render_device::readback::Request::whole(image);
+        if !settings.ui {
+            // Crop to the viewport, within the image.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let to_texels = |v: ultraviolet::Vec2| [v.x.max(0.0) as u32, v.y.max(0.0) as u32];
+            let (position, size) = self.viewport;
+            let offset = to_texels(position);
+            let offset = [offset[0].min(extent[0]), offset[1].min(extent[1])];
+            let size = to_texels(size);
+            request.offset = offset;
+            request.extent = [
+                size[0].min(extent[0] - offset[0]),
+                size[1].min(extent[1] - offset[1]),
+            ];
+        }
+        if request.extent.contains(&0) {
+            anyhow::bail!("viewport is empty");
+        }
+        let size = request.extent;
+        let download = self.render_context.readback().download(request);
+        std::thread::spawn(move || {
+            let result = download
+                .blocking_recv()
+                .map_err(anyhow::Error::from)
+                .and_then(|texels| texels)
+                .and_then(|texels| crate::export::write_screenshot(&texels, size, &settings));
+            match result {
+                Ok(()) => tracing::info!(target = ?settings.target, "took screenshot"),
+                Err(e) => crate::errors::Report::new(
+                    crate::errors::Severity::Recoverable,
+                    tr!("screenshot-save-failed"),
+                    &e,
+                )
+                .send(),
+            }
+        });
+        Ok(())
+    }
+    /// Execute the UI draw after `future`, bracketed by timestamps if diagnostics are enabled.
+    fn execute_ui_draw(
+        &mut self,
+        future: Box<dyn GpuFuture>,
+        draw: Arc<vk::PrimaryAutoCommandBuffer>,
+    ) -> AnyResult<Box<dyn GpuFuture>> {
+        let queue = self.render_context.queues().graphics().queue().clone();
+        let Some(timer) = self
+            .egui_timer
+            .as_ref()
+            .filter(|_| crate::diagnostics::enabled())
+        else {
+            return Ok(future.then_execute(queue, draw)?.boxed());
+        };
+
+        let future = future
+            .then_execute(queue.clone(), timer.begin()?)?
+            .then_execute(queue.clone(), draw)?
+            .then_execute(queue, timer.end()?)?
+            .boxed();
+        Ok(future)
+    }
 }